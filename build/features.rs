@@ -0,0 +1,24 @@
+// Shared by the build scripts of the crates of the workspace
+
+/// Generate the `features()` function listing the cargo features enabled
+/// for this build
+pub fn write_features() {
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|name| name.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(
+        std::path::Path::new(&out_dir).join("features.rs"),
+        format!(
+            "/// Cargo features enabled when building the crate\n\
+             pub fn features() -> &'static [&'static str] {{\n    &{:?}\n}}\n",
+            features
+        ),
+    )
+    .unwrap();
+}
@@ -1,3 +1,8 @@
+#[path = "../build/features.rs"]
+mod features;
+
 fn main() {
     println!("cargo:rerun-if-changed=migrations/");
+
+    features::write_features();
 }
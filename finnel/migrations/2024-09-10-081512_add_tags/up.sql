@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE tags (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+CREATE TABLE record_tags (
+  record_id BIGINT REFERENCES records(id) NOT NULL,
+  tag_id BIGINT REFERENCES tags(id) NOT NULL,
+  CONSTRAINT record_tags_pk PRIMARY KEY (record_id, tag_id)
+);
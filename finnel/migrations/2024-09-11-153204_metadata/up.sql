@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE metadata (
+  key TEXT NOT NULL PRIMARY KEY,
+  value TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE records DROP COLUMN flag_reason;
+ALTER TABLE records DROP COLUMN flagged_at;
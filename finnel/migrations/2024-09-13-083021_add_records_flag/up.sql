@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE records ADD COLUMN flagged_at TIMESTAMP;
+ALTER TABLE records ADD COLUMN flag_reason TEXT;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE merchant_aliases (
+  id INTEGER NOT NULL PRIMARY KEY,
+  merchant_id BIGINT REFERENCES merchants(id) NOT NULL,
+  alias TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+-- Names of replaced merchants become aliases of their replacer, the replaced
+-- merchants are kept so records referencing them are unchanged
+INSERT OR IGNORE INTO merchant_aliases (merchant_id, alias)
+SELECT replaced_by_id, name FROM merchants WHERE replaced_by_id IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- category_id is not a foreign key so that the rules of a deleted category
+-- are kept and can be reported as broken
+CREATE TABLE category_rules (
+  id INTEGER NOT NULL PRIMARY KEY,
+  mode TEXT NOT NULL,
+  direction TEXT,
+  category_id BIGINT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX merchants_name_nocase;
+DROP INDEX categories_name_nocase;
@@ -0,0 +1,44 @@
+-- Your SQL goes here
+
+-- Names are trimmed with their runs of whitespace collapsed into a single
+-- space, as they are saved from now on. The ones left empty are named after
+-- their id, and the ones only differing by case get the id of their row
+-- appended, so the indexes below can be created. The names changing are
+-- first replaced by a placeholder, for the old ones not to collide with the
+-- new ones on the way.
+CREATE TEMPORARY TABLE category_names AS
+SELECT id, replace(replace(replace(name, char(9), ' '), char(10), ' '), char(13), ' ') AS name
+FROM categories;
+UPDATE category_names
+SET name = trim(replace(replace(replace(name, ' ', ' ' || char(1)), char(1) || ' ', ''), char(1), ''));
+UPDATE category_names SET name = 'Category ' || id WHERE name = '';
+UPDATE category_names SET name = name || ' (' || id || ')'
+WHERE EXISTS (
+  SELECT 1 FROM category_names AS other
+  WHERE other.id < category_names.id AND other.name = category_names.name COLLATE NOCASE
+);
+UPDATE categories SET name = char(1) || id
+WHERE name != (SELECT name FROM category_names WHERE category_names.id = categories.id);
+UPDATE categories SET name = (SELECT name FROM category_names WHERE category_names.id = categories.id)
+WHERE name = char(1) || id;
+DROP TABLE category_names;
+
+CREATE TEMPORARY TABLE merchant_names AS
+SELECT id, replace(replace(replace(name, char(9), ' '), char(10), ' '), char(13), ' ') AS name
+FROM merchants;
+UPDATE merchant_names
+SET name = trim(replace(replace(replace(name, ' ', ' ' || char(1)), char(1) || ' ', ''), char(1), ''));
+UPDATE merchant_names SET name = 'Merchant ' || id WHERE name = '';
+UPDATE merchant_names SET name = name || ' (' || id || ')'
+WHERE EXISTS (
+  SELECT 1 FROM merchant_names AS other
+  WHERE other.id < merchant_names.id AND other.name = merchant_names.name COLLATE NOCASE
+);
+UPDATE merchants SET name = char(1) || id
+WHERE name != (SELECT name FROM merchant_names WHERE merchant_names.id = merchants.id);
+UPDATE merchants SET name = (SELECT name FROM merchant_names WHERE merchant_names.id = merchants.id)
+WHERE name = char(1) || id;
+DROP TABLE merchant_names;
+
+CREATE UNIQUE INDEX categories_name_nocase ON categories (name COLLATE NOCASE);
+CREATE UNIQUE INDEX merchants_name_nocase ON merchants (name COLLATE NOCASE);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE imports (
+  id INTEGER NOT NULL PRIMARY KEY,
+  profile TEXT NOT NULL,
+  hash TEXT NOT NULL,
+  imported_at TIMESTAMP NOT NULL,
+  count BIGINT NOT NULL
+);
+
+CREATE INDEX imports_profile_hash ON imports (profile, hash);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE accounts DROP COLUMN reconciled_balance;
+ALTER TABLE accounts DROP COLUMN reconciled_at;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE accounts ADD COLUMN reconciled_at DATE;
+ALTER TABLE accounts ADD COLUMN reconciled_balance BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE records DROP COLUMN original_currency;
+ALTER TABLE records DROP COLUMN original_amount;
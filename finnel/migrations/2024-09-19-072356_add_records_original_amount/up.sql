@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE records ADD COLUMN original_amount BIGINT;
+ALTER TABLE records ADD COLUMN original_currency TEXT;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE recurring_payments DROP COLUMN end_date;
+ALTER TABLE recurring_payments DROP COLUMN start_date;
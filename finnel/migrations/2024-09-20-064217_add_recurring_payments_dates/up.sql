@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE recurring_payments ADD COLUMN start_date DATE;
+ALTER TABLE recurring_payments ADD COLUMN end_date DATE;
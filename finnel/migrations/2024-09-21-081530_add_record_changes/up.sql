@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE record_changes (
+  id INTEGER NOT NULL PRIMARY KEY,
+  record_id BIGINT NOT NULL,
+  changed_at TIMESTAMP NOT NULL,
+  field TEXT NOT NULL,
+  old_value TEXT,
+  new_value TEXT
+);
+
+CREATE INDEX record_changes_record_id ON record_changes (record_id);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE INDEX categories_parent_id ON categories (parent_id);
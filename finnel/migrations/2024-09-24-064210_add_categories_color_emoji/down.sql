@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE categories DROP COLUMN emoji;
+ALTER TABLE categories DROP COLUMN color;
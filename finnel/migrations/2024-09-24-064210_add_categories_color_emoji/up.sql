@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE categories ADD COLUMN color TEXT;
+ALTER TABLE categories ADD COLUMN emoji TEXT;
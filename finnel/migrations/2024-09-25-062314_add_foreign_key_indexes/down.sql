@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX monthly_category_stats_category_id;
+DROP INDEX monthly_category_stats_month;
+DROP INDEX reports_categories_category_id;
+DROP INDEX merchant_aliases_merchant_id;
+DROP INDEX merchants_replaced_by_id;
+DROP INDEX merchants_default_category_id;
+DROP INDEX categories_replaced_by_id;
+DROP INDEX record_tags_tag_id;
+DROP INDEX records_merchant_id;
+DROP INDEX records_category_id;
+DROP INDEX records_account_id;
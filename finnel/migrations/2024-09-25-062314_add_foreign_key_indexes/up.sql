@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE INDEX records_account_id ON records (account_id);
+CREATE INDEX records_category_id ON records (category_id);
+CREATE INDEX records_merchant_id ON records (merchant_id);
+CREATE INDEX record_tags_tag_id ON record_tags (tag_id);
+CREATE INDEX categories_replaced_by_id ON categories (replaced_by_id);
+CREATE INDEX merchants_default_category_id ON merchants (default_category_id);
+CREATE INDEX merchants_replaced_by_id ON merchants (replaced_by_id);
+CREATE INDEX merchant_aliases_merchant_id ON merchant_aliases (merchant_id);
+CREATE INDEX reports_categories_category_id ON reports_categories (category_id);
+CREATE INDEX monthly_category_stats_month ON monthly_category_stats (year, month, currency);
+CREATE INDEX monthly_category_stats_category_id ON monthly_category_stats (category_id);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+-- The references removed can't be restored, only the foreign key is
+CREATE TABLE new_category_rules (
+  id INTEGER NOT NULL PRIMARY KEY,
+  mode TEXT NOT NULL,
+  direction TEXT,
+  category_id BIGINT NOT NULL
+);
+INSERT INTO new_category_rules (id, mode, direction, category_id)
+SELECT id, mode, direction, category_id FROM category_rules;
+DROP TABLE category_rules;
+ALTER TABLE new_category_rules RENAME TO category_rules;
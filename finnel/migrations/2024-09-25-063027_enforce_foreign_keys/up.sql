@@ -0,0 +1,73 @@
+-- Your SQL goes here
+-- Foreign keys are enforced from now on, so first remove the references to
+-- rows deleted while they weren't
+
+-- The records and recurring payments of deleted accounts are kept in a
+-- "Recovered records (<currency>)" account for each of their currencies. The
+-- orphans are listed first, for the new accounts not to take the id of a
+-- deleted one on the way.
+CREATE TEMPORARY TABLE orphan_records AS
+SELECT id, currency FROM records WHERE account_id NOT IN (SELECT id FROM accounts);
+CREATE TEMPORARY TABLE orphan_recurring_payments AS
+SELECT id, currency FROM recurring_payments WHERE account_id NOT IN (SELECT id FROM accounts);
+INSERT OR IGNORE INTO accounts (name, balance, currency)
+SELECT 'Recovered records (' || currency || ')', 0, currency
+FROM (
+  SELECT currency FROM orphan_records
+  UNION SELECT currency FROM orphan_recurring_payments
+);
+UPDATE records
+SET account_id = (
+  SELECT id FROM accounts WHERE name = 'Recovered records (' || records.currency || ')'
+)
+WHERE id IN (SELECT id FROM orphan_records);
+UPDATE recurring_payments
+SET account_id = (
+  SELECT id FROM accounts
+  WHERE name = 'Recovered records (' || recurring_payments.currency || ')'
+)
+WHERE id IN (SELECT id FROM orphan_recurring_payments);
+DROP TABLE orphan_records;
+DROP TABLE orphan_recurring_payments;
+
+UPDATE records SET category_id = NULL WHERE category_id NOT IN (SELECT id FROM categories);
+UPDATE records SET merchant_id = NULL WHERE merchant_id NOT IN (SELECT id FROM merchants);
+DELETE FROM record_tags
+WHERE record_id NOT IN (SELECT id FROM records) OR tag_id NOT IN (SELECT id FROM tags);
+
+UPDATE categories SET parent_id = NULL WHERE parent_id NOT IN (SELECT id FROM categories);
+UPDATE categories SET replaced_by_id = NULL
+WHERE replaced_by_id NOT IN (SELECT id FROM categories);
+
+UPDATE merchants SET default_category_id = NULL
+WHERE default_category_id NOT IN (SELECT id FROM categories);
+UPDATE merchants SET replaced_by_id = NULL WHERE replaced_by_id NOT IN (SELECT id FROM merchants);
+DELETE FROM merchant_aliases WHERE merchant_id NOT IN (SELECT id FROM merchants);
+
+UPDATE recurring_payments SET category_id = NULL
+WHERE category_id NOT IN (SELECT id FROM categories);
+UPDATE recurring_payments SET merchant_id = NULL
+WHERE merchant_id NOT IN (SELECT id FROM merchants);
+
+DELETE FROM reports_categories
+WHERE report_id NOT IN (SELECT id FROM reports) OR category_id NOT IN (SELECT id FROM categories);
+
+DELETE FROM monthly_category_stats
+WHERE category_id NOT IN (SELECT id FROM categories)
+  OR (year, month, currency) NOT IN (SELECT year, month, currency FROM monthly_stats);
+
+-- https://sqlite.org/lang_altertable.html#otheralter
+-- category_rules didn't reference categories, rebuild it with the foreign
+-- key, leaving out the rules of deleted categories
+CREATE TABLE new_category_rules (
+  id INTEGER NOT NULL PRIMARY KEY,
+  mode TEXT NOT NULL,
+  direction TEXT,
+  category_id BIGINT NOT NULL REFERENCES categories(id)
+);
+INSERT INTO new_category_rules (id, mode, direction, category_id)
+SELECT id, mode, direction, category_id
+FROM category_rules
+WHERE category_id IN (SELECT id FROM categories);
+DROP TABLE category_rules;
+ALTER TABLE new_category_rules RENAME TO category_rules;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE account_groups (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE account_groups_accounts (
+  group_id BIGINT REFERENCES account_groups(id) NOT NULL,
+  account_id BIGINT REFERENCES accounts(id) NOT NULL,
+  CONSTRAINT account_groups_accounts_pk PRIMARY KEY (group_id, account_id)
+);
+
+CREATE INDEX account_groups_accounts_account_id ON account_groups_accounts (account_id);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+-- The rules matching the details can't be kept
+CREATE TABLE new_category_rules (
+  id INTEGER NOT NULL PRIMARY KEY,
+  mode TEXT NOT NULL,
+  direction TEXT,
+  category_id BIGINT NOT NULL REFERENCES categories(id)
+);
+INSERT INTO new_category_rules (id, mode, direction, category_id)
+SELECT id, mode, direction, category_id FROM category_rules WHERE mode IS NOT NULL;
+DROP TABLE category_rules;
+ALTER TABLE new_category_rules RENAME TO category_rules;
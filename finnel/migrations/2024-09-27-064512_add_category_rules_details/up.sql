@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- https://sqlite.org/lang_altertable.html#otheralter
+-- Rules now match either the mode of the records or their details, so the
+-- mode becomes optional
+CREATE TABLE new_category_rules (
+  id INTEGER NOT NULL PRIMARY KEY,
+  mode TEXT,
+  direction TEXT,
+  pattern TEXT,
+  is_regex BOOLEAN NOT NULL DEFAULT FALSE,
+  priority INTEGER NOT NULL DEFAULT 0,
+  category_id BIGINT NOT NULL REFERENCES categories(id),
+  CHECK ((mode IS NULL) <> (pattern IS NULL))
+);
+INSERT INTO new_category_rules (id, mode, direction, category_id)
+SELECT id, mode, direction, category_id FROM category_rules;
+DROP TABLE category_rules;
+ALTER TABLE new_category_rules RENAME TO category_rules;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE categories DROP COLUMN exclude_from_stats;
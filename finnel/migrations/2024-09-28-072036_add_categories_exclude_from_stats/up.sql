@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- NULL inherits the flag of the parent category
+ALTER TABLE categories ADD COLUMN exclude_from_stats BOOLEAN;
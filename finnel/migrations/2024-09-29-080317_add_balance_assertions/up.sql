@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE balance_assertions (
+  id INTEGER NOT NULL PRIMARY KEY,
+  account_id BIGINT NOT NULL REFERENCES accounts(id),
+  date DATE NOT NULL,
+  amount BIGINT NOT NULL,
+  currency TEXT NOT NULL
+);
+
+CREATE INDEX balance_assertions_account_id_date ON balance_assertions (account_id, date);
@@ -0,0 +1,44 @@
+-- This file should undo anything in `up.sql`
+-- Pending records have no account to go back to
+DELETE FROM record_tags WHERE record_id IN (SELECT id FROM records WHERE pending);
+DELETE FROM records WHERE pending;
+
+CREATE TABLE new_records (
+  id INTEGER NOT NULL PRIMARY KEY,
+  account_id BIGINT NOT NULL REFERENCES accounts(id),
+  amount BIGINT NOT NULL,
+  currency TEXT NOT NULL,
+  operation_date DATE NOT NULL,
+  value_date DATE NOT NULL,
+  direction TEXT NOT NULL DEFAULT 'Debit',
+  mode TEXT NOT NULL DEFAULT 'Direct',
+  details TEXT NOT NULL DEFAULT '',
+  category_id BIGINT REFERENCES categories(id),
+  merchant_id BIGINT REFERENCES merchants(id)
+, flagged_at TIMESTAMP, flag_reason TEXT, original_amount BIGINT, original_currency TEXT);
+INSERT INTO new_records
+SELECT
+  id,
+  account_id,
+  amount,
+  currency,
+  operation_date,
+  value_date,
+  direction,
+  mode,
+  details,
+  category_id,
+  merchant_id,
+  flagged_at,
+  flag_reason,
+  original_amount,
+  original_currency
+FROM records;
+DROP TABLE records;
+ALTER TABLE new_records RENAME TO records;
+
+CREATE INDEX records_value_date ON records (value_date DESC);
+CREATE INDEX records_operation_date ON records (operation_date DESC);
+CREATE INDEX records_account_id ON records (account_id);
+CREATE INDEX records_category_id ON records (category_id);
+CREATE INDEX records_merchant_id ON records (merchant_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE merchants DROP COLUMN notes;
+ALTER TABLE merchants DROP COLUMN website;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE merchants ADD COLUMN website TEXT;
+ALTER TABLE merchants ADD COLUMN notes TEXT;
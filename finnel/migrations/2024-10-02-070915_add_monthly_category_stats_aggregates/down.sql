@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE monthly_category_stats DROP COLUMN max_amount;
+ALTER TABLE monthly_category_stats DROP COLUMN min_amount;
+ALTER TABLE monthly_category_stats DROP COLUMN records;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- The stats saved so far lack the aggregates, they are computed again when
+-- needed
+DELETE FROM monthly_category_stats;
+DELETE FROM monthly_stats;
+
+ALTER TABLE monthly_category_stats ADD COLUMN records BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE monthly_category_stats ADD COLUMN min_amount BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE monthly_category_stats ADD COLUMN max_amount BIGINT NOT NULL DEFAULT 0;
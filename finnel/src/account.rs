@@ -1,8 +1,23 @@
-use crate::{essentials::*, schema::accounts, Amount, Currency, Decimal};
+use crate::{
+    essentials::*,
+    record::Direction,
+    schema::{accounts, records, recurring_payments},
+    Amount, Currency, Decimal,
+};
 
-use diesel::prelude::*;
+use chrono::NaiveDate;
+use diesel::{dsl::count_star, prelude::*};
 
-#[derive(Debug, Queryable, Selectable, Identifiable)]
+pub mod assertion;
+pub mod group;
+pub mod recomputation;
+pub mod reconciliation;
+pub use assertion::BalanceAssertion;
+pub use group::AccountGroup;
+pub use recomputation::BalanceRecomputation;
+pub use reconciliation::Reconciliation;
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
 #[diesel(table_name = accounts)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Account {
@@ -12,6 +27,13 @@ pub struct Account {
     pub balance: Decimal,
     #[diesel(deserialize_as = crate::db::Currency)]
     pub currency: Currency,
+    pub iban: Option<String>,
+    /// Date up to which the records were last checked against a bank
+    /// statement
+    pub reconciled_at: Option<NaiveDate>,
+    /// Balance of the statement at `reconciled_at`
+    #[diesel(deserialize_as = crate::db::Decimal)]
+    pub reconciled_balance: Decimal,
 }
 
 impl Account {
@@ -19,6 +41,48 @@ impl Account {
         Amount(self.balance, self.currency)
     }
 
+    /// Date and balance of the last reconciliation, if any
+    pub fn reconciliation(&self) -> Option<(NaiveDate, Amount)> {
+        self.reconciled_at
+            .map(|date| (date, Amount(self.reconciled_balance, self.currency)))
+    }
+
+    /// Balance after the records with an operation date up to `date`
+    /// included, starting from the initial balance of the account
+    pub fn balance_at(&self, conn: &mut Conn, date: NaiveDate) -> Result<Decimal> {
+        let totals = records::table
+            .filter(records::account_id.eq(self.id))
+            .filter(records::operation_date.le(date))
+            .filter(records::currency.eq(db::Currency::from(self.currency)))
+            .filter(db::type_of(records::amount).eq("integer"))
+            .group_by(records::direction)
+            .select((records::direction, db::group_sum(records::amount)))
+            .load::<(Direction, db::Decimal)>(conn)?;
+
+        Ok(totals.into_iter().fold(
+            self.balance,
+            |balance, (direction, amount)| match direction {
+                Direction::Debit => balance - amount.0,
+                Direction::Credit => balance + amount.0,
+            },
+        ))
+    }
+
+    /// Record that the records up to `date` match the balance of a bank
+    /// statement
+    pub fn reconcile(&mut self, conn: &mut Conn, date: NaiveDate, balance: Decimal) -> Result<()> {
+        diesel::update(&*self)
+            .set((
+                accounts::reconciled_at.eq(date),
+                accounts::reconciled_balance.eq(db::Decimal::from(balance)),
+            ))
+            .execute(conn)?;
+
+        self.reconciled_at = Some(date);
+        self.reconciled_balance = balance;
+        Ok(())
+    }
+
     pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
         accounts::table
             .find(id)
@@ -35,17 +99,121 @@ impl Account {
             .map_err(|e| e.into())
     }
 
-    /// Delete the current account, removing associated records too
+    /// Whether the text contains the IBAN of the account, ignoring spaces and
+    /// case
+    pub fn iban_in(&self, text: &str) -> bool {
+        match &self.iban {
+            Some(iban) => normalize_iban(text).contains(iban.as_str()),
+            None => false,
+        }
+    }
+
+    /// Summary of what deleting the account would remove
+    pub fn deletion_report(&self, conn: &mut Conn) -> Result<DeletionReport> {
+        let (records, first, last) = records::table
+            .filter(records::account_id.eq(self.id))
+            .select((
+                count_star(),
+                diesel::dsl::min(records::operation_date),
+                diesel::dsl::max(records::operation_date),
+            ))
+            .first::<(i64, Option<NaiveDate>, Option<NaiveDate>)>(conn)?;
+
+        let totals = records::table
+            .filter(records::account_id.eq(self.id))
+            .filter(records::currency.eq(db::Currency::from(self.currency)))
+            .filter(db::type_of(records::amount).eq("integer"))
+            .group_by(records::direction)
+            .select((records::direction, db::group_sum(records::amount)))
+            .load::<(Direction, db::Decimal)>(conn)?;
+        let total = |direction| {
+            totals
+                .iter()
+                .find(|(d, _)| *d == direction)
+                .map(|(_, amount)| amount.0)
+                .unwrap_or_default()
+        };
+
+        let recurring_payments = recurring_payments::table
+            .filter(recurring_payments::account_id.eq(self.id))
+            .count()
+            .get_result(conn)?;
+
+        Ok(DeletionReport {
+            records,
+            dates: first.zip(last),
+            debit: total(Direction::Debit),
+            credit: total(Direction::Credit),
+            currency: self.currency,
+            recurring_payments,
+        })
+    }
+
+    /// Delete the current account, removing associated records too and
+    /// taking it out of its groups
     ///
     /// This method executes multiple queries without wrapping them in a
     /// transaction
     pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
         crate::record::delete_by_account_id(conn, self.id)?;
         crate::recurring_payment::delete_by_account_id(conn, self.id)?;
+        assertion::delete_by_account_id(conn, self.id)?;
+        group::clear_account_id(conn, self.id)?;
         diesel::delete(&*self).execute(conn)?;
 
         Ok(())
     }
+
+    /// Delete the current account after moving its records to `other`, in
+    /// the same currency, in a single transaction
+    pub fn delete_moving_records(&mut self, conn: &mut Conn, other: &Account) -> Result<()> {
+        if self.id == other.id {
+            return Err(Error::Invalid(format!(
+                "Cannot move the records of {} to itself",
+                self.name
+            )));
+        }
+        if self.currency != other.currency {
+            return Err(Error::Invalid(format!(
+                "Cannot move the records of {} in {} to {} in {}",
+                self.name,
+                self.currency.code(),
+                other.name,
+                other.currency.code()
+            )));
+        }
+
+        conn.transaction(|conn| {
+            diesel::update(records::table.filter(records::account_id.eq(self.id)))
+                .set(records::account_id.eq(other.id))
+                .execute(conn)?;
+            self.delete(conn)
+        })
+    }
+}
+
+/// What deleting an account would remove
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletionReport {
+    pub records: i64,
+    /// Operation dates of the first and last records
+    pub dates: Option<(NaiveDate, NaiveDate)>,
+    /// Total of the debits in the currency of the account
+    pub debit: Decimal,
+    /// Total of the credits in the currency of the account
+    pub credit: Decimal,
+    pub currency: Currency,
+    pub recurring_payments: i64,
+}
+
+impl DeletionReport {
+    pub fn debit(&self) -> Amount {
+        Amount(self.debit, self.currency)
+    }
+
+    pub fn credit(&self) -> Amount {
+        Amount(self.credit, self.currency)
+    }
 }
 
 #[derive(Insertable)]
@@ -56,6 +224,7 @@ pub struct NewAccount<'a> {
     pub balance: Decimal,
     #[diesel(serialize_as = crate::db::Currency)]
     pub currency: Currency,
+    pub iban: Option<&'a str>,
 }
 
 impl<'a> NewAccount<'a> {
@@ -64,12 +233,17 @@ impl<'a> NewAccount<'a> {
             name,
             balance: Decimal::ZERO,
             currency: Currency::EUR,
+            iban: None,
         }
     }
 }
 
 impl NewAccount<'_> {
     pub fn save(self, conn: &mut Conn) -> Result<Account> {
+        if let Some(iban) = self.iban {
+            validate_iban(iban)?;
+        }
+
         Ok(diesel::insert_into(accounts::table)
             .values(self)
             .returning(Account::as_returning())
@@ -81,10 +255,15 @@ impl NewAccount<'_> {
 #[diesel(table_name = accounts)]
 pub struct ChangeAccount<'a> {
     pub name: Option<&'a str>,
+    pub iban: Option<Option<&'a str>>,
 }
 
 impl ChangeAccount<'_> {
     pub fn save(self, conn: &mut Conn, account: &Account) -> Result<()> {
+        if let Some(Some(iban)) = self.iban {
+            validate_iban(iban)?;
+        }
+
         diesel::update(account).set(self).execute(conn)?;
         Ok(())
     }
@@ -95,6 +274,9 @@ impl ChangeAccount<'_> {
         if let Some(value) = self.name {
             account.name = value.to_string();
         }
+        if let Some(value) = self.iban {
+            account.iban = value.map(str::to_string);
+        }
 
         Ok(())
     }
@@ -121,6 +303,53 @@ impl QueryAccount<'_> {
     }
 }
 
+/// Remove the spaces and uppercase the IBAN, as they are often written in
+/// groups of 4 characters
+pub fn normalize_iban(iban: &str) -> String {
+    iban.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Check that the IBAN is normalized and that its check digits are valid
+pub fn validate_iban(iban: &str) -> Result<()> {
+    let invalid = |reason: &str| Err(Error::Invalid(format!("IBAN {iban} {reason}")));
+
+    if iban != normalize_iban(iban) {
+        return invalid("is not normalized");
+    }
+    if !(15..=34).contains(&iban.len()) || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return invalid("has an invalid format");
+    }
+    let (country, check) = iban.split_at(2);
+    if !country.chars().all(|c| c.is_ascii_alphabetic())
+        || !check[..2].chars().all(|c| c.is_ascii_digit())
+    {
+        return invalid("has an invalid format");
+    }
+
+    // Move the first 4 characters at the end, and convert letters to numbers
+    // with A = 10, then compute the remainder modulo 97 digit by digit
+    let remainder = iban[4..]
+        .chars()
+        .chain(iban[..4].chars())
+        .fold(0u32, |remainder, c| {
+            let value = c.to_digit(36).unwrap_or_default();
+            if value < 10 {
+                (remainder * 10 + value) % 97
+            } else {
+                (remainder * 100 + value) % 97
+            }
+        });
+
+    if remainder != 1 {
+        return invalid("has invalid check digits");
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,6 +363,7 @@ mod tests {
             name: "Bar",
             balance: Decimal::new(314, 3),
             currency: Currency::EUR,
+            iban: None,
         }
         .save(conn)?;
 
@@ -142,4 +372,91 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn iban() -> Result<()> {
+        let conn = &mut test::db()?;
+        let iban = "FR1420041010050500013M02606";
+
+        assert_eq!(iban, normalize_iban("fr14 2004 1010 0505 0001 3m02 606"));
+        assert!(validate_iban(iban).is_ok());
+        assert!(validate_iban("GB82WEST12345698765432").is_ok());
+        assert!(validate_iban("FR14 2004 1010 0505 0001 3M02 606").is_err());
+        assert!(validate_iban("FR1520041010050500013M02606").is_err());
+        assert!(validate_iban("FR14").is_err());
+
+        assert!(NewAccount {
+            iban: Some("FR1520041010050500013M02606"),
+            ..NewAccount::new("Invalid")
+        }
+        .save(conn)
+        .is_err());
+
+        let mut account = test::account!(conn, "Bank", iban: Some(iban));
+        assert!(account.iban_in("VIR SEPA fr14 2004 1010 0505 0001 3m02 606"));
+        assert!(!account.iban_in("VIR SEPA FR76 3000"));
+
+        ChangeAccount {
+            iban: Some(None),
+            ..Default::default()
+        }
+        .apply(conn, &mut account)?;
+        assert_eq!(None, account.reload(conn)?.iban);
+        assert!(!account.iban_in(iban));
+
+        Ok(())
+    }
+
+    #[test]
+    fn deletion() -> Result<()> {
+        let conn = &mut test::db()?;
+        let mut bank = test::account!(conn, "Bank");
+        let cash = test::account!(conn, "Cash");
+        let dollars = test::account!(conn, "Dollars", currency: Currency::USD);
+
+        let empty = bank.deletion_report(conn)?;
+        assert_eq!(0, empty.records);
+        assert_eq!(None, empty.dates);
+        assert_eq!(Decimal::ZERO, empty.debit);
+
+        let date = |day| NaiveDate::from_ymd_opt(2024, 8, day).unwrap();
+        for (amount, direction, day) in [
+            (20, Direction::Debit, 3),
+            (5, Direction::Debit, 10),
+            (100, Direction::Credit, 7),
+        ] {
+            test::record!(
+                conn,
+                &bank,
+                amount: Decimal::from(amount),
+                direction: direction,
+                operation_date: date(day),
+                value_date: date(day)
+            );
+        }
+        test::record!(conn, &cash, amount: Decimal::from(1));
+
+        assert_eq!(
+            DeletionReport {
+                records: 3,
+                dates: Some((date(3), date(10))),
+                debit: Decimal::from(25),
+                credit: Decimal::from(100),
+                currency: Currency::EUR,
+                recurring_payments: 0,
+            },
+            bank.deletion_report(conn)?
+        );
+
+        assert!(bank.delete_moving_records(conn, &dollars).is_err());
+        let itself = Account::find(conn, bank.id)?;
+        assert!(bank.delete_moving_records(conn, &itself).is_err());
+        assert_eq!(3, bank.deletion_report(conn)?.records);
+
+        bank.delete_moving_records(conn, &cash)?;
+        assert!(Account::find(conn, bank.id).is_err());
+        assert_eq!(4, cash.deletion_report(conn)?.records);
+
+        Ok(())
+    }
 }
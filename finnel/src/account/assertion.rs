@@ -0,0 +1,117 @@
+use super::Account;
+use crate::{essentials::*, schema::balance_assertions};
+
+use chrono::NaiveDate;
+use diesel::{dsl::count_star, prelude::*};
+
+/// Balance of an account at a date, as given by a bank statement, that the
+/// records should keep adding up to
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = balance_assertions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct BalanceAssertion {
+    pub id: i64,
+    pub account_id: i64,
+    /// Counting the records with an operation date up to this one included
+    pub date: NaiveDate,
+    #[diesel(deserialize_as = crate::db::Decimal)]
+    pub amount: Decimal,
+    #[diesel(deserialize_as = crate::db::Currency)]
+    pub currency: Currency,
+}
+
+impl BalanceAssertion {
+    pub fn create(
+        conn: &mut Conn,
+        account: &Account,
+        date: NaiveDate,
+        amount: Decimal,
+    ) -> Result<Self> {
+        Ok(diesel::insert_into(balance_assertions::table)
+            .values((
+                balance_assertions::account_id.eq(account.id),
+                balance_assertions::date.eq(date),
+                balance_assertions::amount.eq(db::Decimal::from(amount)),
+                balance_assertions::currency.eq(db::Currency::from(account.currency)),
+            ))
+            .returning(BalanceAssertion::as_returning())
+            .get_result(conn)?)
+    }
+
+    /// Assertions of the account, oldest first
+    pub fn of_account(conn: &mut Conn, account: &Account) -> Result<Vec<Self>> {
+        Ok(balance_assertions::table
+            .filter(balance_assertions::account_id.eq(account.id))
+            .select(BalanceAssertion::as_select())
+            .order((balance_assertions::date.asc(), balance_assertions::id.asc()))
+            .load(conn)?)
+    }
+
+    /// Number of assertions of the account that a record dated `date`
+    /// counts in
+    pub fn count_from(conn: &mut Conn, account_id: i64, date: NaiveDate) -> Result<i64> {
+        Ok(balance_assertions::table
+            .filter(balance_assertions::account_id.eq(account_id))
+            .filter(balance_assertions::date.ge(date))
+            .select(count_star())
+            .first(conn)?)
+    }
+
+    pub fn amount(&self) -> Amount {
+        Amount(self.amount, self.currency)
+    }
+
+    /// Difference between the balance computed from the records and the
+    /// asserted one, zero when the assertion holds
+    pub fn delta(&self, conn: &mut Conn, account: &Account) -> Result<Decimal> {
+        Ok(account.balance_at(conn, self.date)? - self.amount)
+    }
+
+    pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        diesel::delete(&*self).execute(conn)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn delete_by_account_id(conn: &mut Conn, id: i64) -> Result<()> {
+    diesel::delete(balance_assertions::table)
+        .filter(balance_assertions::account_id.eq(id))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn delta() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Checking");
+        let date = |day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap();
+        let august = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+
+        test::record!(conn, account, amount: Decimal::new(100, 0), operation_date: date(10));
+        test::record!(conn, account, amount: Decimal::new(20, 0), operation_date: date(31));
+        test::record!(conn, account, amount: Decimal::new(5, 0), operation_date: august);
+
+        let holding = BalanceAssertion::create(conn, account, date(31), Decimal::new(-120, 0))?;
+        let failing = BalanceAssertion::create(conn, account, date(10), Decimal::new(-90, 0))?;
+
+        assert_eq!(Decimal::ZERO, holding.delta(conn, account)?);
+        assert_eq!(Decimal::new(-10, 0), failing.delta(conn, account)?);
+
+        let ids = BalanceAssertion::of_account(conn, account)?
+            .into_iter()
+            .map(|a| a.id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![failing.id, holding.id], ids);
+
+        assert_eq!(2, BalanceAssertion::count_from(conn, account.id, date(1))?);
+        assert_eq!(1, BalanceAssertion::count_from(conn, account.id, date(31))?);
+        assert_eq!(0, BalanceAssertion::count_from(conn, account.id, august)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use super::Account;
+use crate::{
+    essentials::*,
+    schema::{account_groups, account_groups_accounts, accounts},
+};
+
+use diesel::prelude::*;
+
+/// Named set of accounts, to list records and compute stats across them
+pub struct AccountGroup {
+    pub id: i64,
+    pub name: String,
+    pub accounts: Vec<Account>,
+}
+
+impl AccountGroup {
+    pub fn create(conn: &mut Conn, name: &str) -> Result<Self> {
+        diesel::insert_into(account_groups::table)
+            .values(account_groups::name.eq(name))
+            .returning((account_groups::id, account_groups::name))
+            .get_result(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Account group", None))
+            .and_then(|(id, name)| Self::load(conn, id, name))
+    }
+
+    pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
+        account_groups::table
+            .find(id)
+            .select((account_groups::id, account_groups::name))
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Account group", None))
+            .and_then(|(id, name)| Self::load(conn, id, name))
+    }
+
+    pub fn find_by_name(conn: &mut Conn, name: &str) -> Result<Self> {
+        account_groups::table
+            .filter(account_groups::name.eq(name))
+            .select((account_groups::id, account_groups::name))
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Account group", Some("name")))
+            .and_then(|(id, name)| Self::load(conn, id, name))
+    }
+
+    /// All groups with their accounts, sorted by name
+    pub fn all(conn: &mut Conn) -> Result<Vec<Self>> {
+        account_groups::table
+            .select((account_groups::id, account_groups::name))
+            .order(account_groups::name.asc())
+            .load::<(i64, String)>(conn)?
+            .into_iter()
+            .map(|(id, name)| Self::load(conn, id, name))
+            .collect()
+    }
+
+    pub fn account_ids(&self) -> Vec<i64> {
+        self.accounts.iter().map(|a| a.id).collect()
+    }
+
+    pub fn add<'a, T>(&mut self, conn: &mut Conn, iter: T) -> Result<()>
+    where
+        T: IntoIterator<Item = &'a Account>,
+    {
+        let values = iter
+            .into_iter()
+            .map(|a| {
+                (
+                    account_groups_accounts::group_id.eq(self.id),
+                    account_groups_accounts::account_id.eq(a.id),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(account_groups_accounts::table)
+            .values(values)
+            .execute(conn)?;
+
+        self.accounts = Self::load_accounts(conn, self.id)?;
+        Ok(())
+    }
+
+    pub fn remove<'a, T>(&mut self, conn: &mut Conn, iter: T) -> Result<()>
+    where
+        T: IntoIterator<Item = &'a Account>,
+    {
+        let values = iter.into_iter().map(|a| a.id).collect::<Vec<_>>();
+        diesel::delete(account_groups_accounts::table)
+            .filter(account_groups_accounts::group_id.eq(self.id))
+            .filter(account_groups_accounts::account_id.eq_any(values))
+            .execute(conn)?;
+
+        self.accounts = Self::load_accounts(conn, self.id)?;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        diesel::delete(account_groups_accounts::table)
+            .filter(account_groups_accounts::group_id.eq(self.id))
+            .execute(conn)?;
+        diesel::delete(account_groups::table)
+            .filter(account_groups::id.eq(self.id))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn load(conn: &mut Conn, id: i64, name: String) -> Result<Self> {
+        Ok(AccountGroup {
+            id,
+            name,
+            accounts: Self::load_accounts(conn, id)?,
+        })
+    }
+
+    fn load_accounts(conn: &mut Conn, id: i64) -> Result<Vec<Account>> {
+        Ok(accounts::table
+            .inner_join(account_groups_accounts::table)
+            .filter(account_groups_accounts::group_id.eq(id))
+            .select(Account::as_select())
+            .order(accounts::name.asc())
+            .load::<Account>(conn)?)
+    }
+}
+
+/// Remove the account from the groups it belongs to
+pub(crate) fn clear_account_id(conn: &mut Conn, id: i64) -> Result<()> {
+    diesel::delete(account_groups_accounts::table)
+        .filter(account_groups_accounts::account_id.eq(id))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use diesel::dsl::count_star;
+
+    #[test]
+    fn test() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let mut group = AccountGroup::create(conn, "Daily")?;
+        assert!(AccountGroup::create(conn, "Daily").is_err());
+
+        let checking = &test::account!(conn, "Checking");
+        let cash = &test::account!(conn, "Cash");
+        let savings = &test::account!(conn, "Savings");
+
+        group.add(conn, [checking, cash])?;
+        assert!(group.add(conn, [cash]).is_err());
+
+        let mut group = AccountGroup::find_by_name(conn, "Daily")?;
+        assert_eq!(vec![cash.id, checking.id], group.account_ids());
+
+        group.remove(conn, [savings, cash])?;
+        assert_eq!(vec![checking.id], group.account_ids());
+
+        AccountGroup::create(conn, "Savings")?;
+        let names = AccountGroup::all(conn)?
+            .into_iter()
+            .map(|g| g.name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["Daily", "Savings"], names);
+
+        group.delete(conn)?;
+        assert!(AccountGroup::find(conn, group.id).is_err());
+        assert_eq!(
+            0i64,
+            account_groups_accounts::table
+                .select(count_star())
+                .first(conn)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete_account() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let mut group = AccountGroup::create(conn, "Daily")?;
+        let mut account = test::account!(conn, "Checking");
+        group.add(conn, [&account])?;
+
+        account.delete(conn)?;
+
+        let group = AccountGroup::find(conn, group.id)?;
+        assert!(group.accounts.is_empty());
+
+        Ok(())
+    }
+}
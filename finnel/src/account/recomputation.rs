@@ -0,0 +1,115 @@
+use super::Account;
+use crate::{essentials::*, record::Direction, result::RowError, schema::records};
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// Balance of an account derived again from its records one by one, checked
+/// against the one summed by the database
+///
+/// The account only stores its initial balance, so there is nothing to
+/// overwrite: a difference means the sums read from the database can't be
+/// trusted, and the records left out are listed
+#[derive(Debug)]
+pub struct BalanceRecomputation {
+    pub date: NaiveDate,
+    /// Balance as shown everywhere else, summed by the database
+    pub summed: Decimal,
+    /// Initial balance plus the amount of each record, added exactly
+    pub derived: Decimal,
+    /// Number of records counted in the balance
+    pub records: usize,
+    /// Records left out of both balances, their amount being no number of
+    /// thousandths
+    pub invalid: Vec<RowError>,
+}
+
+impl BalanceRecomputation {
+    /// What the derived balance has more than the summed one, zero when they
+    /// agree
+    pub fn correction(&self) -> Decimal {
+        self.derived - self.summed
+    }
+
+    /// Derive the balance of the account at `date`, reading everything in a
+    /// single transaction so records changed meanwhile can't skew it
+    pub fn run(conn: &mut Conn, account: &Account, date: NaiveDate) -> Result<Self> {
+        conn.transaction(|conn| {
+            let query = || {
+                records::table
+                    .filter(records::account_id.eq(account.id))
+                    .filter(records::operation_date.le(date))
+                    .filter(records::currency.eq(db::Currency::from(account.currency)))
+                    .into_boxed()
+            };
+
+            let amounts = query()
+                .filter(db::type_of(records::amount).eq("integer"))
+                .select((records::direction, records::amount))
+                .load::<(Direction, db::Decimal)>(conn)?;
+            let derived =
+                amounts.iter().fold(
+                    account.balance,
+                    |balance, (direction, amount)| match direction {
+                        Direction::Debit => balance - amount.0,
+                        Direction::Credit => balance + amount.0,
+                    },
+                );
+
+            Ok(BalanceRecomputation {
+                date,
+                summed: account.balance_at(conn, date)?,
+                derived,
+                records: amounts.len(),
+                invalid: crate::record::invalid_amounts(conn, query())?,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn run() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash", balance: Decimal::new(100, 0));
+        let today = chrono::Utc::now().date_naive();
+
+        test::record!(conn, &account, amount: Decimal::new(1250, 2));
+        test::record!(
+            conn,
+            &account,
+            amount: Decimal::new(1, 3),
+            direction: Direction::Credit
+        );
+        test::record!(
+            conn,
+            &account,
+            amount: Decimal::new(5, 0),
+            operation_date: today.succ_opt().unwrap(),
+            value_date: today.succ_opt().unwrap()
+        );
+        let invalid = test::record!(conn, &account);
+        diesel::sql_query("UPDATE records SET amount = '12,34' WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(invalid.id)
+            .execute(conn)?;
+
+        let recomputation = BalanceRecomputation::run(conn, &account, today)?;
+        assert_eq!(Decimal::new(87501, 3), recomputation.derived);
+        assert_eq!(recomputation.derived, recomputation.summed);
+        assert!(recomputation.correction().is_zero());
+        assert_eq!(2, recomputation.records);
+        assert_eq!(
+            vec![RowError {
+                record_id: invalid.id,
+                value: "12,34".to_owned()
+            }],
+            recomputation.invalid
+        );
+
+        Ok(())
+    }
+}
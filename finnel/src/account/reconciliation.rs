@@ -0,0 +1,268 @@
+use crate::{
+    account::Account,
+    essentials::*,
+    record::{Direction, QueryRecord, Record},
+};
+
+use std::cmp::Reverse;
+
+use chrono::NaiveDate;
+
+/// Number of most recent records whose combinations are tried to explain
+/// the difference, as there are 2^RECENT_RECORDS of them
+pub const RECENT_RECORDS: usize = 20;
+
+/// Number of combinations of records kept in `matches`
+pub const MAX_MATCHES: usize = 5;
+
+/// Factor of the median amount above which the amount of a record is
+/// unusual
+const UNUSUAL_FACTOR: i64 = 5;
+
+/// Comparison of the balance computed from the records of an account with
+/// the balance of a bank statement at the same date, along with the records
+/// most likely to explain a difference
+#[derive(Debug)]
+pub struct Reconciliation {
+    pub at: NaiveDate,
+    pub currency: Currency,
+    /// Balance from the records with an operation date up to `at`
+    pub balance: Decimal,
+    pub statement_balance: Decimal,
+    /// Records after the last reconciliation of the account, up to `at`
+    pub unreconciled: Vec<Record>,
+    /// Unreconciled records with an amount well above the usual ones
+    pub unusual: Vec<Record>,
+    /// Combinations of the most recent records adding up to the difference,
+    /// smallest first, e.g. records not yet on the statement
+    pub matches: Vec<Vec<Record>>,
+}
+
+impl Reconciliation {
+    pub fn new(
+        conn: &mut Conn,
+        account: &Account,
+        at: NaiveDate,
+        statement_balance: Decimal,
+    ) -> Result<Self> {
+        let mut records = QueryRecord {
+            account_id: Some(account.id),
+            to: at.succ_opt(),
+            operation_date: true,
+            skip_invalid: true,
+            ..Default::default()
+        }
+        .run(conn)?;
+        records.retain(|record| record.currency == account.currency);
+        records.sort_by_key(|record| Reverse((record.operation_date, record.id)));
+
+        let unreconciled = records
+            .iter()
+            .take_while(|record| {
+                account
+                    .reconciled_at
+                    .is_none_or(|date| record.operation_date > date)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let threshold = median(records.iter().map(|r| r.amount)) * Decimal::from(UNUSUAL_FACTOR);
+        let unusual = unreconciled
+            .iter()
+            .filter(|record| !threshold.is_zero() && record.amount > threshold)
+            .cloned()
+            .collect();
+
+        let mut reconciliation = Reconciliation {
+            at,
+            currency: account.currency,
+            balance: account.balance_at(conn, at)?,
+            statement_balance,
+            unreconciled,
+            unusual,
+            matches: Vec::new(),
+        };
+        if !reconciliation.is_balanced() {
+            // Records before the last reconciliation were already checked
+            let recent = &reconciliation.unreconciled
+                [..reconciliation.unreconciled.len().min(RECENT_RECORDS)];
+            // Records missing from the statement add up to what the records
+            // show more than the statement
+            reconciliation.matches =
+                combinations_adding_up_to(recent, -reconciliation.difference())
+                    .into_iter()
+                    .map(|indexes| indexes.into_iter().map(|i| recent[i].clone()).collect())
+                    .collect();
+        }
+        Ok(reconciliation)
+    }
+
+    /// What the statement shows more than the records
+    pub fn difference(&self) -> Decimal {
+        self.statement_balance - self.balance
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.difference().is_zero()
+    }
+}
+
+fn signed_thousandths(record: &Record) -> i128 {
+    let mut amount = record.amount;
+    amount.rescale(3);
+    match record.direction {
+        Direction::Debit => -amount.mantissa(),
+        Direction::Credit => amount.mantissa(),
+    }
+}
+
+/// Indexes of the records of the combinations whose signed amounts add up to
+/// `total`, with the fewest records first
+fn combinations_adding_up_to(records: &[Record], mut total: Decimal) -> Vec<Vec<usize>> {
+    total.rescale(3);
+    let total = total.mantissa();
+    let amounts = records.iter().map(signed_thousandths).collect::<Vec<_>>();
+
+    // Walk through the combinations in Gray code order, where a single
+    // record is added or removed at each step
+    let mut matches = Vec::new();
+    let mut mask = 0u32;
+    let mut sum = 0i128;
+    for step in 1..1u32 << amounts.len() {
+        let bit = step.trailing_zeros();
+        mask ^= 1 << bit;
+        if mask & (1 << bit) != 0 {
+            sum += amounts[bit as usize];
+        } else {
+            sum -= amounts[bit as usize];
+        }
+
+        if sum == total {
+            matches.push(mask);
+        }
+    }
+
+    matches.sort_by_key(|mask| (mask.count_ones(), *mask));
+    matches
+        .into_iter()
+        .take(MAX_MATCHES)
+        .map(|mask| {
+            (0..amounts.len())
+                .filter(|i| mask & (1 << i) != 0)
+                .collect()
+        })
+        .collect()
+}
+
+fn median(amounts: impl Iterator<Item = Decimal>) -> Decimal {
+    let mut amounts = amounts.collect::<Vec<_>>();
+    amounts.sort();
+    match amounts.len() {
+        0 => Decimal::ZERO,
+        len if len % 2 == 1 => amounts[len / 2],
+        len => (amounts[len / 2 - 1] + amounts[len / 2]) / Decimal::from(2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    fn date(day: &str) -> Result<NaiveDate> {
+        Ok(NaiveDate::parse_from_str(day, "%Y-%m-%d")?)
+    }
+
+    #[test]
+    fn reconcile() -> Result<()> {
+        let conn = &mut test::db()?;
+        let mut account = test::account!(conn, "Bank", balance: Decimal::from(100));
+        let mut record = |day, amount: Decimal, direction| -> Result<Record> {
+            Ok(test::record!(
+                conn,
+                &account,
+                amount: amount,
+                direction: direction,
+                operation_date: date(day)?,
+                value_date: date(day)?
+            ))
+        };
+        record("2024-08-05", Decimal::from(20), Direction::Debit)?;
+        record("2024-08-20", Decimal::from(50), Direction::Credit)?;
+        let coffee = record("2024-09-02", Decimal::new(125, 1), Direction::Debit)?;
+        let lunch = record("2024-09-03", Decimal::new(75, 1), Direction::Debit)?;
+        let laptop = record("2024-09-04", Decimal::from(300), Direction::Debit)?;
+        record("2024-10-01", Decimal::from(5), Direction::Debit)?;
+
+        assert_eq!(
+            Decimal::from(100),
+            account.balance_at(conn, date("2024-08-01")?)?
+        );
+        assert_eq!(
+            Decimal::from(130),
+            account.balance_at(conn, date("2024-08-31")?)?
+        );
+
+        let reconciliation =
+            Reconciliation::new(conn, &account, date("2024-08-31")?, Decimal::from(130))?;
+        assert!(reconciliation.is_balanced());
+        assert!(reconciliation.matches.is_empty());
+        assert_eq!(2, reconciliation.unreconciled.len());
+
+        account.reconcile(conn, date("2024-08-31")?, Decimal::from(130))?;
+        assert_eq!(
+            Some((
+                date("2024-08-31")?,
+                Amount(Decimal::from(130), Currency::EUR)
+            )),
+            account.reload(conn)?.reconciliation()
+        );
+
+        // The coffee and the lunch aren't on the statement yet
+        let reconciliation =
+            Reconciliation::new(conn, &account, date("2024-09-30")?, Decimal::from(-170))?;
+        assert_eq!(Decimal::from(-190), reconciliation.balance);
+        assert_eq!(Decimal::from(20), reconciliation.difference());
+        let ids = |records: &[Record]| records.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(
+            vec![laptop.id, lunch.id, coffee.id],
+            ids(&reconciliation.unreconciled)
+        );
+        assert_eq!(vec![laptop.id], ids(&reconciliation.unusual));
+        assert_eq!(
+            vec![vec![lunch.id, coffee.id]],
+            reconciliation
+                .matches
+                .iter()
+                .map(|records| ids(records))
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn combinations_adding_up_to() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Bank");
+        let records = [
+            (2, Direction::Debit),
+            (3, Direction::Debit),
+            (5, Direction::Debit),
+            (5, Direction::Credit),
+        ]
+        .into_iter()
+        .map(|(amount, direction)| {
+            Ok(test::record!(conn, &account, amount: Decimal::from(amount), direction: direction))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            vec![vec![2], vec![0, 1], vec![0, 1, 2, 3]],
+            super::combinations_adding_up_to(&records, Decimal::from(-5))
+        );
+        assert!(super::combinations_adding_up_to(&records, Decimal::from(100)).is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,691 @@
+//! High-level operations on the database
+//!
+//! Each function composes the New*, Change* and Query* structs the same way
+//! finnelctl does, taking plain owned parameters referencing other models by
+//! id instead of borrowing them.
+//!
+//! This module is the entry point meant for other programs: everything they
+//! need is re-exported here, so that they don't have to import diesel traits
+//! nor depend on how the other modules are organized.
+//!
+//! ```
+//! use finnel::api::*;
+//!
+//! # fn main() -> Result<()> {
+//! let db = &mut open_database(":memory:")?;
+//! let account = create_account(db, CreateAccountParams::new("Cash"))?;
+//! let record = create_record(
+//!     db,
+//!     CreateRecordParams {
+//!         amount: parse::amount("4,50 €")?,
+//!         details: "Coffee".to_string(),
+//!         ..CreateRecordParams::new(account.id)
+//!     },
+//! )?;
+//! assert_eq!(Decimal::new(450, 2), record.amount);
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::{
+    account::{Account, ChangeAccount, NewAccount, QueryAccount, Reconciliation},
+    category::{Category, ChangeCategory, NewCategory, QueryCategory},
+    date::{Month, Shift, Week},
+    essentials::{Amount, Conn, Currency, Decimal, Error, OptionalExtension, Result},
+    merchant::{ChangeMerchant, Merchant, NewMerchant, QueryMerchant},
+    order::OrderBy,
+    parse,
+    record::{
+        change::{ChangeRecord, ViolatingChangeRecord},
+        query::{OrderDirection, OrderField, OrderNulls},
+        Direction, Mode, NewRecord, PaymentMethod, QueryRecord, Record,
+    },
+    stats::{
+        category_series, ActivityStats, CategoriesStats, CategoryMonthStats, CategoryStats,
+        MerchantStats, MonthlyStats,
+    },
+    Database,
+};
+pub use chrono::{NaiveDate, NaiveDateTime};
+
+use diesel::Connection;
+use std::ops::Range;
+
+/// Open the database at `path`, running the pending migrations
+///
+/// The path `:memory:` opens a new database that only lives as long as the
+/// returned value.
+pub fn open_database<T: AsRef<std::path::Path>>(path: T) -> Result<Database> {
+    let mut db = Database::open(path)?;
+    db.setup()?;
+    Ok(db)
+}
+
+/// Run `function` in a transaction, rolled back if it returns an error
+pub fn transaction<T, F>(conn: &mut Conn, function: F) -> Result<T>
+where
+    F: FnOnce(&mut Conn) -> Result<T>,
+{
+    conn.transaction(function)
+}
+
+fn find_optional<T>(
+    conn: &mut Conn,
+    id: Option<i64>,
+    finder: fn(&mut Conn, i64) -> Result<T>,
+) -> Result<Option<T>> {
+    id.map(|id| finder(conn, id)).transpose()
+}
+
+fn find_optional_change<T>(
+    conn: &mut Conn,
+    id: Option<Option<i64>>,
+    finder: fn(&mut Conn, i64) -> Result<T>,
+) -> Result<Option<Option<T>>> {
+    id.map(|id| find_optional(conn, id, finder)).transpose()
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateAccountParams {
+    pub name: String,
+    pub currency: Currency,
+    pub iban: Option<String>,
+}
+
+impl CreateAccountParams {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            currency: Currency::EUR,
+            iban: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UpdateAccountParams {
+    pub name: Option<String>,
+    pub iban: Option<Option<String>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AccountFilter {
+    pub name: Option<String>,
+    pub count: Option<i64>,
+}
+
+pub fn create_account(conn: &mut Conn, params: CreateAccountParams) -> Result<Account> {
+    NewAccount {
+        currency: params.currency,
+        iban: params.iban.as_deref(),
+        ..NewAccount::new(&params.name)
+    }
+    .save(conn)
+}
+
+/// Update the account, doing nothing if no change is requested
+pub fn update_account(conn: &mut Conn, id: i64, params: UpdateAccountParams) -> Result<Account> {
+    let mut account = Account::find(conn, id)?;
+
+    ChangeAccount {
+        name: params.name.as_deref(),
+        iban: params.iban.as_ref().map(Option::as_deref),
+    }
+    .apply(conn, &mut account)
+    .optional_empty_changeset()?;
+
+    Ok(account)
+}
+
+pub fn list_accounts(conn: &mut Conn, filter: AccountFilter) -> Result<Vec<Account>> {
+    QueryAccount {
+        name: filter.name.as_deref(),
+        count: filter.count,
+    }
+    .run(conn)
+}
+
+/// Delete the account along with its records
+pub fn delete_account(conn: &mut Conn, id: i64) -> Result<()> {
+    transaction(conn, |conn| Account::find(conn, id)?.delete(conn))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CreateCategoryParams {
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub replaced_by_id: Option<i64>,
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    pub exclude_from_stats: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UpdateCategoryParams {
+    pub name: Option<String>,
+    pub parent_id: Option<Option<i64>>,
+    pub replaced_by_id: Option<Option<i64>>,
+    pub color: Option<Option<String>>,
+    pub emoji: Option<Option<String>>,
+    pub exclude_from_stats: Option<Option<bool>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CategoryFilter {
+    pub name: Option<String>,
+    pub parent_id: Option<Option<i64>>,
+    pub replaced_by_id: Option<Option<i64>>,
+    pub count: Option<i64>,
+    pub order: Vec<(OrderBy, OrderDirection)>,
+}
+
+pub fn create_category(conn: &mut Conn, params: CreateCategoryParams) -> Result<Category> {
+    let parent = find_optional(conn, params.parent_id, Category::find)?;
+    let replaced_by = find_optional(conn, params.replaced_by_id, Category::find)?;
+
+    NewCategory {
+        name: &params.name,
+        parent: parent.as_ref(),
+        replaced_by: replaced_by.as_ref(),
+        color: params.color.as_deref(),
+        emoji: params.emoji.as_deref(),
+        exclude_from_stats: params.exclude_from_stats,
+    }
+    .save(conn)
+}
+
+/// Update the category, doing nothing if no change is requested
+pub fn update_category(conn: &mut Conn, id: i64, params: UpdateCategoryParams) -> Result<Category> {
+    let mut category = Category::find(conn, id)?;
+    let parent = find_optional_change(conn, params.parent_id, Category::find)?;
+    let replaced_by = find_optional_change(conn, params.replaced_by_id, Category::find)?;
+
+    ChangeCategory {
+        name: params.name.as_deref(),
+        parent: parent.as_ref().map(Option::as_ref),
+        replaced_by: replaced_by.as_ref().map(Option::as_ref),
+        color: params.color.as_ref().map(Option::as_deref),
+        emoji: params.emoji.as_ref().map(Option::as_deref),
+        exclude_from_stats: params.exclude_from_stats,
+    }
+    .apply(conn, &mut category)
+    .optional_empty_changeset()?;
+
+    Ok(category)
+}
+
+pub fn list_categories(conn: &mut Conn, filter: CategoryFilter) -> Result<Vec<Category>> {
+    QueryCategory {
+        name: filter.name.as_deref(),
+        parent_id: filter.parent_id,
+        replaced_by_id: filter.replaced_by_id,
+        count: filter.count,
+        order: filter.order,
+    }
+    .run(conn)
+}
+
+/// Delete the category, removing it from the records, merchants and reports
+pub fn delete_category(conn: &mut Conn, id: i64) -> Result<()> {
+    transaction(conn, |conn| Category::find(conn, id)?.delete(conn))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CreateMerchantParams {
+    pub name: String,
+    pub default_category_id: Option<i64>,
+    pub replaced_by_id: Option<i64>,
+    pub website: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UpdateMerchantParams {
+    pub name: Option<String>,
+    pub default_category_id: Option<Option<i64>>,
+    pub replaced_by_id: Option<Option<i64>>,
+    pub website: Option<Option<String>>,
+    pub notes: Option<Option<String>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MerchantFilter {
+    pub name: Option<String>,
+    pub default_category_id: Option<Option<i64>>,
+    pub replaced_by_id: Option<Option<i64>>,
+    pub count: Option<i64>,
+    pub order: Vec<(OrderBy, OrderDirection)>,
+}
+
+pub fn create_merchant(conn: &mut Conn, params: CreateMerchantParams) -> Result<Merchant> {
+    let default_category = find_optional(conn, params.default_category_id, Category::find)?;
+    let replaced_by = find_optional(conn, params.replaced_by_id, Merchant::find)?;
+
+    NewMerchant {
+        name: &params.name,
+        default_category: default_category.as_ref(),
+        replaced_by: replaced_by.as_ref(),
+        website: params.website.as_deref(),
+        notes: params.notes.as_deref(),
+    }
+    .save(conn)
+}
+
+/// Update the merchant, doing nothing if no change is requested
+pub fn update_merchant(conn: &mut Conn, id: i64, params: UpdateMerchantParams) -> Result<Merchant> {
+    let mut merchant = Merchant::find(conn, id)?;
+    let default_category = find_optional_change(conn, params.default_category_id, Category::find)?;
+    let replaced_by = find_optional_change(conn, params.replaced_by_id, Merchant::find)?;
+
+    ChangeMerchant {
+        name: params.name.as_deref(),
+        default_category: default_category.as_ref().map(Option::as_ref),
+        replaced_by: replaced_by.as_ref().map(Option::as_ref),
+        website: params.website.as_ref().map(Option::as_deref),
+        notes: params.notes.as_ref().map(Option::as_deref),
+    }
+    .apply(conn, &mut merchant)
+    .optional_empty_changeset()?;
+
+    Ok(merchant)
+}
+
+pub fn list_merchants(conn: &mut Conn, filter: MerchantFilter) -> Result<Vec<Merchant>> {
+    QueryMerchant {
+        name: filter.name.as_deref(),
+        default_category_id: filter.default_category_id,
+        replaced_by_id: filter.replaced_by_id,
+        count: filter.count,
+        order: filter.order,
+    }
+    .run(conn)
+}
+
+/// Delete the merchant, removing it from the records
+pub fn delete_merchant(conn: &mut Conn, id: i64) -> Result<()> {
+    transaction(conn, |conn| Merchant::find(conn, id)?.delete(conn))
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateRecordParams {
+    pub account_id: i64,
+    pub amount: Decimal,
+    pub operation_date: NaiveDate,
+    pub value_date: NaiveDate,
+    pub direction: Direction,
+    pub mode: Mode,
+    pub details: String,
+    pub category_id: Option<i64>,
+    pub merchant_id: Option<i64>,
+    pub original_amount: Option<Decimal>,
+    pub original_currency: Option<Currency>,
+    pub allow_inverted_dates: bool,
+}
+
+impl CreateRecordParams {
+    /// Same defaults as `NewRecord::new`
+    pub fn new(account_id: i64) -> Self {
+        let date = chrono::Utc::now().date_naive();
+
+        Self {
+            account_id,
+            amount: Decimal::ZERO,
+            operation_date: date,
+            value_date: date,
+            direction: Direction::Debit,
+            mode: Mode::Direct(PaymentMethod::Empty),
+            details: String::new(),
+            category_id: None,
+            merchant_id: None,
+            original_amount: None,
+            original_currency: None,
+            allow_inverted_dates: false,
+        }
+    }
+}
+
+/// Changes to a record, including the ones finnelctl only allows with
+/// `--confirm` (amount, operation date, direction and mode)
+#[derive(Debug, Default, Clone)]
+pub struct UpdateRecordParams {
+    pub amount: Option<Decimal>,
+    pub operation_date: Option<NaiveDate>,
+    pub value_date: Option<NaiveDate>,
+    pub direction: Option<Direction>,
+    pub mode: Option<Mode>,
+    pub details: Option<String>,
+    pub category_id: Option<Option<i64>>,
+    pub merchant_id: Option<Option<i64>>,
+    pub flagged_at: Option<Option<NaiveDateTime>>,
+    pub flag_reason: Option<Option<String>>,
+    pub original_amount: Option<Option<Decimal>>,
+    pub original_currency: Option<Option<Currency>>,
+    pub allow_inverted_dates: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RecordFilter {
+    pub account_id: Option<i64>,
+    /// Only records of one of these accounts
+    pub account_ids: Option<Vec<i64>>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Filter `from` and `to` on the operation date instead of the value date
+    pub operation_date: bool,
+    pub greater_than: Option<Decimal>,
+    pub less_than: Option<Decimal>,
+    pub direction: Option<Direction>,
+    pub mode: Option<Mode>,
+    pub details: Option<String>,
+    pub merchant_id: Option<Option<i64>>,
+    pub category_id: Option<Option<i64>>,
+    pub category_ids: Option<Vec<i64>>,
+    pub exclude_category_ids: Option<Vec<i64>>,
+    pub exclude_merchant_ids: Option<Vec<i64>>,
+    pub exclude_mode: Option<Mode>,
+    pub tag_ids: Option<Vec<i64>>,
+    pub flagged: bool,
+    pub pending: bool,
+    pub original_currency: Option<Currency>,
+    pub text: Option<String>,
+    pub count: Option<i64>,
+    pub offset: Option<i64>,
+    pub order: Vec<(OrderField, OrderDirection, OrderNulls)>,
+}
+
+pub fn create_record(conn: &mut Conn, params: CreateRecordParams) -> Result<Record> {
+    let account = Account::find(conn, params.account_id)?;
+    let category = find_optional(conn, params.category_id, Category::find)?;
+    let merchant = find_optional(conn, params.merchant_id, Merchant::find)?;
+
+    NewRecord {
+        amount: params.amount,
+        operation_date: params.operation_date,
+        value_date: params.value_date,
+        direction: params.direction,
+        mode: params.mode,
+        details: &params.details,
+        category: category.as_ref(),
+        merchant: merchant.as_ref(),
+        original_amount: params.original_amount,
+        original_currency: params.original_currency,
+        allow_inverted_dates: params.allow_inverted_dates,
+        ..NewRecord::new(&account)
+    }
+    .save(conn)
+}
+
+/// Update the record, doing nothing if no change is requested
+pub fn update_record(conn: &mut Conn, id: i64, params: UpdateRecordParams) -> Result<Record> {
+    let mut record = Record::find(conn, id)?;
+    let category = find_optional_change(conn, params.category_id, Category::find)?;
+    let merchant = find_optional_change(conn, params.merchant_id, Merchant::find)?;
+
+    ViolatingChangeRecord {
+        amount: params.amount,
+        operation_date: params.operation_date,
+        value_date: params.value_date,
+        direction: params.direction,
+        mode: params.mode,
+        details: params.details.as_deref(),
+        category: category.as_ref().map(Option::as_ref),
+        merchant: merchant.as_ref().map(Option::as_ref),
+        flagged_at: params.flagged_at,
+        flag_reason: params.flag_reason.as_ref().map(Option::as_deref),
+        original_amount: params.original_amount,
+        original_currency: params.original_currency,
+        allow_inverted_dates: params.allow_inverted_dates,
+    }
+    .apply(conn, &mut record)
+    .optional_empty_changeset()?;
+
+    Ok(record)
+}
+
+pub fn list_records(conn: &mut Conn, filter: RecordFilter) -> Result<Vec<Record>> {
+    QueryRecord {
+        account_id: filter.account_id,
+        account_ids: filter.account_ids.as_deref(),
+        from: filter.from,
+        to: filter.to,
+        operation_date: filter.operation_date,
+        greater_than: filter.greater_than,
+        less_than: filter.less_than,
+        direction: filter.direction,
+        mode: filter.mode,
+        details: filter.details.as_deref(),
+        merchant_id: filter.merchant_id,
+        category_id: filter.category_id,
+        category_ids: filter.category_ids.as_deref(),
+        exclude_category_ids: filter.exclude_category_ids.as_deref(),
+        exclude_merchant_ids: filter.exclude_merchant_ids.as_deref(),
+        exclude_mode: filter.exclude_mode,
+        tag_ids: filter.tag_ids.as_deref(),
+        flagged: filter.flagged,
+        pending: filter.pending,
+        original_currency: filter.original_currency,
+        text: filter.text.as_deref(),
+        skip_invalid: false,
+        count: filter.count,
+        merchant_names_first: None,
+        offset: filter.offset,
+        order: filter.order,
+    }
+    .run(conn)
+}
+
+pub fn delete_record(conn: &mut Conn, id: i64) -> Result<()> {
+    transaction(conn, |conn| Record::find(conn, id)?.delete(conn))
+}
+
+/// Debits and credits of each category, for the records in `currency` with
+/// an operation date in the range, of the account or of all accounts
+pub fn category_stats(
+    conn: &mut Conn,
+    range: Range<NaiveDate>,
+    currency: Currency,
+    account_id: Option<i64>,
+) -> Result<CategoriesStats> {
+    let account_ids = account_id.map(|id| vec![id]);
+    CategoriesStats::from_date_range_currency_and_accounts(
+        conn,
+        range,
+        currency,
+        account_ids.as_deref(),
+    )
+}
+
+/// Debits and credits of the month, cached once computed
+pub fn monthly_stats(
+    conn: &mut Conn,
+    year: i32,
+    month: i32,
+    currency: Currency,
+) -> Result<MonthlyStats> {
+    MonthlyStats::find_or_create(conn, year, month, currency, false)
+}
+
+/// Debits and credits of the account for each day of the range
+pub fn account_activity(
+    conn: &mut Conn,
+    account_id: i64,
+    range: Range<NaiveDate>,
+) -> Result<ActivityStats> {
+    let account = Account::find(conn, account_id)?;
+    ActivityStats::from_date_range(conn, &account, range)
+}
+
+/// Compare the balance of the account at the date with the one of a bank
+/// statement, without recording anything
+pub fn reconcile_account(
+    conn: &mut Conn,
+    account_id: i64,
+    at: NaiveDate,
+    statement_balance: Decimal,
+) -> Result<Reconciliation> {
+    let account = Account::find(conn, account_id)?;
+    Reconciliation::new(conn, &account, at, statement_balance)
+}
+
+/// Totals of the records of the merchant, one per currency
+pub fn merchant_stats(conn: &mut Conn, merchant_id: i64) -> Result<Vec<MerchantStats>> {
+    MerchantStats::from_merchant(conn, merchant_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn record_resolves_replacers() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let merchant = test::merchant!(conn, "Chariot");
+        let replaced = test::merchant!(conn, "Chariot SAS", replaced_by: Some(&merchant));
+
+        let expected = test::record!(conn, &account, merchant: Some(&replaced));
+        let record = create_record(
+            conn,
+            CreateRecordParams {
+                merchant_id: Some(replaced.id),
+                ..CreateRecordParams::new(account.id)
+            },
+        )?;
+        assert_eq!(expected.merchant_id, record.merchant_id);
+        assert_eq!(Some(merchant.id), record.merchant_id);
+
+        let record = update_record(
+            conn,
+            record.id,
+            UpdateRecordParams {
+                amount: Some(Decimal::from(12)),
+                merchant_id: Some(None),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(Decimal::from(12), record.amount);
+        assert_eq!(None, record.merchant_id);
+        assert_eq!(Decimal::from(12), Record::find(conn, record.id)?.amount);
+
+        // Nothing to change is not an error
+        let record = update_record(conn, record.id, Default::default())?;
+        assert_eq!(Decimal::from(12), record.amount);
+
+        Ok(())
+    }
+
+    #[test]
+    fn category_validation() -> Result<()> {
+        let conn = &mut test::db()?;
+        let parent = create_category(
+            conn,
+            CreateCategoryParams {
+                name: "Food".to_string(),
+                ..Default::default()
+            },
+        )?;
+        let child = create_category(
+            conn,
+            CreateCategoryParams {
+                name: "Restaurant".to_string(),
+                parent_id: Some(parent.id),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(Some(parent.id), child.parent_id);
+
+        assert!(update_category(
+            conn,
+            parent.id,
+            UpdateCategoryParams {
+                parent_id: Some(Some(child.id)),
+                ..Default::default()
+            },
+        )
+        .is_err());
+        assert_eq!(None, Category::find(conn, parent.id)?.parent_id);
+
+        let parent = update_category(
+            conn,
+            parent.id,
+            UpdateCategoryParams {
+                name: Some("Groceries".to_string()),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!("Groceries", parent.name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_parity() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = create_account(conn, CreateAccountParams::new("Cash"))?;
+        let bank = test::account!(conn, "Bank");
+        let merchant = create_merchant(
+            conn,
+            CreateMerchantParams {
+                name: "Chariot".to_string(),
+                ..Default::default()
+            },
+        )?;
+
+        for amount in [5, 10, 15] {
+            test::record!(conn, &cash, amount: Decimal::from(amount));
+        }
+        test::record!(conn, &bank, amount: Decimal::from(7), merchant: Some(&merchant));
+
+        let order = vec![(
+            OrderField::Amount,
+            OrderDirection::Desc,
+            OrderNulls::Default,
+        )];
+        let expected = QueryRecord {
+            account_id: Some(cash.id),
+            greater_than: Some(Decimal::from(10)),
+            order: order.clone(),
+            ..QueryRecord::default()
+        }
+        .run(conn)?;
+        let records = list_records(
+            conn,
+            RecordFilter {
+                account_id: Some(cash.id),
+                greater_than: Some(Decimal::from(10)),
+                order,
+                ..Default::default()
+            },
+        )?;
+        let ids = |records: &[Record]| records.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(&expected), ids(&records));
+        assert_eq!(2, records.len());
+
+        let records = list_records(
+            conn,
+            RecordFilter {
+                merchant_id: Some(Some(merchant.id)),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(1, records.len());
+        assert_eq!(Some(bank.id), records[0].account_id);
+
+        let accounts = list_accounts(conn, Default::default())?;
+        assert_eq!(2, accounts.len());
+        assert_eq!("Cash", accounts[0].name);
+
+        let merchants = list_merchants(
+            conn,
+            MerchantFilter {
+                name: Some("chariot".to_string()),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(1, merchants.len());
+        assert_eq!(merchant.id, merchants[0].id);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{essentials::*, schema::categories};
 
 use diesel::prelude::*;
+use std::collections::HashMap;
 
 pub mod new;
 pub use new::NewCategory;
@@ -11,6 +12,17 @@ pub use change::ChangeCategory;
 mod query;
 pub use query::QueryCategory;
 
+pub mod path;
+pub use path::CategoryPaths;
+
+pub mod tree;
+pub use tree::CategoryDepth;
+
+pub mod rule;
+pub use rule::{CategoryRule, CompiledRules, NewCategoryRule};
+
+pub mod style;
+
 #[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
 #[diesel(table_name = categories)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -19,6 +31,12 @@ pub struct Category {
     pub name: String,
     pub parent_id: Option<i64>,
     pub replaced_by_id: Option<i64>,
+    /// Hex code like `#aabbcc` used by the HTML reports
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    /// Leave the records of the category out of the stats, None inheriting
+    /// the flag of the parent category
+    pub exclude_from_stats: Option<bool>,
 }
 
 impl Category {
@@ -42,24 +60,118 @@ impl Category {
             .map_err(|e| Error::from_diesel_error(e, "Category", None))
     }
 
+    /// Find the category by its name, normalized and ignoring case
     pub fn find_by_name(conn: &mut Conn, name: &str) -> Result<Self> {
+        let name = crate::name::normalize("Category", name)?;
         categories::table
-            .filter(categories::name.eq(name))
+            .filter(db::nocase(categories::name).eq(name))
             .select(Category::as_select())
             .first(conn)
             .map_err(|e| Error::from_diesel_error(e, "Category", Some("name")))
     }
 
+    /// Find the categories by their names in a single query, like
+    /// `find_by_name`, keyed by the name given and leaving out the ones not
+    /// found
+    pub fn find_all_by_name<'a, I>(conn: &mut Conn, names: I) -> Result<HashMap<String, Self>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let keys = crate::name::lookup_keys("Category", names);
+        let mut found = Vec::new();
+        for chunk in keys.chunks(crate::name::LOOKUP_CHUNK) {
+            found.extend(
+                categories::table
+                    .filter(db::lower(categories::name).eq_any(chunk.iter().map(|(_, key)| key)))
+                    .select(Category::as_select())
+                    .load::<Category>(conn)?,
+            );
+        }
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|(name, key)| {
+                found
+                    .iter()
+                    .find(|category| category.name.to_ascii_lowercase() == key)
+                    .map(|category| (name, category.clone()))
+            })
+            .collect())
+    }
+
+    /// Fail if a category other than `id` is already named `name`,
+    /// ignoring case
+    pub(crate) fn check_unique_name(conn: &mut Conn, name: &str, id: Option<i64>) -> Result<()> {
+        match Self::find_by_name(conn, name) {
+            Ok(existing) if Some(existing.id) != id => Err(Error::AlreadyExists {
+                model: "Category",
+                name: existing.name,
+                id: existing.id,
+            }),
+            Err(e) if !e.is_not_found() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ids of the children of the category, and their children recursively,
+    /// not including the category itself
+    pub fn descendant_ids(&self, conn: &mut Conn) -> Result<Vec<i64>> {
+        Ok(Self::descendants(conn, self.id)?
+            .into_iter()
+            .map(|category| category.id)
+            .collect())
+    }
+
+    /// Ids of the categories left out of the stats, flagged themselves or
+    /// inheriting the flag of their closest ancestor having one
+    pub fn excluded_from_stats_ids(conn: &mut Conn) -> Result<Vec<i64>> {
+        let flags = categories::table
+            .select((
+                categories::id,
+                categories::parent_id,
+                categories::exclude_from_stats,
+            ))
+            .load::<(i64, Option<i64>, Option<bool>)>(conn)?
+            .into_iter()
+            .map(|(id, parent_id, exclude)| (id, (parent_id, exclude)))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let excluded = |mut id: i64| {
+            let mut seen = Vec::new();
+            while let Some(&(parent_id, exclude)) = flags.get(&id) {
+                if let Some(exclude) = exclude {
+                    return exclude;
+                }
+                seen.push(id);
+                match parent_id {
+                    Some(parent_id) if !seen.contains(&parent_id) => id = parent_id,
+                    _ => break,
+                }
+            }
+            false
+        };
+
+        let mut ids = flags
+            .keys()
+            .copied()
+            .filter(|id| excluded(*id))
+            .collect::<Vec<_>>();
+        ids.sort();
+        Ok(ids)
+    }
+
     /// Delete the current category, nulling references to it where possible
     ///
     /// This method executes multiple queries without wrapping them in a
     /// transaction
     pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        let excluded = Self::excluded_from_stats_ids(conn)?;
         crate::record::clear_category_id(conn, self.id)?;
         crate::recurring_payment::clear_category_id(conn, self.id)?;
         crate::merchant::clear_category_id(conn, self.id)?;
         crate::report::clear_category_id(conn, self.id)?;
         crate::stats::clear_category_id(conn, self.id)?;
+        rule::clear_category_id(conn, self.id)?;
         diesel::update(categories::table)
             .filter(categories::replaced_by_id.eq(Some(self.id)))
             .set(categories::replaced_by_id.eq(None::<i64>))
@@ -69,12 +181,41 @@ impl Category {
             .set(categories::parent_id.eq(None::<i64>))
             .execute(conn)?;
         diesel::delete(&*self).execute(conn)?;
+        invalidate_stats_if_excluded_changed(conn, excluded)?;
 
         Ok(())
     }
 }
 
+/// Forget the cached stats when the categories left out of them are no
+/// longer the `previous` ones
+pub(crate) fn invalidate_stats_if_excluded_changed(
+    conn: &mut Conn,
+    previous: Vec<i64>,
+) -> Result<()> {
+    if Category::excluded_from_stats_ids(conn)? != previous {
+        crate::stats::MonthlyStats::invalidate_all(conn)?;
+    }
+    Ok(())
+}
+
+impl crate::resolved::Link for Category {
+    const MODEL: &'static str = "Category";
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl Resolvable for Category {
+    fn replacements(self, conn: &mut Conn) -> Result<Vec<Self>> {
+        crate::resolved::chain(conn, self, Self::find, |c| c.replaced_by_id)
+    }
+
     fn resolve(self, conn: &mut Conn) -> Result<Self> {
         crate::resolved::resolve(conn, self, Self::find, |c| c.replaced_by_id)
     }
@@ -89,6 +230,37 @@ mod tests {
     use super::*;
     use crate::test::prelude::{assert_eq, Result, *};
 
+    #[test]
+    fn excluded_from_stats_ids() -> Result<()> {
+        let conn = &mut test::db()?;
+        let transfers = &mut test::category!(conn, "Transfers", exclude_from_stats: Some(true));
+        let savings = &test::category!(conn, "Savings", parent: Some(transfers));
+        let livret = &test::category!(conn, "Livret A", parent: Some(savings));
+        let refunds = &test::category!(
+            conn,
+            "Refunds",
+            parent: Some(transfers),
+            exclude_from_stats: Some(false)
+        );
+        test::category!(conn, "Card", parent: Some(refunds));
+        test::category!(conn, "Food");
+
+        assert_eq!(
+            vec![transfers.id, savings.id, livret.id],
+            Category::excluded_from_stats_ids(conn)?
+        );
+
+        ChangeCategory {
+            exclude_from_stats: Some(None),
+            ..Default::default()
+        }
+        .apply(conn, transfers)?;
+        assert_eq!(None, transfers.exclude_from_stats);
+        assert!(Category::excluded_from_stats_ids(conn)?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn crud() -> Result<()> {
         let conn = &mut test::db()?;
@@ -115,6 +287,92 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn normalized_name() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let foo = NewCategory::new("  Foo   Bar ").save(conn)?;
+        assert_eq!("Foo Bar", foo.name);
+        assert_eq!(foo.id, Category::find_by_name(conn, "foo bar ")?.id);
+        let found = Category::find_all_by_name(conn, ["foo bar ", "FOO\tBAR", "Baz", ""])?;
+        assert_eq!(2, found.len());
+        assert_eq!(foo.id, found["foo bar "].id);
+        assert_eq!(foo.id, found["FOO\tBAR"].id);
+
+        assert!(matches!(
+            NewCategory::new(" foo  bar ").save(conn),
+            Err(Error::AlreadyExists { id, name, .. }) if id == foo.id && name == "Foo Bar"
+        ));
+        assert!(matches!(
+            NewCategory::new(" \t").save(conn),
+            Err(Error::Invalid(_))
+        ));
+
+        let mut baz = test::category!(conn, "Baz");
+        assert!(matches!(
+            ChangeCategory {
+                name: Some("FOO BAR"),
+                ..Default::default()
+            }
+            .apply(conn, &mut baz),
+            Err(Error::AlreadyExists { id, .. }) if id == foo.id
+        ));
+        ChangeCategory {
+            name: Some(" BAZ "),
+            ..Default::default()
+        }
+        .apply(conn, &mut baz)?;
+        assert_eq!("BAZ", baz.reload(conn)?.name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicated_names_renamed_by_migration() -> Result<()> {
+        use diesel::migration::MigrationSource;
+        use diesel_migrations::MigrationHarness;
+
+        let conn = &mut test::db()?;
+        let migration = MigrationSource::<diesel::sqlite::Sqlite>::migrations(&crate::MIGRATIONS)
+            .map_err(Error::from)?
+            .into_iter()
+            .find(|m| m.name().to_string().ends_with("_add_nocase_name_indexes"))
+            .unwrap();
+        conn.revert_migration(&*migration).map_err(Error::from)?;
+
+        let insert = |conn: &mut Conn, name: &str| {
+            new::InsertableCategory {
+                name: name.to_owned(),
+                ..Default::default()
+            }
+            .save(conn)
+        };
+        let restaurants = insert(conn, "Restaurants")?;
+        let duplicate = insert(conn, "restaurants")?;
+        let spaced = insert(conn, " Bars\tand  Pubs ")?;
+        let normalized = insert(conn, "Bars and Pubs")?;
+        let blank = insert(conn, " ")?;
+        conn.run_migration(&*migration).map_err(Error::from)?;
+
+        assert_eq!("Restaurants", restaurants.clone().reload(conn)?.name);
+        assert_eq!(
+            format!("restaurants ({})", duplicate.id),
+            duplicate.clone().reload(conn)?.name
+        );
+        assert_eq!("Bars and Pubs", spaced.clone().reload(conn)?.name);
+        assert_eq!(
+            format!("Bars and Pubs ({})", normalized.id),
+            normalized.clone().reload(conn)?.name
+        );
+        assert_eq!(
+            format!("Category {}", blank.id),
+            blank.clone().reload(conn)?.name
+        );
+        assert!(insert(conn, "RESTAURANTS").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn delete() -> Result<()> {
         let conn = &mut test::db()?;
@@ -129,4 +387,93 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn descendant_ids() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let food = test::category!(conn, "Food");
+        let restaurants = test::category!(conn, "Restaurants", parent: Some(&food));
+        let fast_food = test::category!(conn, "Fast food", parent: Some(&restaurants));
+        let burgers = test::category!(conn, "Burgers", parent: Some(&fast_food));
+        let groceries = test::category!(conn, "Groceries", parent: Some(&food));
+        test::category!(conn, "Other");
+
+        assert_eq!(
+            vec![restaurants.id, groceries.id, fast_food.id, burgers.id],
+            food.descendant_ids(conn)?
+        );
+        assert_eq!(vec![burgers.id], fast_food.descendant_ids(conn)?);
+        assert!(burgers.descendant_ids(conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn descendant_ids_cycle() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let cat1 = test::category!(conn, "cat1");
+        let cat2 = test::category!(conn, "cat2", parent: Some(&cat1));
+        let cat3 = test::category!(conn, "cat3", parent: Some(&cat2));
+
+        // ChangeCategory refuses to create loops, so bypass it
+        diesel::update(&cat1)
+            .set(categories::parent_id.eq(Some(cat3.id)))
+            .execute(conn)?;
+
+        assert_eq!(vec![cat2.id, cat3.id], cat1.descendant_ids(conn)?);
+        assert_eq!(vec![cat1.id, cat2.id], cat3.descendant_ids(conn)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_cycle() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let mut cat1 = test::category!(conn, "cat1");
+        let cat2 = test::category!(conn, "cat2", replaced_by: Some(&cat1));
+        let cat3 = test::category!(conn, "cat3");
+        let cycle = |error: Error| match error {
+            Error::ReplacementCycle { model, names } => {
+                assert_eq!("Category", model);
+                names
+            }
+            e => panic!("Unexpected error {e}"),
+        };
+
+        // ChangeCategory refuses to create cycles, so bypass it
+        diesel::update(&cat1)
+            .set(categories::replaced_by_id.eq(Some(cat2.id)))
+            .execute(conn)?;
+        cat1.reload(conn)?;
+        assert_eq!(
+            vec!["cat1", "cat2", "cat1"],
+            cycle(cat1.clone().resolve(conn).unwrap_err())
+        );
+        assert_eq!(
+            vec!["cat2", "cat1", "cat2"],
+            cycle(cat1.as_resolved(conn).err().unwrap())
+        );
+
+        // Longer cycle, reached from outside of it
+        diesel::update(&cat1)
+            .set(categories::replaced_by_id.eq(Some(cat3.id)))
+            .execute(conn)?;
+        diesel::update(&cat3)
+            .set(categories::replaced_by_id.eq(Some(cat2.id)))
+            .execute(conn)?;
+        let mut cat4 = test::category!(conn, "cat4");
+        diesel::update(&cat4)
+            .set(categories::replaced_by_id.eq(Some(cat3.id)))
+            .execute(conn)?;
+        cat4.reload(conn)?;
+        assert_eq!(
+            vec!["cat3", "cat2", "cat1", "cat3"],
+            cycle(cat4.replacements(conn).unwrap_err())
+        );
+
+        Ok(())
+    }
 }
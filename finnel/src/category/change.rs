@@ -1,5 +1,6 @@
 use crate::{
-    category::Category,
+    category::{style, Category},
+    diff::{self, FieldDiff},
     essentials::*,
     resolved::{as_resolved, mapmapmap, mapmapmapresult, mapmapresolve},
     schema::categories,
@@ -12,6 +13,9 @@ pub struct ChangeCategory<'a> {
     pub name: Option<&'a str>,
     pub parent: Option<Option<&'a Category>>,
     pub replaced_by: Option<Option<&'a Category>>,
+    pub color: Option<Option<&'a str>>,
+    pub emoji: Option<Option<&'a str>>,
+    pub exclude_from_stats: Option<Option<bool>>,
 }
 
 impl<'a> ChangeCategory<'a> {
@@ -27,7 +31,7 @@ impl<'a> ChangeCategory<'a> {
         resolved.validate(conn, category)?.save(conn)?;
 
         if let Some(value) = changeset.name {
-            category.name = value.to_string();
+            category.name = value;
         }
         if let Some(value) = changeset.parent_id {
             category.parent_id = value;
@@ -35,23 +39,50 @@ impl<'a> ChangeCategory<'a> {
         if let Some(value) = changeset.replaced_by_id {
             category.replaced_by_id = value;
         }
+        if let Some(value) = changeset.color {
+            category.color = value;
+        }
+        if let Some(value) = changeset.emoji {
+            category.emoji = value;
+        }
+        if let Some(value) = changeset.exclude_from_stats {
+            category.exclude_from_stats = value;
+        }
 
         Ok(())
     }
 
     pub fn into_resolved(self, conn: &mut Conn) -> Result<ResolvedChangeCategory<'a>> {
         Ok(ResolvedChangeCategory {
-            name: self.name,
+            name: self
+                .name
+                .map(|name| crate::name::normalize("Category", name))
+                .transpose()?,
             parent: mapmapresolve(conn, self.parent)?,
+            replaced_by_target: self.replaced_by.flatten().map(|r| r.id),
             replaced_by: mapmapresolve(conn, self.replaced_by)?,
+            color: self
+                .color
+                .map(|color| color.map(style::normalize_color).transpose())
+                .transpose()?,
+            emoji: self
+                .emoji
+                .map(|emoji| emoji.map(style::normalize_emoji).transpose())
+                .transpose()?,
+            exclude_from_stats: self.exclude_from_stats,
         })
     }
 }
 
 pub struct ResolvedChangeCategory<'a> {
-    name: Option<&'a str>,
+    name: Option<String>,
     parent: Option<Option<Resolved<'a, Category>>>,
     replaced_by: Option<Option<Resolved<'a, Category>>>,
+    /// Id of the replacer given, before following its own replacements
+    replaced_by_target: Option<i64>,
+    color: Option<Option<String>>,
+    emoji: Option<Option<String>>,
+    exclude_from_stats: Option<Option<bool>>,
 }
 
 impl<'a> ResolvedChangeCategory<'a> {
@@ -76,7 +107,8 @@ impl<'a> ResolvedChangeCategory<'a> {
         Ok(())
     }
 
-    fn validate_replace_by(&self, _conn: &mut Conn, category: &Category) -> Result<()> {
+    fn validate_replace_by(&self, conn: &mut Conn, category: &Category) -> Result<()> {
+        self.validate_replace_by_cycle(conn, category)?;
         mapmapmapresult(&self.replaced_by, |replaced_by| {
             if category.id == replaced_by.id {
                 return Err(Error::Invalid(
@@ -89,6 +121,33 @@ impl<'a> ResolvedChangeCategory<'a> {
         Ok(())
     }
 
+    /// Walk the replacements from the replacer given, which must not come
+    /// back to the category
+    fn validate_replace_by_cycle(&self, conn: &mut Conn, category: &Category) -> Result<()> {
+        let Some(target) = self.replaced_by_target.filter(|id| *id != category.id) else {
+            return Ok(());
+        };
+
+        let chain = Category::find(conn, target)?.replacements(conn)?;
+        if let Some(position) = chain.iter().position(|r| r.id == category.id) {
+            return Err(Error::ReplacementCycle {
+                model: "Category",
+                names: [category.name.clone()]
+                    .into_iter()
+                    .chain(chain[..=position].iter().map(|r| r.name.clone()))
+                    .collect(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_name(&self, conn: &mut Conn, category: &Category) -> Result<()> {
+        match &self.name {
+            Some(name) => Category::check_unique_name(conn, name, Some(category.id)),
+            None => Ok(()),
+        }
+    }
+
     pub fn validate(
         &self,
         conn: &mut Conn,
@@ -96,40 +155,104 @@ impl<'a> ResolvedChangeCategory<'a> {
     ) -> Result<ValidatedChangeCategory<'a>> {
         self.validate_parent(conn, category)?;
         self.validate_replace_by(conn, category)?;
+        self.validate_name(conn, category)?;
 
         Ok(ValidatedChangeCategory(category, self.as_changeset()))
     }
 
-    pub fn as_changeset(&self) -> CategoryChangeset<'a> {
+    /// Fields of the category the change would modify, leaving out the ones
+    /// keeping their value, with the other categories by name
+    pub fn diff(&self, conn: &mut Conn, category: &Category) -> Result<Vec<FieldDiff>> {
+        let mut fields = Vec::new();
+
+        if let Some(name) = &self.name {
+            diff::push(&mut fields, "name", Some(&category.name), Some(name));
+        }
+        if let Some(parent) = &self.parent {
+            let old = diff::category_name(conn, category.parent_id)?;
+            let new = parent.as_ref().map(|c| c.map(|c| c.name.clone()));
+            diff::push(&mut fields, "parent", old, new);
+        }
+        if let Some(replaced_by) = &self.replaced_by {
+            let old = diff::category_name(conn, category.replaced_by_id)?;
+            let new = replaced_by.as_ref().map(|c| c.map(|c| c.name.clone()));
+            diff::push(&mut fields, "replaced_by", old, new);
+        }
+        if let Some(color) = &self.color {
+            diff::push(
+                &mut fields,
+                "color",
+                category.color.as_ref(),
+                color.as_ref(),
+            );
+        }
+        if let Some(emoji) = &self.emoji {
+            diff::push(
+                &mut fields,
+                "emoji",
+                category.emoji.as_ref(),
+                emoji.as_ref(),
+            );
+        }
+        if let Some(exclude) = self.exclude_from_stats {
+            diff::push(
+                &mut fields,
+                "exclude_from_stats",
+                category.exclude_from_stats,
+                exclude,
+            );
+        }
+
+        Ok(fields)
+    }
+
+    pub fn as_changeset(&self) -> CategoryChangeset {
         CategoryChangeset {
-            name: self.name,
+            name: self.name.clone(),
             parent_id: mapmapmap(&self.parent, |c| c.id),
             replaced_by_id: mapmapmap(&self.replaced_by, |c| c.id),
+            color: self.color.clone(),
+            emoji: self.emoji.clone(),
+            exclude_from_stats: self.exclude_from_stats,
         }
     }
 }
 
-pub struct ValidatedChangeCategory<'a>(&'a Category, CategoryChangeset<'a>);
+pub struct ValidatedChangeCategory<'a>(&'a Category, CategoryChangeset);
 
 impl<'a> ValidatedChangeCategory<'a> {
     pub fn save(self, conn: &mut Conn) -> Result<()> {
+        // Either changes which categories are left out of the stats
+        let excluded = if self.1.parent_id.is_some() || self.1.exclude_from_stats.is_some() {
+            Some(Category::excluded_from_stats_ids(conn)?)
+        } else {
+            None
+        };
+
         diesel::update(self.0).set(self.1).execute(conn)?;
+
+        if let Some(excluded) = excluded {
+            super::invalidate_stats_if_excluded_changed(conn, excluded)?;
+        }
         Ok(())
     }
 }
 
 #[derive(Default, Clone, AsChangeset)]
 #[diesel(table_name = categories)]
-pub struct CategoryChangeset<'a> {
-    pub name: Option<&'a str>,
+pub struct CategoryChangeset {
+    pub name: Option<String>,
     pub parent_id: Option<Option<i64>>,
     pub replaced_by_id: Option<Option<i64>>,
+    pub color: Option<Option<String>>,
+    pub emoji: Option<Option<String>>,
+    pub exclude_from_stats: Option<Option<bool>>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::prelude::{Result, *};
+    use crate::test::prelude::{assert_eq, Result, *};
 
     #[test]
     fn update_loop() -> Result<()> {
@@ -158,4 +281,64 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn replace_by_cycle() -> Result<()> {
+        let conn = &mut test::db()?;
+        let foo = &mut test::category!(conn, "Foo");
+        let bar = &mut test::category!(conn, "Bar");
+        let baz = &mut test::category!(conn, "Baz");
+
+        // Bar is replaced by Baz, which is later replaced by Foo
+        ChangeCategory {
+            replaced_by: Some(Some(baz)),
+            ..Default::default()
+        }
+        .apply(conn, bar)?;
+        ChangeCategory {
+            replaced_by: Some(Some(foo)),
+            ..Default::default()
+        }
+        .apply(conn, baz)?;
+
+        let result = ChangeCategory {
+            replaced_by: Some(Some(bar)),
+            ..Default::default()
+        }
+        .save(conn, foo);
+        match result {
+            Err(Error::ReplacementCycle { model, names }) => {
+                assert_eq!("Category", model);
+                assert_eq!(vec!["Foo", "Bar", "Baz", "Foo"], names);
+            }
+            result => panic!("Unexpected result {:?}", result.err()),
+        }
+        assert_eq!(None, foo.reload(conn)?.replaced_by_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff() -> Result<()> {
+        let conn = &mut test::db()?;
+        let home = &test::category!(conn, "Home");
+        let food = &test::category!(conn, "Food");
+
+        let change = ChangeCategory {
+            name: Some("Food"),
+            parent: Some(Some(home)),
+            color: Some(None),
+            exclude_from_stats: Some(Some(true)),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                ("parent", None, Some("Home".to_string())),
+                ("exclude_from_stats", None, Some("true".to_string())),
+            ],
+            change.into_resolved(conn)?.diff(conn, food)?
+        );
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    category::Category,
+    category::{style, Category},
     essentials::*,
     resolved::{mapmap, mapresolve},
     schema::categories,
@@ -12,6 +12,9 @@ pub struct NewCategory<'a> {
     pub name: &'a str,
     pub parent: Option<&'a Category>,
     pub replaced_by: Option<&'a Category>,
+    pub color: Option<&'a str>,
+    pub emoji: Option<&'a str>,
+    pub exclude_from_stats: Option<bool>,
 }
 
 impl<'a> NewCategory<'a> {
@@ -26,13 +29,20 @@ impl<'a> NewCategory<'a> {
         self.to_insertable(conn)?.save(conn)
     }
 
-    pub fn to_insertable(self, conn: &mut Conn) -> Result<InsertableCategory<'a>> {
+    /// Normalize the name, failing if it is empty or already used by another
+    /// category
+    pub fn to_insertable(self, conn: &mut Conn) -> Result<InsertableCategory> {
         let NewCategory {
             name,
             parent,
             replaced_by,
+            color,
+            emoji,
+            exclude_from_stats,
         } = self;
 
+        let name = crate::name::normalize("Category", name)?;
+        Category::check_unique_name(conn, &name, None)?;
         let parent = mapresolve(conn, parent)?;
         let replaced_by = mapresolve(conn, replaced_by)?;
 
@@ -40,19 +50,25 @@ impl<'a> NewCategory<'a> {
             name,
             parent_id: mapmap(&parent, |c| c.id),
             replaced_by_id: mapmap(&replaced_by, |c| c.id),
+            color: color.map(style::normalize_color).transpose()?,
+            emoji: emoji.map(style::normalize_emoji).transpose()?,
+            exclude_from_stats,
         })
     }
 }
 
 #[derive(Default, Insertable)]
 #[diesel(table_name = categories)]
-pub struct InsertableCategory<'a> {
-    pub name: &'a str,
+pub struct InsertableCategory {
+    pub name: String,
     pub parent_id: Option<i64>,
     pub replaced_by_id: Option<i64>,
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    pub exclude_from_stats: Option<bool>,
 }
 
-impl InsertableCategory<'_> {
+impl InsertableCategory {
     pub fn save(self, conn: &mut Conn) -> Result<Category> {
         Ok(diesel::insert_into(categories::table)
             .values(self)
@@ -0,0 +1,141 @@
+use crate::{category::Category, essentials::*, schema::categories};
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+
+/// Separator between the names of the categories in a path
+pub const SEPARATOR: &str = " > ";
+
+/// Full path of every category, loaded with a single query so displaying
+/// many records doesn't fetch their ancestors one by one
+#[derive(Debug, Default)]
+pub struct CategoryPaths {
+    paths: HashMap<i64, String>,
+}
+
+impl CategoryPaths {
+    pub fn load(conn: &mut Conn) -> Result<Self> {
+        let categories = categories::table
+            .select((categories::id, (categories::name, categories::parent_id)))
+            .load::<(i64, (String, Option<i64>))>(conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        let paths = categories
+            .keys()
+            .map(|id| (*id, path(*id, |id| categories.get(&id).cloned())))
+            .collect();
+
+        Ok(CategoryPaths { paths })
+    }
+
+    pub fn get(&self, id: i64) -> Option<&str> {
+        self.paths.get(&id).map(String::as_str)
+    }
+}
+
+impl Category {
+    /// Names of the ancestors of the category, from the root, followed by its
+    /// own name, e.g. "Food > Restaurants"
+    pub fn full_path(&self, conn: &mut Conn) -> Result<String> {
+        let mut ids = Vec::new();
+        if let Some(parent_id) = self.parent_id.filter(|id| *id != self.id) {
+            ids.push(parent_id);
+            ids.extend(
+                Category::ancestors(conn, parent_id)?
+                    .into_iter()
+                    .map(|ancestor| ancestor.id)
+                    .take_while(|id| *id != self.id),
+            );
+        }
+
+        let names = categories::table
+            .filter(categories::id.eq_any(&ids))
+            .select((categories::id, categories::name))
+            .load::<(i64, String)>(conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        Ok(ids
+            .iter()
+            .rev()
+            .filter_map(|id| names.get(id).map(String::as_str))
+            .chain([self.name.as_str()])
+            .collect::<Vec<_>>()
+            .join(SEPARATOR))
+    }
+}
+
+/// Walk up the parents with `find`, stopping at the root, at a missing
+/// category or when the parents loop back
+fn path<F>(id: i64, mut find: F) -> String
+where
+    F: FnMut(i64) -> Option<(String, Option<i64>)>,
+{
+    let mut seen = Vec::new();
+    let mut names = Vec::new();
+    let mut next = Some(id);
+
+    while let Some(id) = next.filter(|id| !seen.contains(id)) {
+        let Some((name, parent_id)) = find(id) else {
+            break;
+        };
+        seen.push(id);
+        names.push(name);
+        next = parent_id;
+    }
+
+    names.reverse();
+    names.join(SEPARATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn full_path() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let food = test::category!(conn, "Food");
+        let restaurants = test::category!(conn, "Restaurants", parent: Some(&food));
+        let fast_food = test::category!(conn, "Fast food", parent: Some(&restaurants));
+        let other = test::category!(conn, "Other");
+
+        assert_eq!("Food", food.full_path(conn)?);
+        assert_eq!("Food > Restaurants > Fast food", fast_food.full_path(conn)?);
+
+        let paths = CategoryPaths::load(conn)?;
+        assert_eq!(Some("Food > Restaurants"), paths.get(restaurants.id));
+        assert_eq!(
+            Some("Food > Restaurants > Fast food"),
+            paths.get(fast_food.id)
+        );
+        assert_eq!(Some("Other"), paths.get(other.id));
+        assert_eq!(None, paths.get(other.id + 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn full_path_cycle() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let cat1 = test::category!(conn, "cat1");
+        let cat2 = test::category!(conn, "cat2", parent: Some(&cat1));
+
+        // ChangeCategory refuses to create loops, so bypass it
+        diesel::update(&cat1)
+            .set(categories::parent_id.eq(Some(cat2.id)))
+            .execute(conn)?;
+        let cat1 = Category::find(conn, cat1.id)?;
+
+        assert_eq!("cat1 > cat2", cat2.full_path(conn)?);
+        assert_eq!("cat2 > cat1", cat1.full_path(conn)?);
+        assert_eq!(Some("cat1 > cat2"), CategoryPaths::load(conn)?.get(cat2.id));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use super::Category;
 use crate::essentials::*;
-use crate::schema::categories;
+use crate::order::{OrderBy, OrderDirection};
+use crate::schema::{categories, records};
 
 use diesel::{
     expression::SqlLiteral,
@@ -23,6 +24,8 @@ pub struct QueryCategory<'a> {
     pub parent_id: Option<Option<i64>>,
     pub replaced_by_id: Option<Option<i64>>,
     pub count: Option<i64>,
+    /// Ordered by id when empty
+    pub order: Vec<(OrderBy, OrderDirection)>,
 }
 
 pub struct QueryCategoryWithParent<'a>(QueryCategory<'a>);
@@ -43,6 +46,20 @@ type QueryType<'a> = IntoBoxed<
 >;
 
 impl<'a> QueryCategory<'a> {
+    fn sort_by<U>(query: QueryType<'a>, expression: U, direction: OrderDirection) -> QueryType<'a>
+    where
+        U: 'a
+            + ExpressionMethods
+            + diesel::query_builder::QueryFragment<Sqlite>
+            + AppearsOnTable<Alias<CategoryAlias>>
+            + Send,
+    {
+        match direction {
+            OrderDirection::Asc => query.then_order_by(expression.asc()),
+            OrderDirection::Desc => query.then_order_by(expression.desc()),
+        }
+    }
+
     fn build(&self) -> QueryType<'a> {
         let mut query = CATEGORIES_ALIAS.into_boxed();
 
@@ -63,7 +80,29 @@ impl<'a> QueryCategory<'a> {
             query = query.limit(count);
         }
 
-        query
+        let id = CATEGORIES_ALIAS.field(categories::id);
+        // Only the records directly in the category, not in its children
+        let category_records = || records::table.filter(records::category_id.eq(id.nullable()));
+        for (field, direction) in &self.order {
+            query = match field {
+                OrderBy::Id => Self::sort_by(query, id, *direction),
+                OrderBy::Name => {
+                    Self::sort_by(query, CATEGORIES_ALIAS.field(categories::name), *direction)
+                }
+                OrderBy::Records => {
+                    Self::sort_by(query, category_records().count().single_value(), *direction)
+                }
+                OrderBy::LastUsed => Self::sort_by(
+                    query,
+                    category_records()
+                        .select(diesel::dsl::max(records::operation_date))
+                        .single_value(),
+                    *direction,
+                ),
+            };
+        }
+
+        query.then_order_by(id.asc())
     }
 
     pub fn run(&self, conn: &mut Conn) -> Result<Vec<Category>> {
@@ -196,4 +235,95 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn query_filters() -> Result<()> {
+        let conn = &mut test::db()?;
+        let food = &mut test::category!(conn, "Food");
+        let bars = &mut test::category!(conn, "Bars");
+        let pubs = &mut test::category!(conn, "Pubs");
+        let bistros = &mut test::category!(conn, "Bistros");
+
+        ChangeCategory {
+            parent: Some(Some(food)),
+            ..Default::default()
+        }
+        .apply(conn, bars)?;
+        ChangeCategory {
+            replaced_by: Some(Some(food)),
+            ..Default::default()
+        }
+        .apply(conn, pubs)?;
+        ChangeCategory {
+            parent: Some(Some(food)),
+            replaced_by: Some(Some(bars)),
+            ..Default::default()
+        }
+        .apply(conn, bistros)?;
+
+        let ids = |query: QueryCategory, conn: &mut Conn| -> Result<Vec<i64>> {
+            Ok(query.run(conn)?.into_iter().map(|c| c.id).collect())
+        };
+
+        assert_eq!(
+            vec![food.id],
+            ids(
+                QueryCategory {
+                    parent_id: Some(None),
+                    replaced_by_id: Some(None),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![bars.id],
+            ids(
+                QueryCategory {
+                    parent_id: Some(Some(food.id)),
+                    replaced_by_id: Some(None),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![pubs.id],
+            ids(
+                QueryCategory {
+                    parent_id: Some(None),
+                    replaced_by_id: Some(Some(food.id)),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![bistros.id],
+            ids(
+                QueryCategory {
+                    name: Some("%s%"),
+                    parent_id: Some(Some(food.id)),
+                    replaced_by_id: Some(Some(bars.id)),
+                    count: Some(1),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![bars.id],
+            ids(
+                QueryCategory {
+                    name: Some("%s%"),
+                    parent_id: Some(Some(food.id)),
+                    count: Some(1),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+
+        Ok(())
+    }
 }
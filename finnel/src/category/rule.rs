@@ -0,0 +1,469 @@
+use super::Category;
+use crate::{
+    essentials::*,
+    merchant::Merchant,
+    record::{Direction, Mode, PaymentMethod},
+    schema::{categories, category_rules},
+};
+
+use std::cmp::Reverse;
+
+use diesel::prelude::*;
+
+/// Category given to new records created without one, either from their
+/// mode, and possibly their direction, or from their details
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = category_rules)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CategoryRule {
+    pub id: i64,
+    /// Mode of the records, where a mode without payment method matches the
+    /// records of that mode with any payment method, None for the rules
+    /// matching the details
+    pub mode: Option<Mode>,
+    /// Direction of the records, or any direction
+    pub direction: Option<Direction>,
+    /// Text found in the details of the records, ignoring case, or regular
+    /// expression they match
+    pub pattern: Option<String>,
+    pub is_regex: bool,
+    /// Rules of higher priority are applied first
+    pub priority: i32,
+    pub category_id: i64,
+}
+
+impl CategoryRule {
+    pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
+        category_rules::table
+            .find(id)
+            .select(CategoryRule::as_select())
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Category rule", None))
+    }
+
+    /// List all rules along with their category
+    pub fn all(conn: &mut Conn) -> Result<Vec<(Self, Category)>> {
+        Ok(category_rules::table
+            .inner_join(categories::table.on(categories::id.eq(category_rules::category_id)))
+            .select((CategoryRule::as_select(), Category::as_select()))
+            .order(category_rules::id.asc())
+            .load(conn)?)
+    }
+
+    /// Rules giving the category, deleted along with it
+    pub fn of_category(conn: &mut Conn, category_id: i64) -> Result<Vec<Self>> {
+        Ok(category_rules::table
+            .filter(category_rules::category_id.eq(category_id))
+            .select(CategoryRule::as_select())
+            .order(category_rules::id.asc())
+            .load(conn)?)
+    }
+
+    /// Category of the most specific rule matching the mode of the record
+    ///
+    /// Past the priority, a rule with a payment method or a direction is more
+    /// specific than one without, and the oldest rule wins between equally
+    /// specific ones.
+    pub fn category_for(
+        conn: &mut Conn,
+        mode: Mode,
+        direction: Direction,
+    ) -> Result<Option<Category>> {
+        Ok(category_rules::table
+            .inner_join(categories::table.on(categories::id.eq(category_rules::category_id)))
+            .filter(category_rules::mode.is_not_null())
+            .select((CategoryRule::as_select(), Category::as_select()))
+            .load::<(Self, Category)>(conn)?
+            .into_iter()
+            .filter(|(rule, _)| rule.matches(mode, direction))
+            .max_by_key(|(rule, _)| (rule.priority, rule.specificity(), Reverse(rule.id)))
+            .map(|(_, category)| category))
+    }
+
+    /// Category of a record from its details, when it has none
+    ///
+    /// The default category of the merchant comes first, then the one of the
+    /// first rule matching the details, by priority then from the oldest.
+    pub fn categorize(
+        conn: &mut Conn,
+        details: &str,
+        merchant_id: Option<i64>,
+    ) -> Result<Option<Category>> {
+        CompiledRules::load(conn)?.categorize(conn, details, merchant_id)
+    }
+
+    /// First rule matching the details, by priority then from the oldest,
+    /// along with its category
+    pub fn for_details(conn: &mut Conn, details: &str) -> Result<Option<(Self, Category)>> {
+        Ok(CompiledRules::load(conn)?.for_details(details).cloned())
+    }
+
+    pub fn matches(&self, mode: Mode, direction: Direction) -> bool {
+        let mode_matches = match (self.mode, mode) {
+            (Some(Mode::Direct(PaymentMethod::Empty)), Mode::Direct(_)) => true,
+            (Some(Mode::Atm(PaymentMethod::Empty)), Mode::Atm(_)) => true,
+            (rule, mode) => rule == Some(mode),
+        };
+
+        mode_matches && self.direction.is_none_or(|d| d == direction)
+    }
+
+    pub fn matches_details(&self, details: &str) -> bool {
+        match &self.pattern {
+            Some(pattern) if self.is_regex => {
+                regex::Regex::new(pattern).is_ok_and(|regex| regex.is_match(details))
+            }
+            Some(pattern) => details.to_lowercase().contains(&pattern.to_lowercase()),
+            None => false,
+        }
+    }
+
+    fn specificity(&self) -> (bool, bool) {
+        let with_method = matches!(
+            self.mode,
+            Some(
+                Mode::Direct(PaymentMethod::CardLast4Digit(..))
+                    | Mode::Atm(PaymentMethod::CardLast4Digit(..))
+            )
+        );
+        (with_method, self.direction.is_some())
+    }
+
+    pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        diesel::delete(&*self).execute(conn)?;
+        Ok(())
+    }
+}
+
+/// Rules matching the details, loaded and compiled once to categorize many
+/// records, e.g. during an import
+pub struct CompiledRules {
+    /// By priority then from the oldest
+    rules: Vec<(Matcher, (CategoryRule, Category))>,
+    /// Rules whose regular expression doesn't compile, left out, one message
+    /// for each
+    pub invalid: Vec<String>,
+}
+
+enum Matcher {
+    Regex(regex::Regex),
+    /// Lowercased text
+    Text(String),
+}
+
+impl CompiledRules {
+    pub fn load(conn: &mut Conn) -> Result<Self> {
+        let mut compiled = Self {
+            rules: Vec::new(),
+            invalid: Vec::new(),
+        };
+
+        for (rule, category) in category_rules::table
+            .inner_join(categories::table.on(categories::id.eq(category_rules::category_id)))
+            .filter(category_rules::pattern.is_not_null())
+            .select((CategoryRule::as_select(), Category::as_select()))
+            .order((category_rules::priority.desc(), category_rules::id.asc()))
+            .load::<(CategoryRule, Category)>(conn)?
+        {
+            let Some(pattern) = &rule.pattern else {
+                continue;
+            };
+            let matcher = if rule.is_regex {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) => Matcher::Regex(regex),
+                    Err(e) => {
+                        compiled
+                            .invalid
+                            .push(format!("Rule {}: regex {pattern:?}: {e}", rule.id));
+                        continue;
+                    }
+                }
+            } else {
+                Matcher::Text(pattern.to_lowercase())
+            };
+            compiled.rules.push((matcher, (rule, category)));
+        }
+
+        Ok(compiled)
+    }
+
+    /// Category of a record from its details, when it has none, see
+    /// [`CategoryRule::categorize`]
+    pub fn categorize(
+        &self,
+        conn: &mut Conn,
+        details: &str,
+        merchant_id: Option<i64>,
+    ) -> Result<Option<Category>> {
+        if let Some(id) = merchant_id {
+            if let Some(category) = Merchant::find(conn, id)?.fetch_default_category(conn)? {
+                return Ok(Some(category));
+            }
+        }
+
+        Ok(self
+            .for_details(details)
+            .map(|(_, category)| category.clone()))
+    }
+
+    /// First rule matching the details along with its category
+    pub fn for_details(&self, details: &str) -> Option<&(CategoryRule, Category)> {
+        let lowercase = details.to_lowercase();
+        self.rules
+            .iter()
+            .find(|(matcher, _)| match matcher {
+                Matcher::Regex(regex) => regex.is_match(details),
+                Matcher::Text(text) => lowercase.contains(text.as_str()),
+            })
+            .map(|(_, found)| found)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = category_rules)]
+pub struct NewCategoryRule<'a> {
+    pub mode: Option<Mode>,
+    pub direction: Option<Direction>,
+    pub pattern: Option<&'a str>,
+    pub is_regex: bool,
+    pub priority: i32,
+    pub category_id: i64,
+}
+
+impl<'a> NewCategoryRule<'a> {
+    pub fn new(mode: Mode, category: &Category) -> Self {
+        Self {
+            mode: Some(mode),
+            direction: None,
+            pattern: None,
+            is_regex: false,
+            priority: 0,
+            category_id: category.id,
+        }
+    }
+
+    /// Rule matching the records whose details contain the pattern
+    pub fn details(pattern: &'a str, category: &Category) -> Self {
+        Self {
+            mode: None,
+            direction: None,
+            pattern: Some(pattern),
+            is_regex: false,
+            priority: 0,
+            category_id: category.id,
+        }
+    }
+
+    pub fn save(self, conn: &mut Conn) -> Result<CategoryRule> {
+        if let Some(pattern) = self.pattern.filter(|_| self.is_regex) {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(Error::Invalid(format!("Regex {pattern:?}: {e}")));
+            }
+        }
+
+        Ok(diesel::insert_into(category_rules::table)
+            .values(self)
+            .returning(CategoryRule::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+/// Delete the rules giving the category
+pub(crate) fn clear_category_id(conn: &mut Conn, id: i64) -> Result<()> {
+    diesel::delete(category_rules::table)
+        .filter(category_rules::category_id.eq(id))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn category_for() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::category!(conn, "Cash");
+        let card = test::category!(conn, "Card");
+        let refund = test::category!(conn, "Refund");
+        let card_mode = Mode::Atm(PaymentMethod::CardLast4Digit('1', '2', '3', '4'));
+
+        let category_for = |conn: &mut Conn, mode, direction| {
+            CategoryRule::category_for(conn, mode, direction)
+                .map(|category| category.map(|c| c.name))
+        };
+
+        assert_eq!(
+            None,
+            category_for(conn, Mode::Atm(PaymentMethod::Empty), Direction::Debit)?
+        );
+
+        NewCategoryRule::new(Mode::Atm(PaymentMethod::Empty), &cash).save(conn)?;
+        NewCategoryRule::new(card_mode, &card).save(conn)?;
+        NewCategoryRule {
+            direction: Some(Direction::Credit),
+            ..NewCategoryRule::new(Mode::Direct(PaymentMethod::Empty), &refund)
+        }
+        .save(conn)?;
+
+        assert_eq!(
+            Some("Cash".to_owned()),
+            category_for(conn, Mode::Atm(PaymentMethod::Empty), Direction::Debit)?
+        );
+        assert_eq!(
+            Some("Card".to_owned()),
+            category_for(conn, card_mode, Direction::Credit)?
+        );
+        assert_eq!(
+            Some("Refund".to_owned()),
+            category_for(conn, Mode::Direct(PaymentMethod::Empty), Direction::Credit)?
+        );
+        assert_eq!(None, category_for(conn, Mode::Transfer, Direction::Credit)?);
+        assert_eq!(
+            None,
+            category_for(conn, Mode::Direct(PaymentMethod::Empty), Direction::Debit)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn categorize() -> Result<()> {
+        let conn = &mut test::db()?;
+        let subscriptions = test::category!(conn, "Subscriptions");
+        let energy = test::category!(conn, "Energy");
+        let video = test::category!(conn, "Video");
+        let mut netflix = test::merchant!(conn, "Netflix");
+
+        let categorize = |conn: &mut Conn, details, merchant_id| {
+            CategoryRule::categorize(conn, details, merchant_id)
+                .map(|category| category.map(|c| c.name))
+        };
+
+        assert_eq!(None, categorize(conn, "PRLV SEPA NETFLIX", None)?);
+
+        NewCategoryRule::details("netflix", &subscriptions).save(conn)?;
+        NewCategoryRule {
+            is_regex: true,
+            ..NewCategoryRule::details(r"^PRLV .*\bEDF\b", &energy)
+        }
+        .save(conn)?;
+
+        assert_eq!(
+            Some("Subscriptions".to_owned()),
+            categorize(conn, "PRLV SEPA NETFLIX", None)?
+        );
+        assert_eq!(
+            Some("Energy".to_owned()),
+            categorize(conn, "PRLV SEPA EDF CLIENTS", None)?
+        );
+        assert_eq!(None, categorize(conn, "CB EDFI", None)?);
+
+        // Higher priority first, then the oldest rule
+        NewCategoryRule {
+            priority: 1,
+            ..NewCategoryRule::details("SEPA NETFLIX", &video)
+        }
+        .save(conn)?;
+        assert_eq!(
+            Some("Video".to_owned()),
+            categorize(conn, "PRLV SEPA NETFLIX", None)?
+        );
+        assert_eq!(
+            Some("Subscriptions".to_owned()),
+            categorize(conn, "CB NETFLIX", None)?
+        );
+
+        // The default category of the merchant comes first
+        crate::merchant::ChangeMerchant {
+            default_category: Some(Some(&energy)),
+            ..Default::default()
+        }
+        .apply(conn, &mut netflix)?;
+        assert_eq!(
+            Some("Energy".to_owned()),
+            categorize(conn, "CB NETFLIX", Some(netflix.id))?
+        );
+
+        // Details rules don't match modes and the other way around
+        assert!(CategoryRule::category_for(conn, Mode::Transfer, Direction::Debit)?.is_none());
+        NewCategoryRule::new(Mode::Transfer, &video).save(conn)?;
+        assert_eq!(None, categorize(conn, "Transfer", None)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_regex() -> Result<()> {
+        let conn = &mut test::db()?;
+        let energy = test::category!(conn, "Energy");
+
+        let rule = NewCategoryRule {
+            is_regex: true,
+            ..NewCategoryRule::details("EDF (", &energy)
+        };
+        assert!(matches!(rule.save(conn), Err(Error::Invalid(_))));
+        assert!(CategoryRule::all(conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compiled_rules() -> Result<()> {
+        let conn = &mut test::db()?;
+        let energy = test::category!(conn, "Energy");
+        let video = test::category!(conn, "Video");
+
+        // Saved before the regex was validated
+        let invalid = diesel::insert_into(category_rules::table)
+            .values(NewCategoryRule {
+                is_regex: true,
+                priority: 1,
+                ..NewCategoryRule::details("EDF (", &video)
+            })
+            .returning(CategoryRule::as_returning())
+            .get_result(conn)?;
+        NewCategoryRule {
+            is_regex: true,
+            ..NewCategoryRule::details(r"\bEDF\b", &energy)
+        }
+        .save(conn)?;
+
+        let rules = CompiledRules::load(conn)?;
+        assert_eq!(1, rules.invalid.len());
+        assert!(rules.invalid[0].starts_with(&format!("Rule {}: regex \"EDF (\"", invalid.id)));
+        assert_eq!(
+            Some("Energy"),
+            rules
+                .for_details("PRLV EDF (CLIENTS")
+                .map(|(_, category)| category.name.as_str())
+        );
+        assert!(rules.for_details("CB EDFI").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn deleted_category() -> Result<()> {
+        let conn = &mut test::db()?;
+        let mut cash = test::category!(conn, "Cash");
+        let mode = Mode::Atm(PaymentMethod::Empty);
+        let rule = NewCategoryRule::new(mode, &cash).save(conn)?;
+        assert_eq!(
+            vec![rule.id],
+            CategoryRule::of_category(conn, cash.id)?
+                .iter()
+                .map(|r| r.id)
+                .collect::<Vec<_>>()
+        );
+
+        cash.delete(conn)?;
+
+        assert!(CategoryRule::category_for(conn, mode, Direction::Debit)?.is_none());
+        assert!(CategoryRule::all(conn)?.is_empty());
+        assert!(CategoryRule::find(conn, rule.id).is_err());
+
+        Ok(())
+    }
+}
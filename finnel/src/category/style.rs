@@ -0,0 +1,70 @@
+use crate::essentials::*;
+
+/// Color of a category as it is saved, a lowercase hex code like `#aabbcc`
+///
+/// The short form `#abc` is expanded, anything else fails naming the value.
+pub fn normalize_color(color: &str) -> Result<String> {
+    let invalid = || {
+        Error::Invalid(format!(
+            "Invalid category color {color:?}, expected a hex code like #aabbcc"
+        ))
+    };
+
+    let digits = color.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let digits = match digits.len() {
+        3 => digits.chars().flat_map(|c| [c, c]).collect(),
+        6 => digits.to_owned(),
+        _ => return Err(invalid()),
+    };
+    Ok(format!("#{}", digits.to_ascii_lowercase()))
+}
+
+/// Emoji of a category as it is saved, trimmed
+///
+/// Fails if nothing is left, or if it contains whitespace or letters, which
+/// would rather be part of the name.
+pub fn normalize_emoji(emoji: &str) -> Result<String> {
+    let emoji = emoji.trim();
+    if emoji.is_empty()
+        || emoji
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_alphanumeric())
+    {
+        return Err(Error::Invalid(format!(
+            "Invalid category emoji {emoji:?}, expected a single emoji"
+        )));
+    }
+    Ok(emoji.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn normalize_color() -> Result<()> {
+        assert_eq!("#aabbcc", super::normalize_color("#AABBCC")?);
+        assert_eq!("#aabbcc", super::normalize_color(" #abc ")?);
+        for color in ["aabbcc", "#aabbc", "#aabbccdd", "#ggbbcc", "#", ""] {
+            let error = super::normalize_color(color).unwrap_err().to_string();
+            assert!(error.contains(&format!("{color:?}")), "{error}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalize_emoji() -> Result<()> {
+        assert_eq!("🍕", super::normalize_emoji(" 🍕 ")?);
+        assert_eq!("🏳️‍🌈", super::normalize_emoji("🏳️‍🌈")?);
+        assert!(super::normalize_emoji("").is_err());
+        assert!(super::normalize_emoji("🍕 🍔").is_err());
+        assert!(super::normalize_emoji("pizza").is_err());
+
+        Ok(())
+    }
+}
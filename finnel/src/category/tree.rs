@@ -0,0 +1,234 @@
+use crate::{category::Category, essentials::*};
+
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Integer},
+};
+
+/// Deepest level walked from a category, bounding the recursion when the
+/// parents loop back
+pub const MAX_DEPTH: i32 = 64;
+
+/// Category found walking the tree from another one, `depth` levels away
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct CategoryDepth {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = Integer)]
+    pub depth: i32,
+}
+
+// Each category has a single parent, so a loop below the category always goes
+// back through it, which ends the recursion
+const DESCENDANTS: &str = "
+WITH RECURSIVE tree(id, depth) AS (
+    SELECT id, 1
+    FROM categories
+    WHERE parent_id = ?1 AND id != ?1
+  UNION ALL
+    SELECT categories.id, tree.depth + 1
+    FROM categories
+    JOIN tree ON categories.parent_id = tree.id
+    WHERE tree.depth < ?2 AND categories.id != ?1
+)
+SELECT id, depth FROM tree ORDER BY depth, id";
+
+// A single chain, so the ids walked are kept in `path`, as `,1,4,`, to stop at
+// the first loop whatever category it goes back to
+const ANCESTORS: &str = "
+WITH RECURSIVE tree(id, depth, path) AS (
+    SELECT parent_id, 1, ',' || id || ',' || parent_id || ','
+    FROM categories
+    WHERE id = ?1 AND parent_id IS NOT NULL AND parent_id != ?1
+  UNION ALL
+    SELECT categories.parent_id, tree.depth + 1, tree.path || categories.parent_id || ','
+    FROM categories
+    JOIN tree ON categories.id = tree.id
+    WHERE categories.parent_id IS NOT NULL
+      AND tree.depth < ?2
+      AND instr(tree.path, ',' || categories.parent_id || ',') = 0
+)
+SELECT tree.id, tree.depth FROM tree
+JOIN categories ON categories.id = tree.id
+ORDER BY tree.depth";
+
+impl Category {
+    /// Children of the category, their children and so on, with a single
+    /// query, ordered by depth then id
+    pub fn descendants(conn: &mut Conn, id: i64) -> Result<Vec<CategoryDepth>> {
+        Ok(diesel::sql_query(DESCENDANTS)
+            .bind::<BigInt, _>(id)
+            .bind::<Integer, _>(MAX_DEPTH)
+            .load(conn)?)
+    }
+
+    /// Parent of the category, its parent and so on up to the root, with a
+    /// single query
+    pub fn ancestors(conn: &mut Conn, id: i64) -> Result<Vec<CategoryDepth>> {
+        Ok(diesel::sql_query(ANCESTORS)
+            .bind::<BigInt, _>(id)
+            .bind::<Integer, _>(MAX_DEPTH)
+            .load(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::categories;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    fn depths(categories: Vec<CategoryDepth>) -> Vec<(i64, i32)> {
+        categories.into_iter().map(|c| (c.id, c.depth)).collect()
+    }
+
+    #[test]
+    fn descendants_and_ancestors() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let food = test::category!(conn, "Food");
+        let restaurants = test::category!(conn, "Restaurants", parent: Some(&food));
+        let fast_food = test::category!(conn, "Fast food", parent: Some(&restaurants));
+        let burgers = test::category!(conn, "Burgers", parent: Some(&fast_food));
+        let groceries = test::category!(conn, "Groceries", parent: Some(&food));
+        test::category!(conn, "Other");
+
+        assert_eq!(
+            vec![
+                (restaurants.id, 1),
+                (groceries.id, 1),
+                (fast_food.id, 2),
+                (burgers.id, 3)
+            ],
+            depths(Category::descendants(conn, food.id)?)
+        );
+        assert!(Category::descendants(conn, burgers.id)?.is_empty());
+
+        assert_eq!(
+            vec![(fast_food.id, 1), (restaurants.id, 2), (food.id, 3)],
+            depths(Category::ancestors(conn, burgers.id)?)
+        );
+        assert!(Category::ancestors(conn, food.id)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cycle() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let cat1 = test::category!(conn, "cat1");
+        let cat2 = test::category!(conn, "cat2", parent: Some(&cat1));
+        let cat3 = test::category!(conn, "cat3", parent: Some(&cat2));
+        let cat4 = test::category!(conn, "cat4");
+        let cat5 = test::category!(conn, "cat5", parent: Some(&cat4));
+        let cat6 = test::category!(conn, "cat6", parent: Some(&cat5));
+
+        // ChangeCategory refuses to create loops, so bypass it
+        diesel::update(&cat1)
+            .set(categories::parent_id.eq(Some(cat3.id)))
+            .execute(conn)?;
+        diesel::update(&cat5)
+            .set(categories::parent_id.eq(Some(cat6.id)))
+            .execute(conn)?;
+
+        assert_eq!(
+            vec![(cat2.id, 1), (cat3.id, 2)],
+            depths(Category::descendants(conn, cat1.id)?)
+        );
+        assert_eq!(
+            vec![(cat2.id, 1), (cat1.id, 2)],
+            depths(Category::ancestors(conn, cat3.id)?)
+        );
+        assert!(Category::descendants(conn, cat4.id)?.is_empty());
+        assert_eq!(
+            vec![(cat6.id, 1)],
+            depths(Category::descendants(conn, cat5.id)?)
+        );
+        assert_eq!(
+            vec![(cat5.id, 1)],
+            depths(Category::ancestors(conn, cat6.id)?)
+        );
+
+        Ok(())
+    }
+
+    /// Previous implementation of `descendant_ids`, one query per level
+    fn descendant_ids_by_level(conn: &mut Conn, id: i64) -> Result<Vec<i64>> {
+        let mut ids = Vec::new();
+        let mut parent_ids = vec![id];
+
+        while !parent_ids.is_empty() {
+            parent_ids = categories::table
+                .filter(categories::parent_id.eq_any(&parent_ids))
+                .select(categories::id)
+                .order(categories::id.asc())
+                .load::<i64>(conn)?
+                .into_iter()
+                .filter(|child| *child != id && !ids.contains(child))
+                .collect();
+            ids.extend(&parent_ids);
+        }
+
+        Ok(ids)
+    }
+
+    #[test]
+    fn descendants_compared_to_levels() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        // 5 levels of 1, 3, 9, 27 and 160 categories
+        let root = test::category!(conn, "root");
+        let mut level = vec![root.id];
+        let mut count = 1;
+        for depth in 2..=5 {
+            let mut next = Vec::new();
+            for (index, parent_id) in level.iter().enumerate() {
+                let children = match (depth, index) {
+                    (5, 0) => 4,
+                    (5, _) => 6,
+                    _ => 3,
+                };
+                for child in 0..children {
+                    let category = diesel::insert_into(categories::table)
+                        .values((
+                            categories::name.eq(format!("cat {depth}.{index}.{child}")),
+                            categories::parent_id.eq(parent_id),
+                        ))
+                        .returning(categories::id)
+                        .get_result::<i64>(conn)?;
+                    next.push(category);
+                }
+            }
+            count += next.len();
+            level = next;
+        }
+        assert_eq!(200, count);
+
+        let start = std::time::Instant::now();
+        let mut by_levels = Vec::new();
+        for _ in 0..20 {
+            by_levels = descendant_ids_by_level(conn, root.id)?;
+        }
+        let levels_time = start.elapsed() / 20;
+
+        let start = std::time::Instant::now();
+        let mut descendants = Vec::new();
+        for _ in 0..20 {
+            descendants = Category::descendants(conn, root.id)?;
+        }
+        let cte_time = start.elapsed() / 20;
+
+        println!(
+            "descendants of 200 categories: {cte_time:?} with a CTE, {levels_time:?} by level"
+        );
+        assert_eq!(199, descendants.len());
+        assert_eq!(Some(4), descendants.last().map(|c| c.depth));
+        assert_eq!(
+            by_levels,
+            descendants.into_iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+//! Comparison of the database with another copy of it
+//!
+//! The other database is attached read-only under the `other` schema, so
+//! that the comparisons are plain set differences between the two schemas.
+
+use std::path::Path;
+
+use crate::{essentials::*, record::Direction};
+
+use chrono::NaiveDate;
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Date, Nullable, Text},
+};
+
+/// Tables whose rows are counted in the summary
+const TABLES: [&str; 7] = [
+    "accounts",
+    "categories",
+    "merchants",
+    "records",
+    "tags",
+    "recurring_payments",
+    "reports",
+];
+
+/// Columns identifying a record across databases
+///
+/// Records have no external id, and their ids are not stable from one copy
+/// to the other, so they are matched by account name, date, amount and
+/// details instead.
+const RECORD_KEY: &str = "accounts.name AS account, records.operation_date, records.amount, \
+     records.currency, records.direction, records.details";
+
+/// Columns of [`RECORD_KEY`], without their aliases
+const RECORD_COLUMNS: &str = "accounts.name, records.operation_date, records.amount, \
+     records.currency, records.direction, records.details";
+
+/// Database holding the records, the one the connection was opened on or
+/// the attached one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    This,
+    Other,
+}
+
+impl Side {
+    fn schema(&self) -> &'static str {
+        match self {
+            Side::This => "main",
+            Side::Other => "other",
+        }
+    }
+
+    fn opposite(&self) -> Self {
+        match self {
+            Side::This => Side::Other,
+            Side::Other => Side::This,
+        }
+    }
+}
+
+/// Rows found by a comparison, limited to a number of them
+#[derive(Debug)]
+pub struct Listing<T> {
+    /// Number of rows found, including those beyond the limit
+    pub count: i64,
+    pub rows: Vec<T>,
+}
+
+#[derive(Debug, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RecordKey {
+    #[diesel(sql_type = Text)]
+    pub account: String,
+    #[diesel(sql_type = Date)]
+    pub operation_date: NaiveDate,
+    #[diesel(sql_type = BigInt, deserialize_as = db::Decimal)]
+    pub amount: Decimal,
+    #[diesel(sql_type = Text, deserialize_as = db::Currency)]
+    pub currency: Currency,
+    #[diesel(sql_type = Text)]
+    pub direction: Direction,
+    #[diesel(sql_type = Text)]
+    pub details: String,
+}
+
+impl RecordKey {
+    pub fn amount(&self) -> Amount {
+        Amount(self.amount, self.currency)
+    }
+}
+
+/// Attribute of an entity having the same name in both databases, but a
+/// different value
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Divergence {
+    #[diesel(sql_type = Text)]
+    pub table: String,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = Text)]
+    pub attribute: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub this: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub other: Option<String>,
+}
+
+/// Attributes compared between entities of the same name
+///
+/// References are compared by the name of the referenced entity, as ids
+/// differ between databases.
+enum Attribute {
+    Column(&'static str, &'static str, &'static str),
+    Reference(&'static str, &'static str, &'static str, &'static str),
+}
+
+const ATTRIBUTES: [Attribute; 6] = [
+    Attribute::Column("accounts", "currency", "currency"),
+    Attribute::Column("accounts", "iban", "iban"),
+    Attribute::Reference("categories", "parent", "parent_id", "categories"),
+    Attribute::Reference("categories", "replaced_by", "replaced_by_id", "categories"),
+    Attribute::Reference(
+        "merchants",
+        "default_category",
+        "default_category_id",
+        "categories",
+    ),
+    Attribute::Reference("merchants", "replaced_by", "replaced_by_id", "merchants"),
+];
+
+impl Attribute {
+    fn query(&self) -> String {
+        match self {
+            Attribute::Column(table, attribute, column) => format!(
+                "SELECT '{table}' AS \"table\", t.name AS name, '{attribute}' AS attribute, \
+                 t.{column} AS this, o.{column} AS other \
+                 FROM main.{table} t INNER JOIN other.{table} o ON o.name = t.name \
+                 WHERE t.{column} IS NOT o.{column}"
+            ),
+            Attribute::Reference(table, attribute, column, target) => format!(
+                "SELECT '{table}' AS \"table\", t.name AS name, '{attribute}' AS attribute, \
+                 tr.name AS this, orf.name AS other \
+                 FROM main.{table} t INNER JOIN other.{table} o ON o.name = t.name \
+                 LEFT JOIN main.{target} tr ON tr.id = t.{column} \
+                 LEFT JOIN other.{target} orf ON orf.id = o.{column} \
+                 WHERE tr.name IS NOT orf.name"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TableSummary {
+    #[diesel(sql_type = Text)]
+    pub table: String,
+    #[diesel(sql_type = BigInt)]
+    pub this: i64,
+    #[diesel(sql_type = BigInt)]
+    pub other: i64,
+}
+
+/// Sum of the records in a currency, credits minus debits
+#[derive(Debug, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RecordsTotal {
+    #[diesel(sql_type = Text, deserialize_as = db::Currency)]
+    pub currency: Currency,
+    #[diesel(sql_type = BigInt, deserialize_as = db::Decimal)]
+    pub this: Decimal,
+    #[diesel(sql_type = BigInt, deserialize_as = db::Decimal)]
+    pub other: Decimal,
+}
+
+impl RecordsTotal {
+    pub fn this(&self) -> Amount {
+        Amount(self.this, self.currency)
+    }
+
+    pub fn other(&self) -> Amount {
+        Amount(self.other, self.currency)
+    }
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Connection with another database attached, detached when dropped
+pub struct Comparison<'a> {
+    conn: &'a mut Conn,
+}
+
+impl<'a> Comparison<'a> {
+    /// Attach the database at `path`, read-only
+    pub fn attach<P: AsRef<Path>>(conn: &'a mut Conn, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::Invalid(format!(
+                "Database not found: {}",
+                path.display()
+            )));
+        }
+
+        sql_query("ATTACH DATABASE ? AS other")
+            .bind::<Text, _>(read_only_uri(path))
+            .execute(conn)?;
+
+        Ok(Comparison { conn })
+    }
+
+    /// Records of one side without a matching record on the other
+    ///
+    /// Identical records are told apart by their rank among them, so that a
+    /// side with more copies of a record than the other lists the extra ones.
+    pub fn missing_records(&mut self, side: Side, limit: i64) -> Result<Listing<RecordKey>> {
+        let query = format!(
+            "{} EXCEPT {}",
+            record_keys(side),
+            record_keys(side.opposite())
+        );
+        self.listing(&query, "account, operation_date, details", limit)
+    }
+
+    /// Attributes differing between entities with the same name
+    pub fn divergences(&mut self, limit: i64) -> Result<Listing<Divergence>> {
+        let query = ATTRIBUTES
+            .iter()
+            .map(Attribute::query)
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        self.listing(&query, "\"table\", name, attribute", limit)
+    }
+
+    /// Number of rows of the main tables in both databases
+    pub fn summaries(&mut self) -> Result<Vec<TableSummary>> {
+        let query = TABLES
+            .iter()
+            .map(|table| {
+                format!(
+                    "SELECT '{table}' AS \"table\", \
+                     (SELECT COUNT(*) FROM main.{table}) AS this, \
+                     (SELECT COUNT(*) FROM other.{table}) AS other"
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        Ok(sql_query(query).load(self.conn)?)
+    }
+
+    /// Totals of the records of both databases, by currency
+    pub fn records_totals(&mut self) -> Result<Vec<RecordsTotal>> {
+        let total = |schema: &str| {
+            format!(
+                "SELECT currency, \
+                 TOTAL(CASE direction WHEN 'Debit' THEN -amount ELSE amount END) AS amount \
+                 FROM {schema}.records GROUP BY currency"
+            )
+        };
+        let query = format!(
+            "SELECT currency, \
+             CAST(TOTAL(CASE side WHEN 'main' THEN amount END) AS INTEGER) AS this, \
+             CAST(TOTAL(CASE side WHEN 'other' THEN amount END) AS INTEGER) AS other \
+             FROM (SELECT 'main' AS side, * FROM ({}) UNION ALL SELECT 'other', * FROM ({})) \
+             GROUP BY currency ORDER BY currency",
+            total("main"),
+            total("other")
+        );
+        Ok(sql_query(query).load(self.conn)?)
+    }
+
+    fn listing<T>(&mut self, query: &str, order: &str, limit: i64) -> Result<Listing<T>>
+    where
+        T: QueryableByName<diesel::sqlite::Sqlite> + 'static,
+    {
+        let count = sql_query(format!("SELECT COUNT(*) AS count FROM ({query})"))
+            .get_result::<Count>(self.conn)?
+            .count;
+        let rows = sql_query(format!("SELECT * FROM ({query}) ORDER BY {order} LIMIT ?"))
+            .bind::<BigInt, _>(limit)
+            .load(self.conn)?;
+
+        Ok(Listing { count, rows })
+    }
+}
+
+impl Drop for Comparison<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = sql_query("DETACH DATABASE other").execute(self.conn) {
+            log::warn!("Unable to detach the compared database: {}", e);
+        }
+    }
+}
+
+fn record_keys(side: Side) -> String {
+    let schema = side.schema();
+    format!(
+        "SELECT {RECORD_KEY}, ROW_NUMBER() OVER (PARTITION BY {RECORD_COLUMNS}) AS occurrence \
+         FROM {schema}.records \
+         INNER JOIN {schema}.accounts ON accounts.id = records.account_id"
+    )
+}
+
+/// URI opening the file read-only, escaping the characters with a meaning
+/// in URIs
+fn read_only_uri(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{}?mode=ro", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use crate::{account::Account, category::Category};
+
+    use assert_fs::{prelude::*, TempDir};
+
+    fn open(dir: &TempDir, name: &str) -> Result<crate::Database> {
+        let mut db = crate::Database::open(dir.child(name).path())?;
+        db.setup()?;
+        Ok(db)
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 8, day).unwrap()
+    }
+
+    #[test]
+    fn compare() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut this = open(&dir, "this.db")?;
+        let mut other = open(&dir, "other.db")?;
+
+        for conn in [&mut *this, &mut *other] {
+            let cash = test::account!(conn, "Cash");
+            let food = test::category!(conn, "Food");
+            test::category!(conn, "Bread", parent: Some(&food));
+            test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(1),
+                details: "Groceries");
+        }
+
+        // Created in another order, so that the ids differ
+        let conn = &mut *other;
+        let bank = test::account!(conn, "Bank");
+        let cash = Account::find_by_name(conn, "Cash")?;
+        test::record!(conn, &bank, amount: Decimal::ONE, operation_date: date(2),
+            details: "Fee");
+        test::record!(conn, &cash, amount: Decimal::TWO, operation_date: date(3),
+            details: "Bakery", direction: Direction::Credit);
+        test::category!(conn, "Sweets");
+        let sweets = Category::find_by_name(conn, "Sweets")?;
+        let mut bread = Category::find_by_name(conn, "Bread")?;
+        crate::category::ChangeCategory {
+            parent: Some(Some(&sweets)),
+            ..Default::default()
+        }
+        .apply(conn, &mut bread)?;
+
+        let conn = &mut *this;
+        let cash = Account::find_by_name(conn, "Cash")?;
+        test::record!(conn, &cash, amount: Decimal::TWO, operation_date: date(3),
+            details: "Bakery");
+        drop(other);
+
+        let mut comparison = Comparison::attach(conn, dir.child("other.db").path())?;
+
+        let missing = comparison.missing_records(Side::This, 10)?;
+        assert_eq!(1, missing.count);
+        assert_eq!("Bakery", missing.rows[0].details);
+        assert_eq!(Direction::Debit, missing.rows[0].direction);
+
+        let missing = comparison.missing_records(Side::Other, 1)?;
+        assert_eq!(2, missing.count);
+        assert_eq!(1, missing.rows.len());
+        assert_eq!("Bank", missing.rows[0].account);
+        assert_eq!(Decimal::ONE, missing.rows[0].amount);
+
+        let divergences = comparison.divergences(10)?;
+        assert_eq!(1, divergences.count);
+        assert_eq!(
+            Divergence {
+                table: "categories".to_owned(),
+                name: "Bread".to_owned(),
+                attribute: "parent".to_owned(),
+                this: Some("Food".to_owned()),
+                other: Some("Sweets".to_owned()),
+            },
+            divergences.rows[0]
+        );
+
+        let summaries = comparison.summaries()?;
+        let summary = |table| {
+            let summary = summaries.iter().find(|s| s.table == table).unwrap();
+            (summary.this, summary.other)
+        };
+        assert_eq!((1, 2), summary("accounts"));
+        assert_eq!((2, 3), summary("categories"));
+        assert_eq!((2, 3), summary("records"));
+        assert_eq!((0, 0), summary("tags"));
+
+        let totals = comparison.records_totals()?;
+        assert_eq!(1, totals.len());
+        assert_eq!(Decimal::new(-12, 0), totals[0].this);
+        assert_eq!(Decimal::new(-9, 0), totals[0].other);
+
+        drop(comparison);
+        // Detached once dropped
+        assert!(sql_query("SELECT 1 FROM other.records")
+            .execute(conn)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_records() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut this = open(&dir, "this.db")?;
+        let mut other = open(&dir, "other.db")?;
+
+        for (conn, copies) in [(&mut *this, 3), (&mut *other, 1)] {
+            let cash = test::account!(conn, "Cash");
+            for _ in 0..copies {
+                test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(1),
+                    details: "Coffee");
+            }
+        }
+        drop(other);
+
+        let conn = &mut *this;
+        let mut comparison = Comparison::attach(conn, dir.child("other.db").path())?;
+
+        let missing = comparison.missing_records(Side::This, 10)?;
+        assert_eq!(2, missing.count);
+        assert_eq!("Coffee", missing.rows[0].details);
+        assert_eq!(0, comparison.missing_records(Side::Other, 10)?.count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file() -> Result<()> {
+        let conn = &mut test::db()?;
+        assert!(Comparison::attach(conn, "/nonexistent/finnel.db").is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use crate::prelude::*;
 
 mod categories;
+pub use categories::{deduplicate as deduplicate_categories, CategoryMerge, Deduplication};
 mod merchants;
 mod records;
+pub use records::{consolidate_categories, with_replaced_category};
 mod recurring_payments;
 mod reports;
 
@@ -1,6 +1,14 @@
 use crate::category::ChangeCategory;
 use crate::prelude::*;
-use crate::schema::{self, categories};
+use crate::schema::{
+    self, categories, category_rules, merchants, monthly_category_stats, records,
+    recurring_payments, reports_categories,
+};
+use crate::stats::MonthlyStats;
+
+use std::collections::{BTreeMap, HashMap};
+
+use diesel::dsl::count_star;
 
 pub fn consolidate(conn: &mut Conn) -> Result<()> {
     consolidate_replace_by(conn)?;
@@ -71,6 +79,196 @@ pub fn consolidate_parent(conn: &mut Conn) -> Result<()> {
     Ok(())
 }
 
+/// Categories with the same normalized name, to be merged into the kept one
+#[derive(Debug)]
+pub struct CategoryMerge {
+    pub kept: Category,
+    pub duplicates: Vec<Category>,
+}
+
+#[derive(Debug, Default)]
+pub struct Deduplication {
+    pub merges: Vec<CategoryMerge>,
+    /// Root categories with the same name as categories under different
+    /// parents, left as they are since which parent to merge them under can't
+    /// be guessed, along with the kept categories under those parents
+    pub ambiguous: Vec<Vec<Category>>,
+}
+
+/// Merge the categories under the same parent whose names only differ by
+/// case, whitespace or the ` (id)` suffix given to names only differing by
+/// case, and the root categories into the one category with the same name
+/// under a parent, if there is a single one
+///
+/// The category with a parent is kept, or the one with more records, and the
+/// records, recurring payments, merchants, rules and reports of the others are
+/// moved to it before they are deleted, in a single transaction. With
+/// `dry_run`, the merges are only returned.
+pub fn deduplicate(conn: &mut Conn, dry_run: bool) -> Result<Deduplication> {
+    let record_counts = records::table
+        .filter(records::category_id.is_not_null())
+        .group_by(records::category_id)
+        .select((records::category_id, count_star()))
+        .load::<(Option<i64>, i64)>(conn)?
+        .into_iter()
+        .filter_map(|(id, count)| Some((id?, count)))
+        .collect::<HashMap<_, _>>();
+    let record_count = |category: &Category| record_counts.get(&category.id).copied().unwrap_or(0);
+
+    // By name, then by parent, roots first
+    let mut groups = BTreeMap::<String, BTreeMap<Option<i64>, Vec<Category>>>::new();
+    for category in categories::table
+        .select(Category::as_select())
+        .order(categories::id.asc())
+        .load::<Category>(conn)?
+    {
+        groups
+            .entry(normalized_name(&category))
+            .or_default()
+            .entry(category.parent_id)
+            .or_default()
+            .push(category);
+    }
+
+    let mut deduplication = Deduplication::default();
+    for (_, mut by_parent) in groups {
+        let ambiguous = by_parent.len() > 2 && by_parent.contains_key(&None);
+        if by_parent.len() == 2 {
+            // Roots are merged under the only parent
+            if let Some(roots) = by_parent.remove(&None) {
+                if let Some(group) = by_parent.values_mut().next() {
+                    group.extend(roots);
+                }
+            }
+        }
+
+        let mut kept = Vec::new();
+        for (_, mut group) in by_parent {
+            // Prefer a parent, then more records, then the oldest category
+            let index = group
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, c)| (c.parent_id.is_some(), record_count(c), -c.id))
+                .map(|(index, _)| index)
+                .unwrap_or_default();
+            let category = group.remove(index);
+            if ambiguous {
+                kept.push(category.clone());
+            }
+            if !group.is_empty() {
+                deduplication.merges.push(CategoryMerge {
+                    kept: category,
+                    duplicates: group,
+                });
+            }
+        }
+        if ambiguous {
+            deduplication.ambiguous.push(kept);
+        }
+    }
+
+    if !dry_run {
+        conn.transaction(|conn| {
+            for merge in &mut deduplication.merges {
+                for duplicate in &mut merge.duplicates {
+                    merge_into(conn, duplicate, &merge.kept)?;
+                }
+                // The name no longer conflicts with the duplicates, but may
+                // still with categories under other parents
+                let name = plain_name(&merge.kept);
+                let taken = categories::table
+                    .filter(db::nocase(categories::name).eq(&name))
+                    .filter(categories::id.ne(merge.kept.id))
+                    .select(count_star())
+                    .first::<i64>(conn)?
+                    > 0;
+                if name != merge.kept.name && !taken {
+                    diesel::update(&merge.kept)
+                        .set(categories::name.eq(name))
+                        .execute(conn)?;
+                }
+                merge.kept = Category::find(conn, merge.kept.id)?;
+            }
+            Ok::<_, Error>(())
+        })?;
+    }
+
+    Ok(deduplication)
+}
+
+/// Name of the category without the ` (id)` suffix and extra whitespace
+fn plain_name(category: &Category) -> String {
+    let suffix = format!(" ({})", category.id);
+    let name = category
+        .name
+        .strip_suffix(&suffix)
+        .unwrap_or(&category.name);
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalized_name(category: &Category) -> String {
+    plain_name(category).to_lowercase()
+}
+
+/// Move everything referencing `duplicate` to `kept`, then delete it
+fn merge_into(conn: &mut Conn, duplicate: &mut Category, kept: &Category) -> Result<()> {
+    // The stats of the months of the duplicate need to be computed again
+    let months = monthly_category_stats::table
+        .filter(monthly_category_stats::category_id.eq(duplicate.id))
+        .select((
+            monthly_category_stats::year,
+            monthly_category_stats::month,
+            monthly_category_stats::currency,
+        ))
+        .distinct()
+        .load::<(i32, i32, db::Currency)>(conn)?;
+    for (year, month, currency) in months {
+        MonthlyStats::invalidate(conn, year, month, currency.0)?;
+    }
+
+    diesel::update(records::table)
+        .filter(records::category_id.eq(duplicate.id))
+        .set(records::category_id.eq(kept.id))
+        .execute(conn)?;
+    diesel::update(recurring_payments::table)
+        .filter(recurring_payments::category_id.eq(duplicate.id))
+        .set(recurring_payments::category_id.eq(kept.id))
+        .execute(conn)?;
+    diesel::update(merchants::table)
+        .filter(merchants::default_category_id.eq(duplicate.id))
+        .set(merchants::default_category_id.eq(kept.id))
+        .execute(conn)?;
+    diesel::update(category_rules::table)
+        .filter(category_rules::category_id.eq(duplicate.id))
+        .set(category_rules::category_id.eq(kept.id))
+        .execute(conn)?;
+    diesel::update(categories::table)
+        .filter(categories::parent_id.eq(duplicate.id))
+        .filter(categories::id.ne(kept.id))
+        .set(categories::parent_id.eq(kept.id))
+        .execute(conn)?;
+    diesel::update(categories::table)
+        .filter(categories::replaced_by_id.eq(duplicate.id))
+        .filter(categories::id.ne(kept.id))
+        .set(categories::replaced_by_id.eq(kept.id))
+        .execute(conn)?;
+
+    let report_ids = reports_categories::table
+        .filter(reports_categories::category_id.eq(duplicate.id))
+        .select(reports_categories::report_id)
+        .load::<i64>(conn)?;
+    for report_id in report_ids {
+        diesel::insert_or_ignore_into(reports_categories::table)
+            .values((
+                reports_categories::report_id.eq(report_id),
+                reports_categories::category_id.eq(kept.id),
+            ))
+            .execute(conn)?;
+    }
+
+    duplicate.delete(conn)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,4 +327,127 @@ mod tests {
 
         Ok(())
     }
+
+    fn insert_duplicate(
+        conn: &mut Conn,
+        name: &str,
+        parent: Option<&Category>,
+    ) -> Result<Category> {
+        Ok(diesel::insert_into(categories::table)
+            .values((
+                categories::name.eq(name),
+                categories::parent_id.eq(parent.map(|p| p.id)),
+            ))
+            .returning(Category::as_returning())
+            .get_result(conn)?)
+    }
+
+    #[test]
+    fn deduplicate() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+
+        let root = test::category!(conn, "Restaurants");
+        let food = test::category!(conn, "Food");
+        let nested = insert_duplicate(conn, "restaurants  ", Some(&food))?;
+        let suffixed = insert_duplicate(conn, &format!("Groceries ({})", root.id + 3), None)?;
+        let groceries = test::category!(conn, "Groceries");
+        test::category!(conn, "Other");
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let mut record = test::record!(conn, account, operation_date: date, category: Some(&root));
+        test::record!(conn, account, operation_date: date, category: Some(&suffixed));
+        test::record!(conn, account, operation_date: date, category: Some(&suffixed));
+        let mut chariot = test::merchant!(conn, "Chariot", default_category: Some(&root));
+        let mut recpay = test::recpay!(conn, account, category: Some(&root));
+        let mut report = crate::report::Report::create(conn, "Eating out")?;
+        report.add(conn, [&root, &nested])?;
+        MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+
+        let deduplication = super::deduplicate(conn, true)?;
+        let merges = deduplication
+            .merges
+            .iter()
+            .map(|m| (m.kept.id, m.duplicates.iter().map(|d| d.id).collect()))
+            .collect::<Vec<(i64, Vec<i64>)>>();
+        assert_eq!(
+            vec![
+                (suffixed.id, vec![groceries.id]),
+                (nested.id, vec![root.id])
+            ],
+            merges
+        );
+        assert!(deduplication.ambiguous.is_empty());
+        assert!(Category::find(conn, root.id).is_ok());
+
+        super::deduplicate(conn, false)?;
+        assert!(Category::find(conn, root.id).unwrap_err().is_not_found());
+        assert!(Category::find(conn, groceries.id)
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!("Groceries", Category::find(conn, suffixed.id)?.name);
+        assert_eq!("restaurants", Category::find(conn, nested.id)?.name);
+
+        record.reload(conn)?;
+        assert_eq!(Some(nested.id), record.category_id);
+        chariot.reload(conn)?;
+        assert_eq!(Some(nested.id), chariot.default_category_id);
+        recpay.reload(conn)?;
+        assert_eq!(Some(nested.id), recpay.category_id);
+        let report = crate::report::Report::find(conn, report.id)?;
+        assert_eq!(
+            vec![nested.id],
+            report.categories.iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            0i64,
+            monthly_category_stats::table
+                .select(count_star())
+                .first(conn)?
+        );
+
+        assert!(super::deduplicate(conn, false)?.merges.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn deduplicate_ambiguous() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let food = test::category!(conn, "Food");
+        let leisure = test::category!(conn, "Leisure");
+        let bar = test::category!(conn, "Bar");
+        let food_bar = insert_duplicate(conn, "bar ", Some(&food))?;
+        let leisure_bar = insert_duplicate(conn, " BAR", Some(&leisure))?;
+        let duplicate = insert_duplicate(conn, "  bar", Some(&food))?;
+        // Different categories under different parents
+        let snacks = insert_duplicate(conn, "Snacks", Some(&food))?;
+        insert_duplicate(conn, "snacks ", Some(&leisure))?;
+
+        let deduplication = super::deduplicate(conn, false)?;
+        let merges = deduplication
+            .merges
+            .iter()
+            .map(|m| (m.kept.id, m.duplicates.iter().map(|d| d.id).collect()))
+            .collect::<Vec<(i64, Vec<i64>)>>();
+        assert_eq!(vec![(food_bar.id, vec![duplicate.id])], merges);
+        assert_eq!(1, deduplication.ambiguous.len());
+        assert_eq!(
+            vec![bar.id, food_bar.id, leisure_bar.id],
+            deduplication.ambiguous[0]
+                .iter()
+                .map(|c| c.id)
+                .collect::<Vec<_>>()
+        );
+        assert!(Category::find(conn, bar.id).is_ok());
+        assert!(Category::find(conn, duplicate.id)
+            .unwrap_err()
+            .is_not_found());
+        // Still conflicting with the root category
+        assert_eq!("bar ", Category::find(conn, food_bar.id)?.name);
+        assert!(Category::find(conn, snacks.id).is_ok());
+
+        Ok(())
+    }
 }
@@ -100,7 +100,7 @@ mod tests {
     fn consolidate_default_category() -> Result<()> {
         let conn = &mut test::db()?;
 
-        let bar = test::category!(conn, "bar");
+        let bar = test::category!(conn, "Old bar");
         let mut chariot = NewMerchant {
             name: "Chariot",
             default_category: Some(&bar),
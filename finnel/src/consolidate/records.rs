@@ -9,13 +9,19 @@ pub fn consolidate(conn: &mut Conn) -> Result<()> {
     Ok(())
 }
 
-pub fn consolidate_categories(conn: &mut Conn) -> Result<()> {
-    let query = records::table
+/// Records still using a category which has been replaced, along with that
+/// category
+pub fn with_replaced_category(conn: &mut Conn) -> Result<Vec<(Record, Category)>> {
+    Ok(records::table
         .inner_join(categories::table)
         .filter(categories::replaced_by_id.is_not_null())
-        .select((Record::as_select(), Category::as_select()));
+        .select((Record::as_select(), Category::as_select()))
+        .order(records::id.asc())
+        .load::<(Record, Category)>(conn)?)
+}
 
-    for (record, category) in query.load::<(Record, Category)>(conn)? {
+pub fn consolidate_categories(conn: &mut Conn) -> Result<()> {
+    for (record, category) in with_replaced_category(conn)? {
         let category = category.resolve(conn)?;
 
         ChangeRecord {
@@ -83,6 +89,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn with_replaced_category() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+
+        let bar = test::category!(conn, "Bar");
+        let mut public_house = test::category!(conn, "Public House");
+        let record = test::record!(conn, &account, category: Some(&public_house));
+        test::record!(conn, &account, category: Some(&bar));
+        assert!(super::with_replaced_category(conn)?.is_empty());
+
+        crate::category::ChangeCategory {
+            replaced_by: Some(Some(&bar)),
+            ..Default::default()
+        }
+        .apply(conn, &mut public_house)?;
+
+        let records = super::with_replaced_category(conn)?;
+        assert_eq!(1, records.len());
+        assert_eq!(record.id, records[0].0.id);
+        assert_eq!(public_house.id, records[0].1.id);
+
+        super::consolidate_categories(conn)?;
+        assert!(super::with_replaced_category(conn)?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn consolidate_merchants() -> Result<()> {
         let conn = &mut test::db()?;
@@ -66,6 +66,41 @@ impl Month {
     }
 }
 
+/// Offset applied to dates, e.g. to fix records imported with the wrong year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shift {
+    Days(i64),
+    /// Clamped to the end of the month, so that one month after January 31
+    /// is the last day of February
+    Months(i32),
+    Years(i32),
+}
+
+impl Shift {
+    pub fn apply(&self, date: NaiveDate) -> Result<NaiveDate> {
+        let months = |n: i32| match n.is_negative() {
+            true => date.checked_sub_months(Months::new(n.unsigned_abs())),
+            false => date.checked_add_months(Months::new(n as u32)),
+        };
+        let result = match *self {
+            Self::Days(n) => date.checked_add_signed(chrono::TimeDelta::days(n)),
+            Self::Months(n) => months(n),
+            Self::Years(n) => n.checked_mul(12).and_then(months),
+        };
+        result.ok_or_else(|| Error::Invalid(format!("Cannot shift {date} by {self}")))
+    }
+}
+
+impl std::fmt::Display for Shift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Days(n) => write!(f, "{n:+} day(s)"),
+            Self::Months(n) => write!(f, "{n:+} month(s)"),
+            Self::Years(n) => write!(f, "{n:+} year(s)"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +157,31 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn shift() -> Result<()> {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(date(2024, 3, 2), Shift::Days(2).apply(date(2024, 2, 29))?);
+        assert_eq!(date(2024, 2, 27), Shift::Days(-2).apply(date(2024, 2, 29))?);
+        assert_eq!(
+            date(2024, 2, 29),
+            Shift::Months(1).apply(date(2024, 1, 31))?
+        );
+        assert_eq!(
+            date(2024, 2, 29),
+            Shift::Months(-1).apply(date(2024, 3, 31))?
+        );
+        assert_eq!(date(2025, 2, 28), Shift::Years(1).apply(date(2024, 2, 29))?);
+        assert_eq!(
+            date(2023, 8, 10),
+            Shift::Years(-1).apply(date(2024, 8, 10))?
+        );
+
+        assert!(Shift::Years(i32::MAX).apply(date(2024, 1, 1)).is_err());
+        assert_eq!("+1 year(s)", Shift::Years(1).to_string());
+        assert_eq!("-3 day(s)", Shift::Days(-3).to_string());
+
+        Ok(())
+    }
 }
@@ -6,8 +6,8 @@ use diesel::{
     expression::AsExpression,
     prelude::*,
     serialize::{self, IsNull, Output, ToSql},
-    sql_types::{BigInt, Text},
-    sqlite::Sqlite,
+    sql_types::{is_nullable::NotNull, BigInt, Date, Nullable, SingleValue, SqlType, Text},
+    sqlite::{Sqlite, SqliteType},
 };
 
 define_sql_function! {
@@ -20,28 +20,128 @@ define_sql_function! {
     fn total(x: BigInt) -> BigInt;
 }
 
+define_sql_function! {
+    /// Sum of the group, declared as not null since groups can't be empty
+    ///
+    /// Unlike TOTAL, which adds floats, the sum of integers stays exact and
+    /// fails instead of rounding when it overflows
+    #[aggregate]
+    #[sql_name = "SUM"]
+    fn group_sum(x: BigInt) -> BigInt;
+}
+
+define_sql_function! {
+    /// Smallest value of the group, declared as not null since groups can't
+    /// be empty
+    #[aggregate]
+    #[sql_name = "MIN"]
+    fn group_min(x: BigInt) -> BigInt;
+}
+
+define_sql_function! {
+    /// Largest value of the group, not null like `group_min`
+    #[aggregate]
+    #[sql_name = "MAX"]
+    fn group_max(x: BigInt) -> BigInt;
+}
+
+define_sql_function! {
+    /// Number of days since the beginning of the julian period, to compute the
+    /// number of days between two dates
+    fn julianday(x: Date) -> Double;
+}
+
+define_sql_function! {
+    /// Lowercase of the ASCII characters, matching the NOCASE collation
+    fn lower(x: Text) -> Text;
+}
+
+diesel::postfix_operator!(CollateNocase, " COLLATE NOCASE", Text);
+
+/// Text compared ignoring the case of the ASCII characters, like the NOCASE
+/// indexes of the names do, so that comparing it can use them
+pub fn nocase<T: AsExpression<Text>>(text: T) -> CollateNocase<T::Expression> {
+    CollateNocase::new(text.as_expression())
+}
+
+define_sql_function! {
+    /// Date formatted as text, e.g. `%Y-%m` to group by month
+    fn strftime(format: Text, date: Date) -> Text;
+}
+
+define_sql_function! {
+    /// Storage class of the value, e.g. to find amounts stored as text
+    #[sql_name = "typeof"]
+    fn type_of(x: BigInt) -> Text;
+}
+
+/// Stored amount that isn't a number of thousandths, e.g. edited by hand
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+#[display("Invalid stored decimal {_0:?}")]
+pub struct InvalidDecimal(#[error(not(source))] pub String);
+
+/// Amount that can't be stored as a number of thousandths without rounding
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+#[display("{_0} can't be stored exactly, amounts are kept to 3 decimal places")]
+pub struct ImpreciseDecimal(#[error(not(source))] pub oxydized_money::Decimal);
+
+/// Largest number of thousandths read exactly from a float, as returned by
+/// aggregates like TOTAL
+const MAX_EXACT_DOUBLE: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+
+/// Amount stored as an integer number of thousandths, whatever the currency
+///
+/// Nothing is ever rounded: writing an amount with more than 3 decimal
+/// places, or too large to fit, fails instead, and reading anything but an
+/// integer fails with `InvalidDecimal`
 #[derive(Copy, Clone, Debug, derive_more::From, derive_more::Into, FromSqlRow, AsExpression)]
 #[diesel(sql_type = BigInt)]
 pub struct Decimal(pub oxydized_money::Decimal);
 
-impl ToSql<BigInt, Sqlite> for Decimal {
-    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
-        let mut value = self.0;
+impl Decimal {
+    /// Number of thousandths stored for the amount, if it can be stored
+    /// exactly
+    pub fn thousandths(&self) -> Result<i64, ImpreciseDecimal> {
+        let mut value = self.0.normalize();
+        if value.scale() > 3 {
+            return Err(ImpreciseDecimal(self.0));
+        }
         value.rescale(3);
+        value
+            .mantissa()
+            .try_into()
+            .map_err(|_| ImpreciseDecimal(self.0))
+    }
+}
 
-        match TryInto::<i64>::try_into(value.mantissa()) {
-            Ok(value) => {
-                out.set_value(value);
-                Ok(IsNull::No)
-            }
-            Err(e) => Err(Box::new(e)),
-        }
+impl ToSql<BigInt, Sqlite> for Decimal {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.thousandths()?);
+        Ok(IsNull::No)
     }
 }
 
 impl FromSql<BigInt, Sqlite> for Decimal {
     fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
-        Ok(oxydized_money::Decimal::new(i64::from_sql(bytes)?, 3).into())
+        // SQLite would silently read the leading digits of a text value
+        match bytes.value_type() {
+            Some(SqliteType::Long) => {
+                Ok(oxydized_money::Decimal::new(i64::from_sql(bytes)?, 3).into())
+            }
+            // Aggregates like TOTAL return a float
+            Some(SqliteType::Double) => {
+                let value = f64::from_sql(bytes)?;
+                if value.fract() == 0.0 && value.abs() < MAX_EXACT_DOUBLE {
+                    Ok(oxydized_money::Decimal::new(value as i64, 3).into())
+                } else {
+                    Err(Box::new(InvalidDecimal(value.to_string())))
+                }
+            }
+            _ => {
+                let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+                Err(Box::new(InvalidDecimal(value)))
+            }
+        }
     }
 }
 
@@ -76,3 +176,177 @@ impl FromSql<Text, Sqlite> for Currency {
         }
     }
 }
+
+/// Nullable column read through one of the types above, e.g.
+/// `Optional<Decimal>` for a nullable amount
+#[derive(Copy, Clone, Debug)]
+pub struct Optional<T>(pub Option<T>);
+
+impl<T, ST> Queryable<Nullable<ST>, Sqlite> for Optional<T>
+where
+    T: FromSql<ST, Sqlite>,
+    ST: SqlType<IsNull = NotNull> + SingleValue,
+{
+    type Row = Option<T>;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        Ok(Optional(row))
+    }
+}
+
+impl From<Optional<Decimal>> for Option<oxydized_money::Decimal> {
+    fn from(value: Optional<Decimal>) -> Self {
+        value.0.map(|decimal| decimal.0)
+    }
+}
+
+impl From<Optional<Currency>> for Option<oxydized_money::Currency> {
+    fn from(value: Optional<Currency>) -> Self {
+        value.0.map(|currency| currency.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImpreciseDecimal;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use crate::{
+        db,
+        record::{Direction, NewRecord, Record},
+        schema::records,
+    };
+
+    use diesel::sql_types::BigInt;
+    use diesel::{dsl::sql, prelude::*};
+
+    /// Amounts of every scale that can be stored, from a fixed seed so
+    /// failures can be reproduced
+    fn amounts() -> Vec<Decimal> {
+        let mut amounts = vec![
+            Decimal::ZERO,
+            Decimal::new(1, 3),
+            Decimal::new(-1, 3),
+            Decimal::new(5, 1),
+            Decimal::new(105, 2),
+            Decimal::new(123_000, 5),
+            Decimal::new(i64::MAX, 3),
+            Decimal::new(i64::MIN, 3),
+        ];
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for i in 0..1000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mantissa = (state % 20_000_000_000) as i64 - 10_000_000_000;
+            amounts.push(Decimal::new(mantissa, i % 4));
+        }
+        amounts
+    }
+
+    fn round_trip(conn: &mut Conn, amount: Decimal) -> Result<Decimal> {
+        Ok(diesel::select(db::Decimal(amount).into_sql::<BigInt>())
+            .get_result::<db::Decimal>(conn)?
+            .0)
+    }
+
+    #[test]
+    fn exact_round_trip() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+
+        for amount in amounts() {
+            assert_eq!(amount, round_trip(conn, amount)?);
+        }
+
+        // The opposite of i64::MIN thousandths can't be stored
+        for amount in amounts()
+            .into_iter()
+            .skip(1)
+            .take(100)
+            .filter(|a| a.abs() < Decimal::new(i64::MAX, 3))
+        {
+            let record = test::record!(conn, &account, amount: amount.abs());
+            assert_eq!(amount.abs(), Record::find(conn, record.id)?.amount);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn imprecise() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+
+        for amount in [
+            Decimal::new(1, 4),
+            Decimal::new(12345, 4),
+            Decimal::new(i64::MAX, 3) + Decimal::new(1, 3),
+        ] {
+            let error = round_trip(conn, amount).unwrap_err();
+            assert!(error.to_string().contains(&amount.to_string()));
+            assert!(db::Decimal(amount).thousandths().is_err());
+        }
+
+        let mut record = NewRecord::new(&account);
+        record.amount = Decimal::new(10005, 4);
+        assert!(record.save(conn).is_err());
+        assert_eq!(
+            0,
+            records::table.count().get_result::<i64>(conn)?,
+            "nothing stored rounded"
+        );
+        assert_eq!(
+            "1.0005 can't be stored exactly, amounts are kept to 3 decimal places",
+            ImpreciseDecimal(Decimal::new(10005, 4)).to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let read = |conn: &mut Conn, value: &str| {
+            diesel::select(sql::<BigInt>(value)).get_result::<db::Decimal>(conn)
+        };
+        assert_eq!(Decimal::new(12, 0), read(conn, "12000")?.0);
+        assert_eq!(Decimal::new(12, 0), read(conn, "12000.0")?.0);
+        assert_eq!(Decimal::new(-1, 3), read(conn, "-1.0")?.0);
+
+        for value in ["12.5", "'12000'", "9007199254740992.0"] {
+            let error = read(conn, value).unwrap_err();
+            assert!(
+                error.to_string().contains("Invalid stored decimal"),
+                "{value} read as {error}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn total() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+
+        let mut expected = Decimal::ZERO;
+        for amount in amounts().into_iter().skip(8).take(200) {
+            let direction = if amount.is_sign_negative() {
+                Direction::Debit
+            } else {
+                Direction::Credit
+            };
+            test::record!(conn, &account, amount: amount.abs(), direction: direction);
+            expected += amount;
+        }
+
+        assert_eq!(
+            expected,
+            account.balance_at(conn, chrono::Utc::now().date_naive())?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{category::Category, essentials::*, merchant::Merchant};
+
+/// Field changed from an old value to a new one, both as displayed and
+/// `None` when there is no value
+pub type FieldDiff = (&'static str, Option<String>, Option<String>);
+
+/// Add the change of the field, unless the value stays the same
+pub(crate) fn push<T: ToString + PartialEq>(
+    diff: &mut Vec<FieldDiff>,
+    field: &'static str,
+    old: Option<T>,
+    new: Option<T>,
+) {
+    if old != new {
+        diff.push((
+            field,
+            old.map(|v| v.to_string()),
+            new.map(|v| v.to_string()),
+        ));
+    }
+}
+
+/// Name of the category, to show it instead of its id
+pub(crate) fn category_name(conn: &mut Conn, id: Option<i64>) -> Result<Option<String>> {
+    id.map(|id| Category::find(conn, id).map(|category| category.name))
+        .transpose()
+}
+
+/// Name of the merchant, to show it instead of its id
+pub(crate) fn merchant_name(conn: &mut Conn, id: Option<i64>) -> Result<Option<String>> {
+    id.map(|id| Merchant::find(conn, id).map(|merchant| merchant.name))
+        .transpose()
+}
@@ -0,0 +1,110 @@
+use crate::{essentials::*, schema::imports};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// File imported with a profile, identified by the hash of its content so
+/// that importing it again can be detected
+#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = imports)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Import {
+    pub id: i64,
+    pub profile: String,
+    /// SHA-256 of the file, in lowercase hexadecimal
+    pub hash: String,
+    pub imported_at: NaiveDateTime,
+    /// Number of records created by the import
+    pub count: i64,
+}
+
+impl Import {
+    /// Latest import of the file with this hash using the profile
+    pub fn find_by_hash(conn: &mut Conn, profile: &str, hash: &str) -> Result<Self> {
+        imports::table
+            .filter(imports::profile.eq(profile))
+            .filter(imports::hash.eq(hash))
+            .order(imports::imported_at.desc())
+            .select(Import::as_select())
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Import", Some("hash")))
+    }
+
+    /// Imports using the profile, most recent first
+    pub fn of_profile(conn: &mut Conn, profile: &str) -> Result<Vec<Self>> {
+        Ok(imports::table
+            .filter(imports::profile.eq(profile))
+            .order((imports::imported_at.desc(), imports::id.desc()))
+            .select(Import::as_select())
+            .load(conn)?)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = imports)]
+pub struct NewImport<'a> {
+    pub profile: &'a str,
+    pub hash: &'a str,
+    pub imported_at: NaiveDateTime,
+    pub count: i64,
+}
+
+impl<'a> NewImport<'a> {
+    pub fn new(profile: &'a str, hash: &'a str) -> Self {
+        Self {
+            profile,
+            hash,
+            imported_at: chrono::Utc::now().naive_utc(),
+            count: 0,
+        }
+    }
+
+    pub fn save(self, conn: &mut Conn) -> Result<Import> {
+        Ok(diesel::insert_into(imports::table)
+            .values(self)
+            .returning(Import::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn find_by_hash() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        assert!(Import::find_by_hash(conn, "boursobank", "abc")
+            .unwrap_err()
+            .is_not_found());
+
+        let first = NewImport {
+            count: 3,
+            ..NewImport::new("boursobank", "abc")
+        }
+        .save(conn)?;
+        NewImport::new("logseq", "abc").save(conn)?;
+        let second = NewImport {
+            imported_at: first.imported_at + chrono::Days::new(1),
+            ..NewImport::new("boursobank", "abc")
+        }
+        .save(conn)?;
+
+        assert_eq!(
+            second.id,
+            Import::find_by_hash(conn, "boursobank", "abc")?.id
+        );
+        assert!(Import::find_by_hash(conn, "boursobank", "def").is_err());
+
+        let imports = Import::of_profile(conn, "boursobank")?;
+        assert_eq!(
+            vec![second.id, first.id],
+            imports.iter().map(|i| i.id).collect::<Vec<_>>()
+        );
+        assert_eq!(3, imports[1].count);
+
+        Ok(())
+    }
+}
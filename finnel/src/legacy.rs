@@ -0,0 +1,494 @@
+//! Reading of the databases of the versions of finnel before diesel, to
+//! recreate their content in the current one
+//!
+//! These databases have a `finnel` key-value table instead of the migrations
+//! one, and the tables read here:
+//!
+//! - `accounts (id, name, balance, currency)`, the balance being the current
+//!   one as text
+//! - `categories (id, name)`
+//! - `merchants (id, name, default_category)`
+//! - `records (id, account, amount, operation_date, value_date, direction,
+//!   mode, details, category, merchant)`, the dates being date times as text
+
+use crate::{
+    account::NewAccount, category::NewCategory, merchant::NewMerchant, parse, prelude::*,
+    record::NewRecord,
+};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use diesel::sql_types::{BigInt, Nullable, Text};
+
+#[derive(QueryableByName)]
+struct LegacyAccount {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    balance: String,
+    #[diesel(sql_type = Text)]
+    currency: String,
+}
+
+#[derive(QueryableByName)]
+struct LegacyCategory {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct LegacyMerchant {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    default_category: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct LegacyRecord {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+    #[diesel(sql_type = BigInt)]
+    account: i64,
+    #[diesel(sql_type = Text)]
+    amount: String,
+    #[diesel(sql_type = Text)]
+    operation_date: String,
+    #[diesel(sql_type = Text)]
+    value_date: String,
+    #[diesel(sql_type = Text)]
+    direction: String,
+    #[diesel(sql_type = Text)]
+    mode: String,
+    #[diesel(sql_type = Text)]
+    details: String,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    category: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    merchant: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct TableCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Database of a version of finnel before diesel, opened read-only
+pub struct LegacyDatabase(Conn);
+
+impl LegacyDatabase {
+    /// Open the file without ever writing to it, failing unless it is a
+    /// legacy database
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::Invalid(format!("{} is not a file", path.display())));
+        }
+
+        // Characters with a meaning in URIs, escaped as the path is given as is
+        let path = path
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace('?', "%3f")
+            .replace('#', "%23");
+        let mut conn = Conn::establish(&format!("file:{path}?mode=ro"))?;
+        diesel::sql_query("PRAGMA query_only = ON").execute(&mut conn)?;
+
+        Self::check(conn)
+    }
+
+    fn check(mut conn: Conn) -> Result<Self> {
+        let mut has_table = |name: &str| -> Result<bool> {
+            Ok(diesel::sql_query(
+                "SELECT count(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?",
+            )
+            .bind::<Text, _>(name)
+            .get_result::<TableCount>(&mut conn)?
+            .count
+                > 0)
+        };
+
+        if has_table("__diesel_schema_migrations")? {
+            return Err(Error::Invalid(
+                "Database is already in the current format".to_owned(),
+            ));
+        }
+        if !has_table("finnel")? {
+            return Err(Error::Invalid("Not a legacy finnel database".to_owned()));
+        }
+
+        Ok(Self(conn))
+    }
+
+    fn accounts(&mut self) -> Result<Vec<LegacyAccount>> {
+        Ok(
+            diesel::sql_query("SELECT id, name, balance, currency FROM accounts ORDER BY id")
+                .load(&mut self.0)?,
+        )
+    }
+
+    fn categories(&mut self) -> Result<Vec<LegacyCategory>> {
+        Ok(diesel::sql_query("SELECT id, name FROM categories ORDER BY id").load(&mut self.0)?)
+    }
+
+    fn merchants(&mut self) -> Result<Vec<LegacyMerchant>> {
+        Ok(
+            diesel::sql_query("SELECT id, name, default_category FROM merchants ORDER BY id")
+                .load(&mut self.0)?,
+        )
+    }
+
+    fn records(&mut self) -> Result<Vec<LegacyRecord>> {
+        Ok(diesel::sql_query(
+            "SELECT id, account, amount, operation_date, value_date, direction, mode, details,
+                category, merchant
+             FROM records ORDER BY id",
+        )
+        .load(&mut self.0)?)
+    }
+}
+
+/// What was recreated from a legacy database
+#[derive(Debug, Default)]
+pub struct Migration {
+    pub accounts: usize,
+    pub categories: usize,
+    pub merchants: usize,
+    pub records: Vec<Record>,
+    /// What couldn't be mapped, e.g. an unknown mode or a missing category,
+    /// one message for each
+    pub issues: Vec<String>,
+    /// Why each record left out couldn't be recreated, e.g. a missing
+    /// account
+    pub rejected: Vec<String>,
+}
+
+/// Recreate the accounts, categories, merchants and records of the legacy
+/// database in the current one
+///
+/// The accounts, categories and merchants already existing with the same
+/// name are used as they are. The records which can't be mapped are left out
+/// and reported in the rejected ones, their references to missing categories
+/// or merchants dropped and reported in the issues.
+pub fn migrate(conn: &mut Conn, legacy: &mut LegacyDatabase) -> Result<Migration> {
+    let mut migration = Migration::default();
+
+    let mut categories = HashMap::new();
+    for category in legacy.categories()? {
+        let found = match Category::find_by_name(conn, &category.name) {
+            Err(e) if e.is_not_found() => {
+                let saved = NewCategory::new(&category.name).save(conn);
+                migration.categories += saved.is_ok() as usize;
+                saved
+            }
+            found => found,
+        };
+        match found {
+            Ok(found) => {
+                categories.insert(category.id, found);
+            }
+            Err(e) => migration
+                .issues
+                .push(format!("Category {}: {e}", category.id)),
+        }
+    }
+
+    let mut merchants = HashMap::new();
+    for merchant in legacy.merchants()? {
+        let default_category = match merchant.default_category {
+            Some(id) if !categories.contains_key(&id) => {
+                migration.issues.push(format!(
+                    "Merchant {}: default category {id} not found",
+                    merchant.id
+                ));
+                None
+            }
+            id => id.map(|id| &categories[&id]),
+        };
+        let found = match Merchant::find_by_name(conn, &merchant.name) {
+            Err(e) if e.is_not_found() => {
+                let saved = NewMerchant {
+                    default_category,
+                    ..NewMerchant::new(&merchant.name)
+                }
+                .save(conn);
+                migration.merchants += saved.is_ok() as usize;
+                saved
+            }
+            found => found,
+        };
+        match found {
+            Ok(found) => {
+                merchants.insert(merchant.id, found);
+            }
+            Err(e) => migration
+                .issues
+                .push(format!("Merchant {}: {e}", merchant.id)),
+        }
+    }
+
+    let mut records = HashMap::<i64, Vec<LegacyRecord>>::new();
+    for record in legacy.records()? {
+        records.entry(record.account).or_default().push(record);
+    }
+
+    for account in legacy.accounts()? {
+        let records = records.remove(&account.id).unwrap_or_default();
+        let account = match create_account(conn, &account, &records) {
+            Ok(Some(account)) => {
+                migration.accounts += 1;
+                account
+            }
+            Ok(None) => Account::find_by_name(conn, &account.name)?,
+            Err(e) => {
+                migration
+                    .issues
+                    .push(format!("Account {}: {e}", account.id));
+                migration.rejected.extend(records.iter().map(|record| {
+                    format!("Record {}: account {} not created", record.id, account.id)
+                }));
+                continue;
+            }
+        };
+
+        for record in records {
+            let mut record_issues = Vec::new();
+            let mode = record.mode.parse::<Mode>().unwrap_or_else(|_| {
+                record_issues.push(format!(
+                    "unknown mode {:?}, imported as Direct",
+                    record.mode
+                ));
+                Mode::default()
+            });
+            let category = record.category.and_then(|id| {
+                let category = categories.get(&id);
+                if category.is_none() {
+                    record_issues.push(format!("category {id} not found"));
+                }
+                category
+            });
+            let merchant = record.merchant.and_then(|id| {
+                let merchant = merchants.get(&id);
+                if merchant.is_none() {
+                    record_issues.push(format!("merchant {id} not found"));
+                }
+                merchant
+            });
+
+            let saved = signed_amount(&record).and_then(|(direction, amount)| {
+                NewRecord {
+                    amount,
+                    direction,
+                    mode,
+                    operation_date: legacy_date(&record.operation_date)?,
+                    value_date: legacy_date(&record.value_date)?,
+                    details: &record.details,
+                    category,
+                    merchant,
+                    allow_inverted_dates: true,
+                    ..NewRecord::new(&account)
+                }
+                .save(conn)
+            });
+            match saved {
+                Ok(saved) => migration.records.push(saved),
+                Err(e) => migration
+                    .rejected
+                    .push(format!("Record {}: {e}", record.id)),
+            }
+
+            migration.issues.extend(
+                record_issues
+                    .into_iter()
+                    .map(|issue| format!("Record {}: {issue}", record.id)),
+            );
+        }
+    }
+
+    for (account, records) in records {
+        for record in records {
+            migration
+                .rejected
+                .push(format!("Record {}: account {account} not found", record.id));
+        }
+    }
+
+    Ok(migration)
+}
+
+/// Create the account, unless one already has its name, with the balance
+/// before its records as the legacy one includes them
+fn create_account(
+    conn: &mut Conn,
+    account: &LegacyAccount,
+    records: &[LegacyRecord],
+) -> Result<Option<Account>> {
+    if Account::find_by_name(conn, &account.name)
+        .optional()?
+        .is_some()
+    {
+        return Ok(None);
+    }
+
+    let currency = Currency::from_code(&account.currency)
+        .ok_or_else(|| Error::Invalid(format!("Unknown currency {:?}", account.currency)))?;
+    let balance = records
+        .iter()
+        .filter_map(|record| signed_amount(record).ok())
+        .fold(
+            parse::decimal(&account.balance)?,
+            |balance, (direction, amount)| match direction {
+                Direction::Debit => balance + amount,
+                Direction::Credit => balance - amount,
+            },
+        );
+
+    NewAccount {
+        balance,
+        currency,
+        ..NewAccount::new(&account.name)
+    }
+    .save(conn)
+    .map(Some)
+}
+
+fn signed_amount(record: &LegacyRecord) -> Result<(Direction, Decimal)> {
+    let direction = record
+        .direction
+        .parse::<Direction>()
+        .map_err(|_| Error::Parse(format!("unknown direction {:?}", record.direction)))?;
+    Ok((direction, parse::decimal(&record.amount)?))
+}
+
+/// Date part of a date time written as text, e.g. `2024-07-01 10:00:00+00:00`
+/// or `2024-07-01T10:00:00Z`
+fn legacy_date(date: &str) -> Result<NaiveDate> {
+    parse::date(date.get(0..10).unwrap_or(date), "%Y-%m-%d")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use diesel::connection::SimpleConnection;
+
+    const SCHEMA: &str = "
+        CREATE TABLE finnel (key TEXT PRIMARY KEY, value TEXT);
+        CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT, balance TEXT, currency TEXT);
+        CREATE TABLE categories (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE merchants (id INTEGER PRIMARY KEY, name TEXT, default_category INTEGER);
+        CREATE TABLE records (
+            id INTEGER PRIMARY KEY, account INTEGER, amount TEXT, operation_date TEXT,
+            value_date TEXT, direction TEXT, mode TEXT, details TEXT, category INTEGER,
+            merchant INTEGER
+        );";
+
+    fn legacy(data: &str) -> Result<LegacyDatabase> {
+        let mut conn = Conn::establish(":memory:")?;
+        conn.batch_execute(SCHEMA)?;
+        conn.batch_execute(data)?;
+        Ok(LegacyDatabase::check(conn)?)
+    }
+
+    #[test]
+    fn migrate() -> Result<()> {
+        let conn = &mut test::db()?;
+        let existing = test::category!(conn, "Bars");
+
+        let legacy = &mut legacy(
+            "
+            INSERT INTO accounts VALUES (1, 'Checking', '90.5', 'EUR'), (2, 'Cash', '0', 'XYZ');
+            INSERT INTO categories VALUES (1, 'bars'), (2, 'Groceries');
+            INSERT INTO merchants VALUES (1, 'Chariot', 1), (2, 'Market', 9);
+            INSERT INTO records VALUES
+                (1, 1, '12.5', '2024-07-01 10:00:00+00:00', '2024-07-02T00:00:00Z', 'Debit',
+                 'Card *1234', 'Beer', NULL, 1),
+                (2, 1, '3', '2024-07-03 10:00:00+00:00', '2024-07-03 10:00:00+00:00', 'Credit',
+                 'Cheque', 'Refund', 2, 7),
+                (3, 1, '1', '2024-07-04', '2024-07-04', 'Sideways', 'Direct', 'Odd', NULL, NULL),
+                (4, 3, '1', '2024-07-04', '2024-07-04', 'Debit', 'Direct', 'Lost', NULL, NULL),
+                (5, 2, '1', '2024-07-04', '2024-07-04', 'Debit', 'Direct', 'Cash', NULL, NULL);",
+        )?;
+
+        let migration = super::migrate(conn, legacy)?;
+        assert_eq!(1, migration.accounts);
+        assert_eq!(1, migration.categories);
+        assert_eq!(2, migration.merchants);
+        assert_eq!(
+            vec![
+                "Merchant 2: default category 9 not found",
+                "Record 2: unknown mode \"Cheque\", imported as Direct",
+                "Record 2: merchant 7 not found",
+                "Account 2: Invalid. Unknown currency \"XYZ\"",
+            ],
+            migration.issues
+        );
+        assert_eq!(
+            vec![
+                "Record 3: unknown direction \"Sideways\"",
+                "Record 5: account 2 not created",
+                "Record 4: account 3 not found",
+            ],
+            migration.rejected
+        );
+
+        let checking = Account::find_by_name(conn, "Checking")?;
+        // Balance before the records, so that it adds up to the legacy one
+        assert_eq!(Decimal::new(1000, 1), checking.balance);
+        assert_eq!(
+            Decimal::new(905, 1),
+            checking.balance_at(conn, NaiveDate::from_ymd_opt(2024, 8, 1).unwrap())?
+        );
+
+        let [beer, refund] = &migration.records[..] else {
+            panic!("Expected 2 records, got {:?}", migration.records);
+        };
+        assert_eq!(Some(checking.id), beer.account_id);
+        assert_eq!(Direction::Debit, beer.direction);
+        assert_eq!(
+            Mode::Direct(PaymentMethod::CardLast4Digit('1', '2', '3', '4')),
+            beer.mode
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 7, 1),
+            Some(beer.operation_date)
+        );
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 7, 2), Some(beer.value_date));
+        assert_eq!(Some(existing.id), beer.category_id);
+        let chariot = Merchant::find_by_name(conn, "Chariot")?;
+        assert_eq!(Some(chariot.id), beer.merchant_id);
+        assert_eq!(Some(existing.id), chariot.default_category_id);
+
+        assert_eq!(Mode::default(), refund.mode);
+        assert_eq!(
+            Some(Category::find_by_name(conn, "Groceries")?.id),
+            refund.category_id
+        );
+        assert_eq!(None, refund.merchant_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check() -> Result<()> {
+        let mut conn = Conn::establish(":memory:")?;
+        conn.batch_execute("CREATE TABLE accounts (id INTEGER PRIMARY KEY)")?;
+        assert!(LegacyDatabase::check(conn).is_err());
+
+        let mut conn = Conn::establish(":memory:")?;
+        conn.batch_execute(SCHEMA)?;
+        conn.batch_execute("CREATE TABLE __diesel_schema_migrations (version TEXT)")?;
+        assert!(LegacyDatabase::check(conn).is_err());
+
+        Ok(())
+    }
+}
@@ -7,18 +7,36 @@ pub mod resolved;
 pub mod result;
 
 pub mod account;
+pub mod api;
 pub mod category;
+pub mod compare;
 pub mod consolidate;
 pub mod date;
+pub mod diff;
+pub mod import;
+pub mod legacy;
 pub mod merchant;
+pub mod name;
+pub mod order;
+pub mod parse;
 pub mod record;
 pub mod recurring_payment;
 pub mod report;
 pub mod stats;
+pub mod tag;
 
 pub mod schema;
 use diesel::prelude::*;
 
+/// Version of the crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How long to wait for another connection to release its lock on the
+/// database before failing with `Error::DatabaseBusy`
+pub const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+include!(concat!(env!("OUT_DIR"), "/features.rs"));
+
 pub mod essentials {
     pub use crate::{
         db,
@@ -36,7 +54,7 @@ pub mod prelude {
     pub use crate::essentials::{OptionalExtension, *};
 
     pub use crate::{
-        account::Account,
+        account::{Account, AccountGroup},
         category::Category,
         consolidate::consolidate,
         date,
@@ -45,6 +63,7 @@ pub mod prelude {
         recurring_payment::{Frequency, RecurringPayment},
         report::Report,
         stats,
+        tag::Tag,
     };
 
     pub use super::Database;
@@ -56,19 +75,419 @@ pub struct Database(SqliteConnection);
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
+/// Migration moving the records of deleted accounts to recovery accounts
+const FOREIGN_KEYS_MIGRATION: &str = "20240925063027";
+
 impl Database {
     pub fn open<T: AsRef<std::path::Path>>(path: T) -> Result<Self> {
-        Ok(Database(SqliteConnection::establish(
-            &path.as_ref().to_string_lossy(),
-        )?))
+        Self::establish(&path.as_ref().to_string_lossy())
+    }
+
+    /// Open the database at `path` so that nothing can be written to it
+    pub fn open_readonly<T: AsRef<std::path::Path>>(path: T) -> Result<Self> {
+        // Opened as a URI, where these characters would be taken for the
+        // query, the fragment or an escape
+        let path = path
+            .as_ref()
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace('?', "%3f")
+            .replace('#', "%23");
+        Self::establish(&format!("file:{path}?mode=ro"))
+    }
+
+    fn establish(url: &str) -> Result<Self> {
+        let mut db = Database(SqliteConnection::establish(url)?);
+        db.set_busy_timeout(BUSY_TIMEOUT)?;
+        db.set_foreign_keys(true)?;
+        Ok(db)
+    }
+
+    /// Wait up to `timeout` for locks held by other connections
+    pub fn set_busy_timeout(&mut self, timeout: std::time::Duration) -> Result<()> {
+        diesel::sql_query(format!("PRAGMA busy_timeout = {}", timeout.as_millis()))
+            .execute(&mut self.0)?;
+        Ok(())
+    }
+
+    /// Enforce the references between tables, which SQLite doesn't by
+    /// default, nor allows changing within a transaction
+    fn set_foreign_keys(&mut self, enabled: bool) -> Result<()> {
+        let value = if enabled { "ON" } else { "OFF" };
+        diesel::sql_query(format!("PRAGMA foreign_keys = {value}")).execute(&mut self.0)?;
+        Ok(())
     }
 
     pub fn memory() -> Result<Self> {
         Self::open(":memory:")
     }
 
+    /// Run the pending migrations and record the version of the crate in the
+    /// database
+    ///
+    /// Fails if the database was used by a newer version of the crate, unless
+    /// the difference is only in the patch version
     pub fn setup(&mut self) -> Result<()> {
-        self.run_pending_migrations(MIGRATIONS)?;
+        let binary = Self::binary_version()?;
+        let db = self.check_version(&binary)?;
+
+        // Migrations rebuilding a table drop the old one, which isn't allowed
+        // while others reference it
+        self.set_foreign_keys(false)?;
+        let migrated = self
+            .run_pending_migrations(MIGRATIONS)
+            .map(|versions| {
+                versions
+                    .iter()
+                    .any(|v| v.to_string() == FOREIGN_KEYS_MIGRATION)
+            })
+            .map_err(migration_error);
+        self.set_foreign_keys(true)?;
+        if migrated? {
+            self.warn_recovered_accounts()?;
+        }
+
+        let version = db.filter(|db| *db > binary).unwrap_or(binary);
+        self.set_version(&version)?;
+
+        Ok(())
+    }
+
+    /// Check that the database can be used as it is, without running the
+    /// pending migrations or recording the version like `setup` does
+    ///
+    /// Fails if the database was used by a newer version of the crate, or if
+    /// some migrations are pending
+    pub fn check(&mut self) -> Result<()> {
+        self.check_version(&Self::binary_version()?)?;
+
+        if self
+            .has_pending_migration(MIGRATIONS)
+            .map_err(migration_error)?
+        {
+            return Err(Error::PendingMigrations);
+        }
+
+        Ok(())
+    }
+
+    fn check_version(&mut self, binary: &semver::Version) -> Result<Option<semver::Version>> {
+        let db = self.version()?;
+
+        if let Some(db) = &db {
+            if (db.major, db.minor) > (binary.major, binary.minor) {
+                return Err(Error::DatabaseFromFuture {
+                    db: db.clone(),
+                    binary: binary.clone(),
+                });
+            }
+        }
+
+        Ok(db)
+    }
+
+    fn binary_version() -> Result<semver::Version> {
+        Ok(VERSION.parse()?)
+    }
+
+    /// Version of the crate stored by the last call to `setup`, if any
+    pub fn version(&mut self) -> Result<Option<semver::Version>> {
+        use diesel::{dsl::sql, sql_types::Bool, OptionalExtension};
+        use schema::metadata;
+
+        let has_metadata = diesel::select(sql::<Bool>(
+            "EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'metadata')",
+        ))
+        .get_result::<bool>(&mut self.0)?;
+        if !has_metadata {
+            return Ok(None);
+        }
+
+        metadata::table
+            .find("version")
+            .select(metadata::value)
+            .first::<String>(&mut self.0)
+            .optional()?
+            .map(|version| version.parse().map_err(Error::from))
+            .transpose()
+    }
+
+    /// Version of the SQLite library in use
+    pub fn sqlite_version(&mut self) -> Result<String> {
+        use diesel::{dsl::sql, sql_types::Text};
+
+        Ok(diesel::select(sql::<Text>("sqlite_version()")).get_result(&mut self.0)?)
+    }
+
+    /// Versions of the migrations applied to the database
+    pub fn migrations(&mut self) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .applied_migrations()?
+            .into_iter()
+            .map(|version| version.to_string())
+            .collect())
+    }
+
+    /// Warn about the accounts created by the foreign keys migration for the
+    /// records of the accounts deleted before
+    fn warn_recovered_accounts(&mut self) -> Result<()> {
+        use schema::accounts;
+
+        let names = accounts::table
+            .filter(accounts::name.like("Recovered records (%)"))
+            .select(accounts::name)
+            .load::<String>(&mut self.0)?;
+        for name in names {
+            log::warn!("Records of deleted accounts were moved to the account {name}");
+        }
+        Ok(())
+    }
+
+    fn set_version(&mut self, version: &semver::Version) -> Result<()> {
+        use schema::metadata;
+
+        diesel::replace_into(metadata::table)
+            .values((
+                metadata::key.eq("version"),
+                metadata::value.eq(version.to_string()),
+            ))
+            .execute(&mut self.0)?;
+
+        Ok(())
+    }
+}
+
+/// Keep the diesel errors, like a locked database, recognizable
+fn migration_error(error: Box<dyn std::error::Error + Send + Sync>) -> Error {
+    match error.downcast::<diesel::result::Error>() {
+        Ok(error) => Error::from(*error),
+        Err(error) => Error::from(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn setup_version() -> Result<()> {
+        let db = &mut Database::memory()?;
+        assert_eq!(None, db.version()?);
+
+        db.setup()?;
+        let binary = Database::binary_version()?;
+        assert_eq!(Some(&binary), db.version()?.as_ref());
+
+        // A newer patch version is allowed and kept
+        let mut patch = binary.clone();
+        patch.patch += 1;
+        db.set_version(&patch)?;
+        db.setup()?;
+        assert_eq!(Some(&patch), db.version()?.as_ref());
+
+        let mut minor = binary.clone();
+        minor.minor += 1;
+        db.set_version(&minor)?;
+        assert!(matches!(
+            db.setup(),
+            Err(Error::DatabaseFromFuture { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check() -> Result<()> {
+        let db = &mut Database::memory()?;
+        assert!(matches!(db.check(), Err(Error::PendingMigrations)));
+
+        db.setup()?;
+        db.check()?;
+
+        db.revert_last_migration(MIGRATIONS)
+            .map_err(migration_error)?;
+        assert!(matches!(db.check(), Err(Error::PendingMigrations)));
+
+        let mut minor = Database::binary_version()?;
+        minor.minor += 1;
+        db.set_version(&minor)?;
+        assert!(matches!(db.check(), Err(Error::DatabaseFromFuture { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn busy() -> Result<()> {
+        use std::time::{Duration, Instant};
+
+        let path = std::env::temp_dir().join(format!("finnel-busy-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let result = (|| {
+            let db1 = &mut Database::open(&path)?;
+            db1.setup()?;
+            let db2 = &mut Database::open(&path)?;
+            db2.set_busy_timeout(Duration::from_millis(100))?;
+
+            diesel::sql_query("BEGIN IMMEDIATE").execute(&mut db1.0)?;
+
+            // Reading is still possible
+            assert!(db2.version()?.is_some());
+
+            let start = Instant::now();
+            assert!(matches!(db2.setup(), Err(Error::DatabaseBusy)));
+            assert!(start.elapsed() >= Duration::from_millis(100));
+
+            diesel::sql_query("COMMIT").execute(&mut db1.0)?;
+            db2.setup()
+        })();
+
+        let _ = std::fs::remove_file(&path);
+        Ok(result?)
+    }
+
+    #[test]
+    fn open_readonly() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let path = dir.path().join("db?#%20.finnel");
+        Database::open(&path)?.setup()?;
+
+        let db = &mut Database::open_readonly(&path)?;
+        db.check()?;
+        assert!(db.setup().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn introspection() -> Result<()> {
+        use diesel::migration::MigrationSource;
+
+        let db = &mut Database::memory()?;
+        assert!(db.migrations()?.is_empty());
+        assert!(db.sqlite_version()?.starts_with("3."));
+
+        db.setup()?;
+        assert_eq!(
+            MigrationSource::<diesel::sqlite::Sqlite>::migrations(&MIGRATIONS)
+                .map_err(Error::from)?
+                .len(),
+            db.migrations()?.len()
+        );
+
+        Ok(())
+    }
+
+    /// Rows referencing missing ones
+    fn foreign_key_violations(conn: &mut Conn) -> Result<i64> {
+        #[derive(QueryableByName)]
+        struct Count {
+            #[diesel(sql_type = diesel::sql_types::BigInt)]
+            count: i64,
+        }
+
+        Ok(
+            diesel::sql_query("SELECT count(*) AS count FROM pragma_foreign_key_check")
+                .get_result::<Count>(conn)?
+                .count,
+        )
+    }
+
+    #[test]
+    fn foreign_keys() -> Result<()> {
+        use crate::category::rule::NewCategoryRule;
+        use crate::record::{Mode, PaymentMethod};
+        use crate::test;
+
+        let conn = &mut test::db()?;
+        let mut account = test::account!(conn, "Cash");
+        let mut food = test::category!(conn, "Food");
+        let restaurants = test::category!(conn, "Restaurants", parent: Some(&food));
+        let mut merchant = test::merchant!(conn, "Chez Lulu", default_category: Some(&food));
+        merchant.add_alias(conn, "LULU")?;
+        let mut tag = test::tag!(conn, "holidays");
+        let record = test::record!(
+            conn,
+            &account,
+            category: Some(&food),
+            merchant: Some(&merchant)
+        );
+        record.add_tag(conn, &tag)?;
+        test::recpay!(conn, &account, category: Some(&food), merchant: Some(&merchant));
+        NewCategoryRule::new(Mode::Direct(PaymentMethod::Empty), &food).save(conn)?;
+        let mut report = crate::report::Report::create(conn, "Spending")?;
+        report.add(conn, [&food, &restaurants])?;
+
+        assert!(diesel::update(&record)
+            .set(crate::schema::records::account_id.eq(account.id + 1))
+            .execute(conn)
+            .is_err());
+
+        food.delete(conn)?;
+        merchant.delete(conn)?;
+        tag.delete(conn)?;
+        report.delete(conn)?;
+        account.delete(conn)?;
+        assert_eq!(0, foreign_key_violations(conn)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn foreign_keys_migration() -> Result<()> {
+        let db = &mut Database::memory()?;
+        db.setup()?;
+        // Back to before the foreign keys were enforced
+        while db.migrations()?.iter().any(|v| v == "20240925063027") {
+            db.revert_last_migration(MIGRATIONS)
+                .map_err(migration_error)?;
+        }
+
+        // References left behind before the foreign keys were enforced
+        db.set_foreign_keys(false)?;
+        for query in [
+            "INSERT INTO categories (id, name, parent_id) VALUES (1, 'Food', 2)",
+            "INSERT INTO category_rules (mode, category_id) VALUES ('direct', 2)",
+            "INSERT INTO accounts (id, name, balance, currency) VALUES (1, 'Cash', 0, 'EUR')",
+            "INSERT INTO records (account_id, amount, currency, operation_date, value_date, \
+             direction, mode, details) VALUES \
+             (1, 100, 'EUR', '2024-09-01', '2024-09-01', 'Debit', 'direct', 'kept'), \
+             (2, 200, 'EUR', '2024-09-02', '2024-09-02', 'Debit', 'direct', 'bread'), \
+             (2, 300, 'USD', '2024-09-03', '2024-09-03', 'Debit', 'direct', 'souvenir')",
+            "INSERT INTO recurring_payments (name, description, frequency, account_id, amount, \
+             currency, direction, mode) VALUES ('Rent', '', 'Monthly', 2, 50000, 'EUR', 'Debit', \
+             'transfer')",
+        ] {
+            diesel::sql_query(query).execute(&mut db.0)?;
+        }
+        // The rule isn't counted as it doesn't have a foreign key yet
+        assert_eq!(4, foreign_key_violations(&mut db.0)?);
+
+        db.setup()?;
+        assert_eq!(0, foreign_key_violations(&mut db.0)?);
+        assert!(crate::category::rule::CategoryRule::all(&mut db.0)?.is_empty());
+
+        // The records of the deleted account are kept in recovery accounts
+        let accounts = schema::accounts::table
+            .inner_join(schema::records::table)
+            .select((schema::accounts::name, schema::records::details))
+            .order(schema::records::id)
+            .load::<(String, String)>(&mut db.0)?;
+        assert_eq!(
+            vec![
+                ("Cash".to_owned(), "kept".to_owned()),
+                ("Recovered records (EUR)".to_owned(), "bread".to_owned()),
+                ("Recovered records (USD)".to_owned(), "souvenir".to_owned()),
+            ],
+            accounts
+        );
+        let recpay = crate::recurring_payment::RecurringPayment::find(&mut db.0, 1)?;
+        assert_eq!(
+            "Recovered records (EUR)",
+            crate::account::Account::find(&mut db.0, recpay.account_id)?.name
+        );
 
         Ok(())
     }
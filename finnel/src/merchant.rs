@@ -1,6 +1,11 @@
-use crate::{category::Category, essentials::*, schema::merchants};
+use crate::{
+    category::Category,
+    essentials::*,
+    schema::{merchant_aliases, merchants},
+};
 
-use diesel::prelude::*;
+use diesel::{prelude::*, OptionalExtension};
+use std::collections::HashMap;
 
 pub mod new;
 pub use new::NewMerchant;
@@ -8,10 +13,12 @@ pub use new::NewMerchant;
 pub mod change;
 pub use change::ChangeMerchant;
 
+pub mod details;
+
 mod query;
 pub use query::QueryMerchant;
 
-#[derive(Debug, Queryable, Selectable, Identifiable, Associations)]
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Associations)]
 #[diesel(table_name = merchants)]
 #[diesel(belongs_to(Category, foreign_key = default_category_id))]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -20,6 +27,8 @@ pub struct Merchant {
     pub name: String,
     pub default_category_id: Option<i64>,
     pub replaced_by_id: Option<i64>,
+    pub website: Option<String>,
+    pub notes: Option<String>,
 }
 
 impl Merchant {
@@ -35,6 +44,27 @@ impl Merchant {
             .transpose()
     }
 
+    /// Ids of the merchant and of the merchants it replaces, directly or
+    /// through other replaced merchants
+    pub fn replaced_ids(&self, conn: &mut Conn) -> Result<Vec<i64>> {
+        let replacements = merchants::table
+            .filter(merchants::replaced_by_id.is_not_null())
+            .select((merchants::id, merchants::replaced_by_id))
+            .load::<(i64, Option<i64>)>(conn)?;
+
+        let mut ids = vec![self.id];
+        let mut index = 0;
+        while let Some(&id) = ids.get(index) {
+            for (replaced_id, _) in replacements.iter().filter(|(_, by)| *by == Some(id)) {
+                if !ids.contains(replaced_id) {
+                    ids.push(*replaced_id);
+                }
+            }
+            index += 1;
+        }
+        Ok(ids)
+    }
+
     pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
         merchants::table
             .find(id)
@@ -43,20 +73,155 @@ impl Merchant {
             .map_err(|e| Error::from_diesel_error(e, "Merchant", None))
     }
 
+    /// Find the merchant by its name, normalized and ignoring case
     pub fn find_by_name(conn: &mut Conn, name: &str) -> Result<Self> {
+        let name = crate::name::normalize("Merchant", name)?;
         merchants::table
-            .filter(merchants::name.eq(name))
+            .filter(db::nocase(merchants::name).eq(name))
             .select(Merchant::as_select())
             .first(conn)
             .map_err(|e| Error::from_diesel_error(e, "Merchant", Some("name")))
     }
 
+    /// Fail if a merchant other than `id` is already named `name`,
+    /// ignoring case
+    pub(crate) fn check_unique_name(conn: &mut Conn, name: &str, id: Option<i64>) -> Result<()> {
+        match Self::find_by_name(conn, name) {
+            Ok(existing) if Some(existing.id) != id => Err(Error::AlreadyExists {
+                model: "Merchant",
+                name: existing.name,
+                id: existing.id,
+            }),
+            Err(e) if !e.is_not_found() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the merchant having this alias, ignoring case, or else this name
+    pub fn find_by_name_or_alias(conn: &mut Conn, name: &str) -> Result<Self> {
+        match merchants::table
+            .inner_join(merchant_aliases::table)
+            .filter(merchant_aliases::alias.eq(name))
+            .select(Merchant::as_select())
+            .first(conn)
+            .optional()?
+        {
+            Some(merchant) => Ok(merchant),
+            None => Self::find_by_name(conn, name),
+        }
+    }
+
+    /// Find the merchants by their aliases, or else their names, in a few
+    /// queries, like `find_by_name_or_alias`, keyed by the name given and
+    /// leaving out the ones not found
+    pub fn find_all_by_name_or_alias<'a, I>(
+        conn: &mut Conn,
+        names: I,
+    ) -> Result<HashMap<String, Self>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let names = names.into_iter().collect::<Vec<_>>();
+        let mut aliased = Vec::new();
+        for chunk in names.chunks(crate::name::LOOKUP_CHUNK) {
+            aliased.extend(
+                merchants::table
+                    .inner_join(merchant_aliases::table)
+                    .filter(merchant_aliases::alias.eq_any(chunk))
+                    .select((merchant_aliases::alias, Merchant::as_select()))
+                    .load::<(String, Merchant)>(conn)?,
+            );
+        }
+
+        let mut found = HashMap::new();
+        let mut unaliased = Vec::new();
+        for name in names {
+            // Aliases ignore case, like the column
+            match aliased
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            {
+                Some((_, merchant)) => {
+                    found.insert(name.to_string(), merchant.clone());
+                }
+                None => unaliased.push((name.to_string(), name)),
+            }
+        }
+
+        let keys = crate::name::lookup_keys("Merchant", unaliased.iter().map(|(_, name)| *name));
+        let mut named = Vec::new();
+        for chunk in keys.chunks(crate::name::LOOKUP_CHUNK) {
+            named.extend(
+                merchants::table
+                    .filter(db::lower(merchants::name).eq_any(chunk.iter().map(|(_, key)| key)))
+                    .select(Merchant::as_select())
+                    .load::<Merchant>(conn)?,
+            );
+        }
+        for (name, key) in keys {
+            if let Some(merchant) = named
+                .iter()
+                .find(|merchant| merchant.name.to_ascii_lowercase() == key)
+            {
+                found.insert(name, merchant.clone());
+            }
+        }
+
+        Ok(found)
+    }
+
+    pub fn fetch_aliases(&self, conn: &mut Conn) -> Result<Vec<String>> {
+        Ok(merchant_aliases::table
+            .filter(merchant_aliases::merchant_id.eq(self.id))
+            .select(merchant_aliases::alias)
+            .order(merchant_aliases::alias.asc())
+            .load(conn)?)
+    }
+
+    /// Make imports using this name use the current merchant
+    pub fn add_alias(&self, conn: &mut Conn, alias: &str) -> Result<()> {
+        if alias.is_empty() {
+            return Err(Error::Invalid("Merchant alias cannot be empty".to_owned()));
+        }
+
+        diesel::insert_into(merchant_aliases::table)
+            .values((
+                merchant_aliases::merchant_id.eq(self.id),
+                merchant_aliases::alias.eq(alias),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Remove the alias, whichever merchant it belongs to
+    pub fn remove_alias(conn: &mut Conn, alias: &str) -> Result<()> {
+        let count = diesel::delete(merchant_aliases::table)
+            .filter(merchant_aliases::alias.eq(alias))
+            .execute(conn)?;
+        if count == 0 {
+            return Err(Error::ModelNotFoundBy("Merchant alias", "alias"));
+        }
+        Ok(())
+    }
+
+    /// List all aliases along with the merchant they belong to
+    pub fn all_aliases(conn: &mut Conn) -> Result<Vec<(String, Merchant)>> {
+        Ok(merchant_aliases::table
+            .inner_join(merchants::table)
+            .select((merchant_aliases::alias, Merchant::as_select()))
+            .order(merchant_aliases::alias.asc())
+            .load(conn)?)
+    }
+
     /// Delete the current merchant, nulling references to it where possible
     ///
     /// This method executes multiple queries without wrapping them in a
     /// transaction
     pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
         crate::record::clear_merchant_id(conn, self.id)?;
+        diesel::delete(merchant_aliases::table)
+            .filter(merchant_aliases::merchant_id.eq(self.id))
+            .execute(conn)?;
         crate::recurring_payment::clear_merchant_id(conn, self.id)?;
         diesel::update(merchants::table)
             .filter(merchants::replaced_by_id.eq(Some(self.id)))
@@ -68,7 +233,23 @@ impl Merchant {
     }
 }
 
+impl crate::resolved::Link for Merchant {
+    const MODEL: &'static str = "Merchant";
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl Resolvable for Merchant {
+    fn replacements(self, conn: &mut Conn) -> Result<Vec<Self>> {
+        crate::resolved::chain(conn, self, Self::find, |c| c.replaced_by_id)
+    }
+
     fn resolve(self, conn: &mut Conn) -> Result<Self> {
         crate::resolved::resolve(conn, self, Self::find, |c| c.replaced_by_id)
     }
@@ -118,6 +299,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn normalized_name() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let foo = NewMerchant::new("Foo").save(conn)?;
+        assert!(matches!(
+            NewMerchant::new(" foo ").save(conn),
+            Err(Error::AlreadyExists { id, name, .. }) if id == foo.id && name == "Foo"
+        ));
+        assert!(matches!(
+            NewMerchant::new("").save(conn),
+            Err(Error::Invalid(_))
+        ));
+
+        let mut bar = NewMerchant::new("Bar\t  Tabac ").save(conn)?;
+        assert_eq!("Bar Tabac", bar.name);
+        assert!(ChangeMerchant {
+            name: Some("FOO"),
+            ..Default::default()
+        }
+        .apply(conn, &mut bar)
+        .is_err());
+        assert!(ChangeMerchant {
+            name: Some(" "),
+            ..Default::default()
+        }
+        .apply(conn, &mut bar)
+        .is_err());
+        assert_eq!("Bar Tabac", bar.reload(conn)?.name);
+
+        Ok(())
+    }
+
     #[test]
     fn delete() -> Result<()> {
         let conn = &mut test::db()?;
@@ -125,8 +339,97 @@ mod tests {
         let mut mer1 = test::merchant!(conn, "mer1");
         let mut mer2 = test::merchant!(conn, "mer2", replaced_by: Some(&mer1));
 
+        mer1.add_alias(conn, "mer one")?;
         mer1.delete(conn)?;
         assert!(mer2.reload(conn)?.replaced_by_id.is_none());
+        assert!(Merchant::all_aliases(conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn aliases() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let amazon = test::merchant!(conn, "Amazon");
+        let marketplace = test::merchant!(conn, "AMZN Mktp");
+        amazon.add_alias(conn, "AMAZON EU SARL")?;
+        amazon.add_alias(conn, "AMZN Mktp")?;
+
+        assert!(amazon.add_alias(conn, "Amazon EU Sarl").is_err());
+        assert!(marketplace.add_alias(conn, "AMAZON EU SARL").is_err());
+        assert!(amazon.add_alias(conn, "").is_err());
+
+        // Aliases take precedence over names, which stay found by find_by_name
+        assert_eq!(
+            amazon.id,
+            Merchant::find_by_name_or_alias(conn, "amzn mktp")?.id
+        );
+        assert_eq!(
+            marketplace.id,
+            Merchant::find_by_name(conn, "AMZN Mktp")?.id
+        );
+        assert_eq!(
+            amazon.id,
+            Merchant::find_by_name_or_alias(conn, "Amazon")?.id
+        );
+        assert!(Merchant::find_by_name_or_alias(conn, "Ebay")
+            .unwrap_err()
+            .is_not_found());
+
+        let found =
+            Merchant::find_all_by_name_or_alias(conn, ["amzn mktp", " amazon", "Ebay", ""])?;
+        assert_eq!(2, found.len());
+        assert_eq!(amazon.id, found["amzn mktp"].id);
+        assert_eq!(amazon.id, found[" amazon"].id);
+
+        assert_eq!(
+            vec!["AMAZON EU SARL", "AMZN Mktp"],
+            amazon.fetch_aliases(conn)?
+        );
+
+        Merchant::remove_alias(conn, "AMZN Mktp")?;
+        assert!(Merchant::remove_alias(conn, "AMZN Mktp").is_err());
+        assert_eq!(
+            marketplace.id,
+            Merchant::find_by_name_or_alias(conn, "AMZN Mktp")?.id
+        );
+
+        let aliases = Merchant::all_aliases(conn)?;
+        assert_eq!(1, aliases.len());
+        assert_eq!("AMAZON EU SARL", aliases[0].0);
+        assert_eq!(amazon.id, aliases[0].1.id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aliases_backfilled_from_replaced_merchants() -> Result<()> {
+        use diesel::migration::MigrationSource;
+        use diesel_migrations::MigrationHarness;
+
+        let conn = &mut test::db()?;
+        let migration = MigrationSource::<diesel::sqlite::Sqlite>::migrations(&crate::MIGRATIONS)
+            .map_err(Error::from)?
+            .into_iter()
+            .find(|m| m.name().to_string().ends_with("_add_merchant_aliases"))
+            .unwrap();
+        conn.revert_migration(&*migration).map_err(Error::from)?;
+
+        let amazon = test::merchant!(conn, "Amazon");
+        let replaced = test::merchant!(conn, "AMZN Mktp", replaced_by: Some(&amazon));
+        conn.run_migration(&*migration).map_err(Error::from)?;
+
+        assert_eq!(vec!["AMZN Mktp"], amazon.fetch_aliases(conn)?);
+        assert_eq!(
+            amazon.id,
+            Merchant::find_by_name_or_alias(conn, "AMZN Mktp")?.id
+        );
+        // The replaced merchant is left as it was
+        assert_eq!(
+            Some(amazon.id),
+            Merchant::find(conn, replaced.id)?.replaced_by_id
+        );
 
         Ok(())
     }
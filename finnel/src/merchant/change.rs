@@ -1,7 +1,8 @@
 use crate::{
     category::Category,
+    diff::{self, FieldDiff},
     essentials::*,
-    merchant::Merchant,
+    merchant::{details, Merchant},
     resolved::{mapmapmap, mapmapmapresult, mapmapresolve},
     schema::merchants,
 };
@@ -13,6 +14,8 @@ pub struct ChangeMerchant<'a> {
     pub name: Option<&'a str>,
     pub default_category: Option<Option<&'a Category>>,
     pub replaced_by: Option<Option<&'a Merchant>>,
+    pub website: Option<Option<&'a str>>,
+    pub notes: Option<Option<&'a str>>,
 }
 
 impl<'a> ChangeMerchant<'a> {
@@ -28,7 +31,7 @@ impl<'a> ChangeMerchant<'a> {
         resolved.validate(conn, merchant)?.save(conn)?;
 
         if let Some(value) = changeset.name {
-            merchant.name = value.to_string();
+            merchant.name = value;
         }
         if let Some(value) = changeset.default_category_id {
             merchant.default_category_id = value;
@@ -36,27 +39,50 @@ impl<'a> ChangeMerchant<'a> {
         if let Some(value) = changeset.replaced_by_id {
             merchant.replaced_by_id = value;
         }
+        if let Some(value) = changeset.website {
+            merchant.website = value;
+        }
+        if let Some(value) = changeset.notes {
+            merchant.notes = value;
+        }
 
         Ok(())
     }
 
     pub fn into_resolved(self, conn: &mut Conn) -> Result<ResolvedChangeMerchant<'a>> {
         Ok(ResolvedChangeMerchant {
-            name: self.name,
+            name: self
+                .name
+                .map(|name| crate::name::normalize("Merchant", name))
+                .transpose()?,
             default_category: mapmapresolve(conn, self.default_category)?,
+            replaced_by_target: self.replaced_by.flatten().map(|r| r.id),
             replaced_by: mapmapresolve(conn, self.replaced_by)?,
+            website: self
+                .website
+                .map(|website| website.map(details::normalize_website).transpose())
+                .transpose()?,
+            notes: self
+                .notes
+                .map(|notes| notes.map(details::normalize_notes).transpose())
+                .transpose()?,
         })
     }
 }
 
 pub struct ResolvedChangeMerchant<'a> {
-    name: Option<&'a str>,
+    name: Option<String>,
     default_category: Option<Option<Resolved<'a, Category>>>,
     replaced_by: Option<Option<Resolved<'a, Merchant>>>,
+    /// Id of the replacer given, before following its own replacements
+    replaced_by_target: Option<i64>,
+    website: Option<Option<String>>,
+    notes: Option<Option<String>>,
 }
 
 impl<'a> ResolvedChangeMerchant<'a> {
-    fn validate_replace_by(&self, _conn: &mut Conn, merchant: &Merchant) -> Result<()> {
+    fn validate_replace_by(&self, conn: &mut Conn, merchant: &Merchant) -> Result<()> {
+        self.validate_replace_by_cycle(conn, merchant)?;
         mapmapmapresult(&self.replaced_by, |replaced_by| {
             if merchant.id == replaced_by.id {
                 return Err(Error::Invalid(
@@ -69,26 +95,94 @@ impl<'a> ResolvedChangeMerchant<'a> {
         Ok(())
     }
 
+    /// Walk the replacements from the replacer given, which must not come
+    /// back to the merchant
+    fn validate_replace_by_cycle(&self, conn: &mut Conn, merchant: &Merchant) -> Result<()> {
+        let Some(target) = self.replaced_by_target.filter(|id| *id != merchant.id) else {
+            return Ok(());
+        };
+
+        let chain = Merchant::find(conn, target)?.replacements(conn)?;
+        if let Some(position) = chain.iter().position(|r| r.id == merchant.id) {
+            return Err(Error::ReplacementCycle {
+                model: "Merchant",
+                names: [merchant.name.clone()]
+                    .into_iter()
+                    .chain(chain[..=position].iter().map(|r| r.name.clone()))
+                    .collect(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_name(&self, conn: &mut Conn, merchant: &Merchant) -> Result<()> {
+        match &self.name {
+            Some(name) => Merchant::check_unique_name(conn, name, Some(merchant.id)),
+            None => Ok(()),
+        }
+    }
+
     pub fn validate(
         &self,
         conn: &mut Conn,
         merchant: &'a Merchant,
     ) -> Result<ValidatedChangeMerchant<'a>> {
         self.validate_replace_by(conn, merchant)?;
+        self.validate_name(conn, merchant)?;
 
         Ok(ValidatedChangeMerchant(merchant, self.as_changeset()))
     }
 
-    pub fn as_changeset(&self) -> MerchantChangeset<'a> {
+    /// Fields of the merchant the change would modify, leaving out the ones
+    /// keeping their value, with the category and merchant by name
+    pub fn diff(&self, conn: &mut Conn, merchant: &Merchant) -> Result<Vec<FieldDiff>> {
+        let mut fields = Vec::new();
+
+        if let Some(name) = &self.name {
+            diff::push(&mut fields, "name", Some(&merchant.name), Some(name));
+        }
+        if let Some(category) = &self.default_category {
+            let old = diff::category_name(conn, merchant.default_category_id)?;
+            let new = category.as_ref().map(|c| c.map(|c| c.name.clone()));
+            diff::push(&mut fields, "default_category", old, new);
+        }
+        if let Some(replaced_by) = &self.replaced_by {
+            let old = diff::merchant_name(conn, merchant.replaced_by_id)?;
+            let new = replaced_by.as_ref().map(|m| m.map(|m| m.name.clone()));
+            diff::push(&mut fields, "replaced_by", old, new);
+        }
+        if let Some(website) = &self.website {
+            diff::push(
+                &mut fields,
+                "website",
+                merchant.website.as_ref(),
+                website.as_ref(),
+            );
+        }
+        if let Some(notes) = &self.notes {
+            diff::push(
+                &mut fields,
+                "notes",
+                merchant.notes.as_ref(),
+                notes.as_ref(),
+            );
+        }
+
+        Ok(fields)
+    }
+
+    pub fn as_changeset(&self) -> MerchantChangeset {
         MerchantChangeset {
-            name: self.name,
+            name: self.name.clone(),
             default_category_id: mapmapmap(&self.default_category, |c| c.id),
             replaced_by_id: mapmapmap(&self.replaced_by, |m| m.id),
+            website: self.website.clone(),
+            notes: self.notes.clone(),
         }
     }
 }
 
-pub struct ValidatedChangeMerchant<'a>(&'a Merchant, MerchantChangeset<'a>);
+pub struct ValidatedChangeMerchant<'a>(&'a Merchant, MerchantChangeset);
 
 impl<'a> ValidatedChangeMerchant<'a> {
     pub fn save(self, conn: &mut Conn) -> Result<()> {
@@ -99,16 +193,18 @@ impl<'a> ValidatedChangeMerchant<'a> {
 
 #[derive(Default, Clone, AsChangeset)]
 #[diesel(table_name = merchants)]
-pub struct MerchantChangeset<'a> {
-    pub name: Option<&'a str>,
+pub struct MerchantChangeset {
+    pub name: Option<String>,
     pub default_category_id: Option<Option<i64>>,
     pub replaced_by_id: Option<Option<i64>>,
+    pub website: Option<Option<String>>,
+    pub notes: Option<Option<String>>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::prelude::{Result, *};
+    use crate::test::prelude::{assert_eq, Result, *};
 
     #[test]
     fn update_loop() -> Result<()> {
@@ -130,7 +226,77 @@ mod tests {
 
         assert!(resolved.validate_replace_by(conn, merchant1).is_err());
 
-        assert!(change.save(conn, merchant1).is_err());
+        match change.save(conn, merchant1) {
+            Err(Error::ReplacementCycle { model, names }) => {
+                assert_eq!("Merchant", model);
+                assert_eq!(vec!["Foo", "Bar", "Foo"], names);
+            }
+            result => panic!("Unexpected result {:?}", result.err()),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff() -> Result<()> {
+        let conn = &mut test::db()?;
+        let bar = test::category!(conn, "Bar");
+        let food = test::category!(conn, "Food");
+        let chariot = test::merchant!(conn, "Chariot", default_category: Some(&bar));
+
+        let change = ChangeMerchant {
+            name: Some("Chariot"),
+            default_category: Some(Some(&food)),
+            replaced_by: Some(None),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![(
+                "default_category",
+                Some("Bar".to_string()),
+                Some("Food".to_string())
+            )],
+            change.into_resolved(conn)?.diff(conn, &chariot)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn details() -> Result<()> {
+        let conn = &mut test::db()?;
+        let bakery = &mut test::merchant!(
+            conn,
+            "Bakery",
+            website: Some("https://bakery.example.com")
+        );
+
+        ChangeMerchant {
+            notes: Some(Some("Closed on sundays")),
+            ..Default::default()
+        }
+        .apply(conn, bakery)?;
+        bakery.reload(conn)?;
+        assert_eq!(
+            Some("https://bakery.example.com"),
+            bakery.website.as_deref()
+        );
+        assert_eq!(Some("Closed on sundays"), bakery.notes.as_deref());
+
+        let change = ChangeMerchant {
+            website: Some(Some("bakery.example.com")),
+            ..Default::default()
+        };
+        assert!(matches!(change.save(conn, bakery), Err(Error::Invalid(_))));
+
+        ChangeMerchant {
+            website: Some(None),
+            ..Default::default()
+        }
+        .save(conn, bakery)?;
+        bakery.reload(conn)?;
+        assert_eq!(None, bakery.website);
+        assert_eq!(Some("Closed on sundays"), bakery.notes.as_deref());
 
         Ok(())
     }
@@ -0,0 +1,84 @@
+use crate::essentials::*;
+
+/// Website of a merchant as it is saved, trimmed
+///
+/// It must be an URL with a scheme and a host, like `https://example.com`,
+/// anything else fails naming the value.
+pub fn normalize_website(website: &str) -> Result<String> {
+    let website = website.trim();
+    let invalid = || {
+        Error::Invalid(format!(
+            "Invalid merchant website {website:?}, expected an URL like https://example.com"
+        ))
+    };
+
+    let (scheme, rest) = website.split_once("://").ok_or_else(invalid)?;
+    let mut scheme_chars = scheme.chars();
+    if !scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        || !scheme_chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    {
+        return Err(invalid());
+    }
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if host.is_empty() || website.chars().any(char::is_whitespace) {
+        return Err(invalid());
+    }
+
+    Ok(website.to_owned())
+}
+
+/// Notes of a merchant as they are saved, trimmed
+///
+/// Fails if nothing is left, removing the notes being the way to clear them.
+pub fn normalize_notes(notes: &str) -> Result<String> {
+    let notes = notes.trim();
+    if notes.is_empty() {
+        return Err(Error::Invalid(
+            "Merchant notes can't be empty, remove them instead".to_owned(),
+        ));
+    }
+    Ok(notes.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn normalize_website() -> Result<()> {
+        assert_eq!(
+            "https://example.com",
+            super::normalize_website(" https://example.com ")?
+        );
+        assert_eq!(
+            "http://shop.example.com:8080/fr?q=1",
+            super::normalize_website("http://shop.example.com:8080/fr?q=1")?
+        );
+        for website in [
+            "example.com",
+            "https://",
+            "https:///path",
+            "://example.com",
+            "1http://example.com",
+            "https://exa mple.com",
+            "",
+        ] {
+            let error = super::normalize_website(website).unwrap_err().to_string();
+            assert!(error.contains(&format!("{website:?}")), "{error}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalize_notes() -> Result<()> {
+        assert_eq!(
+            "Closed on sundays",
+            super::normalize_notes(" Closed on sundays\n")?
+        );
+        assert!(super::normalize_notes(" ").is_err());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     category::Category,
     essentials::*,
-    merchant::Merchant,
+    merchant::{details, Merchant},
     resolved::{mapmap, mapresolve},
     schema::merchants,
 };
@@ -13,6 +13,8 @@ pub struct NewMerchant<'a> {
     pub name: &'a str,
     pub default_category: Option<&'a Category>,
     pub replaced_by: Option<&'a Merchant>,
+    pub website: Option<&'a str>,
+    pub notes: Option<&'a str>,
 }
 
 impl<'a> NewMerchant<'a> {
@@ -27,13 +29,19 @@ impl<'a> NewMerchant<'a> {
         self.to_insertable(conn)?.save(conn)
     }
 
-    pub fn to_insertable(self, conn: &mut Conn) -> Result<InsertableMerchant<'a>> {
+    /// Normalize the name, failing if it is empty or already used by another
+    /// merchant, and the website and notes
+    pub fn to_insertable(self, conn: &mut Conn) -> Result<InsertableMerchant> {
         let NewMerchant {
             name,
             default_category,
             replaced_by,
+            website,
+            notes,
         } = self;
 
+        let name = crate::name::normalize("Merchant", name)?;
+        Merchant::check_unique_name(conn, &name, None)?;
         let default_category = mapresolve(conn, default_category)?;
         let replaced_by = mapresolve(conn, replaced_by)?;
 
@@ -41,19 +49,23 @@ impl<'a> NewMerchant<'a> {
             name,
             default_category_id: mapmap(&default_category, |c| c.id),
             replaced_by_id: mapmap(&replaced_by, |m| m.id),
+            website: website.map(details::normalize_website).transpose()?,
+            notes: notes.map(details::normalize_notes).transpose()?,
         })
     }
 }
 
 #[derive(Default, Insertable)]
 #[diesel(table_name = merchants)]
-pub struct InsertableMerchant<'a> {
-    pub name: &'a str,
+pub struct InsertableMerchant {
+    pub name: String,
     pub default_category_id: Option<i64>,
     pub replaced_by_id: Option<i64>,
+    pub website: Option<String>,
+    pub notes: Option<String>,
 }
 
-impl InsertableMerchant<'_> {
+impl InsertableMerchant {
     pub fn save(self, conn: &mut Conn) -> Result<Merchant> {
         Ok(diesel::insert_into(merchants::table)
             .values(self)
@@ -1,7 +1,8 @@
 use super::Merchant;
 use crate::category::Category;
 use crate::essentials::*;
-use crate::schema::{categories, merchants};
+use crate::order::{OrderBy, OrderDirection};
+use crate::schema::{categories, merchants, records};
 
 use diesel::{
     expression::SqlLiteral,
@@ -23,6 +24,8 @@ pub struct QueryMerchant<'a> {
     pub default_category_id: Option<Option<i64>>,
     pub replaced_by_id: Option<Option<i64>>,
     pub count: Option<i64>,
+    /// Ordered by id when empty
+    pub order: Vec<(OrderBy, OrderDirection)>,
 }
 
 pub struct QueryMerchantWithCategory<'a>(QueryMerchant<'a>);
@@ -43,6 +46,20 @@ type QueryType<'a> = IntoBoxed<
 >;
 
 impl<'a> QueryMerchant<'a> {
+    fn sort_by<U>(query: QueryType<'a>, expression: U, direction: OrderDirection) -> QueryType<'a>
+    where
+        U: 'a
+            + ExpressionMethods
+            + diesel::query_builder::QueryFragment<Sqlite>
+            + AppearsOnTable<Alias<MerchantAlias>>
+            + Send,
+    {
+        match direction {
+            OrderDirection::Asc => query.then_order_by(expression.asc()),
+            OrderDirection::Desc => query.then_order_by(expression.desc()),
+        }
+    }
+
     fn build(&self) -> QueryType<'a> {
         let mut query = MERCHANTS_ALIAS.into_boxed();
 
@@ -67,7 +84,30 @@ impl<'a> QueryMerchant<'a> {
             query = query.limit(count);
         }
 
-        query
+        let id = MERCHANTS_ALIAS.field(merchants::id);
+        // Aggregated in the query itself so that the count applies after
+        // sorting
+        let merchant_records = || records::table.filter(records::merchant_id.eq(id.nullable()));
+        for (field, direction) in &self.order {
+            query = match field {
+                OrderBy::Id => Self::sort_by(query, id, *direction),
+                OrderBy::Name => {
+                    Self::sort_by(query, MERCHANTS_ALIAS.field(merchants::name), *direction)
+                }
+                OrderBy::Records => {
+                    Self::sort_by(query, merchant_records().count().single_value(), *direction)
+                }
+                OrderBy::LastUsed => Self::sort_by(
+                    query,
+                    merchant_records()
+                        .select(diesel::dsl::max(records::operation_date))
+                        .single_value(),
+                    *direction,
+                ),
+            };
+        }
+
+        query.then_order_by(id.asc())
     }
 
     pub fn run(&self, conn: &mut Conn) -> Result<Vec<Merchant>> {
@@ -200,4 +240,51 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn order() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let zed = test::merchant!(conn, "Zed");
+        let alpha = test::merchant!(conn, "Alpha");
+        let mid = test::merchant!(conn, "Mid");
+        for (merchant, day) in [(&alpha, 1), (&alpha, 2), (&mid, 3)] {
+            let date = chrono::NaiveDate::from_ymd_opt(2024, 8, day).unwrap();
+            test::record!(conn, &account, merchant: Some(merchant), operation_date: date);
+        }
+
+        let ids = |order, count, conn: &mut Conn| -> Result<Vec<i64>> {
+            Ok(QueryMerchant {
+                order,
+                count,
+                ..Default::default()
+            }
+            .run(conn)?
+            .into_iter()
+            .map(|m| m.id)
+            .collect())
+        };
+
+        use OrderBy::*;
+        use OrderDirection::*;
+        assert_eq!(vec![zed.id, alpha.id, mid.id], ids(vec![], None, conn)?);
+        assert_eq!(
+            vec![alpha.id, mid.id],
+            ids(vec![(Name, Asc)], Some(2), conn)?
+        );
+        assert_eq!(
+            vec![alpha.id, mid.id],
+            ids(vec![(Records, Desc)], Some(2), conn)?
+        );
+        assert_eq!(
+            vec![mid.id, alpha.id, zed.id],
+            ids(vec![(LastUsed, Desc)], None, conn)?
+        );
+        assert_eq!(
+            vec![zed.id, mid.id],
+            ids(vec![(Records, Asc), (Name, Desc)], Some(2), conn)?
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,47 @@
+use crate::essentials::*;
+
+/// Name of a category or a merchant as it is saved: trimmed, with the runs of
+/// whitespace inside collapsed into a single space
+///
+/// Fails if nothing is left of the name.
+pub fn normalize(model: &'static str, name: &str) -> Result<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err(Error::Invalid(format!("{model} name cannot be empty")));
+    }
+    Ok(name)
+}
+
+/// Number of names looked up per query, well below the limit of bound
+/// parameters of SQLite
+pub(crate) const LOOKUP_CHUNK: usize = 500;
+
+/// Names paired with the key to look them up with, normalized and in lower
+/// case like SQLite `lower()` does, leaving out the invalid ones
+pub(crate) fn lookup_keys<'a, I>(model: &'static str, names: I) -> Vec<(String, String)>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let key = normalize(model, name).ok()?.to_ascii_lowercase();
+            Some((name.to_string(), key))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn normalize() -> Result<()> {
+        assert_eq!("Foo", super::normalize("Category", "Foo")?);
+        assert_eq!("Foo Bar", super::normalize("Category", "  Foo \t  Bar\n")?);
+        assert!(super::normalize("Category", "").is_err());
+        assert!(super::normalize("Category", " \t ").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! Ordering of the categories and merchants, also by how their records use
+//! them
+
+pub use crate::record::query::OrderDirection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderBy {
+    Id,
+    Name,
+    /// Number of records using it
+    Records,
+    /// Most recent operation date of the records using it, never used ones
+    /// coming first when ascending
+    LastUsed,
+}
@@ -0,0 +1,99 @@
+//! Parsing of the amounts and dates found in bank exports or typed by users
+
+use crate::essentials::*;
+
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+/// Parse a number using either a decimal point or a decimal comma, ignoring
+/// the whitespaces used to separate thousands
+pub fn decimal(number: &str) -> Result<Decimal> {
+    Decimal::from_str(
+        number
+            .replace(",", ".")
+            .replace(char::is_whitespace, "")
+            .as_str(),
+    )
+    .map_err(|_| Error::Parse(format!("{number:?} is not a number")))
+}
+
+/// Parse an amount the same way as a number, also accepting a currency
+/// symbol but at most 2 decimal places
+pub fn amount(amount: &str) -> Result<Decimal> {
+    const SYMBOLS: &[char] = &['€', '$', '£'];
+
+    let number = amount
+        .trim()
+        .trim_start_matches(SYMBOLS)
+        .trim_end_matches(SYMBOLS);
+    let decimal = decimal(number).map_err(|_| {
+        Error::Parse(format!(
+            "{amount:?} is not an amount, expected e.g. 12.50 or 12,50"
+        ))
+    })?;
+    if decimal.scale() > 2 {
+        return Err(Error::Parse(format!(
+            "{amount:?} has more than 2 decimal places"
+        )));
+    }
+
+    Ok(decimal)
+}
+
+/// Parse a date in the given `strftime` format, e.g. `%d/%m/%Y`
+pub fn date(date: &str, format: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, format)
+        .map_err(|e| Error::Parse(format!("{date:?} is not a date in format {format}, {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn decimal() -> Result<()> {
+        assert!(super::decimal("hello").is_err());
+
+        assert_eq!(Decimal::new(314, 2), super::decimal("3,14")?);
+        assert_eq!(Decimal::new(314, 2), super::decimal("3.14")?);
+
+        assert_eq!(Decimal::new(65536, 0), super::decimal("65536")?);
+        assert_eq!(Decimal::new(65536, 0), super::decimal("65 536")?);
+        assert_eq!(Decimal::new(65536, 0), super::decimal("65\u{a0}536")?);
+        Ok(())
+    }
+
+    #[test]
+    fn amount() -> Result<()> {
+        assert_eq!(Decimal::new(1250, 2), super::amount("12,50")?);
+        assert_eq!(Decimal::new(1250, 2), super::amount("12.50")?);
+        assert_eq!(Decimal::new(1250, 2), super::amount("€12,50")?);
+        assert_eq!(Decimal::new(1250, 2), super::amount("12,50 €")?);
+        assert_eq!(Decimal::new(125, 1), super::amount(" $ 12.5")?);
+        assert_eq!(Decimal::new(1200, 0), super::amount("1 200")?);
+
+        assert_eq!(
+            "\"12,505\" has more than 2 decimal places",
+            super::amount("12,505").unwrap_err().to_string()
+        );
+        assert!(super::amount("twelve").is_err());
+        assert!(super::amount("€").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn date() -> Result<()> {
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 7, 31),
+            Some(super::date("31/07/24", "%d/%m/%y")?)
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 7, 31),
+            Some(super::date("2024-07-31", "%Y-%m-%d")?)
+        );
+        assert!(super::date("2024-07-31", "%d/%m/%Y").is_err());
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
 use crate::{
-    account::Account, category::Category, essentials::*, merchant::Merchant, schema::records,
+    account::Account,
+    category::Category,
+    essentials::*,
+    merchant::Merchant,
+    result::RowError,
+    schema::{record_tags, records, tags},
+    tag::Tag,
     Amount, Currency, Decimal,
 };
 
-use chrono::NaiveDate;
-use diesel::prelude::*;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use diesel::{dsl::sql, prelude::*, sql_types::Text, sqlite::Sqlite};
 
 mod direction;
 pub use direction::Direction;
@@ -24,7 +30,18 @@ pub use query::QueryRecord;
 pub mod split;
 pub use split::SplitRecord;
 
-#[derive(Debug, Queryable, Selectable, Identifiable, Associations)]
+pub mod transfer;
+pub use transfer::NewTransfer;
+
+pub mod adjustment;
+pub use adjustment::NewBalanceAdjustment;
+
+pub mod duplicates;
+
+pub mod history;
+pub use history::RecordChange;
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Associations)]
 #[diesel(table_name = records)]
 #[diesel(belongs_to(Account, foreign_key = account_id))]
 #[diesel(belongs_to(Category, foreign_key = category_id))]
@@ -32,7 +49,8 @@ pub use split::SplitRecord;
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Record {
     pub id: i64,
-    pub account_id: i64,
+    /// None while the record is pending
+    pub account_id: Option<i64>,
     #[diesel(deserialize_as = crate::db::Decimal)]
     pub amount: Decimal,
     #[diesel(deserialize_as = crate::db::Currency)]
@@ -44,6 +62,18 @@ pub struct Record {
     pub details: String,
     pub category_id: Option<i64>,
     pub merchant_id: Option<i64>,
+    /// When the record was flagged as needing attention
+    pub flagged_at: Option<NaiveDateTime>,
+    pub flag_reason: Option<String>,
+    /// Amount of a foreign currency transaction before its conversion to
+    /// the currency of the account
+    #[diesel(deserialize_as = crate::db::Optional<crate::db::Decimal>)]
+    pub original_amount: Option<Decimal>,
+    #[diesel(deserialize_as = crate::db::Optional<crate::db::Currency>)]
+    pub original_currency: Option<Currency>,
+    /// Waiting for an account to be assigned, left out of the balances and
+    /// stats until then
+    pub pending: bool,
 }
 
 impl Record {
@@ -51,6 +81,12 @@ impl Record {
         Amount(self.amount, self.currency)
     }
 
+    pub fn original_amount(&self) -> Option<Amount> {
+        self.original_amount
+            .zip(self.original_currency)
+            .map(|(value, currency)| Amount(value, currency))
+    }
+
     pub fn fetch_category(&self, conn: &mut Conn) -> Result<Option<Category>> {
         self.category_id
             .map(|id| Category::find(conn, id))
@@ -63,6 +99,35 @@ impl Record {
             .transpose()
     }
 
+    pub fn fetch_tags(&self, conn: &mut Conn) -> Result<Vec<Tag>> {
+        Ok(record_tags::table
+            .inner_join(tags::table)
+            .filter(record_tags::record_id.eq(self.id))
+            .select(Tag::as_select())
+            .order(tags::name.asc())
+            .load(conn)?)
+    }
+
+    /// Associate the tag with the current record, doing nothing if it already
+    /// is
+    pub fn add_tag(&self, conn: &mut Conn, tag: &Tag) -> Result<()> {
+        diesel::insert_or_ignore_into(record_tags::table)
+            .values((
+                record_tags::record_id.eq(self.id),
+                record_tags::tag_id.eq(tag.id),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn remove_tag(&self, conn: &mut Conn, tag: &Tag) -> Result<()> {
+        diesel::delete(record_tags::table)
+            .filter(record_tags::record_id.eq(self.id))
+            .filter(record_tags::tag_id.eq(tag.id))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
         records::table
             .find(id)
@@ -71,8 +136,79 @@ impl Record {
             .map_err(|e| Error::from_diesel_error(e, "Record", None))
     }
 
+    /// Total number of records, in all accounts
+    pub fn count(conn: &mut Conn) -> Result<i64> {
+        Ok(records::table.count().get_result(conn)?)
+    }
+
+    /// Number of records flagged as needing attention, in all accounts
+    pub fn count_flagged(conn: &mut Conn) -> Result<i64> {
+        Ok(records::table
+            .filter(records::flagged_at.is_not_null())
+            .count()
+            .get_result(conn)?)
+    }
+
+    /// Clear the flag of the records of the account up to the date included,
+    /// returning the number of records cleared
+    pub fn clear_flags(conn: &mut Conn, account_id: i64, until: NaiveDate) -> Result<usize> {
+        Ok(diesel::update(records::table)
+            .filter(records::account_id.eq(account_id))
+            .filter(records::operation_date.le(until))
+            .filter(records::flagged_at.is_not_null())
+            .set((
+                records::flagged_at.eq(None::<NaiveDateTime>),
+                records::flag_reason.eq(None::<String>),
+            ))
+            .execute(conn)?)
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.flagged_at.is_some()
+    }
+
+    /// Give the pending record its account, counting it in the balances and
+    /// stats from now on
+    pub fn assign(&mut self, conn: &mut Conn, account: &Account) -> Result<()> {
+        if !self.pending {
+            return Err(Error::Invalid(format!(
+                "Record #{} is not pending, it already has an account",
+                self.id
+            )));
+        }
+        new::validate_currency(self.currency, account)?;
+
+        conn.transaction(|conn| {
+            // Cached stats of the months the record enters
+            for date in [self.operation_date, self.value_date] {
+                crate::stats::MonthlyStats::invalidate(
+                    conn,
+                    date.year(),
+                    date.month() as i32,
+                    self.currency,
+                )?;
+            }
+            diesel::update(&*self)
+                .set((
+                    records::account_id.eq(account.id),
+                    records::pending.eq(false),
+                ))
+                .execute(conn)?;
+            Result::Ok(())
+        })?;
+
+        self.account_id = Some(account.id);
+        self.pending = false;
+        Ok(())
+    }
+
     pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        history::log_deleted(conn, [&*self])?;
+        diesel::delete(record_tags::table)
+            .filter(record_tags::record_id.eq(self.id))
+            .execute(conn)?;
         diesel::delete(&*self).execute(conn)?;
+        crate::stats::MonthlyStats::invalidate_date(conn, self.operation_date, self.currency)?;
 
         Ok(())
     }
@@ -95,12 +231,82 @@ pub(crate) fn clear_merchant_id(conn: &mut Conn, id: i64) -> Result<()> {
 }
 
 pub(crate) fn delete_by_account_id(conn: &mut Conn, id: i64) -> Result<()> {
+    if history::is_enabled(conn)? {
+        let deleted = records::table
+            .filter(records::account_id.eq(id))
+            .select(Record::as_select())
+            .load(conn)?;
+        history::log_deleted(conn, &deleted)?;
+    }
+    diesel::delete(record_tags::table)
+        .filter(
+            record_tags::record_id.eq_any(
+                records::table
+                    .filter(records::account_id.eq(id))
+                    .select(records::id),
+            ),
+        )
+        .execute(conn)?;
+    // Cached stats of the months the records leave
+    let months = records::table
+        .filter(records::account_id.eq(id))
+        .select((records::operation_date, records::currency))
+        .distinct()
+        .load::<(NaiveDate, db::Currency)>(conn)?;
     diesel::delete(records::table)
         .filter(records::account_id.eq(id))
         .execute(conn)?;
+    for (date, currency) in months {
+        crate::stats::MonthlyStats::invalidate_date(conn, date, currency.0)?;
+    }
     Ok(())
 }
 
+/// Records among the queried ones whose stored amount can't be read
+pub(crate) fn invalid_amounts(
+    conn: &mut Conn,
+    query: records::BoxedQuery<'_, Sqlite>,
+) -> Result<Vec<RowError>> {
+    Ok(query
+        .filter(db::type_of(records::amount).ne("integer"))
+        .select((records::id, sql::<Text>("CAST(records.amount AS TEXT)")))
+        .order_by(records::id)
+        .load::<(i64, String)>(conn)?
+        .into_iter()
+        .map(|(record_id, value)| RowError { record_id, value })
+        .collect())
+}
+
+/// Number of records among the queried ones in another currency than the
+/// given one, by currency
+///
+/// Boxed queries can't be grouped, so the query is built again to count the
+/// records of each of the other currencies.
+pub(crate) fn other_currencies<'a, F>(
+    conn: &mut Conn,
+    query: F,
+    currency: Currency,
+) -> Result<Vec<(Currency, i64)>>
+where
+    F: Fn() -> records::BoxedQuery<'a, Sqlite>,
+{
+    query()
+        .filter(records::currency.ne(db::Currency::from(currency)))
+        .select(records::currency)
+        .distinct()
+        .order_by(records::currency)
+        .load::<db::Currency>(conn)?
+        .into_iter()
+        .map(|other| {
+            let count = query()
+                .filter(records::currency.eq(other))
+                .count()
+                .get_result::<i64>(conn)?;
+            Ok((other.0, count))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,4 +406,266 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn tags() -> Result<()> {
+        let db = &mut test::db()?;
+        let account = test::account!(db, "Cash");
+        let foo = test::tag!(db, "foo");
+        let bar = test::tag!(db, "bar");
+
+        let mut record_1 = test::record!(db, &account);
+        let record_2 = test::record!(db, &account);
+
+        record_1.add_tag(db, &foo)?;
+        record_1.add_tag(db, &foo)?;
+        record_1.add_tag(db, &bar)?;
+        record_2.add_tag(db, &foo)?;
+
+        let names = |tags: Vec<Tag>| tags.into_iter().map(|t| t.name).collect::<Vec<_>>();
+        assert_eq!(vec!["bar", "foo"], names(record_1.fetch_tags(db)?));
+
+        let tag_ids = [foo.id, bar.id];
+        let records = QueryRecord {
+            tag_ids: Some(&tag_ids),
+            ..Default::default()
+        }
+        .run(db)?;
+        assert_eq!(1, records.len());
+        assert_eq!(record_1.id, records[0].id);
+
+        record_1.remove_tag(db, &bar)?;
+        assert_eq!(vec!["foo"], names(record_1.fetch_tags(db)?));
+
+        record_1.delete(db)?;
+        assert_eq!(vec!["foo"], names(record_2.fetch_tags(db)?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn flags() -> Result<()> {
+        let db = &mut test::db()?;
+        let account = test::account!(db, "Cash");
+        let mut record_1 = test::record!(db, &account);
+        let record_2 = test::record!(db, &account);
+        assert!(!record_1.is_flagged());
+
+        let flagged_at = chrono::Utc::now().naive_utc();
+        ChangeRecord {
+            flagged_at: Some(Some(flagged_at)),
+            flag_reason: Some(Some("check with bank")),
+            ..Default::default()
+        }
+        .apply(db, &mut record_1)?;
+        assert_eq!(Some("check with bank"), record_1.flag_reason.as_deref());
+
+        // Other changes keep the flag
+        ChangeRecord {
+            details: Some("Groceries"),
+            ..Default::default()
+        }
+        .save(db, &record_1)?;
+        record_1.reload(db)?;
+        assert!(record_1.is_flagged());
+        assert_eq!(Some("check with bank"), record_1.flag_reason.as_deref());
+
+        let records = QueryRecord {
+            flagged: true,
+            ..Default::default()
+        }
+        .run(db)?;
+        assert_eq!(vec![record_1.id], records.iter().map(|r| r.id).collect::<Vec<_>>());
+        assert_eq!(1, Record::count_flagged(db)?);
+
+        ChangeRecord {
+            flagged_at: Some(None),
+            flag_reason: Some(None),
+            ..Default::default()
+        }
+        .apply(db, &mut record_1)?;
+        assert!(!record_1.reload(db)?.is_flagged());
+        assert_eq!(None, record_1.flag_reason);
+        assert_eq!(0, Record::count_flagged(db)?);
+        assert_eq!(2, Record::count(db)?);
+        assert!(!record_2.is_flagged());
+
+        Ok(())
+    }
+
+    #[test]
+    fn original_amount() -> Result<()> {
+        let db = &mut test::db()?;
+        let account = test::account!(db, "Cash");
+        let original = |amount: i64, currency| NewRecord {
+            original_amount: Some(Decimal::from(amount)),
+            original_currency: currency,
+            ..NewRecord::new(&account)
+        };
+
+        assert!(original(10, Some(Currency::EUR)).save(db).is_err());
+        assert!(original(0, Some(Currency::USD)).save(db).is_err());
+        assert!(original(10, None).save(db).is_err());
+
+        let mut record = original(12, Some(Currency::USD)).save(db)?;
+        let plain = test::record!(db, &account);
+        assert_eq!(
+            Some(Amount(Decimal::from(12), Currency::USD)),
+            record.reload(db)?.original_amount()
+        );
+        assert_eq!(None, plain.original_amount());
+
+        let records = QueryRecord {
+            original_currency: Some(Currency::USD),
+            ..Default::default()
+        }
+        .run(db)?;
+        assert_eq!(vec![record.id], records.iter().map(|r| r.id).collect::<Vec<_>>());
+
+        // Changing only the currency is checked against the stored amount
+        assert!(ChangeRecord {
+            original_currency: Some(Some(Currency::EUR)),
+            ..Default::default()
+        }
+        .apply(db, &mut record)
+        .is_err());
+        ChangeRecord {
+            original_currency: Some(Some(Currency::GBP)),
+            ..Default::default()
+        }
+        .apply(db, &mut record)?;
+        assert_eq!(Some(Currency::GBP), record.reload(db)?.original_currency);
+
+        assert!(ChangeRecord {
+            original_amount: Some(None),
+            ..Default::default()
+        }
+        .apply(db, &mut record)
+        .is_err());
+        ChangeRecord {
+            original_amount: Some(None),
+            original_currency: Some(None),
+            ..Default::default()
+        }
+        .apply(db, &mut record)?;
+        assert_eq!(None, record.reload(db)?.original_amount());
+
+        Ok(())
+    }
+
+    #[test]
+    fn inverted_dates() -> Result<()> {
+        let db = &mut test::db()?;
+        let account = test::account!(db, "Cash");
+        let date = |d| NaiveDate::from_ymd_opt(2024, 9, d).unwrap();
+
+        let inverted = NewRecord {
+            operation_date: date(10),
+            value_date: date(9),
+            ..NewRecord::new(&account)
+        };
+        assert!(inverted.save(db).is_err());
+
+        let mut record = NewRecord {
+            operation_date: date(10),
+            value_date: date(9),
+            allow_inverted_dates: true,
+            ..NewRecord::new(&account)
+        }
+        .save(db)?;
+
+        // Already inverted dates don't prevent other changes
+        ChangeRecord {
+            details: Some("Groceries"),
+            ..Default::default()
+        }
+        .apply(db, &mut record)?;
+
+        let change = |value_date| ChangeRecord {
+            value_date: Some(value_date),
+            ..Default::default()
+        };
+        assert!(change(date(8)).apply(db, &mut record).is_err());
+        change(date(11)).apply(db, &mut record)?;
+        assert_eq!(date(11), record.value_date);
+
+        assert!(change::ViolatingChangeRecord {
+            operation_date: Some(date(12)),
+            ..Default::default()
+        }
+        .apply(db, &mut record)
+        .is_err());
+        change::ViolatingChangeRecord {
+            operation_date: Some(date(12)),
+            allow_inverted_dates: true,
+            ..Default::default()
+        }
+        .apply(db, &mut record)?;
+        assert_eq!(date(12), record.operation_date);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pending() -> Result<()> {
+        let db = &mut test::db()?;
+        let cash = test::account!(db, "Cash");
+        let dollars = test::account!(db, "Dollars", currency: Currency::USD);
+        let date = |d| NaiveDate::from_ymd_opt(2024, 9, d).unwrap();
+        let month = date(1)..date(30) + chrono::Days::new(1);
+        let debits = |db: &mut Conn| -> Result<Decimal> {
+            Ok(crate::stats::CategoriesStats::from_date_range_and_currency(
+                db,
+                month.clone(),
+                Currency::EUR,
+            )?
+            .stats
+            .iter()
+            .map(|stats| stats.amount)
+            .sum())
+        };
+
+        test::record!(db, &cash, amount: Decimal::from(5), operation_date: date(2), value_date: date(2));
+        let mut record = NewRecord {
+            amount: Decimal::from(12),
+            operation_date: date(3),
+            value_date: date(3),
+            ..NewRecord::pending(Currency::EUR)
+        }
+        .save(db)?;
+        assert!(record.pending);
+        assert_eq!(None, record.account_id);
+
+        // Left out of the balances, stats and lists
+        assert_eq!(Decimal::from(-5), cash.balance_at(db, date(30))?);
+        assert_eq!(Decimal::from(5), debits(db)?);
+        assert_eq!(1, QueryRecord::default().run(db)?.len());
+        let pending = QueryRecord {
+            pending: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![record.id],
+            pending.run(db)?.iter().map(|r| r.id).collect::<Vec<_>>()
+        );
+
+        // Only in the currency of the account
+        assert!(record.assign(db, &dollars).is_err());
+        record.assign(db, &cash)?;
+        assert!(!record.reload(db)?.pending);
+        assert_eq!(Some(cash.id), record.account_id);
+        assert!(record.assign(db, &cash).is_err());
+
+        assert_eq!(Decimal::from(-17), cash.balance_at(db, date(30))?);
+        assert_eq!(Decimal::from(17), debits(db)?);
+        assert!(pending.run(db)?.is_empty());
+
+        // A record with an account can't be pending
+        assert!(diesel::update(&record)
+            .set(records::pending.eq(true))
+            .execute(db)
+            .is_err());
+
+        Ok(())
+    }
 }
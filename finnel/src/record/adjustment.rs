@@ -0,0 +1,127 @@
+use crate::{category::NewCategory, prelude::*, record::NewRecord};
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// Name of the category of the adjustment records, created excluded from
+/// the stats the first time it is needed
+pub const CATEGORY: &str = "Balance adjustment";
+
+/// Record bringing the computed balance of an account to the actual one,
+/// e.g. when the cash in hand drifted from the records
+pub struct NewBalanceAdjustment<'a> {
+    pub account: &'a Account,
+    /// Actual balance of the account
+    pub balance: Decimal,
+    /// Date of the record, counting the records up to this one included
+    pub date: NaiveDate,
+    pub details: Option<&'a str>,
+}
+
+impl<'a> NewBalanceAdjustment<'a> {
+    pub fn new(account: &'a Account, balance: Decimal) -> Self {
+        Self {
+            account,
+            balance,
+            date: chrono::Utc::now().date_naive(),
+            details: None,
+        }
+    }
+
+    /// Amount to credit the account with, negative for a debit
+    pub fn difference(&self, conn: &mut Conn) -> Result<Decimal> {
+        Ok(self.balance - self.account.balance_at(conn, self.date)?)
+    }
+
+    /// Save the record, unless the balance is already the actual one
+    pub fn save(self, conn: &mut Conn) -> Result<Option<Record>> {
+        conn.transaction(|conn| {
+            let difference = self.difference(conn)?;
+            if difference.is_zero() {
+                return Ok(None);
+            }
+
+            let category = category(conn)?;
+            NewRecord {
+                amount: difference.abs(),
+                operation_date: self.date,
+                value_date: self.date,
+                direction: match difference.is_sign_negative() {
+                    true => Direction::Debit,
+                    false => Direction::Credit,
+                },
+                details: self.details.unwrap_or(CATEGORY),
+                category: Some(&category),
+                ..NewRecord::new(self.account)
+            }
+            .save(conn)
+            .map(Some)
+        })
+    }
+}
+
+fn category(conn: &mut Conn) -> Result<Category> {
+    match Category::find_by_name(conn, CATEGORY) {
+        Ok(category) => category.resolve(conn),
+        Err(e) if e.is_not_found() => NewCategory {
+            exclude_from_stats: Some(true),
+            ..NewCategory::new(CATEGORY)
+        }
+        .save(conn),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn save() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::account!(conn, "Cash");
+        let date = |day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap();
+        test::record!(conn, &cash, amount: Decimal::new(30, 0), operation_date: date(1));
+
+        // Less cash than recorded
+        let debit = NewBalanceAdjustment {
+            date: date(10),
+            ..NewBalanceAdjustment::new(&cash, Decimal::new(-50, 0))
+        }
+        .save(conn)?
+        .unwrap();
+        assert_eq!(Decimal::new(20, 0), debit.amount);
+        assert_eq!(Direction::Debit, debit.direction);
+        assert_eq!(date(10), debit.operation_date);
+        assert_eq!(CATEGORY, debit.details);
+        let category = Category::find(conn, debit.category_id.unwrap())?;
+        assert_eq!(CATEGORY, category.name);
+        assert_eq!(Some(true), category.exclude_from_stats);
+        assert_eq!(Decimal::new(-50, 0), cash.balance_at(conn, date(10))?);
+
+        // More cash than recorded, reusing the category
+        let credit = NewBalanceAdjustment {
+            date: date(12),
+            details: Some("Found in a coat"),
+            ..NewBalanceAdjustment::new(&cash, Decimal::new(5, 0))
+        }
+        .save(conn)?
+        .unwrap();
+        assert_eq!(Decimal::new(55, 0), credit.amount);
+        assert_eq!(Direction::Credit, credit.direction);
+        assert_eq!("Found in a coat", credit.details);
+        assert_eq!(debit.category_id, credit.category_id);
+        assert_eq!(Decimal::new(5, 0), cash.balance_at(conn, date(12))?);
+
+        // Nothing to adjust
+        let adjustment = NewBalanceAdjustment {
+            date: date(12),
+            ..NewBalanceAdjustment::new(&cash, Decimal::new(5, 0))
+        };
+        assert_eq!(Decimal::ZERO, adjustment.difference(conn)?);
+        assert!(adjustment.save(conn)?.is_none());
+
+        Ok(())
+    }
+}
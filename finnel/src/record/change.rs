@@ -1,10 +1,14 @@
 use crate::{
+    diff::{self, FieldDiff},
     prelude::*,
+    record::history::Changes,
+    record::new::{validate_dates, validate_original},
     resolved::{mapmapmap, mapmapresolve},
     schema::records,
+    stats::MonthlyStats,
 };
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 
 #[derive(Default, Clone)]
@@ -13,6 +17,12 @@ pub struct ChangeRecord<'a> {
     pub details: Option<&'a str>,
     pub category: Option<Option<&'a Category>>,
     pub merchant: Option<Option<&'a Merchant>>,
+    pub flagged_at: Option<Option<NaiveDateTime>>,
+    pub flag_reason: Option<Option<&'a str>>,
+    pub original_amount: Option<Option<Decimal>>,
+    pub original_currency: Option<Option<Currency>>,
+    /// Accept a value date before the operation date
+    pub allow_inverted_dates: bool,
 }
 
 impl<'a> ChangeRecord<'a> {
@@ -34,6 +44,11 @@ impl<'a> ChangeRecord<'a> {
             details: self.details,
             category: self.category,
             merchant: self.merchant,
+            flagged_at: self.flagged_at,
+            flag_reason: self.flag_reason,
+            original_amount: self.original_amount,
+            original_currency: self.original_currency,
+            allow_inverted_dates: self.allow_inverted_dates,
             ..Default::default()
         }
     }
@@ -50,6 +65,12 @@ pub struct ViolatingChangeRecord<'a> {
     pub details: Option<&'a str>,
     pub category: Option<Option<&'a Category>>,
     pub merchant: Option<Option<&'a Merchant>>,
+    pub flagged_at: Option<Option<NaiveDateTime>>,
+    pub flag_reason: Option<Option<&'a str>>,
+    pub original_amount: Option<Option<Decimal>>,
+    pub original_currency: Option<Option<Currency>>,
+    /// Accept a value date before the operation date
+    pub allow_inverted_dates: bool,
 }
 
 impl<'a> ViolatingChangeRecord<'a> {
@@ -86,6 +107,18 @@ impl<'a> ViolatingChangeRecord<'a> {
         if let Some(value) = changeset.merchant_id {
             record.merchant_id = value;
         }
+        if let Some(value) = changeset.flagged_at {
+            record.flagged_at = value;
+        }
+        if let Some(value) = changeset.flag_reason {
+            record.flag_reason = value.map(str::to_string);
+        }
+        if let Some(value) = changeset.original_amount {
+            record.original_amount = value.map(|amount| amount.0);
+        }
+        if let Some(value) = changeset.original_currency {
+            record.original_currency = value.map(|currency| currency.0);
+        }
 
         Ok(())
     }
@@ -100,6 +133,11 @@ impl<'a> ViolatingChangeRecord<'a> {
             details: self.details,
             category: mapmapresolve(conn, self.category)?,
             merchant: mapmapresolve(conn, self.merchant)?,
+            flagged_at: self.flagged_at,
+            flag_reason: self.flag_reason,
+            original_amount: self.original_amount,
+            original_currency: self.original_currency,
+            allow_inverted_dates: self.allow_inverted_dates,
         })
     }
 }
@@ -113,6 +151,11 @@ pub struct ResolvedChangeRecord<'a> {
     pub details: Option<&'a str>,
     pub category: Option<Option<Resolved<'a, Category>>>,
     pub merchant: Option<Option<Resolved<'a, Merchant>>>,
+    pub flagged_at: Option<Option<NaiveDateTime>>,
+    pub flag_reason: Option<Option<&'a str>>,
+    pub original_amount: Option<Option<Decimal>>,
+    pub original_currency: Option<Option<Currency>>,
+    pub allow_inverted_dates: bool,
 }
 
 impl<'a> ResolvedChangeRecord<'a> {
@@ -121,11 +164,50 @@ impl<'a> ResolvedChangeRecord<'a> {
         _conn: &mut Conn,
         record: &'a Record,
     ) -> Result<ValidatedChangeRecord<'a>> {
-        // nothing to do?
+        // Only check the dates being changed, the record may already have
+        // been saved with inverted ones
+        let changes_dates = self.operation_date.is_some() || self.value_date.is_some();
+        if changes_dates && !self.allow_inverted_dates {
+            validate_dates(
+                self.operation_date.unwrap_or(record.operation_date),
+                self.value_date.unwrap_or(record.value_date),
+            )?;
+        }
+        if self.original_amount.is_some() || self.original_currency.is_some() {
+            validate_original(
+                self.original_amount.unwrap_or(record.original_amount),
+                self.original_currency.unwrap_or(record.original_currency),
+                record.currency,
+            )?;
+        }
 
         Ok(ValidatedChangeRecord(record, self.as_changeset()))
     }
 
+    /// Fields of the record the change would modify, leaving out the ones
+    /// keeping their value, with the category and merchant by name
+    pub fn diff(&self, conn: &mut Conn, record: &Record) -> Result<Vec<FieldDiff>> {
+        let changeset = RecordChangeset {
+            category_id: None,
+            merchant_id: None,
+            ..self.as_changeset()
+        };
+        let mut fields = ValidatedChangeRecord(record, changeset).changes().0;
+
+        if let Some(category) = &self.category {
+            let old = diff::category_name(conn, record.category_id)?;
+            let new = category.as_ref().map(|c| c.map(|c| c.name.clone()));
+            diff::push(&mut fields, "category", old, new);
+        }
+        if let Some(merchant) = &self.merchant {
+            let old = diff::merchant_name(conn, record.merchant_id)?;
+            let new = merchant.as_ref().map(|m| m.map(|m| m.name.clone()));
+            diff::push(&mut fields, "merchant", old, new);
+        }
+
+        Ok(fields)
+    }
+
     pub fn as_changeset(&self) -> RecordChangeset<'a> {
         RecordChangeset {
             amount: self.amount,
@@ -136,6 +218,14 @@ impl<'a> ResolvedChangeRecord<'a> {
             details: self.details,
             category_id: mapmapmap(&self.category, |c| c.id),
             merchant_id: mapmapmap(&self.merchant, |m| m.id),
+            flagged_at: self.flagged_at,
+            flag_reason: self.flag_reason,
+            original_amount: self
+                .original_amount
+                .map(|amount| amount.map(db::Decimal::from)),
+            original_currency: self
+                .original_currency
+                .map(|currency| currency.map(db::Currency::from)),
         }
     }
 }
@@ -144,8 +234,70 @@ pub struct ValidatedChangeRecord<'a>(&'a Record, RecordChangeset<'a>);
 
 impl<'a> ValidatedChangeRecord<'a> {
     pub fn save(self, conn: &mut Conn) -> Result<()> {
+        let changes = self.changes();
+        // Cached stats of the months the record leaves and enters
+        let (record, operation_date) = (self.0, self.1.operation_date);
         diesel::update(self.0).set(self.1).execute(conn)?;
-        Ok(())
+        for date in [Some(record.operation_date), operation_date]
+            .into_iter()
+            .flatten()
+        {
+            MonthlyStats::invalidate_date(conn, date, record.currency)?;
+        }
+        changes.log(conn, record.id)
+    }
+
+    /// Fields of the record actually changed, for its history
+    fn changes(&self) -> Changes {
+        let (record, changeset) = (self.0, &self.1);
+        let mut changes = Changes::default();
+
+        if let Some(amount) = changeset.amount {
+            changes.push("amount", Some(record.amount), Some(amount));
+        }
+        if let Some(date) = changeset.operation_date {
+            changes.push("operation_date", Some(record.operation_date), Some(date));
+        }
+        if let Some(date) = changeset.value_date {
+            changes.push("value_date", Some(record.value_date), Some(date));
+        }
+        if let Some(direction) = changeset.direction {
+            changes.push("direction", Some(record.direction), Some(direction));
+        }
+        if let Some(mode) = changeset.mode {
+            changes.push("mode", Some(record.mode), Some(mode));
+        }
+        if let Some(details) = changeset.details {
+            changes.push("details", Some(record.details.as_str()), Some(details));
+        }
+        if let Some(category_id) = changeset.category_id {
+            changes.push("category_id", record.category_id, category_id);
+        }
+        if let Some(merchant_id) = changeset.merchant_id {
+            changes.push("merchant_id", record.merchant_id, merchant_id);
+        }
+        if let Some(flagged_at) = changeset.flagged_at {
+            changes.push("flagged_at", record.flagged_at, flagged_at);
+        }
+        if let Some(flag_reason) = changeset.flag_reason {
+            changes.push("flag_reason", record.flag_reason.as_deref(), flag_reason);
+        }
+        if let Some(original_amount) = &changeset.original_amount {
+            changes.push(
+                "original_amount",
+                record.original_amount,
+                original_amount.as_ref().map(|amount| amount.0),
+            );
+        }
+        if let Some(original_currency) = &changeset.original_currency {
+            changes.push(
+                "original_currency",
+                record.original_currency.map(|c| c.code()),
+                original_currency.as_ref().map(|c| c.0.code()),
+            );
+        }
+
+        changes
     }
 }
 
@@ -161,4 +313,50 @@ pub struct RecordChangeset<'a> {
     pub details: Option<&'a str>,
     pub category_id: Option<Option<i64>>,
     pub merchant_id: Option<Option<i64>>,
+    pub flagged_at: Option<Option<NaiveDateTime>>,
+    pub flag_reason: Option<Option<&'a str>>,
+    pub original_amount: Option<Option<db::Decimal>>,
+    pub original_currency: Option<Option<db::Currency>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn diff() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+        let food = test::category!(conn, "Food");
+        let chariot = test::merchant!(conn, "Chariot");
+        let date = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let record = test::record!(
+            conn,
+            account,
+            details: "coffee",
+            operation_date: date(1),
+            value_date: date(1),
+            category: Some(&food)
+        );
+
+        let change = ChangeRecord {
+            details: Some("coffee"),
+            value_date: Some(date(3)),
+            category: Some(None),
+            merchant: Some(Some(&chariot)),
+            ..Default::default()
+        };
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(
+            vec![
+                ("value_date", some("2024-09-01"), some("2024-09-03")),
+                ("category", some("Food"), None),
+                ("merchant", None, some("Chariot")),
+            ],
+            change.into_resolved(conn)?.diff(conn, &record)?
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,90 @@
+use crate::db::julianday;
+use crate::prelude::*;
+use crate::schema::records;
+
+/// Ids of the pairs of records of the same account, with the same amount and
+/// direction and whose operation dates are at most `days` apart, the record
+/// with the lowest id first
+///
+/// Comparing the details is left to the caller, this only narrows the pairs
+/// down to those worth a closer look.
+pub fn candidates(conn: &mut Conn, days: i64, account_id: Option<i64>) -> Result<Vec<(i64, i64)>> {
+    let (first, second) = diesel::alias!(records as first, records as second);
+
+    let mut query = first
+        .inner_join(
+            second.on(second
+                .field(records::account_id)
+                .eq(first.field(records::account_id))),
+        )
+        .filter(second.field(records::id).gt(first.field(records::id)))
+        .filter(
+            second
+                .field(records::amount)
+                .eq(first.field(records::amount)),
+        )
+        .filter(
+            second
+                .field(records::currency)
+                .eq(first.field(records::currency)),
+        )
+        .filter(
+            second
+                .field(records::direction)
+                .eq(first.field(records::direction)),
+        )
+        .filter(
+            (julianday(second.field(records::operation_date))
+                - julianday(first.field(records::operation_date)))
+            .between(-(days as f64), days as f64),
+        )
+        .select((first.field(records::id), second.field(records::id)))
+        .order((first.field(records::id), second.field(records::id)))
+        .into_boxed();
+
+    if let Some(id) = account_id {
+        query = query.filter(first.field(records::account_id).eq(id));
+    }
+
+    Ok(query.load(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn candidates() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::account!(conn, "Cash");
+        let bank = test::account!(conn, "Bank");
+        let date = |day| chrono::NaiveDate::from_ymd_opt(2024, 8, day).unwrap();
+
+        let r1 = test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(1));
+        let r2 = test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(3));
+        // Too late
+        test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(10));
+        // Other amount
+        test::record!(conn, &cash, amount: Decimal::ONE, operation_date: date(1));
+        // Other direction
+        test::record!(conn, &cash, amount: Decimal::TEN, operation_date: date(1),
+            direction: Direction::Credit);
+        // Other account
+        let r6 = test::record!(conn, &bank, amount: Decimal::TEN, operation_date: date(2));
+        let r7 = test::record!(conn, &bank, amount: Decimal::TEN, operation_date: date(2));
+
+        assert_eq!(
+            vec![(r1.id, r2.id), (r6.id, r7.id)],
+            super::candidates(conn, 3, None)?
+        );
+        assert_eq!(vec![(r6.id, r7.id)], super::candidates(conn, 0, None)?);
+        assert_eq!(
+            vec![(r1.id, r2.id)],
+            super::candidates(conn, 3, Some(cash.id))?
+        );
+        assert!(super::candidates(conn, 1, Some(cash.id))?.is_empty());
+
+        Ok(())
+    }
+}
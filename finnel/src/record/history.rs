@@ -0,0 +1,230 @@
+use crate::{
+    diff::FieldDiff,
+    essentials::*,
+    record::Record,
+    schema::{metadata, record_changes},
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{prelude::*, OptionalExtension};
+
+/// Key of the metadata enabling the history, off unless set to `true`
+const AUDIT_ENABLED: &str = "audit_enabled";
+
+/// Field of the entry logged when the record is deleted
+pub const DELETED: &str = "deleted";
+
+/// One field of a record changed at some point, or its deletion
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = record_changes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RecordChange {
+    pub id: i64,
+    pub record_id: i64,
+    pub changed_at: NaiveDateTime,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl RecordChange {
+    /// Changes of the record, oldest first
+    pub fn for_record(conn: &mut Conn, record_id: i64) -> Result<Vec<Self>> {
+        Ok(record_changes::table
+            .filter(record_changes::record_id.eq(record_id))
+            .order(record_changes::id.asc())
+            .select(RecordChange::as_select())
+            .load(conn)?)
+    }
+}
+
+/// Whether the changes of the records are logged
+pub fn is_enabled(conn: &mut Conn) -> Result<bool> {
+    Ok(metadata::table
+        .find(AUDIT_ENABLED)
+        .select(metadata::value)
+        .first::<String>(conn)
+        .optional()?
+        .is_some_and(|value| value == "true"))
+}
+
+pub fn set_enabled(conn: &mut Conn, enabled: bool) -> Result<()> {
+    diesel::replace_into(metadata::table)
+        .values((
+            metadata::key.eq(AUDIT_ENABLED),
+            metadata::value.eq(enabled.to_string()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Changes of a record's fields as `(field, old value, new value)`, waiting
+/// to be logged
+#[derive(Debug, Default)]
+pub(crate) struct Changes(pub Vec<FieldDiff>);
+
+impl Changes {
+    /// Add the change of the field, unless the value is the same
+    pub fn push<T: ToString + PartialEq>(
+        &mut self,
+        field: &'static str,
+        old: Option<T>,
+        new: Option<T>,
+    ) {
+        crate::diff::push(&mut self.0, field, old, new);
+    }
+
+    /// Insert the changes in the history of the record, when it's enabled
+    pub fn log(self, conn: &mut Conn, record_id: i64) -> Result<()> {
+        if self.0.is_empty() || !is_enabled(conn)? {
+            return Ok(());
+        }
+
+        let changed_at = Utc::now().naive_utc();
+        let rows = self
+            .0
+            .into_iter()
+            .map(|(field, old_value, new_value)| {
+                (
+                    record_changes::record_id.eq(record_id),
+                    record_changes::changed_at.eq(changed_at),
+                    record_changes::field.eq(field),
+                    record_changes::old_value.eq(old_value),
+                    record_changes::new_value.eq(new_value),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(record_changes::table)
+            .values(rows)
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+/// Log the deletion of the records, describing what they were
+pub(crate) fn log_deleted<'a>(
+    conn: &mut Conn,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> Result<()> {
+    if !is_enabled(conn)? {
+        return Ok(());
+    }
+
+    for record in records {
+        let mut changes = Changes::default();
+        changes.push(
+            DELETED,
+            Some(format!(
+                "{} {} {} {}",
+                record.operation_date,
+                record.direction,
+                Amount(record.amount, record.currency),
+                record.details
+            )),
+            None,
+        );
+        changes.log(conn, record.id)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{ChangeRecord, SplitRecord};
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    fn fields(conn: &mut Conn, record_id: i64) -> Result<Vec<(String, Option<String>)>> {
+        Ok(RecordChange::for_record(conn, record_id)?
+            .into_iter()
+            .map(|change| (change.field, change.new_value))
+            .collect())
+    }
+
+    #[test]
+    fn disabled() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let mut record = test::record!(conn, &account);
+
+        assert!(!is_enabled(conn)?);
+        ChangeRecord {
+            details: Some("Groceries"),
+            ..Default::default()
+        }
+        .save(conn, &record)?;
+        record.delete(conn)?;
+
+        assert!(RecordChange::for_record(conn, record.id)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn history() -> Result<()> {
+        let conn = &mut test::db()?;
+        set_enabled(conn, true)?;
+        assert!(is_enabled(conn)?);
+
+        let account = test::account!(conn, "Cash");
+        let category = test::category!(conn, "Food");
+        let mut record = test::record!(
+            conn,
+            &account,
+            amount: Decimal::new(10, 0),
+            details: "Bread",
+            operation_date: chrono::NaiveDate::from_ymd_opt(2024, 7, 30).unwrap()
+        );
+
+        ChangeRecord {
+            details: Some("Baguette"),
+            category: Some(Some(&category)),
+            ..Default::default()
+        }
+        .apply(conn, &mut record)?;
+        // Unchanged values are left out
+        ChangeRecord {
+            details: Some("Baguette"),
+            ..Default::default()
+        }
+        .save(conn, &record)?;
+
+        let mut split = SplitRecord {
+            amount: Decimal::new(4, 0),
+            ..Default::default()
+        }
+        .apply(conn, &mut record)?;
+
+        let changes = RecordChange::for_record(conn, record.id)?;
+        assert_eq!(Some("Bread"), changes[0].old_value.as_deref());
+        assert_eq!(
+            vec![
+                ("details".to_owned(), Some("Baguette".to_owned())),
+                ("category_id".to_owned(), Some(category.id.to_string())),
+                ("amount".to_owned(), Some("6.000".to_owned())),
+                ("split".to_owned(), Some(split.id.to_string())),
+            ],
+            fields(conn, record.id)?
+        );
+        assert_eq!(
+            vec![("split_from".to_owned(), Some(record.id.to_string()))],
+            fields(conn, split.id)?
+        );
+
+        split.delete(conn)?;
+        let changes = RecordChange::for_record(conn, split.id)?;
+        assert_eq!(DELETED, changes[1].field);
+        assert_eq!(
+            Some("2024-07-30 Debit € 4.00 Baguette"),
+            changes[1].old_value.as_deref()
+        );
+
+        set_enabled(conn, false)?;
+        record.delete(conn)?;
+        assert_eq!(4, RecordChange::for_record(conn, record.id)?.len());
+
+        Ok(())
+    }
+}
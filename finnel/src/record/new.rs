@@ -1,14 +1,19 @@
 use crate::{
+    category::{CategoryRule, CompiledRules},
     prelude::*,
     resolved::{mapmap, mapresolve},
     schema::records,
+    stats::MonthlyStats,
 };
 
 use chrono::NaiveDate;
 use diesel::prelude::*;
 
 pub struct NewRecord<'a> {
-    pub account: &'a Account,
+    /// None for a pending record, waiting for an account to be assigned
+    pub account: Option<&'a Account>,
+    /// Currency of the account, or of the pending record
+    pub currency: Currency,
     pub amount: Decimal,
     pub operation_date: NaiveDate,
     pub value_date: NaiveDate,
@@ -17,14 +22,33 @@ pub struct NewRecord<'a> {
     pub details: &'a str,
     pub category: Option<&'a Category>,
     pub merchant: Option<&'a Merchant>,
+    /// Amount of a foreign currency transaction, before its conversion to
+    /// the currency of the account
+    pub original_amount: Option<Decimal>,
+    pub original_currency: Option<Currency>,
+    /// Accept a value date before the operation date, which some banks use
+    pub allow_inverted_dates: bool,
+    /// Rules giving the category of the record created without one, loaded
+    /// for this record only when None
+    pub rules: Option<&'a CompiledRules>,
 }
 
 impl<'a> NewRecord<'a> {
     pub fn new(account: &'a Account) -> Self {
+        Self {
+            account: Some(account),
+            ..Self::pending(account.currency)
+        }
+    }
+
+    /// Record whose account is yet to be decided, left out of the balances
+    /// and stats until it's assigned one
+    pub fn pending(currency: Currency) -> Self {
         let date = chrono::Utc::now().date_naive();
 
         Self {
-            account,
+            account: None,
+            currency,
             amount: Decimal::ZERO,
             operation_date: date,
             value_date: date,
@@ -33,6 +57,10 @@ impl<'a> NewRecord<'a> {
             details: "",
             category: None,
             merchant: None,
+            original_amount: None,
+            original_currency: None,
+            allow_inverted_dates: false,
+            rules: None,
         }
     }
 
@@ -41,22 +69,49 @@ impl<'a> NewRecord<'a> {
     }
 
     pub fn into_resolved(self, conn: &mut Conn) -> Result<ResolvedNewRecord<'a>> {
+        let category = match self.category {
+            Some(category) => Some(category.as_resolved(conn)?),
+            None => self
+                .default_category(conn)?
+                .map(|category| category.resolve(conn).map(Resolved::Replacer))
+                .transpose()?,
+        };
+
         Ok(ResolvedNewRecord {
             account: self.account,
+            currency: self.currency,
             amount: self.amount,
             operation_date: self.operation_date,
             value_date: self.value_date,
             direction: self.direction,
             mode: self.mode,
             details: self.details,
-            category: mapresolve(conn, self.category)?,
+            category,
             merchant: mapresolve(conn, self.merchant)?,
+            original_amount: self.original_amount,
+            original_currency: self.original_currency,
+            allow_inverted_dates: self.allow_inverted_dates,
         })
     }
+
+    /// Category of the record created without one, from its merchant or its
+    /// details, then from its mode
+    fn default_category(&self, conn: &mut Conn) -> Result<Option<Category>> {
+        let merchant_id = self.merchant.map(|merchant| merchant.id);
+        let category = match self.rules {
+            Some(rules) => rules.categorize(conn, self.details, merchant_id)?,
+            None => CategoryRule::categorize(conn, self.details, merchant_id)?,
+        };
+        match category {
+            Some(category) => Ok(Some(category)),
+            None => CategoryRule::category_for(conn, self.mode, self.direction),
+        }
+    }
 }
 
 pub struct ResolvedNewRecord<'a> {
-    pub account: &'a Account,
+    pub account: Option<&'a Account>,
+    pub currency: Currency,
     pub amount: Decimal,
     pub operation_date: NaiveDate,
     pub value_date: NaiveDate,
@@ -65,18 +120,29 @@ pub struct ResolvedNewRecord<'a> {
     pub details: &'a str,
     pub category: Option<Resolved<'a, Category>>,
     pub merchant: Option<Resolved<'a, Merchant>>,
+    pub original_amount: Option<Decimal>,
+    pub original_currency: Option<Currency>,
+    pub allow_inverted_dates: bool,
 }
 
 impl<'a> ResolvedNewRecord<'a> {
     pub fn validate(&self, _conn: &mut Conn) -> Result<ValidatedNewRecord<'a>> {
+        if let Some(account) = self.account {
+            validate_currency(self.currency, account)?;
+        }
+        if !self.allow_inverted_dates {
+            validate_dates(self.operation_date, self.value_date)?;
+        }
+        validate_original(self.original_amount, self.original_currency, self.currency)?;
+
         Ok(ValidatedNewRecord(self.as_insertable()))
     }
 
     pub fn as_insertable(&self) -> InsertableRecord<'a> {
         InsertableRecord {
-            account_id: self.account.id,
+            account_id: self.account.map(|account| account.id),
             amount: self.amount,
-            currency: self.account.currency,
+            currency: self.currency,
             operation_date: self.operation_date,
             value_date: self.value_date,
             direction: self.direction,
@@ -84,14 +150,72 @@ impl<'a> ResolvedNewRecord<'a> {
             details: self.details,
             category_id: mapmap(&self.category, |c| c.id),
             merchant_id: mapmap(&self.merchant, |m| m.id),
+            original_amount: self.original_amount.map(db::Decimal::from),
+            original_currency: self.original_currency.map(db::Currency::from),
+            pending: self.account.is_none(),
         }
     }
 }
 
+/// Reject a record in another currency than the one of its account
+pub(crate) fn validate_currency(currency: Currency, account: &Account) -> Result<()> {
+    if currency != account.currency {
+        return Err(Error::Invalid(format!(
+            "Record in {} can't go to account {} in {}",
+            currency.code(),
+            account.name,
+            account.currency.code()
+        )));
+    }
+    Ok(())
+}
+
+/// Reject a value date before the operation date, usually a typo
+pub(crate) fn validate_dates(operation_date: NaiveDate, value_date: NaiveDate) -> Result<()> {
+    if value_date < operation_date {
+        return Err(Error::Invalid(format!(
+            "Value date {} is before operation date {}",
+            value_date, operation_date
+        )));
+    }
+    Ok(())
+}
+
+/// Reject an original amount without its currency or the other way around,
+/// a negative one, or one in the currency of the account
+pub(crate) fn validate_original(
+    amount: Option<Decimal>,
+    original_currency: Option<Currency>,
+    currency: Currency,
+) -> Result<()> {
+    match (amount, original_currency) {
+        (None, None) => Ok(()),
+        (Some(amount), Some(_)) if amount <= Decimal::ZERO => Err(Error::Invalid(format!(
+            "Original amount must be positive, not {amount}"
+        ))),
+        (Some(_), Some(original)) if original == currency => Err(Error::Invalid(format!(
+            "Original currency must differ from the account currency {}",
+            currency.code()
+        ))),
+        (Some(_), Some(_)) => Ok(()),
+        _ => Err(Error::Invalid(
+            "Original amount and currency must be given together".to_owned(),
+        )),
+    }
+}
+
 pub struct ValidatedNewRecord<'a>(InsertableRecord<'a>);
 
 impl<'a> ValidatedNewRecord<'a> {
     pub fn save(self, conn: &mut Conn) -> Result<Record> {
+        let record = self.save_keeping_stats(conn)?;
+        MonthlyStats::invalidate_date(conn, record.operation_date, record.currency)?;
+        Ok(record)
+    }
+
+    /// Save the record without forgetting the cached stats of its month, for
+    /// callers saving many records and forgetting them once per month instead
+    pub fn save_keeping_stats(self, conn: &mut Conn) -> Result<Record> {
         Ok(diesel::insert_into(records::table)
             .values(self.0)
             .returning(Record::as_returning())
@@ -102,7 +226,7 @@ impl<'a> ValidatedNewRecord<'a> {
 #[derive(Debug, Clone, Insertable)]
 #[diesel(table_name = records)]
 pub struct InsertableRecord<'a> {
-    pub account_id: i64,
+    pub account_id: Option<i64>,
     #[diesel(serialize_as = db::Decimal)]
     pub amount: Decimal,
     #[diesel(serialize_as = db::Currency)]
@@ -114,4 +238,7 @@ pub struct InsertableRecord<'a> {
     pub details: &'a str,
     pub category_id: Option<i64>,
     pub merchant_id: Option<i64>,
+    pub original_amount: Option<db::Decimal>,
+    pub original_currency: Option<db::Currency>,
+    pub pending: bool,
 }
@@ -1,12 +1,17 @@
 use std::marker::PhantomData;
 
 use crate::prelude::*;
-use crate::schema::{accounts, categories, merchants, records};
+use crate::result::RowError;
+use crate::schema::{accounts, categories, merchants, record_tags, records};
 
 use chrono::NaiveDate;
 
 use diesel::{
-    expression::SqlLiteral, helper_types::*, prelude::*, sql_types::BigInt, sqlite::Sqlite,
+    expression::SqlLiteral,
+    helper_types::*,
+    prelude::*,
+    sql_types::{BigInt, Nullable},
+    sqlite::Sqlite,
 };
 
 diesel::alias! {
@@ -28,9 +33,21 @@ pub enum OrderDirection {
     Desc,
 }
 
+/// Where to put NULL values when ordering by a nullable field
+#[derive(Debug, Default, Clone, Copy)]
+pub enum OrderNulls {
+    /// Let SQLite decide, i.e. first when ascending and last when descending
+    #[default]
+    Default,
+    First,
+    Last,
+}
+
 #[derive(Default)]
 pub struct QueryRecord<'a> {
     pub account_id: Option<i64>,
+    /// Only records of one of these accounts
+    pub account_ids: Option<&'a [i64]>,
     pub from: Option<NaiveDate>,
     pub to: Option<NaiveDate>,
     pub operation_date: bool,
@@ -42,8 +59,35 @@ pub struct QueryRecord<'a> {
     pub merchant_id: Option<Option<i64>>,
     pub category_id: Option<Option<i64>>,
     pub category_ids: Option<&'a [i64]>,
+    /// Leave out the records of these categories, keeping the ones without
+    /// category
+    pub exclude_category_ids: Option<&'a [i64]>,
+    /// Leave out the records of these merchants, keeping the ones without
+    /// merchant
+    pub exclude_merchant_ids: Option<&'a [i64]>,
+    pub exclude_mode: Option<Mode>,
+    /// Only records having all of these tags
+    pub tag_ids: Option<&'a [i64]>,
+    /// Only records flagged as needing attention
+    pub flagged: bool,
+    /// Only pending records, which are otherwise left out
+    pub pending: bool,
+    /// Only records of a transaction originally in this currency
+    pub original_currency: Option<Currency>,
+    /// Only records matching every whitespace separated term of the text,
+    /// in their details or the name of their merchant or category
+    pub text: Option<&'a str>,
+    /// Leave out records whose stored amount can't be read, instead of
+    /// failing to load them. See `invalid_amounts` to find them
+    pub skip_invalid: bool,
     pub count: Option<i64>,
-    pub order: Vec<(OrderField, OrderDirection)>,
+    /// Order first the records whose merchant has one of these names,
+    /// ignoring the case, before the other orders and the count
+    pub merchant_names_first: Option<&'a [String]>,
+    /// Number of records to skip, ordering them by id last so that
+    /// consecutive pages don't overlap
+    pub offset: Option<i64>,
+    pub order: Vec<(OrderField, OrderDirection, OrderNulls)>,
 }
 
 pub type RA = (Record, Account);
@@ -62,34 +106,77 @@ pub type RCC = (Record, Option<Category>, Option<Category>);
 pub type RCCM = (Record, Option<Category>, Option<Category>, Option<Merchant>);
 pub type RCM = (Record, Option<Category>, Option<Merchant>);
 
-type QueryType<'a> =
-    IntoBoxed<'a, Filter<records::table, Eq<records::account_id, SqlLiteral<BigInt>>>, Sqlite>;
+type QueryType<'a> = IntoBoxed<
+    'a,
+    Filter<records::table, Eq<records::account_id, SqlLiteral<Nullable<BigInt>>>>,
+    Sqlite,
+>;
 
 impl<'a> QueryRecord<'a> {
     fn sort_by_column<U>(
         query: QueryType<'a>,
         column: U,
         direction: &OrderDirection,
+        nulls: &OrderNulls,
     ) -> QueryType<'a>
     where
         U: 'a
             + ExpressionMethods
             + diesel::query_builder::QueryFragment<Sqlite>
             + AppearsOnTable<records::table>
-            + std::marker::Send,
+            + std::marker::Send
+            + Copy,
     {
+        // SQLite has NULL values smaller than anything else, so order by
+        // whether the column IS NULL first to put them where requested
+        let query = match nulls {
+            OrderNulls::Default => query,
+            OrderNulls::First => query.then_order_by(column.is_null().desc()),
+            OrderNulls::Last => query.then_order_by(column.is_null().asc()),
+        };
+
         match direction {
             OrderDirection::Asc => query.then_order_by(column.asc()),
             OrderDirection::Desc => query.then_order_by(column.desc()),
         }
     }
 
-    fn build(&'a self) -> Result<QueryType<'a>> {
-        let mut query = records::table.into_boxed();
+    /// Number of records matching the filters, ignoring count and offset
+    pub fn total(&self, conn: &mut Conn) -> Result<i64> {
+        Ok(self.filter_valid()?.count().get_result(conn)?)
+    }
+
+    /// Records matching the filters whose stored amount can't be read
+    pub fn invalid_amounts(&self, conn: &mut Conn) -> Result<Vec<RowError>> {
+        crate::record::invalid_amounts(conn, self.filter()?)
+    }
+
+    fn filter_valid<'b>(&'b self) -> Result<QueryType<'b>>
+    where
+        'a: 'b,
+    {
+        let query = self.filter()?;
+        if self.skip_invalid {
+            Ok(query.filter(db::type_of(records::amount).eq("integer")))
+        } else {
+            Ok(query)
+        }
+    }
+
+    fn filter<'b>(&'b self) -> Result<QueryType<'b>>
+    where
+        'a: 'b,
+    {
+        let mut query = records::table
+            .into_boxed()
+            .filter(records::pending.eq(self.pending));
 
         if let Some(account_id) = self.account_id {
             query = query.filter(records::account_id.eq(account_id));
         }
+        if let Some(account_ids) = self.account_ids {
+            query = query.filter(records::account_id.eq_any(account_ids));
+        }
 
         if self.operation_date {
             if let Some(date) = self.from {
@@ -131,29 +218,106 @@ impl<'a> QueryRecord<'a> {
         if let Some(merchant_id) = self.merchant_id {
             query = query.filter(records::merchant_id.is(merchant_id));
         }
+        // A NULL id is different from all of them, but `!=` would be NULL
+        if let Some(category_ids) = self.exclude_category_ids {
+            query = query.filter(
+                records::category_id
+                    .is_null()
+                    .or(records::category_id.ne_all(category_ids)),
+            );
+        }
+        if let Some(merchant_ids) = self.exclude_merchant_ids {
+            query = query.filter(
+                records::merchant_id
+                    .is_null()
+                    .or(records::merchant_id.ne_all(merchant_ids)),
+            );
+        }
+        if let Some(mode) = &self.exclude_mode {
+            query = query.filter(records::mode.ne(mode));
+        }
+        if self.flagged {
+            query = query.filter(records::flagged_at.is_not_null());
+        }
+        if let Some(currency) = self.original_currency {
+            query = query.filter(records::original_currency.eq(db::Currency::from(currency)));
+        }
+        for term in self.text.unwrap_or_default().split_whitespace() {
+            let pattern = contains_pattern(term);
+            query = query.filter(
+                records::details
+                    .like(pattern.clone())
+                    .escape('\\')
+                    .or(records::merchant_id.eq_any(
+                        merchants::table
+                            .filter(merchants::name.like(pattern.clone()).escape('\\'))
+                            .select(merchants::id.nullable()),
+                    ))
+                    .or(records::category_id.eq_any(
+                        categories::table
+                            .filter(categories::name.like(pattern).escape('\\'))
+                            .select(categories::id.nullable()),
+                    )),
+            );
+        }
+        for tag_id in self.tag_ids.unwrap_or_default() {
+            query = query.filter(
+                records::id.eq_any(
+                    record_tags::table
+                        .filter(record_tags::tag_id.eq(*tag_id))
+                        .select(record_tags::record_id),
+                ),
+            );
+        }
+
+        Ok(query)
+    }
+
+    fn build(&'a self) -> Result<QueryType<'a>> {
+        let mut query = self.filter_valid()?;
 
         if let Some(count) = self.count {
             query = query.limit(count);
         }
+        if let Some(offset) = self.offset {
+            query = query.offset(offset);
+        }
 
-        for (field, direction) in &self.order {
+        if let Some(names) = self.merchant_names_first {
+            // Records without merchant compare as NULL, last in descending order
+            query = query.then_order_by(
+                records::merchant_id
+                    .eq_any(
+                        merchants::table
+                            .filter(db::nocase(merchants::name).eq_any(names))
+                            .select(merchants::id.nullable()),
+                    )
+                    .desc(),
+            );
+        }
+        for (field, direction, nulls) in &self.order {
             query = match field {
-                OrderField::Amount => Self::sort_by_column(query, records::amount, direction),
+                OrderField::Amount => {
+                    Self::sort_by_column(query, records::amount, direction, nulls)
+                }
                 OrderField::Date => {
                     if self.operation_date {
-                        Self::sort_by_column(query, records::operation_date, direction)
+                        Self::sort_by_column(query, records::operation_date, direction, nulls)
                     } else {
-                        Self::sort_by_column(query, records::value_date, direction)
+                        Self::sort_by_column(query, records::value_date, direction, nulls)
                     }
                 }
                 OrderField::CategoryId => {
-                    Self::sort_by_column(query, records::category_id, direction)
+                    Self::sort_by_column(query, records::category_id, direction, nulls)
                 }
                 OrderField::MerchantId => {
-                    Self::sort_by_column(query, records::merchant_id, direction)
+                    Self::sort_by_column(query, records::merchant_id, direction, nulls)
                 }
             };
         }
+        if self.offset.is_some() {
+            query = query.then_order_by(records::id.asc());
+        }
 
         Ok(query)
     }
@@ -187,6 +351,15 @@ impl<'a> QueryRecord<'a> {
     }
 }
 
+/// LIKE pattern matching `term` anywhere, with its wildcards escaped by `\`
+fn contains_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
 pub struct BuiltQueryRecord<'a, T>(QueryRecord<'a>, PhantomData<T>);
 
 impl<'a, T> BuiltQueryRecord<'a, T> {
@@ -203,6 +376,10 @@ impl<'a, T> BuiltQueryRecord<'a, T> {
         self.0.load::<_, T>(conn, query)
     }
 
+    pub fn invalid_amounts(&self, conn: &mut Conn) -> Result<Vec<RowError>> {
+        self.0.invalid_amounts(conn)
+    }
+
     pub fn type_marker(&self) -> PhantomData<T> {
         self.1
     }
@@ -431,3 +608,275 @@ impl<'a> BuiltQueryRecord<'a, RCM> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn order_nulls() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let cat_1 = test::category!(conn, "cat_1");
+        let cat_2 = test::category!(conn, "cat_2");
+
+        let r1 = test::record!(conn, &account, amount: Decimal::ONE, category: Some(&cat_2));
+        let r2 = test::record!(conn, &account, amount: Decimal::TWO);
+        let r3 = test::record!(conn, &account, amount: Decimal::TEN, category: Some(&cat_1));
+        let r4 = test::record!(conn, &account, amount: Decimal::ONE_HUNDRED);
+
+        let mut ids = |direction, nulls| -> Result<Vec<i64>> {
+            Ok(QueryRecord {
+                order: vec![
+                    (OrderField::CategoryId, direction, nulls),
+                    (OrderField::Amount, OrderDirection::Asc, OrderNulls::Default),
+                ],
+                ..QueryRecord::default()
+            }
+            .run(conn)?
+            .into_iter()
+            .map(|r| r.id)
+            .collect())
+        };
+
+        use OrderDirection::{Asc, Desc};
+
+        assert_eq!(
+            vec![r2.id, r4.id, r3.id, r1.id],
+            ids(Asc, OrderNulls::Default)?
+        );
+        assert_eq!(
+            vec![r3.id, r1.id, r2.id, r4.id],
+            ids(Asc, OrderNulls::Last)?
+        );
+        assert_eq!(
+            vec![r1.id, r3.id, r2.id, r4.id],
+            ids(Desc, OrderNulls::Default)?
+        );
+        assert_eq!(
+            vec![r2.id, r4.id, r1.id, r3.id],
+            ids(Desc, OrderNulls::First)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn text() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let food = test::category!(conn, "Food");
+        let grocer = test::merchant!(conn, "Grocer");
+
+        let r1 = test::record!(conn, &account, details: "Bread", category: Some(&food));
+        let r2 = test::record!(conn, &account, details: "Fresh milk", merchant: Some(&grocer));
+        let r3 = test::record!(conn, &account, details: "Cinema");
+
+        let mut ids = |text| -> Result<Vec<i64>> {
+            Ok(QueryRecord {
+                text: Some(text),
+                ..QueryRecord::default()
+            }
+            .run(conn)?
+            .into_iter()
+            .map(|r| r.id)
+            .collect())
+        };
+
+        assert_eq!(vec![r1.id], ids("bread")?);
+        assert_eq!(vec![r1.id], ids("FOOD")?);
+        assert_eq!(vec![r2.id], ids("groc")?);
+        assert_eq!(vec![r2.id], ids("milk grocer")?);
+        assert_eq!(vec![r3.id], ids("cine")?);
+        assert_eq!(Vec::<i64>::new(), ids("bread grocer")?);
+        assert_eq!(vec![r1.id, r2.id], ids("re")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_wildcards() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+
+        let r1 = test::record!(conn, &account, details: "50% off");
+        let r2 = test::record!(conn, &account, details: "a_b");
+        let r3 = test::record!(conn, &account, details: "C:\\temp");
+        test::record!(conn, &account, details: "500 shares");
+        test::record!(conn, &account, details: "axb");
+
+        let mut ids = |text| -> Result<Vec<i64>> {
+            Ok(QueryRecord {
+                text: Some(text),
+                ..QueryRecord::default()
+            }
+            .run(conn)?
+            .into_iter()
+            .map(|r| r.id)
+            .collect())
+        };
+
+        assert_eq!(vec![r1.id], ids("50%")?);
+        assert_eq!(vec![r2.id], ids("a_b")?);
+        assert_eq!(vec![r3.id], ids("\\temp")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exclude() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let rent = test::category!(conn, "Rent");
+        let food = test::category!(conn, "Food");
+        let amazon = test::merchant!(conn, "Amazon");
+
+        let r1 = test::record!(conn, &account, category: Some(&rent));
+        let r2 = test::record!(conn, &account, category: Some(&food), merchant: Some(&amazon));
+        let r3 = test::record!(conn, &account, mode: Mode::Atm(PaymentMethod::Empty));
+
+        let ids = |query: QueryRecord, conn: &mut Conn| -> Result<Vec<i64>> {
+            Ok(query.run(conn)?.into_iter().map(|r| r.id).collect())
+        };
+
+        // Records without category or merchant are kept
+        assert_eq!(
+            vec![r2.id, r3.id],
+            ids(
+                QueryRecord {
+                    exclude_category_ids: Some(&[rent.id]),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![r3.id],
+            ids(
+                QueryRecord {
+                    exclude_category_ids: Some(&[rent.id, food.id]),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![r1.id, r3.id],
+            ids(
+                QueryRecord {
+                    exclude_merchant_ids: Some(&[amazon.id]),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![r1.id, r2.id],
+            ids(
+                QueryRecord {
+                    exclude_mode: Some(Mode::Atm(PaymentMethod::Empty)),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn account_ids() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::account!(conn, "Cash");
+        let checking = test::account!(conn, "Checking");
+        let savings = test::account!(conn, "Savings");
+
+        let r1 = test::record!(conn, &cash);
+        let r2 = test::record!(conn, &checking);
+        test::record!(conn, &savings);
+
+        let ids = QueryRecord {
+            account_ids: Some(&[cash.id, checking.id]),
+            ..Default::default()
+        }
+        .run(conn)?
+        .into_iter()
+        .map(|r| r.id)
+        .collect::<Vec<_>>();
+        assert_eq!(vec![r1.id, r2.id], ids);
+
+        Ok(())
+    }
+
+    #[test]
+    fn offset_and_total() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let ids = (0..5)
+            .map(|_| Ok(test::record!(conn, &account, amount: Decimal::TEN).id))
+            .collect::<Result<Vec<_>>>()?;
+        test::record!(conn, &account, amount: Decimal::ONE);
+
+        let page = |conn: &mut Conn, offset| -> Result<Vec<i64>> {
+            Ok(QueryRecord {
+                greater_than: Some(Decimal::TWO),
+                count: Some(2),
+                offset: Some(offset),
+                order: vec![(OrderField::Amount, OrderDirection::Asc, OrderNulls::Default)],
+                ..QueryRecord::default()
+            }
+            .run(conn)?
+            .into_iter()
+            .map(|r| r.id)
+            .collect())
+        };
+
+        // Equal amounts are ordered by id
+        assert_eq!(ids[0..2], page(conn, 0)?);
+        assert_eq!(ids[2..4], page(conn, 2)?);
+        assert_eq!(ids[4..], page(conn, 4)?);
+        assert!(page(conn, 6)?.is_empty());
+
+        let query = QueryRecord {
+            greater_than: Some(Decimal::TWO),
+            count: Some(2),
+            offset: Some(2),
+            ..QueryRecord::default()
+        };
+        assert_eq!(5, query.total(conn)?);
+        assert_eq!(6, QueryRecord::default().total(conn)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_amount() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let record = test::record!(conn, &account);
+        test::record!(conn, &account);
+        diesel::sql_query("UPDATE records SET amount = '12,34' WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(record.id)
+            .execute(conn)?;
+
+        let error = QueryRecord::default().run(conn).err().unwrap().to_string();
+        assert!(error.contains("\"12,34\""), "{error}");
+
+        let query = QueryRecord {
+            skip_invalid: true,
+            ..QueryRecord::default()
+        };
+        assert_eq!(1, query.run(conn)?.len());
+        assert_eq!(1, query.total(conn)?);
+        assert_eq!(
+            vec![RowError {
+                record_id: record.id,
+                value: "12,34".to_string()
+            }],
+            query.invalid_amounts(conn)?
+        );
+
+        Ok(())
+    }
+}
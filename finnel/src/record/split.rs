@@ -1,8 +1,9 @@
 use crate::{
     prelude::*,
-    record::new::InsertableRecord,
+    record::{history::Changes, new::InsertableRecord},
     resolved::{mapmap, mapmapresolve},
     schema::records,
+    stats::MonthlyStats,
 };
 use diesel::prelude::*;
 
@@ -87,6 +88,10 @@ impl<'a> ResolvedSplitRecord<'a> {
             details: self.details.unwrap_or(record.details.as_str()),
             category_id,
             merchant_id: record.merchant_id,
+            // Stays on the split record, the exchange rate being unknown
+            original_amount: None,
+            original_currency: None,
+            pending: record.pending,
         }
     }
 }
@@ -102,11 +107,23 @@ pub struct ValidatedSplitRecord<'a>(&'a Record, SplitRecordChangeset, Insertable
 
 impl<'a> ValidatedSplitRecord<'a> {
     pub fn save(self, conn: &mut Conn) -> Result<Record> {
+        let mut changes = Changes::default();
+        changes.push("amount", Some(self.0.amount), Some(self.1.amount));
+
         diesel::update(self.0).set(self.1).execute(conn)?;
-        Ok(diesel::insert_into(records::table)
+        let split = diesel::insert_into(records::table)
             .values(self.2)
             .returning(Record::as_returning())
-            .get_result(conn)?)
+            .get_result::<Record>(conn)?;
+        MonthlyStats::invalidate_date(conn, self.0.operation_date, self.0.currency)?;
+
+        changes.push("split", None, Some(split.id.to_string()));
+        changes.log(conn, self.0.id)?;
+        let mut changes = Changes::default();
+        changes.push("split_from", None, Some(self.0.id.to_string()));
+        changes.log(conn, split.id)?;
+
+        Ok(split)
     }
 }
 
@@ -167,13 +184,13 @@ mod tests {
         let conn = &mut test::db()?;
 
         let account = test::account!(conn, "Cash");
-        let mut record = test::record!(conn, &account, amount: Decimal::new(5, 0));
+        let record = test::record!(conn, &account, amount: Decimal::new(5, 0));
 
         assert!(SplitRecord {
             amount: Decimal::new(5, 0),
             ..Default::default()
         }
-        .save(conn, &mut record)
+        .save(conn, &record)
         .is_err());
 
         Ok(())
@@ -0,0 +1,134 @@
+use crate::{prelude::*, record::NewRecord};
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// Money moved from one account to another, saved as a debit on the first
+/// and a credit on the second
+pub struct NewTransfer<'a> {
+    pub from: &'a Account,
+    pub to: &'a Account,
+    pub amount: Decimal,
+    pub date: NaiveDate,
+    /// Details of both records, by default naming the two accounts
+    pub details: Option<&'a str>,
+}
+
+impl<'a> NewTransfer<'a> {
+    pub fn new(from: &'a Account, to: &'a Account) -> Self {
+        Self {
+            from,
+            to,
+            amount: Decimal::ZERO,
+            date: chrono::Utc::now().date_naive(),
+            details: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.from.id == self.to.id {
+            return Err(Error::Invalid(format!(
+                "Cannot transfer from {} to itself",
+                self.from.name
+            )));
+        }
+        if self.from.currency != self.to.currency {
+            return Err(Error::Invalid(format!(
+                "Cannot transfer from {} in {} to {} in {}",
+                self.from.name,
+                self.from.currency.code(),
+                self.to.name,
+                self.to.currency.code()
+            )));
+        }
+        if self.amount <= Decimal::ZERO {
+            return Err(Error::Invalid(format!(
+                "Cannot transfer an amount of {}",
+                self.amount
+            )));
+        }
+        Ok(())
+    }
+
+    /// Save both records at once, returning the debit and then the credit
+    pub fn save(self, conn: &mut Conn) -> Result<(Record, Record)> {
+        self.validate()?;
+
+        let default_details;
+        let details = match self.details {
+            Some(details) => details,
+            None => {
+                default_details = format!("Transfer from {} to {}", self.from.name, self.to.name);
+                default_details.as_str()
+            }
+        };
+        let record = |account, direction| NewRecord {
+            amount: self.amount,
+            operation_date: self.date,
+            value_date: self.date,
+            direction,
+            mode: Mode::Transfer,
+            details,
+            ..NewRecord::new(account)
+        };
+
+        conn.transaction(|conn| {
+            let debit = record(self.from, Direction::Debit).save(conn)?;
+            let credit = record(self.to, Direction::Credit).save(conn)?;
+            Ok((debit, credit))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::records;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn save() -> Result<()> {
+        let conn = &mut test::db()?;
+        let bank = test::account!(conn, "Bank");
+        let cash = test::account!(conn, "Cash");
+
+        let (debit, credit) = NewTransfer {
+            amount: Decimal::new(40, 0),
+            ..NewTransfer::new(&bank, &cash)
+        }
+        .save(conn)?;
+
+        assert_eq!(Some(bank.id), debit.account_id);
+        assert_eq!(Direction::Debit, debit.direction);
+        assert_eq!(Some(cash.id), credit.account_id);
+        assert_eq!(Direction::Credit, credit.direction);
+        for record in [&debit, &credit] {
+            assert_eq!(Decimal::new(40, 0), record.amount);
+            assert_eq!(Mode::Transfer, record.mode);
+            assert_eq!("Transfer from Bank to Cash", record.details);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        let conn = &mut test::db()?;
+        let bank = test::account!(conn, "Bank");
+        let dollars = test::account!(conn, "Dollars", currency: Currency::USD);
+
+        let transfer = |from, to, amount| NewTransfer {
+            amount: Decimal::new(amount, 0),
+            ..NewTransfer::new(from, to)
+        };
+
+        assert!(transfer(&bank, &dollars, 10).save(conn).is_err());
+        assert!(transfer(&bank, &bank, 10).save(conn).is_err());
+        assert!(transfer(&bank, &bank, 0).save(conn).is_err());
+
+        let count: i64 = records::table.count().get_result(conn)?;
+        assert_eq!(0, count);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
-use crate::schema::recurring_payments;
+use crate::schema::{records, recurring_payments};
+use chrono::NaiveDate;
 use diesel::prelude::*;
 
 pub mod frequency;
@@ -11,6 +12,9 @@ pub use new::NewRecurringPayment;
 pub mod change;
 pub use change::ChangeRecurringPayment;
 
+pub mod query;
+pub use query::QueryRecurringPayment;
+
 #[derive(Debug, Queryable, Selectable, Identifiable, Associations)]
 #[diesel(table_name = recurring_payments)]
 #[diesel(belongs_to(Account, foreign_key = account_id))]
@@ -31,6 +35,10 @@ pub struct RecurringPayment {
     pub mode: Mode,
     pub category_id: Option<i64>,
     pub merchant_id: Option<i64>,
+    /// Date of the first occurrence, anchoring the following ones
+    pub start_date: Option<NaiveDate>,
+    /// Date after which no occurrence is expected
+    pub end_date: Option<NaiveDate>,
 }
 
 impl RecurringPayment {
@@ -50,6 +58,58 @@ impl RecurringPayment {
             .map_err(|e| Error::from_diesel_error(e, "RecurringPayment", Some("name")))
     }
 
+    pub fn all(conn: &mut Conn) -> Result<Vec<Self>> {
+        Ok(recurring_payments::table
+            .select(RecurringPayment::as_select())
+            .order(recurring_payments::id.asc())
+            .load(conn)?)
+    }
+
+    /// Find the recurring payments associated with the given merchant that
+    /// haven't ended before the given date
+    pub fn for_merchant(conn: &mut Conn, merchant_id: i64, date: NaiveDate) -> Result<Vec<Self>> {
+        Ok(recurring_payments::table
+            .filter(recurring_payments::merchant_id.eq(merchant_id))
+            .filter(
+                recurring_payments::end_date
+                    .is_null()
+                    .or(recurring_payments::end_date.ge(date)),
+            )
+            .select(RecurringPayment::as_select())
+            .order(recurring_payments::name.asc())
+            .load(conn)?)
+    }
+
+    /// Fetch the most recent record looking like an occurrence of this
+    /// recurring payment, i.e. of the same account, merchant, direction and
+    /// amount
+    pub fn fetch_last_matching_record(&self, conn: &mut Conn) -> Result<Option<Record>> {
+        let Some(merchant_id) = self.merchant_id else {
+            return Ok(None);
+        };
+
+        records::table
+            .filter(records::account_id.eq(self.account_id))
+            .filter(records::merchant_id.eq(merchant_id))
+            .filter(records::direction.eq(self.direction))
+            .order((records::value_date.desc(), records::id.desc()))
+            .select(Record::as_select())
+            .load::<Record>(conn)
+            .map(|records| records.into_iter().find(|r| r.amount == self.amount))
+            .map_err(|e| e.into())
+    }
+
+    /// Date of the first occurrence on or after the given date, from the
+    /// start date and the frequency, if there is one before the end date
+    pub fn next_due(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let due = self.frequency.first_date_from(self.start_date?, date)?;
+
+        match self.end_date {
+            Some(end_date) if due > end_date => None,
+            _ => Some(due),
+        }
+    }
+
     pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
         diesel::delete(&*self).execute(conn)?;
 
@@ -85,6 +145,96 @@ mod tests {
     use super::*;
     use crate::test::prelude::{assert_eq, Result, *};
 
+    #[test]
+    fn for_merchant() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let netflix = test::merchant!(conn, "Netflix");
+        let grocer = test::merchant!(conn, "Grocer");
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        let recpay = test::recpay!(conn, &account, name: "Netflix", merchant: Some(&netflix));
+        test::recpay!(conn, &account, name: "Groceries", merchant: Some(&grocer));
+        test::recpay!(
+            conn,
+            &account,
+            name: "Old Netflix",
+            merchant: Some(&netflix),
+            end_date: Some(date(8, 31))
+        );
+
+        let found = RecurringPayment::for_merchant(conn, netflix.id, date(9, 1))?;
+        assert_eq!(1, found.len());
+        assert_eq!(recpay.id, found[0].id);
+        assert_eq!(
+            2,
+            RecurringPayment::for_merchant(conn, netflix.id, date(8, 31))?.len()
+        );
+        assert_eq!(3, RecurringPayment::all(conn)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_last_matching_record() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let netflix = test::merchant!(conn, "Netflix");
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        let recpay = test::recpay!(
+            conn,
+            &account,
+            amount: Decimal::new(1399, 2),
+            merchant: Some(&netflix)
+        );
+        assert!(recpay.fetch_last_matching_record(conn)?.is_none());
+
+        test::record!(conn, &account, amount: Decimal::new(1399, 2), operation_date: date(8, 3), value_date: date(8, 3), merchant: Some(&netflix));
+        let last = test::record!(conn, &account, amount: Decimal::new(1399, 2), operation_date: date(9, 3), value_date: date(9, 3), merchant: Some(&netflix));
+        // A one-off record at the same merchant
+        test::record!(conn, &account, amount: Decimal::new(450, 2), operation_date: date(9, 8), value_date: date(9, 8), merchant: Some(&netflix));
+
+        assert_eq!(
+            last.id,
+            recpay.fetch_last_matching_record(conn)?.unwrap().id
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn next_due() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        let recpay = test::recpay!(conn, &account);
+        assert_eq!(None, recpay.next_due(date(2024, 1, 1)));
+
+        let rent = test::recpay!(conn, &account, start_date: Some(date(2024, 1, 31)));
+        assert_eq!(Some(date(2024, 1, 31)), rent.next_due(date(2023, 12, 1)));
+        assert_eq!(Some(date(2024, 1, 31)), rent.next_due(date(2024, 1, 31)));
+        assert_eq!(Some(date(2024, 2, 29)), rent.next_due(date(2024, 2, 1)));
+        // Back on the 31st after a shorter month
+        assert_eq!(Some(date(2024, 3, 31)), rent.next_due(date(2024, 3, 1)));
+        assert_eq!(Some(date(2024, 4, 30)), rent.next_due(date(2024, 4, 1)));
+        assert_eq!(Some(date(2024, 5, 31)), rent.next_due(date(2024, 5, 1)));
+
+        let yearly = test::recpay!(
+            conn,
+            &account,
+            frequency: Frequency::Yearly,
+            start_date: Some(date(2024, 2, 29)),
+            end_date: Some(date(2028, 12, 31))
+        );
+        assert_eq!(Some(date(2025, 2, 28)), yearly.next_due(date(2024, 3, 1)));
+        assert_eq!(Some(date(2028, 2, 29)), yearly.next_due(date(2027, 3, 1)));
+        assert_eq!(None, yearly.next_due(date(2028, 3, 1)));
+
+        Ok(())
+    }
+
     #[test]
     fn clear_merchant_id() -> Result<()> {
         let conn = &mut test::db()?;
@@ -3,6 +3,7 @@ use crate::{
     resolved::{mapmapmap, mapmapresolve},
     schema::recurring_payments,
 };
+use chrono::NaiveDate;
 use diesel::prelude::*;
 
 #[derive(Default, Clone)]
@@ -16,6 +17,8 @@ pub struct ChangeRecurringPayment<'a> {
     pub mode: Option<Mode>,
     pub category: Option<Option<&'a Category>>,
     pub merchant: Option<Option<&'a Merchant>>,
+    pub start_date: Option<Option<NaiveDate>>,
+    pub end_date: Option<Option<NaiveDate>>,
 }
 
 impl<'a> ChangeRecurringPayment<'a> {
@@ -34,6 +37,8 @@ impl<'a> ChangeRecurringPayment<'a> {
             mode: self.mode,
             category: mapmapresolve(conn, self.category)?,
             merchant: mapmapresolve(conn, self.merchant)?,
+            start_date: self.start_date,
+            end_date: self.end_date,
         })
     }
 }
@@ -48,11 +53,18 @@ pub struct ResolvedChangeRecurringPayment<'a> {
     pub mode: Option<Mode>,
     pub category: Option<Option<Resolved<'a, Category>>>,
     pub merchant: Option<Option<Resolved<'a, Merchant>>>,
+    pub start_date: Option<Option<NaiveDate>>,
+    pub end_date: Option<Option<NaiveDate>>,
 }
 
 impl<'a> ResolvedChangeRecurringPayment<'a> {
     pub fn validate(&self, _conn: &mut Conn, recpay: &'a RecurringPayment) -> Result<ValidatedChangeRecurringPayment<'a>> {
-        // Is there anything to do ?
+        if self.start_date.is_some() || self.end_date.is_some() {
+            super::new::validate_dates(
+                self.start_date.unwrap_or(recpay.start_date),
+                self.end_date.unwrap_or(recpay.end_date),
+            )?;
+        }
 
         Ok(ValidatedChangeRecurringPayment(recpay, self.as_changeset()))
     }
@@ -68,6 +80,8 @@ impl<'a> ResolvedChangeRecurringPayment<'a> {
             mode: self.mode,
             category_id: mapmapmap(&self.category, |c| c.id),
             merchant_id: mapmapmap(&self.merchant, |m| m.id),
+            start_date: self.start_date,
+            end_date: self.end_date,
         }
     }
 }
@@ -94,6 +108,8 @@ pub struct RecurringPaymentChangeset<'a> {
     pub mode: Option<Mode>,
     pub category_id: Option<Option<i64>>,
     pub merchant_id: Option<Option<i64>>,
+    pub start_date: Option<Option<NaiveDate>>,
+    pub end_date: Option<Option<NaiveDate>>,
 }
 
 #[cfg(test)]
@@ -115,6 +131,22 @@ mod tests {
         recpay.reload(conn)?;
         assert_eq!("Foo", recpay.name.as_str());
 
+        let date = |d| NaiveDate::from_ymd_opt(2024, 9, d);
+        ChangeRecurringPayment {
+            start_date: Some(date(10)),
+            ..Default::default()
+        }
+        .save(conn, &recpay)?;
+        recpay.reload(conn)?;
+
+        let end = |end_date| ChangeRecurringPayment {
+            end_date: Some(end_date),
+            ..Default::default()
+        };
+        assert!(end(date(9)).save(conn, &recpay).is_err());
+        end(date(30)).save(conn, &recpay)?;
+        assert_eq!(date(30), recpay.reload(conn)?.end_date);
+
         Ok(())
     }
 }
@@ -6,6 +6,7 @@ use diesel::{
     sql_types::Text,
     sqlite::Sqlite,
 };
+use chrono::{Datelike, Days, Months, NaiveDate};
 use derive_more::{Display, FromStr};
 
 #[derive(Default, Debug, Display, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression, FromStr)]
@@ -14,6 +15,45 @@ pub enum Frequency {
     Weekly,
     #[default]
     Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    /// Date of the nth occurrence after the one at the anchor date, None when
+    /// out of range
+    ///
+    /// Computed from the anchor rather than the previous occurrence, so that
+    /// the 31st of a month comes back after a shorter month
+    pub fn nth_date(&self, anchor: NaiveDate, n: u32) -> Option<NaiveDate> {
+        match self {
+            Frequency::Weekly => anchor.checked_add_days(Days::new(7 * u64::from(n))),
+            Frequency::Monthly => anchor.checked_add_months(Months::new(n)),
+            Frequency::Yearly => anchor.checked_add_months(Months::new(n.checked_mul(12)?)),
+        }
+    }
+
+    /// Date of the first occurrence on or after the given date, from the one
+    /// at the anchor date, None when out of range
+    pub fn first_date_from(&self, anchor: NaiveDate, date: NaiveDate) -> Option<NaiveDate> {
+        if date <= anchor {
+            return Some(anchor);
+        }
+
+        // Occurrences up to the date, one short when the occurrence of its
+        // month or year comes before it
+        let months = i64::from(date.year() - anchor.year()) * 12 + i64::from(date.month())
+            - i64::from(anchor.month());
+        let n = match self {
+            Frequency::Weekly => ((date - anchor).num_days() + 6) / 7,
+            Frequency::Monthly => months,
+            Frequency::Yearly => months / 12,
+        };
+        let n = u32::try_from(n).ok()?;
+        match self.nth_date(anchor, n)? {
+            due if due >= date => Some(due),
+            _ => self.nth_date(anchor, n.checked_add(1)?),
+        }
+    }
 }
 
 impl ToSql<Text, Sqlite> for Frequency {
@@ -28,3 +68,93 @@ impl FromSql<Text, Sqlite> for Frequency {
         Ok(<String as FromSql<Text, Sqlite>>::from_sql(bytes)?.parse()?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn nth_date() -> Result<()> {
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        assert_eq!(
+            Some(date(1, 31)),
+            Frequency::Monthly.nth_date(date(1, 31), 0)
+        );
+        assert_eq!(
+            Some(date(2, 29)),
+            Frequency::Monthly.nth_date(date(1, 31), 1)
+        );
+        assert_eq!(
+            Some(date(3, 31)),
+            Frequency::Monthly.nth_date(date(1, 31), 2)
+        );
+        assert_eq!(Some(date(1, 29)), Frequency::Weekly.nth_date(date(1, 1), 4));
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2028, 2, 29),
+            Frequency::Yearly.nth_date(date(2, 29), 4)
+        );
+        assert_eq!(None, Frequency::Yearly.nth_date(date(2, 29), u32::MAX));
+
+        assert_eq!(Frequency::Yearly, "yearly".parse()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn first_date_from() -> Result<()> {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let first = |frequency: Frequency, anchor, from| frequency.first_date_from(anchor, from);
+
+        let weekly = date(2024, 1, 1);
+        assert_eq!(
+            Some(weekly),
+            first(Frequency::Weekly, weekly, date(2023, 6, 1))
+        );
+        assert_eq!(Some(weekly), first(Frequency::Weekly, weekly, weekly));
+        assert_eq!(
+            Some(date(2024, 1, 8)),
+            first(Frequency::Weekly, weekly, date(2024, 1, 2))
+        );
+        assert_eq!(
+            Some(date(2024, 1, 8)),
+            first(Frequency::Weekly, weekly, date(2024, 1, 8))
+        );
+
+        let monthly = date(2024, 1, 31);
+        assert_eq!(
+            Some(date(2024, 2, 29)),
+            first(Frequency::Monthly, monthly, date(2024, 2, 29))
+        );
+        assert_eq!(
+            Some(date(2024, 3, 31)),
+            first(Frequency::Monthly, monthly, date(2024, 3, 1))
+        );
+        assert_eq!(
+            Some(date(2024, 5, 31)),
+            first(Frequency::Monthly, monthly, date(2024, 5, 1))
+        );
+        assert_eq!(
+            Some(date(2025, 1, 31)),
+            first(Frequency::Monthly, monthly, date(2025, 1, 31))
+        );
+        assert_eq!(
+            Some(date(2024, 12, 31)),
+            first(Frequency::Monthly, monthly, date(2024, 12, 31))
+        );
+
+        let yearly = date(2020, 2, 29);
+        assert_eq!(
+            Some(date(2025, 2, 28)),
+            first(Frequency::Yearly, yearly, date(2025, 2, 22))
+        );
+        assert_eq!(
+            Some(date(2028, 2, 29)),
+            first(Frequency::Yearly, yearly, date(2027, 3, 1))
+        );
+        assert_eq!(None, first(Frequency::Yearly, yearly, NaiveDate::MAX));
+
+        Ok(())
+    }
+}
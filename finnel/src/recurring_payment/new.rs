@@ -4,6 +4,7 @@ use crate::{
     schema::recurring_payments,
 };
 
+use chrono::NaiveDate;
 use diesel::prelude::*;
 
 pub struct NewRecurringPayment<'a> {
@@ -16,6 +17,8 @@ pub struct NewRecurringPayment<'a> {
     pub mode: Mode,
     pub category: Option<&'a Category>,
     pub merchant: Option<&'a Merchant>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
 }
 
 impl<'a> NewRecurringPayment<'a> {
@@ -30,6 +33,8 @@ impl<'a> NewRecurringPayment<'a> {
             mode: Mode::default(),
             category: None,
             merchant: None,
+            start_date: None,
+            end_date: None,
         }
     }
 
@@ -48,6 +53,8 @@ impl<'a> NewRecurringPayment<'a> {
             mode: self.mode,
             category: mapresolve(conn, self.category)?,
             merchant: mapresolve(conn, self.merchant)?,
+            start_date: self.start_date,
+            end_date: self.end_date,
         })
     }
 }
@@ -62,10 +69,14 @@ pub struct ResolvedNewRecurringPayment<'a> {
     pub mode: Mode,
     pub category: Option<Resolved<'a, Category>>,
     pub merchant: Option<Resolved<'a, Merchant>>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
 }
 
 impl<'a> ResolvedNewRecurringPayment<'a> {
     pub fn validate(&self, _conn: &mut Conn) -> Result<ValidatedNewRecurringPayment<'a>> {
+        validate_dates(self.start_date, self.end_date)?;
+
         Ok(ValidatedNewRecurringPayment(self.as_insertable()))
     }
 
@@ -81,10 +92,25 @@ impl<'a> ResolvedNewRecurringPayment<'a> {
             mode: self.mode,
             category_id: mapmap(&self.category, |c| c.id),
             merchant_id: mapmap(&self.merchant, |m| m.id),
+            start_date: self.start_date,
+            end_date: self.end_date,
         }
     }
 }
 
+/// Reject an end date before the start date
+pub(crate) fn validate_dates(
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+) -> Result<()> {
+    match (start_date, end_date) {
+        (Some(start_date), Some(end_date)) if end_date < start_date => Err(Error::Invalid(
+            format!("End date {end_date} is before start date {start_date}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
 pub struct ValidatedNewRecurringPayment<'a>(InsertableRecurringPayment<'a>);
 
 impl<'a> ValidatedNewRecurringPayment<'a> {
@@ -111,4 +137,6 @@ pub struct InsertableRecurringPayment<'a> {
     pub mode: Mode,
     pub category_id: Option<i64>,
     pub merchant_id: Option<i64>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
 }
@@ -0,0 +1,114 @@
+use super::RecurringPayment;
+use crate::essentials::*;
+use crate::schema::recurring_payments;
+
+use diesel::prelude::*;
+
+#[derive(Default)]
+pub struct QueryRecurringPayment<'a> {
+    pub name: Option<&'a str>,
+    pub account_id: Option<i64>,
+    /// Only recurring payments of one of these accounts
+    pub account_ids: Option<&'a [i64]>,
+    pub category_id: Option<Option<i64>>,
+    pub merchant_id: Option<Option<i64>>,
+}
+
+impl QueryRecurringPayment<'_> {
+    /// Recurring payments matching the filters, ordered by id
+    pub fn run(&self, conn: &mut Conn) -> Result<Vec<RecurringPayment>> {
+        let mut query = recurring_payments::table.into_boxed();
+
+        if let Some(name) = self.name {
+            query = query.filter(recurring_payments::name.like(name));
+        }
+        if let Some(account_id) = self.account_id {
+            query = query.filter(recurring_payments::account_id.eq(account_id));
+        }
+        if let Some(account_ids) = self.account_ids {
+            query = query.filter(recurring_payments::account_id.eq_any(account_ids));
+        }
+        if let Some(category_id) = self.category_id {
+            query = query.filter(recurring_payments::category_id.is(category_id));
+        }
+        if let Some(merchant_id) = self.merchant_id {
+            query = query.filter(recurring_payments::merchant_id.is(merchant_id));
+        }
+
+        Ok(query
+            .order(recurring_payments::id.asc())
+            .select(RecurringPayment::as_select())
+            .load(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn query() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::account!(conn, "Cash");
+        let bank = test::account!(conn, "Bank");
+        let netflix = test::merchant!(conn, "Netflix");
+
+        let subscription =
+            test::recpay!(conn, &bank, name: "Subscription", merchant: Some(&netflix));
+        let rent = test::recpay!(conn, &bank, name: "Rent");
+        let allowance = test::recpay!(conn, &cash, name: "Allowance");
+
+        let ids = |query: QueryRecurringPayment, conn: &mut Conn| -> Result<Vec<i64>> {
+            Ok(query.run(conn)?.into_iter().map(|r| r.id).collect())
+        };
+
+        assert_eq!(
+            vec![subscription.id, rent.id, allowance.id],
+            ids(Default::default(), conn)?
+        );
+        assert_eq!(
+            vec![subscription.id, rent.id],
+            ids(
+                QueryRecurringPayment {
+                    account_id: Some(bank.id),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![subscription.id, rent.id, allowance.id],
+            ids(
+                QueryRecurringPayment {
+                    account_ids: Some(&[bank.id, cash.id]),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![rent.id],
+            ids(
+                QueryRecurringPayment {
+                    account_id: Some(bank.id),
+                    merchant_id: Some(None),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+        assert_eq!(
+            vec![allowance.id],
+            ids(
+                QueryRecurringPayment {
+                    name: Some("%ow%"),
+                    ..Default::default()
+                },
+                conn
+            )?
+        );
+
+        Ok(())
+    }
+}
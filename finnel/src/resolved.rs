@@ -1,21 +1,56 @@
 use crate::essentials::*;
 
 pub trait Resolvable: Sized {
+    /// The object followed by the ones replacing it in turn, failing with
+    /// `Error::ReplacementCycle` instead of following them forever
+    fn replacements(self, conn: &mut Conn) -> Result<Vec<Self>>;
     fn resolve(self, conn: &mut Conn) -> Result<Self>;
     fn as_resolved<'a>(&'a self, conn: &mut Conn) -> Result<Resolved<'a, Self>>;
 }
 
-pub fn resolve<T, F, G>(conn: &mut Conn, object: T, finder: F, getter: G) -> Result<T>
+/// Entity which can be found along a chain of references, like the
+/// categories replacing one another
+pub trait Link {
+    const MODEL: &'static str;
+
+    fn id(&self) -> i64;
+    fn name(&self) -> &str;
+}
+
+/// Objects of the chain starting with the object and following the ids given
+/// by the getter, failing when the chain comes back to one of them
+pub fn chain<T, F, G>(conn: &mut Conn, object: T, finder: F, getter: G) -> Result<Vec<T>>
 where
+    T: Link,
     F: Fn(&mut Conn, i64) -> Result<T>,
     G: Fn(&T) -> Option<i64>,
 {
-    if let Some(id) = getter(&object) {
-        let object = finder(conn, id)?;
-        resolve(conn, object, finder, getter)
-    } else {
-        Ok(object)
+    let mut chain = vec![object];
+    while let Some(id) = chain.last().and_then(&getter) {
+        if let Some(start) = chain.iter().position(|object| object.id() == id) {
+            let names = chain[start..]
+                .iter()
+                .chain([&chain[start]])
+                .map(|object| object.name().to_owned())
+                .collect();
+            return Err(Error::ReplacementCycle {
+                model: T::MODEL,
+                names,
+            });
+        }
+        chain.push(finder(conn, id)?);
     }
+    Ok(chain)
+}
+
+pub fn resolve<T, F, G>(conn: &mut Conn, object: T, finder: F, getter: G) -> Result<T>
+where
+    T: Link,
+    F: Fn(&mut Conn, i64) -> Result<T>,
+    G: Fn(&T) -> Option<i64>,
+{
+    let mut chain = chain(conn, object, finder, getter)?;
+    Ok(chain.pop().expect("the chain starts with the object"))
 }
 
 pub fn as_resolved<'a, T, F, G>(
@@ -26,6 +61,7 @@ pub fn as_resolved<'a, T, F, G>(
 ) -> Result<Resolved<'a, T>>
 where
     F: Fn(&mut Conn, i64) -> Result<T>,
+    T: Link,
     G: Fn(&T) -> Option<i64>,
 {
     if let Some(id) = getter(object) {
@@ -12,11 +12,29 @@ pub enum Error {
     ModelNotFoundBy(&'static str, &'static str),
     #[display("Conflict with existing data. {_0}")]
     NonUnique(#[error(not(source))] String),
+    #[display("{model} {name:?} already exists with id {id}")]
+    AlreadyExists {
+        model: &'static str,
+        name: String,
+        id: i64,
+    },
+    #[display("{model} replacements form a cycle: {}", names.join(" → "))]
+    ReplacementCycle {
+        model: &'static str,
+        names: Vec<String>,
+    },
     #[display("Invalid. {_0}")]
     Invalid(#[error(not(source))] String),
+    #[display("{_0}")]
+    Parse(#[error(not(source))] String),
     #[display("Parsing version information")]
     #[from]
     VersionError(semver::Error),
+    #[display("Database was last used by version {db}, which is newer than this one ({binary})")]
+    DatabaseFromFuture {
+        db: semver::Version,
+        binary: semver::Version,
+    },
     #[display("Reading currency. {_0}")]
     #[from]
     CurrencyError(CurrencyError),
@@ -32,6 +50,12 @@ pub enum Error {
     InvalidMonth(i32, i32),
     #[display("Invalid week {_0:?}/{_1}")]
     InvalidWeek(chrono::IsoWeek, chrono::Weekday),
+    #[display("{_0}")]
+    InvalidRow(#[error(not(source))] RowError),
+    #[display("Database is in use by another process, try again")]
+    DatabaseBusy,
+    #[display("Database needs to be migrated, run any write command to migrate it")]
+    PendingMigrations,
 }
 
 impl Error {
@@ -72,6 +96,13 @@ impl From<diesel::result::Error> for Error {
             DatabaseError(DatabaseErrorKind::UniqueViolation, e) => {
                 Error::NonUnique(e.message().to_string())
             }
+            // Still locked by another connection once the busy timeout expired
+            DatabaseError(DatabaseErrorKind::Unknown, e)
+                if e.message() == "database is locked"
+                    || e.message().starts_with("database table is locked") =>
+            {
+                Error::DatabaseBusy
+            }
             _ => Error::DieselError(e),
         }
     }
@@ -105,3 +136,12 @@ impl<T> OptionalExtension<T> for Result<T> {
 #[derive(Debug, PartialEq, Eq, derive_more::Display, derive_more::Error)]
 #[display("Parse Type Error: {_0} {_1}")]
 pub struct ParseTypeError(pub &'static str, pub String);
+
+/// Record that couldn't be read and was left out of a computation
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+#[display("Record #{record_id} has an invalid amount {value:?}")]
+pub struct RowError {
+    pub record_id: i64,
+    /// Amount as stored in the database
+    pub value: String,
+}
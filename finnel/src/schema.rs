@@ -1,5 +1,23 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    account_groups (id) {
+        id -> BigInt,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    account_groups_accounts (group_id, account_id) {
+        group_id -> BigInt,
+        account_id -> BigInt,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -8,6 +26,21 @@ diesel::table! {
         name -> Text,
         balance -> BigInt,
         currency -> Text,
+        iban -> Nullable<Text>,
+        reconciled_at -> Nullable<Date>,
+        reconciled_balance -> BigInt,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    balance_assertions (id) {
+        id -> BigInt,
+        account_id -> BigInt,
+        date -> Date,
+        amount -> BigInt,
+        currency -> Text,
     }
 }
 
@@ -19,6 +52,54 @@ diesel::table! {
         name -> Text,
         parent_id -> Nullable<BigInt>,
         replaced_by_id -> Nullable<BigInt>,
+        color -> Nullable<Text>,
+        emoji -> Nullable<Text>,
+        exclude_from_stats -> Nullable<Bool>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    category_rules (id) {
+        id -> BigInt,
+        mode -> Nullable<Text>,
+        direction -> Nullable<Text>,
+        pattern -> Nullable<Text>,
+        is_regex -> Bool,
+        priority -> Integer,
+        category_id -> BigInt,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    metadata (key) {
+        key -> Text,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    imports (id) {
+        id -> BigInt,
+        profile -> Text,
+        hash -> Text,
+        imported_at -> Timestamp,
+        count -> BigInt,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    merchant_aliases (id) {
+        id -> BigInt,
+        merchant_id -> BigInt,
+        alias -> Text,
     }
 }
 
@@ -30,6 +111,8 @@ diesel::table! {
         name -> Text,
         default_category_id -> Nullable<BigInt>,
         replaced_by_id -> Nullable<BigInt>,
+        website -> Nullable<Text>,
+        notes -> Nullable<Text>,
     }
 }
 
@@ -44,6 +127,9 @@ diesel::table! {
         currency -> Text,
         category_id -> Nullable<BigInt>,
         direction -> Text,
+        records -> BigInt,
+        min_amount -> BigInt,
+        max_amount -> BigInt,
     }
 }
 
@@ -59,12 +145,34 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    record_changes (id) {
+        id -> BigInt,
+        record_id -> BigInt,
+        changed_at -> Timestamp,
+        field -> Text,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    record_tags (record_id, tag_id) {
+        record_id -> BigInt,
+        tag_id -> BigInt,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
     records (id) {
         id -> BigInt,
-        account_id -> BigInt,
+        account_id -> Nullable<BigInt>,
         amount -> BigInt,
         currency -> Text,
         operation_date -> Date,
@@ -74,6 +182,11 @@ diesel::table! {
         details -> Text,
         category_id -> Nullable<BigInt>,
         merchant_id -> Nullable<BigInt>,
+        flagged_at -> Nullable<Timestamp>,
+        flag_reason -> Nullable<Text>,
+        original_amount -> Nullable<BigInt>,
+        original_currency -> Nullable<Text>,
+        pending -> Bool,
     }
 }
 
@@ -92,6 +205,8 @@ diesel::table! {
         mode -> Text,
         category_id -> Nullable<BigInt>,
         merchant_id -> Nullable<BigInt>,
+        start_date -> Nullable<Date>,
+        end_date -> Nullable<Date>,
     }
 }
 
@@ -113,8 +228,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    tags (id) {
+        id -> BigInt,
+        name -> Text,
+    }
+}
+
+diesel::joinable!(account_groups_accounts -> account_groups (group_id));
+diesel::joinable!(account_groups_accounts -> accounts (account_id));
+diesel::joinable!(balance_assertions -> accounts (account_id));
+diesel::joinable!(merchant_aliases -> merchants (merchant_id));
 diesel::joinable!(merchants -> categories (default_category_id));
 diesel::joinable!(monthly_category_stats -> categories (category_id));
+diesel::joinable!(record_tags -> records (record_id));
+diesel::joinable!(record_tags -> tags (tag_id));
 diesel::joinable!(records -> accounts (account_id));
 diesel::joinable!(records -> categories (category_id));
 diesel::joinable!(records -> merchants (merchant_id));
@@ -125,13 +255,23 @@ diesel::joinable!(reports_categories -> categories (category_id));
 diesel::joinable!(reports_categories -> reports (report_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    account_groups,
+    account_groups_accounts,
     accounts,
+    balance_assertions,
     categories,
+    category_rules,
+    imports,
+    merchant_aliases,
     merchants,
+    metadata,
     monthly_category_stats,
     monthly_stats,
+    record_changes,
+    record_tags,
     records,
     recurring_payments,
     reports,
     reports_categories,
+    tags,
 );
@@ -2,14 +2,43 @@ use crate::{
     date,
     essentials::*,
     record::Direction,
+    result::RowError,
     schema::{monthly_category_stats, monthly_stats},
 };
 
-use diesel::prelude::*;
+use chrono::{Datelike, NaiveDate};
+use diesel::{prelude::*, OptionalExtension};
+
+mod activity;
+pub use activity::{ActivityDayStats, ActivityStats, ActivityTotals};
 
 mod categories;
 pub use categories::{CategoriesStats, CategoryStats};
 
+mod daily;
+pub use daily::{DayStats, DaysStats};
+
+mod merchant;
+pub use merchant::{merchant_months, MerchantMonthStats, MerchantStats};
+
+mod precomputed;
+pub use precomputed::{for_range, rebuild_months, RebuiltMonths};
+
+mod series;
+pub use series::{category_series, CategoryMonthStats};
+
+mod modes;
+pub use modes::{modes, ModeKind, ModeStats};
+
+mod similar;
+pub use similar::SimilarRecords;
+
+mod usage;
+pub use usage::{UsageStats, UsagesStats};
+
+mod year;
+pub use year::{year, year_currencies, YearMonthStats};
+
 #[derive(Debug, Queryable, Selectable)]
 #[diesel(table_name = monthly_stats)]
 #[diesel(primary_key(year, month, currency))]
@@ -54,80 +83,163 @@ impl MonthlyStats {
         Amount(self.credit_amount, self.currency)
     }
 
+    /// Find the stats of the month, or compute them
+    ///
+    /// Unless `persist_if_empty` is set, stats of a month without any record
+    /// (e.g. in the future) are returned without being saved
     pub fn find_or_create(
         conn: &mut Conn,
         year: i32,
         month: i32,
         currency: Currency,
+        persist_if_empty: bool,
     ) -> Result<Self> {
         // Check if it's possible to build a date range with the given year/month first
-        date::Month::calendar(year, month).as_date_range()?;
+        let range = date::Month::calendar(year, month).as_date_range()?;
+
+        if let Some(instance) = Self::find(conn, year, month, currency)? {
+            return Ok(instance);
+        }
+
+        let stats = CategoriesStats::from_date_range_and_currency(conn, range, currency)?;
+        if persist_if_empty || !stats.is_empty() {
+            Self::create_from(conn, year, month, currency, stats)
+        } else {
+            Ok(MonthlyStats {
+                year,
+                month,
+                debit_amount: Decimal::ZERO,
+                credit_amount: Decimal::ZERO,
+                currency,
+            })
+        }
+    }
+
+    /// Find the stats of the month when it is over and they were computed,
+    /// computing them from the records otherwise without saving them, so that
+    /// only reading them doesn't write
+    pub fn find_or_compute(
+        conn: &mut Conn,
+        year: i32,
+        month: i32,
+        currency: Currency,
+        today: NaiveDate,
+    ) -> Result<Self> {
+        let range = date::Month::calendar(year, month).as_date_range()?;
 
-        if let Some(instance) = monthly_stats::table
+        // Records can still be added to the current month
+        if (year, month) < (today.year(), today.month() as i32) {
+            if let Some(instance) = Self::find(conn, year, month, currency)? {
+                return Ok(instance);
+            }
+        }
+
+        let stats = CategoriesStats::from_date_range_and_currency(conn, range, currency)?;
+        for warning in &stats.warnings {
+            log::warn!("{warning}, left out of the stats of {year}/{month}");
+        }
+        let (debit_amount, credit_amount) = totals(&stats);
+        Ok(MonthlyStats {
+            year,
+            month,
+            debit_amount,
+            credit_amount,
+            currency,
+        })
+    }
+
+    /// Find the stats of the month if they were computed, without computing
+    /// them otherwise
+    pub fn find(
+        conn: &mut Conn,
+        year: i32,
+        month: i32,
+        currency: Currency,
+    ) -> Result<Option<Self>> {
+        Ok(monthly_stats::table
             .filter(monthly_stats::year.eq(year))
             .filter(monthly_stats::month.eq(month))
             .filter(monthly_stats::currency.eq(db::Currency::from(currency)))
             .select(MonthlyStats::as_select())
             .first(conn)
-            .optional()?
-        {
-            Ok(instance)
-        } else {
-            Self::create(conn, year, month, currency)
-        }
+            .optional()?)
     }
 
     pub fn create(conn: &mut Conn, year: i32, month: i32, currency: Currency) -> Result<Self> {
         // Check if it's possible to build a date range with the given year/month first
-        date::Month::calendar(year, month).as_date_range()?;
+        let range = date::Month::calendar(year, month).as_date_range()?;
+        let stats = CategoriesStats::from_date_range_and_currency(conn, range, currency)?;
 
-        let mut monthly_stats = diesel::insert_into(monthly_stats::table)
-            .values((
-                monthly_stats::year.eq(year),
-                monthly_stats::month.eq(month),
-                monthly_stats::debit_amount.eq(db::Decimal::from(Decimal::ZERO)),
-                monthly_stats::credit_amount.eq(db::Decimal::from(Decimal::ZERO)),
-                monthly_stats::currency.eq(db::Currency::from(currency)),
-            ))
-            .returning(MonthlyStats::as_select())
-            .get_result(conn)?;
+        Self::create_from(conn, year, month, currency, stats)
+    }
 
-        monthly_stats.rebuild(conn)?;
+    fn create_from(
+        conn: &mut Conn,
+        year: i32,
+        month: i32,
+        currency: Currency,
+        stats: CategoriesStats,
+    ) -> Result<Self> {
+        let mut monthly_stats = MonthlyStats {
+            year,
+            month,
+            debit_amount: Decimal::ZERO,
+            credit_amount: Decimal::ZERO,
+            currency,
+        };
+
+        for warning in monthly_stats.save(conn, stats)? {
+            log::warn!("{warning}, left out of the stats of {year}/{month}");
+        }
 
         Ok(monthly_stats)
     }
 
-    pub fn rebuild(&mut self, conn: &mut Conn) -> Result<()> {
-        self.delete_category_stats(conn)?;
-
-        self.debit_amount = Decimal::new(0, 0);
-        self.credit_amount = Decimal::new(0, 0);
-
+    /// Compute the stats again, returning the records that were left out
+    /// because their amount can't be read
+    pub fn rebuild(&mut self, conn: &mut Conn) -> Result<Vec<RowError>> {
         let stats = CategoriesStats::from_date_range_and_currency(
             conn,
             date::Month::calendar(self.year, self.month).as_date_range()?,
             self.currency,
         )?;
 
+        self.save(conn, stats)
+    }
+
+    /// Save the stats computed from the records of the month, replacing the
+    /// previous ones
+    fn save(&mut self, conn: &mut Conn, stats: CategoriesStats) -> Result<Vec<RowError>> {
+        self.delete_category_stats(conn)?;
+
+        (self.debit_amount, self.credit_amount) = totals(&stats);
+
+        // Saved again rather than updated, the records of the month may have
+        // changed since, forgetting these stats
+        diesel::replace_into(monthly_stats::table)
+            .values((
+                monthly_stats::year.eq(self.year),
+                monthly_stats::month.eq(self.month),
+                monthly_stats::debit_amount.eq(db::Decimal::from(self.debit_amount)),
+                monthly_stats::credit_amount.eq(db::Decimal::from(self.credit_amount)),
+                monthly_stats::currency.eq(db::Currency::from(self.currency)),
+            ))
+            .execute(conn)?;
+
         let monthly_category_stats = stats
-            .0
+            .stats
             .into_iter()
-            .map(|category_stats| {
-                if category_stats.direction.is_debit() {
-                    self.debit_amount += category_stats.amount;
-                } else {
-                    self.credit_amount += category_stats.amount;
-                }
-
-                MonthlyCategoryStats {
-                    id: -1,
-                    year: self.year,
-                    month: self.month,
-                    amount: category_stats.amount,
-                    currency: category_stats.currency,
-                    category_id: category_stats.category_id,
-                    direction: category_stats.direction,
-                }
+            .map(|category_stats| MonthlyCategoryStats {
+                id: -1,
+                year: self.year,
+                month: self.month,
+                amount: category_stats.amount,
+                currency: category_stats.currency,
+                category_id: category_stats.category_id,
+                direction: category_stats.direction,
+                records: category_stats.count,
+                min_amount: category_stats.min,
+                max_amount: category_stats.max,
             })
             .collect::<Vec<MonthlyCategoryStats>>();
 
@@ -137,16 +249,51 @@ impl MonthlyStats {
                 .execute(conn)?;
         }
 
-        diesel::update(&*self)
-            .set((
-                monthly_stats::debit_amount.eq(db::Decimal::from(self.debit_amount)),
-                monthly_stats::credit_amount.eq(db::Decimal::from(self.credit_amount)),
-            ))
-            .execute(conn)?;
+        Ok(stats.warnings)
+    }
 
+    /// Forget the stats of the month, so that they are computed again from
+    /// the records the next time they are needed
+    pub fn invalidate(conn: &mut Conn, year: i32, month: i32, currency: Currency) -> Result<()> {
+        let stats = MonthlyStats {
+            year,
+            month,
+            debit_amount: Decimal::ZERO,
+            credit_amount: Decimal::ZERO,
+            currency,
+        };
+        stats.delete_category_stats(conn)?;
+        diesel::delete(&stats).execute(conn)?;
         Ok(())
     }
 
+    /// Forget the stats of the month of `date`, where a record of that
+    /// currency is added, changed or removed
+    pub fn invalidate_date(conn: &mut Conn, date: NaiveDate, currency: Currency) -> Result<()> {
+        Self::invalidate(conn, date.year(), date.month() as i32, currency)
+    }
+
+    /// Forget the stats of every month, e.g. when the records they include
+    /// change
+    pub fn invalidate_all(conn: &mut Conn) -> Result<()> {
+        diesel::delete(monthly_category_stats::table).execute(conn)?;
+        diesel::delete(monthly_stats::table).execute(conn)?;
+        Ok(())
+    }
+
+    /// Stats of each category of the month, as computed from the records
+    pub fn category_stats(&self, conn: &mut Conn) -> Result<Vec<CategoryStats>> {
+        Ok(monthly_category_stats::table
+            .filter(monthly_category_stats::year.eq(self.year))
+            .filter(monthly_category_stats::month.eq(self.month))
+            .filter(monthly_category_stats::currency.eq(db::Currency::from(self.currency)))
+            .select(MonthlyCategoryStats::as_select())
+            .load::<MonthlyCategoryStats>(conn)?
+            .into_iter()
+            .map(CategoryStats::from)
+            .collect())
+    }
+
     fn delete_category_stats(&self, conn: &mut Conn) -> Result<()> {
         diesel::delete(monthly_category_stats::table)
             .filter(monthly_category_stats::year.eq(self.year))
@@ -157,6 +304,17 @@ impl MonthlyStats {
     }
 }
 
+/// Debit and credit amounts of the stats of the categories
+fn totals(stats: &CategoriesStats) -> (Decimal, Decimal) {
+    stats.iter().fold(
+        (Decimal::ZERO, Decimal::ZERO),
+        |(debit, credit), category_stats| match category_stats.direction.is_debit() {
+            true => (debit + category_stats.amount, credit),
+            false => (debit, credit + category_stats.amount),
+        },
+    )
+}
+
 #[derive(Debug, Queryable, Selectable, Identifiable, Insertable)]
 #[diesel(table_name = monthly_category_stats)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -171,6 +329,14 @@ pub struct MonthlyCategoryStats {
     pub currency: Currency,
     pub category_id: Option<i64>,
     pub direction: Direction,
+    /// Number of records
+    pub records: i64,
+    /// Smallest amount of a record
+    #[diesel(deserialize_as = db::Decimal, serialize_as = db::Decimal)]
+    pub min_amount: Decimal,
+    /// Largest amount of a record
+    #[diesel(deserialize_as = db::Decimal, serialize_as = db::Decimal)]
+    pub max_amount: Decimal,
 }
 
 impl MonthlyCategoryStats {
@@ -179,10 +345,35 @@ impl MonthlyCategoryStats {
     }
 }
 
+impl From<MonthlyCategoryStats> for CategoryStats {
+    fn from(stats: MonthlyCategoryStats) -> Self {
+        CategoryStats {
+            category_id: stats.category_id,
+            direction: stats.direction,
+            amount: stats.amount,
+            currency: stats.currency,
+            count: stats.records,
+            min: stats.min_amount,
+            max: stats.max_amount,
+        }
+    }
+}
+
+/// Forget the stats of the months with records of the category, which are
+/// left without one
 pub(crate) fn clear_category_id(conn: &mut Conn, id: i64) -> Result<()> {
-    diesel::delete(monthly_category_stats::table)
+    let months = monthly_category_stats::table
         .filter(monthly_category_stats::category_id.eq(Some(id)))
-        .execute(conn)?;
+        .select((
+            monthly_category_stats::year,
+            monthly_category_stats::month,
+            monthly_category_stats::currency,
+        ))
+        .distinct()
+        .load::<(i32, i32, db::Currency)>(conn)?;
+    for (year, month, currency) in months {
+        MonthlyStats::invalidate(conn, year, month, currency.0)?;
+    }
     Ok(())
 }
 
@@ -191,7 +382,6 @@ mod tests {
     use super::*;
     use crate::record::NewRecord;
     use crate::test::prelude::{assert_eq, Result, *};
-    use chrono::NaiveDate;
     use diesel::dsl::count_star;
 
     #[test]
@@ -203,13 +393,128 @@ mod tests {
         let stats = MonthlyStats::create(conn, 2024, 8, Currency::EUR)?;
         assert_eq!(Decimal::ZERO, stats.debit_amount);
 
-        MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR)?;
+        MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
 
         assert_eq!(1i64, monthly_stats::table.select(count_star()).first(conn)?);
 
         Ok(())
     }
 
+    #[test]
+    fn record_changes_invalidate() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let mut record = test::record!(conn, account, amount: Decimal::ONE, operation_date: date);
+        let stats = |conn: &mut Conn| {
+            MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)
+                .map(|s| s.debit_amount)
+        };
+        assert_eq!(Decimal::ONE, stats(conn)?);
+
+        test::record!(conn, account, amount: Decimal::TWO, operation_date: date);
+        assert!(MonthlyStats::find(conn, 2024, 8, Currency::EUR)?.is_none());
+        assert_eq!(Decimal::new(3, 0), stats(conn)?);
+
+        crate::record::change::ViolatingChangeRecord {
+            amount: Some(Decimal::TEN),
+            ..Default::default()
+        }
+        .save(conn, &record)?;
+        assert_eq!(Decimal::new(12, 0), stats(conn)?);
+
+        record.reload(conn)?;
+        record.delete(conn)?;
+        assert_eq!(Decimal::TWO, stats(conn)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalidate() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+        let record = test::record!(
+            conn,
+            account,
+            amount: Decimal::new(314, 2),
+            operation_date: NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()
+        );
+
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(314, 2), stats.debit_amount);
+
+        diesel::delete(&record).execute(conn)?;
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(314, 2), stats.debit_amount);
+
+        MonthlyStats::invalidate(conn, 2024, 8, Currency::EUR)?;
+        assert_eq!(0i64, monthly_stats::table.select(count_star()).first(conn)?);
+        assert_eq!(
+            0i64,
+            monthly_category_stats::table
+                .select(count_star())
+                .first(conn)?
+        );
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::ZERO, stats.debit_amount);
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_or_create_empty() -> Result<()> {
+        let conn = &mut test::db()?;
+        let next_year = chrono::Utc::now().year() + 1;
+
+        let stats = MonthlyStats::find_or_create(conn, next_year, 1, Currency::EUR, false)?;
+        assert_eq!(Decimal::ZERO, stats.debit_amount);
+        assert_eq!(0i64, monthly_stats::table.select(count_star()).first(conn)?);
+
+        MonthlyStats::find_or_create(conn, next_year, 1, Currency::EUR, true)?;
+        assert_eq!(1i64, monthly_stats::table.select(count_star()).first(conn)?);
+
+        let account = &test::account!(conn, "Cash");
+        test::record!(
+            conn,
+            account,
+            amount: Decimal::new(314, 2),
+            operation_date: NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()
+        );
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(314, 2), stats.debit_amount);
+        assert_eq!(2i64, monthly_stats::table.select(count_star()).first(conn)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_or_compute() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let record = test::record!(
+            conn,
+            account,
+            amount: Decimal::new(314, 2),
+            operation_date: date(8, 1)
+        );
+
+        let stats = MonthlyStats::find_or_compute(conn, 2024, 8, Currency::EUR, date(9, 1))?;
+        assert_eq!(Decimal::new(314, 2), stats.debit_amount);
+        assert_eq!(0i64, monthly_stats::table.select(count_star()).first(conn)?);
+
+        // Found once computed, when the month is over
+        MonthlyStats::create(conn, 2024, 8, Currency::EUR)?;
+        diesel::delete(&record).execute(conn)?;
+        let stats = MonthlyStats::find_or_compute(conn, 2024, 8, Currency::EUR, date(9, 1))?;
+        assert_eq!(Decimal::new(314, 2), stats.debit_amount);
+        let stats = MonthlyStats::find_or_compute(conn, 2024, 8, Currency::EUR, date(8, 31))?;
+        assert_eq!(Decimal::ZERO, stats.debit_amount);
+
+        Ok(())
+    }
+
     #[test]
     fn create() -> Result<()> {
         let conn = &mut test::db()?;
@@ -231,6 +536,8 @@ mod tests {
     fn rebuild_deletes_existing_category_stats() -> Result<()> {
         let conn = &mut test::db()?;
         let mut stats = MonthlyStats::create(conn, 2024, 8, Currency::EUR)?;
+        MonthlyStats::create(conn, 2024, 8, Currency::USD)?;
+        MonthlyStats::create(conn, 2024, 7, Currency::EUR)?;
 
         diesel::insert_into(monthly_category_stats::table)
             .values([
@@ -242,6 +549,9 @@ mod tests {
                     currency: Currency::EUR,
                     category_id: None,
                     direction: Direction::Debit,
+                    records: 0,
+                    min_amount: Decimal::ZERO,
+                    max_amount: Decimal::ZERO,
                 },
                 MonthlyCategoryStats {
                     id: 0,
@@ -251,6 +561,9 @@ mod tests {
                     currency: Currency::USD,
                     category_id: None,
                     direction: Direction::Debit,
+                    records: 0,
+                    min_amount: Decimal::ZERO,
+                    max_amount: Decimal::ZERO,
                 },
                 MonthlyCategoryStats {
                     id: 0,
@@ -260,24 +573,57 @@ mod tests {
                     currency: Currency::EUR,
                     category_id: None,
                     direction: Direction::Debit,
+                    records: 0,
+                    min_amount: Decimal::ZERO,
+                    max_amount: Decimal::ZERO,
                 },
             ])
             .execute(conn)?;
 
         assert_eq!(
             3i64,
-            monthly_category_stats::table
-                .select(count_star())
-                .first(conn)?
+            monthly_category_stats::table.select(count_star()).first(conn)?
         );
         stats.rebuild(conn)?;
         assert_eq!(
             2i64,
-            monthly_category_stats::table
-                .select(count_star())
-                .first(conn)?
+            monthly_category_stats::table.select(count_star()).first(conn)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn excluded_category_invalidates_stats() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Checking");
+        let mut transfers = test::category!(conn, "Transfers");
+        let date = NaiveDate::from_ymd_opt(2024, 8, 10).unwrap();
+        test::record!(conn, account, amount: Decimal::new(50, 0), operation_date: date);
+        test::record!(
+            conn,
+            account,
+            amount: Decimal::new(500, 0),
+            operation_date: date,
+            category: Some(&transfers)
         );
 
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(550, 0), stats.debit_amount);
+
+        crate::category::ChangeCategory {
+            exclude_from_stats: Some(Some(true)),
+            ..Default::default()
+        }
+        .apply(conn, &mut transfers)?;
+
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(50, 0), stats.debit_amount);
+
+        transfers.delete(conn)?;
+        let stats = MonthlyStats::find_or_create(conn, 2024, 8, Currency::EUR, false)?;
+        assert_eq!(Decimal::new(550, 0), stats.debit_amount);
+
         Ok(())
     }
 
@@ -331,6 +677,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn rebuild_skips_invalid_amounts() -> Result<()> {
+        let conn = &mut test::db()?;
+        let mut stats = MonthlyStats::create(conn, 2024, 8, Currency::EUR)?;
+
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let account = &test::account!(conn, "account");
+        let record = test::record!(conn, account, amount: Decimal::ONE, operation_date: date);
+        test::record!(conn, account, amount: Decimal::TWO, operation_date: date);
+        diesel::sql_query("UPDATE records SET amount = 'abc' WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(record.id)
+            .execute(conn)?;
+
+        let warnings = stats.rebuild(conn)?;
+        assert_eq!(Decimal::TWO, stats.debit_amount);
+        assert_eq!(1, warnings.len());
+        assert_eq!(record.id, warnings[0].record_id);
+        assert_eq!("abc", warnings[0].value);
+
+        Ok(())
+    }
+
     #[test]
     fn delete_category() -> Result<()> {
         let conn = &mut test::db()?;
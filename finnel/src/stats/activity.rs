@@ -0,0 +1,143 @@
+use crate::{account::Account, essentials::*, record::Direction, schema::records};
+
+use std::ops::Range;
+
+use chrono::NaiveDate;
+use diesel::{dsl::count_star, prelude::*};
+
+/// Number and amounts of the records of an account per operation date and
+/// direction, computed with a single query
+#[derive(derive_more::Deref)]
+pub struct ActivityStats {
+    #[deref]
+    pub stats: Vec<ActivityDayStats>,
+    pub currency: Currency,
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ActivityDayStats {
+    #[diesel(select_expression = records::operation_date)]
+    pub date: NaiveDate,
+    #[diesel(select_expression = records::direction)]
+    pub direction: Direction,
+    #[diesel(
+        select_expression = db::total(records::amount),
+        deserialize_as = db::Decimal
+    )]
+    pub amount: Decimal,
+    #[diesel(select_expression = count_star())]
+    pub count: i64,
+}
+
+/// Activity summed over some of the days
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActivityTotals {
+    pub count: i64,
+    pub debit_amount: Decimal,
+    pub credit_amount: Decimal,
+    pub currency: Currency,
+}
+
+impl ActivityStats {
+    /// Stats of the records of the account with an operation date in the
+    /// range, leaving out the ones whose amount can't be read
+    pub fn from_date_range(
+        conn: &mut Conn,
+        account: &Account,
+        range: Range<NaiveDate>,
+    ) -> Result<Self> {
+        let stats = records::table
+            .filter(records::account_id.eq(account.id))
+            .filter(records::operation_date.ge(range.start))
+            .filter(records::operation_date.lt(range.end))
+            .filter(records::currency.eq(db::Currency::from(account.currency)))
+            .filter(db::type_of(records::amount).eq("integer"))
+            .group_by((records::operation_date, records::direction))
+            .select(ActivityDayStats::as_select())
+            .order(records::operation_date.asc())
+            .load::<ActivityDayStats>(conn)?;
+
+        Ok(ActivityStats {
+            stats,
+            currency: account.currency,
+        })
+    }
+
+    /// Activity of the days in the range
+    pub fn totals(&self, range: Range<NaiveDate>) -> ActivityTotals {
+        let mut totals = ActivityTotals {
+            count: 0,
+            debit_amount: Decimal::ZERO,
+            credit_amount: Decimal::ZERO,
+            currency: self.currency,
+        };
+
+        for day in self.stats.iter().filter(|day| range.contains(&day.date)) {
+            totals.count += day.count;
+            match day.direction {
+                Direction::Debit => totals.debit_amount += day.amount,
+                Direction::Credit => totals.credit_amount += day.amount,
+            }
+        }
+
+        totals
+    }
+}
+
+impl ActivityTotals {
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+
+    pub fn credit_amount(&self) -> Amount {
+        Amount(self.credit_amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn from_date_range() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = test::account!(conn, "Cash");
+        let bank = test::account!(conn, "Bank");
+        let date = |d| NaiveDate::from_ymd_opt(2024, 9, d).unwrap();
+
+        for (account, amount, direction, day) in [
+            (&cash, 10, Direction::Debit, 2),
+            (&cash, 5, Direction::Debit, 2),
+            (&cash, 20, Direction::Credit, 9),
+            (&cash, 7, Direction::Debit, 12),
+            (&cash, 100, Direction::Debit, 30),
+            (&bank, 50, Direction::Debit, 9),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::from(amount),
+                direction: direction,
+                operation_date: date(day)
+            );
+        }
+
+        let stats = ActivityStats::from_date_range(conn, &cash, date(1)..date(30))?;
+        assert_eq!(3, stats.len());
+        assert_eq!(2, stats[0].count);
+        assert_eq!(Decimal::from(15), stats[0].amount);
+
+        let totals = stats.totals(date(1)..date(30));
+        assert_eq!(4, totals.count);
+        assert_eq!(Decimal::from(22), totals.debit_amount);
+        assert_eq!(Decimal::from(20), totals.credit_amount);
+
+        let totals = stats.totals(date(9)..date(12));
+        assert_eq!(1, totals.count);
+        assert_eq!(Decimal::ZERO, totals.debit_amount);
+
+        Ok(())
+    }
+}
@@ -1,12 +1,21 @@
-use crate::{essentials::*, record::Direction, schema::records};
+use crate::{
+    category::Category, essentials::*, record::Direction, result::RowError, schema::records,
+};
 
 use std::ops::Range;
 
 use chrono::NaiveDate;
-use diesel::prelude::*;
+use diesel::{dsl::count_star, prelude::*, sql_types::Bool, sqlite::Sqlite};
 
-#[derive(derive_more::From, derive_more::Deref)]
-pub struct CategoriesStats(pub Vec<CategoryStats>);
+#[derive(Default, derive_more::Deref)]
+pub struct CategoriesStats {
+    #[deref]
+    pub stats: Vec<CategoryStats>,
+    /// Records left out of the stats because their amount can't be read
+    pub warnings: Vec<RowError>,
+    /// Number of records left out because they are in another currency
+    pub skipped_currencies: Vec<(Currency, i64)>,
+}
 
 impl CategoriesStats {
     pub fn from_date_range_and_currency(
@@ -14,15 +23,128 @@ impl CategoriesStats {
         range: Range<NaiveDate>,
         currency: Currency,
     ) -> Result<Self> {
-        let stats = records::table
-            .filter(records::operation_date.ge(range.start))
-            .filter(records::operation_date.lt(range.end))
-            .filter(records::currency.eq(db::Currency::from(currency)))
+        Self::from_date_range_currency_and_accounts(conn, range, currency, None)
+    }
+
+    /// Stats of the records of the given accounts only, or of all accounts
+    pub fn from_date_range_currency_and_accounts(
+        conn: &mut Conn,
+        range: Range<NaiveDate>,
+        currency: Currency,
+        account_ids: Option<&[i64]>,
+    ) -> Result<Self> {
+        let filter = StatsFilter::new(conn, range, account_ids)?;
+        let mut stats = filter.left_out(conn, currency)?;
+
+        stats.stats = records::table
+            .filter(filter.summed(currency))
             .group_by((records::currency, records::direction, records::category_id))
             .select(CategoryStats::as_select())
             .load::<CategoryStats>(conn)?;
+        Ok(stats)
+    }
+
+    /// Fail on the first record that was left out instead of skipping it
+    pub fn strict(self) -> Result<Self> {
+        match self.warnings.into_iter().next() {
+            Some(warning) => Err(Error::InvalidRow(warning)),
+            None => Ok(CategoriesStats {
+                stats: self.stats,
+                warnings: Vec::new(),
+                skipped_currencies: self.skipped_currencies,
+            }),
+        }
+    }
+}
+
+type Condition<'a> = Box<dyn BoxableExpression<records::table, Sqlite, SqlType = Bool> + 'a>;
+
+/// Records the stats of a range are computed from: not pending, in the
+/// range, of the given accounts only if any, and not in a category excluded
+/// from the stats
+pub(super) struct StatsFilter<'a> {
+    range: Range<NaiveDate>,
+    /// Select the records by value date rather than by operation date
+    by_value_date: bool,
+    account_ids: Option<&'a [i64]>,
+    excluded: Vec<i64>,
+}
+
+impl<'a> StatsFilter<'a> {
+    pub(super) fn new(
+        conn: &mut Conn,
+        range: Range<NaiveDate>,
+        account_ids: Option<&'a [i64]>,
+    ) -> Result<Self> {
+        Ok(StatsFilter {
+            range,
+            by_value_date: false,
+            account_ids,
+            excluded: Category::excluded_from_stats_ids(conn)?,
+        })
+    }
+
+    pub(super) fn by_value_date(self) -> Self {
+        StatsFilter {
+            by_value_date: true,
+            ..self
+        }
+    }
+
+    fn condition(&self) -> Condition<'_> {
+        let Range { start, end } = self.range;
+        let mut condition: Condition = if self.by_value_date {
+            Box::new(
+                records::value_date
+                    .ge(start)
+                    .and(records::value_date.lt(end)),
+            )
+        } else {
+            Box::new(
+                records::operation_date
+                    .ge(start)
+                    .and(records::operation_date.lt(end)),
+            )
+        };
+        condition = Box::new(condition.and(records::pending.eq(false)));
+        if let Some(ids) = self.account_ids {
+            condition = Box::new(condition.and(records::account_id.eq_any(ids)));
+        }
+        Box::new(
+            condition.and(
+                records::category_id.is_null().or(records::category_id
+                    .ne_all(&self.excluded)
+                    .assume_not_null()),
+            ),
+        )
+    }
+
+    /// Records whose amounts are summed in the stats of the currency
+    pub(super) fn summed(&self, currency: Currency) -> Condition<'_> {
+        Box::new(
+            self.condition()
+                .and(records::currency.eq(db::Currency::from(currency)))
+                .and(db::type_of(records::amount).eq("integer")),
+        )
+    }
+
+    /// No stats, only the records left out of them
+    pub(super) fn left_out(&self, conn: &mut Conn, currency: Currency) -> Result<CategoriesStats> {
+        let records = || records::table.filter(self.condition()).into_boxed();
+
+        // TOTAL would happily sum the leading digits of an amount stored as
+        // text, so these records have to be found and left out explicitly
+        let warnings = crate::record::invalid_amounts(
+            conn,
+            records().filter(records::currency.eq(db::Currency::from(currency))),
+        )?;
+        let skipped_currencies = crate::record::other_currencies(conn, records, currency)?;
 
-        Ok(stats.into())
+        Ok(CategoriesStats {
+            stats: Vec::new(),
+            warnings,
+            skipped_currencies,
+        })
     }
 }
 
@@ -43,12 +165,47 @@ pub struct CategoryStats {
         deserialize_as = db::Currency
     )]
     pub currency: Currency,
+    /// Number of records
+    #[diesel(select_expression = count_star())]
+    pub count: i64,
+    /// Smallest amount of a record
+    #[diesel(
+        select_expression = db::group_min(records::amount),
+        deserialize_as = db::Decimal
+    )]
+    pub min: Decimal,
+    /// Largest amount of a record
+    #[diesel(
+        select_expression = db::group_max(records::amount),
+        deserialize_as = db::Decimal
+    )]
+    pub max: Decimal,
 }
 
 impl CategoryStats {
     pub fn amount(&self) -> Amount {
         Amount(self.amount, self.currency)
     }
+
+    /// Average amount of the records, rounded to the cent
+    pub fn mean(&self) -> Decimal {
+        match self.count {
+            0 => Decimal::ZERO,
+            count => (self.amount / Decimal::from(count)).round_dp(2),
+        }
+    }
+
+    pub fn min_amount(&self) -> Amount {
+        Amount(self.min, self.currency)
+    }
+
+    pub fn max_amount(&self) -> Amount {
+        Amount(self.max, self.currency)
+    }
+
+    pub fn mean_amount(&self) -> Amount {
+        Amount(self.mean(), self.currency)
+    }
 }
 
 #[cfg(test)]
@@ -114,6 +271,145 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn excluded_from_stats() -> Result<()> {
+        let conn = &mut test::db()?;
+        let transfers = &test::category!(conn, "Transfers", exclude_from_stats: Some(true));
+        let savings = &test::category!(conn, "Savings", parent: Some(transfers));
+        let refunds = &test::category!(
+            conn,
+            "Refunds",
+            parent: Some(transfers),
+            exclude_from_stats: Some(false)
+        );
+        let account = &test::account!(conn, "account");
+
+        let start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        for category in [None, Some(transfers), Some(savings), Some(refunds)] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::new(100, 0),
+                operation_date: start,
+                category: category
+            );
+        }
+
+        let stats = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::EUR)?;
+        let mut ids = stats.iter().map(|e| e.category_id).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(vec![None, Some(refunds.id)], ids);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aggregates() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cat = &test::category!(conn, "cat");
+        let account = &test::account!(conn, "account");
+
+        let start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        for amount in [1050, 200, 333] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::new(amount, 2),
+                operation_date: start,
+                category: Some(cat)
+            );
+        }
+        test::record!(
+            conn,
+            account,
+            amount: Decimal::new(5000, 2),
+            direction: Direction::Credit,
+            operation_date: start,
+            category: Some(cat)
+        );
+
+        let stats = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::EUR)?;
+        let debit = stats.iter().find(|e| e.direction.is_debit()).unwrap();
+        assert_eq!(3, debit.count);
+        assert_eq!(Decimal::new(1583, 2), debit.amount);
+        assert_eq!(Decimal::new(200, 2), debit.min);
+        assert_eq!(Decimal::new(1050, 2), debit.max);
+        assert_eq!(Decimal::new(528, 2), debit.mean());
+
+        let credit = stats.iter().find(|e| e.direction.is_credit()).unwrap();
+        assert_eq!(1, credit.count);
+        assert_eq!(Decimal::new(5000, 2), credit.min);
+        assert_eq!(Decimal::new(5000, 2), credit.max);
+        assert_eq!(Decimal::new(5000, 2), credit.mean());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_date_range_currency_and_accounts() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cat = &test::category!(conn, "cat");
+        let acc1 = &test::account!(conn, "acc1");
+        let acc2 = &test::account!(conn, "acc2");
+
+        let start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        for (account, amount) in [(acc1, 100), (acc2, 20), (acc1, 3)] {
+            NewRecord {
+                amount: Decimal::new(amount, 0),
+                operation_date: start,
+                category: Some(cat),
+                ..NewRecord::new(account)
+            }
+            .save(conn)?;
+        }
+
+        let total =
+            |stats: CategoriesStats| stats.iter().fold(Decimal::ZERO, |acc, e| acc + e.amount);
+
+        let stats = CategoriesStats::from_date_range_currency_and_accounts(
+            conn,
+            start..end,
+            Currency::EUR,
+            Some(&[acc1.id]),
+        )?;
+        assert_eq!(1, stats.len());
+        assert_eq!(Some(cat.id), stats[0].category_id);
+        assert_eq!(Decimal::new(103, 0), total(stats));
+
+        let stats = CategoriesStats::from_date_range_currency_and_accounts(
+            conn,
+            start..end,
+            Currency::EUR,
+            Some(&[acc2.id]),
+        )?;
+        assert_eq!(Decimal::new(20, 0), total(stats));
+
+        let stats = CategoriesStats::from_date_range_currency_and_accounts(
+            conn,
+            start..end,
+            Currency::EUR,
+            Some(&[acc1.id, acc2.id]),
+        )?;
+        assert_eq!(1, stats.len());
+        assert_eq!(Decimal::new(123, 0), total(stats));
+
+        let stats = CategoriesStats::from_date_range_currency_and_accounts(
+            conn,
+            start..end,
+            Currency::EUR,
+            None,
+        )?;
+        assert_eq!(Decimal::new(123, 0), total(stats));
+
+        Ok(())
+    }
+
     #[test]
     fn without_category() -> Result<()> {
         let conn = &mut test::db()?;
@@ -171,19 +467,63 @@ mod tests {
         }
         .save(conn)?;
 
+        NewRecord {
+            amount: Decimal::new(100, 2),
+            operation_date: start,
+            ..NewRecord::new(dollar)
+        }
+        .save(conn)?;
+
         let stats = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::EUR)?;
         assert!(stats.iter().all(|e| e.currency == Currency::EUR));
         assert_eq!(
             Decimal::new(420, 2),
             stats.iter().fold(Decimal::ZERO, |acc, e| acc + e.amount)
         );
+        assert_eq!(vec![(Currency::USD, 2)], stats.skipped_currencies);
 
         let stats = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::USD)?;
         assert!(stats.iter().all(|e| e.currency == Currency::USD));
         assert_eq!(
-            Decimal::new(210, 2),
+            Decimal::new(310, 2),
             stats.iter().fold(Decimal::ZERO, |acc, e| acc + e.amount)
         );
+        assert_eq!(vec![(Currency::EUR, 1)], stats.skipped_currencies);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_amount() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "account");
+        let start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let record = test::record!(conn, account, amount: Decimal::ONE, operation_date: start);
+        test::record!(conn, account, amount: Decimal::TEN, operation_date: start);
+        diesel::sql_query("UPDATE records SET amount = '12,34' WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(record.id)
+            .execute(conn)?;
+
+        let stats = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::EUR)?;
+        assert_eq!(1, stats.len());
+        assert_eq!(Decimal::TEN, stats[0].amount);
+        assert_eq!(
+            vec![RowError {
+                record_id: record.id,
+                value: "12,34".to_string()
+            }],
+            stats.warnings
+        );
+
+        let error = CategoriesStats::from_date_range_and_currency(conn, start..end, Currency::EUR)?
+            .strict()
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, Error::InvalidRow(RowError { record_id, .. }) if record_id == record.id)
+        );
 
         Ok(())
     }
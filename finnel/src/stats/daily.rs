@@ -0,0 +1,163 @@
+use super::categories::{CategoriesStats, StatsFilter};
+use crate::{essentials::*, record::Direction, result::RowError, schema::records};
+
+use std::ops::Range;
+
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// Amounts per day, direction and category over a date range, computed with
+/// a single query
+#[derive(derive_more::Deref)]
+pub struct DaysStats {
+    #[deref]
+    pub stats: Vec<DayStats>,
+    /// Records left out of the stats because their amount can't be read
+    pub warnings: Vec<RowError>,
+    /// Number of records left out because they are in another currency
+    pub skipped_currencies: Vec<(Currency, i64)>,
+}
+
+impl DaysStats {
+    /// Stats by value date of the records of the given accounts only, or
+    /// of all accounts
+    pub fn from_date_range_currency_and_accounts(
+        conn: &mut Conn,
+        range: Range<NaiveDate>,
+        currency: Currency,
+        account_ids: Option<&[i64]>,
+    ) -> Result<Self> {
+        let filter = StatsFilter::new(conn, range, account_ids)?.by_value_date();
+        let CategoriesStats {
+            warnings,
+            skipped_currencies,
+            ..
+        } = filter.left_out(conn, currency)?;
+
+        let stats = records::table
+            .filter(filter.summed(currency))
+            .group_by((
+                records::value_date,
+                records::currency,
+                records::direction,
+                records::category_id,
+            ))
+            .select(DayStats::as_select())
+            .order(records::value_date.asc())
+            .load::<DayStats>(conn)?;
+
+        Ok(DaysStats {
+            stats,
+            warnings,
+            skipped_currencies,
+        })
+    }
+
+    /// Fail on the first record that was left out instead of skipping it
+    pub fn strict(self) -> Result<Self> {
+        match self.warnings.into_iter().next() {
+            Some(warning) => Err(Error::InvalidRow(warning)),
+            None => Ok(DaysStats {
+                stats: self.stats,
+                warnings: Vec::new(),
+                skipped_currencies: self.skipped_currencies,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DayStats {
+    #[diesel(select_expression = records::value_date)]
+    pub date: NaiveDate,
+    #[diesel(select_expression = records::category_id)]
+    pub category_id: Option<i64>,
+    #[diesel(select_expression = records::direction)]
+    pub direction: Direction,
+    #[diesel(
+        select_expression = db::total(records::amount),
+        deserialize_as = db::Decimal
+    )]
+    pub amount: Decimal,
+    #[diesel(
+        select_expression = records::currency,
+        deserialize_as = db::Currency
+    )]
+    pub currency: Currency,
+}
+
+impl DayStats {
+    pub fn amount(&self) -> Amount {
+        Amount(self.amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn from_date_range_currency_and_accounts() -> Result<()> {
+        let conn = &mut test::db()?;
+        let cash = &test::account!(conn, "Cash");
+        let bank = &test::account!(conn, "Bank");
+        let cat = &test::category!(conn, "cat");
+
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+        test::record!(conn, cash, amount: Decimal::new(5, 0), operation_date: day(1), value_date: day(1));
+        test::record!(conn, cash, amount: Decimal::new(7, 0), operation_date: day(1), value_date: day(1));
+        test::record!(conn, cash, amount: Decimal::new(3, 0), operation_date: day(1), value_date: day(1), category: Some(cat));
+        test::record!(conn, cash, amount: Decimal::new(100, 0), operation_date: day(1), value_date: day(1), direction: Direction::Credit);
+        test::record!(conn, bank, amount: Decimal::new(20, 0), operation_date: day(2), value_date: day(2));
+        // Outside of the range
+        test::record!(conn, cash, amount: Decimal::new(1, 0), operation_date: day(31), value_date: day(31));
+
+        let stats = DaysStats::from_date_range_currency_and_accounts(
+            conn,
+            day(1)..day(31),
+            Currency::EUR,
+            None,
+        )?;
+        assert_eq!(4, stats.len());
+        assert!(stats.warnings.is_empty());
+
+        let amount = |stats: &DaysStats, date, direction, category_id| {
+            stats
+                .iter()
+                .find(|s| {
+                    s.date == date && s.direction == direction && s.category_id == category_id
+                })
+                .map(|s| s.amount)
+        };
+        assert_eq!(
+            Some(Decimal::new(12, 0)),
+            amount(&stats, day(1), Direction::Debit, None)
+        );
+        assert_eq!(
+            Some(Decimal::new(3, 0)),
+            amount(&stats, day(1), Direction::Debit, Some(cat.id))
+        );
+        assert_eq!(
+            Some(Decimal::new(100, 0)),
+            amount(&stats, day(1), Direction::Credit, None)
+        );
+        assert_eq!(
+            Some(Decimal::new(20, 0)),
+            amount(&stats, day(2), Direction::Debit, None)
+        );
+
+        let stats = DaysStats::from_date_range_currency_and_accounts(
+            conn,
+            day(1)..day(31),
+            Currency::EUR,
+            Some(&[bank.id]),
+        )?;
+        assert_eq!(1, stats.len());
+        assert_eq!(day(2), stats[0].date);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{essentials::*, record::Direction, schema::records};
+
+use std::ops::Range;
+
+use chrono::{Datelike, NaiveDate};
+use diesel::{dsl::count_star, prelude::*};
+
+/// Aggregated amounts of the records of a merchant in a single currency
+#[derive(Debug, Clone)]
+pub struct MerchantStats {
+    pub currency: Currency,
+    pub debit_amount: Decimal,
+    pub credit_amount: Decimal,
+    pub count: i64,
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+}
+
+impl MerchantStats {
+    /// Compute the stats of the merchant, one per currency of its records
+    pub fn from_merchant(conn: &mut Conn, merchant_id: i64) -> Result<Vec<Self>> {
+        let rows = records::table
+            .filter(records::pending.eq(false))
+            .filter(records::merchant_id.eq(merchant_id))
+            .group_by((records::currency, records::direction))
+            .select((
+                records::currency,
+                records::direction,
+                db::total(records::amount),
+                count_star(),
+                diesel::dsl::min(records::operation_date),
+                diesel::dsl::max(records::operation_date),
+            ))
+            .order((records::currency, records::direction))
+            .load::<(
+                db::Currency,
+                Direction,
+                db::Decimal,
+                i64,
+                Option<NaiveDate>,
+                Option<NaiveDate>,
+            )>(conn)?;
+
+        let mut stats = Vec::<Self>::new();
+
+        for (currency, direction, amount, count, first_date, last_date) in rows {
+            let (Some(first_date), Some(last_date)) = (first_date, last_date) else {
+                continue;
+            };
+            let currency = Currency::from(currency);
+
+            let index = match stats.iter().position(|s| s.currency == currency) {
+                Some(index) => index,
+                None => {
+                    stats.push(MerchantStats {
+                        currency,
+                        debit_amount: Decimal::ZERO,
+                        credit_amount: Decimal::ZERO,
+                        count: 0,
+                        first_date,
+                        last_date,
+                    });
+                    stats.len() - 1
+                }
+            };
+            let stat = &mut stats[index];
+
+            if direction.is_debit() {
+                stat.debit_amount += amount.0;
+            } else {
+                stat.credit_amount += amount.0;
+            }
+            stat.count += count;
+            stat.first_date = stat.first_date.min(first_date);
+            stat.last_date = stat.last_date.max(last_date);
+        }
+
+        Ok(stats)
+    }
+
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+
+    pub fn credit_amount(&self) -> Amount {
+        Amount(self.credit_amount, self.currency)
+    }
+
+    /// Number of calendar months between the first and last records, both
+    /// included
+    pub fn months(&self) -> i64 {
+        let months = |date: NaiveDate| date.year() as i64 * 12 + date.month0() as i64;
+        months(self.last_date) - months(self.first_date) + 1
+    }
+
+    pub fn monthly_debit_average(&self) -> Amount {
+        self.monthly_average(self.debit_amount)
+    }
+
+    pub fn monthly_credit_average(&self) -> Amount {
+        self.monthly_average(self.credit_amount)
+    }
+
+    fn monthly_average(&self, amount: Decimal) -> Amount {
+        // Round to cents as displaying an amount truncates the extra decimals
+        Amount(
+            (amount / Decimal::from(self.months())).round_dp(2),
+            self.currency,
+        )
+    }
+}
+
+/// Debits of a month to a set of merchants
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerchantMonthStats {
+    pub year: i32,
+    pub month: u32,
+    pub debit_amount: Decimal,
+    pub count: i64,
+    pub currency: Currency,
+}
+
+impl MerchantMonthStats {
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+
+    /// Mean amount of the debits, rounded to cents
+    pub fn average_ticket(&self) -> Option<Amount> {
+        (self.count > 0).then(|| {
+            Amount(
+                (self.debit_amount / Decimal::from(self.count)).round_dp(2),
+                self.currency,
+            )
+        })
+    }
+}
+
+/// Debits to the merchants in the currency by month of operation date,
+/// ordered by month, leaving out the months without any
+///
+/// Pass the ids from `Merchant::replaced_ids` to include the history of the
+/// replaced merchants.
+pub fn merchant_months(
+    conn: &mut Conn,
+    merchant_ids: &[i64],
+    range: Range<NaiveDate>,
+    currency: Currency,
+) -> Result<Vec<MerchantMonthStats>> {
+    let month = db::strftime("%Y-%m", records::operation_date);
+    let rows = records::table
+        .filter(records::pending.eq(false))
+        .filter(records::merchant_id.eq_any(merchant_ids))
+        .filter(records::operation_date.ge(range.start))
+        .filter(records::operation_date.lt(range.end))
+        .filter(records::direction.eq(Direction::Debit))
+        .filter(records::currency.eq(db::Currency::from(currency)))
+        .filter(db::type_of(records::amount).eq("integer"))
+        .group_by(month)
+        // The month is the same across the group, but diesel only lets
+        // grouped expressions be selected through an aggregate
+        .select((
+            diesel::dsl::min(month),
+            db::total(records::amount),
+            count_star(),
+        ))
+        .order(month)
+        .load::<(Option<String>, db::Decimal, i64)>(conn)?;
+
+    rows.into_iter()
+        .filter_map(|(month, amount, count)| Some((month?, amount, count)))
+        .map(|(month, amount, count)| {
+            let invalid = || Error::Invalid(format!("Month {month:?}"));
+            let (year, month) = month.split_once('-').ok_or_else(invalid)?;
+            Ok(MerchantMonthStats {
+                year: year.parse().map_err(|_| invalid())?,
+                month: month.parse().map_err(|_| invalid())?,
+                debit_amount: amount.0,
+                count,
+                currency,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn from_merchant() -> Result<()> {
+        let conn = &mut test::db()?;
+        let merchant = test::merchant!(conn, "Chariot");
+        let other = test::merchant!(conn, "Grognon");
+        let eur = test::account!(conn, "Cash");
+        let usd = test::account!(conn, "Dollars", currency: Currency::USD);
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        assert!(MerchantStats::from_merchant(conn, merchant.id)?.is_empty());
+
+        for (account, amount, direction, month) in [
+            (&eur, 10, Direction::Debit, 1),
+            (&eur, 20, Direction::Debit, 3),
+            (&eur, 5, Direction::Credit, 2),
+            (&usd, 7, Direction::Debit, 6),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::from(amount),
+                direction: direction,
+                operation_date: date(month, 15),
+                merchant: Some(&merchant)
+            );
+        }
+        test::record!(conn, &eur, amount: Decimal::from(100), merchant: Some(&other));
+
+        let stats = MerchantStats::from_merchant(conn, merchant.id)?;
+        assert_eq!(2, stats.len());
+
+        let eur = &stats[0];
+        assert_eq!(Currency::EUR, eur.currency);
+        assert_eq!(Decimal::from(30), eur.debit_amount);
+        assert_eq!(Decimal::from(5), eur.credit_amount);
+        assert_eq!(3, eur.count);
+        assert_eq!(date(1, 15), eur.first_date);
+        assert_eq!(date(3, 15), eur.last_date);
+        assert_eq!(3, eur.months());
+        assert_eq!(Decimal::from(10), eur.monthly_debit_average().0);
+
+        let usd = &stats[1];
+        assert_eq!(Currency::USD, usd.currency);
+        assert_eq!(Decimal::from(7), usd.debit_amount);
+        assert_eq!(1, usd.count);
+        assert_eq!(1, usd.months());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merchant_months() -> Result<()> {
+        let conn = &mut test::db()?;
+        let amazon = test::merchant!(conn, "Amazon");
+        let mktp = test::merchant!(conn, "AMZN Mktp");
+        let old = test::merchant!(conn, "AMZN", replaced_by: Some(&mktp));
+        // Replaced after being the replacement of another merchant
+        crate::merchant::ChangeMerchant {
+            replaced_by: Some(Some(&amazon)),
+            ..Default::default()
+        }
+        .save(conn, &mktp)?;
+        let other = test::merchant!(conn, "Grognon");
+        let account = test::account!(conn, "Cash");
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        let mut ids = amazon.replaced_ids(conn)?;
+        ids.sort();
+        assert_eq!(vec![amazon.id, mktp.id, old.id], ids);
+        assert_eq!(vec![mktp.id, old.id], mktp.replaced_ids(conn)?);
+
+        for (merchant, amount, direction, day) in [
+            (&old, 10, Direction::Debit, date(1, 5)),
+            (&mktp, 20, Direction::Debit, date(1, 31)),
+            (&amazon, 15, Direction::Debit, date(3, 1)),
+            (&amazon, 50, Direction::Credit, date(3, 2)),
+            (&other, 100, Direction::Debit, date(3, 3)),
+            (&amazon, 30, Direction::Debit, date(4, 1)),
+        ] {
+            test::record!(
+                conn,
+                &account,
+                amount: Decimal::from(amount),
+                direction: direction,
+                operation_date: day,
+                merchant: Some(merchant)
+            );
+        }
+
+        let months = super::merchant_months(conn, &ids, date(1, 1)..date(4, 1), Currency::EUR)?;
+        assert_eq!(
+            vec![(1, Decimal::from(30), 2), (3, Decimal::from(15), 1)],
+            months
+                .iter()
+                .map(|m| (m.month, m.debit_amount, m.count))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2024, months[0].year);
+        assert_eq!(
+            Some(Decimal::from(15)),
+            months[0].average_ticket().map(|a| a.0)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    essentials::*,
+    record::{Direction, Mode},
+    schema::records,
+};
+
+use std::ops::Range;
+
+use chrono::{Datelike, NaiveDate};
+use diesel::{dsl::count_star, prelude::*};
+
+/// Payment mode of a record, without the card used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ModeKind {
+    Direct,
+    Atm,
+    Transfer,
+}
+
+impl ModeKind {
+    pub const ALL: [ModeKind; 3] = [ModeKind::Direct, ModeKind::Atm, ModeKind::Transfer];
+}
+
+impl From<Mode> for ModeKind {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Direct(_) => ModeKind::Direct,
+            Mode::Atm(_) => ModeKind::Atm,
+            Mode::Transfer => ModeKind::Transfer,
+        }
+    }
+}
+
+impl std::fmt::Display for ModeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ModeKind::Direct => "Direct",
+            ModeKind::Atm => "ATM",
+            ModeKind::Transfer => "Transfer",
+        })
+    }
+}
+
+/// Amounts of the records of a month with the same kind of payment mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeStats {
+    pub year: i32,
+    pub month: u32,
+    pub mode: ModeKind,
+    pub count: i64,
+    pub debit_amount: Decimal,
+    pub credit_amount: Decimal,
+    pub currency: Currency,
+}
+
+impl ModeStats {
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+
+    pub fn credit_amount(&self) -> Amount {
+        Amount(self.credit_amount, self.currency)
+    }
+}
+
+/// Stats by month of value date and kind of payment mode of the records in
+/// the currency, ordered by month then mode
+///
+/// The records are grouped by their stored mode, e.g. `Card *1234` and
+/// `Card *5678` apart, then merged by kind of mode
+pub fn modes(
+    conn: &mut Conn,
+    range: Range<NaiveDate>,
+    currency: Currency,
+) -> Result<Vec<ModeStats>> {
+    let rows = records::table
+        .filter(records::pending.eq(false))
+        .filter(records::value_date.ge(range.start))
+        .filter(records::value_date.lt(range.end))
+        .filter(records::currency.eq(db::Currency::from(currency)))
+        .filter(db::type_of(records::amount).eq("integer"))
+        .group_by((records::value_date, records::mode, records::direction))
+        .select((
+            records::value_date,
+            records::mode,
+            records::direction,
+            db::total(records::amount),
+            count_star(),
+        ))
+        .load::<(NaiveDate, Mode, Direction, db::Decimal, i64)>(conn)?;
+
+    let mut stats = Vec::<ModeStats>::new();
+    for (date, mode, direction, amount, count) in rows {
+        let (year, month) = (date.year(), date.month());
+        let mode = ModeKind::from(mode);
+
+        let index = match stats
+            .iter()
+            .position(|s| (s.year, s.month, s.mode) == (year, month, mode))
+        {
+            Some(index) => index,
+            None => {
+                stats.push(ModeStats {
+                    year,
+                    month,
+                    mode,
+                    count: 0,
+                    debit_amount: Decimal::ZERO,
+                    credit_amount: Decimal::ZERO,
+                    currency,
+                });
+                stats.len() - 1
+            }
+        };
+        let stat = &mut stats[index];
+
+        stat.count += count;
+        match direction {
+            Direction::Debit => stat.debit_amount += amount.0,
+            Direction::Credit => stat.credit_amount += amount.0,
+        }
+    }
+    stats.sort_by_key(|s| (s.year, s.month, s.mode));
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::PaymentMethod;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn modes() -> Result<()> {
+        let conn = &mut test::db()?;
+        let eur = test::account!(conn, "Cash");
+        let usd = test::account!(conn, "Dollars", currency: Currency::USD);
+        let card = PaymentMethod::CardLast4Digit('1', '2', '3', '4');
+
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        for (account, month, mode, amount, direction) in [
+            (
+                &eur,
+                7,
+                Mode::Direct(PaymentMethod::Empty),
+                10,
+                Direction::Debit,
+            ),
+            (&eur, 7, Mode::Direct(card), 5, Direction::Debit),
+            (&eur, 7, Mode::Atm(card), 40, Direction::Debit),
+            (
+                &eur,
+                7,
+                Mode::Atm(PaymentMethod::Empty),
+                20,
+                Direction::Debit,
+            ),
+            (&eur, 7, Mode::Transfer, 1000, Direction::Credit),
+            (&eur, 7, Mode::Transfer, 300, Direction::Debit),
+            (&eur, 8, Mode::Direct(card), 7, Direction::Debit),
+            (&eur, 9, Mode::Direct(card), 100, Direction::Debit),
+            (&usd, 8, Mode::Atm(card), 50, Direction::Debit),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::from(amount),
+                direction: direction,
+                mode: mode,
+                operation_date: date(month, 2),
+                value_date: date(month, 2)
+            );
+        }
+
+        let stats = super::modes(conn, date(7, 1)..date(9, 1), Currency::EUR)?;
+        let amounts = |debit: i64, credit: i64| (Decimal::from(debit), Decimal::from(credit));
+        assert_eq!(
+            vec![
+                (7, ModeKind::Direct, 2, amounts(15, 0)),
+                (7, ModeKind::Atm, 2, amounts(60, 0)),
+                (7, ModeKind::Transfer, 2, amounts(300, 1000)),
+                (8, ModeKind::Direct, 1, amounts(7, 0)),
+            ],
+            stats
+                .iter()
+                .map(|s| (s.month, s.mode, s.count, (s.debit_amount, s.credit_amount)))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ModeKind::Atm, ModeKind::from(Mode::Atm(card)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use super::{categories::StatsFilter, CategoriesStats, MonthlyStats};
+use crate::{
+    date,
+    essentials::*,
+    result::RowError,
+    schema::{monthly_category_stats, monthly_stats, records},
+};
+
+use std::ops::Range;
+
+use chrono::{Datelike, Months, NaiveDate};
+use diesel::{dsl, prelude::*};
+
+/// Stats of the records of all accounts in the range
+///
+/// The months the range covers entirely are read from their precomputed
+/// stats when there are some, the rest from the records. The records left
+/// out are always looked up in the records, so both give the same stats.
+pub fn for_range(
+    conn: &mut Conn,
+    range: Range<NaiveDate>,
+    currency: Currency,
+) -> Result<CategoriesStats> {
+    let mut stats = CategoriesStats::default();
+    // Start of the part of the range still to read from the records
+    let mut remaining = range.start;
+
+    let mut month = range.start.with_day(1).unwrap_or(range.start);
+    if month < range.start {
+        month = month + Months::new(1);
+    }
+    while month + Months::new(1) <= range.end {
+        let next = month + Months::new(1);
+        if let Some(monthly) =
+            MonthlyStats::find(conn, month.year(), month.month() as i32, currency)?
+        {
+            if remaining < month {
+                merge(
+                    &mut stats,
+                    CategoriesStats::from_date_range_and_currency(
+                        conn,
+                        remaining..month,
+                        currency,
+                    )?,
+                );
+            }
+            let mut month_stats =
+                StatsFilter::new(conn, month..next, None)?.left_out(conn, currency)?;
+            month_stats.stats = monthly.category_stats(conn)?;
+            merge(&mut stats, month_stats);
+            remaining = next;
+        }
+        month = next;
+    }
+
+    if remaining < range.end {
+        merge(
+            &mut stats,
+            CategoriesStats::from_date_range_and_currency(conn, remaining..range.end, currency)?,
+        );
+    }
+
+    stats.warnings.sort_by_key(|warning| warning.record_id);
+    stats
+        .skipped_currencies
+        .sort_by(|(a, _), (b, _)| a.code().cmp(b.code()));
+    Ok(stats)
+}
+
+/// Add the stats of another part of the range
+fn merge(stats: &mut CategoriesStats, other: CategoriesStats) {
+    for other in other.stats {
+        match stats
+            .stats
+            .iter_mut()
+            .find(|s| s.category_id == other.category_id && s.direction == other.direction)
+        {
+            Some(category_stats) => {
+                category_stats.amount += other.amount;
+                category_stats.count += other.count;
+                category_stats.min = category_stats.min.min(other.min);
+                category_stats.max = category_stats.max.max(other.max);
+            }
+            None => stats.stats.push(other),
+        }
+    }
+
+    stats.warnings.extend(other.warnings);
+    for (currency, count) in other.skipped_currencies {
+        match stats
+            .skipped_currencies
+            .iter_mut()
+            .find(|(c, _)| *c == currency)
+        {
+            Some((_, total)) => *total += count,
+            None => stats.skipped_currencies.push((currency, count)),
+        }
+    }
+}
+
+/// Months whose stats were computed again
+#[derive(Debug, Default)]
+pub struct RebuiltMonths {
+    /// Number of months, with or without records
+    pub months: usize,
+    /// Currencies of the records of these months
+    pub currencies: Vec<Currency>,
+    /// Records left out of the stats because their amount can't be read
+    pub warnings: Vec<RowError>,
+}
+
+/// Compute again the stats of each month of the year, or of every month from
+/// the first record to the last one, in the currencies of their records
+///
+/// The stats saved for these months are forgotten first, and each month is
+/// then computed in a single pass over its records.
+pub fn rebuild_months(conn: &mut Conn, year: Option<i32>) -> Result<RebuiltMonths> {
+    conn.transaction(|conn| {
+        let range = match year {
+            Some(year) => {
+                let start = date::Month::calendar(year, 1).as_date_range()?.start;
+                start..start + Months::new(12)
+            }
+            None => {
+                let (first, last) = records::table
+                    .filter(records::pending.eq(false))
+                    .select((
+                        dsl::min(records::operation_date),
+                        dsl::max(records::operation_date),
+                    ))
+                    .first::<(Option<NaiveDate>, Option<NaiveDate>)>(conn)?;
+                let (Some(first), Some(last)) = (first, last) else {
+                    return Ok(RebuiltMonths::default());
+                };
+                let start = date::Month::calendar(first.year(), first.month() as i32)
+                    .as_date_range()?
+                    .start;
+                let end = date::Month::calendar(last.year(), last.month() as i32)
+                    .as_date_range()?
+                    .end;
+                start..end
+            }
+        };
+
+        let currencies = records::table
+            .filter(records::pending.eq(false))
+            .filter(records::operation_date.ge(range.start))
+            .filter(records::operation_date.lt(range.end))
+            .select(records::currency)
+            .distinct()
+            .order_by(records::currency)
+            .load::<db::Currency>(conn)?
+            .into_iter()
+            .map(|currency| currency.0)
+            .collect::<Vec<_>>();
+
+        let mut rebuilt = RebuiltMonths {
+            currencies,
+            ..Default::default()
+        };
+        let mut month = range.start;
+        while month < range.end {
+            let (year, month_number) = (month.year(), month.month() as i32);
+            // Including the stats in currencies no longer used that month
+            diesel::delete(monthly_category_stats::table)
+                .filter(monthly_category_stats::year.eq(year))
+                .filter(monthly_category_stats::month.eq(month_number))
+                .execute(conn)?;
+            diesel::delete(monthly_stats::table)
+                .filter(monthly_stats::year.eq(year))
+                .filter(monthly_stats::month.eq(month_number))
+                .execute(conn)?;
+
+            for &currency in &rebuilt.currencies {
+                let mut stats = MonthlyStats {
+                    year,
+                    month: month_number,
+                    debit_amount: Decimal::ZERO,
+                    credit_amount: Decimal::ZERO,
+                    currency,
+                };
+                rebuilt.warnings.extend(stats.rebuild(conn)?);
+            }
+
+            rebuilt.months += 1;
+            month = month + Months::new(1);
+        }
+
+        Ok(rebuilt)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Direction;
+    use crate::stats::CategoryStats;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// Stats in a stable order, to compare them whatever their source
+    fn sorted(stats: &CategoriesStats) -> Vec<(Option<i64>, bool, Decimal, i64, Decimal, Decimal)> {
+        let mut stats = stats
+            .iter()
+            .map(|s: &CategoryStats| {
+                (
+                    s.category_id,
+                    s.direction.is_debit(),
+                    s.amount,
+                    s.count,
+                    s.min,
+                    s.max,
+                )
+            })
+            .collect::<Vec<_>>();
+        stats.sort();
+        stats
+    }
+
+    /// Records from January to April 2024, in two categories and without,
+    /// with one in dollars and one whose amount can't be read
+    fn seed(conn: &mut Conn) -> Result<()> {
+        let account = &test::account!(conn, "Cash");
+        let dollars = &test::account!(conn, "Dollars", currency: Currency::USD);
+        let food = &test::category!(conn, "Food");
+        let rent = &test::category!(conn, "Rent");
+
+        for (month, day, amount, category) in [
+            (1, 10, 1250, Some(food)),
+            (1, 20, 300, None),
+            (2, 1, 80000, Some(rent)),
+            (2, 14, 4520, Some(food)),
+            (2, 29, 1005, Some(food)),
+            (3, 3, 80000, Some(rent)),
+            (3, 15, 2000, None),
+            (3, 31, 733, Some(food)),
+            (4, 2, 80000, Some(rent)),
+            (4, 25, 1999, Some(food)),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::new(amount, 2),
+                operation_date: date(2024, month, day),
+                value_date: date(2024, month, day),
+                category: category
+            );
+        }
+        test::record!(
+            conn,
+            account,
+            amount: Decimal::new(250000, 2),
+            direction: Direction::Credit,
+            operation_date: date(2024, 3, 1),
+            value_date: date(2024, 3, 1)
+        );
+        test::record!(
+            conn,
+            dollars,
+            amount: Decimal::new(1500, 2),
+            operation_date: date(2024, 2, 10),
+            value_date: date(2024, 2, 10)
+        );
+        let invalid = test::record!(
+            conn,
+            account,
+            operation_date: date(2024, 3, 20),
+            value_date: date(2024, 3, 20)
+        );
+        diesel::sql_query("UPDATE records SET amount = '12,34' WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(invalid.id)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn for_range() -> Result<()> {
+        let conn = &mut test::db()?;
+        seed(conn)?;
+        super::rebuild_months(conn, Some(2024))?;
+
+        for range in [
+            // Aligned on February and March
+            date(2024, 2, 1)..date(2024, 4, 1),
+            // Partially aligned, with days of January and April around
+            date(2024, 1, 15)..date(2024, 4, 10),
+            date(2024, 1, 1)..date(2024, 3, 16),
+            // Not aligned at all
+            date(2024, 2, 10)..date(2024, 3, 10),
+            date(2024, 3, 2)..date(2024, 3, 31),
+            date(2024, 3, 1)..date(2024, 3, 1),
+        ] {
+            let expected =
+                CategoriesStats::from_date_range_and_currency(conn, range.clone(), Currency::EUR)?;
+            let stats = super::for_range(conn, range.clone(), Currency::EUR)?;
+            assert_eq!(sorted(&expected), sorted(&stats), "{range:?}");
+            assert_eq!(expected.warnings, stats.warnings, "{range:?}");
+            assert_eq!(
+                expected.skipped_currencies, stats.skipped_currencies,
+                "{range:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn for_range_prefers_precomputed() -> Result<()> {
+        let conn = &mut test::db()?;
+        seed(conn)?;
+        MonthlyStats::create(conn, 2024, 2, Currency::EUR)?;
+
+        // Changed behind the back of the stats, which don't notice
+        diesel::sql_query(
+            "UPDATE records SET amount = amount * 2 WHERE typeof(amount) = 'integer'",
+        )
+        .execute(conn)?;
+
+        let stats = super::for_range(conn, date(2024, 2, 1)..date(2024, 3, 1), Currency::EUR)?;
+        let total = stats
+            .iter()
+            .fold(Decimal::ZERO, |total, s| total + s.amount);
+        assert_eq!(Decimal::new(85525, 2), total);
+
+        // March was not precomputed
+        let stats = super::for_range(conn, date(2024, 2, 1)..date(2024, 4, 1), Currency::EUR)?;
+        let debit = stats
+            .iter()
+            .filter(|s| s.direction.is_debit())
+            .fold(Decimal::ZERO, |total, s| total + s.amount);
+        assert_eq!(Decimal::new(85525 + 165466, 2), debit);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rebuild_months() -> Result<()> {
+        let conn = &mut test::db()?;
+        assert_eq!(0, super::rebuild_months(conn, None)?.months);
+
+        seed(conn)?;
+        // Stats of a currency no longer used, forgotten by the rebuild
+        MonthlyStats::create(conn, 2024, 1, Currency::GBP)?;
+
+        let rebuilt = super::rebuild_months(conn, None)?;
+        assert_eq!(4, rebuilt.months);
+        assert_eq!(vec![Currency::EUR, Currency::USD], rebuilt.currencies);
+        assert_eq!(1, rebuilt.warnings.len());
+        assert!(MonthlyStats::find(conn, 2024, 1, Currency::GBP)?.is_none());
+
+        let march = MonthlyStats::find(conn, 2024, 3, Currency::EUR)?.unwrap();
+        assert_eq!(Decimal::new(82733, 2), march.debit_amount);
+        assert_eq!(Decimal::new(250000, 2), march.credit_amount);
+        // In dollars, without records this month
+        let march = MonthlyStats::find(conn, 2024, 3, Currency::USD)?.unwrap();
+        assert_eq!(Decimal::ZERO, march.debit_amount);
+
+        assert_eq!(12, super::rebuild_months(conn, Some(2024))?.months);
+        assert!(MonthlyStats::find(conn, 2024, 12, Currency::EUR)?.is_some());
+
+        Ok(())
+    }
+}
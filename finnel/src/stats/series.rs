@@ -0,0 +1,243 @@
+use crate::{
+    date,
+    essentials::*,
+    record::Direction,
+    schema::{monthly_category_stats, records},
+    stats::{CategoriesStats, MonthlyStats},
+};
+
+use std::ops::Range;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use diesel::{dsl::count_star, prelude::*};
+
+/// Amounts of some categories over one month, or the part of it within the
+/// requested range
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryMonthStats {
+    pub year: i32,
+    pub month: u32,
+    pub debit_amount: Decimal,
+    pub credit_amount: Decimal,
+    pub currency: Currency,
+    /// Number of records in the month
+    pub count: i64,
+    /// Number of records in the month left out because they are in another
+    /// currency
+    pub skipped_currencies: Vec<(Currency, i64)>,
+    /// Change of the net spending, i.e. debit minus credit, since the
+    /// previous month of the series
+    pub delta: Option<Decimal>,
+}
+
+impl CategoryMonthStats {
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+
+    pub fn credit_amount(&self) -> Amount {
+        Amount(self.credit_amount, self.currency)
+    }
+
+    pub fn spent(&self) -> Decimal {
+        self.debit_amount - self.credit_amount
+    }
+}
+
+/// Stats of the categories for each month overlapping the range
+///
+/// Whole months that are over come from the monthly stats when they were
+/// computed, while the other months are computed from the records without
+/// saving anything.
+pub fn category_series(
+    conn: &mut Conn,
+    category_ids: &[i64],
+    range: Range<NaiveDate>,
+    currency: Currency,
+) -> Result<Vec<CategoryMonthStats>> {
+    let today = Utc::now().date_naive();
+    let mut series = Vec::<CategoryMonthStats>::new();
+
+    let mut start = range.start.with_day(1).unwrap_or(range.start);
+    while start < range.end {
+        let (year, month) = (start.year(), start.month());
+        let month_range = date::Month::calendar(year, month as i32).as_date_range()?;
+        start = month_range.end;
+
+        let within = month_range.start.max(range.start)..month_range.end.min(range.end);
+        let (debit_amount, credit_amount) = if within == month_range {
+            monthly_amounts(conn, category_ids, year, month, currency, today)?
+        } else {
+            partial_amounts(conn, category_ids, within.clone(), currency)?
+        };
+
+        let filter = || {
+            records::table
+                .filter(records::pending.eq(false))
+                .filter(records::operation_date.ge(within.start))
+                .filter(records::operation_date.lt(within.end))
+                .filter(records::category_id.eq_any(category_ids))
+                .into_boxed()
+        };
+        let count = filter()
+            .filter(records::currency.eq(db::Currency::from(currency)))
+            .select(count_star())
+            .first(conn)?;
+        let skipped_currencies = crate::record::other_currencies(conn, filter, currency)?;
+
+        let mut stats = CategoryMonthStats {
+            year,
+            month,
+            debit_amount,
+            credit_amount,
+            currency,
+            count,
+            skipped_currencies,
+            delta: None,
+        };
+        stats.delta = series
+            .last()
+            .map(|previous| stats.spent() - previous.spent());
+        series.push(stats);
+    }
+
+    Ok(series)
+}
+
+fn monthly_amounts(
+    conn: &mut Conn,
+    category_ids: &[i64],
+    year: i32,
+    month: u32,
+    currency: Currency,
+    today: NaiveDate,
+) -> Result<(Decimal, Decimal)> {
+    // Records can still be added to the current month, and the months not
+    // computed yet aren't saved here, so that only reading doesn't write
+    let over = (year, month) < (today.year(), today.month());
+    if !over || MonthlyStats::find(conn, year, month as i32, currency)?.is_none() {
+        let range = date::Month::calendar(year, month as i32).as_date_range()?;
+        return partial_amounts(conn, category_ids, range, currency);
+    }
+
+    let rows = monthly_category_stats::table
+        .filter(monthly_category_stats::year.eq(year))
+        .filter(monthly_category_stats::month.eq(month as i32))
+        .filter(monthly_category_stats::currency.eq(db::Currency::from(currency)))
+        .filter(monthly_category_stats::category_id.eq_any(category_ids))
+        .select((
+            monthly_category_stats::direction,
+            monthly_category_stats::amount,
+        ))
+        .load::<(Direction, db::Decimal)>(conn)?;
+
+    Ok(split_directions(
+        rows.into_iter()
+            .map(|(direction, amount)| (direction, amount.0)),
+    ))
+}
+
+fn partial_amounts(
+    conn: &mut Conn,
+    category_ids: &[i64],
+    range: Range<NaiveDate>,
+    currency: Currency,
+) -> Result<(Decimal, Decimal)> {
+    let stats = CategoriesStats::from_date_range_and_currency(conn, range, currency)?;
+    for warning in &stats.warnings {
+        log::warn!("{warning}, left out of the stats");
+    }
+
+    Ok(split_directions(
+        stats
+            .stats
+            .into_iter()
+            .filter(|s| s.category_id.is_some_and(|id| category_ids.contains(&id)))
+            .map(|s| (s.direction, s.amount)),
+    ))
+}
+
+fn split_directions(amounts: impl Iterator<Item = (Direction, Decimal)>) -> (Decimal, Decimal) {
+    amounts.fold(
+        (Decimal::ZERO, Decimal::ZERO),
+        |(debit, credit), (direction, amount)| {
+            if direction.is_debit() {
+                (debit + amount, credit)
+            } else {
+                (debit, credit + amount)
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn category_series() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+        let groceries = &test::category!(conn, "Groceries");
+        let other = &test::category!(conn, "Other");
+
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        for (category, amount, day, direction) in [
+            (groceries, 100, date(1, 10), Direction::Debit),
+            (groceries, 20, date(1, 25), Direction::Credit),
+            (other, 1000, date(1, 10), Direction::Debit),
+            (groceries, 150, date(2, 3), Direction::Debit),
+            (groceries, 50, date(3, 14), Direction::Debit),
+            (groceries, 70, date(3, 20), Direction::Debit),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::new(amount, 0),
+                operation_date: day,
+                category: Some(category),
+                direction: direction
+            );
+        }
+
+        // March is only partially in the range
+        let series = super::category_series(
+            conn,
+            &[groceries.id],
+            date(1, 1)..date(3, 15),
+            Currency::EUR,
+        )?;
+        assert_eq!(3, series.len());
+        assert_eq!((2024, 1), (series[0].year, series[0].month));
+        assert_eq!(Decimal::new(100, 0), series[0].debit_amount);
+        assert_eq!(Decimal::new(20, 0), series[0].credit_amount);
+        assert_eq!(2, series[0].count);
+        assert_eq!(None, series[0].delta);
+
+        assert_eq!(Decimal::new(150, 0), series[1].debit_amount);
+        assert_eq!(Some(Decimal::new(70, 0)), series[1].delta);
+
+        assert_eq!(Decimal::new(50, 0), series[2].debit_amount);
+        assert_eq!(1, series[2].count);
+        assert_eq!(Some(Decimal::new(-100, 0)), series[2].delta);
+
+        // Nothing is saved in the monthly stats
+        assert!(MonthlyStats::find(conn, 2024, 1, Currency::EUR)?.is_none());
+
+        // Whole months are read from them once computed
+        MonthlyStats::find_or_create(conn, 2024, 1, Currency::EUR, false)?;
+
+        let series = super::category_series(
+            conn,
+            &[groceries.id, other.id],
+            date(1, 1)..date(2, 1),
+            Currency::EUR,
+        )?;
+        assert_eq!(1, series.len());
+        assert_eq!(Decimal::new(1100, 0), series[0].debit_amount);
+        assert_eq!(3, series[0].count);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{essentials::*, schema::records};
+
+use diesel::{dsl::count_star, prelude::*, sql_types::Bool, sqlite::Sqlite};
+
+type Condition = Box<dyn BoxableExpression<records::table, Sqlite, SqlType = Bool>>;
+
+/// Records looking like another one, having the same merchant or any of the
+/// tokens in their details
+#[derive(Debug, Default, Clone)]
+pub struct SimilarRecords<'a> {
+    pub merchant_id: Option<i64>,
+    /// Words of the details, matched case-insensitively anywhere in the
+    /// details of the other records
+    pub tokens: &'a [&'a str],
+    /// Record to leave out, usually the one the others are similar to
+    pub excluded_id: Option<i64>,
+}
+
+impl SimilarRecords<'_> {
+    /// Number of similar records in each category, most frequent first,
+    /// leaving out the records without a category
+    pub fn categories(&self, conn: &mut Conn) -> Result<Vec<(i64, i64)>> {
+        let mut conditions = Vec::<Condition>::new();
+        if let Some(merchant_id) = self.merchant_id {
+            conditions.push(Box::new(records::merchant_id.is(Some(merchant_id))));
+        }
+        for token in self.tokens {
+            conditions.push(Box::new(records::details.like(format!("%{token}%"))));
+        }
+        let Some(similar) = conditions.into_iter().reduce(|a, b| Box::new(a.or(b))) else {
+            return Ok(Vec::new());
+        };
+
+        let mut query = records::table
+            .filter(similar)
+            .filter(records::category_id.is_not_null())
+            .group_by(records::category_id)
+            .select((records::category_id.assume_not_null(), count_star()))
+            .order((count_star().desc(), records::category_id.asc()))
+            .into_boxed();
+        if let Some(id) = self.excluded_id {
+            query = query.filter(records::id.ne(id));
+        }
+
+        Ok(query.load::<(i64, i64)>(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn categories() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Bank");
+        let bar = test::category!(conn, "Bar");
+        let food = test::category!(conn, "Food");
+        let chariot = test::merchant!(conn, "Chariot");
+
+        let record =
+            test::record!(conn, &account, details: "CB CHARIOT 01/08", merchant: Some(&chariot));
+        test::record!(conn, &account, details: "Beers", category: Some(&bar), merchant: Some(&chariot));
+        test::record!(conn, &account, details: "CB Chariot", category: Some(&bar));
+        test::record!(conn, &account, details: "chariot snacks", category: Some(&food));
+        test::record!(conn, &account, details: "Chariot", merchant: Some(&chariot));
+        test::record!(conn, &account, details: "Bakery", category: Some(&food));
+
+        let similar = SimilarRecords {
+            merchant_id: Some(chariot.id),
+            tokens: &["chariot"],
+            excluded_id: Some(record.id),
+        };
+        assert_eq!(vec![(bar.id, 2), (food.id, 1)], similar.categories(conn)?);
+
+        let similar = SimilarRecords {
+            tokens: &["snacks", "bakery"],
+            ..Default::default()
+        };
+        assert_eq!(vec![(food.id, 2)], similar.categories(conn)?);
+
+        assert!(SimilarRecords::default().categories(conn)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{essentials::*, record::Direction, schema::records};
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use diesel::{
+    dsl::{self, count_star},
+    prelude::*,
+    query_dsl::{methods, LoadQuery},
+    sql_types::Bool,
+    sqlite::Sqlite,
+};
+
+/// How much a category or a merchant is used over a period
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UsageStats {
+    /// Number of records, whatever their currency
+    pub count: i64,
+    /// Total of the debits in the requested currency
+    pub debit_amount: Decimal,
+    pub currency: Currency,
+}
+
+/// Usage of each category or merchant, by id, along with the usage of the
+/// records without one under `None`
+#[derive(Debug, Clone)]
+pub struct UsagesStats {
+    pub stats: HashMap<Option<i64>, UsageStats>,
+    pub currency: Currency,
+}
+
+type Row = (Option<i64>, db::Currency, Direction, db::Decimal, i64);
+
+type Condition = Box<dyn BoxableExpression<records::table, Sqlite, SqlType = Bool>>;
+type Filtered = dsl::Filter<records::table, Condition>;
+type GroupColumns<C> = (C, records::currency, records::direction);
+type Columns<C> = (
+    C,
+    records::currency,
+    records::direction,
+    db::total<records::amount>,
+    dsl::CountStar,
+);
+
+impl UsageStats {
+    pub fn new(currency: Currency) -> Self {
+        UsageStats {
+            count: 0,
+            debit_amount: Decimal::ZERO,
+            currency,
+        }
+    }
+
+    pub fn debit_amount(&self) -> Amount {
+        Amount(self.debit_amount, self.currency)
+    }
+}
+
+impl UsagesStats {
+    /// Usage of the categories by the records with an operation date in
+    /// `from..to`, each bound being optional
+    pub fn by_category(
+        conn: &mut Conn,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        currency: Currency,
+    ) -> Result<Self> {
+        Self::by_column(conn, records::category_id, from, to, currency)
+    }
+
+    /// Usage of the merchants by the records with an operation date in
+    /// `from..to`, each bound being optional
+    pub fn by_merchant(
+        conn: &mut Conn,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        currency: Currency,
+    ) -> Result<Self> {
+        Self::by_column(conn, records::merchant_id, from, to, currency)
+    }
+
+    /// Usage of the values of `column` by the records with an operation date
+    /// in `from..to`
+    fn by_column<C, G, S>(
+        conn: &mut Conn,
+        column: C,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        currency: Currency,
+    ) -> Result<Self>
+    where
+        C: Column<Table = records::table> + Copy,
+        Filtered: methods::GroupByDsl<GroupColumns<C>, Output = G>,
+        G: methods::SelectDsl<Columns<C>, Output = S>,
+        S: LoadQuery<'static, Conn, Row>,
+    {
+        let mut condition: Condition = Box::new(
+            records::pending
+                .eq(false)
+                .and(db::type_of(records::amount).eq("integer")),
+        );
+        if let Some(from) = from {
+            condition = Box::new(condition.and(records::operation_date.ge(from)));
+        }
+        if let Some(to) = to {
+            condition = Box::new(condition.and(records::operation_date.lt(to)));
+        }
+
+        let rows = records::table
+            .filter(condition)
+            .group_by((column, records::currency, records::direction))
+            .select((
+                column,
+                records::currency,
+                records::direction,
+                db::total(records::amount),
+                count_star(),
+            ))
+            .load::<Row>(conn)?;
+        Ok(Self::from_rows(rows, currency))
+    }
+
+    fn from_rows(rows: Vec<Row>, currency: Currency) -> Self {
+        let mut stats = HashMap::<Option<i64>, UsageStats>::new();
+
+        for (id, row_currency, direction, amount, count) in rows {
+            let stat = stats.entry(id).or_insert_with(|| UsageStats::new(currency));
+            stat.count += count;
+            if direction.is_debit() && Currency::from(row_currency) == currency {
+                stat.debit_amount += amount.0;
+            }
+        }
+
+        UsagesStats { stats, currency }
+    }
+
+    /// Usage of the given category or merchant, zero if it has no records
+    pub fn get(&self, id: Option<i64>) -> UsageStats {
+        self.stats
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| UsageStats::new(self.currency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn by_category_and_merchant() -> Result<()> {
+        let conn = &mut test::db()?;
+        let food = test::category!(conn, "Food");
+        let unused = test::category!(conn, "Unused");
+        let merchant = test::merchant!(conn, "Chariot");
+        let eur = test::account!(conn, "Cash");
+        let usd = test::account!(conn, "Dollars", currency: Currency::USD);
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        for (account, amount, direction, month) in [
+            (&eur, 10, Direction::Debit, 1),
+            (&eur, 20, Direction::Debit, 3),
+            (&eur, 5, Direction::Credit, 2),
+            (&usd, 7, Direction::Debit, 2),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::from(amount),
+                direction: direction,
+                operation_date: date(month, 15),
+                category: Some(&food),
+                merchant: Some(&merchant)
+            );
+        }
+        test::record!(conn, &eur, amount: Decimal::from(100), operation_date: date(2, 1));
+
+        let stats = UsagesStats::by_category(conn, None, None, Currency::EUR)?;
+        let food_stats = stats.get(Some(food.id));
+        assert_eq!(4, food_stats.count);
+        assert_eq!(Decimal::from(30), food_stats.debit_amount);
+        assert_eq!(UsageStats::new(Currency::EUR), stats.get(Some(unused.id)));
+        assert_eq!(Decimal::from(100), stats.get(None).debit_amount);
+
+        let stats =
+            UsagesStats::by_merchant(conn, Some(date(2, 1)), Some(date(3, 1)), Currency::EUR)?;
+        let merchant_stats = stats.get(Some(merchant.id));
+        assert_eq!(2, merchant_stats.count);
+        assert_eq!(Decimal::ZERO, merchant_stats.debit_amount);
+        assert_eq!(1, stats.get(None).count);
+
+        let stats = UsagesStats::by_merchant(conn, None, None, Currency::USD)?;
+        assert_eq!(Decimal::from(7), stats.get(Some(merchant.id)).debit_amount);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{date, essentials::*, schema::records, stats::MonthlyStats};
+
+use chrono::{Datelike, NaiveDate, Utc};
+use diesel::prelude::*;
+
+/// Amounts of one month of a year, with the net amount accumulated since the
+/// start of the year
+#[derive(Debug)]
+pub struct YearMonthStats {
+    pub year: i32,
+    pub month: u32,
+    /// Stats of the month, `None` when it hasn't started yet
+    pub stats: Option<MonthlyStats>,
+    /// Credit minus debit of the months of the year up to this one included
+    pub cumulative_net: Decimal,
+}
+
+impl YearMonthStats {
+    /// Credit minus debit of the month
+    pub fn net(&self) -> Option<Decimal> {
+        self.stats
+            .as_ref()
+            .map(|stats| stats.credit_amount - stats.debit_amount)
+    }
+}
+
+/// Stats of the twelve months of the year in the currency
+///
+/// The months which aren't over or whose stats weren't computed yet are
+/// computed from the records, without saving them.
+pub fn year(conn: &mut Conn, year: i32, currency: Currency) -> Result<Vec<YearMonthStats>> {
+    let today = Utc::now().date_naive();
+    let mut cumulative_net = Decimal::ZERO;
+
+    (1..=12)
+        .map(|month| {
+            let stats = if (year, month) > (today.year(), today.month()) {
+                None
+            } else {
+                let stats =
+                    MonthlyStats::find_or_compute(conn, year, month as i32, currency, today)?;
+                cumulative_net += stats.credit_amount - stats.debit_amount;
+                Some(stats)
+            };
+
+            Ok(YearMonthStats {
+                year,
+                month,
+                stats,
+                cumulative_net,
+            })
+        })
+        .collect()
+}
+
+/// Currencies of the records of the year, sorted by code
+pub fn year_currencies(conn: &mut Conn, year: i32) -> Result<Vec<Currency>> {
+    let start = date::Month::calendar(year, 1).as_date_range()?.start;
+    let end = NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        .ok_or_else(|| Error::Invalid(format!("Cannot compute the end of year {year}")))?;
+
+    Ok(records::table
+        .filter(records::pending.eq(false))
+        .filter(records::operation_date.ge(start))
+        .filter(records::operation_date.lt(end))
+        .select(records::currency)
+        .distinct()
+        .order_by(records::currency)
+        .load::<db::Currency>(conn)?
+        .into_iter()
+        .map(|currency| currency.0)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Direction;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn year() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = &test::account!(conn, "Cash");
+
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        for (amount, day, direction) in [
+            (100, date(1, 10), Direction::Debit),
+            (2000, date(1, 25), Direction::Credit),
+            (150, date(3, 3), Direction::Debit),
+            (50, date(3, 14), Direction::Debit),
+            (300, date(11, 20), Direction::Credit),
+        ] {
+            test::record!(
+                conn,
+                account,
+                amount: Decimal::new(amount, 0),
+                operation_date: day,
+                direction: direction
+            );
+        }
+        let usd = &test::account!(conn, "Dollars", currency: Currency::USD);
+        test::record!(conn, usd, amount: Decimal::new(40, 0), operation_date: date(2, 1));
+
+        let months = super::year(conn, 2024, Currency::EUR)?;
+        assert_eq!(12, months.len());
+        assert_eq!(
+            (1..=12).collect::<Vec<_>>(),
+            months.iter().map(|m| m.month).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            [1900, 1900, 1700, 1700, 1700, 1700, 1700, 1700, 1700, 1700, 2000, 2000]
+                .map(|amount| Decimal::new(amount, 0))
+                .to_vec(),
+            months.iter().map(|m| m.cumulative_net).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(Decimal::new(-200, 0)), months[2].net());
+        assert_eq!(Some(Decimal::ZERO), months[1].net());
+        assert!(MonthlyStats::find(conn, 2024, 3, Currency::EUR)?.is_none());
+
+        assert_eq!(
+            vec![Currency::EUR, Currency::USD],
+            year_currencies(conn, 2024)?
+        );
+        assert_eq!(Vec::<Currency>::new(), year_currencies(conn, 2023)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn year_in_the_future() -> Result<()> {
+        let conn = &mut test::db()?;
+        let next_year = Utc::now().year() + 1;
+
+        let months = super::year(conn, next_year, Currency::EUR)?;
+        assert!(months.iter().all(|m| m.stats.is_none()));
+        assert!(months.iter().all(|m| m.cumulative_net.is_zero()));
+
+        Ok(())
+    }
+}
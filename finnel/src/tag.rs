@@ -0,0 +1,155 @@
+use crate::{
+    essentials::*,
+    schema::{record_tags, tags},
+};
+
+use diesel::prelude::*;
+
+#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = tags)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+}
+
+impl Tag {
+    pub fn find(conn: &mut Conn, id: i64) -> Result<Self> {
+        tags::table
+            .find(id)
+            .select(Tag::as_select())
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Tag", None))
+    }
+
+    /// Find a tag by its name, ignoring case
+    pub fn find_by_name(conn: &mut Conn, name: &str) -> Result<Self> {
+        tags::table
+            .filter(tags::name.eq(name))
+            .select(Tag::as_select())
+            .first(conn)
+            .map_err(|e| Error::from_diesel_error(e, "Tag", Some("name")))
+    }
+
+    /// Find a tag by its name, creating it if it does not exist yet
+    pub fn find_or_create(conn: &mut Conn, name: &str) -> Result<Self> {
+        match Self::find_by_name(conn, name) {
+            Err(e) if e.is_not_found() => NewTag::new(name).save(conn),
+            result => result,
+        }
+    }
+
+    /// List all tags along with the number of records using them
+    pub fn all_with_count(conn: &mut Conn) -> Result<Vec<(Tag, i64)>> {
+        Ok(tags::table
+            .left_join(record_tags::table)
+            .group_by(tags::id)
+            .select((
+                Tag::as_select(),
+                diesel::dsl::count(record_tags::record_id.nullable()),
+            ))
+            .order(tags::name.asc())
+            .load(conn)?)
+    }
+
+    /// Delete the current tag, removing its associations with records
+    ///
+    /// This method executes multiple queries without wrapping them in a
+    /// transaction
+    pub fn delete(&mut self, conn: &mut Conn) -> Result<()> {
+        diesel::delete(record_tags::table)
+            .filter(record_tags::tag_id.eq(self.id))
+            .execute(conn)?;
+        diesel::delete(&*self).execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tags)]
+pub struct NewTag<'a> {
+    pub name: &'a str,
+}
+
+impl<'a> NewTag<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self { name }
+    }
+}
+
+impl NewTag<'_> {
+    pub fn save(self, conn: &mut Conn) -> Result<Tag> {
+        if self.name.is_empty() {
+            return Err(Error::Invalid("Tag name cannot be empty".to_owned()));
+        }
+
+        Ok(diesel::insert_into(tags::table)
+            .values(self)
+            .returning(Tag::as_returning())
+            .get_result(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn crud() -> Result<()> {
+        let conn = &mut test::db()?;
+
+        let mut tag = NewTag::new("Holidays").save(conn)?;
+        assert_eq!(tag.id, Tag::find_by_name(conn, "holidays")?.id);
+        assert_eq!(tag.id, Tag::find_or_create(conn, "HOLIDAYS")?.id);
+        assert!(Tag::find_or_create(conn, "Work")?.id != tag.id);
+        assert!(NewTag::new("holidays").save(conn).is_err());
+        assert!(NewTag::new("").save(conn).is_err());
+
+        tag.delete(conn)?;
+        assert!(Tag::find(conn, tag.id).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let record = test::record!(conn, &account);
+
+        let mut tag = test::tag!(conn, "foo");
+        let bar = test::tag!(conn, "bar");
+        record.add_tag(conn, &tag)?;
+        record.add_tag(conn, &bar)?;
+
+        tag.delete(conn)?;
+        let tags = record.fetch_tags(conn)?;
+        assert_eq!(1, tags.len());
+        assert_eq!(bar.id, tags[0].id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn all_with_count() -> Result<()> {
+        let conn = &mut test::db()?;
+        let account = test::account!(conn, "Cash");
+        let record_1 = test::record!(conn, &account);
+        let record_2 = test::record!(conn, &account);
+
+        let foo = test::tag!(conn, "foo");
+        test::tag!(conn, "bar");
+        record_1.add_tag(conn, &foo)?;
+        record_2.add_tag(conn, &foo)?;
+
+        let tags = Tag::all_with_count(conn)?
+            .into_iter()
+            .map(|(tag, count)| (tag.name, count))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("bar".to_owned(), 0), ("foo".to_owned(), 2)], tags);
+
+        Ok(())
+    }
+}
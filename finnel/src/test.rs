@@ -24,7 +24,7 @@ macro_rules! reloadable {
     };
 }
 
-reloadable!(Account, Category, Merchant, Record, Report, RecurringPayment);
+reloadable!(Account, Category, Merchant, Record, Report, RecurringPayment, Tag);
 
 pub fn db() -> Result<Conn> {
     let mut db = crate::Database::memory()?;
@@ -109,8 +109,15 @@ macro_rules! recurring_payment {
     };
 }
 
+macro_rules! tag {
+    ($conn:ident, $name:expr) => {
+        crate::tag::NewTag::new($name).save($conn)?
+    };
+}
+
 pub(crate) use account;
 pub(crate) use category;
 pub(crate) use merchant;
 pub(crate) use record;
 pub(crate) use recurring_payment as recpay;
+pub(crate) use tag;
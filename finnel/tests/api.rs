@@ -0,0 +1,160 @@
+//! Use the crate like another program would, only through `finnel::api`
+
+use finnel::api::*;
+
+use pretty_assertions::assert_eq;
+
+fn date(date: &str) -> Result<NaiveDate> {
+    parse::date(date, "%Y-%m-%d")
+}
+
+#[test]
+fn end_to_end() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("finnel-api-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let result = (|| {
+        let db = &mut open_database(&path)?;
+        let account = create_account(db, CreateAccountParams::new("Cash"))?;
+        let food = create_category(
+            db,
+            CreateCategoryParams {
+                name: "Food".to_string(),
+                ..Default::default()
+            },
+        )?;
+        let bakery = create_merchant(
+            db,
+            CreateMerchantParams {
+                name: "Bakery".to_string(),
+                default_category_id: Some(food.id),
+                ..Default::default()
+            },
+        )?;
+
+        for (amount, operation_date) in [("4,50 €", "2024-08-02"), ("12.30", "2024-08-20")] {
+            create_record(
+                db,
+                CreateRecordParams {
+                    amount: parse::amount(amount)?,
+                    operation_date: date(operation_date)?,
+                    value_date: date(operation_date)?,
+                    details: "Bread".to_string(),
+                    category_id: Some(food.id),
+                    merchant_id: Some(bakery.id),
+                    ..CreateRecordParams::new(account.id)
+                },
+            )?;
+        }
+        let salary = create_record(
+            db,
+            CreateRecordParams {
+                amount: Decimal::from(2000),
+                direction: Direction::Credit,
+                operation_date: date("2024-08-31")?,
+                value_date: date("2024-08-31")?,
+                details: "Salary".to_string(),
+                ..CreateRecordParams::new(account.id)
+            },
+        )?;
+
+        let records = list_records(
+            db,
+            RecordFilter {
+                account_id: Some(account.id),
+                order: vec![(
+                    OrderField::Amount,
+                    OrderDirection::Desc,
+                    OrderNulls::Default,
+                )],
+                ..Default::default()
+            },
+        )?;
+        let amounts = records.iter().map(|r| r.amount).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Decimal::from(2000),
+                Decimal::new(1230, 2),
+                Decimal::new(450, 2)
+            ],
+            amounts
+        );
+
+        // The query structs can be used directly too
+        let records = QueryRecord {
+            merchant_id: Some(Some(bakery.id)),
+            ..Default::default()
+        }
+        .run(db)?;
+        assert_eq!(2, records.len());
+        let categories = QueryCategory {
+            name: Some("food"),
+            ..Default::default()
+        }
+        .run(db)?;
+        assert_eq!(
+            vec![food.id],
+            categories.iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+        let merchants = QueryMerchant::default().run(db)?;
+        assert_eq!(
+            vec![bakery.id],
+            merchants.iter().map(|m| m.id).collect::<Vec<_>>()
+        );
+
+        let august = Month::calendar(2024, 8).as_date_range()?;
+        let stats = monthly_stats(db, 2024, 8, Currency::EUR)?;
+        assert_eq!(Decimal::new(1680, 2), stats.debit_amount);
+        assert_eq!(Decimal::from(2000), stats.credit_amount);
+
+        let stats = category_stats(db, august.clone(), Currency::EUR, Some(account.id))?;
+        let food_stats = stats
+            .iter()
+            .find(|s| s.category_id == Some(food.id))
+            .expect("stats of the category");
+        assert_eq!(Decimal::new(1680, 2), food_stats.amount);
+
+        let totals = account_activity(db, account.id, august.clone())?.totals(august);
+        assert_eq!(3, totals.count);
+
+        let stats = merchant_stats(db, bakery.id)?;
+        assert_eq!(1, stats.len());
+
+        // Changes made in a failed transaction are rolled back
+        let result = transaction(db, |conn| {
+            update_record(
+                conn,
+                salary.id,
+                UpdateRecordParams {
+                    details: Some("Bonus".to_string()),
+                    ..Default::default()
+                },
+            )?;
+            delete_record(conn, -1)
+        });
+        assert!(result.is_err_and(|e| e.is_not_found()));
+        assert_eq!("Salary", Record::find(db, salary.id)?.details);
+
+        delete_merchant(db, bakery.id)?;
+        assert!(Merchant::find(db, bakery.id).is_err());
+        delete_category(db, food.id)?;
+        assert_eq!(None, Record::find(db, records[0].id)?.category_id);
+        delete_account(db, account.id)?;
+        assert!(list_records(db, RecordFilter::default())?.is_empty());
+
+        Ok(())
+    })();
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(
+        "\"12,505\" has more than 2 decimal places",
+        parse::amount("12,505").unwrap_err().to_string()
+    );
+    assert!(parse::decimal("twelve").is_err());
+    assert!(date("31/08/2024").is_err());
+}
@@ -0,0 +1,6 @@
+#[path = "../build/features.rs"]
+mod features;
+
+fn main() {
+    features::write_features();
+}
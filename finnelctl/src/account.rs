@@ -1,33 +1,137 @@
 use anyhow::Result;
+use clap::ValueEnum;
 
 use finnel::{
-    account::{NewAccount, QueryAccount},
+    account::{AccountGroup, BalanceAssertion, BalanceRecomputation, QueryAccount, Reconciliation},
+    api::{self, CreateAccountParams, UpdateAccountParams},
     prelude::*,
+    record::{
+        query::{OrderDirection, OrderField, OrderNulls},
+        NewBalanceAdjustment, QueryRecord,
+    },
+    stats::ActivityStats,
 };
 
 use crate::cli::account::*;
-use crate::config::Config;
+use crate::cli::category::Identifier as CategoryIdentifier;
+use crate::config::{Config, ConfigStore};
+use crate::error::CliError;
+use crate::journal::Journal;
+use crate::utils::max_amount::MaxAmount;
+use crate::utils::table_display::Style;
+
+use chrono::{Days, NaiveDate, Utc, Weekday};
+use serde_json::json;
+use std::collections::BTreeMap;
 
 use tabled::builder::Builder as TableBuilder;
 
+/// Number of weeks whose debits `show` prints
+const WEEKS: u64 = 8;
+
+/// Number of records since the last reconciliation that `reconcile` prints
+const UNRECONCILED_SHOWN: usize = 20;
+
 struct CommandContext<'a> {
     config: &'a Config,
     conn: &'a mut Database,
+    style: Style,
 }
 
 pub fn run(config: &Config, command: &Command) -> Result<()> {
-    let conn = &mut config.database()?;
-    let mut cmd = CommandContext { conn, config };
+    let conn = &mut config.database_for(command)?;
+    let mut cmd = CommandContext {
+        conn,
+        config,
+        style: config.style()?,
+    };
 
     match &command {
         Command::List(args) => cmd.list(args),
         Command::Create(args) => cmd.create(args),
+        Command::Update(args) => cmd.update(args),
         Command::Show(args) => cmd.show(args),
         Command::Delete(args) => cmd.delete(args),
         Command::Default(args) => cmd.default(args),
+        Command::Reconcile(args) => cmd.reconcile(args),
+        Command::AssertBalance(args) => cmd.assert_balance(args),
+        Command::Check(args) => cmd.check(args),
+        Command::Adjust(args) => cmd.adjust(args),
+        Command::RecomputeBalance(args) => cmd.recompute_balance(args),
+        Command::Group(action) => cmd.group(action),
+        Command::Config(action) => cmd.configure(action),
+    }
+}
+
+/// Category configured for the account, ignoring a category deleted since
+pub fn category_setting(
+    config: &Config,
+    conn: &mut Conn,
+    account: &Account,
+    key: ConfigurationKey,
+) -> Result<Option<Category>> {
+    let Some(id) = settings(config, account)?.get(key.as_str())? else {
+        return Ok(None);
+    };
+
+    match Category::find(conn, id.trim().parse()?) {
+        Ok(category) => Ok(Some(category.resolve(conn)?)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e.into()),
     }
 }
 
+fn settings(config: &Config, account: &Account) -> Result<ConfigStore> {
+    config
+        .store()?
+        .scoped("accounts")?
+        .scoped(&account.id.to_string())
+}
+
+/// Warn that the balance assertions counting the records, given by account
+/// and operation date, may no longer hold
+pub fn warn_balance_assertions<I>(conn: &mut Conn, records: I) -> Result<()>
+where
+    I: IntoIterator<Item = (i64, NaiveDate)>,
+{
+    let mut earliest = BTreeMap::<i64, NaiveDate>::new();
+    for (account_id, date) in records {
+        earliest
+            .entry(account_id)
+            .and_modify(|earliest| *earliest = date.min(*earliest))
+            .or_insert(date);
+    }
+
+    for (account_id, date) in earliest {
+        let count = BalanceAssertion::count_from(conn, account_id, date)?;
+        if count > 0 {
+            let account = Account::find(conn, account_id)?;
+            eprintln!(
+                "Warning: {count} balance assertion(s) of {} may need rechecking with account check",
+                account.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// One bar per amount, the highest one being full
+fn sparkline(amounts: impl Iterator<Item = Decimal> + Clone) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = amounts.clone().max().unwrap_or_default();
+    amounts
+        .map(|amount| {
+            let level = (1..BARS.len())
+                .rev()
+                .find(|&i| amount * Decimal::from(BARS.len() - 1) >= max * Decimal::from(i))
+                .filter(|_| !max.is_zero())
+                .unwrap_or_default();
+            BARS[level]
+        })
+        .collect()
+}
+
 impl CommandContext<'_> {
     fn get(&mut self, name: Option<&str>) -> Result<Account> {
         Ok(if let Some(name) = name {
@@ -41,10 +145,15 @@ impl CommandContext<'_> {
 
     fn list(&mut self, _args: &List) -> Result<()> {
         let mut builder = TableBuilder::new();
-        table_push_row_elements!(builder, "id", "name", "balance");
+        table_push_row_elements!(builder, &self.style; "id", "name", "balance");
 
         for account in QueryAccount::default().run(self.conn)? {
-            table_push_row_elements!(builder, account.id, account.name, account.balance());
+            table_push_row_elements!(
+                builder, &self.style;
+                account.id,
+                account.name,
+                account.balance()
+            );
         }
 
         println!("{}", builder.build());
@@ -56,33 +165,392 @@ impl CommandContext<'_> {
         let account = self.get(args.name.as_deref())?;
 
         println!("{} | {}", account.id, account.name);
-        println!("\tBalance: {}", account.balance());
+        println!("\tBalance: {}", self.style.format_amount(account.balance()));
+        if let Some(iban) = &account.iban {
+            println!("\tIBAN: {}", iban);
+        }
+        if let Some((date, balance)) = account.reconciliation() {
+            println!(
+                "\tReconciled: {} on {}",
+                self.style.format_amount(balance),
+                date
+            );
+        }
+        if !BalanceAssertion::of_account(self.conn, &account)?.is_empty() {
+            println!("\tBalance assertions:");
+            self.check_assertions(&account, "\t\t")?;
+        }
+
+        let today = Utc::now().date_naive();
+        let tomorrow = today + Days::new(1);
+        let from = today
+            .checked_sub_days(Days::new(u64::from(args.days) - 1))
+            .ok_or_else(|| anyhow::anyhow!("Can't summarize the last {} days", args.days))?;
+        // Whole weeks, the last one being the current week
+        let weeks_from = today.week(Weekday::Mon).first_day() - Days::new(7 * (WEEKS - 1));
+        let stats =
+            ActivityStats::from_date_range(self.conn, &account, from.min(weeks_from)..tomorrow)?;
+
+        let totals = stats.totals(from..tomorrow);
+        println!(
+            "\tLast {} days: {} records, {} debited, {} credited",
+            args.days,
+            totals.count,
+            self.style.format_amount(totals.debit_amount()),
+            self.style.format_amount(totals.credit_amount())
+        );
+
+        let weeks = (0..WEEKS)
+            .map(|week| {
+                let start = weeks_from + Days::new(7 * week);
+                (
+                    start,
+                    stats.totals(start..start + Days::new(7)).debit_amount(),
+                )
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "\tWeekly debits: {}",
+            sparkline(weeks.iter().map(|(_, amount)| amount.0))
+        );
+        for (start, amount) in &weeks {
+            println!("\t\t{}\t{}", start, self.style.format_amount(*amount));
+        }
+
+        let rows = QueryRecord {
+            account_id: Some(account.id),
+            count: Some(5),
+            order: vec![(OrderField::Date, OrderDirection::Desc, OrderNulls::Default)],
+            ..QueryRecord::default()
+        }
+        .with_category()
+        .with_parent()
+        .with_merchant()
+        .run(self.conn)?;
+        if !rows.is_empty() {
+            println!("\tLast records:");
+            self.style.load_category_paths(self.conn)?;
+            table_display!(self.config, &self.style, rows);
+        }
 
         Ok(())
     }
 
     fn create(&mut self, args: &Create) -> Result<()> {
-        NewAccount::new(&args.name).save(self.conn)?;
+        if let Some(main_currency) = self.config.main_currency()? {
+            if main_currency != args.currency {
+                let message = format!(
+                    "Account {} is in {}, not in the main currency {}",
+                    args.name,
+                    args.currency.code(),
+                    main_currency.code()
+                );
+                if !args.non_main_ok {
+                    anyhow::bail!("{message}, use --non-main-ok to create it anyway");
+                }
+                eprintln!("Warning: {message}");
+            }
+        }
+
+        let account = api::create_account(
+            self.conn,
+            CreateAccountParams {
+                currency: args.currency,
+                iban: args.iban(),
+                ..CreateAccountParams::new(&args.name)
+            },
+        )?;
+        Journal::new(self.config)?.log(
+            "account create",
+            json!({
+                "name": account.name,
+                "currency": account.currency.to_string(),
+                "iban": account.iban,
+            }),
+            [account.id],
+        );
+        Ok(())
+    }
+
+    fn update(&mut self, args: &Update) -> Result<()> {
+        let account = self.get(args.name.as_deref())?;
+        let iban = args.iban();
+        api::update_account(
+            self.conn,
+            account.id,
+            UpdateAccountParams {
+                name: args.new_name.clone(),
+                iban: iban.clone(),
+            },
+        )?;
+        let mut changes = serde_json::Map::new();
+        if let Some(name) = &args.new_name {
+            changes.insert("name".to_owned(), json!(name));
+        }
+        if let Some(iban) = iban {
+            changes.insert("iban".to_owned(), json!(iban));
+        }
+        Journal::new(self.config)?.log("account update", changes.into(), [account.id]);
         Ok(())
     }
 
     fn delete(&mut self, args: &Delete) -> Result<()> {
         let mut account = self.get(args.name.as_deref())?;
+        let reassign_to = args
+            .reassign_to
+            .as_deref()
+            .map(|name| Account::find_by_name(self.conn, name))
+            .transpose()?;
+
+        if args.confirm {
+            let report = account.deletion_report(self.conn)?;
+            match report.dates {
+                Some((first, last)) => println!(
+                    "{} holds {} records, from {first} to {last}",
+                    account.name, report.records
+                ),
+                None => println!("{} holds no records", account.name),
+            }
+            println!("\tDebit: {}", report.debit());
+            println!("\tCredit: {}", report.credit());
+            println!("\tRecurring payments: {}", report.recurring_payments);
+            if let Some(other) = &reassign_to {
+                println!("The records will be moved to {}", other.name);
+            }
+        }
+
+        if args.confirm && crate::utils::confirm(self.config)? {
+            match &reassign_to {
+                Some(other) => account.delete_moving_records(self.conn, other)?,
+                None => account.delete(self.conn)?,
+            }
+            let settings = settings(self.config, &account)?;
+            for key in ConfigurationKey::value_variants() {
+                settings.reset(key.as_str())?;
+            }
+            Journal::new(self.config)?.log(
+                "account delete",
+                json!({ "reassign_to": reassign_to.as_ref().map(|other| other.id) }),
+                [account.id],
+            );
+        } else {
+            anyhow::bail!(CliError::confirmation_required());
+        }
+        Ok(())
+    }
+
+    fn assert_balance(&mut self, args: &AssertBalance) -> Result<()> {
+        let account = self.get(None)?;
+        let assertion = BalanceAssertion::create(self.conn, &account, args.date, args.amount)?;
+        Journal::new(self.config)?.log(
+            "account assert-balance",
+            json!({
+                "account": account.id,
+                "date": args.date,
+                "amount": args.amount.to_string(),
+            }),
+            [assertion.id],
+        );
+
+        let delta = assertion.delta(self.conn, &account)?;
+        if !delta.is_zero() {
+            eprintln!(
+                "Warning: the records add up to {} at {}",
+                Amount(assertion.amount + delta, account.currency),
+                args.date
+            );
+        }
+        Ok(())
+    }
+
+    fn adjust(&mut self, args: &Adjust) -> Result<()> {
+        let account = self.get(args.name.as_deref())?;
+        let adjustment = NewBalanceAdjustment {
+            date: args.date.unwrap_or_else(|| Utc::now().date_naive()),
+            details: args.details.as_deref(),
+            ..NewBalanceAdjustment::new(&account, args.to)
+        };
+        let date = adjustment.date;
+        let amount = |value| Amount(value, account.currency);
+        MaxAmount::load(self.config)?
+            .check_creation(adjustment.difference(self.conn)?, args.confirm)?;
+
+        let Some(record) = adjustment.save(self.conn)? else {
+            println!(
+                "The balance of {} is already {} at {date}, nothing to adjust",
+                account.name,
+                amount(args.to)
+            );
+            return Ok(());
+        };
+        println!(
+            "Adjusted the balance of {} to {} at {date} with a {} of {}",
+            account.name,
+            amount(args.to),
+            record.direction.to_string().to_lowercase(),
+            record.amount()
+        );
+        Journal::new(self.config)?.log(
+            "account adjust",
+            json!({ "to": args.to.to_string(), "date": date }),
+            [record.id],
+        );
+        warn_balance_assertions(self.conn, [(account.id, date)])?;
+
+        Ok(())
+    }
+
+    fn recompute_balance(&mut self, args: &RecomputeBalance) -> Result<()> {
+        let account = self.get(args.name.as_deref())?;
+        let date = args.date.unwrap_or_else(|| Utc::now().date_naive());
+        let recomputation = BalanceRecomputation::run(self.conn, &account, date)?;
+        let amount = |value| Amount(value, account.currency);
+
+        for warning in &recomputation.invalid {
+            eprintln!("Warning: {warning}, left out of the balance");
+        }
+        println!(
+            "Balance of {} at {date}: {} from {} records",
+            account.name,
+            amount(recomputation.derived),
+            recomputation.records
+        );
+
+        let correction = recomputation.correction();
+        if !correction.is_zero() {
+            anyhow::bail!(CliError::ValidationFailed(format!(
+                "The balance summed by the database is {}, off by {}",
+                amount(recomputation.summed),
+                amount(correction)
+            )));
+        }
+        println!("Matches the balance summed by the database");
+        Ok(())
+    }
+
+    fn check(&mut self, args: &Check) -> Result<()> {
+        let account = self.get(args.name.as_deref())?;
+        if BalanceAssertion::of_account(self.conn, &account)?.is_empty() {
+            println!("No balance assertions for {}", account.name);
+            return Ok(());
+        }
+
+        let failed = self.check_assertions(&account, "")?;
+        if failed > 0 {
+            anyhow::bail!(CliError::ValidationFailed(format!(
+                "{failed} balance assertion(s) of {} failed",
+                account.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Print a line per balance assertion of the account telling whether it
+    /// holds, returning the number of failed ones
+    fn check_assertions(&mut self, account: &Account, indent: &str) -> Result<usize> {
+        let mut failed = 0;
+        for assertion in BalanceAssertion::of_account(self.conn, account)? {
+            let delta = assertion.delta(self.conn, account)?;
+            if delta.is_zero() {
+                println!("{indent}{}\t{}\tOK", assertion.date, assertion.amount());
+            } else {
+                failed += 1;
+                println!(
+                    "{indent}{}\t{}\tFAIL\t{}",
+                    assertion.date,
+                    assertion.amount(),
+                    Amount(delta, assertion.currency)
+                );
+            }
+        }
+        Ok(failed)
+    }
+
+    fn reconcile(&mut self, args: &Reconcile) -> Result<()> {
+        let mut account = self.get(args.name.as_deref())?;
+        let reconciliation =
+            Reconciliation::new(self.conn, &account, args.at, args.statement_balance)?;
+        let amount = |value| Amount(value, account.currency);
+
+        println!("Balance of {} at {}", account.name, args.at);
+        println!("\tRecords: {}", amount(reconciliation.balance));
+        println!("\tStatement: {}", amount(reconciliation.statement_balance));
+        match account.reconciliation() {
+            Some((date, balance)) => println!("\tLast reconciled: {} on {}", balance, date),
+            None => println!("\tNever reconciled"),
+        }
 
-        if args.confirm && crate::utils::confirm()? {
-            account.delete(self.conn)?;
+        if reconciliation.is_balanced() {
+            println!("The balances match");
+            if !args.confirm {
+                println!("Use --confirm to record the reconciliation");
+            } else if crate::utils::confirm(self.config)? {
+                account.reconcile(self.conn, args.at, args.statement_balance)?;
+                if crate::record::clears_flags_on_reconcile(self.config)? {
+                    let cleared = Record::clear_flags(self.conn, account.id, args.at)?;
+                    if cleared > 0 {
+                        println!("Cleared the flag of {cleared} record(s)");
+                    }
+                }
+                Journal::new(self.config)?.log(
+                    "account reconcile",
+                    json!({
+                        "at": args.at,
+                        "statement_balance": args.statement_balance.to_string(),
+                    }),
+                    [account.id],
+                );
+                println!("Reconciled {} at {}", account.name, args.at);
+            } else {
+                anyhow::bail!(CliError::confirmation_required());
+            }
+            return Ok(());
+        }
+
+        println!("Difference: {}", amount(reconciliation.difference()));
+        if args.confirm {
+            eprintln!("Warning: not recording the reconciliation as the balances differ");
+        }
+
+        self.style.load_category_paths(self.conn)?;
+        let unreconciled = reconciliation.unreconciled.len();
+        println!("\n{unreconciled} record(s) since the last reconciliation");
+        table_display!(
+            self.config,
+            &self.style,
+            reconciliation
+                .unreconciled
+                .into_iter()
+                .take(UNRECONCILED_SHOWN)
+                .collect::<Vec<_>>()
+        );
+        if unreconciled > UNRECONCILED_SHOWN {
+            println!("... and {} more", unreconciled - UNRECONCILED_SHOWN);
+        }
+
+        if !reconciliation.unusual.is_empty() {
+            println!("\nRecords with unusual amounts");
+            table_display!(self.config, &self.style, reconciliation.unusual);
+        }
+
+        if reconciliation.matches.is_empty() {
+            println!("\nNo combination of recent records adds up to the difference");
         } else {
-            anyhow::bail!("operation requires confirmation");
+            println!("\nRecent records adding up to the difference, e.g. not yet on the statement");
+        }
+        for records in reconciliation.matches {
+            table_display!(self.config, &self.style, records);
         }
+
         Ok(())
     }
 
     fn default(&mut self, args: &Default) -> Result<()> {
         if let Some(name) = args.name.as_deref().or(self.config.account_name()) {
             let account = Account::find_by_name(self.conn, name)?;
-            Ok(self.config.set("default_account", &account.name)?)
+            self.config.store()?.set("default_account", &account.name)
         } else if args.reset {
-            Ok(self.config.reset("default_account")?)
+            self.config.store()?.reset("default_account")
         } else {
             let account_name = self
                 .config
@@ -93,4 +561,99 @@ impl CommandContext<'_> {
             Ok(())
         }
     }
+
+    fn group(&mut self, action: &GroupAction) -> Result<()> {
+        let journal = Journal::new(self.config)?;
+        let mut find_accounts = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| Ok(Account::find_by_name(self.conn, name)?))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        match action {
+            GroupAction::List => {
+                let mut builder = TableBuilder::new();
+                table_push_row_elements!(builder, &self.style; "id", "name", "accounts");
+
+                for group in AccountGroup::all(self.conn)? {
+                    let names = group
+                        .accounts
+                        .iter()
+                        .map(|a| a.name.as_str())
+                        .collect::<Vec<_>>();
+                    table_push_row_elements!(builder, &self.style; group.id, group.name, names.join(", "));
+                }
+
+                println!("{}", builder.build());
+            }
+            GroupAction::Create { name } => {
+                let group = AccountGroup::create(self.conn, name)?;
+                journal.log("account group create", json!({ "name": name }), [group.id]);
+            }
+            GroupAction::Add { name, accounts } => {
+                let accounts = find_accounts(accounts)?;
+                let mut group = AccountGroup::find_by_name(self.conn, name)?;
+                group.add(self.conn, &accounts)?;
+                journal.log(
+                    "account group add",
+                    json!({ "accounts": accounts.iter().map(|a| a.id).collect::<Vec<_>>() }),
+                    [group.id],
+                );
+            }
+            GroupAction::Remove { name, accounts } => {
+                let accounts = find_accounts(accounts)?;
+                let mut group = AccountGroup::find_by_name(self.conn, name)?;
+                group.remove(self.conn, &accounts)?;
+                journal.log(
+                    "account group remove",
+                    json!({ "accounts": accounts.iter().map(|a| a.id).collect::<Vec<_>>() }),
+                    [group.id],
+                );
+            }
+            GroupAction::Delete { name } => {
+                let mut group = AccountGroup::find_by_name(self.conn, name)?;
+                group.delete(self.conn)?;
+                journal.log("account group delete", json!({}), [group.id]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn configure(&mut self, action: &ConfigurationAction) -> Result<()> {
+        let account = self.get(None)?;
+
+        use ConfigurationAction::*;
+
+        match action {
+            Get { key } => {
+                if let Some(category) = category_setting(self.config, self.conn, &account, *key)? {
+                    println!("{}", category.name);
+                }
+            }
+            Set { key, value } => {
+                let category = CategoryIdentifier::from(value.clone()).find(self.conn)?;
+                settings(self.config, &account)?.set(key.as_str(), &category.id.to_string())?;
+            }
+            Reset { key } => {
+                settings(self.config, &account)?.reset(key.as_str())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::assert_eq;
+
+    #[test]
+    fn sparkline() {
+        let amounts = [0, 7, 14, 70, 35].map(Decimal::from);
+        assert_eq!("▁▁▂█▄", super::sparkline(amounts.into_iter()));
+        assert_eq!("▁▁", super::sparkline([Decimal::ZERO; 2].into_iter()));
+    }
 }
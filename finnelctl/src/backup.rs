@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use finnel::{
+    account::{NewAccount, QueryAccount},
+    category::{ChangeCategory, NewCategory, QueryCategory},
+    merchant::{ChangeMerchant, NewMerchant, QueryMerchant},
+    prelude::*,
+    record::{ChangeRecord, NewRecord, QueryRecord},
+    recurring_payment::NewRecurringPayment,
+};
+
+use crate::cli::backup::*;
+use crate::config::Config;
+use crate::journal::Journal;
+
+/// Version of the backup document, to bump whenever its format changes
+const VERSION: u32 = 1;
+
+/// Content of the database, referencing other objects by name
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub accounts: Vec<AccountData>,
+    pub categories: Vec<CategoryData>,
+    pub merchants: Vec<MerchantData>,
+    pub records: Vec<RecordData>,
+    pub recurring_payments: Vec<RecurringPaymentData>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountData {
+    pub name: String,
+    pub currency: String,
+    pub balance: String,
+    pub iban: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryData {
+    pub name: String,
+    pub parent: Option<String>,
+    pub replaced_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_from_stats: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MerchantData {
+    pub name: String,
+    pub default_category: Option<String>,
+    pub replaced_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordData {
+    /// None for a pending record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Currency of a pending record, the others having the one of their
+    /// account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    pub amount: String,
+    pub operation_date: NaiveDate,
+    pub value_date: NaiveDate,
+    pub direction: String,
+    pub mode: String,
+    pub details: String,
+    pub category: Option<String>,
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flagged_at: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_amount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecurringPaymentData {
+    pub name: String,
+    pub description: String,
+    pub frequency: String,
+    pub account: String,
+    pub amount: String,
+    pub direction: String,
+    pub mode: String,
+    pub category: Option<String>,
+    pub merchant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<NaiveDate>,
+}
+
+pub fn export(config: &Config, args: &Export) -> Result<()> {
+    let conn = &mut config.database()?;
+    let document = Document::from_database(conn)?;
+    let json = serde_json::to_string_pretty(&document)? + "\n";
+
+    if let Some(path) = &args.output {
+        std::fs::write(path, json)
+            .with_context(|| format!("Unable to write backup to {}", path.display()))?;
+    } else {
+        print!("{}", json);
+    }
+
+    Ok(())
+}
+
+pub fn restore(config: &Config, args: &Restore) -> Result<()> {
+    let content = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("Unable to read backup {}", args.file.display()))?;
+    let document = serde_json::from_str::<Document>(&content)?;
+
+    if document.version > VERSION {
+        anyhow::bail!(
+            "Backup version {} is not supported, please upgrade finnelctl",
+            document.version
+        );
+    }
+
+    let conn = &mut config.database()?;
+    conn.transaction(|conn| {
+        if !args.merge && !is_empty(conn)? {
+            anyhow::bail!("Database is not empty, use --merge to restore into it anyway");
+        }
+        document.restore(conn)
+    })?;
+    Journal::new(config)?.log(
+        "restore",
+        serde_json::json!({
+            "file": args.file,
+            "merge": args.merge,
+            "records": document.records.len(),
+        }),
+        [],
+    );
+
+    Ok(())
+}
+
+fn is_empty(conn: &mut Conn) -> Result<bool> {
+    Ok(QueryAccount::default().run(conn)?.is_empty()
+        && QueryCategory::default().run(conn)?.is_empty()
+        && QueryMerchant::default().run(conn)?.is_empty())
+}
+
+fn name_of(names: &HashMap<i64, String>, id: Option<i64>) -> Option<String> {
+    id.and_then(|id| names.get(&id).cloned())
+}
+
+fn get<'a, T>(objects: &'a HashMap<String, T>, kind: &str, name: &str) -> Result<&'a T> {
+    objects
+        .get(name)
+        .with_context(|| format!("{} not found in backup: {}", kind, name))
+}
+
+fn get_optional<'a, T>(
+    objects: &'a HashMap<String, T>,
+    kind: &str,
+    name: Option<&String>,
+) -> Result<Option<&'a T>> {
+    name.map(|name| get(objects, kind, name)).transpose()
+}
+
+fn parse<T>(value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse::<T>()
+        .with_context(|| format!("Unable to parse {:?}", value))
+}
+
+/// Order the replacements so that an object is always replaced before its
+/// own replacer, otherwise the replacer resolution would follow the chain and
+/// store the last replacer instead
+fn replacement_order<'a>(links: Vec<(&'a str, &'a str)>) -> Vec<(&'a str, &'a str)> {
+    let replacers = links.iter().copied().collect::<HashMap<_, _>>();
+    let depth = |mut name: &'a str| {
+        let mut depth = 0;
+        while let Some(replacer) = replacers.get(name) {
+            depth += 1;
+            if depth > replacers.len() {
+                break;
+            }
+            name = replacer;
+        }
+        depth
+    };
+
+    let mut links = links;
+    links.sort_by_key(|(name, _)| std::cmp::Reverse(depth(name)));
+    links
+}
+
+impl Document {
+    pub fn from_database(conn: &mut Conn) -> Result<Self> {
+        let mut document = Document {
+            version: VERSION,
+            ..Default::default()
+        };
+
+        let accounts = QueryAccount::default().run(conn)?;
+        let account_names = accounts
+            .iter()
+            .map(|a| (a.id, a.name.clone()))
+            .collect::<HashMap<_, _>>();
+        for account in accounts {
+            document.accounts.push(AccountData {
+                name: account.name,
+                currency: account.currency.code().to_string(),
+                balance: account.balance.to_string(),
+                iban: account.iban,
+            });
+        }
+
+        let categories = QueryCategory::default().run(conn)?;
+        let category_names = categories
+            .iter()
+            .map(|c| (c.id, c.name.clone()))
+            .collect::<HashMap<_, _>>();
+        for category in categories {
+            document.categories.push(CategoryData {
+                parent: name_of(&category_names, category.parent_id),
+                replaced_by: name_of(&category_names, category.replaced_by_id),
+                name: category.name,
+                color: category.color,
+                emoji: category.emoji,
+                exclude_from_stats: category.exclude_from_stats,
+            });
+        }
+
+        let merchants = QueryMerchant::default().run(conn)?;
+        let merchant_names = merchants
+            .iter()
+            .map(|m| (m.id, m.name.clone()))
+            .collect::<HashMap<_, _>>();
+        for merchant in merchants {
+            document.merchants.push(MerchantData {
+                default_category: name_of(&category_names, merchant.default_category_id),
+                replaced_by: name_of(&merchant_names, merchant.replaced_by_id),
+                name: merchant.name,
+                website: merchant.website,
+                notes: merchant.notes,
+            });
+        }
+
+        let pending = QueryRecord {
+            pending: true,
+            ..Default::default()
+        };
+        for record in QueryRecord::default()
+            .run(conn)?
+            .into_iter()
+            .chain(pending.run(conn)?)
+        {
+            document.records.push(RecordData {
+                account: record.account_id.map(|id| account_names[&id].clone()),
+                currency: record.pending.then(|| record.currency.code().to_string()),
+                amount: record.amount.to_string(),
+                operation_date: record.operation_date,
+                value_date: record.value_date,
+                direction: record.direction.to_string(),
+                mode: record.mode.to_string(),
+                category: name_of(&category_names, record.category_id),
+                merchant: name_of(&merchant_names, record.merchant_id),
+                tags: record
+                    .fetch_tags(conn)?
+                    .into_iter()
+                    .map(|tag| tag.name)
+                    .collect(),
+                flagged_at: record.flagged_at,
+                flag_reason: record.flag_reason,
+                original_amount: record.original_amount.map(|amount| amount.to_string()),
+                original_currency: record
+                    .original_currency
+                    .map(|currency| currency.code().to_string()),
+                details: record.details,
+            });
+        }
+
+        for recpay in RecurringPayment::all(conn)? {
+            document.recurring_payments.push(RecurringPaymentData {
+                account: account_names[&recpay.account_id].clone(),
+                amount: recpay.amount.to_string(),
+                frequency: recpay.frequency.to_string(),
+                direction: recpay.direction.to_string(),
+                mode: recpay.mode.to_string(),
+                category: name_of(&category_names, recpay.category_id),
+                merchant: name_of(&merchant_names, recpay.merchant_id),
+                start_date: recpay.start_date,
+                end_date: recpay.end_date,
+                name: recpay.name,
+                description: recpay.description,
+            });
+        }
+
+        Ok(document)
+    }
+
+    /// Recreate the content of the document in the database
+    ///
+    /// Objects are first created without their references, which are set in
+    /// a second pass. Replacements come last, so the references set before
+    /// are kept as they were instead of being resolved to the replacers.
+    pub fn restore(&self, conn: &mut Conn) -> Result<()> {
+        let mut accounts = HashMap::new();
+        for data in &self.accounts {
+            let account = match Account::find_by_name(conn, &data.name).optional()? {
+                Some(account) => account,
+                None => NewAccount {
+                    balance: parse(&data.balance)?,
+                    currency: Currency::from_code(&data.currency)
+                        .with_context(|| format!("Unknown currency {}", data.currency))?,
+                    iban: data.iban.as_deref(),
+                    ..NewAccount::new(&data.name)
+                }
+                .save(conn)?,
+            };
+            accounts.insert(data.name.clone(), account);
+        }
+
+        let mut categories = HashMap::new();
+        let mut new_categories = Vec::new();
+        for data in &self.categories {
+            let category = match Category::find_by_name(conn, &data.name) {
+                Ok(category) => category,
+                Err(e) if e.is_not_found() => {
+                    new_categories.push(data);
+                    NewCategory {
+                        color: data.color.as_deref(),
+                        emoji: data.emoji.as_deref(),
+                        exclude_from_stats: data.exclude_from_stats,
+                        ..NewCategory::new(&data.name)
+                    }
+                    .save(conn)?
+                }
+                Err(e) => return Err(e.into()),
+            };
+            categories.insert(data.name.clone(), category);
+        }
+        for data in &new_categories {
+            if let Some(parent) = get_optional(&categories, "Category", data.parent.as_ref())? {
+                ChangeCategory {
+                    parent: Some(Some(parent)),
+                    ..Default::default()
+                }
+                .save(conn, get(&categories, "Category", &data.name)?)?;
+            }
+        }
+
+        let mut merchants = HashMap::new();
+        let mut new_merchants = Vec::new();
+        for data in &self.merchants {
+            let merchant = match Merchant::find_by_name(conn, &data.name) {
+                Ok(merchant) => merchant,
+                Err(e) if e.is_not_found() => {
+                    new_merchants.push(data);
+                    NewMerchant {
+                        default_category: get_optional(
+                            &categories,
+                            "Category",
+                            data.default_category.as_ref(),
+                        )?,
+                        website: data.website.as_deref(),
+                        notes: data.notes.as_deref(),
+                        ..NewMerchant::new(&data.name)
+                    }
+                    .save(conn)?
+                }
+                Err(e) => return Err(e.into()),
+            };
+            merchants.insert(data.name.clone(), merchant);
+        }
+
+        for data in &self.records {
+            let new = match (&data.account, &data.currency) {
+                (Some(account), _) => NewRecord::new(get(&accounts, "Account", account)?),
+                (None, Some(code)) => NewRecord::pending(
+                    Currency::from_code(code)
+                        .with_context(|| format!("Unknown currency {code}"))?,
+                ),
+                (None, None) => anyhow::bail!("Record without account nor currency"),
+            };
+            let record = NewRecord {
+                amount: parse(&data.amount)?,
+                operation_date: data.operation_date,
+                value_date: data.value_date,
+                direction: parse(&data.direction)?,
+                mode: parse(&data.mode)?,
+                details: &data.details,
+                category: get_optional(&categories, "Category", data.category.as_ref())?,
+                merchant: get_optional(&merchants, "Merchant", data.merchant.as_ref())?,
+                allow_inverted_dates: true,
+                original_amount: data.original_amount.as_deref().map(parse).transpose()?,
+                original_currency: data
+                    .original_currency
+                    .as_deref()
+                    .map(|code| {
+                        Currency::from_code(code)
+                            .with_context(|| format!("Unknown currency {code}"))
+                    })
+                    .transpose()?,
+                ..new
+            }
+            .save(conn)?;
+
+            for name in &data.tags {
+                let tag = Tag::find_or_create(conn, name)?;
+                record.add_tag(conn, &tag)?;
+            }
+
+            if data.flagged_at.is_some() {
+                ChangeRecord {
+                    flagged_at: Some(data.flagged_at),
+                    flag_reason: Some(data.flag_reason.as_deref()),
+                    ..Default::default()
+                }
+                .save(conn, &record)?;
+            }
+        }
+
+        for data in &self.recurring_payments {
+            NewRecurringPayment {
+                name: &data.name,
+                description: &data.description,
+                frequency: parse(&data.frequency)?,
+                amount: parse(&data.amount)?,
+                direction: parse(&data.direction)?,
+                mode: parse(&data.mode)?,
+                category: get_optional(&categories, "Category", data.category.as_ref())?,
+                merchant: get_optional(&merchants, "Merchant", data.merchant.as_ref())?,
+                start_date: data.start_date,
+                end_date: data.end_date,
+                ..NewRecurringPayment::new(get(&accounts, "Account", &data.account)?)
+            }
+            .save(conn)?;
+        }
+
+        let links = new_categories
+            .iter()
+            .filter_map(|data| Some((data.name.as_str(), data.replaced_by.as_deref()?)))
+            .collect();
+        for (name, replacer) in replacement_order(links) {
+            ChangeCategory {
+                replaced_by: Some(Some(get(&categories, "Category", replacer)?)),
+                ..Default::default()
+            }
+            .save(conn, get(&categories, "Category", name)?)?;
+        }
+
+        let links = new_merchants
+            .iter()
+            .filter_map(|data| Some((data.name.as_str(), data.replaced_by.as_deref()?)))
+            .collect();
+        for (name, replacer) in replacement_order(links) {
+            ChangeMerchant {
+                replaced_by: Some(Some(get(&merchants, "Merchant", replacer)?)),
+                ..Default::default()
+            }
+            .save(conn, get(&merchants, "Merchant", name)?)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::Result;
+use toml::{Table, Value};
+
+use crate::config::Config;
+
+/// Print the information useful to a bug report, as a block ready to be
+/// pasted in an issue
+///
+/// Paths are reduced to their file name and the values stored in the
+/// key-value store are never printed, only their keys.
+pub fn run(config: &Config) -> Result<()> {
+    let conn = &mut config.database()?;
+
+    println!("```");
+    println!(
+        "finnelctl {}{}",
+        env!("CARGO_PKG_VERSION"),
+        list(crate::features())
+    );
+    println!("finnel {}{}", finnel::VERSION, list(finnel::features()));
+    println!("sqlite {}", conn.sqlite_version()?);
+    println!("os {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    println!();
+
+    let mut database = Table::new();
+    database.insert("file".into(), basename(&config.database_path()).into());
+    if let Some(version) = conn.version()? {
+        database.insert("version".into(), version.to_string().into());
+    }
+    let mut migrations = conn.migrations()?;
+    migrations.sort();
+    database.insert("migrations".into(), migrations.into());
+
+    let mut settings = Table::new();
+    settings.insert("dir".into(), basename(&config.dir).into());
+    settings.insert("data_dir".into(), basename(&config.data_dir).into());
+    if let Some(workspace) = &config.workspace {
+        settings.insert("workspace".into(), workspace.name.clone().into());
+    }
+    settings.insert("key_value_store".into(), config.store()?.list()?.into());
+    settings.insert("file".into(), redact_table(config.table()).into());
+
+    let mut report = Table::new();
+    report.insert("database".into(), database.into());
+    report.insert("config".into(), settings.into());
+    print!("{}", toml::to_string(&report)?);
+    println!("```");
+
+    Ok(())
+}
+
+fn list(features: &[&str]) -> String {
+    if features.is_empty() {
+        " (features: none)".to_string()
+    } else {
+        format!(" (features: {})", features.join(", "))
+    }
+}
+
+fn basename(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn redact_table(table: &Table) -> Table {
+    table
+        .iter()
+        .map(|(key, value)| (key.clone(), redact(value)))
+        .collect()
+}
+
+/// Replace the strings looking like paths by their file name
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::String(s) if s.contains('/') || s.contains(std::path::MAIN_SEPARATOR) => {
+            Value::String(basename(Path::new(s)))
+        }
+        Value::Array(values) => Value::Array(values.iter().map(redact).collect()),
+        Value::Table(table) => Value::Table(redact_table(table)),
+        value => value.clone(),
+    }
+}
@@ -1,52 +1,173 @@
 use anyhow::Result;
+use std::collections::BTreeMap;
 use std::ops::Range;
 
 use finnel::{
+    parse,
     prelude::*,
     record::QueryRecord,
-    stats::{CategoriesStats, CategoryStats},
+    result::RowError,
+    stats::{CategoriesStats, CategoryStats, DaysStats},
 };
 
 use crate::cli::calendar::*;
 use crate::config::Config;
+use crate::error::CliError;
+use crate::utils::{note_skipped_currencies, table_display::Style};
 
 use chrono::{prelude::*, Days, Months};
 
 use tabled::{builder::Builder as TableBuilder, settings::Panel};
 
 struct CommandContext<'a> {
-    #[allow(dead_code)]
     config: &'a Config,
     conn: &'a mut Database,
     stats_retriever: StatsRetriever,
+    first_weekday: Weekday,
+    style: Style,
 }
 
 pub fn run(config: &Config, args: &Arguments) -> Result<()> {
-    let conn = &mut config.database()?;
+    if let Some(Command::Config(action)) = &args.command {
+        return configure(config, action);
+    }
+
+    let conn = &mut config.database_readonly()?;
     let categories = args.categories(conn)?;
+    // Stats cover all accounts unless one or a group is explicitly selected
+    let account_ids = config
+        .accounts(conn)?
+        .map(|accounts| accounts.iter().map(|a| a.id).collect());
     let mut cmd = CommandContext {
         conn,
         config,
+        first_weekday: first_weekday(config, args)?,
+        style: config.style()?,
         stats_retriever: StatsRetriever {
+            currency: config.main_currency()?.unwrap_or(Currency::EUR),
+            account_ids,
             categories,
             direction: args.direction,
-        }
+            strict: args.strict,
+            warnings: Default::default(),
+            skipped_currencies: Default::default(),
+        },
     };
 
     match &args.command.clone().unwrap_or_default() {
         Command::Month(args) => cmd.month(args),
         Command::Today(args) => cmd.today(args),
+        Command::Year(args) => cmd.year(args),
+        Command::Config(_) => unreachable!("configured above"),
+    }?;
+
+    for warning in cmd.stats_retriever.warnings.values() {
+        eprintln!("Warning: {warning}, left out of the stats");
+    }
+    note_skipped_currencies(
+        cmd.stats_retriever.currency,
+        cmd.stats_retriever.skipped_currencies.values(),
+    );
+
+    Ok(())
+}
+
+fn configure(config: &Config, action: &ConfigurationAction) -> Result<()> {
+    let settings = config.store()?.scoped("calendar")?;
+
+    match action {
+        ConfigurationAction::Get { key } => {
+            if let Some(value) = settings.get(key.as_str())? {
+                println!("{}", value);
+            }
+        }
+        ConfigurationAction::Set { key, value } => {
+            let value = match key {
+                ConfigurationKey::MonthlyTarget => parse::amount(value)?.to_string(),
+                ConfigurationKey::FirstWeekday => weekday_name(parse_weekday(value)?).to_string(),
+            };
+            settings.set(key.as_str(), &value)?;
+        }
+        ConfigurationAction::Reset { key } => settings.reset(key.as_str())?,
+    }
+
+    Ok(())
+}
+
+/// Monthly spending target, if configured
+fn monthly_target(config: &Config) -> Result<Option<Decimal>> {
+    let key = ConfigurationKey::MonthlyTarget.as_str();
+    config
+        .store()?
+        .scoped("calendar")?
+        .get(key)?
+        .map(|value| {
+            parse::amount(&value).map_err(|e| anyhow::anyhow!("Invalid calendar {key}: {e}"))
+        })
+        .transpose()
+}
+
+/// Day starting the weeks, from the flag, the configuration or Monday
+fn first_weekday(config: &Config, args: &Arguments) -> Result<Weekday> {
+    if let Some(weekday) = args.first_weekday {
+        return Ok(weekday);
+    }
+    let key = ConfigurationKey::FirstWeekday.as_str();
+    Ok(config
+        .store()?
+        .scoped("calendar")?
+        .get(key)?
+        .map(|value| {
+            parse_weekday(&value).map_err(|e| anyhow::anyhow!("Invalid calendar {key}: {e}"))
+        })
+        .transpose()?
+        .unwrap_or(Weekday::Mon))
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday> {
+    value.parse().map_err(|_| {
+        CliError::ValidationFailed(format!(
+            "Unknown day {value:?}, expected e.g. monday or sunday"
+        ))
+        .into()
+    })
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
     }
 }
 
 impl CommandContext<'_> {
+    /// Print a hint instead of empty tables when there is nothing to show
+    fn check_any_record(&mut self) -> Result<bool> {
+        if Record::count(self.conn)? == 0 {
+            println!("No data yet, create an account and add records first");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     fn today(&mut self, _args: &Today) -> Result<()> {
+        if !self.check_any_record()? {
+            return Ok(());
+        }
+
         let today = Utc::now().date_naive();
         let tomorrow = today + Days::new(1);
 
         let query = QueryRecord {
+            account_ids: self.stats_retriever.account_ids.as_deref(),
             from: Some(today),
             to: Some(tomorrow),
+            skip_invalid: !self.stats_retriever.strict,
             ..QueryRecord::default()
         }
         .with_account()
@@ -54,131 +175,309 @@ impl CommandContext<'_> {
         .with_parent()
         .with_merchant();
 
+        self.style.load_category_paths(self.conn)?;
         let mut builder = TableBuilder::new();
-        table_push_row!(builder, query.type_marker());
+        table_push_row!(builder, &self.style, query.type_marker());
         for result in query.run(self.conn)? {
-            table_push_row!(builder, result);
+            table_push_row!(builder, &self.style, result);
         }
 
         println!("{}", builder.build());
 
+        self.stats_retriever
+            .add_warnings(query.invalid_amounts(self.conn)?);
+
         Ok(())
     }
 
     fn month(&mut self, args: &Monthly) -> Result<()> {
-        let month = args.calendar_month()?.build(self.conn, &self.stats_retriever)?;
+        if !self.check_any_record()? {
+            return Ok(());
+        }
+
+        let mut month = args.calendar_month()?;
+        month.target = monthly_target(self.config)?;
+        let month = month.build(self.conn, &mut self.stats_retriever, self.first_weekday)?;
         println!("{}", month);
 
         Ok(())
     }
+
+    fn year(&mut self, args: &Yearly) -> Result<()> {
+        if !self.check_any_record()? {
+            return Ok(());
+        }
+
+        let year = args.year.unwrap_or_else(|| Utc::now().year());
+        let months = match args.quarter {
+            Some(quarter) => (quarter * 3 - 2)..(quarter * 3 + 1),
+            None => 1..13,
+        };
+        let start = NaiveDate::from_ymd_opt(year, months.start, 1)
+            .ok_or(anyhow::anyhow!("Cannot compute start of year {year}"))?;
+        let end = start + Months::new(months.len() as u32);
+
+        let days = self.stats_retriever.days(self.conn, start..end)?;
+        let heat_map = HeatMap::new(
+            year,
+            args.quarter,
+            months,
+            args.metric,
+            days,
+            self.stats_retriever.currency,
+        );
+        println!("{}", heat_map);
+
+        Ok(())
+    }
 }
 
 struct StatsRetriever {
+    /// Currency of the records in the stats, the main one
+    currency: Currency,
+    account_ids: Option<Vec<i64>>,
     categories: Option<Vec<Category>>,
     direction: Option<Direction>,
+    strict: bool,
+    /// Records left out of the stats, by id as days and month overlap
+    warnings: BTreeMap<i64, RowError>,
+    /// Records in other currencies left out of the widest range retrieved,
+    /// by currency code
+    skipped_currencies: BTreeMap<String, (Currency, i64)>,
 }
 
 impl StatsRetriever {
-    pub fn get(&self, conn: &mut Conn, range: Range<NaiveDate>) -> Result<Stats> {
-        let stats =
-            CategoriesStats::from_date_range_and_currency(conn, range, Currency::EUR)?.0;
+    pub fn get(&mut self, conn: &mut Conn, range: Range<NaiveDate>) -> Result<Stats> {
+        let mut stats = match &self.account_ids {
+            Some(ids) => CategoriesStats::from_date_range_currency_and_accounts(
+                conn,
+                range,
+                self.currency,
+                Some(ids),
+            )?,
+            // Whole months may have been precomputed
+            None => stats::for_range(conn, range, self.currency)?,
+        };
+        if self.strict {
+            stats = stats.strict()?;
+        }
+        self.add_warnings(stats.warnings);
+        self.add_skipped_currencies(&stats.skipped_currencies);
+
+        Ok(Stats::from_category_stats(
+            stats
+                .stats
+                .into_iter()
+                .filter(|stats| self.keeps(stats.direction, stats.category_id)),
+            self.currency,
+        ))
+    }
 
-        Ok(stats
-            .into_iter()
-            .filter(|stats| {
-                self.direction
-                    .as_ref()
-                    .map(|dir| stats.direction == *dir)
-                    .unwrap_or(true)
-                    && self
-                        .categories
-                        .as_ref()
-                        .map(|cats| cats.iter().any(|cat| Some(cat.id) == stats.category_id))
-                        .unwrap_or(true)
-            })
-            .collect::<Vec<_>>()
-            .into())
+    /// Stats of each day of the range having records, from a single query
+    pub fn days(
+        &mut self,
+        conn: &mut Conn,
+        range: Range<NaiveDate>,
+    ) -> Result<BTreeMap<NaiveDate, Stats>> {
+        let mut stats = DaysStats::from_date_range_currency_and_accounts(
+            conn,
+            range,
+            self.currency,
+            self.account_ids.as_deref(),
+        )?;
+        if self.strict {
+            stats = stats.strict()?;
+        }
+        self.add_warnings(std::mem::take(&mut stats.warnings));
+        self.add_skipped_currencies(&stats.skipped_currencies);
+
+        let mut days = BTreeMap::<NaiveDate, Stats>::new();
+        for stats in stats.stats {
+            if !self.keeps(stats.direction, stats.category_id) {
+                continue;
+            }
+            let day = days
+                .entry(stats.date)
+                .or_insert_with(|| Stats::new(self.currency));
+            if stats.direction.is_debit() {
+                day.debit_amount += stats.amount;
+            } else {
+                day.credit_amount += stats.amount;
+            }
+        }
+
+        Ok(days)
+    }
+
+    fn keeps(&self, direction: Direction, category_id: Option<i64>) -> bool {
+        self.direction
+            .as_ref()
+            .map(|dir| direction == *dir)
+            .unwrap_or(true)
+            && self
+                .categories
+                .as_ref()
+                .map(|cats| cats.iter().any(|cat| Some(cat.id) == category_id))
+                .unwrap_or(true)
+    }
+
+    fn add_warnings(&mut self, warnings: Vec<RowError>) {
+        self.warnings
+            .extend(warnings.into_iter().map(|w| (w.record_id, w)));
+    }
+
+    fn add_skipped_currencies(&mut self, skipped: &[(Currency, i64)]) {
+        // The month includes its days, so keep the count of the widest range
+        for (currency, count) in skipped {
+            let entry = self
+                .skipped_currencies
+                .entry(currency.code().to_string())
+                .or_insert((*currency, 0));
+            entry.1 = entry.1.max(*count);
+        }
     }
 }
 
-#[derive(Default)]
 struct Stats {
     debit_amount: Decimal,
     credit_amount: Decimal,
+    currency: Currency,
 }
 
-impl From<Vec<CategoryStats>> for Stats {
-    fn from(stats: Vec<CategoryStats>) -> Self {
+impl Stats {
+    fn new(currency: Currency) -> Self {
         Self {
-            debit_amount: stats
-                .iter()
-                .filter(|stats| stats.direction.is_debit())
-                .fold(Decimal::ZERO, |acc, e| acc + e.amount),
-            credit_amount: stats
-                .iter()
-                .filter(|stats| stats.direction.is_credit())
-                .fold(Decimal::ZERO, |acc, e| acc + e.amount),
+            debit_amount: Decimal::ZERO,
+            credit_amount: Decimal::ZERO,
+            currency,
         }
     }
-}
 
-impl Stats {
+    fn from_category_stats(
+        stats: impl IntoIterator<Item = CategoryStats>,
+        currency: Currency,
+    ) -> Self {
+        let mut result = Self::new(currency);
+        for stats in stats {
+            if stats.direction.is_debit() {
+                result.debit_amount += stats.amount;
+            } else {
+                result.credit_amount += stats.amount;
+            }
+        }
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.debit_amount.is_zero() && self.credit_amount.is_zero()
+    }
+
     pub fn debit_amount(&self) -> Amount {
-        Amount(self.debit_amount, Currency::EUR)
+        Amount(self.debit_amount, self.currency)
     }
 
     pub fn credit_amount(&self) -> Amount {
-        Amount(self.credit_amount, Currency::EUR)
+        Amount(self.credit_amount, self.currency)
     }
 }
 
 pub struct CalendarMonth {
     pub start_of_month: NaiveDate,
+    month: Month,
+    /// Day of the first column
+    first_weekday: Weekday,
+    future: bool,
     days: Vec<Vec<Option<CalendarDay>>>,
-    stats: Stats,
+    /// Stats of the whole month, once built
+    stats: Option<Stats>,
+    target: Option<Decimal>,
+    /// Days left in the month including today, none once it is over
+    days_left: Option<u32>,
 }
 
 impl CalendarMonth {
-    fn month(&self) -> Result<Month> {
-        Ok(Month::try_from(u8::try_from(self.start_of_month.month())?)?)
-    }
-
-    fn build(mut self, conn: &mut Conn, retriever: &StatsRetriever) -> Result<Self> {
+    fn build(
+        mut self,
+        conn: &mut Conn,
+        retriever: &mut StatsRetriever,
+        first_weekday: Weekday,
+    ) -> Result<Self> {
         let start_of_month = self.start_of_month;
         let end_of_month = start_of_month + Months::new(1) - Days::new(1);
 
-        let offset = start_of_month.weekday().num_days_from_monday();
+        self.first_weekday = first_weekday;
+        let offset = start_of_month.weekday().days_since(first_weekday);
         let days = end_of_month.day() + offset;
-        let number_of_weeks = days / 7 + u32::from(days % 7 != 0);
+        let number_of_weeks = days / 7 + u32::from(!days.is_multiple_of(7));
 
         self.days = (0..number_of_weeks)
             .map(|week| {
-                (0..7).map(|day_of_week| {
-                    // We add 1 because days are 1..=7 not 0..=6
-                    let index = week * 7 + day_of_week + 1;
-                    if index <= offset || index > days {
-                        Ok(None)
-                    } else {
-                        let date = NaiveDate::from_ymd_opt(
-                            start_of_month.year(),
-                            start_of_month.month(),
-                            index - offset,
-                        )
+                (0..7)
+                    .map(|day_of_week| {
+                        // We add 1 because days are 1..=7 not 0..=6
+                        let index = week * 7 + day_of_week + 1;
+                        if index <= offset || index > days {
+                            Ok(None)
+                        } else {
+                            let date = NaiveDate::from_ymd_opt(
+                                start_of_month.year(),
+                                start_of_month.month(),
+                                index - offset,
+                            )
                             .ok_or(anyhow::anyhow!("Cannot compute day {}", index - offset))?;
-                        Ok(Some(CalendarDay::new(
-                                    date,
-                                    retriever.get(conn, date..(date + Days::new(1)))?,
-                        )))
-                    }
-                })
-                .collect::<Result<Vec<Option<CalendarDay>>>>()
+                            Ok(Some(CalendarDay::new(
+                                date,
+                                retriever.get(conn, date..(date + Days::new(1)))?,
+                            )))
+                        }
+                    })
+                    .collect::<Result<Vec<Option<CalendarDay>>>>()
             })
             .collect::<Result<_>>()?;
 
-        self.stats = retriever.get(conn, start_of_month..end_of_month)?;
+        self.stats = Some(retriever.get(conn, start_of_month..end_of_month)?);
+        let today = Utc::now().date_naive();
+        self.future = start_of_month > today;
+        self.days_left = days_left(start_of_month..=end_of_month, today);
 
         Ok(self)
     }
+
+    /// Comparison of the debit with the monthly target, with the amount
+    /// which can still be spent each day of the current or a future month
+    fn target_summary(&self) -> Option<String> {
+        let target = self.target?;
+        let stats = self.stats.as_ref()?;
+        let remaining = target - stats.debit_amount;
+        let amount = |value| Amount(value, stats.currency);
+
+        let summary = if remaining.is_sign_negative() {
+            format!("Over target by {}", amount(-remaining))
+        } else if let Some(days) = self.days_left {
+            let pace = (remaining / Decimal::from(days)).round_dp(2);
+            format!(
+                "Remaining: {}, {} per day for {} days",
+                amount(remaining),
+                amount(pace),
+                days
+            )
+        } else {
+            format!("Remaining: {}", amount(remaining))
+        };
+
+        Some(format!("Target: {}\n{}", amount(target), summary))
+    }
+}
+
+/// Days of the month from today, all of them for a future month and none for
+/// a past one
+fn days_left(month: std::ops::RangeInclusive<NaiveDate>, today: NaiveDate) -> Option<u32> {
+    if today > *month.end() {
+        None
+    } else {
+        let from = today.max(*month.start());
+        Some(month.end().day() - from.day() + 1)
+    }
 }
 
 impl TryFrom<NaiveDate> for CalendarMonth {
@@ -193,8 +492,13 @@ impl TryFrom<NaiveDate> for CalendarMonth {
         }
         Ok(CalendarMonth {
             start_of_month,
+            month: Month::try_from(u8::try_from(start_of_month.month())?)?,
+            first_weekday: Weekday::Mon,
+            future: false,
             days: Default::default(),
-            stats: Default::default(),
+            stats: None,
+            target: None,
+            days_left: None,
         })
     }
 }
@@ -202,31 +506,45 @@ impl TryFrom<NaiveDate> for CalendarMonth {
 impl std::fmt::Display for CalendarMonth {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut builder = TableBuilder::new();
-        table_push_row_elements!(
-            builder,
-            "Monday",
-            "Tuesday",
-            "Wednesday",
-            "Thursday",
-            "Friday",
-            "Saturday",
-            "Sunday"
+        builder.push_record(
+            std::iter::successors(Some(self.first_weekday), |day| Some(day.succ()))
+                .take(7)
+                .map(weekday_name),
         );
 
         for week in &self.days {
             table_push_row_elements!(
-                builder, week[0], week[1], week[2], week[3], week[4], week[5], week[6],
+                builder, &Style::default();
+                week[0], week[1], week[2], week[3], week[4], week[5], week[6],
             );
         }
-        writeln!(f, "{}",
+        let mut footer = self
+            .stats
+            .as_ref()
+            .map(|stats| {
+                format!(
+                    "Debit: {}\nCredit: {}",
+                    stats.debit_amount(),
+                    stats.credit_amount()
+                )
+            })
+            .unwrap_or_default();
+        if let Some(summary) = self.target_summary() {
+            footer = format!("{footer}\n{summary}");
+        }
+        let header = if self.future && self.stats.as_ref().is_none_or(Stats::is_empty) {
+            format!("{} (future, no data)", self.month.name())
+        } else {
+            self.month.name().to_string()
+        };
+
+        writeln!(
+            f,
+            "{}",
             builder
                 .build()
-                .with(Panel::header(self.month().unwrap().name()))
-                .with(Panel::footer(format!(
-                    "Debit: {}\nCredit: {}",
-                    self.stats.debit_amount(),
-                    self.stats.credit_amount()
-                )))
+                .with(Panel::header(header))
+                .with(Panel::footer(footer))
         )
     }
 }
@@ -253,7 +571,123 @@ impl std::fmt::Display for CalendarDay {
 }
 
 impl crate::utils::table_display::RowElementDisplay for Option<CalendarDay> {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.as_ref().map(|d| d.to_string()).unwrap_or_default()
     }
 }
+
+/// Compact view of a period, one character per day bucketed by its amount
+struct HeatMap {
+    title: String,
+    year: i32,
+    months: Range<u32>,
+    values: BTreeMap<NaiveDate, Decimal>,
+    currency: Currency,
+    /// Upper bounds of the first four buckets, empty without any value
+    thresholds: Vec<Decimal>,
+}
+
+impl HeatMap {
+    const BUCKETS: [char; 5] = [' ', '.', ':', '*', '#'];
+
+    fn new(
+        year: i32,
+        quarter: Option<u32>,
+        months: Range<u32>,
+        metric: Metric,
+        days: BTreeMap<NaiveDate, Stats>,
+        currency: Currency,
+    ) -> Self {
+        let values = days
+            .into_iter()
+            .filter_map(|(date, stats)| match metric {
+                Metric::Debit if stats.debit_amount.is_zero() => None,
+                Metric::Debit => Some((date, stats.debit_amount)),
+                Metric::Net => Some((date, stats.credit_amount - stats.debit_amount)),
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut sorted = values.values().copied().collect::<Vec<_>>();
+        sorted.sort();
+        // Nearest-rank quintiles
+        let thresholds = if sorted.is_empty() {
+            Vec::new()
+        } else {
+            (1..5)
+                .map(|k| sorted[(k * sorted.len()).div_ceil(5) - 1])
+                .collect()
+        };
+
+        let period = match quarter {
+            Some(quarter) => format!("{year} Q{quarter}"),
+            None => year.to_string(),
+        };
+        let metric = match metric {
+            Metric::Debit => "debit",
+            Metric::Net => "net",
+        };
+
+        HeatMap {
+            title: format!("{period}, {metric}"),
+            year,
+            months,
+            values,
+            currency,
+            thresholds,
+        }
+    }
+
+    fn cell(&self, date: NaiveDate) -> char {
+        match self.values.get(&date) {
+            Some(value) => Self::BUCKETS[self.thresholds.iter().filter(|t| value > *t).count()],
+            None => ' ',
+        }
+    }
+}
+
+impl std::fmt::Display for HeatMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.title)?;
+
+        let tens = (1..=31)
+            .map(|day| {
+                if day < 10 {
+                    ' '
+                } else {
+                    char::from(b'0' + day / 10)
+                }
+            })
+            .collect::<String>();
+        let units = (1..=31)
+            .map(|day| char::from(b'0' + day % 10))
+            .collect::<String>();
+        writeln!(f, "    {}", tens)?;
+        writeln!(f, "    {}", units)?;
+
+        for month in self.months.clone() {
+            let name = Month::try_from(month as u8)
+                .map(|m| m.name()[..3].to_string())
+                .unwrap_or_default();
+            let cells = (1..=31)
+                .map_while(|day| NaiveDate::from_ymd_opt(self.year, month, day))
+                .map(|date| self.cell(date))
+                .collect::<String>();
+            writeln!(f, "{name} {cells}")?;
+        }
+
+        if self.thresholds.is_empty() {
+            write!(f, "No data for this period")
+        } else {
+            let bounds = self
+                .thresholds
+                .iter()
+                .zip(Self::BUCKETS)
+                .map(|(threshold, bucket)| {
+                    format!("'{bucket}' up to {}", Amount(*threshold, self.currency))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "Legend: {bounds}, '#' above")
+        }
+    }
+}
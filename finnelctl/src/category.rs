@@ -1,30 +1,41 @@
 use anyhow::{Context, Result};
+use serde_json::json;
 use std::cell::OnceCell;
 
 use finnel::{
     category::{
         change::{ChangeCategory, ResolvedChangeCategory},
-        NewCategory, QueryCategory,
+        CategoryRule, NewCategory, NewCategoryRule, QueryCategory,
     },
     prelude::*,
     record::QueryRecord,
+    stats::UsagesStats,
 };
 
 use crate::cli::{category::*, record::Sort};
 use crate::config::Config;
-use crate::utils::DeferrableResolvedUpdateArgs;
+use crate::error::CliError;
+use crate::journal::{self, Journal};
+use crate::utils::{
+    table_display::{builder_display, prepare_output, RowElementDisplay, Style},
+    DeferrableResolvedUpdateArgs,
+};
 
 use tabled::builder::Builder as TableBuilder;
 
 struct CommandContext<'a> {
-    #[allow(dead_code)]
     config: &'a Config,
     conn: &'a mut Database,
+    style: Style,
 }
 
 pub fn run(config: &Config, command: &Command) -> Result<()> {
-    let conn = &mut config.database()?;
-    let mut cmd = CommandContext { conn, config };
+    let conn = &mut config.database_for(command)?;
+    let mut cmd = CommandContext {
+        conn,
+        config,
+        style: config.style()?,
+    };
 
     match &command {
         Command::List(args) => cmd.list(args),
@@ -32,6 +43,8 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
         Command::Update(args) => cmd.update(args),
         Command::Show(args) => cmd.show(args),
         Command::Delete(args) => cmd.delete(args),
+        Command::Rules(action) => cmd.rules(action),
+        Command::Doctor { fix } => cmd.doctor(*fix),
     }
 }
 
@@ -45,6 +58,7 @@ impl CommandContext<'_> {
             parent_id: args.parent(self.conn)?.map(|c| c.map(|c| c.id)),
             replaced_by_id: args.replace_by(self.conn)?.map(|c| c.map(|c| c.id)),
             count: count.map(|c| c as i64),
+            order: args.sort.iter().map(|&sort| sort.into()).collect(),
         };
 
         match &args.action {
@@ -59,31 +73,95 @@ impl CommandContext<'_> {
                 }
             }
             Some(Action::Delete { confirm }) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                let categories = query.run(self.conn)?;
+                if *confirm {
+                    for category in &categories {
+                        self.report_deleted_rules(category)?;
+                    }
+                }
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
                 self.conn.transaction(|conn| {
-                    for mut category in query.run(conn)? {
+                    for mut category in categories {
                         category.delete(conn)?;
                     }
                     Result::<()>::Ok(())
                 })?;
             }
             None => {
+                self.style.load_category_paths(self.conn)?;
+                prepare_output(&args.output);
+                let stats = match args.stats.range()? {
+                    Some((from, to)) => {
+                        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+                        Some(UsagesStats::by_category(self.conn, from, to, currency)?)
+                    }
+                    None => None,
+                };
+
+                let excluded = Category::excluded_from_stats_ids(self.conn)?;
                 let mut builder = TableBuilder::new();
-                table_push_row_elements!(builder, "id", "name", "parent", "replaced by");
+                if stats.is_some() {
+                    table_push_row_elements!(
+                        builder, &self.style;
+                        "id",
+                        "name",
+                        "parent",
+                        "replaced by",
+                        "records",
+                        "debit"
+                    );
+                } else {
+                    table_push_row_elements!(
+                        builder, &self.style;
+                        "id",
+                        "name",
+                        "parent",
+                        "replaced by"
+                    );
+                }
+                // Only shown when some listed category is excluded
+                let mut in_stats = vec!["excluded from stats".to_owned()];
 
                 let not_in = args.not_in(self.conn)?;
 
                 for (category, parent, replacer) in
                     query.with_parent().with_replacer().run(self.conn)?
                 {
-                    if not_in.iter().all(|c| c.id != category.id) {
-                        table_push_row_elements!(builder, category.id, category.name, parent, replacer);
+                    if not_in.iter().any(|c| c.id == category.id) {
+                        continue;
+                    }
+                    in_stats.push(match excluded.contains(&category.id) {
+                        true => "yes".to_owned(),
+                        false => String::new(),
+                    });
+                    if let Some(stats) = &stats {
+                        let usage = stats.get(Some(category.id));
+                        table_push_row_elements!(
+                            builder, &self.style;
+                            category.id,
+                            category,
+                            parent,
+                            replacer,
+                            usage.count,
+                            usage.debit_amount()
+                        );
+                    } else {
+                        table_push_row_elements!(
+                            builder, &self.style;
+                            category.id,
+                            category,
+                            parent,
+                            replacer
+                        );
                     }
                 }
+                if in_stats.iter().skip(1).any(|cell| !cell.is_empty()) {
+                    builder.push_column(in_stats);
+                }
 
-                println!("{}", builder.build());
+                builder_display(builder, &args.output)?;
             }
         }
 
@@ -103,14 +181,16 @@ impl CommandContext<'_> {
                     .save(self.conn)?;
             }
             Some(Action::Delete { confirm }) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                if *confirm {
+                    self.report_deleted_rules(&category)?;
+                }
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
                 self.conn.transaction(|conn| category.delete(conn))?;
             }
             None => {
-                let mut ids = vec![category.id];
-                println!("{} | {}", category.id, category.name);
+                println!("{} | {}", category.id, category.full_path(self.conn)?);
 
                 if let Some(parent) = category.fetch_parent(self.conn)? {
                     println!("  Parent: {} | {}", parent.id, parent.name);
@@ -118,9 +198,24 @@ impl CommandContext<'_> {
                 if let Some(replaced_by) = category.fetch_replaced_by(self.conn)? {
                     println!("  Replaced by: {} | {}", replaced_by.id, replaced_by.name);
                 }
+                if let Some(color) = &category.color {
+                    println!("  Color: {color}");
+                }
+                if let Some(emoji) = &category.emoji {
+                    println!("  Emoji: {emoji}");
+                }
+                let excluded = Category::excluded_from_stats_ids(self.conn)?;
+                match category.exclude_from_stats {
+                    Some(true) => println!("  Stats: excluded"),
+                    Some(false) => println!("  Stats: included"),
+                    None if excluded.contains(&category.id) => {
+                        println!("  Stats: excluded, as its parent")
+                    }
+                    None => {}
+                }
 
                 let mut builder = TableBuilder::new();
-                table_push_row_elements!(builder, "id", "name", "replaced by");
+                table_push_row_elements!(builder, &self.style; "id", "name", "replaced by");
                 for (child, replacer) in (QueryCategory {
                     parent_id: Some(Some(category.id)),
                     ..QueryCategory::default()
@@ -128,14 +223,19 @@ impl CommandContext<'_> {
                 .with_replacer()
                 .run(self.conn)?
                 {
-                    ids.push(child.id);
-                    table_push_row_elements!(builder, child.id, child.name, replacer);
+                    table_push_row_elements!(builder, &self.style; child.id, child, replacer);
                 }
 
                 if builder.count_records() > 1 {
                     println!("Children:\n{}", builder.build());
                 }
 
+                let mut ids = vec![category.id];
+                ids.extend(
+                    Category::descendants(self.conn, category.id)?
+                        .into_iter()
+                        .map(|descendant| descendant.id),
+                );
                 self.show_category_records(&ids)?;
             }
         }
@@ -143,7 +243,7 @@ impl CommandContext<'_> {
         Ok(())
     }
 
-    fn show_category_records(&mut self, ids: &Vec<i64>) -> Result<()> {
+    fn show_category_records(&mut self, ids: &[i64]) -> Result<()> {
         println!();
         let query = QueryRecord {
             category_ids: Some(ids),
@@ -155,23 +255,39 @@ impl CommandContext<'_> {
         .with_merchant();
 
         let records = query.run(self.conn)?;
+        self.style.load_category_paths(self.conn)?;
 
         if records.is_empty() {
             println!("No associated records");
         } else {
-            table_display!(records);
+            table_display!(self.config, &self.style, records);
         }
 
         Ok(())
     }
 
     fn create(&mut self, args: &Create) -> Result<()> {
-        NewCategory {
+        let category = NewCategory {
             name: &args.name,
             parent: args.parent(self.conn)?.as_ref(),
             replaced_by: args.replace_by(self.conn)?.as_ref(),
+            color: args.color.as_deref(),
+            emoji: args.emoji.as_deref(),
+            exclude_from_stats: args.exclude_from_stats(),
         }
         .save(self.conn)?;
+        Journal::new(self.config)?.log(
+            "category create",
+            json!({
+                "name": category.name,
+                "parent": category.parent_id,
+                "replaced_by": category.replaced_by_id,
+                "color": category.color,
+                "emoji": category.emoji,
+                "exclude_from_stats": category.exclude_from_stats,
+            }),
+            [category.id],
+        );
 
         Ok(())
     }
@@ -179,11 +295,18 @@ impl CommandContext<'_> {
     fn update(&mut self, args: &Update) -> Result<()> {
         let category = args.identifier.find(self.conn)?;
 
-        ResolvedUpdateArgs::new(self.conn, &args.args)?
-            .get(self.conn)?
+        let journal = Journal::new(self.config)?;
+        let changes = ResolvedUpdateArgs::new(self.conn, &args.args)?;
+        let changes = changes.get(self.conn)?;
+        let fields = match journal.is_enabled() {
+            true => changes.diff(self.conn, &category)?,
+            false => Vec::new(),
+        };
+        changes
             .validate(self.conn, &category)?
             .save(self.conn)
             .optional_empty_changeset()?;
+        journal.log("category update", journal::changes(&fields), [category.id]);
 
         Ok(())
     }
@@ -191,14 +314,181 @@ impl CommandContext<'_> {
     fn delete(&mut self, args: &Delete) -> Result<()> {
         let mut category = args.identifier.find(self.conn)?;
 
-        if args.confirm && crate::utils::confirm()? {
+        if args.confirm {
+            self.report_deleted_rules(&category)?;
+        }
+        if args.confirm && crate::utils::confirm(self.config)? {
             category.delete(self.conn)?;
+            Journal::new(self.config)?.log("category delete", json!({}), [category.id]);
         } else {
-            anyhow::bail!("operation requires confirmation");
+            anyhow::bail!(CliError::confirmation_required());
+        }
+
+        Ok(())
+    }
+
+    fn rules(&mut self, action: &RulesAction) -> Result<()> {
+        match action {
+            RulesAction::List {} => {
+                self.style.load_category_paths(self.conn)?;
+                let mut builder = TableBuilder::new();
+                table_push_row_elements!(
+                    builder, &self.style;
+                    "id",
+                    "mode",
+                    "direction",
+                    "details",
+                    "priority",
+                    "category"
+                );
+                for (rule, category) in CategoryRule::all(self.conn)? {
+                    let (mode, direction, details) = rule_cells(&rule);
+                    table_push_row_elements!(
+                        builder, &self.style;
+                        rule.id,
+                        mode,
+                        direction,
+                        details,
+                        rule.priority as i64,
+                        Some(&category)
+                    );
+                }
+
+                println!("{}", builder.build());
+            }
+            RulesAction::Add {
+                rule,
+                category,
+                direction,
+                details,
+                regex,
+                priority,
+            } => {
+                let category = category.find(self.conn)?;
+                let new_rule = if *details || *regex {
+                    NewCategoryRule {
+                        is_regex: *regex,
+                        ..NewCategoryRule::details(rule, &category)
+                    }
+                } else {
+                    let mode = rule
+                        .parse::<Mode>()
+                        .map_err(|e| CliError::ValidationFailed(e.to_string()))?;
+                    NewCategoryRule {
+                        direction: *direction,
+                        ..NewCategoryRule::new(mode, &category)
+                    }
+                };
+                let rule = NewCategoryRule {
+                    priority: *priority,
+                    ..new_rule
+                }
+                .save(self.conn)?;
+                Journal::new(self.config)?.log(
+                    "category rules add",
+                    json!({
+                        "category": rule.category_id,
+                        "mode": rule.mode.map(|mode| mode.to_string()),
+                        "direction": rule.direction.map(|direction| direction.to_string()),
+                        "pattern": rule.pattern,
+                        "is_regex": rule.is_regex,
+                        "priority": rule.priority,
+                    }),
+                    [rule.id],
+                );
+            }
+            RulesAction::Remove { id } => {
+                let mut rule = CategoryRule::find(self.conn, *id as i64)?;
+                rule.delete(self.conn)?;
+                Journal::new(self.config)?.log("category rules remove", json!({}), [rule.id]);
+            }
+            RulesAction::Test { details } => match CategoryRule::for_details(self.conn, details)? {
+                Some((rule, category)) => {
+                    self.style.load_category_paths(self.conn)?;
+                    let category = Some(&category).to_row_element(&self.style);
+                    println!("Rule {} gives {category}", rule.id);
+                }
+                None => println!("No rule matches"),
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Print the rules giving the category, as deleting it deletes them too
+    fn report_deleted_rules(&mut self, category: &Category) -> Result<()> {
+        let rules = CategoryRule::of_category(self.conn, category.id)?;
+        if rules.is_empty() {
+            return Ok(());
         }
 
+        println!("Deleting {} also deletes its rules:", category.name);
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "id", "mode", "direction", "details");
+        for rule in rules {
+            let (mode, direction, details) = rule_cells(&rule);
+            table_push_row_elements!(builder, &self.style; rule.id, mode, direction, details);
+        }
+        println!("{}", builder.build());
+
         Ok(())
     }
+
+    fn doctor(&mut self, fix: bool) -> Result<()> {
+        let records = finnel::consolidate::with_replaced_category(self.conn)?;
+        if records.is_empty() {
+            println!("No record uses a replaced category");
+            return Ok(());
+        }
+
+        self.style.load_category_paths(self.conn)?;
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "id", "details", "category", "replaced by");
+        for (record, category) in &records {
+            let replacer = category.clone().resolve(self.conn)?;
+            table_push_row_elements!(
+                builder, &self.style;
+                record.id,
+                record.details.clone(),
+                category.to_row_element(&self.style),
+                replacer.to_row_element(&self.style)
+            );
+        }
+        println!("{}", builder.build());
+
+        if fix {
+            self.conn
+                .transaction(finnel::consolidate::consolidate_categories)?;
+            Journal::new(self.config)?.log(
+                "category doctor",
+                json!({ "fix": true }),
+                records.iter().map(|(record, _)| record.id),
+            );
+            println!(
+                "Moved {} records to the replacing categories",
+                records.len()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Mode, direction and details the rule matches, or any
+fn rule_cells(rule: &CategoryRule) -> (String, String, String) {
+    let any = || "any".to_owned();
+    let details = match &rule.pattern {
+        Some(pattern) if rule.is_regex => format!("/{pattern}/"),
+        Some(pattern) => pattern.clone(),
+        None => any(),
+    };
+    (
+        rule.mode.map(|mode| mode.to_string()).unwrap_or_else(any),
+        rule.direction
+            .map(|direction| direction.to_string())
+            .unwrap_or_else(any),
+        details,
+    )
 }
 
 struct ResolvedUpdateArgs<'a> {
@@ -220,7 +510,7 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeCategory<'a>
         })
     }
 
-    fn get(&'a self, conn: &mut Conn) -> Result<&ResolvedChangeCategory<'a>> {
+    fn get(&'a self, conn: &mut Conn) -> Result<&'a ResolvedChangeCategory<'a>> {
         #[allow(clippy::collapsible_if)]
         if self.change_args.get().is_none() {
             if self
@@ -230,6 +520,9 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeCategory<'a>
                         name: self.args.new_name.as_deref(),
                         parent: self.parent.as_ref().map(|o| o.as_ref()),
                         replaced_by: self.replaced_by.as_ref().map(|o| o.as_ref()),
+                        color: self.args.color(),
+                        emoji: self.args.emoji(),
+                        exclude_from_stats: self.args.exclude_from_stats(),
                     }
                     .into_resolved(conn)?,
                 )
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 macro_rules! create_identifier {
     ($struct:ty) => {
@@ -29,16 +29,42 @@ macro_rules! create_identifier {
 }
 
 pub mod account;
+pub mod backup;
 pub mod calendar;
 pub mod category;
+pub mod complete;
+pub mod config;
+pub mod consolidate;
+pub mod diff_db;
 pub mod import;
+pub mod introspect;
+pub mod journal;
 pub mod merchant;
+pub mod quick;
 pub mod record;
+pub mod recurring;
 pub mod report;
+pub mod stats;
+pub mod tag;
+pub mod workspace;
+
+/// Exit codes, matching the classes of `crate::error::CliError`
+const EXIT_STATUS: &str = "\
+Exit status:
+  0   Success
+  1   Any other failure
+  2   Invalid command line
+  3   Nothing imported, with import --porcelain
+  4   Not found, e.g. an unknown account
+  5   Conflict with existing data, e.g. a name already in use
+  6   Validation failed, e.g. an invalid IBAN or date
+  7   Confirmation required, with --confirm or at the prompt
+  74  Unable to read or write a file
+  75  Database or import in use by another process, try again later";
 
 /// Finnel control
 #[derive(Default, Clone, Debug, Parser)]
-#[command(version, infer_subcommands = true)]
+#[command(version, infer_subcommands = true, after_long_help = EXIT_STATUS)]
 pub struct Cli {
     #[clap(flatten)]
     pub verbose: clap_verbosity_flag::Verbosity,
@@ -69,6 +95,20 @@ pub struct Cli {
     )]
     pub data: Option<PathBuf>,
 
+    /// Sets the workspace, one of the [workspaces] of config.toml
+    ///
+    /// The default value is the one saved with `workspace set-default`,
+    /// unless --data is given, which otherwise only replaces the data
+    /// directory of the workspace
+    #[arg(
+        short = 'W',
+        long,
+        value_name = "NAME",
+        global = true,
+        help_heading = "Global options"
+    )]
+    pub workspace: Option<String>,
+
     /// Sets the account to consider for the following command
     ///
     /// A default value can be configured
@@ -81,6 +121,32 @@ pub struct Cli {
     )]
     pub account: Option<String>,
 
+    /// Sets the group of accounts to consider for the following command
+    ///
+    /// Lists, reports and calendar then cover all the accounts of the group
+    #[arg(
+        short = 'G',
+        long,
+        value_name = "NAME",
+        global = true,
+        conflicts_with = "account",
+        help_heading = "Global options"
+    )]
+    pub group: Option<String>,
+
+    /// Answers yes to interactive confirmations
+    ///
+    /// Commands still require their own --confirm flag, this only skips the
+    /// prompt, which is printed nonetheless
+    #[arg(short = 'y', long, global = true, help_heading = "Global options")]
+    pub yes: bool,
+
+    /// Prints lists as tab-separated rows instead of tables
+    ///
+    /// This is the default when the output is not a terminal
+    #[arg(long, global = true, help_heading = "Global options")]
+    pub plain: bool,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -94,21 +160,59 @@ pub enum Commands {
     /// Record related commands
     #[command(subcommand)]
     Record(record::Command),
+    /// Create a record from a short entry, dated today
+    Quick(quick::Arguments),
     /// Category related commands
     #[command(subcommand)]
     Category(category::Command),
     /// Merchant related commands
     #[command(subcommand)]
     Merchant(merchant::Command),
+    /// Tag related commands
+    #[command(subcommand)]
+    Tag(tag::Command),
+    /// Recurring payment related commands
+    #[command(subcommand)]
+    Recurring(recurring::Command),
     /// Display the calendar
     Calendar(calendar::Arguments),
     /// Configure reports
     #[command(subcommand)]
     Report(report::Command),
+    /// Manage the precomputed stats
+    #[command(subcommand)]
+    Stats(stats::Command),
     /// Import records
     Import(import::Command),
+    /// Inspect the settings saved by the other commands
+    #[command(subcommand)]
+    Config(config::Command),
+    /// Select the books to work on
+    #[command(subcommand)]
+    Workspace(workspace::Command),
+    /// Export the database to a JSON backup
+    Export(backup::Export),
+    /// Restore a JSON backup created by export
+    Restore(backup::Restore),
+    /// Print versions and configuration to paste in a bug report
+    Bugreport {},
+    /// Compare the database with another copy of it
+    DiffDb(diff_db::Arguments),
     /// Consolidate the database
-    Consolidate {},
+    Consolidate(consolidate::Arguments),
+    /// Read the journal of the operations changing the database
+    #[command(subcommand)]
+    Journal(journal::Command),
+    /// Describe the commands and arguments for external tooling
+    Introspect(introspect::Arguments),
+    /// Print the script completing the commands and names in a shell, e.g.
+    /// `source <(finnelctl complete bash)`
+    #[command(hide = true)]
+    Complete(complete::Arguments),
+    /// Print the names of accounts, categories or merchants for the
+    /// completion scripts
+    #[command(name = "_complete-values", hide = true)]
+    CompleteValues(complete::Values),
     /// Reset the database
     #[command(hide = true)]
     Reset {
@@ -117,6 +221,43 @@ pub enum Commands {
     },
 }
 
+/// Commands of which some only read the database
+pub trait Readonly {
+    /// Whether the command only reads the database, which is then opened
+    /// read-only and doesn't need to be migrated
+    fn is_readonly(&self) -> bool;
+}
+
+/// Options of the listings which can be read by other programs
+#[derive(Args, Clone, Debug, Default)]
+pub struct ListOutput {
+    /// Prints the rows as delimiter-separated values instead, e.g. to paste
+    /// them in a spreadsheet, with the amounts and dates left unformatted
+    #[arg(long, value_name = "FORMAT", help_heading = "Output")]
+    pub output: Option<ListFormat>,
+
+    /// Leaves out the row of column names
+    #[arg(long, requires = "output", help_heading = "Output")]
+    pub no_header: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ListFormat {
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+}
+
+impl ListFormat {
+    pub fn delimiter(&self) -> u8 {
+        match self {
+            Self::Csv => b',',
+            Self::Tsv => b'\t',
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
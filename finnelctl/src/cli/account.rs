@@ -1,4 +1,6 @@
-use clap::{Args, Subcommand};
+use chrono::NaiveDate;
+use clap::{Args, Subcommand, ValueEnum};
+use finnel::{account::normalize_iban, parse, Currency, Decimal};
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum Command {
@@ -8,10 +10,44 @@ pub enum Command {
     Show(Show),
     /// Create a new account
     Create(Create),
+    /// Update an account
+    Update(Update),
     /// Delete an account
     Delete(Delete),
     /// Check or set the default account
     Default(Default),
+    /// Compare the balance of the account with the one of a bank statement
+    Reconcile(Reconcile),
+    /// Record the balance of a bank statement, for check to verify that the
+    /// records keep adding up to it
+    AssertBalance(AssertBalance),
+    /// Verify the balance assertions of the account against its records
+    Check(Check),
+    /// Create a record bringing the balance to the actual one, e.g. the cash
+    /// in hand
+    Adjust(Adjust),
+    /// Derive the balance again from the records one by one, to check the
+    /// one shown everywhere else
+    RecomputeBalance(RecomputeBalance),
+    /// Manage groups of accounts, selected with --group
+    #[command(subcommand)]
+    Group(GroupAction),
+    /// Manage the configuration of the account
+    #[command(subcommand)]
+    Config(ConfigurationAction),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        matches!(
+            self,
+            Command::List(_)
+                | Command::Show(_)
+                | Command::Check(_)
+                | Command::RecomputeBalance(_)
+                | Command::Group(GroupAction::List)
+        )
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -21,6 +57,28 @@ pub struct List {}
 pub struct Create {
     /// Name of the new account
     pub name: String,
+
+    /// IBAN of the account, used to recognize transfers between own accounts
+    #[arg(long)]
+    iban: Option<String>,
+
+    /// Currency of the account, as an ISO 4217 code
+    #[arg(long, value_name = "CODE", default_value = "EUR", value_parser = parse_currency)]
+    pub currency: Currency,
+
+    /// Create the account even when its currency isn't the main currency
+    #[arg(long)]
+    pub non_main_ok: bool,
+}
+
+pub fn parse_currency(code: &str) -> Result<Currency, String> {
+    Currency::from_code(&code.to_uppercase()).ok_or(format!("Unknown currency {code}"))
+}
+
+impl Create {
+    pub fn iban(&self) -> Option<String> {
+        self.iban.as_deref().map(normalize_iban)
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -31,12 +89,34 @@ pub struct Update {
     /// New name of the account
     #[arg(long)]
     pub new_name: Option<String>,
+
+    /// IBAN of the account, used to recognize transfers between own accounts
+    #[arg(long, group = "iban_args")]
+    iban: Option<String>,
+
+    /// Remove the IBAN of the account
+    #[arg(long, group = "iban_args")]
+    no_iban: bool,
+}
+
+impl Update {
+    pub fn iban(&self) -> Option<Option<String>> {
+        if self.no_iban {
+            Some(None)
+        } else {
+            self.iban.as_deref().map(|iban| Some(normalize_iban(iban)))
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
 pub struct Show {
     /// Name of the account to show
     pub name: Option<String>,
+
+    /// Number of days, up to today, summarized after the balance
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub days: u32,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -47,6 +127,99 @@ pub struct Delete {
     /// Confirm deletion
     #[arg(long)]
     pub confirm: bool,
+
+    /// Keep the records of the account, moving them to another one
+    #[arg(long, requires = "reassign_to")]
+    pub keep_records: bool,
+
+    /// Name of the account, in the same currency, receiving the records
+    #[arg(long, value_name = "ACCOUNT", requires = "keep_records")]
+    pub reassign_to: Option<String>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Reconcile {
+    /// Name of the account to reconcile
+    pub name: Option<String>,
+
+    /// Balance shown on the bank statement
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = parse::amount,
+        allow_negative_numbers = true
+    )]
+    pub statement_balance: Decimal,
+
+    /// Date of the statement, counting the records with an operation date up
+    /// to this one included
+    #[arg(long, value_name = "DATE")]
+    pub at: NaiveDate,
+
+    /// Record the reconciliation of the account when the balances match
+    #[arg(long)]
+    pub confirm: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct AssertBalance {
+    /// Date of the statement, counting the records with an operation date up
+    /// to this one included
+    pub date: NaiveDate,
+
+    /// Balance shown on the statement
+    #[arg(value_parser = parse::amount, allow_negative_numbers = true)]
+    pub amount: Decimal,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Adjust {
+    /// Name of the account to adjust
+    pub name: Option<String>,
+
+    /// Actual balance of the account
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = parse::amount,
+        allow_negative_numbers = true
+    )]
+    pub to: Decimal,
+
+    /// Date of the adjustment, counting the records with an operation date
+    /// up to this one included
+    ///
+    /// The default value is today
+    #[arg(long, value_name = "DATE")]
+    pub date: Option<NaiveDate>,
+
+    /// Details of the record, by default the name of its category
+    #[arg(long, value_name = "TEXT")]
+    pub details: Option<String>,
+
+    /// Adjust even if the amount of the record is above the
+    /// records/max_amount setting
+    #[arg(long)]
+    pub confirm: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct RecomputeBalance {
+    /// Name of the account to recompute
+    pub name: Option<String>,
+
+    /// Date of the balance, counting the records with an operation date up
+    /// to this one included
+    ///
+    /// The default value is today
+    #[arg(long, value_name = "DATE")]
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Check {
+    /// Name of the account to check
+    pub name: Option<String>,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -59,3 +232,67 @@ pub struct Default {
     #[arg(long)]
     pub reset: bool,
 }
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum GroupAction {
+    /// List the groups and their accounts
+    List,
+    /// Create a group
+    Create {
+        /// Name of the new group
+        name: String,
+    },
+    /// Add accounts to a group
+    Add {
+        /// Name of the group
+        name: String,
+        /// Names of the accounts to add
+        #[arg(required = true)]
+        accounts: Vec<String>,
+    },
+    /// Remove accounts from a group
+    Remove {
+        /// Name of the group
+        name: String,
+        /// Names of the accounts to remove
+        #[arg(required = true)]
+        accounts: Vec<String>,
+    },
+    /// Delete a group, keeping its accounts
+    Delete {
+        /// Name of the group to delete
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigurationAction {
+    /// Print the configuration value
+    Get { key: ConfigurationKey },
+    /// Set the configuration value
+    Set {
+        key: ConfigurationKey,
+        value: String,
+    },
+    /// Remove the configuration value
+    Reset { key: ConfigurationKey },
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ConfigurationKey {
+    /// Category, by name or id, of imported ATM withdrawals without another
+    /// category
+    AtmCategory,
+    /// Category, by name or id, of imported fees without another category
+    FeeCategory,
+}
+
+impl ConfigurationKey {
+    pub fn as_str(&self) -> &str {
+        use ConfigurationKey::*;
+        match self {
+            AtmCategory => "atm_category",
+            FeeCategory => "fee_category",
+        }
+    }
+}
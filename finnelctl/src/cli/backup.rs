@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+#[derive(Args, Clone, Debug)]
+pub struct Export {
+    /// File to write the backup to, instead of the standard output
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Restore {
+    /// Backup file created by the export command
+    pub file: PathBuf,
+
+    /// Restore into a database that already has data
+    ///
+    /// Accounts, categories and merchants are matched by name and kept
+    /// untouched, records and recurring payments are always added
+    #[arg(long)]
+    pub merge: bool,
+}
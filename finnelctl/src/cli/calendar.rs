@@ -2,7 +2,8 @@ use crate::calendar::CalendarMonth;
 use crate::cli::category::Identifier as CategoryIdentifier;
 use crate::cli::report::Identifier as ReportIdentifier;
 use anyhow::Result;
-use clap::{Args, Subcommand};
+use chrono::Weekday;
+use clap::{value_parser, Args, Subcommand, ValueEnum};
 use finnel::prelude::*;
 
 #[derive(Args, Clone, Debug)]
@@ -32,6 +33,15 @@ pub struct Arguments {
     /// Show only stats for the given direction (credit or debit)
     #[arg(long, global = true, help_heading = "Filter stats")]
     pub direction: Option<Direction>,
+
+    /// Fail on records whose amount can't be read instead of leaving them out
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Day starting the weeks of the month view, by default the configured
+    /// one or Monday
+    #[arg(long, global = true, value_name = "DAY")]
+    pub first_weekday: Option<Weekday>,
 }
 
 impl Arguments {
@@ -55,6 +65,42 @@ pub enum Command {
     Today(Today),
     /// Show monthly view
     Month(Monthly),
+    /// Show a heat map of the days of a year, or of one of its quarters
+    Year(Yearly),
+    /// Configure the calendar
+    #[command(subcommand)]
+    Config(ConfigurationAction),
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigurationAction {
+    /// Print the configuration value
+    Get { key: ConfigurationKey },
+    /// Set the configuration value
+    Set {
+        key: ConfigurationKey,
+        value: String,
+    },
+    /// Remove the configuration value
+    Reset { key: ConfigurationKey },
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ConfigurationKey {
+    /// Amount not to spend over in a month, compared to its debit
+    MonthlyTarget,
+    /// Day starting the weeks of the month view, e.g. sunday
+    FirstWeekday,
+}
+
+impl ConfigurationKey {
+    pub fn as_str(&self) -> &str {
+        use ConfigurationKey::*;
+        match self {
+            MonthlyTarget => "monthly_target",
+            FirstWeekday => "first_weekday",
+        }
+    }
 }
 
 impl Default for Command {
@@ -87,14 +133,37 @@ pub struct Monthly {
     pub month: Option<String>,
 }
 
+#[derive(Default, Args, Clone, Debug)]
+pub struct Yearly {
+    /// Year to show, the current one by default
+    pub year: Option<i32>,
+
+    /// Show only the given quarter of the year
+    #[arg(long, value_parser = value_parser!(u32).range(1..=4))]
+    pub quarter: Option<u32>,
+
+    /// Amount of the day used to pick its character
+    #[arg(long, value_enum, default_value_t)]
+    pub metric: Metric,
+}
+
+#[derive(Default, Copy, Clone, Debug, ValueEnum)]
+pub enum Metric {
+    /// Amount spent
+    #[default]
+    Debit,
+    /// Amount received minus amount spent
+    Net,
+}
+
 impl Monthly {
     pub fn calendar_month(&self) -> Result<CalendarMonth> {
+        use anyhow::Context;
         #[cfg(not(test))]
         use chrono::Utc;
+        use chrono::{Datelike, Month, Months, NaiveDate};
         #[cfg(test)]
         use tests::Utc;
-        use anyhow::Context;
-        use chrono::{Datelike, Month, Months, NaiveDate};
 
         let today = Utc::now().date_naive();
         let mut start_of_month = NaiveDate::from_ymd_opt(today.year(), today.month(), 1)
@@ -186,15 +255,15 @@ mod tests {
         );
 
         assert_eq!(
-            NaiveDate::from_ymd_opt(2025, 09, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 9, 1).unwrap(),
             monthly!("2025/Sep").calendar_month()?.start_of_month
         );
         assert_eq!(
-            NaiveDate::from_ymd_opt(2026, 09, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
             monthly!("2026/september").calendar_month()?.start_of_month
         );
         assert_eq!(
-            NaiveDate::from_ymd_opt(2024, 09, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 9, 1).unwrap(),
             monthly!("2024/09").calendar_month()?.start_of_month
         );
         assert_eq!(
@@ -1,9 +1,15 @@
 use anyhow::Result;
 
-use clap::{Args, Subcommand};
+use chrono::NaiveDate;
+use clap::{builder::PossibleValue, Args, Subcommand, ValueEnum};
 
-use finnel::{category::NewCategory, prelude::*};
 use crate::cli::report::Identifier as ReportIdentifier;
+use crate::cli::ListOutput;
+use finnel::{
+    category::NewCategory,
+    order::{OrderBy, OrderDirection},
+    prelude::*,
+};
 
 create_identifier! {Category}
 
@@ -54,6 +60,107 @@ impl CategoryArgument {
             Ok(None)
         }
     }
+
+    /// Same as `resolve`, but following the replacements of the selected
+    /// category, printing a notice when the category has been replaced
+    pub fn resolve_replacements(
+        &self,
+        conn: &mut Conn,
+        create: Option<&str>,
+        absence: bool,
+    ) -> Result<Option<Option<Category>>> {
+        Ok(match self.resolve(conn, create, absence)? {
+            Some(Some(category)) if category.replaced_by_id.is_some() => {
+                let replacer = category.clone().resolve(conn)?;
+                eprintln!(
+                    "Category {} has been replaced by {}, using it instead",
+                    category.name, replacer.name
+                );
+                Some(Some(replacer))
+            }
+            category => category,
+        })
+    }
+}
+
+/// Statistics about the records of each listed category or merchant
+#[derive(Args, Clone, Debug)]
+pub struct StatsArguments {
+    /// Show the number of records and the total debit amount of each one
+    #[arg(long)]
+    pub with_stats: bool,
+
+    /// Compute the statistics from this date, by default from the first
+    /// record
+    #[arg(short = 'a', long, value_name = "DATE", requires = "with_stats")]
+    pub from: Option<NaiveDate>,
+
+    /// Compute the statistics up to this date, excluded, by default up to the
+    /// last record
+    #[arg(short = 'b', long, value_name = "DATE", requires = "with_stats")]
+    pub to: Option<NaiveDate>,
+}
+
+impl StatsArguments {
+    /// The date range of the statistics, if requested
+    pub fn range(&self) -> Result<Option<(Option<NaiveDate>, Option<NaiveDate>)>> {
+        if !self.with_stats {
+            return Ok(None);
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                anyhow::bail!("--from must be before --to");
+            }
+        }
+        Ok(Some((self.from, self.to)))
+    }
+}
+
+/// Order of the listed categories or merchants
+#[derive(Debug, Clone, Copy, derive_more::Into)]
+pub struct ListSort(OrderBy, OrderDirection);
+
+impl ValueEnum for ListSort {
+    fn value_variants<'a>() -> &'a [Self] {
+        use OrderBy::*;
+        use OrderDirection::*;
+
+        &[
+            ListSort(Id, Asc),
+            ListSort(Name, Asc),
+            ListSort(Records, Asc),
+            ListSort(LastUsed, Asc),
+            ListSort(Id, Desc),
+            ListSort(Name, Desc),
+            ListSort(Records, Desc),
+            ListSort(LastUsed, Desc),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let value = PossibleValue::new(self.to_string());
+
+        Some(match self.0 {
+            OrderBy::Records => value.help("Number of records using it"),
+            OrderBy::LastUsed => value.help("Operation date of its most recent record"),
+            _ => value,
+        })
+    }
+}
+
+impl core::fmt::Display for ListSort {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            OrderBy::Id => write!(f, "id")?,
+            OrderBy::Name => write!(f, "name")?,
+            OrderBy::Records => write!(f, "records")?,
+            OrderBy::LastUsed => write!(f, "last-used")?,
+        }
+        match self.1 {
+            OrderDirection::Asc => Ok(()),
+            OrderDirection::Desc => write!(f, ".desc"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -68,6 +175,28 @@ pub enum Command {
     Update(Update),
     /// Delete a category
     Delete(Delete),
+    /// Manage the rules giving a category to new records created without one
+    #[command(subcommand)]
+    Rules(RulesAction),
+    /// List the records still using a replaced category
+    Doctor {
+        /// Move the records to the replacing category
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        match self {
+            Command::List(List { action, .. }) | Command::Show(Show { action, .. }) => {
+                action.is_none()
+            }
+            Command::Rules(RulesAction::List {} | RulesAction::Test { .. }) => true,
+            Command::Doctor { fix } => !fix,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -100,6 +229,16 @@ pub struct List {
     /// Maximum number of categories to show
     #[arg(short = 'c', long, help_heading = "Filter records")]
     pub count: Option<u32>,
+
+    /// Order of the categories, by id when not given
+    #[arg(long, help_heading = "Sort categories")]
+    pub sort: Vec<ListSort>,
+
+    #[command(flatten, next_help_heading = "Statistics")]
+    pub stats: StatsArguments,
+
+    #[command(flatten)]
+    pub output: ListOutput,
 }
 
 impl List {
@@ -175,9 +314,38 @@ pub struct Create {
         help_heading = "Replace by"
     )]
     create_replace_by: Option<String>,
+
+    /// Color of the category in the HTML reports, as a hex code like #aabbcc
+    #[arg(long, value_name = "HEX", help_heading = "Display")]
+    pub color: Option<String>,
+
+    /// Emoji shown before the name of the category, when the display/emoji
+    /// setting is true
+    #[arg(long, help_heading = "Display")]
+    pub emoji: Option<String>,
+
+    /// Leave the records of the category out of the stats, along with the
+    /// ones of its children unless they are included
+    #[arg(long, help_heading = "Stats")]
+    exclude_from_stats: bool,
+
+    /// Keep the records of the category in the stats, even when its parent
+    /// is excluded
+    #[arg(long, conflicts_with = "exclude_from_stats", help_heading = "Stats")]
+    include_in_stats: bool,
 }
 
 impl Create {
+    pub fn exclude_from_stats(&self) -> Option<bool> {
+        if self.exclude_from_stats {
+            Some(true)
+        } else if self.include_in_stats {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
     pub fn parent(&self, conn: &mut Conn) -> Result<Option<Category>> {
         Ok(self
             .parent
@@ -241,6 +409,37 @@ pub struct UpdateArgs {
     /// Remove the indication to replace this category by another one
     #[arg(long, group = "replace_by_args", help_heading = "Replace by")]
     no_replace_by: bool,
+
+    /// Change the color of the category, as a hex code like #aabbcc
+    #[arg(long, value_name = "HEX", help_heading = "Display")]
+    color: Option<String>,
+
+    /// Remove the color
+    #[arg(long, conflicts_with = "color", help_heading = "Display")]
+    no_color: bool,
+
+    /// Change the emoji of the category
+    #[arg(long, help_heading = "Display")]
+    emoji: Option<String>,
+
+    /// Remove the emoji
+    #[arg(long, conflicts_with = "emoji", help_heading = "Display")]
+    no_emoji: bool,
+
+    /// Leave the records of the category out of the stats, along with the
+    /// ones of its children unless they are included
+    #[arg(long, group = "stats_args", help_heading = "Stats")]
+    exclude_from_stats: bool,
+
+    /// Keep the records of the category in the stats, even when its parent
+    /// is excluded
+    #[arg(long, group = "stats_args", help_heading = "Stats")]
+    include_in_stats: bool,
+
+    /// Exclude the records of the category from the stats only when its
+    /// parent is
+    #[arg(long, group = "stats_args", help_heading = "Stats")]
+    inherit_stats: bool,
 }
 
 impl UpdateArgs {
@@ -253,6 +452,33 @@ impl UpdateArgs {
         self.replace_by
             .resolve(conn, self.create_replace_by.as_deref(), self.no_replace_by)
     }
+    pub fn color(&self) -> Option<Option<&str>> {
+        if self.no_color {
+            Some(None)
+        } else {
+            self.color.as_deref().map(Some)
+        }
+    }
+
+    pub fn emoji(&self) -> Option<Option<&str>> {
+        if self.no_emoji {
+            Some(None)
+        } else {
+            self.emoji.as_deref().map(Some)
+        }
+    }
+
+    pub fn exclude_from_stats(&self) -> Option<Option<bool>> {
+        if self.exclude_from_stats {
+            Some(Some(true))
+        } else if self.include_in_stats {
+            Some(Some(false))
+        } else if self.inherit_stats {
+            Some(None)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -274,6 +500,46 @@ pub struct Delete {
     pub confirm: bool,
 }
 
+#[derive(Subcommand, Clone, Debug)]
+pub enum RulesAction {
+    /// List rules
+    List {},
+    /// Add a rule
+    Add {
+        /// Mode of the records, e.g. ATM for all withdrawals or ATM Card *1234
+        /// for the ones of a single card, or the pattern searched in their
+        /// details with --details or --regex
+        #[arg(value_name = "MODE_OR_PATTERN")]
+        rule: String,
+        /// Name or id of the category to give to the records
+        category: Identifier,
+        /// Only give the category to the records of this direction
+        #[arg(short = 'd', long, conflicts_with_all = ["details", "regex"])]
+        direction: Option<Direction>,
+        /// Give the category to the records whose details contain the pattern,
+        /// ignoring case
+        #[arg(long)]
+        details: bool,
+        /// Give the category to the records whose details match the regular
+        /// expression
+        #[arg(long, conflicts_with = "details")]
+        regex: bool,
+        /// Rules of higher priority are applied first
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+    },
+    /// Remove a rule
+    Remove {
+        /// Id of the rule
+        id: u32,
+    },
+    /// Show the category the rules give to records with these details
+    Test {
+        /// Details of the records
+        details: String,
+    },
+}
+
 #[derive(Args, Clone, Debug)]
 #[group(id = "parent_args")]
 pub struct ParentCategoryArgument {
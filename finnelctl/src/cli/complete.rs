@@ -0,0 +1,35 @@
+use clap::{Args, ValueEnum};
+
+#[derive(Args, Clone, Debug)]
+pub struct Arguments {
+    /// Shell to complete the commands in
+    pub shell: Shell,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Values {
+    /// Kind of names to print
+    pub kind: Kind,
+
+    /// Only print the names starting with this word, as typed in the shell
+    pub prefix: Option<String>,
+
+    /// Shell to escape the names for
+    #[arg(long, default_value = "bash")]
+    pub shell: Shell,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Kind {
+    Account,
+    Group,
+    Category,
+    Merchant,
+}
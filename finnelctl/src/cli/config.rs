@@ -0,0 +1,16 @@
+use clap::Subcommand;
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List the keys of the key-value store
+    List {
+        /// Only list the keys under this scope, like `records`
+        scope: Option<String>,
+    },
+    /// Print the value of a key, like `records/default_sort`
+    Get { key: String },
+    /// Set the value of a key
+    Set { key: String, value: String },
+    /// Remove a key
+    Reset { key: String },
+}
@@ -0,0 +1,13 @@
+use clap::Args;
+
+#[derive(Args, Clone, Debug)]
+pub struct Arguments {
+    /// Merge the categories with the same name once normalized, keeping the
+    /// one with a parent or the one with more records
+    #[arg(long)]
+    pub dedup_categories: bool,
+
+    /// Only report the categories that would be merged
+    #[arg(long, requires = "dedup_categories")]
+    pub dry_run: bool,
+}
@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+#[derive(Args, Clone, Debug)]
+pub struct Arguments {
+    /// Database file to compare with the current one, opened read-only
+    pub other: PathBuf,
+
+    /// Maximum number of rows listed in each section
+    #[arg(long, default_value_t = 20)]
+    pub limit: u32,
+}
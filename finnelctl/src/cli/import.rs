@@ -4,7 +4,7 @@ use clap::{Args, Subcommand, ValueEnum};
 #[derive(Default, Args, Clone, Debug)]
 pub struct Command {
     #[command(subcommand)]
-    pub configuration_action: Option<ConfigurationAction>,
+    pub action: Option<Action>,
 
     /// File to import
     #[arg(help_heading = "Import")]
@@ -22,6 +22,51 @@ pub struct Command {
     #[arg(long, help_heading = "Import")]
     pub pretend: bool,
 
+    /// Import the file even if the same content was already imported
+    #[arg(long, help_heading = "Import")]
+    pub force: bool,
+
+    /// Print a final machine-readable summary line and exit with code 3 when
+    /// no records were imported
+    #[arg(long, help_heading = "Import")]
+    pub porcelain: bool,
+
+    /// Import the records in another currency than the one of the account
+    /// with their amount unchanged, instead of failing
+    #[arg(long, help_heading = "Import")]
+    pub coerce_currency: bool,
+
+    /// Import the records with an amount above the records/max_amount
+    /// setting, instead of failing
+    #[arg(long, help_heading = "Import")]
+    pub allow_large_amounts: bool,
+
+    /// Leave out the rows failing to import instead of aborting, printing
+    /// them at the end and writing them to a .rejected.csv file next to the
+    /// imported one
+    #[arg(long, help_heading = "Import")]
+    pub skip_errors: bool,
+
+    /// Import the rows of this account number into this account, for the
+    /// files listing several accounts, adding to the account_map setting
+    #[arg(
+        long,
+        value_name = "NUM=ACCOUNT",
+        value_parser = parse_account_mapping,
+        help_heading = "Import"
+    )]
+    pub map_account: Vec<(String, String)>,
+
+    /// Fail on the rows of an account number not mapped to an account,
+    /// instead of skipping them
+    #[arg(long, help_heading = "Import")]
+    pub strict_accounts: bool,
+
+    /// Wait up to this number of seconds for another import to finish
+    /// instead of failing right away
+    #[arg(long, value_name = "SECONDS", help_heading = "Import")]
+    pub wait: Option<u64>,
+
     /// Only import records with an operation date greater than or equal to this one
     #[arg(long, value_name = "DATE", help_heading = "Filter records")]
     pub from: Option<NaiveDate>,
@@ -31,6 +76,24 @@ pub struct Command {
     pub to: Option<NaiveDate>,
 }
 
+/// Account number and name of the account to import its rows into
+pub fn parse_account_mapping(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((number, account)) if !number.trim().is_empty() && !account.trim().is_empty() => {
+            Ok((number.trim().to_owned(), account.trim().to_owned()))
+        }
+        _ => Err(format!("expected NUM=ACCOUNT, got {value:?}")),
+    }
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Action {
+    #[command(flatten)]
+    Config(ConfigurationAction),
+    /// List the past imports of the profile
+    History {},
+}
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum ConfigurationAction {
     /// Print the configuration value
@@ -48,6 +111,18 @@ pub enum ConfigurationAction {
 pub enum ConfigurationKey {
     DefaultAccount,
     DefaultFile,
+    /// Skip rows with a zero amount (default: true)
+    SkipZeroAmount,
+    /// Merge fee rows into the preceding record with the same value date (default: false)
+    MergeFeeRows,
+    /// Text identifying the details of a fee row (default: "frais")
+    FeePattern,
+    /// Whether expenses are noted with a positive or a negative amount, either
+    /// expense-positive or expense-negative (default: expense-positive)
+    SignConvention,
+    /// Accounts to import the rows of each account number into, as
+    /// comma-separated NUM=ACCOUNT pairs
+    AccountMap,
 }
 
 impl ConfigurationKey {
@@ -56,6 +131,11 @@ impl ConfigurationKey {
         match self {
             DefaultAccount => "default_account",
             DefaultFile => "default_file",
+            SkipZeroAmount => "skip_zero_amount",
+            MergeFeeRows => "merge_fee_rows",
+            FeePattern => "fee_pattern",
+            SignConvention => "sign_convention",
+            AccountMap => "account_map",
         }
     }
 }
@@ -0,0 +1,15 @@
+use clap::{Args, ValueEnum};
+
+#[derive(Args, Clone, Debug)]
+pub struct Arguments {
+    /// Format of the description
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+}
+
+#[derive(Default, Copy, Clone, Debug, ValueEnum)]
+pub enum Format {
+    /// JSON document with the commands nested under their parent
+    #[default]
+    Json,
+}
@@ -0,0 +1,11 @@
+use clap::Subcommand;
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Print the operations journaled, oldest first
+    Show {
+        /// Only print the last N operations
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+    },
+}
@@ -1,4 +1,7 @@
-use crate::cli::category::{CategoryArgument, Identifier as CategoryIdentifier};
+use crate::cli::category::{
+    CategoryArgument, Identifier as CategoryIdentifier, ListSort, StatsArguments,
+};
+use crate::cli::ListOutput;
 use anyhow::Result;
 use clap::{Args, Subcommand};
 use finnel::{merchant::NewMerchant, prelude::*};
@@ -66,6 +69,20 @@ pub enum Command {
     Update(Update),
     /// Delete a merchant
     Delete(Delete),
+    /// Manage the names used by imports to recognize a merchant
+    #[command(subcommand)]
+    Alias(AliasAction),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        match self {
+            Command::List(List { action, .. }) | Command::Show(Show { action, .. }) => {
+                action.is_none()
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -102,6 +119,16 @@ pub struct List {
     /// Maximum number of merchants to show
     #[arg(short = 'c', long, help_heading = "Filter records")]
     pub count: Option<usize>,
+
+    /// Order of the merchants, by id when not given
+    #[arg(long, help_heading = "Sort merchants")]
+    pub sort: Vec<ListSort>,
+
+    #[command(flatten, next_help_heading = "Statistics")]
+    pub stats: StatsArguments,
+
+    #[command(flatten)]
+    pub output: ListOutput,
 }
 
 impl List {
@@ -168,6 +195,14 @@ pub struct Create {
         help_heading = "Replace by"
     )]
     create_replace_by: Option<String>,
+
+    /// Website of the merchant, as an URL like https://example.com
+    #[arg(long, value_name = "URL", help_heading = "Details")]
+    pub website: Option<String>,
+
+    /// Free text notes about the merchant
+    #[arg(long, help_heading = "Details")]
+    pub notes: Option<String>,
 }
 
 impl Create {
@@ -233,6 +268,22 @@ pub struct UpdateArgs {
     /// Remove the indication to replace this merchant by another one
     #[arg(long, group = "replace_by_merchant_args", help_heading = "Replace by")]
     no_replace_by: bool,
+
+    /// Change the website of the merchant, as an URL like https://example.com
+    #[arg(long, value_name = "URL", help_heading = "Details")]
+    website: Option<String>,
+
+    /// Remove the website
+    #[arg(long, conflicts_with = "website", help_heading = "Details")]
+    no_website: bool,
+
+    /// Change the notes about the merchant
+    #[arg(long, help_heading = "Details")]
+    notes: Option<String>,
+
+    /// Remove the notes
+    #[arg(long, conflicts_with = "notes", help_heading = "Details")]
+    no_notes: bool,
 }
 
 impl UpdateArgs {
@@ -248,6 +299,22 @@ impl UpdateArgs {
         self.replace_by
             .resolve(conn, self.create_replace_by.as_deref(), self.no_replace_by)
     }
+
+    pub fn website(&self) -> Option<Option<&str>> {
+        if self.no_website {
+            Some(None)
+        } else {
+            self.website.as_deref().map(Some)
+        }
+    }
+
+    pub fn notes(&self) -> Option<Option<&str>> {
+        if self.no_notes {
+            Some(None)
+        } else {
+            self.notes.as_deref().map(Some)
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -269,6 +336,24 @@ pub struct Delete {
     pub confirm: bool,
 }
 
+#[derive(Subcommand, Clone, Debug)]
+pub enum AliasAction {
+    /// List aliases, of all merchants or of the given one
+    List {
+        /// Name or id of the merchant
+        merchant: Option<Identifier>,
+    },
+    /// Add an alias to a merchant
+    Add {
+        /// Name or id of the merchant
+        merchant: Identifier,
+        /// Name found in imported records
+        alias: String,
+    },
+    /// Remove an alias
+    Remove { alias: String },
+}
+
 #[derive(Args, Clone, Debug)]
 #[group(id = "default_category_args")]
 pub struct DefaultCategoryArgument {
@@ -0,0 +1,20 @@
+use clap::Args;
+
+#[derive(Args, Clone, Debug)]
+pub struct Arguments {
+    /// Amount followed by the details, with an optional @merchant and
+    /// #category, e.g. "4.5 coffee @bakery #food"
+    ///
+    /// Quotes group words, e.g. 12 lunch @"Chez Paul". The merchant and
+    /// category are created when they don't exist yet.
+    pub entry: String,
+
+    /// Record a credit instead of a debit
+    #[arg(long)]
+    pub credit: bool,
+
+    /// Create the record even if its amount is above the records/max_amount
+    /// setting
+    #[arg(long)]
+    pub confirm: bool,
+}
@@ -1,10 +1,14 @@
-use crate::cli::category::CategoryArgument;
-use crate::cli::merchant::MerchantArgument;
+use crate::cli::account::parse_currency;
+use crate::cli::category::{CategoryArgument, Identifier as CategoryIdentifier};
+use crate::cli::merchant::{Identifier as MerchantIdentifier, MerchantArgument};
+use crate::cli::ListOutput;
+use crate::utils::max_amount;
 use anyhow::Result;
 use chrono::{NaiveDate, Utc};
 use clap::{builder::PossibleValue, Args, Subcommand, ValueEnum};
-use finnel::prelude::*;
+use finnel::{date::Shift, parse, prelude::*};
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Subcommand)]
 pub enum Command {
     /// List records
@@ -13,8 +17,38 @@ pub enum Command {
     Show(Show),
     /// Create a new record
     Create(Create),
+    /// Create the debit and the credit of a transfer between two accounts
+    Transfer(Transfer),
     /// Update a record
     Update(Update),
+    /// Search records by details, merchant or category name
+    Search(Search),
+    /// List records that look like duplicates of an earlier record
+    Duplicates(Duplicates),
+    /// Flag a record as needing attention
+    Flag(Flag),
+    /// Remove the attention flag of a record
+    Unflag(Unflag),
+    /// Go through the records one at a time to set their category or
+    /// merchant, or delete them, e.g. after an import
+    Review(Review),
+    /// Give a pending record its account
+    Assign(Assign),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        match self {
+            Command::List(List { action, .. }) => action.is_none(),
+            Command::Show(Show { action, .. }) => matches!(
+                action,
+                None | Some(ShowAction::History)
+                    | Some(ShowAction::Suggest(Suggest { apply_best: false }))
+            ),
+            Command::Search(_) | Command::Duplicates(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -36,14 +70,38 @@ impl Show {
 pub enum ShowAction {
     /// Split the record
     Split(Split),
+    /// Manage the tags of the record
+    #[command(subcommand)]
+    Tag(TagAction),
+    /// Suggest categories from the ones of records with the same merchant or
+    /// similar details
+    Suggest(Suggest),
+    /// List the changes of the record, when `db/audit_enabled` is set
+    History,
     #[command(flatten)]
     Other(Action),
 }
 
+#[derive(Args, Clone, Debug)]
+pub struct Suggest {
+    /// Set the category of the record to the first suggestion when more than
+    /// 80% of the similar records are in it
+    #[arg(long)]
+    pub apply_best: bool,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TagAction {
+    /// Add a tag to the record, creating it if needed
+    Add { name: String },
+    /// Remove a tag from the record
+    Remove { name: String },
+}
+
 #[derive(Args, Clone, Debug)]
 pub struct Split {
     /// Amount of the record to split into a new record
-    #[arg(help_heading = "New record")]
+    #[arg(help_heading = "New record", value_parser = parse::amount)]
     pub amount: Decimal,
 
     #[arg(long, help_heading = "New record")]
@@ -69,7 +127,7 @@ pub struct Split {
 impl Split {
     pub fn category(&self, conn: &mut Conn) -> Result<Option<Option<Category>>> {
         self.category
-            .resolve(conn, self.create_category.as_deref(), self.no_category)
+            .resolve_replacements(conn, self.create_category.as_deref(), self.no_category)
     }
 }
 
@@ -78,7 +136,7 @@ pub struct Create {
     /// Amount of the record
     ///
     /// Without currency symbol, the currency is inferred from the account
-    #[arg(help_heading = "Record")]
+    #[arg(help_heading = "Record", value_parser = parse::amount)]
     pub amount: Decimal,
 
     /// Describe the record
@@ -101,10 +159,55 @@ pub struct Create {
     #[arg(long, value_name = "DATE", help_heading = "Record")]
     operation_date: Option<NaiveDate>,
 
-    /// Value date
+    /// Value date, by default the operation date
     #[arg(long, value_name = "DATE", help_heading = "Record")]
     value_date: Option<NaiveDate>,
 
+    /// Accept a value date before the operation date
+    #[arg(long, help_heading = "Record")]
+    pub allow_inverted_dates: bool,
+
+    /// Create the record without account, left out of the balances and
+    /// stats until assigned one with record assign
+    #[arg(long, help_heading = "Record")]
+    pub pending: bool,
+
+    /// Currency of the pending record, EUR by default
+    #[arg(
+        long,
+        value_name = "CODE",
+        requires = "pending",
+        value_parser = parse_currency,
+        help_heading = "Record"
+    )]
+    pub currency: Option<Currency>,
+
+    /// Create the record even if its amount is above the records/max_amount
+    /// setting
+    #[arg(long)]
+    pub confirm: bool,
+
+    /// Amount of the transaction in its original currency, when the account
+    /// was debited or credited after a conversion
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        requires = "original_currency",
+        value_parser = parse::amount,
+        help_heading = "Record"
+    )]
+    pub original_amount: Option<Decimal>,
+
+    /// Original currency of the transaction, as an ISO 4217 code
+    #[arg(
+        long,
+        value_name = "CODE",
+        requires = "original_amount",
+        value_parser = parse_currency,
+        help_heading = "Record"
+    )]
+    pub original_currency: Option<Currency>,
+
     #[command(flatten, next_help_heading = "Category")]
     category: CategoryArgument,
 
@@ -137,13 +240,13 @@ impl Create {
     }
 
     pub fn value_date(&self) -> NaiveDate {
-        self.value_date.unwrap_or_else(|| Utc::now().date_naive())
+        self.value_date.unwrap_or_else(|| self.operation_date())
     }
 
     pub fn category(&self, conn: &mut Conn) -> Result<Option<Category>> {
         Ok(self
             .category
-            .resolve(conn, self.create_category.as_deref(), false)?
+            .resolve_replacements(conn, self.create_category.as_deref(), false)?
             .flatten())
     }
 
@@ -155,6 +258,39 @@ impl Create {
     }
 }
 
+#[derive(Args, Clone, Debug)]
+pub struct Transfer {
+    /// Amount to transfer
+    #[arg(value_parser = parse::amount)]
+    pub amount: Decimal,
+
+    /// Name of the account the money comes from
+    #[arg(long, value_name = "NAME")]
+    pub from_account: String,
+
+    /// Name of the account the money goes to
+    #[arg(long, value_name = "NAME")]
+    pub to_account: String,
+
+    /// Operation and value date of both records, today by default
+    #[arg(long, value_name = "DATE")]
+    date: Option<NaiveDate>,
+
+    /// Describe the transfer, by default with the names of the accounts
+    #[arg(long)]
+    pub details: Option<String>,
+
+    /// Transfer even if the amount is above the records/max_amount setting
+    #[arg(long)]
+    pub confirm: bool,
+}
+
+impl Transfer {
+    pub fn date(&self) -> NaiveDate {
+        self.date.unwrap_or_else(|| Utc::now().date_naive())
+    }
+}
+
 #[derive(Args, Clone, Debug)]
 pub struct Update {
     /// Id of the record to update
@@ -170,10 +306,108 @@ impl Update {
     }
 }
 
-use finnel::record::query::{OrderDirection, OrderField};
+#[derive(Args, Clone, Debug)]
+pub struct Search {
+    /// Terms to look for, case-insensitively, in the details and the names
+    /// of the merchant and category of the records, all of them have to match
+    #[arg(required = true)]
+    pub terms: Vec<String>,
+
+    /// Show only records from after this date
+    #[arg(short = 'a', long, value_name = "DATE")]
+    pub from: Option<NaiveDate>,
+
+    /// Show only records from before this date
+    #[arg(short = 'b', long, value_name = "DATE")]
+    pub to: Option<NaiveDate>,
+
+    /// Maximum number of records to show
+    #[arg(short = 'c', long)]
+    pub count: Option<i64>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Review {
+    /// Review only records from after this date
+    #[arg(short = 'a', long, value_name = "DATE")]
+    pub from: Option<NaiveDate>,
+
+    /// Review only records from before this date
+    #[arg(short = 'b', long, value_name = "DATE")]
+    pub to: Option<NaiveDate>,
+
+    /// Review only records without a category
+    #[arg(long)]
+    pub no_category: bool,
+
+    /// Review only records without a merchant
+    #[arg(long)]
+    pub no_merchant: bool,
+
+    /// Review only records flagged as needing attention
+    #[arg(long)]
+    pub flagged: bool,
+
+    /// Maximum number of records to review
+    #[arg(short = 'c', long)]
+    pub count: Option<i64>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Duplicates {
+    /// Maximum number of days between the operation dates of duplicates
+    #[arg(long, default_value_t = 3)]
+    pub days: u32,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Flag {
+    /// Id of the record to flag
+    id: u32,
+
+    /// Why the record needs attention
+    #[arg(long)]
+    pub reason: Option<String>,
+}
+
+impl Flag {
+    pub fn id(&self) -> i64 {
+        self.id as i64
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Unflag {
+    /// Id of the record to unflag
+    id: u32,
+}
+
+impl Unflag {
+    pub fn id(&self) -> i64 {
+        self.id as i64
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Assign {
+    /// Id of the pending record
+    id: u32,
+
+    /// Name of the account the record goes to
+    #[arg(long, value_name = "NAME")]
+    pub account: String,
+}
+
+impl Assign {
+    pub fn id(&self) -> i64 {
+        self.id as i64
+    }
+}
+
+use finnel::record::query::{OrderDirection, OrderField, OrderNulls};
 
 #[derive(Debug, Clone, Copy, derive_more::Into)]
-pub struct Sort(OrderField, OrderDirection);
+pub struct Sort(OrderField, OrderDirection, OrderNulls);
 
 impl Sort {
     pub fn try_from(value: &str) -> Result<Self> {
@@ -183,15 +417,27 @@ impl Sort {
 
 impl ValueEnum for Sort {
     fn value_variants<'a>() -> &'a [Self] {
+        use OrderDirection::*;
+        use OrderField::*;
+        use OrderNulls::{First, Last};
+
         &[
-            Sort(OrderField::Amount, OrderDirection::Asc),
-            Sort(OrderField::Date, OrderDirection::Asc),
-            Sort(OrderField::CategoryId, OrderDirection::Asc),
-            Sort(OrderField::MerchantId, OrderDirection::Asc),
-            Sort(OrderField::Amount, OrderDirection::Desc),
-            Sort(OrderField::Date, OrderDirection::Desc),
-            Sort(OrderField::CategoryId, OrderDirection::Desc),
-            Sort(OrderField::MerchantId, OrderDirection::Desc),
+            Sort(Amount, Asc, OrderNulls::Default),
+            Sort(Date, Asc, OrderNulls::Default),
+            Sort(CategoryId, Asc, OrderNulls::Default),
+            Sort(MerchantId, Asc, OrderNulls::Default),
+            Sort(Amount, Desc, OrderNulls::Default),
+            Sort(Date, Desc, OrderNulls::Default),
+            Sort(CategoryId, Desc, OrderNulls::Default),
+            Sort(MerchantId, Desc, OrderNulls::Default),
+            Sort(CategoryId, Asc, First),
+            Sort(CategoryId, Asc, Last),
+            Sort(CategoryId, Desc, First),
+            Sort(CategoryId, Desc, Last),
+            Sort(MerchantId, Asc, First),
+            Sort(MerchantId, Asc, Last),
+            Sort(MerchantId, Desc, First),
+            Sort(MerchantId, Desc, Last),
         ]
     }
 
@@ -209,22 +455,19 @@ impl ValueEnum for Sort {
 impl core::fmt::Display for Sort {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self.0 {
-            OrderField::Amount => match self.1 {
-                OrderDirection::Asc => write!(f, "amount"),
-                OrderDirection::Desc => write!(f, "amount.desc"),
-            },
-            OrderField::Date => match self.1 {
-                OrderDirection::Asc => write!(f, "date"),
-                OrderDirection::Desc => write!(f, "date.desc"),
-            },
-            OrderField::CategoryId => match self.1 {
-                OrderDirection::Asc => write!(f, "category_id"),
-                OrderDirection::Desc => write!(f, "category_id.desc"),
-            },
-            OrderField::MerchantId => match self.1 {
-                OrderDirection::Asc => write!(f, "merchant_id"),
-                OrderDirection::Desc => write!(f, "merchant_id.desc"),
-            },
+            OrderField::Amount => write!(f, "amount")?,
+            OrderField::Date => write!(f, "date")?,
+            OrderField::CategoryId => write!(f, "category_id")?,
+            OrderField::MerchantId => write!(f, "merchant_id")?,
+        }
+        match self.1 {
+            OrderDirection::Asc => {}
+            OrderDirection::Desc => write!(f, ".desc")?,
+        }
+        match self.2 {
+            OrderNulls::Default => Ok(()),
+            OrderNulls::First => write!(f, ".nulls_first"),
+            OrderNulls::Last => write!(f, ".nulls_last"),
         }
     }
 }
@@ -252,6 +495,20 @@ pub struct List {
     )]
     pub to: Option<NaiveDate>,
 
+    /// Show only records from the day the records were last listed, all of
+    /// them the first time
+    #[arg(long, conflicts_with = "from", help_heading = "Filter records")]
+    pub since_last: bool,
+
+    /// Show only the pending records, waiting for an account, whatever the
+    /// account selected
+    #[arg(long, help_heading = "Filter records")]
+    pub pending: bool,
+
+    /// Don't remember this listing as the last one for --since-last
+    #[arg(long)]
+    pub no_mark: bool,
+
     /// Sort and filter according to the operation date instead of the
     /// value date
     #[arg(short = 'o', long, help_heading = "Filter records")]
@@ -263,6 +520,7 @@ pub struct List {
         long,
         alias = "gt",
         value_name = "AMOUNT",
+        value_parser = parse::amount,
         help_heading = "Filter records"
     )]
     pub greater_than: Option<Decimal>,
@@ -273,6 +531,7 @@ pub struct List {
         long,
         alias = "lt",
         value_name = "AMOUNT",
+        value_parser = parse::amount,
         help_heading = "Filter records"
     )]
     pub less_than: Option<Decimal>,
@@ -285,10 +544,46 @@ pub struct List {
     #[arg(short = 'm', long, help_heading = "Filter records")]
     pub mode: Option<Mode>,
 
+    /// Leave out the records with this transaction mode
+    #[arg(long, value_name = "MODE", help_heading = "Filter records")]
+    pub exclude_mode: Option<Mode>,
+
     /// Show only records with this text in the details
     #[arg(long, help_heading = "Filter records")]
     details: Option<String>,
 
+    /// Show only records with this tag, can be repeated to require several
+    /// tags
+    #[arg(long, value_name = "NAME", help_heading = "Filter records")]
+    tag: Vec<String>,
+
+    /// Show only records flagged as needing attention, with the reason
+    #[arg(long, help_heading = "Filter records")]
+    pub flagged: bool,
+
+    /// Show only records of a transaction originally in this currency
+    #[arg(
+        long,
+        value_name = "CODE",
+        value_parser = parse_currency,
+        help_heading = "Filter records"
+    )]
+    pub original_currency: Option<Currency>,
+
+    /// Show the dates relative to today, like yesterday or 3 days ago
+    ///
+    /// This is the default when the display/relative_dates setting is true
+    #[arg(long)]
+    pub relative_dates: bool,
+
+    #[command(flatten)]
+    pub output: ListOutput,
+
+    /// Leave out the number of records shown and the totals of their amounts
+    /// printed after them
+    #[arg(long, help_heading = "Output")]
+    pub no_footer: bool,
+
     /// Maximum number of records to show
     #[arg(short = 'c', long, help_heading = "Filter records")]
     pub count: Option<i64>,
@@ -296,22 +591,83 @@ pub struct List {
     #[arg(long, help_heading = "Sort records")]
     pub sort: Vec<Sort>,
 
-    #[command(flatten, next_help_heading = "Filter by category")]
-    category: CategoryArgument,
+    /// Show this page of the records, starting at 1
+    ///
+    /// Without --sort, the records are sorted by date
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        conflicts_with = "count",
+        help_heading = "Pagination"
+    )]
+    page: Option<i64>,
+
+    /// Number of records per page, 50 by default
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..),
+        conflicts_with = "count",
+        help_heading = "Pagination"
+    )]
+    per_page: Option<i64>,
+
+    /// Name or id of the category to use, can be repeated to show records
+    /// of any of them
+    #[arg(
+        long,
+        value_name = "NAME_OR_ID",
+        group = "category_args",
+        help_heading = "Filter by category"
+    )]
+    category: Vec<CategoryIdentifier>,
+
+    /// Also show records of the children of the categories, recursively
+    #[arg(long, requires = "category", help_heading = "Filter by category")]
+    with_children: bool,
 
     /// Show only records without a category
     #[arg(long, group = "category_args", help_heading = "Filter by category")]
     no_category: bool,
 
+    /// Name or id of a category whose records to leave out, can be repeated
+    ///
+    /// Records without a category are kept
+    #[arg(
+        long,
+        value_name = "NAME_OR_ID",
+        conflicts_with = "no_category",
+        help_heading = "Filter by category"
+    )]
+    exclude_category: Vec<CategoryIdentifier>,
+
     #[command(flatten, next_help_heading = "Filter by merchant")]
     merchant: MerchantArgument,
 
     /// Show only records without a merchant
     #[arg(long, group = "merchant_args", help_heading = "Filter by merchant")]
     no_merchant: bool,
+
+    /// Name or id of a merchant whose records to leave out, can be repeated
+    ///
+    /// Records without a merchant are kept
+    #[arg(
+        long,
+        value_name = "NAME_OR_ID",
+        conflicts_with = "no_merchant",
+        help_heading = "Filter by merchant"
+    )]
+    exclude_merchant: Vec<MerchantIdentifier>,
 }
 
 impl List {
+    /// Page to show and number of records per page, when paginating
+    pub fn page(&self) -> Option<(i64, i64)> {
+        if self.page.is_none() && self.per_page.is_none() {
+            return None;
+        }
+        Some((self.page.unwrap_or(1), self.per_page.unwrap_or(50)))
+    }
+
     pub fn details(&self) -> Option<String> {
         self.details.clone().map(|mut n| {
             if !n.starts_with("%") {
@@ -324,13 +680,74 @@ impl List {
         })
     }
 
-    pub fn category(&self, conn: &mut Conn) -> Result<Option<Option<Category>>> {
-        self.category.resolve(conn, None, self.no_category)
+    pub fn tag_ids(&self, conn: &mut Conn) -> Result<Option<Vec<i64>>> {
+        if self.tag.is_empty() {
+            return Ok(None);
+        }
+
+        self.tag
+            .iter()
+            .map(|name| Ok(Tag::find_by_name(conn, name)?.id))
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    pub fn category_id(&self) -> Option<Option<i64>> {
+        self.no_category.then_some(None)
+    }
+
+    /// Ids of the selected categories, and of their descendants with
+    /// --with-children
+    pub fn category_ids(&self, conn: &mut Conn) -> Result<Option<Vec<i64>>> {
+        if self.category.is_empty() {
+            return Ok(None);
+        }
+
+        let mut ids = Vec::new();
+        for identifier in &self.category {
+            let category = identifier.find(conn)?;
+            let mut category_ids = vec![category.id];
+            if self.with_children {
+                category_ids.extend(category.descendant_ids(conn)?);
+            }
+
+            for id in category_ids {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        Ok(Some(ids))
     }
 
     pub fn merchant(&self, conn: &mut Conn) -> Result<Option<Option<Merchant>>> {
         self.merchant.resolve(conn, None, self.no_merchant)
     }
+
+    pub fn exclude_category_ids(&self, conn: &mut Conn) -> Result<Option<Vec<i64>>> {
+        if self.exclude_category.is_empty() {
+            return Ok(None);
+        }
+
+        self.exclude_category
+            .iter()
+            .map(|identifier| Ok(identifier.find(conn)?.id))
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    pub fn exclude_merchant_ids(&self, conn: &mut Conn) -> Result<Option<Vec<i64>>> {
+        if self.exclude_merchant.is_empty() {
+            return Ok(None);
+        }
+
+        self.exclude_merchant
+            .iter()
+            .map(|identifier| Ok(identifier.find(conn)?.id))
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -339,6 +756,8 @@ pub enum ListAction {
     Config(ConfigurationAction),
     #[command(flatten)]
     Other(Action),
+    /// Shift the operation and value dates of the listed record(s)
+    ShiftDates(ShiftDatesArgs),
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -357,6 +776,12 @@ pub enum ConfigurationAction {
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum ConfigurationKey {
     DefaultSort,
+    /// Largest amount of the records created or imported without
+    /// confirmation (default: none)
+    MaxAmount,
+    /// Clear the flags of the records up to the date of a confirmed account
+    /// reconciliation, true or false (default: true)
+    ClearFlagsOnReconcile,
 }
 
 impl ConfigurationKey {
@@ -364,6 +789,8 @@ impl ConfigurationKey {
         use ConfigurationKey::*;
         match self {
             DefaultSort => "default_sort",
+            MaxAmount => max_amount::KEY,
+            ClearFlagsOnReconcile => "clear_flags_on_reconcile",
         }
     }
 }
@@ -381,6 +808,54 @@ pub enum Action {
     },
 }
 
+#[derive(Args, Clone, Debug)]
+pub struct ShiftDatesArgs {
+    #[command(flatten)]
+    shift: ShiftArgument,
+
+    /// Confirm the update of the dates
+    #[arg(long)]
+    pub confirm: bool,
+
+    /// Only print the dates before and after the shift
+    #[arg(long)]
+    pub pretend: bool,
+}
+
+impl ShiftDatesArgs {
+    pub fn shift(&self) -> Shift {
+        self.shift.resolve()
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+#[group(id = "shift_args", required = true, multiple = false)]
+pub struct ShiftArgument {
+    /// Number of days to add, negative to go back in time
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    days: Option<i64>,
+
+    /// Number of months to add, keeping the end of the month if the day
+    /// does not exist, e.g. from January 31 to February 29
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    months: Option<i32>,
+
+    /// Number of years to add
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    years: Option<i32>,
+}
+
+impl ShiftArgument {
+    pub fn resolve(&self) -> Shift {
+        match (self.days, self.months, self.years) {
+            (Some(days), _, _) => Shift::Days(days),
+            (_, Some(months), _) => Shift::Months(months),
+            (_, _, Some(years)) => Shift::Years(years),
+            (None, None, None) => unreachable!("required by the group"),
+        }
+    }
+}
+
 #[derive(Args, Clone, Debug)]
 pub struct UpdateArgs {
     /// Change the record details
@@ -391,12 +866,26 @@ pub struct UpdateArgs {
     #[arg(long, value_name = "DATE", help_heading = "Record")]
     pub value_date: Option<NaiveDate>,
 
+    /// Accept a value date before the operation date
+    #[arg(long, help_heading = "Record")]
+    pub allow_inverted_dates: bool,
+
     /// Confirm update of sensitive information
     #[arg(long)]
     pub confirm: bool,
 
+    /// Only print the fields each record would change from and to, without
+    /// saving anything
+    #[arg(long, conflicts_with_all = ["create_category", "create_merchant"])]
+    pub pretend: bool,
+
     /// Amount of the record
-    #[arg(long, requires = "confirm", help_heading = "Record")]
+    #[arg(
+        long,
+        requires = "confirm",
+        value_parser = parse::amount,
+        help_heading = "Record"
+    )]
     pub amount: Option<Decimal>,
 
     /// Transaction direction
@@ -420,6 +909,32 @@ pub struct UpdateArgs {
     )]
     pub operation_date: Option<NaiveDate>,
 
+    /// Change the amount of the transaction in its original currency
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        value_parser = parse::amount,
+        help_heading = "Record"
+    )]
+    pub original_amount: Option<Decimal>,
+
+    /// Change the original currency of the transaction
+    #[arg(
+        long,
+        value_name = "CODE",
+        value_parser = parse_currency,
+        help_heading = "Record"
+    )]
+    pub original_currency: Option<Currency>,
+
+    /// Remove the original amount and currency
+    #[arg(
+        long,
+        conflicts_with_all = ["original_amount", "original_currency"],
+        help_heading = "Record"
+    )]
+    pub no_original_amount: bool,
+
     #[command(flatten, next_help_heading = "Category")]
     category: CategoryArgument,
 
@@ -454,9 +969,25 @@ pub struct UpdateArgs {
 }
 
 impl UpdateArgs {
+    pub fn original_amount(&self) -> Option<Option<Decimal>> {
+        if self.no_original_amount {
+            Some(None)
+        } else {
+            self.original_amount.map(Some)
+        }
+    }
+
+    pub fn original_currency(&self) -> Option<Option<Currency>> {
+        if self.no_original_amount {
+            Some(None)
+        } else {
+            self.original_currency.map(Some)
+        }
+    }
+
     pub fn category(&self, conn: &mut Conn) -> Result<Option<Option<Category>>> {
         self.category
-            .resolve(conn, self.create_category.as_deref(), self.no_category)
+            .resolve_replacements(conn, self.create_category.as_deref(), self.no_category)
     }
 
     pub fn merchant(&self, conn: &mut Conn) -> Result<Option<Option<Merchant>>> {
@@ -464,3 +995,33 @@ impl UpdateArgs {
             .resolve(conn, self.create_merchant.as_deref(), self.no_merchant)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn sort() -> Result<()> {
+        for value in [
+            "amount",
+            "date.desc",
+            "category_id",
+            "category_id.nulls_last",
+            "category_id.desc.nulls_first",
+            "merchant_id.desc.nulls_last",
+        ] {
+            assert_eq!(value, Sort::try_from(value)?.to_string());
+        }
+
+        let Sort(field, direction, nulls) = Sort::try_from("category_id.desc.nulls_last")?;
+        assert!(matches!(field, OrderField::CategoryId));
+        assert!(matches!(direction, OrderDirection::Desc));
+        assert!(matches!(nulls, OrderNulls::Last));
+
+        assert!(Sort::try_from("amount.nulls_last").is_err());
+        assert!(Sort::try_from("category_id.nulls_last.desc").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use crate::cli::category::CategoryArgument;
+use crate::cli::merchant::MerchantArgument;
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use clap::{Args, Subcommand};
+use finnel::{parse, prelude::*};
+
+create_identifier! {RecurringPayment}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List recurring payments with their next due date
+    List(List),
+    /// Create a new recurring payment
+    Create(Create),
+    /// Update a recurring payment
+    Update(Update),
+    /// Delete a recurring payment
+    Delete(Delete),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        matches!(self, Command::List(_))
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct List {
+    /// Show only the payments due within this number of days
+    #[arg(long, value_name = "DAYS")]
+    pub due: Option<u64>,
+
+    /// Date from which the next due dates are computed, by default today
+    #[arg(long, value_name = "DATE")]
+    date: Option<NaiveDate>,
+}
+
+impl List {
+    pub fn date(&self) -> NaiveDate {
+        self.date.unwrap_or_else(|| Utc::now().date_naive())
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Create {
+    /// Name of the new recurring payment
+    pub name: String,
+
+    /// Amount of each payment
+    #[arg(value_parser = parse::amount)]
+    pub amount: Decimal,
+
+    /// Describe the payment
+    #[arg(long, default_value = "")]
+    pub description: String,
+
+    /// How often the payment happens
+    ///
+    /// Possible values are weekly, monthly and yearly
+    #[arg(short = 'f', long, default_value_t)]
+    pub frequency: Frequency,
+
+    /// Transaction direction
+    ///
+    /// Possible values include debit, credit, and variants
+    #[arg(short = 'd', long, default_value_t)]
+    pub direction: Direction,
+
+    /// Transaction mode
+    ///
+    /// Possible values include direct, transfer, ATM, ATM CB *WXYZ, CB *WXYZ
+    #[arg(short = 'm', long, default_value_t)]
+    pub mode: Mode,
+
+    /// Date of the first payment, by default today
+    ///
+    /// Following payments fall on the same day of the week, month or year
+    #[arg(long, value_name = "DATE")]
+    start_date: Option<NaiveDate>,
+
+    /// Date after which the payment stops
+    #[arg(long, value_name = "DATE")]
+    pub end_date: Option<NaiveDate>,
+
+    #[command(flatten, next_help_heading = "Category")]
+    category: CategoryArgument,
+
+    #[command(flatten, next_help_heading = "Merchant")]
+    merchant: MerchantArgument,
+}
+
+impl Create {
+    pub fn start_date(&self) -> NaiveDate {
+        self.start_date.unwrap_or_else(|| Utc::now().date_naive())
+    }
+
+    pub fn category(&self, conn: &mut Conn) -> Result<Option<Category>> {
+        Ok(self
+            .category
+            .resolve_replacements(conn, None, false)?
+            .flatten())
+    }
+
+    pub fn merchant(&self, conn: &mut Conn) -> Result<Option<Merchant>> {
+        Ok(self.merchant.resolve(conn, None, false)?.flatten())
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Update {
+    #[command(flatten)]
+    pub identifier: Identifier,
+
+    /// New name of the recurring payment
+    #[arg(long)]
+    pub new_name: Option<String>,
+
+    /// Change the description
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// Change the amount of each payment
+    #[arg(long, value_parser = parse::amount)]
+    pub amount: Option<Decimal>,
+
+    /// Change how often the payment happens
+    #[arg(short = 'f', long)]
+    pub frequency: Option<Frequency>,
+
+    /// Change the transaction direction
+    #[arg(short = 'd', long)]
+    pub direction: Option<Direction>,
+
+    /// Change the transaction mode
+    #[arg(short = 'm', long)]
+    pub mode: Option<Mode>,
+
+    /// Change the date of the first payment
+    #[arg(long, value_name = "DATE")]
+    pub start_date: Option<NaiveDate>,
+
+    /// Change the date after which the payment stops
+    #[arg(long, value_name = "DATE")]
+    end_date: Option<NaiveDate>,
+
+    /// Remove the end date
+    #[arg(long, conflicts_with = "end_date")]
+    no_end_date: bool,
+
+    #[command(flatten, next_help_heading = "Category")]
+    category: CategoryArgument,
+
+    /// Remove the category
+    #[arg(long, group = "category_args", help_heading = "Category")]
+    no_category: bool,
+
+    #[command(flatten, next_help_heading = "Merchant")]
+    merchant: MerchantArgument,
+
+    /// Remove the merchant
+    #[arg(long, group = "merchant_args", help_heading = "Merchant")]
+    no_merchant: bool,
+}
+
+impl Update {
+    pub fn end_date(&self) -> Option<Option<NaiveDate>> {
+        if self.no_end_date {
+            Some(None)
+        } else {
+            self.end_date.map(Some)
+        }
+    }
+
+    pub fn category(&self, conn: &mut Conn) -> Result<Option<Option<Category>>> {
+        self.category
+            .resolve_replacements(conn, None, self.no_category)
+    }
+
+    pub fn merchant(&self, conn: &mut Conn) -> Result<Option<Option<Merchant>>> {
+        self.merchant.resolve(conn, None, self.no_merchant)
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Delete {
+    #[command(flatten)]
+    pub identifier: Identifier,
+
+    /// Confirm deletion
+    #[arg(long)]
+    pub confirm: bool,
+}
@@ -1,9 +1,13 @@
 use anyhow::Result;
 
-use clap::{Args, Subcommand};
+use clap::{Args, Subcommand, ValueEnum};
 
+use crate::cli::calendar::Monthly;
 use crate::cli::category::Identifier as CategoryIdentifier;
+use crate::cli::merchant::Identifier as MerchantIdentifier;
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
 use finnel::prelude::*;
+use std::path::PathBuf;
 
 create_identifier! {Report}
 
@@ -17,6 +21,37 @@ pub enum Command {
     Create(Create),
     /// Delete a report
     Delete(Delete),
+    /// Show the amounts of a category month by month
+    Category(CategorySeries),
+    /// Show the number of records and their total, smallest, largest and
+    /// average amounts of each category for a month
+    CategoryDetail(CategoryDetail),
+    /// Show the debits to a merchant and the ones it replaces month by month,
+    /// with the trend of the last month
+    Merchant(MerchantSeries),
+    /// Show the debit amounts of each payment mode month by month
+    Modes(ModeSeries),
+    /// Show the debit, credit and net amounts of each month of a year
+    Year(YearOverview),
+    /// Summarize the records since a date as plain text, e.g. to send it by
+    /// mail
+    Digest(DigestPeriod),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        match self {
+            Command::List(_)
+            | Command::Category(_)
+            | Command::CategoryDetail(_)
+            | Command::Merchant(_)
+            | Command::Modes(_)
+            | Command::Year(_)
+            | Command::Digest(_) => true,
+            Command::Show(Show { action, .. }) => action.is_none(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -27,6 +62,9 @@ pub struct Show {
     #[command(flatten)]
     pub identifier: Identifier,
 
+    #[command(flatten)]
+    pub output: Output,
+
     #[command(subcommand)]
     pub action: Option<Action>,
 }
@@ -60,3 +98,168 @@ pub struct Delete {
     #[arg(long)]
     pub confirm: bool,
 }
+
+#[derive(Args, Clone, Debug)]
+pub struct CategorySeries {
+    #[command(flatten)]
+    pub category: CategoryIdentifier,
+
+    /// Start from this date, by default the first day of the month a year
+    /// ago
+    #[arg(short = 'a', long, value_name = "DATE")]
+    pub from: Option<NaiveDate>,
+
+    /// Stop before this date, by default the first day of next month
+    #[arg(short = 'b', long, value_name = "DATE")]
+    pub to: Option<NaiveDate>,
+
+    /// Include the children of the category, recursively
+    #[arg(long)]
+    pub with_children: bool,
+
+    #[command(flatten)]
+    pub output: Output,
+}
+
+impl CategorySeries {
+    pub fn range(&self) -> Result<std::ops::Range<NaiveDate>> {
+        month_range(self.from, self.to)
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct MerchantSeries {
+    #[command(flatten)]
+    pub merchant: MerchantIdentifier,
+
+    /// Number of months to show, the current one being the last
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(1..))]
+    pub months: u32,
+
+    #[command(flatten)]
+    pub output: Output,
+}
+
+impl MerchantSeries {
+    pub fn range(&self) -> Result<std::ops::Range<NaiveDate>> {
+        let start_of_month = Utc::now()
+            .date_naive()
+            .with_day(1)
+            .ok_or(anyhow::anyhow!("Cannot compute start of month"))?;
+
+        Ok(start_of_month - Months::new(self.months - 1)..start_of_month + Months::new(1))
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ModeSeries {
+    /// Start from this date, by default the first day of the month a year
+    /// ago
+    #[arg(short = 'a', long, value_name = "DATE")]
+    pub from: Option<NaiveDate>,
+
+    /// Stop before this date, by default the first day of next month
+    #[arg(short = 'b', long, value_name = "DATE")]
+    pub to: Option<NaiveDate>,
+
+    #[command(flatten)]
+    pub output: Output,
+}
+
+impl ModeSeries {
+    pub fn range(&self) -> Result<std::ops::Range<NaiveDate>> {
+        month_range(self.from, self.to)
+    }
+}
+
+/// Range of the series, the last twelve months by default
+fn month_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<std::ops::Range<NaiveDate>> {
+    let today = Utc::now().date_naive();
+    let start_of_month = today
+        .with_day(1)
+        .ok_or(anyhow::anyhow!("Cannot compute start of month"))?;
+
+    let from = from.unwrap_or(start_of_month - Months::new(11));
+    let to = to.unwrap_or(start_of_month + Months::new(1));
+    if from >= to {
+        anyhow::bail!("--from must be before --to");
+    }
+
+    Ok(from..to)
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct YearOverview {
+    /// Year to show, by default the current one
+    #[arg(value_name = "YYYY")]
+    pub year: Option<i32>,
+
+    #[command(flatten)]
+    pub output: Output,
+}
+
+impl YearOverview {
+    pub fn year(&self) -> i32 {
+        self.year.unwrap_or_else(|| Utc::now().year())
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct DigestPeriod {
+    /// Summarize the records from this date, by default 7 days ago
+    #[arg(long, value_name = "DATE")]
+    since: Option<NaiveDate>,
+
+    /// Summarize the records from the day of the last digest, or of the
+    /// default period the first time
+    #[arg(long, conflicts_with = "since")]
+    pub since_last: bool,
+
+    /// Don't remember this digest as the last one for --since-last
+    #[arg(long)]
+    pub no_mark: bool,
+}
+
+impl DigestPeriod {
+    pub fn since(&self) -> NaiveDate {
+        self.since
+            .unwrap_or_else(|| Utc::now().date_naive() - Days::new(7))
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CategoryDetail {
+    #[command(flatten)]
+    pub month: Monthly,
+
+    #[command(flatten)]
+    pub output: Output,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Output {
+    /// Write the tables to FILE as a standalone document in FORMAT instead
+    /// of printing them, e.g. `--output html report.html`
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "FILE"])]
+    output: Option<Vec<String>>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Html,
+}
+
+impl Output {
+    pub fn get(&self) -> Result<Option<(OutputFormat, PathBuf)>> {
+        let Some([format, file]) = self.output.as_deref() else {
+            return Ok(None);
+        };
+        let format = OutputFormat::from_str(format, true)
+            .map_err(|_| anyhow::anyhow!("Unknown output format {format:?}, expected html"))?;
+
+        Ok(Some((format, PathBuf::from(file))))
+    }
+}
@@ -0,0 +1,19 @@
+use clap::{Args, Subcommand};
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Compute again the monthly stats read by the calendar and the reports,
+    /// of the current year unless told otherwise
+    Rebuild(Rebuild),
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Rebuild {
+    /// Rebuild the months of this year
+    #[arg(long, value_name = "YYYY")]
+    pub year: Option<i32>,
+
+    /// Rebuild every month from the first record to the last one
+    #[arg(long, conflicts_with = "year")]
+    pub all: bool,
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use finnel::prelude::*;
+
+create_identifier! {Tag}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List tags with the number of records using them
+    List {},
+    /// Delete a tag, removing it from all records
+    Delete(Delete),
+}
+
+impl super::Readonly for Command {
+    fn is_readonly(&self) -> bool {
+        matches!(self, Command::List {})
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Delete {
+    #[command(flatten)]
+    pub identifier: Identifier,
+
+    /// Confirm deletion
+    #[arg(long)]
+    pub confirm: bool,
+}
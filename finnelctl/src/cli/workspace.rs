@@ -0,0 +1,12 @@
+use clap::Subcommand;
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List the workspaces of config.toml with their database, the current
+    /// one marked with a star
+    List {},
+    /// Print the name of the workspace in use, if any
+    Current {},
+    /// Use the workspace when none is given with --workspace
+    SetDefault { name: String },
+}
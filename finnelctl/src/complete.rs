@@ -0,0 +1,273 @@
+use anyhow::Result;
+use clap::CommandFactory;
+
+use finnel::{
+    account::{AccountGroup, QueryAccount},
+    category::QueryCategory,
+    merchant::QueryMerchant,
+};
+
+use crate::cli::{complete::*, Cli};
+use crate::config::Config;
+
+/// Name of the hidden command printing the names of a kind
+const VALUES_COMMAND: &str = "_complete-values";
+
+/// Options taking a name, along with the top-level command they belong to
+/// when the same option takes names of different kinds
+const OPTIONS: &[(&str, Option<&str>, Kind)] = &[
+    ("-A", None, Kind::Account),
+    ("--account", None, Kind::Account),
+    ("--from-account", None, Kind::Account),
+    ("--to-account", None, Kind::Account),
+    ("--reassign-to", None, Kind::Account),
+    ("-G", None, Kind::Group),
+    ("--group", None, Kind::Group),
+    ("--category", None, Kind::Category),
+    ("--exclude-category", None, Kind::Category),
+    ("--default-category", None, Kind::Category),
+    ("--parent", None, Kind::Category),
+    ("--replace-by", Some("category"), Kind::Category),
+    ("--merchant", None, Kind::Merchant),
+    ("--exclude-merchant", None, Kind::Merchant),
+    ("--replace-by", Some("merchant"), Kind::Merchant),
+];
+
+/// Print the completion script generated by clap, followed by the functions
+/// completing the names from the database
+pub fn run(args: &Arguments) -> Result<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    let shell = match args.shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+    };
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, &name, &mut script);
+    print!("{}", String::from_utf8(script)?);
+    print!("{}", dynamic_script(args.shell, &name));
+
+    Ok(())
+}
+
+fn dynamic_script(shell: Shell, name: &str) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let values = |shell: &str, kind: &str, prefix: &str| {
+        format!("{name} {VALUES_COMMAND} --shell {shell} {kind}{prefix} 2>/dev/null")
+    };
+    // Case patterns of bash and zsh, matching the first word and the option
+    // before the one being completed
+    let mut cases = String::new();
+    for (option, command, kind) in OPTIONS {
+        let pattern = match command {
+            Some(command) => format!("\"{command} {option}\""),
+            None => format!("*\" {option}\""),
+        };
+        cases += &format!("        {pattern}) kind={} ;;\n", kind_name(*kind));
+    }
+
+    match shell {
+        Shell::Bash => format!(
+            r#"
+{function}_values() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" kind=
+    case "${{COMP_WORDS[1]}} ${{COMP_WORDS[COMP_CWORD-1]}}" in
+{cases}    esac
+    if [[ -z "$kind" ]]; then
+        {function} "$@"
+        return
+    fi
+    local IFS=$'\n'
+    COMPREPLY=($({}))
+}}
+complete -F {function}_values -o bashdefault -o default {name}
+"#,
+            values("bash", "\"$kind\"", " -- \"$cur\"")
+        ),
+        Shell::Zsh => format!(
+            r#"
+{function}_values() {{
+    local kind=
+    case "${{words[2]}} ${{words[CURRENT-1]}}" in
+{cases}    esac
+    if [[ -z "$kind" ]]; then
+        {function} "$@"
+        return
+    fi
+    local -a names
+    names=(${{(f)"$({})"}})
+    compadd -a names
+}}
+compdef {function}_values {name}
+"#,
+            values("zsh", "\"$kind\"", "")
+        ),
+        Shell::Fish => {
+            let mut lines = String::from("\n");
+            for (option, command, kind) in OPTIONS {
+                let flag = match option.strip_prefix("--") {
+                    Some(long) => format!("-l {long}"),
+                    None => format!("-s {}", option.trim_start_matches('-')),
+                };
+                let condition = match command {
+                    Some(command) => format!(" -n '__fish_seen_subcommand_from {command}'"),
+                    None => String::new(),
+                };
+                lines += &format!(
+                    "complete -c {name}{condition} {flag} -x -a '({})'\n",
+                    values("fish", kind_name(*kind), "")
+                );
+            }
+            lines
+        }
+    }
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Account => "account",
+        Kind::Group => "group",
+        Kind::Category => "category",
+        Kind::Merchant => "merchant",
+    }
+}
+
+/// Print the names of the kind, one per line, escaped for the shell
+///
+/// Completion must never break the shell of the user, so errors only lead
+/// to printing nothing, and a missing database isn't created.
+pub fn values(config: &Config, args: &Values) -> Result<()> {
+    let names = if config.database_path().exists() {
+        names(config, args.kind).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let prefix = args
+        .prefix
+        .as_deref()
+        .map(|prefix| unescape(prefix, args.shell))
+        .unwrap_or_default();
+    for name in names {
+        // A name spanning lines can't be printed one per line
+        if name.starts_with(&prefix) && !name.contains('\n') {
+            println!("{}", escape(&name, args.shell));
+        }
+    }
+
+    Ok(())
+}
+
+fn names(config: &Config, kind: Kind) -> Result<Vec<String>> {
+    let conn = &mut config.database_readonly()?;
+    let mut names = match kind {
+        Kind::Account => QueryAccount::default()
+            .run(conn)?
+            .into_iter()
+            .map(|account| account.name)
+            .collect(),
+        Kind::Group => AccountGroup::all(conn)?
+            .into_iter()
+            .map(|group| group.name)
+            .collect(),
+        Kind::Category => QueryCategory::default()
+            .run(conn)?
+            .into_iter()
+            .map(|category| category.name)
+            .collect(),
+        Kind::Merchant => QueryMerchant::default()
+            .run(conn)?
+            .into_iter()
+            .map(|merchant| merchant.name)
+            .collect::<Vec<_>>(),
+    };
+    names.sort();
+
+    Ok(names)
+}
+
+/// Escape the characters bash would interpret in a word, zsh and fish
+/// quoting the candidates themselves
+fn escape(name: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Bash => {
+            let mut escaped = String::with_capacity(name.len());
+            for c in name.chars() {
+                if !(c.is_alphanumeric() || "_-+=.,:/@%".contains(c)) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        Shell::Zsh | Shell::Fish => name.to_string(),
+    }
+}
+
+/// Word typed in the shell, without the backslashes and quotes escaping it
+fn unescape(word: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Bash => {
+            let mut unescaped = String::with_capacity(word.len());
+            let mut chars = word.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.extend(chars.next()),
+                    '\'' | '"' => {}
+                    c => unescaped.push(c),
+                }
+            }
+            unescaped
+        }
+        Shell::Zsh | Shell::Fish => word.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::assert_eq;
+
+    #[test]
+    fn escape() {
+        assert_eq!("Cash", super::escape("Cash", Shell::Bash));
+        assert_eq!(
+            "Fish\\ \\&\\ Chips\\ \\(l\\'Étoile\\)",
+            super::escape("Fish & Chips (l'Étoile)", Shell::Bash)
+        );
+        assert_eq!("Fish & Chips", super::escape("Fish & Chips", Shell::Zsh));
+
+        for name in ["Fish & Chips", "l'Étoile", "$HOME"] {
+            assert_eq!(
+                name,
+                unescape(&super::escape(name, Shell::Bash), Shell::Bash)
+            );
+        }
+        assert_eq!("Fish & ", unescape("'Fish & ", Shell::Bash));
+    }
+
+    #[test]
+    fn dynamic_script() {
+        let bash = super::dynamic_script(Shell::Bash, "finnelctl");
+        assert!(bash.contains("        \"merchant --replace-by\") kind=merchant ;;\n"));
+        assert!(bash.contains(
+            "COMPREPLY=($(finnelctl _complete-values --shell bash \"$kind\" -- \"$cur\" 2>/dev/null))"
+        ));
+        assert!(bash.contains("complete -F _finnelctl_values -o bashdefault -o default finnelctl"));
+
+        let zsh = super::dynamic_script(Shell::Zsh, "finnelctl");
+        assert!(zsh.contains("        *\" -A\") kind=account ;;\n"));
+        assert!(zsh.contains("compdef _finnelctl_values finnelctl"));
+
+        let fish = super::dynamic_script(Shell::Fish, "finnelctl");
+        assert!(fish.contains(
+            "complete -c finnelctl -n '__fish_seen_subcommand_from category' -l replace-by -x \
+             -a '(finnelctl _complete-values --shell fish category 2>/dev/null)'\n"
+        ));
+        assert!(fish.contains(
+            "complete -c finnelctl -s A -x -a '(finnelctl _complete-values --shell fish account 2>/dev/null)'\n"
+        ));
+    }
+}
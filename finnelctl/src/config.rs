@@ -1,17 +1,30 @@
 use std::fs::create_dir;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
 use toml::{Table, Value};
 
-use finnel::prelude::*;
+use finnel::{prelude::*, record::history};
 
-use crate::cli::{Cli, Commands};
+use crate::cli::{Cli, Commands, Readonly};
+use crate::error::CliError;
+use crate::utils::{amount, table_display::Style};
+
+mod command;
+pub use command::run;
+
+mod store;
+pub use store::ConfigStore;
+
+mod workspace;
+pub use workspace::Workspace;
 
 #[derive(Debug)]
 pub struct Config {
     pub dir: PathBuf,
     pub data_dir: PathBuf,
+    /// Workspace given with --workspace, or the default one
+    pub workspace: Option<Workspace>,
     cli: Cli,
     table: Table,
 }
@@ -36,12 +49,19 @@ impl Config {
             Err(_) => Table::new(),
         };
 
+        // -D is about a single database, so it leaves out the default
+        // workspace but not the one explicitly given
+        let workspace = match &cli.workspace {
+            Some(name) => Some(Workspace::find(&table, name)?),
+            None if cli.data.is_some() => None,
+            None => default_workspace(&dir, &table)?,
+        };
+
         let data_dir = cli.data.clone().unwrap_or_else(|| {
-            table
-                .get("data_dir")
-                .and_then(Value::as_str)
-                .map(PathBuf::from)
-                .unwrap_or_else(data_home)
+            workspace
+                .as_ref()
+                .and_then(|workspace| workspace.data_dir.clone())
+                .unwrap_or_else(|| default_data_dir(&table))
         });
 
         if !data_dir.is_dir() {
@@ -54,6 +74,7 @@ impl Config {
         Ok(Config {
             dir,
             data_dir,
+            workspace,
             cli,
             table,
         })
@@ -63,28 +84,102 @@ impl Config {
         self.cli.verbose.log_level_filter()
     }
 
+    /// Whether interactive confirmations should be answered automatically
+    pub fn assume_yes(&self) -> bool {
+        self.cli.yes
+    }
+
+    /// Whether lists should be printed as plain rows instead of tables
+    pub fn plain(&self) -> bool {
+        self.cli.plain
+    }
+
+    /// How the cells of the tables are written
+    pub fn style(&self) -> Result<Style> {
+        Ok(Style::new(amount::load_style(self)?))
+    }
+
     pub fn account_name(&self) -> Option<&str> {
         self.cli.account.as_deref()
     }
 
-    pub fn account_or_default(&self, conn: &mut Conn) -> Result<Option<Account>> {
-        if let Some(name) = self.account_name() {
-            match Account::find_by_name(conn, name) {
-                Ok(account) => Ok(Some(account)),
-                Err(e) if e.is_not_found() => Err(anyhow!("Account not found: {}", name)),
-                Err(e) => Err(e.into()),
+    /// Account given on the command line, ignoring the default account
+    pub fn account(&self, conn: &mut Conn) -> Result<Option<Account>> {
+        self.ensure_single_account()?;
+        let Some(name) = self.account_name() else {
+            return Ok(None);
+        };
+        match Account::find_by_name(conn, name) {
+            Ok(account) => Ok(Some(account)),
+            Err(e) if e.is_not_found() => {
+                Err(CliError::NotFound(format!("Account not found: {}", name)).into())
             }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn account_or_default(&self, conn: &mut Conn) -> Result<Option<Account>> {
+        self.ensure_single_account()?;
+        if self.account_name().is_some() {
+            self.account(conn)
         } else {
             self.default_account(conn)
         }
     }
 
+    pub fn group_name(&self) -> Option<&str> {
+        self.cli.group.as_deref()
+    }
+
+    /// Fail when a group is given to a command working on a single account
+    pub fn ensure_single_account(&self) -> Result<()> {
+        match self.group_name() {
+            Some(name) => Err(anyhow!(
+                "Group {name} cannot be used with this command, use --account instead"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Group of accounts given on the command line
+    pub fn group(&self, conn: &mut Conn) -> Result<Option<AccountGroup>> {
+        let Some(name) = self.group_name() else {
+            return Ok(None);
+        };
+        match AccountGroup::find_by_name(conn, name) {
+            Ok(group) => Ok(Some(group)),
+            Err(e) if e.is_not_found() => {
+                Err(CliError::NotFound(format!("Account group not found: {}", name)).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Accounts of the group, or the account, given on the command line,
+    /// ignoring the default account
+    pub fn accounts(&self, conn: &mut Conn) -> Result<Option<Vec<Account>>> {
+        match self.group(conn)? {
+            Some(group) => Ok(Some(group.accounts)),
+            None => Ok(self.account(conn)?.map(|account| vec![account])),
+        }
+    }
+
+    /// Accounts of the group or the account given on the command line, or
+    /// the default account
+    pub fn accounts_or_default(&self, conn: &mut Conn) -> Result<Option<Vec<Account>>> {
+        match self.group(conn)? {
+            Some(group) => Ok(Some(group.accounts)),
+            None => Ok(self.account_or_default(conn)?.map(|account| vec![account])),
+        }
+    }
+
     pub fn default_account(&self, conn: &mut Conn) -> Result<Option<Account>> {
-        if let Some(account_name) = self.get("default_account")? {
+        let store = self.store()?;
+        if let Some(account_name) = store.get("default_account")? {
             match Account::find_by_name(conn, &account_name) {
                 Ok(entity) => Ok(Some(entity)),
                 Err(e) if e.is_not_found() => {
-                    self.reset("default_account")?;
+                    store.reset("default_account")?;
                     Ok(None)
                 }
                 Err(error) => Err(error.into()),
@@ -94,76 +189,183 @@ impl Config {
         }
     }
 
+    /// Currency most of the accounts and records are expected to be in, from
+    /// the `main_currency` setting
+    pub fn main_currency(&self) -> Result<Option<Currency>> {
+        let Some(code) = self.table.get("main_currency") else {
+            return Ok(None);
+        };
+        code.as_str()
+            .and_then(Currency::from_code)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Invalid main_currency in config.toml: {code}"))
+    }
+
+    /// Content of the config.toml file
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
     pub fn command(&self) -> Option<&Commands> {
         self.cli.command.as_ref()
     }
 
+    /// Path of the database, in the data directory, named after the
+    /// workspace's filename or the `db.filename` setting
     pub fn database_path(&self) -> PathBuf {
-        let db_filename = if let Some(db_table) = self.table.get("db").and_then(Value::as_table) {
-            db_table
-                .get("filename")
-                .and_then(Value::as_str)
-                .unwrap_or("db.finnel")
-        } else {
-            "db.finnel"
-        };
+        let filename = self
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.filename.as_deref());
+        self.data_dir
+            .join(filename.unwrap_or_else(|| default_filename(&self.table)))
+    }
 
-        self.data_dir.join(db_filename)
+    /// Path of the database of a workspace as if it was given with
+    /// --workspace
+    pub fn workspace_database_path(&self, workspace: &Workspace) -> PathBuf {
+        self.cli
+            .data
+            .clone()
+            .or_else(|| workspace.data_dir.clone())
+            .unwrap_or_else(|| default_data_dir(&self.table))
+            .join(
+                workspace
+                    .filename
+                    .as_deref()
+                    .unwrap_or_else(|| default_filename(&self.table)),
+            )
     }
 
     pub fn database(&self) -> Result<Database> {
         let mut conn = Database::open(self.database_path())?;
-        conn.setup()?;
-        Ok(conn)
+        match conn.setup() {
+            Ok(()) => {
+                self.apply_audit_setting(&mut conn)?;
+                Ok(conn)
+            }
+            Err(e) => Err(Self::database_error(e)),
+        }
     }
 
-    pub fn kvdir(&self) -> Result<PathBuf> {
-        let dir = self.dir.join("key_value_store");
+    /// Copy the `db/audit_enabled` setting to the database, where the library
+    /// checks it when records change
+    fn apply_audit_setting(&self, conn: &mut Conn) -> Result<()> {
+        let enabled = match self.store()?.scoped("db")?.get("audit_enabled")? {
+            Some(value) => value.trim().parse::<bool>().unwrap_or_else(|_| {
+                eprintln!("Warning: ignoring db/audit_enabled, expected true or false");
+                false
+            }),
+            None => false,
+        };
+        if history::is_enabled(conn)? != enabled {
+            history::set_enabled(conn, enabled)?;
+        }
+        Ok(())
+    }
 
-        if !dir.is_dir() {
-            std::fs::create_dir(&dir)?;
+    /// Open the database read-only and without migrating it, so that commands
+    /// only reading it leave the file untouched
+    ///
+    /// A database that doesn't exist yet is created as usual, there is
+    /// nothing to preserve.
+    pub fn database_readonly(&self) -> Result<Database> {
+        let path = self.database_path();
+        if !path.exists() {
+            return self.database();
         }
 
-        Ok(dir)
+        let mut conn = Database::open_readonly(path)?;
+        match conn.check() {
+            Ok(()) => Ok(conn),
+            Err(e) => Err(Self::database_error(e)),
+        }
     }
 
-    pub fn path(&self, key: &str) -> Result<PathBuf> {
-        let kvdir = self.kvdir()?;
-        let path = kvdir.join(key);
+    /// Open the database as [`Config::database_readonly`] does when the
+    /// command only reads it, as [`Config::database`] does otherwise
+    pub fn database_for(&self, command: &impl Readonly) -> Result<Database> {
+        if command.is_readonly() {
+            self.database_readonly()
+        } else {
+            self.database()
+        }
+    }
 
-        if let Some(parent) = path.parent() {
-            if !parent.is_dir() {
-                std::fs::create_dir_all(parent)?;
+    fn database_error(error: Error) -> anyhow::Error {
+        match error {
+            e @ Error::DatabaseFromFuture { .. } => {
+                anyhow!("{}\nPlease upgrade finnelctl to use this database", e)
             }
+            e => e.into(),
         }
-
-        Ok(path)
     }
 
-    pub fn get(&self, key: &str) -> Result<Option<String>> {
-        let path = self.path(key)?;
+    pub fn kvdir(&self) -> Result<PathBuf> {
+        let dir = self.dir.join("key_value_store");
 
-        match path.exists() {
-            true => Ok(Some(std::fs::read_to_string(self.path(key)?)?)),
-            false => Ok(None),
+        if !dir.is_dir() {
+            std::fs::create_dir(&dir)?;
         }
+
+        Ok(dir)
     }
 
-    pub fn set(&self, key: &str, value: &str) -> Result<()> {
-        std::fs::write(self.path(key)?, value)?;
-        Ok(())
+    /// Key-value store of the settings changed from the command line, kept
+    /// apart for each workspace
+    pub fn store(&self) -> Result<ConfigStore> {
+        let store = self.shared_store()?;
+        match &self.workspace {
+            Some(workspace) => store.scoped("workspaces")?.scoped(&workspace.name),
+            None => Ok(store),
+        }
     }
 
-    pub fn reset(&self, key: &str) -> Result<()> {
-        let path = self.path(key)?;
+    /// Save the workspace used when none is given with --workspace
+    pub fn set_default_workspace(&self, name: &str) -> Result<()> {
+        Workspace::find(&self.table, name)?;
+        self.shared_store()?.set(workspace::DEFAULT_KEY, name)
+    }
 
-        if path.exists() {
-            std::fs::remove_file(path)?;
+    /// Key-value store of the settings common to all the workspaces
+    pub fn shared_store(&self) -> Result<ConfigStore> {
+        Ok(ConfigStore::new(self.kvdir()?))
+    }
+}
+
+/// Workspace saved as the default one, ignored with a warning when it is no
+/// longer declared so that another one can still be set
+fn default_workspace(dir: &Path, table: &Table) -> Result<Option<Workspace>> {
+    let store = ConfigStore::new(dir.join("key_value_store"));
+    let Some(name) = store.get(workspace::DEFAULT_KEY)? else {
+        return Ok(None);
+    };
+    match Workspace::find(table, name.trim()) {
+        Ok(workspace) => Ok(Some(workspace)),
+        Err(e) => {
+            eprintln!("Warning: ignoring the default workspace. {e}");
+            Ok(None)
         }
-        Ok(())
     }
 }
 
+fn default_data_dir(table: &Table) -> PathBuf {
+    table
+        .get("data_dir")
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .unwrap_or_else(data_home)
+}
+
+fn default_filename(table: &Table) -> &str {
+    table
+        .get("db")
+        .and_then(Value::as_table)
+        .and_then(|db| db.get("filename"))
+        .and_then(Value::as_str)
+        .unwrap_or("db.finnel")
+}
+
 fn config_home() -> PathBuf {
     match std::env::var("FINNEL_CONFIG") {
         Ok(val) if !val.is_empty() => PathBuf::from(val),
@@ -236,6 +438,98 @@ mod tests {
         })
     }
 
+    #[test]
+    fn workspaces() -> Result<()> {
+        with_dirs(|confd, datad| {
+            let personal = datad.child("personal");
+            let other = datad.child("other");
+            create_dir(personal.path())?;
+            create_dir(other.path())?;
+            let path = |p: &assert_fs::fixture::ChildPath| p.path().to_str().unwrap().to_owned();
+            confd.child("config.toml").write_str(&format!(
+                "data_dir = '{}'\n\
+                 [workspaces]\n\
+                 personal = '{}'\n\
+                 association = {{ filename = 'association.finnel' }}\n",
+                datad.path().display(),
+                path(&personal),
+            ))?;
+            let parse = |args: &[&str]| Config::try_parse_from([&["arg0"], args].concat());
+
+            // Config defaults
+            let config = parse(&[])?;
+            assert_eq!(None, config.workspace);
+            assert_eq!(datad.child("db.finnel").path(), config.database_path());
+
+            let config = parse(&["--workspace", "association"])?;
+            assert_eq!("association", config.workspace.unwrap().name);
+            assert_eq!(datad.path(), config.data_dir);
+            let config = parse(&["-W", "personal"])?;
+            assert_eq!(personal.child("db.finnel").path(), config.database_path());
+
+            let error = parse(&["--workspace", "work"]).unwrap_err().to_string();
+            assert_eq!(
+                "Unknown workspace work, expected one of: association, personal",
+                error
+            );
+
+            config.set_default_workspace("personal")?;
+            assert!(config.set_default_workspace("work").is_err());
+            let config = parse(&[])?;
+            assert_eq!("personal", config.workspace.unwrap().name);
+
+            // -D replaces the default workspace, and only the data directory
+            // of the one given
+            let config = parse(&["-D", &path(&other)])?;
+            assert_eq!(None, config.workspace);
+            assert_eq!(other.child("db.finnel").path(), config.database_path());
+            let config = parse(&["-D", &path(&other), "--workspace", "association"])?;
+            assert_eq!(
+                other.child("association.finnel").path(),
+                config.database_path()
+            );
+
+            // A default workspace no longer declared is ignored
+            confd
+                .child("config.toml")
+                .write_str(&format!("data_dir = '{}'", datad.path().display()))?;
+            assert_eq!(None, parse(&[])?.workspace);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn workspaces_store() -> Result<()> {
+        with_dirs(|confd, _| {
+            confd.child("config.toml").write_str(
+                "[workspaces]\n\
+                 personal = { filename = 'personal.finnel' }\n\
+                 association = { filename = 'association.finnel' }\n",
+            )?;
+            let store = |workspace: Option<&str>| -> Result<ConfigStore> {
+                let mut args = vec!["arg0"];
+                args.extend(workspace.map(|name| ["--workspace", name]).iter().flatten());
+                Config::try_parse_from(args)?.store()
+            };
+
+            store(Some("personal"))?.set("default_account", "Cash")?;
+            store(None)?.set("default_account", "Bank")?;
+
+            assert_eq!(
+                Some("Cash".to_owned()),
+                store(Some("personal"))?.get("default_account")?
+            );
+            assert_eq!(None, store(Some("association"))?.get("default_account")?);
+            assert_eq!(
+                Some("Bank".to_owned()),
+                store(None)?.get("default_account")?
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn config_home_default() {
         temp_env::with_var("FINNEL_CONFIG", None::<&str>, || {
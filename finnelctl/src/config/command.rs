@@ -0,0 +1,40 @@
+use anyhow::Result;
+
+use super::Config;
+use crate::cli::config::Command;
+
+/// Expose the key-value store, mostly to debug the settings of the other
+/// commands
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    let store = config.store()?;
+
+    match command {
+        Command::List { scope } => {
+            let scoped = match scope.as_deref() {
+                Some(scope) => scope
+                    .split('/')
+                    .try_fold(store, |store, scope| store.scoped(scope))?,
+                None => store,
+            };
+            for key in scoped.list()? {
+                println!("{}", scoped.name(&key));
+            }
+        }
+        Command::Get { key } => {
+            let (store, key) = store.resolve(key)?;
+            if let Some(value) = store.get(key)? {
+                println!("{}", value);
+            }
+        }
+        Command::Set { key, value } => {
+            let (store, key) = store.resolve(key)?;
+            store.set(key, value)?;
+        }
+        Command::Reset { key } => {
+            let (store, key) = store.resolve(key)?;
+            store.reset(key)?;
+        }
+    }
+
+    Ok(())
+}
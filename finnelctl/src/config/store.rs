@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Key-value store kept as one file per key, where scopes are directories
+///
+/// Values are written to a temporary file renamed over the previous one, so
+/// concurrent invocations never read a partially written value.
+#[derive(Debug, Clone)]
+pub struct ConfigStore {
+    dir: PathBuf,
+    prefix: Vec<String>,
+}
+
+impl ConfigStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            prefix: Vec::new(),
+        }
+    }
+
+    /// Handle on the keys under the given scope, nested in this one
+    pub fn scoped(&self, prefix: &str) -> Result<Self> {
+        validate(prefix)?;
+
+        let mut scoped = self.clone();
+        scoped.prefix.push(prefix.to_owned());
+        Ok(scoped)
+    }
+
+    /// Handle on the store nested in the scopes of a `/` separated key, along
+    /// with the last segment of the key
+    pub fn resolve<'a>(&self, key: &'a str) -> Result<(Self, &'a str)> {
+        let mut segments = key.split('/');
+        let name = segments.next_back().unwrap_or_default();
+        let store = segments.try_fold(self.clone(), |store, scope| store.scoped(scope))?;
+        validate(name)?;
+
+        Ok((store, name))
+    }
+
+    /// Full name of the key, including the scopes
+    pub fn name(&self, key: &str) -> String {
+        self.prefix
+            .iter()
+            .map(String::as_str)
+            .chain([key])
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let path = self.path(key)?;
+
+        match path.is_file() {
+            true => Ok(Some(std::fs::read_to_string(path)?)),
+            false => Ok(None),
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        let path = self.path(key)?;
+        std::fs::create_dir_all(self.scope_dir())?;
+
+        let tmp = self
+            .scope_dir()
+            .join(format!(".{}.{}.tmp", key, std::process::id()));
+        std::fs::write(&tmp, value)?;
+        if let Err(e) = std::fs::rename(&tmp, &path) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&self, key: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keys set under this scope, including the ones of nested scopes as
+    /// `scope/key`, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        fn walk(dir: &Path, prefix: &str, keys: &mut Vec<String>) -> Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Values being written
+                if name.starts_with('.') {
+                    continue;
+                }
+
+                let key = format!("{}{}", prefix, name);
+                if entry.file_type()?.is_dir() {
+                    walk(&entry.path(), &format!("{}/", key), keys)?;
+                } else {
+                    keys.push(key);
+                }
+            }
+            Ok(())
+        }
+
+        let mut keys = Vec::new();
+        let dir = self.scope_dir();
+        if dir.is_dir() {
+            walk(&dir, "", &mut keys)?;
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn scope_dir(&self) -> PathBuf {
+        self.prefix
+            .iter()
+            .fold(self.dir.clone(), |dir, scope| dir.join(scope))
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate(key)?;
+        Ok(self.scope_dir().join(key))
+    }
+}
+
+/// Reject the keys and scopes which could point outside of their directory
+/// or clash with the temporary files
+fn validate(key: &str) -> Result<()> {
+    if key.is_empty() {
+        anyhow::bail!("Configuration key cannot be empty");
+    }
+    if key.contains(['/', '\\', std::path::MAIN_SEPARATOR]) {
+        anyhow::bail!("Configuration key cannot contain a path separator: {key}");
+    }
+    if key.starts_with('.') {
+        anyhow::bail!("Configuration key cannot start with a dot: {key}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn scoped() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let store = ConfigStore::new(dir.path().to_path_buf());
+        let records = store.scoped("records")?;
+
+        assert_eq!(None, records.get("default_sort")?);
+        records.set("default_sort", "date")?;
+        assert_eq!(Some("date".to_owned()), records.get("default_sort")?);
+        assert_eq!(None, store.get("default_sort")?);
+        assert_eq!(
+            "date",
+            std::fs::read_to_string(dir.child("records").child("default_sort").path())?
+        );
+
+        store.set("default_account", "Cash")?;
+        store
+            .scoped("accounts")?
+            .scoped("1")?
+            .set("atm_category", "2")?;
+        assert_eq!(vec!["default_sort"], records.list()?);
+        assert_eq!(
+            vec![
+                "accounts/1/atm_category",
+                "default_account",
+                "records/default_sort"
+            ],
+            store.list()?
+        );
+        assert!(store.scoped("unknown")?.list()?.is_empty());
+
+        let (accounts, key) = store.resolve("accounts/1/atm_category")?;
+        assert_eq!("atm_category", key);
+        assert_eq!(Some("2".to_owned()), accounts.get(key)?);
+        assert_eq!("accounts/1/atm_category", accounts.name(key));
+
+        records.reset("default_sort")?;
+        records.reset("default_sort")?;
+        assert_eq!(None, records.get("default_sort")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_keys() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let store = ConfigStore::new(dir.child("kv").path().to_path_buf());
+
+        assert!(store.get("../config.toml").is_err());
+        assert!(store.set("records/default_sort", "date").is_err());
+        assert!(store.set("..", "date").is_err());
+        assert!(store.scoped("..").is_err());
+        assert!(store.scoped("").is_err());
+        assert!(store.resolve("records/../../config.toml").is_err());
+        assert!(store.resolve("/etc/passwd").is_err());
+        assert!(store.resolve("records//default_sort").is_err());
+
+        Ok(())
+    }
+}
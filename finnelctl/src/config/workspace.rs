@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use toml::{Table, Value};
+
+/// Key of the store holding the workspace used when none is given
+pub const DEFAULT_KEY: &str = "default_workspace";
+
+/// Separate books kept side by side, declared in the `[workspaces]` table of
+/// config.toml
+///
+/// A workspace is either the path of its data directory:
+///
+/// ```toml
+/// [workspaces]
+/// personal = "/home/me/finnel"
+/// association = { data_dir = "/home/me/finnel", filename = "association.finnel" }
+/// ```
+///
+/// or a table with the data directory, the database filename, or both, the
+/// missing one being the same as without workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub name: String,
+    pub data_dir: Option<PathBuf>,
+    pub filename: Option<String>,
+}
+
+impl Workspace {
+    /// Workspaces declared in config.toml, sorted by name
+    pub fn all(table: &Table) -> Result<Vec<Self>> {
+        let Some(workspaces) = table.get("workspaces") else {
+            return Ok(Vec::new());
+        };
+        let workspaces = workspaces
+            .as_table()
+            .ok_or_else(|| anyhow!("Invalid workspaces in config.toml, expected a table"))?;
+
+        let mut all = workspaces
+            .iter()
+            .map(|(name, value)| Self::parse(name, value))
+            .collect::<Result<Vec<_>>>()?;
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(all)
+    }
+
+    pub fn find(table: &Table, name: &str) -> Result<Self> {
+        let all = Self::all(table)?;
+        if let Some(workspace) = all.iter().find(|w| w.name == name) {
+            return Ok(workspace.clone());
+        }
+
+        if all.is_empty() {
+            Err(anyhow!(
+                "Unknown workspace {name}, none is declared in the [workspaces] table of config.toml"
+            ))
+        } else {
+            let names = all.iter().map(|w| w.name.as_str()).collect::<Vec<_>>();
+            Err(anyhow!(
+                "Unknown workspace {name}, expected one of: {}",
+                names.join(", ")
+            ))
+        }
+    }
+
+    fn parse(name: &str, value: &Value) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid workspace {name} in config.toml, expected a data directory or a table with data_dir and filename"
+            )
+        };
+        // Names are used as a scope of the key-value store
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(anyhow!("Invalid workspace name in config.toml: {name:?}"));
+        }
+
+        let (data_dir, filename) = match value {
+            Value::String(dir) => (Some(PathBuf::from(dir)), None),
+            Value::Table(table) => {
+                let get = |key| match table.get(key) {
+                    Some(value) => value.as_str().map(Some).ok_or_else(invalid),
+                    None => Ok(None),
+                };
+                let data_dir = get("data_dir")?.map(PathBuf::from);
+                let filename = get("filename")?.map(str::to_owned);
+                if data_dir.is_none() && filename.is_none() {
+                    return Err(invalid());
+                }
+                (data_dir, filename)
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            name: name.to_owned(),
+            data_dir,
+            filename,
+        })
+    }
+}
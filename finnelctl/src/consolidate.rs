@@ -0,0 +1,76 @@
+use anyhow::Result;
+use serde_json::json;
+
+use finnel::{
+    category::{path::SEPARATOR, CategoryPaths},
+    consolidate::Deduplication,
+    prelude::*,
+};
+
+use crate::cli::consolidate::Arguments;
+use crate::config::Config;
+use crate::journal::Journal;
+
+pub fn run(config: &Config, args: &Arguments) -> Result<()> {
+    let conn = &mut config.database()?;
+    finnel::consolidate::consolidate(conn)?;
+
+    let mut merged = Vec::new();
+    if args.dedup_categories {
+        // Loaded first, as the parents may be merged too
+        let paths = CategoryPaths::load(conn)?;
+        let deduplication = finnel::consolidate::deduplicate_categories(conn, args.dry_run)?;
+        report(&deduplication, &paths, args.dry_run);
+        if !args.dry_run {
+            merged = deduplication
+                .merges
+                .iter()
+                .flat_map(|merge| merge.duplicates.iter().map(|c| c.id))
+                .collect();
+        }
+    }
+    Journal::new(config)?.log(
+        "consolidate",
+        json!({ "dedup_categories": args.dedup_categories, "dry_run": args.dry_run }),
+        merged,
+    );
+
+    Ok(())
+}
+
+fn report(deduplication: &Deduplication, paths: &CategoryPaths, dry_run: bool) {
+    let describe = |categories: &[Category]| {
+        categories
+            .iter()
+            .map(|c| {
+                // Names only differing by case already end with the id
+                let suffix = format!(" ({})", c.id);
+                let name = c.name.strip_suffix(&suffix).unwrap_or(&c.name);
+                match c.parent_id.and_then(|id| paths.get(id)) {
+                    Some(parent) => format!("{parent}{SEPARATOR}{name}{suffix}"),
+                    None => format!("{name}{suffix}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let verb = if dry_run { "Would merge" } else { "Merged" };
+    for merge in &deduplication.merges {
+        println!(
+            "{verb} {} into {}",
+            describe(&merge.duplicates),
+            describe(std::slice::from_ref(&merge.kept))
+        );
+    }
+    if deduplication.merges.is_empty() {
+        println!("No duplicate categories");
+    }
+
+    for group in &deduplication.ambiguous {
+        eprintln!(
+            "Skipped {}, which have different parents, use `category update --replace-by` to merge them",
+            describe(group)
+        );
+    }
+}
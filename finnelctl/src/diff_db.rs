@@ -0,0 +1,112 @@
+use anyhow::Result;
+
+use finnel::compare::{Comparison, Listing, Side};
+
+use crate::cli::diff_db::Arguments;
+use crate::config::Config;
+
+use tabled::builder::Builder as TableBuilder;
+
+/// Print how the database differs from the other one, section by section
+pub fn run(config: &Config, args: &Arguments) -> Result<()> {
+    let conn = &mut config.database()?;
+    let style = &config.style()?;
+    let mut comparison = Comparison::attach(conn, &args.other)?;
+    let limit = args.limit.into();
+
+    let other = args.other.display();
+    for (side, title) in [
+        (Side::This, "Records only in this database".to_owned()),
+        (Side::Other, format!("Records only in {}", other)),
+    ] {
+        section(
+            &title,
+            comparison.missing_records(side, limit)?,
+            |builder| {
+                table_push_row_elements!(
+                    builder, style;
+                    "account",
+                    "operation date",
+                    "amount",
+                    "details"
+                )
+            },
+            |builder, key| {
+                table_push_row_elements!(
+                    builder, style;
+                    key.account,
+                    key.operation_date,
+                    (key.amount(), key.direction),
+                    key.details
+                )
+            },
+        );
+    }
+
+    section(
+        "Entities with different attributes",
+        comparison.divergences(limit)?,
+        |builder| {
+            table_push_row_elements!(
+                builder, style;
+                "table",
+                "name",
+                "attribute",
+                "this",
+                "other"
+            )
+        },
+        |builder, divergence| {
+            table_push_row_elements!(
+                builder, style;
+                divergence.table,
+                divergence.name,
+                divergence.attribute,
+                divergence.this,
+                divergence.other
+            )
+        },
+    );
+
+    println!("Summary");
+    let mut builder = TableBuilder::new();
+    table_push_row_elements!(builder, style; "table", "this", "other");
+    for summary in comparison.summaries()? {
+        table_push_row_elements!(builder, style; summary.table, summary.this, summary.other);
+    }
+    for total in comparison.records_totals()? {
+        table_push_row_elements!(
+            builder, style;
+            format!("records total ({})", total.currency.code()),
+            total.this(),
+            total.other()
+        );
+    }
+    println!("{}", builder.build());
+
+    Ok(())
+}
+
+fn section<T, H, R>(title: &str, listing: Listing<T>, header: H, row: R)
+where
+    H: Fn(&mut TableBuilder),
+    R: Fn(&mut TableBuilder, T),
+{
+    println!("{} ({})", title, listing.count);
+    if listing.rows.is_empty() {
+        println!();
+        return;
+    }
+
+    let listed = listing.rows.len() as i64;
+    let mut builder = TableBuilder::new();
+    header(&mut builder);
+    for value in listing.rows {
+        row(&mut builder, value);
+    }
+    println!("{}", builder.build());
+    if listing.count > listed {
+        println!("... and {} more", listing.count - listed);
+    }
+    println!();
+}
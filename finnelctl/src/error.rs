@@ -0,0 +1,127 @@
+/// Exit code of the failures that don't belong to any class below
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Failures scripts may want to tell apart, each class exiting with its own
+/// code
+///
+/// They are raised as is for the failures detected by finnelctl itself, and
+/// found at the source of the other errors for the ones of finnel and of the
+/// file system, the message printed being the same either way.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    /// An account, category, merchant or other entity doesn't exist
+    NotFound(String),
+    /// The change would duplicate existing data, e.g. a name already in use
+    Conflict(String),
+    /// A value given or read is invalid
+    ValidationFailed(String),
+    /// The operation needs --confirm, or wasn't confirmed at the prompt
+    ConfirmationRequired(String),
+    /// The database or the import lock is held by another process, trying
+    /// again later should work
+    DatabaseBusy(String),
+    /// A file couldn't be read or written
+    Io(String),
+}
+
+impl CliError {
+    pub fn confirmation_required() -> Self {
+        Self::ConfirmationRequired("operation requires confirmation".to_string())
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::NotFound(_) => 4,
+            Self::Conflict(_) => 5,
+            Self::ValidationFailed(_) => 6,
+            Self::ConfirmationRequired(_) => 7,
+            // EX_IOERR and EX_TEMPFAIL from sysexits.h
+            Self::Io(_) => 74,
+            Self::DatabaseBusy(_) => 75,
+        }
+    }
+
+    /// Class of the error of finnel, if it has one
+    pub fn from_finnel(error: &finnel::Error) -> Option<Self> {
+        use finnel::Error::*;
+
+        let message = error.to_string();
+        Some(match error {
+            NotFound | ModelNotFound(_) | ModelNotFoundBy(..) => Self::NotFound(message),
+            NonUnique(_) | AlreadyExists { .. } => Self::Conflict(message),
+            Invalid(_)
+            | Parse(_)
+            | CurrencyError(_)
+            | InvalidMonth(..)
+            | InvalidWeek(..)
+            | InvalidRow(_)
+            | ReplacementCycle { .. } => Self::ValidationFailed(message),
+            DatabaseBusy => Self::DatabaseBusy(message),
+            _ => return None,
+        })
+    }
+
+    /// Class of the first error of the chain having one
+    pub fn find(error: &anyhow::Error) -> Option<Self> {
+        error.chain().find_map(|e| {
+            if let Some(error) = e.downcast_ref::<CliError>() {
+                Some(error.clone())
+            } else if let Some(error) = e.downcast_ref::<finnel::Error>() {
+                Self::from_finnel(error)
+            } else {
+                e.downcast_ref::<std::io::Error>()
+                    .map(|error| Self::Io(error.to_string()))
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(message)
+            | Self::Conflict(message)
+            | Self::ValidationFailed(message)
+            | Self::ConfirmationRequired(message)
+            | Self::DatabaseBusy(message)
+            | Self::Io(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Exit code of the error, from its class or the generic one
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    CliError::find(error).map_or(EXIT_FAILURE, |error| error.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::assert_eq;
+
+    #[test]
+    fn exit_codes() {
+        let code = |error: anyhow::Error| exit_code(&error);
+
+        assert_eq!(1, code(anyhow::anyhow!("No command provided")));
+        assert_eq!(4, code(finnel::Error::ModelNotFound("Account").into()));
+        assert_eq!(
+            5,
+            code(finnel::Error::NonUnique("UNIQUE constraint failed".to_string()).into())
+        );
+        assert_eq!(6, code(finnel::Error::Invalid("IBAN".to_string()).into()));
+        assert_eq!(7, code(CliError::confirmation_required().into()));
+        assert_eq!(75, code(finnel::Error::DatabaseBusy.into()));
+
+        // Found at the source of the error
+        let error = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(
+            74,
+            code(anyhow::Error::from(error).context("Unable to read statement.csv"))
+        );
+        let error = anyhow::Error::from(finnel::Error::NotFound).context("Record 12");
+        assert_eq!(4, code(error));
+    }
+}
@@ -1,13 +1,24 @@
-use std::collections::HashMap;
-use std::str::FromStr;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
 
+use crate::cli::account::ConfigurationKey as AccountConfigurationKey;
 use crate::cli::import::*;
 use crate::config::Config;
-
-use finnel::{category::NewCategory, merchant::NewMerchant, prelude::*, record::NewRecord};
-
-use anyhow::Result;
-use chrono::NaiveDate;
+use crate::journal::Journal;
+
+use finnel::{
+    account::QueryAccount,
+    category::{CompiledRules, NewCategory},
+    import::{Import, NewImport},
+    merchant::NewMerchant,
+    prelude::*,
+    record::NewRecord,
+};
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use tabled::builder::Builder as TableBuilder;
 
 mod profile;
@@ -16,20 +27,58 @@ use profile::{Information, Profile};
 mod options;
 use options::Options;
 
+mod lock;
+use lock::ImportLock;
+
+mod progress;
+use progress::Progress;
+
 mod boursobank;
 use boursobank::Boursobank;
+mod camt053;
+use camt053::Camt053;
 mod logseq;
 use logseq::Logseq;
+mod legacy;
 
 type MerchantWithDefaultCategory = (Merchant, Option<Category>);
 
+/// Tag added to the records of transfers between own accounts
+pub const INTERNAL_TAG: &str = "internal";
+
 pub struct Importer<'a> {
     options: Options<'a>,
     pub records: Vec<Record>,
     categories: HashMap<String, Category>,
     merchants: HashMap<String, MerchantWithDefaultCategory>,
+    /// Entities found by id, with the ones replacing them in the end, to
+    /// follow each chain of replacements only once
+    resolved_categories: HashMap<i64, Category>,
+    resolved_merchants: HashMap<i64, MerchantWithDefaultCategory>,
     conn: &'a mut Conn,
-    account: Account,
+    /// Account of the rows, None when they are only imported into the
+    /// accounts mapped to their account number
+    account: Option<Account>,
+    /// Accounts mapped to the account numbers, found once each
+    mapped_accounts: HashMap<String, Account>,
+    own_accounts: Vec<Account>,
+    /// Categories configured on the account for ATM withdrawals and fees
+    atm_category: Option<Category>,
+    fee_category: Option<Category>,
+    /// Category rules of the details, compiled once for all the records
+    rules: CompiledRules,
+    /// Record waiting for a possible fee row to merge, only when merging them
+    pending: Option<RecordToImport>,
+    skipped_zero_amount: usize,
+    skipped_out_of_range: usize,
+    /// Rows before the start date following the last import
+    skipped_duplicates: usize,
+    /// Rows of an account number not mapped to any account
+    skipped_unmapped: usize,
+    /// Rows left out with --skip-errors, reported at the end of the import
+    rejected: Vec<RejectedRow>,
+    /// Rows and files read, the imported and skipped ones are counted above
+    progress: progress::State,
 }
 
 #[derive(Default, Clone)]
@@ -37,43 +86,137 @@ pub struct RecordToImport {
     pub operation_date: NaiveDate,
     pub value_date: NaiveDate,
     pub amount: Decimal,
+    /// Currency of the amount, for the profiles finding it in the file
+    pub currency: Option<Currency>,
     pub direction: Direction,
     pub mode: Mode,
     pub details: String,
     pub category_name: String,
     pub merchant_name: String,
+    /// Transfer from or to another of our accounts
+    pub internal: bool,
+    /// Number of the account in the file, for the profiles listing the rows
+    /// of several accounts
+    pub account_number: Option<String>,
 }
 
-fn parse_date_fmt(date: &str, fmt: &str) -> Result<NaiveDate> {
-    Ok(NaiveDate::parse_from_str(date, fmt)?)
+impl RecordToImport {
+    fn signed_amount(&self) -> Decimal {
+        match self.direction {
+            Direction::Debit => -self.amount,
+            Direction::Credit => self.amount,
+        }
+    }
+
+    /// Add the amount of the fee to this record
+    fn merge_fee(&mut self, fee: &RecordToImport) {
+        let amount = self.signed_amount() + fee.signed_amount();
+        if amount.is_sign_negative() {
+            self.direction = Direction::Debit;
+        } else if !amount.is_zero() {
+            self.direction = Direction::Credit;
+        }
+        self.amount = amount.abs();
+    }
 }
 
-fn parse_decimal(number: &str) -> Result<Decimal> {
-    Ok(Decimal::from_str(
-        number.replace(",", ".").replace(" ", "").as_str(),
-    )?)
+/// Row that failed to import, left out with --skip-errors
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    /// Number of the row in the file, the header excluded
+    pub row: usize,
+    /// Row as read from the file
+    pub content: String,
+    pub error: String,
 }
 
-pub fn run(config: &Config, command: &Command) -> Result<()> {
+/// Summary of an import, printed as a single `key=value` line with
+/// `--porcelain`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Outcome {
+    pub imported: usize,
+    /// Rows left out because of their zero amount, their date or their
+    /// unmapped account number
+    pub skipped: usize,
+    /// Rows left out as already imported, i.e. before the day following the
+    /// last import
+    pub duplicates: usize,
+    /// Rows rejected with `--skip-errors`
+    pub errors: usize,
+    /// Operation dates of the oldest and newest imported records
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Records imported, for the operations journal
+    pub ids: Vec<i64>,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = |date: Option<NaiveDate>| date.map(|d| d.to_string()).unwrap_or_default();
+        write!(
+            f,
+            "imported={} skipped={} duplicates={} errors={} from={} to={}",
+            self.imported,
+            self.skipped,
+            self.duplicates,
+            self.errors,
+            date(self.from),
+            date(self.to)
+        )
+    }
+}
+
+/// Import the records, returning the outcome unless only configuring the
+/// profile
+pub fn run(config: &Config, command: &Command) -> Result<Option<Outcome>> {
     let conn = &mut config.database()?;
 
-    let options = Options::try_from(command, config)?;
+    let mut options = Options::try_from(command, config)?;
 
     if options.has_configuration_action() {
         options.configure(conn)?;
-        return Ok(());
+        return Ok(None);
+    }
+    if let Some(Action::History {}) = command.action {
+        history(conn, &options)?;
+        return Ok(None);
     }
 
+    // Held until the end of the import so that another one can't interleave
+    // its records with ours
+    let _lock = ImportLock::acquire(&config.data_dir, command.wait.map(Duration::from_secs))?;
+    let arguments = json!({
+        "profile": options.profile_info.name()?,
+        "file": options.file()?,
+    });
+    let outcome = if options.profile_info == Information::LegacyFinnel {
+        legacy::run(conn, command, &options)?
+    } else {
+        options.progress = Some(Box::new(progress::Reporter::new()));
+        import(conn, command, options)?
+    };
+    Journal::new(config)?.log("import", arguments, outcome.ids.iter().copied());
+
+    Ok(Some(outcome))
+}
+
+fn import(conn: &mut Conn, command: &Command, options: Options) -> Result<Outcome> {
     conn.transaction(|conn| {
-        let Importer {
-            records,
-            options,
-            categories,
-            merchants,
-            ..
-        } = {
+        let (
+            outcome,
+            hash,
+            Importer {
+                records,
+                options,
+                categories,
+                merchants,
+                conn,
+                ..
+            },
+        ) = {
             let mut importer = Importer::new(conn, options)?;
-            importer.run().map(|_| importer)
+            let hash = importer.check_journal(command.force)?;
+            importer.run().map(|_| (importer.outcome(), hash, importer))
         }?;
 
         let mut categories_by_id = categories
@@ -92,10 +235,33 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
             })
             .collect::<HashMap<i64, &Merchant>>();
 
+        let dates = records
+            .iter()
+            .filter_map(|record| record.account_id.map(|id| (id, record.operation_date)))
+            .collect::<Vec<_>>();
+
+        // Cached stats of the months the records enter, left to the import
+        let months = records
+            .iter()
+            .map(|r| {
+                (
+                    r.operation_date.year(),
+                    r.operation_date.month() as i32,
+                    r.currency,
+                )
+            })
+            .collect::<BTreeSet<_>>();
+        for (year, month, currency) in months {
+            finnel::stats::MonthlyStats::invalidate(conn, year, month, currency)?;
+        }
+
         if options.print {
+            let mut style = options.config.style()?;
+            style.load_category_paths(conn)?;
             let mut builder = TableBuilder::new();
             table_push_row!(
                 builder,
+                &style,
                 std::marker::PhantomData::<(Record, Option<Category>, Option<Merchant>)>
             );
 
@@ -103,47 +269,449 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
                 let category = record.category_id.as_ref().map(|id| categories_by_id[id]);
                 let merchant = record.merchant_id.as_ref().map(|id| merchants_by_id[id]);
 
-                table_push_row!(builder, (record, category, merchant));
+                table_push_row!(builder, &style, (record, category, merchant));
             }
             println!("{}", builder.build());
         }
 
+        if let Some(hash) = &hash {
+            NewImport {
+                count: outcome.imported as i64,
+                ..NewImport::new(options.profile_info.name()?, hash)
+            }
+            .save(conn)?;
+        }
+
+        if command.porcelain {
+            println!("{outcome}");
+        }
+
         if options.pretend {
             anyhow::bail!("No records were saved as we are pretending");
         }
+        // Only once imported, the rows of a failed import aren't duplicates
+        if outcome.to.is_some() {
+            options.set_last_imported(outcome.to)?;
+        }
+        crate::account::warn_balance_assertions(conn, dates)?;
 
-        Ok(())
+        Ok(outcome)
     })
 }
 
+/// Hash of the imported file for the imports journal, or None when importing
+/// a whole directory which isn't recorded
+fn file_hash(path: &std::path::Path) -> Result<Option<String>> {
+    if path.is_dir() {
+        log::info!(
+            "{} is a directory, not recorded in the imports journal",
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    // Read in chunks rather than entirely in memory
+    let mut hasher = Sha256::new();
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let mut buffer = [0; 8192];
+    loop {
+        let read = std::io::Read::read(&mut file, &mut buffer)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Ok(Some(hash))
+}
+
+/// Hash of the file to record in the imports journal, failing if it was
+/// already imported with the profile unless forced
+fn check_journal(conn: &mut Conn, options: &Options, force: bool) -> Result<Option<String>> {
+    let file = options.file()?;
+    let Some(hash) = file_hash(&file)? else {
+        return Ok(None);
+    };
+
+    let profile = options.profile_info.name()?;
+    let previous = match Import::find_by_hash(conn, profile, &hash) {
+        Ok(previous) => Some(previous),
+        Err(e) if e.is_not_found() => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(previous) = previous {
+        let message = format!(
+            "{} was already imported with profile {} on {} ({} records)",
+            file.display(),
+            profile,
+            previous.imported_at.format("%Y-%m-%d %H:%M:%S"),
+            previous.count
+        );
+        if !force {
+            anyhow::bail!("{message}, use --force to import it again");
+        }
+        eprintln!("Warning: {message}");
+    }
+
+    Ok(Some(hash))
+}
+
+/// File receiving the rejected rows, `statement.csv` giving
+/// `statement.rejected.csv`
+fn rejected_path(path: &std::path::Path) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or(path.as_os_str());
+    let mut name = stem.to_os_string();
+    name.push(".rejected.csv");
+    path.with_file_name(name)
+}
+
+fn history(conn: &mut Conn, options: &Options) -> Result<()> {
+    let style = &options.config.style()?;
+    let mut builder = TableBuilder::new();
+    table_push_row_elements!(builder, style; "id", "imported at", "records", "sha-256");
+
+    for import in Import::of_profile(conn, options.profile_info.name()?)? {
+        table_push_row_elements!(
+            builder, style;
+            import.id,
+            import.imported_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            import.count,
+            import.hash
+        );
+    }
+
+    println!("{}", builder.build());
+
+    Ok(())
+}
+
 impl<'a> Importer<'a> {
     fn new(conn: &'a mut Conn, options: Options<'a>) -> Result<Self> {
+        let account = match options.account(conn) {
+            Ok((account, source)) => {
+                eprintln!("Importing into account {} (source: {source})", account.name);
+                Some(account)
+            }
+            // The rows go to the accounts mapped to their account number
+            Err(e) if !options.account_map.is_empty() => {
+                eprintln!("Importing only into the mapped accounts: {e}");
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        if let (Some(account), Some(main_currency)) = (&account, options.config.main_currency()?) {
+            if account.currency != main_currency {
+                eprintln!(
+                    "Warning: importing into account {} in {}, not in the main currency {}",
+                    account.name,
+                    account.currency.code(),
+                    main_currency.code()
+                );
+            }
+        }
+        let own_accounts = QueryAccount::default()
+            .run(conn)?
+            .into_iter()
+            .filter(|a| Some(a.id) != account.as_ref().map(|account| account.id))
+            .collect();
+
+        let mut setting = |key| match &account {
+            Some(account) => crate::account::category_setting(options.config, conn, account, key),
+            None => Ok(None),
+        };
+        let atm_category = setting(AccountConfigurationKey::AtmCategory)?;
+        let fee_category = setting(AccountConfigurationKey::FeeCategory)?;
+
+        let rules = CompiledRules::load(conn)?;
+        for invalid in &rules.invalid {
+            eprintln!("Warning: {invalid}, ignored by the import");
+        }
+
+        // Make them available by name like the categories found while importing
+        let categories = atm_category
+            .iter()
+            .chain(fee_category.iter())
+            .map(|category| (category.name.clone(), category.clone()))
+            .collect();
+
         Ok(Importer {
-            account: options.account(conn)?,
+            account,
+            mapped_accounts: Default::default(),
+            own_accounts,
+            atm_category,
+            fee_category,
+            rules,
             options,
             records: Default::default(),
-            categories: Default::default(),
+            categories,
             merchants: Default::default(),
+            resolved_categories: Default::default(),
+            resolved_merchants: Default::default(),
             conn,
+            pending: None,
+            skipped_zero_amount: 0,
+            skipped_out_of_range: 0,
+            skipped_duplicates: 0,
+            skipped_unmapped: 0,
+            rejected: Vec::new(),
+            progress: Default::default(),
         })
     }
 
     fn run(&mut self) -> Result<()> {
-        self.options.new_profile()?.run(self)
+        let mut profile = self.options.new_profile()?;
+        self.progress.total = profile.total();
+        if let Some(names) = profile.scan(self)? {
+            self.prefetch(&names)?;
+        }
+        profile.run(self)?;
+        self.finish()?;
+
+        if !self.rejected.is_empty() {
+            self.report_rejected(profile.header())?;
+        }
+        Ok(())
+    }
+
+    /// Save the record still pending and print the summary of skipped rows
+    fn finish(&mut self) -> Result<()> {
+        if let Some(import) = self.pending.take() {
+            self.save_record(import)?;
+        }
+
+        let state = self.progress_state();
+        if let Some(progress) = self.options.progress.as_mut() {
+            progress.finish(&state);
+        }
+
+        if self.skipped_zero_amount > 0 {
+            println!("Skipped {} zero-amount rows", self.skipped_zero_amount);
+        }
+        if self.skipped_unmapped > 0 {
+            println!(
+                "Skipped {} rows of unmapped account numbers",
+                self.skipped_unmapped
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Leave out the row failing to import with --skip-errors, or fail with
+    /// its error otherwise
+    fn reject_row(&mut self, content: String, error: anyhow::Error) -> Result<()> {
+        if !self.options.skip_errors {
+            return Err(error);
+        }
+
+        self.progress.rows += 1;
+        self.rejected.push(RejectedRow {
+            row: self.progress.rows,
+            content,
+            error: format!("{error:#}"),
+        });
+        self.tick();
+        Ok(())
+    }
+
+    /// Print the rejected rows and write them, after the header if any, to
+    /// a file next to the imported one to fix them by hand, unless pretending
+    fn report_rejected(&self, header: Option<String>) -> Result<()> {
+        let style = &self.options.config.style()?;
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, style; "row", "error", "content");
+        for rejected in &self.rejected {
+            table_push_row_elements!(
+                builder, style;
+                rejected.row.to_string(),
+                rejected.error,
+                rejected.content
+            );
+        }
+        println!("Rejected {} rows", self.rejected.len());
+        println!("{}", builder.build());
+        if self.options.pretend {
+            return Ok(());
+        }
+
+        let path = rejected_path(&self.options.file()?);
+        let mut content = header.map(|header| header + "\n").unwrap_or_default();
+        for rejected in &self.rejected {
+            content += &rejected.content;
+            content += "\n";
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Unable to write {}", path.display()))?;
+        println!("Rejected rows written to {}", path.display());
+
+        Ok(())
+    }
+
+    fn outcome(&self) -> Outcome {
+        let dates = self.records.iter().map(|record| record.operation_date);
+        Outcome {
+            imported: self.records.len(),
+            skipped: self.skipped(),
+            duplicates: self.skipped_duplicates,
+            errors: self.rejected.len(),
+            from: dates.clone().min(),
+            to: dates.max(),
+            ids: self.records.iter().map(|record| record.id).collect(),
+        }
+    }
+
+    fn skipped(&self) -> usize {
+        self.skipped_zero_amount + self.skipped_out_of_range + self.skipped_unmapped
+    }
+
+    fn progress_state(&self) -> progress::State {
+        progress::State {
+            imported: self.records.len(),
+            skipped: self.skipped() + self.skipped_duplicates,
+            ..self.progress.clone()
+        }
+    }
+
+    fn tick(&mut self) {
+        let state = self.progress_state();
+        if let Some(progress) = self.options.progress.as_mut() {
+            progress.tick(&state);
+        }
+    }
+
+    /// Count a file of the directory as read, for the profiles importing one
+    fn file_read(&mut self) {
+        self.progress.files += 1;
+        self.tick();
     }
 
     fn add_record(&mut self, import: RecordToImport) -> Result<Option<&Record>> {
+        // Ticked before handling the row as the record returned borrows self
+        self.progress.rows += 1;
+        self.tick();
+
         if let Some(date) = self.options.from {
             if import.operation_date < date {
+                match self.options.from_last_imported {
+                    true => self.skipped_duplicates += 1,
+                    false => self.skipped_out_of_range += 1,
+                }
                 return Ok(None);
             }
         }
         if let Some(date) = self.options.to {
             if import.operation_date > date {
+                self.skipped_out_of_range += 1;
                 return Ok(None);
             }
         }
 
+        if self.options.skip_zero_amount && import.amount.is_zero() {
+            self.skipped_zero_amount += 1;
+            return Ok(None);
+        }
+
+        let Some(account) = self.row_account(&import)? else {
+            self.skipped_unmapped += 1;
+            return Ok(None);
+        };
+
+        if let Some(currency) = import.currency.filter(|c| *c != account.currency) {
+            let message = format!(
+                "row {}: {} record into {} account",
+                self.progress.rows,
+                currency.code(),
+                account.currency.code()
+            );
+            if !self.options.coerce_currency {
+                anyhow::bail!(message);
+            }
+            eprintln!(
+                "Warning: {message}, imported in {}",
+                account.currency.code()
+            );
+        }
+
+        if let Err(e) = self.options.max_amount.check(import.amount) {
+            let message = format!("row {}: {e}", self.progress.rows);
+            if !self.options.allow_large_amounts {
+                anyhow::bail!("{message}, use --allow-large-amounts to import it anyway");
+            }
+            eprintln!("Warning: {message}");
+        }
+
+        if !self.options.merge_fee_rows {
+            return self.save_record(import).map(Some);
+        }
+
+        // Keep the record pending until we know the next row isn't a fee to merge into it
+        match self.pending.take() {
+            Some(mut pending)
+                if pending.value_date == import.value_date
+                    && pending.account_number == import.account_number
+                    && self.options.is_fee(&import.details) =>
+            {
+                pending.merge_fee(&import);
+                self.pending = Some(pending);
+                Ok(None)
+            }
+            previous => {
+                self.pending = Some(import);
+                previous.map(|import| self.save_record(import)).transpose()
+            }
+        }
+    }
+
+    /// Account to import the row into, the one mapped to its account number
+    /// if there is a map, None when the number isn't mapped
+    fn row_account(&mut self, import: &RecordToImport) -> Result<Option<Account>> {
+        let number = import
+            .account_number
+            .as_deref()
+            .filter(|_| !self.options.account_map.is_empty());
+        let Some(number) = number else {
+            return self
+                .account
+                .clone()
+                .map(Some)
+                .ok_or(anyhow::anyhow!("Account not provided"));
+        };
+
+        if let Some(account) = self.mapped_accounts.get(number) {
+            return Ok(Some(account.clone()));
+        }
+        let Some(name) = self.options.mapped_account(number) else {
+            if self.options.strict_accounts {
+                anyhow::bail!(
+                    "row {}: account number {number} is not mapped, \
+                     use --map-account {number}=ACCOUNT to import it",
+                    self.progress.rows
+                );
+            }
+            return Ok(None);
+        };
+
+        let account = Account::find_by_name(self.conn, name)?;
+        self.options.check_currency(&account)?;
+        eprintln!(
+            "Importing account number {number} into account {}",
+            account.name
+        );
+        self.mapped_accounts
+            .insert(number.to_owned(), account.clone());
+        Ok(Some(account))
+    }
+
+    fn save_record(&mut self, import: RecordToImport) -> Result<&Record> {
+        let account = self
+            .row_account(&import)?
+            .ok_or(anyhow::anyhow!("Account not provided"))?;
         // rust doesn't look into the functions to ascertain we can do something or not, so
         // calling get_category/get_merchant here instead makes the borrow checker unhappy
         // error[E0502]: cannot borrow `*self` as immutable because it is also borrowed as mutable
@@ -173,9 +741,15 @@ impl<'a> Importer<'a> {
                 details: import.details.as_str(),
                 category,
                 merchant,
-                ..NewRecord::new(&self.account)
+                // Some banks do give an earlier value date
+                allow_inverted_dates: true,
+                rules: Some(&self.rules),
+                ..NewRecord::new(&account)
             }
-            .save(self.conn)?,
+            .into_resolved(self.conn)?
+            .validate(self.conn)?
+            // Forgotten once per month by the import
+            .save_keeping_stats(self.conn)?,
         );
 
         let record = self
@@ -183,10 +757,45 @@ impl<'a> Importer<'a> {
             .last()
             .ok_or(anyhow::anyhow!("No last record?"))?;
 
-        self.options
-            .set_last_imported(Some(record.operation_date))?;
+        if import.internal {
+            let tag = finnel::tag::Tag::find_or_create(self.conn, INTERNAL_TAG)?;
+            record.add_tag(self.conn, &tag)?;
+        }
+
+        Ok(record)
+    }
+
+    /// Find another of our accounts whose IBAN or name appears in one of the
+    /// texts, preferring the IBAN then the longest name appearing as whole
+    /// words
+    fn find_own_account(&self, texts: &[&str]) -> Option<&Account> {
+        self.own_accounts
+            .iter()
+            .find(|account| texts.iter().any(|text| account.iban_in(text)))
+            .or_else(|| {
+                self.own_accounts
+                    .iter()
+                    .filter(|account| texts.iter().any(|text| contains_words(text, &account.name)))
+                    .max_by_key(|account| account.name.len())
+            })
+    }
 
-        Ok(Some(record))
+    /// Hash of the file to import, failing if the same content was already
+    /// imported with this profile unless forced
+    fn check_journal(&mut self, force: bool) -> Result<Option<String>> {
+        check_journal(self.conn, &self.options, force)
+    }
+
+    /// Category configured on the account for fees or ATM withdrawals, when
+    /// the record is one of them
+    fn account_category(&self, mode: &Mode, details: &str) -> Option<&Category> {
+        if self.options.is_fee(details) {
+            self.fee_category.as_ref()
+        } else if matches!(mode, Mode::Atm(_)) {
+            self.atm_category.as_ref()
+        } else {
+            None
+        }
     }
 
     #[allow(dead_code)]
@@ -206,14 +815,34 @@ impl<'a> Importer<'a> {
                 Err(e) => return Err(e.into()),
             };
 
-            let category = category.resolve(self.conn)?;
-
+            let category = self.resolve_category(category)?;
             self.categories.insert(name.to_string(), category);
         }
 
         Ok(())
     }
 
+    /// Category replacing this one in the end, stopping at the first one
+    /// of the chain already resolved
+    fn resolve_category(&mut self, mut category: Category) -> Result<Category> {
+        let mut ids = Vec::new();
+        let resolved = loop {
+            if let Some(resolved) = self.resolved_categories.get(&category.id) {
+                break resolved.clone();
+            }
+            ids.push(category.id);
+            match category.replaced_by_id {
+                Some(id) => category = Category::find(self.conn, id)?,
+                None => break category,
+            }
+        };
+
+        for id in ids {
+            self.resolved_categories.insert(id, resolved.clone());
+        }
+        Ok(resolved)
+    }
+
     fn get_merchant(&self, name: &str) -> Option<&MerchantWithDefaultCategory> {
         if name.is_empty() {
             None
@@ -224,27 +853,105 @@ impl<'a> Importer<'a> {
 
     fn add_merchant(&mut self, name: &str) -> Result<()> {
         if !name.is_empty() && !self.merchants.contains_key(name) {
-            let merchant = match Merchant::find_by_name(self.conn, name) {
+            let merchant = match Merchant::find_by_name_or_alias(self.conn, name) {
                 Ok(merchant) => merchant,
                 Err(e) if e.is_not_found() => NewMerchant::new(name).save(self.conn)?,
                 Err(e) => return Err(e.into()),
             };
 
-            let merchant = merchant.resolve(self.conn)?;
-            let default_category = merchant.fetch_default_category(self.conn)?;
+            let merchant = self.resolve_merchant(merchant)?;
+            self.merchants.insert(name.to_string(), merchant);
+        }
 
-            self.merchants
-                .insert(name.to_string(), (merchant, default_category));
+        Ok(())
+    }
+
+    /// Merchant replacing this one in the end, with the category replacing
+    /// its default category in the end, stopping at the first one of the
+    /// chain already resolved
+    fn resolve_merchant(&mut self, mut merchant: Merchant) -> Result<MerchantWithDefaultCategory> {
+        let mut ids = Vec::new();
+        let resolved = loop {
+            if let Some(resolved) = self.resolved_merchants.get(&merchant.id) {
+                break resolved.clone();
+            }
+            ids.push(merchant.id);
+            match merchant.replaced_by_id {
+                Some(id) => merchant = Merchant::find(self.conn, id)?,
+                None => {
+                    // Replaced since it was set, unless the merchants were
+                    // consolidated in between
+                    let default_category = match merchant.fetch_default_category(self.conn)? {
+                        Some(category) => Some(self.resolve_category(category)?),
+                        None => None,
+                    };
+                    break (merchant, default_category);
+                }
+            }
+        };
+
+        for id in ids {
+            self.resolved_merchants.insert(id, resolved.clone());
+        }
+        Ok(resolved)
+    }
+
+    /// Find the categories and merchants named in the file at once, instead
+    /// of one name at a time while running. The ones not found are left to
+    /// be created when the rows using them are imported.
+    fn prefetch(&mut self, names: &profile::Names) -> Result<()> {
+        let categories = Category::find_all_by_name(
+            self.conn,
+            names
+                .categories
+                .iter()
+                .filter(|name| !self.categories.contains_key(*name))
+                .map(String::as_str),
+        )?;
+        for (name, category) in categories {
+            let category = self.resolve_category(category)?;
+            self.categories.insert(name, category);
+        }
+
+        let merchants = Merchant::find_all_by_name_or_alias(
+            self.conn,
+            names
+                .merchants
+                .iter()
+                .filter(|name| !self.merchants.contains_key(*name))
+                .map(String::as_str),
+        )?;
+        for (name, merchant) in merchants {
+            let merchant = self.resolve_merchant(merchant)?;
+            self.merchants.insert(name, merchant);
         }
 
         Ok(())
     }
 }
 
+/// Whether the words appear in the text, ignoring case, not as part of
+/// longer words
+fn contains_words(text: &str, words: &str) -> bool {
+    let text = text.to_lowercase();
+    let words = words.to_lowercase();
+    if words.is_empty() {
+        return false;
+    }
+
+    text.match_indices(&words).any(|(start, _)| {
+        let end = start + words.len();
+        !text[..start].ends_with(char::is_alphanumeric)
+            && !text[end..].starts_with(char::is_alphanumeric)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test::prelude::{assert_eq, *};
+    use crate::utils::max_amount::MaxAmount;
+    use finnel::parse;
 
     pub fn with_default_importer<F, R>(function: F) -> Result<R>
     where
@@ -276,7 +983,7 @@ mod tests {
     fn add_record() -> Result<()> {
         with_default_importer(|importer| {
             let conn = &mut importer.options.config.database()?;
-            let account_id = importer.options.account(conn)?.id;
+            let account_id = importer.options.account(conn)?.0.id;
 
             let date = chrono::Utc::now().date_naive();
             let mut record_to_import = RecordToImport {
@@ -294,7 +1001,7 @@ mod tests {
             // the category and merchant were not added to the importer first so it doesn't work
             assert!(record.category_id.is_none());
             assert!(record.merchant_id.is_none());
-            assert_eq!(account_id, record.account_id);
+            assert_eq!(Some(account_id), record.account_id);
 
             let restaurant = test::category!(conn, "restaurant");
             let bar = test::category!(conn, "bar");
@@ -315,9 +1022,20 @@ mod tests {
             record_to_import.category_name = String::new();
 
             // Use merchant's default_category by default
-            let record = importer.add_record(record_to_import)?.unwrap();
+            let record = importer.add_record(record_to_import.clone())?.unwrap();
+            assert_eq!(Some(bar.id), record.category_id);
+
+            // Then the rules matching the details
+            finnel::category::NewCategoryRule::details("world", &restaurant).save(conn)?;
+            // The rules are compiled once when the import starts
+            importer.rules = CompiledRules::load(conn)?;
+            let record = importer.add_record(record_to_import.clone())?.unwrap();
             assert_eq!(Some(bar.id), record.category_id);
 
+            record_to_import.merchant_name = String::new();
+            let record = importer.add_record(record_to_import)?.unwrap();
+            assert_eq!(Some(restaurant.id), record.category_id);
+
             Ok(())
         })
     }
@@ -326,14 +1044,14 @@ mod tests {
     fn add_record_from_to() -> Result<()> {
         with_config(|config| {
             let options = Options {
-                from: Some(parse_date_fmt("2024-07-01", "%Y-%m-%d")?),
-                to: Some(parse_date_fmt("2024-07-31", "%Y-%m-%d")?),
+                from: Some(parse::date("2024-07-01", "%Y-%m-%d")?),
+                to: Some(parse::date("2024-07-31", "%Y-%m-%d")?),
                 profile_info: Information::Boursobank,
                 ..Options::new(config)
             };
 
             with_importer(options, |importer| {
-                let date = parse_date_fmt("2024-06-30", "%Y-%m-%d")?;
+                let date = parse::date("2024-06-30", "%Y-%m-%d")?;
 
                 let mut record_to_import = RecordToImport {
                     amount: Decimal::new(314, 2),
@@ -347,19 +1065,282 @@ mod tests {
 
                 assert!(importer.add_record(record_to_import.clone())?.is_none());
 
-                record_to_import.operation_date = parse_date_fmt("2024-08-01", "%Y-%m-%d")?;
+                record_to_import.operation_date = parse::date("2024-08-01", "%Y-%m-%d")?;
                 assert!(importer.add_record(record_to_import.clone())?.is_none());
 
-                record_to_import.operation_date = parse_date_fmt("2024-07-01", "%Y-%m-%d")?;
-                assert!(importer.add_record(record_to_import)?.is_some());
+                record_to_import.operation_date = parse::date("2024-07-01", "%Y-%m-%d")?;
+                assert!(importer.add_record(record_to_import.clone())?.is_some());
+                assert_eq!(2, importer.outcome().skipped);
+                assert_eq!(0, importer.outcome().duplicates);
 
-                assert!(importer.options.last_imported()?.is_some());
+                // Already imported when starting after the last import
+                importer.options.from_last_imported = true;
+                record_to_import.operation_date = date;
+                assert!(importer.add_record(record_to_import)?.is_none());
+                assert_eq!(2, importer.outcome().skipped);
+                assert_eq!(1, importer.outcome().duplicates);
 
                 Ok(())
             })
         })
     }
 
+    #[test]
+    fn add_record_skip_zero_amount() -> Result<()> {
+        with_default_importer(|importer| {
+            let date = chrono::Utc::now().date_naive();
+            let record_to_import = RecordToImport {
+                operation_date: date,
+                value_date: date,
+                details: "Card check".to_string(),
+                ..Default::default()
+            };
+
+            assert!(importer.add_record(record_to_import.clone())?.is_none());
+            assert!(importer.add_record(record_to_import.clone())?.is_none());
+            assert_eq!(2, importer.skipped_zero_amount);
+            assert!(importer.records.is_empty());
+
+            importer.options.skip_zero_amount = false;
+            assert!(importer.add_record(record_to_import)?.is_some());
+            assert_eq!(2, importer.skipped_zero_amount);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn add_record_currency() -> Result<()> {
+        with_default_importer(|importer| {
+            let date = parse::date("2024-07-01", "%Y-%m-%d")?;
+            let record_to_import = RecordToImport {
+                amount: Decimal::new(314, 2),
+                operation_date: date,
+                value_date: date,
+                currency: Some(Currency::EUR),
+                ..Default::default()
+            };
+            assert!(importer.add_record(record_to_import.clone())?.is_some());
+
+            let dollars = RecordToImport {
+                currency: Some(Currency::USD),
+                ..record_to_import
+            };
+            assert_eq!(
+                "row 2: USD record into EUR account",
+                importer
+                    .add_record(dollars.clone())
+                    .unwrap_err()
+                    .to_string()
+            );
+            assert_eq!(1, importer.records.len());
+
+            importer.options.coerce_currency = true;
+            let record = importer.add_record(dollars)?.unwrap();
+            assert_eq!(Currency::EUR, record.currency);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn add_record_max_amount() -> Result<()> {
+        with_default_importer(|importer| {
+            importer.options.max_amount = MaxAmount(Some(Decimal::new(1000, 0)));
+
+            let date = parse::date("2024-07-01", "%Y-%m-%d")?;
+            let at_max = RecordToImport {
+                amount: Decimal::new(1000, 0),
+                operation_date: date,
+                value_date: date,
+                ..Default::default()
+            };
+            assert!(importer.add_record(at_max.clone())?.is_some());
+
+            let above = RecordToImport {
+                amount: Decimal::new(100001, 2),
+                ..at_max
+            };
+            assert_eq!(
+                "row 2: amount 1000.01 is above records/max_amount 1000, \
+                 use --allow-large-amounts to import it anyway",
+                importer.add_record(above.clone()).unwrap_err().to_string()
+            );
+            assert_eq!(1, importer.records.len());
+
+            importer.options.allow_large_amounts = true;
+            assert!(importer.add_record(above)?.is_some());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn add_record_merge_fee() -> Result<()> {
+        with_default_importer(|importer| {
+            importer.options.merge_fee_rows = true;
+
+            let date = parse::date("2024-07-01", "%Y-%m-%d")?;
+            let purchase = RecordToImport {
+                amount: Decimal::new(2000, 2),
+                operation_date: date,
+                value_date: date,
+                details: "Shop abroad".to_string(),
+                ..Default::default()
+            };
+            let fee = RecordToImport {
+                amount: Decimal::new(50, 2),
+                details: "FRAIS CARTE HORS ZONE EURO".to_string(),
+                ..purchase.clone()
+            };
+
+            // The purchase is kept pending and the fee merged into it
+            assert!(importer.add_record(purchase)?.is_none());
+            assert!(importer.add_record(fee)?.is_none());
+            assert!(importer.records.is_empty());
+
+            importer.finish()?;
+            assert_eq!(1, importer.records.len());
+            let record = &importer.records[0];
+            assert_eq!(Decimal::new(2050, 2), record.amount);
+            assert_eq!(Direction::Debit, record.direction);
+            assert_eq!("Shop abroad", record.details.as_str());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn add_record_fee_without_candidate() -> Result<()> {
+        with_default_importer(|importer| {
+            importer.options.merge_fee_rows = true;
+
+            let date = parse::date("2024-07-01", "%Y-%m-%d")?;
+            let purchase = RecordToImport {
+                amount: Decimal::new(2000, 2),
+                operation_date: date,
+                value_date: date,
+                details: "Shop abroad".to_string(),
+                ..Default::default()
+            };
+            let fee = RecordToImport {
+                amount: Decimal::new(50, 2),
+                value_date: date + chrono::Days::new(1),
+                details: "Frais tenue de compte".to_string(),
+                ..purchase.clone()
+            };
+
+            // A fee as the first row has nothing to be merged into
+            assert!(importer.add_record(fee.clone())?.is_none());
+            let record = importer.add_record(purchase)?.unwrap();
+            assert_eq!(Decimal::new(50, 2), record.amount);
+
+            // Nor when the value dates differ
+            let record = importer.add_record(fee)?.unwrap();
+            assert_eq!(Decimal::new(2000, 2), record.amount);
+
+            importer.finish()?;
+            assert_eq!(
+                vec![
+                    Decimal::new(50, 2),
+                    Decimal::new(2000, 2),
+                    Decimal::new(50, 2)
+                ],
+                importer
+                    .records
+                    .iter()
+                    .map(|record| record.amount)
+                    .collect::<Vec<_>>()
+            );
+
+            Ok(())
+        })
+    }
+
+    #[derive(Debug)]
+    struct Recorder(std::rc::Rc<std::cell::RefCell<Vec<progress::State>>>);
+
+    impl Progress for Recorder {
+        fn tick(&mut self, state: &progress::State) {
+            self.0.borrow_mut().push(state.clone());
+        }
+
+        fn finish(&mut self, state: &progress::State) {
+            self.tick(state);
+        }
+    }
+
+    #[test]
+    fn add_record_progress() -> Result<()> {
+        with_default_importer(|importer| {
+            let states = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            importer.options.progress = Some(Box::new(Recorder(states.clone())));
+            importer.progress.total = Some((3, progress::Unit::Rows));
+
+            let date = parse::date("2024-07-01", "%Y-%m-%d")?;
+            let record_to_import = RecordToImport {
+                amount: Decimal::new(314, 2),
+                operation_date: date,
+                value_date: date,
+                ..Default::default()
+            };
+            importer.add_record(record_to_import.clone())?;
+            importer.add_record(RecordToImport {
+                amount: Decimal::ZERO,
+                ..record_to_import.clone()
+            })?;
+            importer.add_record(record_to_import)?;
+            importer.finish()?;
+
+            let states = states.borrow();
+            assert_eq!(4, states.len());
+            assert_eq!(
+                progress::State {
+                    rows: 2,
+                    total: Some((3, progress::Unit::Rows)),
+                    imported: 1,
+                    ..Default::default()
+                },
+                states[1]
+            );
+            assert_eq!(
+                progress::State {
+                    rows: 3,
+                    total: Some((3, progress::Unit::Rows)),
+                    imported: 2,
+                    skipped: 1,
+                    ..Default::default()
+                },
+                states[3]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn find_own_account() -> Result<()> {
+        with_default_importer(|importer| {
+            let conn = &mut importer.options.config.database()?;
+            importer.own_accounts = vec![
+                test::account!(conn, "Bank"),
+                test::account!(conn, "Bank Joint"),
+                test::account!(conn, "Savings", iban: Some("FR1420041010050500013M02606")),
+            ];
+
+            let name = |texts: &[&str]| importer.find_own_account(texts).map(|a| a.name.clone());
+            assert_eq!(Some("Bank".to_string()), name(&["VIR bank"]));
+            assert_eq!(Some("Bank Joint".to_string()), name(&["VIR BANK JOINT"]));
+            assert_eq!(None, name(&["VIR Banking"]));
+            assert_eq!(
+                Some("Savings".to_string()),
+                name(&["VIR Bank FR14 2004 1010 0505 0001 3M02 606"])
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn add_get_category() -> Result<()> {
         with_default_importer(|importer| {
@@ -431,6 +1412,8 @@ mod tests {
                 ..Default::default()
             }
             .apply(conn, &mut chariot)?;
+            // Resolved merchants are kept by id for the whole import
+            importer.resolved_merchants.clear();
 
             assert!(importer.get_merchant("chariot").is_none());
             importer.add_merchant("chariot")?;
@@ -465,14 +1448,46 @@ mod tests {
     }
 
     #[test]
-    fn parse_decimal() -> Result<()> {
-        assert!(super::parse_decimal("hello").is_err());
+    fn add_get_merchant_replaced_default_category() -> Result<()> {
+        with_default_importer(|importer| {
+            let conn = &mut importer.options.config.database()?;
+
+            let old_bar = test::category!(conn, "old bar");
+            let bar = test::category!(conn, "bar");
+            let tavern = test::category!(conn, "tavern");
+            let mut le_chariot = test::merchant!(conn, "le chariot");
+            let mut chariot = test::merchant!(conn, "chariot");
+            finnel::merchant::ChangeMerchant {
+                replaced_by: Some(Some(&chariot)),
+                ..Default::default()
+            }
+            .apply(conn, &mut le_chariot)?;
+            finnel::merchant::ChangeMerchant {
+                default_category: Some(Some(&old_bar)),
+                ..Default::default()
+            }
+            .apply(conn, &mut chariot)?;
+            // Replaced after being set as default, through a chain
+            for (category, replacer) in [(&old_bar, &bar), (&bar, &tavern)] {
+                finnel::category::ChangeCategory {
+                    replaced_by: Some(Some(replacer)),
+                    ..Default::default()
+                }
+                .save(conn, category)?;
+            }
 
-        assert_eq!(Decimal::new(314, 2), super::parse_decimal("3,14")?);
-        assert_eq!(Decimal::new(314, 2), super::parse_decimal("3.14")?);
+            importer.add_merchant("le chariot")?;
+            importer.add_merchant("chariot")?;
+            for name in ["le chariot", "chariot"] {
+                let (merchant, category) = importer.get_merchant(name).unwrap();
+                assert_eq!(chariot.id, merchant.id);
+                assert_eq!(Some(tavern.id), category.as_ref().map(|c| c.id));
+            }
+            // Each category of the chain is resolved
+            assert_eq!(tavern.id, importer.resolved_categories[&old_bar.id].id);
+            assert_eq!(tavern.id, importer.resolved_categories[&bar.id].id);
 
-        assert_eq!(Decimal::new(65536, 0), super::parse_decimal("65536")?);
-        assert_eq!(Decimal::new(65536, 0), super::parse_decimal("65 536")?);
-        Ok(())
+            Ok(())
+        })
     }
 }
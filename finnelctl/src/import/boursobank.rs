@@ -1,19 +1,24 @@
-use super::{parse_date_fmt, parse_decimal, Importer, Options, Profile, RecordToImport};
+use super::{profile::Names, progress::Unit, Importer, Options, Profile, RecordToImport};
 
-use finnel::prelude::*;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use finnel::{parse, prelude::*};
 
 use anyhow::Result;
 use chrono::NaiveDate;
 
 pub struct Boursobank {
     reader: csv::Reader<std::fs::File>,
+    file: PathBuf,
+    rows: usize,
+    header: String,
 }
 
 impl Boursobank {
     pub fn new(options: &Options) -> Result<Self> {
-        let mut reader = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_path(options.file()?)?;
+        let file = options.file()?;
+        let mut reader = csv::ReaderBuilder::new().delimiter(b';').from_path(&file)?;
 
         {
             let headers = reader.headers()?;
@@ -36,93 +41,49 @@ impl Boursobank {
                 anyhow::bail!("Invalid CSV header, expecting {:?}", expected_headers);
             }
         }
-
-        Ok(Boursobank { reader })
+        let header = to_line(reader.headers()?)?;
+
+        Ok(Boursobank {
+            reader,
+            header,
+            // Without the header
+            rows: count_lines(&file)?.saturating_sub(1),
+            file,
+        })
     }
 }
 
 impl Profile for Boursobank {
     fn run(&mut self, importer: &mut Importer) -> Result<()> {
         for result in self.reader.records() {
-            let row = result?;
-
-            let mut record = RecordToImport {
-                operation_date: parse_date(row.get(0).unwrap())?,
-                value_date: parse_date(row.get(1).unwrap())?,
-                amount: parse_decimal(row.get(6).unwrap())?,
-                mode: Mode::Direct(PaymentMethod::Empty),
-                details: row.get(2).unwrap().to_string(),
-                category_name: row.get(3).unwrap().to_string(),
-                merchant_name: row.get(5).unwrap().to_string(),
-                ..Default::default()
-            };
-
-            if record.details.starts_with("CARTE ") || record.details.starts_with("AVOIR ") {
-                // CARTE DD/MM/YYYY ... CB*WXYZ
-                // AVOIR DD/MM/YYYY ... CB*WXYZ
-                record.operation_date = parse_date_fmt(&record.details[6..14], "%d/%m/%y")?;
-                let payment_method =
-                    PaymentMethod::read(&record.details[record.details.len() - 8..], " CB")?;
-                record.details = record.details[15..record.details.len() - 8].to_string();
-                record.mode = Mode::Direct(payment_method);
-            } else if record.details.starts_with("RETRAIT DAB ") {
-                // RETRAIT DAB DD/MM/YYYY ... CB*WXYZ
-                record.operation_date = parse_date_fmt(&record.details[12..20], "%d/%m/%y")?;
-                let payment_method =
-                    PaymentMethod::read(&record.details[record.details.len() - 8..], " CB")?;
-                record.details = record.details[21..record.details.len() - 8].to_string();
-                record.mode = Mode::Atm(payment_method);
-
-                // We don't need the merchant from Boursobank
-                record.merchant_name = String::new();
-            } else if record.details.starts_with("VIR ") | record.details.starts_with("PRLV ") {
-                // VIR|PRLV INST ...
-                // VIR|PRLV SEPA ...
-                // VIR|PRLV ...
-                record.mode = Mode::Transfer;
-                match &record.details[0..4] {
-                    "VIR " => record.details = record.details[4..].to_string(),
-                    "PRLV" => record.details = record.details[5..].to_string(),
-                    _ => {}
-                }
-                match &record.details[0..5] {
-                    "INST " | "SEPA " => record.details = record.details[5..].to_string(),
-                    _ => {}
+            let row = match result {
+                Ok(row) => row,
+                Err(e) => {
+                    let line = e.position().map(|position| position.line());
+                    importer.reject_row(read_line(&self.file, line)?, e.into())?;
+                    continue;
                 }
-
-                // If the merchant is empty, use the details
-                if record.merchant_name.is_empty() {
-                    record.merchant_name = record.details.clone();
-                }
-
-                if record.merchant_name.starts_with("virement ") {
-                    record.merchant_name = record.merchant_name[9..].to_string();
-                    if record.merchant_name.starts_with("interne depuis ") {
-                        record.merchant_name = record.merchant_name[15..].to_string();
-                    }
+            };
+            let mut record = match parse_row(importer, &row) {
+                Ok(record) => record,
+                Err(e) => {
+                    importer.reject_row(to_line(&row)?, e)?;
+                    continue;
                 }
-            }
-
-            if record.category_name == "Non catégorisé" {
-                record.category_name = String::new();
-            }
-
-            record.direction = if record.amount.is_sign_negative() {
-                Direction::Debit
-            } else {
-                Direction::Credit
             };
-            record.amount = record.amount.abs();
 
             importer.add_merchant(&record.merchant_name)?;
 
             let detected_category_name = record.category_name;
 
-            // merchant's default_category takes precedence over the category_name because
+            // merchant's default_category, then the category configured on the account for ATM
+            // withdrawals and fees, take precedence over the category_name because
             // boursobank's categories are not what we want
             record.category_name = importer
                 .get_merchant(&record.merchant_name)
-                .and_then(|(_, category)| category.as_ref().map(|c| c.name.clone()))
+                .and_then(|(_, category)| category.as_ref())
+                .or_else(|| importer.account_category(&record.mode, &record.details))
+                .map(|c| c.name.clone())
                 .unwrap_or_else(|| detected_category_name.clone());
 
             // If we still end up with the initial category_name, only then do we add it to the
@@ -136,18 +97,176 @@ impl Profile for Boursobank {
 
         Ok(())
     }
+
+    fn scan(&mut self, importer: &Importer) -> Result<Option<Names>> {
+        let mut names = Names::default();
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .from_path(&self.file)?;
+        for row in reader.records() {
+            // Rejected when running
+            let Ok(record) = row
+                .map_err(anyhow::Error::from)
+                .and_then(|row| parse_row(importer, &row))
+            else {
+                continue;
+            };
+            if !record.merchant_name.is_empty() {
+                names.merchants.insert(record.merchant_name);
+            }
+            if !record.category_name.is_empty() {
+                names.categories.insert(record.category_name);
+            }
+        }
+        Ok(Some(names))
+    }
+
+    fn total(&self) -> Option<(usize, Unit)> {
+        Some((self.rows, Unit::Rows))
+    }
+
+    fn header(&self) -> Option<String> {
+        Some(self.header.clone())
+    }
+}
+
+/// Record read from the row, before looking for its merchant and category
+fn parse_row(importer: &Importer, row: &csv::StringRecord) -> Result<RecordToImport> {
+    let mut record = RecordToImport {
+        operation_date: parse_date(row.get(0).unwrap())?,
+        value_date: parse_date(row.get(1).unwrap())?,
+        amount: parse::decimal(row.get(6).unwrap())?,
+        mode: Mode::Direct(PaymentMethod::Empty),
+        details: row.get(2).unwrap().to_string(),
+        category_name: row.get(3).unwrap().to_string(),
+        merchant_name: row.get(5).unwrap().to_string(),
+        account_number: row.get(7).filter(|n| !n.is_empty()).map(str::to_owned),
+        ..Default::default()
+    };
+
+    if record.details.starts_with("CARTE ") || record.details.starts_with("AVOIR ") {
+        // CARTE DD/MM/YYYY ... CB*WXYZ
+        // AVOIR DD/MM/YYYY ... CB*WXYZ
+        record.operation_date = parse::date(&record.details[6..14], "%d/%m/%y")?;
+        let payment_method =
+            PaymentMethod::read(&record.details[record.details.len() - 8..], " CB")?;
+        record.details = record.details[15..record.details.len() - 8].to_string();
+        record.mode = Mode::Direct(payment_method);
+    } else if record.details.starts_with("RETRAIT DAB ") {
+        // RETRAIT DAB DD/MM/YYYY ... CB*WXYZ
+        record.operation_date = parse::date(&record.details[12..20], "%d/%m/%y")?;
+        let payment_method =
+            PaymentMethod::read(&record.details[record.details.len() - 8..], " CB")?;
+        record.details = record.details[21..record.details.len() - 8].to_string();
+        record.mode = Mode::Atm(payment_method);
+
+        // We don't need the merchant from Boursobank
+        record.merchant_name = String::new();
+    } else if record.details.starts_with("VIR ") | record.details.starts_with("PRLV ") {
+        // VIR|PRLV INST ...
+        // VIR|PRLV SEPA ...
+        // VIR|PRLV ...
+        record.mode = Mode::Transfer;
+        match &record.details[0..4] {
+            "VIR " => record.details = record.details[4..].to_string(),
+            "PRLV" => record.details = record.details[5..].to_string(),
+            _ => {}
+        }
+        match &record.details[0..5] {
+            "INST " | "SEPA " => record.details = record.details[5..].to_string(),
+            _ => {}
+        }
+
+        // If the merchant is empty, use the details
+        if record.merchant_name.is_empty() {
+            record.merchant_name = record.details.clone();
+        }
+
+        if record.merchant_name.starts_with("virement ") {
+            record.merchant_name = record.merchant_name[9..].to_string();
+            if record.merchant_name.starts_with("interne depuis ") {
+                record.merchant_name = record.merchant_name[15..].to_string();
+            }
+        }
+
+        if let Some(account) =
+            importer.find_own_account(&[row.get(2).unwrap(), &record.merchant_name])
+        {
+            record.merchant_name = account.name.clone();
+            record.internal = true;
+        }
+    }
+
+    if record.category_name == "Non catégorisé" {
+        record.category_name = String::new();
+    }
+
+    record.direction = if record.amount.is_sign_negative() {
+        Direction::Debit
+    } else {
+        Direction::Credit
+    };
+    record.amount = record.amount.abs();
+
+    Ok(record)
+}
+
+/// Row as a line of the file, to write it back as is
+fn to_line(row: &csv::StringRecord) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_writer(Vec::new());
+    writer.write_record(row)?;
+    let line = String::from_utf8(writer.into_inner()?)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Line of the file as it is, for the rows the CSV reader fails to read, or
+/// an empty one when it isn't known
+fn read_line(path: &Path, line: Option<u64>) -> Result<String> {
+    let Some(index) = line.and_then(|line| line.checked_sub(1)) else {
+        return Ok(String::new());
+    };
+    let content = BufReader::new(std::fs::File::open(path)?)
+        .split(b'\n')
+        .nth(index as usize)
+        .transpose()?
+        .unwrap_or_default();
+    Ok(String::from_utf8_lossy(&content)
+        .trim_end_matches('\r')
+        .to_string())
+}
+
+/// Number of non-empty lines, which is the number of rows as long as no
+/// field spans several lines
+fn count_lines(path: &Path) -> Result<usize> {
+    let mut count = 0;
+    for line in BufReader::new(std::fs::File::open(path)?).split(b'\n') {
+        if !line?.trim_ascii().is_empty() {
+            count += 1;
+        }
+    }
+    Ok(count)
 }
 
 fn parse_date(date: &str) -> Result<NaiveDate> {
-    parse_date_fmt(date, "%d/%m/%Y")
+    Ok(parse::date(date, "%d/%m/%Y")?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::import::tests::with_default_importer;
+    use crate::import::{profile::Information, tests::with_default_importer};
     use crate::test::prelude::{assert_eq, Result, *};
-    use finnel::{category::NewCategory, merchant::NewMerchant};
+    use diesel::{connection::InstrumentationEvent, Connection};
+    use finnel::{
+        category::NewCategory,
+        merchant::{ChangeMerchant, NewMerchant},
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
 
     #[test]
     fn invalid_header() -> Result<()> {
@@ -228,6 +347,7 @@ mod tests {
                 };
 
                 let mut profile = Boursobank::new(&options)?;
+                assert_eq!(Some((9, Unit::Rows)), profile.total());
                 profile.run(importer)?;
 
                 assert_eq!(9, importer.records.len());
@@ -323,4 +443,285 @@ mod tests {
             })
         })
     }
+
+    #[test]
+    fn import_own_transfer() -> Result<()> {
+        let csv = "boursobank/own_transfer.csv";
+        with_fixtures(&[csv], |dir| {
+            with_config(|config| {
+                let conn = &mut config.database()?;
+                let _savings = test::account!(
+                    conn,
+                    "Savings",
+                    iban: Some("FR1420041010050500013M02606")
+                );
+
+                let options = Options {
+                    file: Some(dir.child(csv).path().display().to_string()),
+                    ..Options::new(config)
+                };
+
+                crate::import::tests::with_importer(options, |importer| {
+                    Boursobank::new(&importer.options)?.run(importer)?;
+
+                    assert_eq!(2, importer.records.len());
+
+                    let record = &importer.records[0];
+                    assert_eq!(
+                        Some("Savings"),
+                        record.fetch_merchant(conn)?.map(|m| m.name).as_deref()
+                    );
+                    let tags = record.fetch_tags(conn)?;
+                    assert_eq!(1, tags.len());
+                    assert_eq!(crate::import::INTERNAL_TAG, tags[0].name);
+
+                    let record = &importer.records[1];
+                    assert_eq!(
+                        Some("LOYER GB82 WEST 1234 5698 7654 32"),
+                        record.fetch_merchant(conn)?.map(|m| m.name).as_deref()
+                    );
+                    assert!(record.fetch_tags(conn)?.is_empty());
+
+                    Ok(())
+                })
+            })
+        })
+    }
+
+    /// Import the fixture listing two account numbers into the Bank, Joint
+    /// and Dollars accounts, with the numbers mapped to them as given
+    fn import_accounts<F>(map: &[(&str, &str)], strict_accounts: bool, check: F) -> Result<()>
+    where
+        F: FnOnce(&mut Conn, Result<&Importer>) -> Result<()>,
+    {
+        let csv = "boursobank/accounts.csv";
+        with_fixtures(&[csv], |dir| {
+            with_config(|config| {
+                let conn = &mut config.database()?;
+                test::account!(conn, "Bank");
+                test::account!(conn, "Joint");
+                test::account!(conn, "Dollars", currency: Currency::USD);
+
+                let options = Options {
+                    file: Some(dir.child(csv).path().display().to_string()),
+                    profile_info: Information::Boursobank,
+                    account_map: map
+                        .iter()
+                        .map(|(number, account)| (number.to_string(), account.to_string()))
+                        .collect(),
+                    strict_accounts,
+                    ..Options::new(config)
+                };
+                crate::import::tests::with_importer(options, |importer| {
+                    let result = Boursobank::new(&importer.options)?.run(importer);
+                    check(conn, result.map(|_| &*importer))
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn import_mapped_accounts() -> Result<()> {
+        let map = [("00012345678", "Bank"), ("00087654321", "Joint")];
+        import_accounts(&map, false, |conn, importer| {
+            let importer = importer?;
+            let names = importer
+                .records
+                .iter()
+                .map(|record| Ok(Account::find(conn, record.account_id.unwrap())?.name))
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(vec!["Bank", "Bank", "Joint", "Joint", "Bank"], names);
+            assert_eq!(0, importer.outcome().skipped);
+            Ok(())
+        })?;
+
+        // The rows of the joint account are left out
+        import_accounts(&map[..1], false, |conn, importer| {
+            let importer = importer?;
+            assert_eq!(3, importer.records.len());
+            for record in &importer.records {
+                assert_eq!(
+                    "Bank",
+                    Account::find(conn, record.account_id.unwrap())?.name
+                );
+            }
+            assert_eq!(2, importer.outcome().skipped);
+            Ok(())
+        })?;
+
+        import_accounts(&map[..1], true, |_, importer| {
+            assert_eq!(
+                "row 3: account number 00087654321 is not mapped, \
+                 use --map-account 00087654321=ACCOUNT to import it",
+                importer.err().unwrap().to_string()
+            );
+            Ok(())
+        })?;
+
+        import_accounts(&[("00012345678", "Dollars")], false, |_, importer| {
+            assert!(importer
+                .err()
+                .unwrap()
+                .to_string()
+                .starts_with("Account Dollars is in USD, but profile boursobank"));
+            Ok(())
+        })
+    }
+
+    /// Importing 10k rows naming 200 merchants, half of them replaced
+    /// through a chain of 50 merchants or found by alias, made 1985 queries
+    /// besides inserting the records when each name was looked up and
+    /// resolved on its own, and 514 with the names found at once and each
+    /// chain followed only once. The time is spent inserting the records
+    /// either way.
+    #[test]
+    fn import_many_rows() -> Result<()> {
+        with_temp_dir(|dir| {
+            let file = dir.child("many_rows.csv");
+            let mut content = String::from(
+                "dateOp;dateVal;label;category;categoryParent;supplierFound;amount;\
+                 accountNum;accountLabel;accountBalance;comment;pointer\n",
+            );
+            for i in 0..10_000 {
+                let shop = i % 200;
+                content.push_str(&format!(
+                    "01/07/2024;01/07/2024;\"CARTE 30/06/24 SHOP {shop} CB*1234\";\
+                     \"Category {}\";Parent;\"shop {shop}\";-{},{:02};\
+                     SomeNumber;BoursoBank;;;Non\n",
+                    i % 20,
+                    (i + 1) / 100,
+                    (i + 1) % 100
+                ));
+            }
+            file.write_str(&content)?;
+
+            with_config(|config| {
+                let options = Options {
+                    file: Some(file.path().display().to_string()),
+                    profile_info: Information::Boursobank,
+                    ..Options::new(config)
+                };
+                crate::import::tests::with_importer(options, |importer| {
+                    let conn = &mut importer.options.config.database()?;
+
+                    let groceries = test::category!(conn, "Groceries");
+                    let shop = NewMerchant {
+                        name: "Shop",
+                        default_category: Some(&groceries),
+                        ..Default::default()
+                    }
+                    .save(conn)?;
+                    // shop 0 is replaced by shop 1, ..., shop 49 by Shop, each
+                    // replacement made before its replacer got replaced too
+                    let mut shops = (0..50)
+                        .map(|i| NewMerchant::new(&format!("shop {i}")).save(conn))
+                        .collect::<finnel::Result<Vec<_>>>()?;
+                    shops.push(shop);
+                    for i in 0..50 {
+                        let (replaced, replacers) = shops.split_at_mut(i + 1);
+                        ChangeMerchant {
+                            replaced_by: Some(Some(&replacers[0])),
+                            ..Default::default()
+                        }
+                        .apply(conn, &mut replaced[i])?;
+                    }
+                    let shop = shops.pop().unwrap();
+                    for i in 50..100 {
+                        shop.add_alias(conn, &format!("shop {i}"))?;
+                    }
+
+                    let queries = Arc::new(AtomicUsize::new(0));
+                    let counter = queries.clone();
+                    importer
+                        .conn
+                        .set_instrumentation(move |event: InstrumentationEvent<'_>| {
+                            if let InstrumentationEvent::StartQuery { .. } = event {
+                                counter.fetch_add(1, Ordering::Relaxed);
+                            }
+                        });
+
+                    importer.run()?;
+
+                    assert_eq!(10_000, importer.records.len());
+                    assert!(queries.load(Ordering::Relaxed) - 10_000 < 600);
+                    let shop_records = importer
+                        .records
+                        .iter()
+                        .filter(|r| r.merchant_id == Some(shop.id))
+                        .inspect(|r| assert_eq!(Some(groceries.id), r.category_id))
+                        .count();
+                    assert_eq!(5_000, shop_records);
+
+                    // The chain and Shop, then the 100 merchants created
+                    assert_eq!(151, importer.resolved_merchants.len());
+
+                    Ok(())
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn skip_errors() -> Result<()> {
+        let csv = "boursobank/bad_rows.csv";
+        with_fixtures(&[csv], |dir| {
+            with_config(|config| {
+                let file = dir.child(csv);
+                let options = Options {
+                    file: Some(file.path().display().to_string()),
+                    ..Options::new(config)
+                };
+                crate::import::tests::with_importer(options, |importer| {
+                    let error = Boursobank::new(&importer.options)?
+                        .run(importer)
+                        .unwrap_err();
+                    assert!(error.to_string().contains("32/07/2024"));
+                    Ok(())
+                })
+            })?;
+
+            with_config(|config| {
+                let file = dir.child(csv);
+                let options = Options {
+                    file: Some(file.path().display().to_string()),
+                    profile_info: Information::Boursobank,
+                    skip_errors: true,
+                    ..Options::new(config)
+                };
+                crate::import::tests::with_importer(options, |importer| {
+                    importer.run()?;
+
+                    let details = importer
+                        .records
+                        .iter()
+                        .map(|r| r.details.as_str())
+                        .collect::<Vec<_>>();
+                    assert_eq!(vec!["Spotify", "LOYER"], details);
+
+                    let rows = importer.rejected.iter().map(|r| r.row).collect::<Vec<_>>();
+                    assert_eq!(vec![2, 4, 5], rows);
+                    assert!(importer.rejected[1].error.contains("beaucoup"));
+                    // Unreadable as a row, so written back as it is in the file
+                    assert!(importer.rejected[2].error.contains("fields"));
+
+                    Ok(())
+                })?;
+
+                let rejected =
+                    std::fs::read_to_string(dir.child("boursobank/bad_rows.rejected.csv"))?;
+                assert_eq!(
+                    "dateOp;dateVal;label;category;categoryParent;supplierFound;amount;\
+                     accountNum;accountLabel;accountBalance;comment;pointer\n\
+                     32/07/2024;02/07/2024;PRLV SEPA Netflix;Non catégorisé;Non catégorisé;;\
+                     -13,99;SomeNumber;BoursoBank;;;Non\n\
+                     04/07/2024;04/07/2024;PRLV SEPA EDF;Non catégorisé;Non catégorisé;;\
+                     beaucoup;SomeNumber;BoursoBank;;;Non\n\
+                     05/07/2024;05/07/2024;\"Truncated row\"\n",
+                    rejected
+                );
+
+                Ok(())
+            })
+        })
+    }
 }
@@ -0,0 +1,341 @@
+use super::{profile::Names, progress::Unit, Importer, Options, Profile, RecordToImport};
+
+use finnel::{parse, prelude::*};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+
+/// ISO 20022 bank to customer statement
+pub struct Camt053 {
+    entries: Vec<Entry>,
+}
+
+/// Statement entry, with the texts as they appear in the file
+#[derive(Default, Debug)]
+struct Entry {
+    reference: String,
+    booking_date: String,
+    value_date: String,
+    amount: String,
+    currency: String,
+    indicator: String,
+    family: String,
+    remittance: Vec<String>,
+    additional_info: String,
+    creditor: String,
+    debtor: String,
+}
+
+impl Camt053 {
+    pub fn new(options: &Options) -> Result<Self> {
+        let file = options.file()?;
+        let mut reader = quick_xml::Reader::from_file(&file)
+            .with_context(|| format!("Unable to read {}", file.display()))?;
+        reader.config_mut().trim_text(true);
+
+        let mut entries = Vec::new();
+        let mut entry: Option<Entry> = None;
+        // Path of the current element, relative to the entry being read
+        let mut path: Vec<String> = Vec::new();
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(element) => {
+                    let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                    if let Some(entry) = entry.as_mut() {
+                        if path.is_empty() && name == "Amt" {
+                            if let Some(currency) = element.try_get_attribute("Ccy")? {
+                                entry.currency = currency.unescape_value()?.to_string();
+                            }
+                        }
+                        path.push(name);
+                    } else if name == "Ntry" {
+                        entry = Some(Entry::default());
+                    }
+                }
+                Event::End(_) => {
+                    // Nothing left to close inside the entry, so this is the entry itself
+                    let entry_closed = path.pop().is_none();
+                    if entry_closed {
+                        entries.extend(entry.take());
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.set(&path.join("/"), text.unescape()?.to_string());
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        if entries.is_empty() {
+            anyhow::bail!("No entry found in camt.053 statement {}", file.display());
+        }
+
+        Ok(Camt053 { entries })
+    }
+}
+
+impl Entry {
+    fn set(&mut self, path: &str, value: String) {
+        match path {
+            "NtryRef" => self.reference = value,
+            "AcctSvcrRef" if self.reference.is_empty() => self.reference = value,
+            "Amt" => self.amount = value,
+            "CdtDbtInd" => self.indicator = value,
+            "BookgDt/Dt" | "BookgDt/DtTm" => self.booking_date = value,
+            "ValDt/Dt" | "ValDt/DtTm" => self.value_date = value,
+            "BkTxCd/Domn/Fmly/Cd" => self.family = value,
+            "AddtlNtryInf" => self.additional_info = value,
+            "NtryDtls/TxDtls/RmtInf/Ustrd" => self.remittance.push(value),
+            "NtryDtls/TxDtls/RltdPties/Cdtr/Nm" | "NtryDtls/TxDtls/RltdPties/Cdtr/Pty/Nm" => {
+                self.creditor = value
+            }
+            "NtryDtls/TxDtls/RltdPties/Dbtr/Nm" | "NtryDtls/TxDtls/RltdPties/Dbtr/Pty/Nm" => {
+                self.debtor = value
+            }
+            _ => {}
+        }
+    }
+
+    fn direction(&self) -> Result<Direction> {
+        match self.indicator.as_str() {
+            "DBIT" => Ok(Direction::Debit),
+            "CRDT" => Ok(Direction::Credit),
+            indicator => anyhow::bail!(
+                "Invalid credit/debit indicator {:?} for entry {}",
+                indicator,
+                self.reference
+            ),
+        }
+    }
+
+    /// Payment mode from the family of the bank transaction code
+    fn mode(&self) -> Mode {
+        match self.family.as_str() {
+            "CWDL" => Mode::Atm(PaymentMethod::Empty),
+            "RCDT" | "ICDT" | "RDDT" | "IDDT" => Mode::Transfer,
+            _ => Mode::Direct(PaymentMethod::Empty),
+        }
+    }
+
+    fn to_import(&self) -> Result<RecordToImport> {
+        let direction = self.direction()?;
+        let details = if self.remittance.is_empty() {
+            self.additional_info.clone()
+        } else {
+            self.remittance.join(" ")
+        };
+
+        // The other party of the transaction is the one we paid or who paid us
+        let merchant_name = match direction {
+            Direction::Debit => self.creditor.clone(),
+            Direction::Credit => self.debtor.clone(),
+        };
+
+        let booking_date = parse_date(&self.booking_date)
+            .with_context(|| format!("Invalid booking date for entry {}", self.reference))?;
+        let value_date = if self.value_date.is_empty() {
+            booking_date
+        } else {
+            parse_date(&self.value_date)
+                .with_context(|| format!("Invalid value date for entry {}", self.reference))?
+        };
+
+        let currency = match self.currency.as_str() {
+            "" => None,
+            code => Some(Currency::from_code(code).ok_or_else(|| {
+                anyhow::anyhow!("Unknown currency {} for entry {}", code, self.reference)
+            })?),
+        };
+
+        Ok(RecordToImport {
+            operation_date: booking_date,
+            value_date,
+            currency,
+            amount: self
+                .amount
+                .parse::<Decimal>()
+                .with_context(|| format!("Invalid amount for entry {}", self.reference))?,
+            direction,
+            mode: self.mode(),
+            details,
+            merchant_name,
+            ..Default::default()
+        })
+    }
+}
+
+impl Profile for Camt053 {
+    fn run(&mut self, importer: &mut Importer) -> Result<()> {
+        // Report every mismatching entry before importing any of them
+        if let Some(account) = importer
+            .account
+            .as_ref()
+            .filter(|_| !importer.options.coerce_currency)
+        {
+            let currency = account.currency.code();
+            let mismatches = self
+                .entries
+                .iter()
+                .filter(|entry| !entry.currency.is_empty() && entry.currency != currency)
+                .map(|entry| format!("{} ({})", entry.reference, entry.currency))
+                .collect::<Vec<_>>();
+            if !mismatches.is_empty() {
+                anyhow::bail!(
+                    "Entries with a currency different from the account {} ({}): {}, \
+                     use --coerce-currency to import them anyway",
+                    account.name,
+                    currency,
+                    mismatches.join(", ")
+                );
+            }
+        }
+
+        for entry in &self.entries {
+            let mut record = match entry.to_import() {
+                Ok(record) => record,
+                Err(e) => {
+                    importer.reject_row(entry.reference.clone(), e)?;
+                    continue;
+                }
+            };
+
+            if let Some(account) =
+                importer.find_own_account(&[&record.details, &record.merchant_name])
+            {
+                record.merchant_name = account.name.clone();
+                record.internal = true;
+            }
+
+            importer.add_merchant(&record.merchant_name)?;
+            importer.add_record(record)?;
+        }
+
+        Ok(())
+    }
+
+    fn scan(&mut self, importer: &Importer) -> Result<Option<Names>> {
+        let mut names = Names::default();
+        for record in self.entries.iter().filter_map(|e| e.to_import().ok()) {
+            if !record.merchant_name.is_empty()
+                && importer
+                    .find_own_account(&[&record.details, &record.merchant_name])
+                    .is_none()
+            {
+                names.merchants.insert(record.merchant_name);
+            }
+        }
+        Ok(Some(names))
+    }
+
+    fn total(&self) -> Option<(usize, Unit)> {
+        Some((self.entries.len(), Unit::Rows))
+    }
+}
+
+/// Parse an ISODate or the date part of an ISODateTime
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    Ok(parse::date(date.get(0..10).unwrap_or(date), "%Y-%m-%d")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::tests::with_default_importer;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    fn options<'a>(importer: &Importer<'a>, dir: &assert_fs::TempDir, file: &str) -> Options<'a> {
+        Options {
+            file: Some(dir.child(file).path().display().to_string()),
+            ..Options::new(importer.options.config)
+        }
+    }
+
+    #[test]
+    fn import() -> Result<()> {
+        let xml = "camt053/statement.xml";
+        with_fixtures(&[xml], |dir| {
+            with_default_importer(|importer| {
+                let conn = &mut importer.options.config.database()?;
+
+                let mut profile = Camt053::new(&options(importer, dir, xml))?;
+                assert_eq!(Some((3, Unit::Rows)), profile.total());
+                profile.run(importer)?;
+
+                assert_eq!(3, importer.records.len());
+
+                let record = &importer.records[0];
+                assert_eq!(Direction::Debit, record.direction);
+                assert_eq!(Decimal::new(4250, 2), record.amount);
+                assert_eq!("Bread and croissants", record.details);
+                assert_eq!(
+                    Some("Bakery & Co"),
+                    record.fetch_merchant(conn)?.map(|m| m.name).as_deref()
+                );
+                assert_eq!(Mode::Direct(PaymentMethod::Empty), record.mode);
+                assert_eq!(parse_date("2024-07-02")?, record.operation_date);
+                assert_eq!(parse_date("2024-07-03")?, record.value_date);
+
+                let record = &importer.records[1];
+                assert_eq!(Direction::Credit, record.direction);
+                assert_eq!(Decimal::new(2500, 0), record.amount);
+                assert_eq!("Salary July", record.details);
+                assert_eq!(
+                    Some("ACME Corp"),
+                    record.fetch_merchant(conn)?.map(|m| m.name).as_deref()
+                );
+                assert_eq!(Mode::Transfer, record.mode);
+                assert_eq!(parse_date("2024-07-05")?, record.operation_date);
+
+                let record = &importer.records[2];
+                assert_eq!(Direction::Debit, record.direction);
+                assert_eq!("Cash withdrawal Berlin", record.details);
+                assert_eq!(None, record.merchant_id);
+                assert_eq!(Mode::Atm(PaymentMethod::Empty), record.mode);
+                assert_eq!(record.operation_date, record.value_date);
+
+                Ok(())
+            })
+        })
+    }
+
+    #[test]
+    fn foreign_currency() -> Result<()> {
+        let xml = "camt053/foreign_currency.xml";
+        with_fixtures(&[xml], |dir| {
+            with_default_importer(|importer| {
+                let mut profile = Camt053::new(&options(importer, dir, xml))?;
+                let error = profile.run(importer).unwrap_err().to_string();
+
+                assert!(error.contains("REF-002 (USD), SVC-003 (GBP)"), "{}", error);
+                assert!(importer.records.is_empty());
+
+                Ok(())
+            })
+        })
+    }
+
+    #[test]
+    fn no_entry() -> Result<()> {
+        with_temp_dir(|dir| {
+            let file = dir.child("empty.xml");
+            file.write_str("<Document><BkToCstmrStmt><Stmt/></BkToCstmrStmt></Document>")?;
+
+            with_config(|config| {
+                let options = Options {
+                    file: Some(file.path().display().to_string()),
+                    ..Options::new(config)
+                };
+                assert!(Camt053::new(&options).is_err());
+
+                Ok(())
+            })
+        })
+    }
+}
@@ -0,0 +1,75 @@
+use super::{check_journal, Options, Outcome};
+use crate::cli::import::Command;
+
+use finnel::{
+    import::NewImport,
+    legacy::{self, LegacyDatabase},
+    prelude::*,
+};
+
+use anyhow::{Context, Result};
+
+/// Recreate the content of a database of a version of finnel before diesel,
+/// which is only ever read
+pub fn run(conn: &mut Conn, command: &Command, options: &Options) -> Result<Outcome> {
+    let file = options.file()?;
+    let mut legacy = LegacyDatabase::open(&file)
+        .with_context(|| format!("Unable to open {}", file.display()))?;
+
+    conn.transaction(|conn| {
+        let hash = check_journal(conn, options, command.force)?;
+        let migration = legacy::migrate(conn, &mut legacy)?;
+
+        for issue in &migration.issues {
+            eprintln!("Warning: {issue}");
+        }
+        if !migration.rejected.is_empty() {
+            if !options.skip_errors {
+                anyhow::bail!(
+                    "Unable to recreate {} records: {}, use --skip-errors to leave them out",
+                    migration.rejected.len(),
+                    migration.rejected.join(", ")
+                );
+            }
+            println!("Rejected {} records", migration.rejected.len());
+            for rejected in &migration.rejected {
+                println!("{rejected}");
+            }
+        }
+        eprintln!(
+            "Created {} accounts, {} categories, {} merchants and {} records",
+            migration.accounts,
+            migration.categories,
+            migration.merchants,
+            migration.records.len()
+        );
+
+        let dates = migration.records.iter().map(|r| r.operation_date);
+        let outcome = Outcome {
+            imported: migration.records.len(),
+            from: dates.clone().min(),
+            to: dates.max(),
+            errors: migration.rejected.len(),
+            ids: migration.records.iter().map(|r| r.id).collect(),
+            ..Default::default()
+        };
+
+        if let Some(hash) = &hash {
+            NewImport {
+                count: outcome.imported as i64,
+                ..NewImport::new(options.profile_info.name()?, hash)
+            }
+            .save(conn)?;
+        }
+
+        if command.porcelain {
+            println!("{outcome}");
+        }
+
+        if options.pretend {
+            anyhow::bail!("No records were saved as we are pretending");
+        }
+
+        Ok(outcome)
+    })
+}
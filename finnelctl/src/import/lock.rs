@@ -0,0 +1,162 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::error::CliError;
+
+/// Name of the lock file, in the data directory next to the database
+pub const FILENAME: &str = "import.lock";
+
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Exclusive lock preventing two imports from running at the same time on
+/// the same data directory, released when dropped, including when unwinding.
+///
+/// The lock is taken by the operating system on the open file, so it is also
+/// released if the process is killed and the file left behind is harmless.
+#[derive(Debug)]
+pub struct ImportLock {
+    file: File,
+}
+
+impl ImportLock {
+    /// Acquire the lock in `dir`, retrying for up to `wait` if another import
+    /// holds it
+    pub fn acquire(dir: &Path, wait: Option<Duration>) -> Result<Self> {
+        let path = dir.join(FILENAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Unable to open {}", path.display()))?;
+        // A wait too long to be represented never expires
+        let deadline = Instant::now().checked_add(wait.unwrap_or_default());
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { file }),
+                Err(TryLockError::WouldBlock)
+                    if deadline.is_none_or(|deadline| Instant::now() < deadline) =>
+                {
+                    std::thread::sleep(RETRY_DELAY);
+                }
+                Err(TryLockError::WouldBlock) => return Err(in_progress(&path, wait)),
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Unable to lock {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ImportLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            log::warn!("Unable to release the import lock: {e}");
+        }
+    }
+}
+
+fn in_progress(path: &Path, wait: Option<Duration>) -> anyhow::Error {
+    let message = match wait {
+        Some(wait) => format!(
+            "Another import is still in progress after waiting {} second(s) (locked {})",
+            wait.as_secs(),
+            path.display()
+        ),
+        None => format!(
+            "Another import is in progress (locked {}), use --wait SECONDS to wait for it",
+            path.display()
+        ),
+    };
+    CliError::DatabaseBusy(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn exclusive() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let path = dir.path().to_path_buf();
+
+        let lock = ImportLock::acquire(&path, None)?;
+        let other = {
+            let path = path.clone();
+            std::thread::spawn(move || ImportLock::acquire(&path, None).map(|_| ()))
+        };
+        let error = other.join().unwrap().unwrap_err().to_string();
+        assert!(
+            error.starts_with("Another import is in progress"),
+            "{error}"
+        );
+
+        drop(lock);
+        std::thread::spawn(move || ImportLock::acquire(&path, None).map(|_| ()))
+            .join()
+            .unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let path = dir.path().to_path_buf();
+        let (locked, wait_for_lock) = mpsc::channel();
+        let (release, wait_for_release) = mpsc::channel::<()>();
+
+        let holder = {
+            let path = path.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let _lock = ImportLock::acquire(&path, None)?;
+                locked.send(())?;
+                let _ = wait_for_release.recv();
+                std::thread::sleep(Duration::from_millis(200));
+                Ok(())
+            })
+        };
+        wait_for_lock.recv()?;
+
+        let error = ImportLock::acquire(&path, Some(Duration::from_millis(200)))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Another import is still in progress after waiting"),
+            "{error}"
+        );
+
+        release.send(())?;
+        ImportLock::acquire(&path, Some(Duration::MAX))?;
+        holder.join().unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn released_on_panic() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let path = dir.path().to_path_buf();
+
+        let holder = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let _lock = ImportLock::acquire(&path, None).unwrap();
+                panic!("import failed");
+            })
+        };
+        assert!(holder.join().is_err());
+
+        ImportLock::acquire(&path, None)?;
+        assert_eq!(true, path.join(FILENAME).exists());
+
+        Ok(())
+    }
+}
@@ -1,20 +1,76 @@
 use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use super::{parse_date_fmt, parse_decimal, Importer, Options, Profile, RecordToImport};
+use super::{progress::Unit, Importer, Options, Profile, RecordToImport};
+use crate::cli::import::ConfigurationKey;
 
-use finnel::prelude::*;
+use finnel::{parse, prelude::*};
 
 use anyhow::Result;
-use regex::Regex;
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
 
 pub struct Logseq {
     entries: BTreeSet<PathBuf>,
     regex: Regex,
+    sign_convention: SignConvention,
 }
 
 const FORMAT: &str = "%Y_%m_%d.md";
 
+/// How the sign of an amount in the notes maps to the direction of the record
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SignConvention {
+    /// Bare amounts are expenses, negative ones are incomes
+    #[default]
+    ExpensePositive,
+    /// Negative amounts are expenses, bare ones are incomes
+    ExpenseNegative,
+}
+
+impl SignConvention {
+    pub fn read(options: &Options) -> Result<Self> {
+        Ok(options
+            .profile_info
+            .configuration(options.config, ConfigurationKey::SignConvention)?
+            .map(|value| value.parse())
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub fn direction(&self, negative: bool) -> Direction {
+        match (self, negative) {
+            (SignConvention::ExpensePositive, false) => Direction::Debit,
+            (SignConvention::ExpensePositive, true) => Direction::Credit,
+            (SignConvention::ExpenseNegative, false) => Direction::Credit,
+            (SignConvention::ExpenseNegative, true) => Direction::Debit,
+        }
+    }
+
+    pub fn inverted(&self) -> Self {
+        match self {
+            SignConvention::ExpensePositive => SignConvention::ExpenseNegative,
+            SignConvention::ExpenseNegative => SignConvention::ExpensePositive,
+        }
+    }
+}
+
+impl FromStr for SignConvention {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "expense-positive" => Ok(SignConvention::ExpensePositive),
+            "expense-negative" => Ok(SignConvention::ExpenseNegative),
+            _ => anyhow::bail!(
+                "Invalid value for sign_convention: {}, expected expense-positive or expense-negative",
+                value
+            ),
+        }
+    }
+}
+
 impl Logseq {
     pub fn new(options: &Options) -> Result<Self> {
         let mut entries = BTreeSet::new();
@@ -40,6 +96,7 @@ impl Logseq {
 
         Ok(Logseq {
             entries,
+            sign_convention: SignConvention::read(options)?,
             ..Self::empty()?
         })
     }
@@ -47,11 +104,15 @@ impl Logseq {
     fn empty() -> Result<Self> {
         Ok(Logseq {
             entries: Default::default(),
+            sign_convention: Default::default(),
+            // A ± before the sign inverts the configured sign convention for
+            // this line only, e.g. to note an income among expenses
             regex: Regex::new(
                 r#"(?xm)
                 ^
                 -[[:blank:]]*
                 (?:DONE[[:blank:]]*)?
+                (?<invert>±?)
                 (?<sign>[+-]?)
                 (?<amount>\d+(?:[,.]\d+)?)
                 (?<currency>[€])
@@ -75,34 +136,22 @@ impl Logseq {
         let Some(Ok(date)) = path
             .file_name()
             .and_then(|os_str| os_str.to_str())
-            .map(|date| parse_date_fmt(date, FORMAT))
+            .map(|date| parse::date(date, FORMAT))
         else {
-            anyhow::bail!("Unable to parse date from {}", path.display());
+            return importer.reject_row(
+                path.display().to_string(),
+                anyhow::anyhow!("Unable to parse date from {}", path.display()),
+            );
         };
         let content = std::fs::read_to_string(path)?;
 
         for captures in self.regex.captures_iter(&content) {
-            match &captures["currency"] {
-                "€" => {}
-                _ => anyhow::bail!("Unknown currency {}", &captures["currency"]),
-            }
-
-            let category = captures.name("category").map(|m| m.as_str()).unwrap_or("");
-            let merchant = captures.name("merchant").map(|m| m.as_str()).unwrap_or("");
-
-            let record = RecordToImport {
-                operation_date: date,
-                value_date: date,
-                amount: parse_decimal(&captures["amount"])?,
-                direction: match &captures["sign"] {
-                    "" | "+" => Direction::Debit,
-                    "-" => Direction::Credit,
-                    _ => anyhow::bail!("Unknown sign {}", &captures["sign"]),
-                },
-                details: captures["details"].trim().to_string(),
-                category_name: category.trim().to_string(),
-                merchant_name: merchant.trim().to_string(),
-                ..Default::default()
+            let record = match self.parse_line(date, &captures) {
+                Ok(record) => record,
+                Err(e) => {
+                    importer.reject_row(captures[0].to_string(), e)?;
+                    continue;
+                }
             };
 
             importer.add_category(&record.category_name)?;
@@ -113,15 +162,54 @@ impl Logseq {
 
         Ok(())
     }
+
+    /// Record of a line of the notes of the day
+    fn parse_line(&self, date: NaiveDate, captures: &Captures) -> Result<RecordToImport> {
+        let currency = match &captures["currency"] {
+            "€" => Currency::EUR,
+            _ => anyhow::bail!("Unknown currency {}", &captures["currency"]),
+        };
+
+        let negative = match &captures["sign"] {
+            "" | "+" => false,
+            "-" => true,
+            _ => anyhow::bail!("Unknown sign {}", &captures["sign"]),
+        };
+        let convention = if captures["invert"].is_empty() {
+            self.sign_convention
+        } else {
+            self.sign_convention.inverted()
+        };
+
+        let category = captures.name("category").map(|m| m.as_str()).unwrap_or("");
+        let merchant = captures.name("merchant").map(|m| m.as_str()).unwrap_or("");
+
+        Ok(RecordToImport {
+            operation_date: date,
+            value_date: date,
+            amount: parse::decimal(&captures["amount"])?,
+            currency: Some(currency),
+            direction: convention.direction(negative),
+            details: captures["details"].trim().to_string(),
+            category_name: category.trim().to_string(),
+            merchant_name: merchant.trim().to_string(),
+            ..Default::default()
+        })
+    }
 }
 
 impl Profile for Logseq {
     fn run(&mut self, importer: &mut Importer) -> Result<()> {
         for path in &self.entries {
             self.read(importer, path)?;
+            importer.file_read();
         }
         Ok(())
     }
+
+    fn total(&self) -> Option<(usize, Unit)> {
+        Some((self.entries.len(), Unit::Files))
+    }
 }
 
 #[cfg(test)]
@@ -150,6 +238,7 @@ mod tests {
                 writeln!(file, "- -10€ avance")?;
                 writeln!(file, "- 5€ - beer")?;
                 writeln!(file, "- 5€ -- mc do")?;
+                writeln!(file, "- ±20€ refund")?;
             }
 
             logseq.read(importer, path.as_path())?;
@@ -166,10 +255,10 @@ mod tests {
                 record.fetch_merchant(conn)?.map(|m| m.name).as_deref()
             );
             assert_eq!(
-                parse_date_fmt("2024-07-31", "%Y-%m-%d")?,
+                parse::date("2024-07-31", "%Y-%m-%d")?,
                 record.operation_date
             );
-            assert_eq!(parse_date_fmt("2024-07-31", "%Y-%m-%d")?, record.value_date);
+            assert_eq!(parse::date("2024-07-31", "%Y-%m-%d")?, record.value_date);
             assert_eq!(Direction::Debit, record.direction);
 
             let record = Record::find(conn, 2)?;
@@ -201,6 +290,107 @@ mod tests {
                 record.fetch_merchant(conn)?.map(|c| c.name).as_deref()
             );
 
+            let record = Record::find(conn, 6)?;
+            assert_eq!(Decimal::new(20, 0), record.amount);
+            assert_eq!("refund", record.details);
+            assert_eq!(Direction::Credit, record.direction);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expense_negative() -> Result<()> {
+        with_default_importer(|importer| {
+            let conn = &mut importer.options.config.database()?;
+            let key = ConfigurationKey::SignConvention;
+
+            let profile_info = &importer.options.profile_info;
+            profile_info.set_configuration(importer.options.config, key, Some("foo"))?;
+            assert!(SignConvention::read(&importer.options).is_err());
+
+            profile_info.set_configuration(
+                importer.options.config,
+                key,
+                Some("expense-negative"),
+            )?;
+            let logseq = Logseq {
+                sign_convention: SignConvention::read(&importer.options)?,
+                ..Logseq::empty()?
+            };
+            assert_eq!(SignConvention::ExpenseNegative, logseq.sign_convention);
+
+            let dir = importer.options.config.data_dir.as_path();
+            let path = dir.join("2024_08_01.md");
+            {
+                let mut file = File::create(path.as_path())?;
+                writeln!(file, "- -12€ lunch - food")?;
+                writeln!(file, "- 1500€ salary")?;
+                writeln!(file, "- +3€ found")?;
+                writeln!(file, "- ±7,5€ snack")?;
+            }
+
+            logseq.read(importer, path.as_path())?;
+
+            let record = Record::find(conn, 1)?;
+            assert_eq!(Decimal::new(12, 0), record.amount);
+            assert_eq!(Direction::Debit, record.direction);
+
+            let record = Record::find(conn, 2)?;
+            assert_eq!(Decimal::new(1500, 0), record.amount);
+            assert_eq!(Direction::Credit, record.direction);
+
+            let record = Record::find(conn, 3)?;
+            assert_eq!(Direction::Credit, record.direction);
+
+            let record = Record::find(conn, 4)?;
+            assert_eq!(Decimal::new(75, 1), record.amount);
+            assert_eq!(Direction::Debit, record.direction);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn skip_errors() -> Result<()> {
+        with_default_importer(|importer| {
+            let logseq = Logseq::empty()?;
+
+            let dir = importer.options.config.data_dir.as_path();
+            let path = dir.join("2024_08_02.md");
+            {
+                let mut file = File::create(path.as_path())?;
+                writeln!(file, "- 4€ coffee")?;
+                writeln!(file, "- 99999999999999999999999999999€ yacht")?;
+                writeln!(file, "- 12€ lunch")?;
+            }
+            let notes = dir.join("notes.md");
+            File::create(notes.as_path())?;
+
+            assert!(logseq.read(importer, path.as_path()).is_err());
+            assert!(logseq.read(importer, notes.as_path()).is_err());
+
+            importer.options.skip_errors = true;
+            logseq.read(importer, path.as_path())?;
+            logseq.read(importer, notes.as_path())?;
+
+            let rejected = importer
+                .rejected
+                .iter()
+                .map(|r| r.content.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    "- 99999999999999999999999999999€ yacht",
+                    notes.display().to_string().as_str()
+                ],
+                rejected
+            );
+            assert!(importer
+                .records
+                .iter()
+                .any(|record| record.details == "lunch"));
+
             Ok(())
         })
     }
@@ -2,23 +2,70 @@ use std::path::PathBuf;
 
 use finnel::prelude::*;
 
-use super::{Information, Profile};
+use super::{Information, Profile, Progress};
 use crate::cli::import::*;
 use crate::config::Config;
+use crate::utils::max_amount::MaxAmount;
 
 use anyhow::Result;
 use chrono::{Days, NaiveDate, Utc};
 
-#[derive(Clone, Debug)]
+/// Text searched in the details of a row to detect a fee, unless configured
+const DEFAULT_FEE_PATTERN: &str = "frais";
+
+/// Where the account records are imported into comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountSource {
+    /// The `-A` option
+    Cli,
+    /// The default account configured on the profile
+    Profile,
+    /// The global default account
+    Default,
+}
+
+impl std::fmt::Display for AccountSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AccountSource::Cli => "cli",
+            AccountSource::Profile => "profile",
+            AccountSource::Default => "default",
+        })
+    }
+}
+
+#[derive(Debug)]
 pub struct Options<'a> {
     pub config: &'a Config,
     pub file: Option<String>,
     pub profile_info: Information,
     pub from: Option<NaiveDate>,
+    /// The start date follows the last import, the rows before it were
+    /// already imported
+    pub from_last_imported: bool,
     pub to: Option<NaiveDate>,
     pub print: bool,
     pub pretend: bool,
     pub action: Option<ConfigurationAction>,
+    pub skip_zero_amount: bool,
+    pub merge_fee_rows: bool,
+    pub fee_pattern: String,
+    /// Import records in another currency than the account's one as if they
+    /// were in the account's currency
+    pub coerce_currency: bool,
+    /// Rows with a larger amount fail the import, unless allowed
+    pub max_amount: MaxAmount,
+    pub allow_large_amounts: bool,
+    /// Rows failing to import are collected instead of aborting the import
+    pub skip_errors: bool,
+    /// Account numbers and the name of the account to import their rows
+    /// into, from the account_map setting then the --map-account options
+    pub account_map: Vec<(String, String)>,
+    /// Rows of an account number not in the map fail the import instead of
+    /// being skipped
+    pub strict_accounts: bool,
+    /// Notified as the rows are read, nothing is reported when unset
+    pub progress: Option<Box<dyn Progress>>,
 }
 
 impl<'a> Options<'a> {
@@ -28,10 +75,21 @@ impl<'a> Options<'a> {
             file: Default::default(),
             profile_info: Default::default(),
             from: Default::default(),
+            from_last_imported: false,
             to: Default::default(),
             print: false,
             pretend: false,
             action: None,
+            skip_zero_amount: true,
+            merge_fee_rows: false,
+            fee_pattern: DEFAULT_FEE_PATTERN.to_string(),
+            coerce_currency: false,
+            max_amount: Default::default(),
+            allow_large_amounts: false,
+            skip_errors: false,
+            account_map: Vec::new(),
+            strict_accounts: false,
+            progress: None,
         }
     }
 
@@ -66,15 +124,48 @@ impl<'a> Options<'a> {
             }
         };
 
+        let skip_zero_amount = profile_info
+            .flag(config, ConfigurationKey::SkipZeroAmount)?
+            .unwrap_or(true);
+        let merge_fee_rows = profile_info
+            .flag(config, ConfigurationKey::MergeFeeRows)?
+            .unwrap_or(false);
+        let fee_pattern = profile_info
+            .configuration(config, ConfigurationKey::FeePattern)?
+            .unwrap_or_else(|| DEFAULT_FEE_PATTERN.to_string());
+
+        let mut account_map =
+            match profile_info.configuration(config, ConfigurationKey::AccountMap)? {
+                Some(value) => parse_account_map(&value)?,
+                None => Vec::new(),
+            };
+        for (number, account) in &cli.map_account {
+            account_map.retain(|(n, _)| n != number);
+            account_map.push((number.clone(), account.clone()));
+        }
+
         Ok(Self {
-            config,
             file: cli.file.clone(),
             profile_info,
             from,
+            from_last_imported: cli.from.is_none() && from.is_some(),
             to: cli.to.or(Some(today)),
             print: cli.print,
             pretend: cli.pretend,
-            action: cli.configuration_action.clone(),
+            action: match &cli.action {
+                Some(Action::Config(action)) => Some(action.clone()),
+                _ => None,
+            },
+            skip_zero_amount,
+            merge_fee_rows,
+            fee_pattern,
+            coerce_currency: cli.coerce_currency,
+            max_amount: MaxAmount::load(config)?,
+            allow_large_amounts: cli.allow_large_amounts,
+            skip_errors: cli.skip_errors,
+            account_map,
+            strict_accounts: cli.strict_accounts,
+            ..Self::new(config)
         })
     }
 
@@ -114,6 +205,13 @@ impl<'a> Options<'a> {
                     self.profile_info
                         .set_configuration(self.config, key, Some(account.name))?;
                 }
+                AccountMap => {
+                    for (_, account) in parse_account_map(value)? {
+                        Account::find_by_name(conn, account.as_str())?;
+                    }
+                    self.profile_info
+                        .set_configuration(self.config, key, Some(value))?;
+                }
                 _ => {
                     self.profile_info
                         .set_configuration(self.config, key, Some(value))?;
@@ -142,13 +240,47 @@ impl<'a> Options<'a> {
         }
     }
 
-    pub fn account(&self, conn: &mut Conn) -> Result<Account> {
-        if let Some(account) = self.config.account_or_default(conn)? {
-            Ok(account)
+    /// Account to import into, from the `-A` option, then the default
+    /// account of the profile, then the global default account
+    pub fn account(&self, conn: &mut Conn) -> Result<(Account, AccountSource)> {
+        let (account, source) = if let Some(account) = self.config.account(conn)? {
+            (account, AccountSource::Cli)
+        } else if let Some(account) = self.default_account(conn)? {
+            (account, AccountSource::Profile)
+        } else if let Some(account) = self.config.default_account(conn)? {
+            (account, AccountSource::Default)
         } else {
-            self.default_account(conn)?
-                .ok_or(anyhow::anyhow!("Account not provided"))
+            anyhow::bail!("Account not provided");
+        };
+
+        self.check_currency(&account)?;
+        Ok((account, source))
+    }
+
+    /// Fail if the profile imports records in another currency than the one
+    /// of the account, unless coercing it
+    pub fn check_currency(&self, account: &Account) -> Result<()> {
+        if let Some(currency) = self.profile_info.currency() {
+            if currency != account.currency && !self.coerce_currency {
+                anyhow::bail!(
+                    "Account {} is in {}, but profile {} imports records in {}, \
+                     use --coerce-currency to import them anyway",
+                    account.name,
+                    account.currency.code(),
+                    self.profile_info.name()?,
+                    currency.code()
+                );
+            }
         }
+        Ok(())
+    }
+
+    /// Name of the account the rows of the account number are imported into
+    pub fn mapped_account(&self, number: &str) -> Option<&str> {
+        self.account_map
+            .iter()
+            .find(|(n, _)| n == number)
+            .map(|(_, account)| account.as_str())
     }
 
     pub fn last_imported(&self) -> Result<Option<NaiveDate>> {
@@ -171,6 +303,14 @@ impl<'a> Options<'a> {
         self.profile_info.set_last_imported(self.config, date)
     }
 
+    /// Whether the details of the row match the configured fee pattern
+    pub fn is_fee(&self, details: &str) -> bool {
+        !self.fee_pattern.is_empty()
+            && details
+                .to_lowercase()
+                .contains(&self.fee_pattern.to_lowercase())
+    }
+
     pub fn default_account(&self, conn: &mut Conn) -> Result<Option<Account>> {
         if let Some(account) = self
             .profile_info
@@ -183,6 +323,18 @@ impl<'a> Options<'a> {
     }
 }
 
+/// Read the comma-separated NUM=ACCOUNT pairs of the account_map setting
+fn parse_account_map(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            parse_account_mapping(pair)
+                .map_err(|e| anyhow::anyhow!("Invalid value for account_map: {e}"))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +371,31 @@ mod tests {
         )
     }
 
+    #[test]
+    fn account_map() -> Result<()> {
+        with_config_args(
+            &["import", "-P", "Test", "FILE", "--map-account", "2=Cli"],
+            |config| {
+                let Some(Commands::Import(command)) = config.command() else {
+                    panic!("Unexpected CLI parse")
+                };
+                Information::Test.set_configuration(
+                    config,
+                    ConfigurationKey::AccountMap,
+                    Some("1=Profile, 2=Replaced"),
+                )?;
+
+                let options = Options::try_from(command, config)?;
+                assert_eq!(Some("Profile"), options.mapped_account("1"));
+                assert_eq!(Some("Cli"), options.mapped_account("2"));
+                assert_eq!(None, options.mapped_account("3"));
+
+                assert!(parse_account_map("1=Profile,2").is_err());
+                Ok(())
+            },
+        )
+    }
+
     #[test]
     fn use_last_imported_if_from_is_absent() -> Result<()> {
         with_config_args(&["import", "-P", "Test", "FILE"], |config| {
@@ -353,4 +530,77 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn account_precedence() -> Result<()> {
+        use AccountSource::*;
+
+        for (cli, profile, default, expected) in [
+            (true, true, true, Some(Cli)),
+            (true, true, false, Some(Cli)),
+            (true, false, true, Some(Cli)),
+            (true, false, false, Some(Cli)),
+            (false, true, true, Some(Profile)),
+            (false, true, false, Some(Profile)),
+            (false, false, true, Some(Default)),
+            (false, false, false, None),
+        ] {
+            let args: &[&str] = if cli { &["-A", "Cli"] } else { &[] };
+            with_config_args(args, |config| {
+                let conn = &mut config.database()?;
+                for name in ["Cli", "Profile", "Default"] {
+                    test::account!(conn, name);
+                }
+
+                let options = Options::new(config);
+                if profile {
+                    options.profile_info.set_configuration(
+                        config,
+                        ConfigurationKey::DefaultAccount,
+                        Some("Profile"),
+                    )?;
+                }
+                if default {
+                    config.store()?.set("default_account", "Default")?;
+                }
+
+                match expected {
+                    Some(source) => {
+                        let (account, resolved) = options.account(conn)?;
+                        assert_eq!(source, resolved);
+                        assert_eq!(source.to_string(), account.name.to_lowercase());
+                    }
+                    None => assert!(options.account(conn).is_err()),
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn account_currency() -> Result<()> {
+        with_config_args(&["-A", "Dollars"], |config| {
+            let conn = &mut config.database()?;
+            test::account!(conn, "Dollars", currency: Currency::USD);
+
+            let mut options = Options::new(config);
+            assert_eq!(AccountSource::Cli, options.account(conn)?.1);
+
+            options.profile_info = Information::Boursobank;
+            let error = options.account(conn).unwrap_err();
+            assert_eq!(
+                "Account Dollars is in USD, but profile boursobank imports records in EUR, \
+                 use --coerce-currency to import them anyway",
+                error.to_string()
+            );
+
+            options.coerce_currency = true;
+            assert_eq!("Dollars", options.account(conn)?.0.name);
+
+            Ok(())
+        })
+    }
 }
@@ -1,21 +1,54 @@
 use std::borrow::Borrow;
+use std::collections::BTreeSet;
 use std::str::FromStr;
 
-use super::{Boursobank, Importer, Logseq, Options};
+use super::progress::Unit;
+use super::{Boursobank, Camt053, Importer, Logseq, Options};
 use crate::cli::import::ConfigurationKey;
-use crate::config::Config;
+use crate::config::{Config, ConfigStore};
 
 use anyhow::Result;
 use chrono::NaiveDate;
+use finnel::prelude::Currency;
+
+/// Names of the categories and merchants read in the file, possibly more
+/// than the ones the rows end up using
+#[derive(Debug, Default)]
+pub struct Names {
+    pub categories: BTreeSet<String>,
+    pub merchants: BTreeSet<String>,
+}
 
 pub trait Profile {
     fn run(&mut self, importer: &mut Importer) -> Result<()>;
+
+    /// Names found by a first pass over the file, when it is cheap to make,
+    /// so the importer can find them all at once before running
+    fn scan(&mut self, _importer: &Importer) -> Result<Option<Names>> {
+        Ok(None)
+    }
+
+    /// Number of rows, or files, to read when it is cheap to know before
+    /// running, to report the progress against
+    fn total(&self) -> Option<(usize, Unit)> {
+        None
+    }
+
+    /// First line of the file of rejected rows, for the profiles reading a
+    /// file with a header
+    fn header(&self) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Information {
     Logseq,
     Boursobank,
+    Camt053,
+    /// Database of a version of finnel before diesel, recreated as a whole
+    /// instead of imported into an account
+    LegacyFinnel,
     None,
     #[cfg(test)]
     Test,
@@ -37,6 +70,8 @@ impl FromStr for Information {
         match name.to_lowercase().as_str() {
             "logseq" => Ok(Information::Logseq),
             "boursobank" => Ok(Information::Boursobank),
+            "camt053" | "camt.053" => Ok(Information::Camt053),
+            "legacy-finnel" => Ok(Information::LegacyFinnel),
             #[cfg(test)]
             "test" => Ok(Information::Test),
             _ => anyhow::bail!("Unknown profile '{}'", name),
@@ -48,7 +83,11 @@ impl Information {
     pub fn new_profile(&self, options: &Options) -> Result<Box<dyn Profile>> {
         Ok(match self {
             Information::Boursobank => Box::new(Boursobank::new(options)?),
+            Information::Camt053 => Box::new(Camt053::new(options)?),
             Information::Logseq => Box::new(Logseq::new(options)?),
+            Information::LegacyFinnel => {
+                anyhow::bail!("legacy-finnel doesn't import into an account")
+            }
             Information::None => anyhow::bail!("Profile not set"),
             #[cfg(test)]
             Information::Test => anyhow::bail!("test profile"),
@@ -58,13 +97,26 @@ impl Information {
     pub fn name(&self) -> Result<&str> {
         Ok(match self {
             Information::Boursobank => "boursobank",
+            Information::Camt053 => "camt053",
             Information::Logseq => "logseq",
+            Information::LegacyFinnel => "legacy-finnel",
             Information::None => anyhow::bail!("Profile not set"),
             #[cfg(test)]
             Information::Test => "test",
         })
     }
 
+    /// Currency of all the records read by the profile, when its files
+    /// can't hold any other
+    pub fn currency(&self) -> Option<Currency> {
+        match self {
+            Information::Boursobank | Information::Logseq => Some(Currency::EUR),
+            Information::Camt053 | Information::LegacyFinnel | Information::None => None,
+            #[cfg(test)]
+            Information::Test => None,
+        }
+    }
+
     pub fn last_imported(&self, config: &Config) -> Result<Option<NaiveDate>> {
         Ok(self
             .get(config, "last_imported")?
@@ -97,6 +149,20 @@ impl Information {
         self.get(config, key.borrow().as_str())
     }
 
+    pub fn flag<T>(&self, config: &Config, key: T) -> Result<Option<bool>>
+    where
+        T: Borrow<ConfigurationKey>,
+    {
+        let key = key.borrow();
+        self.configuration(config, key)?
+            .map(|value| match value.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(true),
+                "false" | "no" | "0" => Ok(false),
+                _ => anyhow::bail!("Invalid value for {}: {}", key.as_str(), value),
+            })
+            .transpose()
+    }
+
     pub fn set_configuration<T, U>(&self, config: &Config, key: T, value: Option<U>) -> Result<()>
     where
         T: Borrow<ConfigurationKey>,
@@ -110,15 +176,19 @@ impl Information {
     }
 
     fn get(&self, config: &Config, key: &str) -> Result<Option<String>> {
-        config.get(format!("{}/{}", self.name()?, key).as_str())
+        self.settings(config)?.get(key)
     }
 
     fn set(&self, config: &Config, key: &str, value: &str) -> Result<()> {
-        config.set(format!("{}/{}", self.name()?, key).as_str(), value)
+        self.settings(config)?.set(key, value)
     }
 
     fn reset(&self, config: &Config, key: &str) -> Result<()> {
-        config.reset(format!("{}/{}", self.name()?, key).as_str())
+        self.settings(config)?.reset(key)
+    }
+
+    fn settings(&self, config: &Config) -> Result<ConfigStore> {
+        config.store()?.scoped(self.name()?)
     }
 }
 
@@ -130,6 +200,8 @@ mod tests {
     #[test]
     fn parse() -> Result<()> {
         assert_eq!(Information::Boursobank, "Boursobank".parse()?);
+        assert_eq!(Information::Camt053, "camt.053".parse()?);
+        assert_eq!(Information::LegacyFinnel, "Legacy-Finnel".parse()?);
         assert!("".parse::<Information>().is_err());
 
         Ok(())
@@ -0,0 +1,138 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Delay between two updates of the line on the terminal
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay between two log lines when stderr isn't a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What the total announced by a profile counts
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unit {
+    Rows,
+    /// Files of a directory, each holding any number of rows
+    Files,
+}
+
+/// How far an import went
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct State {
+    /// Rows read, whether they were imported or not
+    pub rows: usize,
+    /// Files read, only for the profiles reading a directory
+    pub files: usize,
+    pub total: Option<(usize, Unit)>,
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.total {
+            Some((total, Unit::Rows)) => write!(f, "{}/{} rows", self.rows, total)?,
+            Some((total, Unit::Files)) => {
+                write!(f, "{}/{} files, {} rows", self.files, total, self.rows)?
+            }
+            None => write!(f, "{} rows", self.rows)?,
+        }
+        write!(f, " ({} imported, {} skipped)", self.imported, self.skipped)
+    }
+}
+
+/// Receiver of the state of an import as its rows are read
+pub trait Progress: std::fmt::Debug {
+    fn tick(&mut self, state: &State);
+
+    /// Called once all the rows were read
+    fn finish(&mut self, _state: &State) {}
+}
+
+/// Report the progress on stderr, on a single line updated in place when it
+/// is a terminal, or with periodic log lines otherwise
+#[derive(Debug)]
+pub struct Reporter {
+    terminal: bool,
+    last_report: Instant,
+    reported: bool,
+}
+
+impl Reporter {
+    pub fn new() -> Self {
+        Self {
+            terminal: std::io::stderr().is_terminal(),
+            // Don't bother reporting imports done in the blink of an eye
+            last_report: Instant::now(),
+            reported: false,
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        if self.terminal {
+            TERMINAL_INTERVAL
+        } else {
+            LOG_INTERVAL
+        }
+    }
+}
+
+impl Progress for Reporter {
+    fn tick(&mut self, state: &State) {
+        if self.last_report.elapsed() < self.interval() {
+            return;
+        }
+        self.last_report = Instant::now();
+        self.reported = true;
+
+        if self.terminal {
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "\r{state}\x1b[K");
+            let _ = stderr.flush();
+        } else {
+            log::info!("Importing: {state}");
+        }
+    }
+
+    fn finish(&mut self, state: &State) {
+        if !self.terminal {
+            log::info!("Imported: {state}");
+        } else if self.reported {
+            eprintln!("\r{state}\x1b[K");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::assert_eq;
+
+    #[test]
+    fn display() {
+        let state = State {
+            rows: 123,
+            imported: 45,
+            skipped: 3,
+            ..Default::default()
+        };
+        assert_eq!("123 rows (45 imported, 3 skipped)", state.to_string());
+
+        assert_eq!(
+            "123/5000 rows (45 imported, 3 skipped)",
+            State {
+                total: Some((5000, Unit::Rows)),
+                ..state.clone()
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "2/12 files, 123 rows (45 imported, 3 skipped)",
+            State {
+                files: 2,
+                total: Some((12, Unit::Files)),
+                ..state
+            }
+            .to_string()
+        );
+    }
+}
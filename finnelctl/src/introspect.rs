@@ -0,0 +1,200 @@
+use std::any::TypeId;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Arg, ArgAction, ArgGroup, Command, CommandFactory};
+use serde::Serialize;
+
+use finnel::{Currency, Decimal};
+
+use crate::cli::{introspect::*, Cli};
+
+/// Print the description of every command, built from the same definition
+/// clap parses the arguments with
+pub fn run(args: &Arguments) -> Result<()> {
+    let schema = schema();
+
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&schema)?),
+    }
+
+    Ok(())
+}
+
+fn schema() -> CommandSchema {
+    let mut command = Cli::command();
+    // Propagate the global arguments and add the generated help subcommands
+    command.build();
+
+    CommandSchema::new(&command)
+}
+
+#[derive(Debug, Serialize)]
+struct CommandSchema {
+    name: String,
+    about: Option<String>,
+    long_about: Option<String>,
+    aliases: Vec<String>,
+    hidden: bool,
+    subcommand_required: bool,
+    /// Whether unknown subcommands are accepted and passed along
+    external_subcommands: bool,
+    args: Vec<ArgSchema>,
+    groups: Vec<GroupSchema>,
+    subcommands: Vec<CommandSchema>,
+}
+
+impl CommandSchema {
+    fn new(command: &Command) -> Self {
+        Self {
+            name: command.get_name().to_string(),
+            about: command.get_about().map(ToString::to_string),
+            long_about: command.get_long_about().map(ToString::to_string),
+            aliases: command.get_all_aliases().map(String::from).collect(),
+            hidden: command.is_hide_set(),
+            subcommand_required: command.is_subcommand_required_set(),
+            external_subcommands: command.is_allow_external_subcommands_set(),
+            args: command.get_arguments().map(ArgSchema::new).collect(),
+            groups: command.get_groups().map(GroupSchema::new).collect(),
+            subcommands: command.get_subcommands().map(CommandSchema::new).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ArgSchema {
+    id: String,
+    long: Option<String>,
+    short: Option<char>,
+    aliases: Vec<String>,
+    positional: bool,
+    required: bool,
+    global: bool,
+    hidden: bool,
+    /// Whether the argument can be given several times or take several
+    /// values
+    multiple: bool,
+    value_type: &'static str,
+    value_names: Vec<String>,
+    possible_values: Vec<PossibleValueSchema>,
+    default_values: Vec<String>,
+    help: Option<String>,
+    long_help: Option<String>,
+    help_heading: Option<String>,
+}
+
+impl ArgSchema {
+    fn new(arg: &Arg) -> Self {
+        let takes_values = arg.get_action().takes_values();
+        let possible_values = if takes_values {
+            arg.get_possible_values()
+                .iter()
+                .map(|value| PossibleValueSchema {
+                    name: value.get_name().to_string(),
+                    aliases: value
+                        .get_name_and_aliases()
+                        .skip(1)
+                        .map(String::from)
+                        .collect(),
+                    help: value.get_help().map(ToString::to_string),
+                    hidden: value.is_hide_set(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            id: arg.get_id().to_string(),
+            long: arg.get_long().map(String::from),
+            short: arg.get_short(),
+            aliases: arg
+                .get_all_aliases()
+                .unwrap_or_default()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            positional: arg.is_positional(),
+            required: arg.is_required_set(),
+            global: arg.is_global_set(),
+            hidden: arg.is_hide_set(),
+            multiple: matches!(arg.get_action(), ArgAction::Append | ArgAction::Count)
+                || arg
+                    .get_num_args()
+                    .is_some_and(|range| range.max_values() > 1),
+            value_type: value_type(arg, !possible_values.is_empty()),
+            value_names: arg
+                .get_value_names()
+                .unwrap_or_default()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            possible_values,
+            default_values: arg
+                .get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect(),
+            help: arg.get_help().map(ToString::to_string),
+            long_help: arg.get_long_help().map(ToString::to_string),
+            help_heading: arg.get_help_heading().map(String::from),
+        }
+    }
+}
+
+/// Name of the type of the values, falling back to string for the types
+/// parsed from their textual representation, like identifiers
+fn value_type(arg: &Arg, has_possible_values: bool) -> &'static str {
+    match arg.get_action() {
+        ArgAction::Count => return "count",
+        action if !action.takes_values() => return "flag",
+        _ if has_possible_values => return "enum",
+        _ => {}
+    }
+
+    let type_id = arg.get_value_parser().type_id();
+    [
+        (TypeId::of::<PathBuf>(), "path"),
+        (TypeId::of::<NaiveDate>(), "date"),
+        (TypeId::of::<Decimal>(), "decimal"),
+        (TypeId::of::<Currency>(), "currency"),
+        (TypeId::of::<i32>(), "integer"),
+        (TypeId::of::<i64>(), "integer"),
+        (TypeId::of::<u32>(), "integer"),
+        (TypeId::of::<u64>(), "integer"),
+        (TypeId::of::<usize>(), "integer"),
+    ]
+    .into_iter()
+    .find(|(id, _)| type_id == *id)
+    .map(|(_, name)| name)
+    .unwrap_or("string")
+}
+
+#[derive(Debug, Serialize)]
+struct PossibleValueSchema {
+    name: String,
+    aliases: Vec<String>,
+    help: Option<String>,
+    hidden: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupSchema {
+    id: String,
+    args: Vec<String>,
+    required: bool,
+    multiple: bool,
+}
+
+impl GroupSchema {
+    fn new(group: &ArgGroup) -> Self {
+        Self {
+            id: group.get_id().to_string(),
+            args: group.get_args().map(ToString::to_string).collect(),
+            required: group.is_required_set(),
+            // Only exposed through a mutable borrow
+            multiple: group.clone().is_multiple(),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use finnel::{diff::FieldDiff, record::Record};
+
+use crate::cli::journal::Command;
+use crate::config::Config;
+
+/// Name of the journal in the data directory
+pub const FILENAME: &str = "journal.jsonl";
+
+/// Operation changing the database, one JSON object per line of the journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    /// Command and subcommand, like `record update`
+    pub command: String,
+    /// Arguments once resolved, e.g. ids instead of names
+    pub arguments: Value,
+    /// Entities created, changed or deleted
+    pub ids: Vec<i64>,
+}
+
+/// Append-only log of the write operations, to sync or debug the database,
+/// enabled with the `journal/enabled` setting
+///
+/// Each entry is written with a single write to a file opened for appending,
+/// so that concurrent invocations never interleave their lines.
+pub struct Journal {
+    path: Option<PathBuf>,
+}
+
+impl Journal {
+    pub fn new(config: &Config) -> Result<Self> {
+        let enabled = match config.store()?.scoped("journal")?.get("enabled")? {
+            Some(value) => value.trim().parse::<bool>().unwrap_or_else(|_| {
+                eprintln!("Warning: ignoring journal/enabled, expected true or false");
+                false
+            }),
+            None => false,
+        };
+
+        Ok(Self {
+            path: enabled.then(|| config.data_dir.join(FILENAME)),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Append the operation, only warning when it fails as the database has
+    /// already changed
+    pub fn log<I>(&self, command: &str, arguments: Value, ids: I)
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let entry = Entry {
+            at: Utc::now(),
+            command: command.to_owned(),
+            arguments,
+            ids: ids.into_iter().collect(),
+        };
+        if let Err(e) = append(path, &entry) {
+            eprintln!("Warning: unable to journal {command}: {e:#}");
+        }
+    }
+}
+
+fn append(path: &Path, entry: &Entry) -> Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Unable to write {}", path.display()))
+}
+
+/// Arguments of a created record, with its category and merchant by id
+pub fn record(record: &Record) -> Value {
+    json!({
+        "account": record.account_id,
+        "amount": record.amount.to_string(),
+        "currency": record.currency.to_string(),
+        "operation_date": record.operation_date,
+        "value_date": record.value_date,
+        "direction": record.direction.to_string(),
+        "mode": record.mode.to_string(),
+        "details": record.details,
+        "category": record.category_id,
+        "merchant": record.merchant_id,
+    })
+}
+
+/// Arguments of an update, the new values of the fields it modifies
+pub fn changes(fields: &[FieldDiff]) -> Value {
+    fields
+        .iter()
+        .map(|(field, _, new)| (field.to_string(), Value::from(new.clone())))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Entries of the journal, oldest first
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Unable to read {}", path.display()));
+        }
+    };
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid entry at line {} of {}", i + 1, path.display()))
+        })
+        .collect()
+}
+
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    match command {
+        Command::Show { tail } => {
+            let path = config.data_dir.join(FILENAME);
+            let entries = read(&path)?;
+            if entries.is_empty() {
+                if !Journal::new(config)?.is_enabled() {
+                    eprintln!("Warning: the journal is disabled, enable it with `config set journal/enabled true`");
+                }
+                println!("No operations journaled");
+                return Ok(());
+            }
+
+            let skip = tail.map_or(0, |tail| entries.len().saturating_sub(tail));
+            for entry in &entries[skip..] {
+                let ids = entry
+                    .ids
+                    .iter()
+                    .map(i64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    entry.command,
+                    ids,
+                    entry.arguments
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn log() -> Result<()> {
+        with_config(|config| {
+            let path = config.data_dir.join(FILENAME);
+
+            // Disabled by default
+            Journal::new(config)?.log("account create", json!({}), [1]);
+            assert!(!path.exists());
+
+            config.store()?.scoped("journal")?.set("enabled", "true")?;
+            let journal = Journal::new(config)?;
+            journal.log("account create", json!({ "name": "Cash" }), [1]);
+            journal.log("record delete", json!({}), [4, 5]);
+
+            let entries = read(&path)?;
+            assert_eq!(2, entries.len());
+            assert_eq!("account create", entries[0].command);
+            assert_eq!(json!({ "name": "Cash" }), entries[0].arguments);
+            assert_eq!(vec![1], entries[0].ids);
+            assert_eq!("record delete", entries[1].command);
+            assert_eq!(vec![4, 5], entries[1].ids);
+            assert!(entries[0].at <= entries[1].at);
+
+            // One line per entry
+            assert_eq!(2, std::fs::read_to_string(&path)?.lines().count());
+
+            Ok(())
+        })
+    }
+}
@@ -4,14 +4,27 @@ use anyhow::Result;
 mod utils;
 
 mod account;
+mod backup;
+mod bugreport;
 mod calendar;
 mod category;
 mod cli;
+mod complete;
 mod config;
+mod consolidate;
+mod diff_db;
+mod error;
 mod import;
+mod introspect;
+mod journal;
 mod merchant;
+mod quick;
 mod record;
+mod recurring;
 mod report;
+mod stats;
+mod tag;
+mod workspace;
 
 #[cfg(test)]
 pub mod test;
@@ -19,30 +32,80 @@ pub mod test;
 use cli::Commands;
 use config::Config;
 
-fn main() -> Result<()> {
+include!(concat!(env!("OUT_DIR"), "/features.rs"));
+
+/// Exit code of `import --porcelain` when no records were imported, so a
+/// scheduled import can tell an empty file from a successful one
+const EXIT_NOTHING_IMPORTED: i32 = 3;
+
+/// Print the error like returning it from main would, but exit with the code
+/// of its class
+fn main() {
+    let Err(error) = run() else {
+        return;
+    };
+
+    // Help, version and usage errors exit with clap's own codes
+    if let Some(error) = error.downcast_ref::<clap::Error>() {
+        error.exit();
+    }
+
+    if is_busy(&error) {
+        eprintln!("Error: {}", finnel::Error::DatabaseBusy);
+    } else {
+        eprintln!("Error: {error:?}");
+    }
+    std::process::exit(error::exit_code(&error));
+}
+
+fn is_busy(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|e| matches!(e.downcast_ref(), Some(finnel::Error::DatabaseBusy)))
+}
+
+fn run() -> Result<()> {
     let config = Config::try_parse()?;
 
     setup_log(config.log_level_filter())?;
+    utils::table_display::load_emoji(&config)?;
+    utils::load_color(&config)?;
 
     if let Some(command) = config.command() {
         log::debug!("Executing {:?}", command);
         match command {
             Commands::Account(cmd) => account::run(&config, cmd)?,
             Commands::Record(cmd) => record::run(&config, cmd)?,
+            Commands::Quick(args) => quick::run(&config, args)?,
             Commands::Category(cmd) => category::run(&config, cmd)?,
             Commands::Merchant(cmd) => merchant::run(&config, cmd)?,
+            Commands::Tag(cmd) => tag::run(&config, cmd)?,
+            Commands::Recurring(cmd) => recurring::run(&config, cmd)?,
             Commands::Calendar(cmd) => calendar::run(&config, cmd)?,
             Commands::Report(cmd) => report::run(&config, cmd)?,
-            Commands::Import(cmd) => import::run(&config, cmd)?,
-            Commands::Consolidate { .. } => {
-                let conn = &mut config.database()?;
-                finnel::consolidate::consolidate(conn)?;
+            Commands::Stats(cmd) => stats::run(&config, cmd)?,
+            Commands::Import(cmd) => {
+                let outcome = import::run(&config, cmd)?;
+                if cmd.porcelain && outcome.is_some_and(|o| o.imported == 0) {
+                    std::process::exit(EXIT_NOTHING_IMPORTED);
+                }
             }
+            Commands::Config(cmd) => config::run(&config, cmd)?,
+            Commands::Workspace(cmd) => workspace::run(&config, cmd)?,
+            Commands::Export(args) => backup::export(&config, args)?,
+            Commands::Restore(args) => backup::restore(&config, args)?,
+            Commands::Bugreport { .. } => bugreport::run(&config)?,
+            Commands::DiffDb(args) => diff_db::run(&config, args)?,
+            Commands::Consolidate(args) => consolidate::run(&config, args)?,
+            Commands::Journal(cmd) => journal::run(&config, cmd)?,
+            Commands::Introspect(args) => introspect::run(args)?,
+            Commands::Complete(args) => complete::run(args)?,
+            Commands::CompleteValues(args) => complete::values(&config, args)?,
             Commands::Reset { confirm } => {
-                if *confirm && utils::confirm()? {
+                if *confirm && utils::confirm(&config)? {
                     std::fs::remove_file(config.database_path())?;
                 } else {
-                    anyhow::bail!("operation requires confirmation");
+                    anyhow::bail!(error::CliError::confirmation_required());
                 }
             }
         }
@@ -1,4 +1,6 @@
 use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_json::json;
 use std::cell::OnceCell;
 
 use finnel::{
@@ -8,23 +10,33 @@ use finnel::{
     },
     prelude::*,
     record::QueryRecord,
+    stats::UsagesStats,
 };
 
 use crate::cli::{merchant::*, record::Sort};
 use crate::config::Config;
-use crate::utils::DeferrableResolvedUpdateArgs;
+use crate::error::CliError;
+use crate::journal::{self, Journal};
+use crate::utils::{
+    table_display::{builder_display, prepare_output, Style},
+    DeferrableResolvedUpdateArgs,
+};
 
 use tabled::builder::Builder as TableBuilder;
 
 struct CommandContext<'a> {
-    #[allow(dead_code)]
     config: &'a Config,
     conn: &'a mut Database,
+    style: Style,
 }
 
 pub fn run(config: &Config, command: &Command) -> Result<()> {
-    let conn = &mut config.database()?;
-    let mut cmd = CommandContext { conn, config };
+    let conn = &mut config.database_for(command)?;
+    let mut cmd = CommandContext {
+        conn,
+        config,
+        style: config.style()?,
+    };
 
     match &command {
         Command::List(args) => cmd.list(args),
@@ -32,6 +44,7 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
         Command::Update(args) => cmd.update(args),
         Command::Show(args) => cmd.show(args),
         Command::Delete(args) => cmd.delete(args),
+        Command::Alias(action) => cmd.alias(action),
     }
 }
 
@@ -45,6 +58,7 @@ impl CommandContext<'_> {
             default_category_id: args.default_category(self.conn)?.map(|c| c.map(|c| c.id)),
             replaced_by_id: args.replace_by(self.conn)?.map(|m| m.map(|m| m.id)),
             count: count.map(|c| c as i64),
+            order: args.sort.iter().map(|&sort| sort.into()).collect(),
         };
 
         match &args.action {
@@ -59,8 +73,8 @@ impl CommandContext<'_> {
                 }
             }
             Some(Action::Delete { confirm }) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
                 self.conn.transaction(|conn| {
                     for mut merchant in query.run(conn)? {
@@ -70,21 +84,61 @@ impl CommandContext<'_> {
                 })?;
             }
             None => {
+                prepare_output(&args.output);
+                let stats = match args.stats.range()? {
+                    Some((from, to)) => {
+                        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+                        Some(UsagesStats::by_merchant(self.conn, from, to, currency)?)
+                    }
+                    None => None,
+                };
+
                 let mut builder = TableBuilder::new();
-                table_push_row_elements!(builder, "id", "name", "default category", "replaced by");
+                if stats.is_some() {
+                    table_push_row_elements!(
+                        builder, &self.style;
+                        "id",
+                        "name",
+                        "default category",
+                        "replaced by",
+                        "records",
+                        "debit"
+                    );
+                } else {
+                    table_push_row_elements!(
+                        builder, &self.style;
+                        "id",
+                        "name",
+                        "default category",
+                        "replaced by"
+                    );
+                }
                 for (merchant, default_category, replacer) in
                     query.with_replacer().with_category().run(self.conn)?
                 {
-                    table_push_row_elements!(
-                        builder,
-                        merchant.id,
-                        merchant.name,
-                        default_category,
-                        replacer,
-                    );
+                    if let Some(stats) = &stats {
+                        let usage = stats.get(Some(merchant.id));
+                        table_push_row_elements!(
+                            builder, &self.style;
+                            merchant.id,
+                            merchant.name,
+                            default_category,
+                            replacer,
+                            usage.count,
+                            usage.debit_amount()
+                        );
+                    } else {
+                        table_push_row_elements!(
+                            builder, &self.style;
+                            merchant.id,
+                            merchant.name,
+                            default_category,
+                            replacer,
+                        );
+                    }
                 }
 
-                println!("{}", builder.build());
+                builder_display(builder, &args.output)?;
             }
         }
 
@@ -105,8 +159,8 @@ impl CommandContext<'_> {
                     .save(self.conn)?;
             }
             Some(Action::Delete { confirm }) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
                 self.conn.transaction(|conn| merchant.delete(conn))?;
             }
@@ -122,7 +176,19 @@ impl CommandContext<'_> {
                 if let Some(replaced_by) = merchant.fetch_replaced_by(self.conn)? {
                     println!("  Replaced by: {} | {}", replaced_by.id, replaced_by.name);
                 }
+                if let Some(website) = &merchant.website {
+                    println!("  Website: {website}");
+                }
+                if let Some(notes) = &merchant.notes {
+                    println!("  Notes: {notes}");
+                }
+                let aliases = merchant.fetch_aliases(self.conn)?;
+                if !aliases.is_empty() {
+                    println!("  Aliases: {}", aliases.join(", "));
+                }
 
+                self.show_merchant_stats(&merchant)?;
+                self.show_merchant_recurring_payments(&merchant)?;
                 self.show_merchant_records(&merchant)?;
             }
         }
@@ -130,6 +196,66 @@ impl CommandContext<'_> {
         Ok(())
     }
 
+    fn show_merchant_stats(&mut self, merchant: &Merchant) -> Result<()> {
+        for stats in stats::MerchantStats::from_merchant(self.conn, merchant.id)? {
+            println!("\nStatistics ({}):", stats.currency.code());
+            println!(
+                "  Records: {}, from {} to {}",
+                stats.count, stats.first_date, stats.last_date
+            );
+            println!(
+                "  Spent: {}, received: {}",
+                stats.debit_amount(),
+                stats.credit_amount()
+            );
+            println!(
+                "  Monthly average over {} month(s): {} spent, {} received",
+                stats.months(),
+                stats.monthly_debit_average(),
+                stats.monthly_credit_average()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn show_merchant_recurring_payments(&mut self, merchant: &Merchant) -> Result<()> {
+        let today = Utc::now().date_naive();
+        let recurring_payments = RecurringPayment::for_merchant(self.conn, merchant.id, today)?;
+        if recurring_payments.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(
+            builder, &self.style;
+            "id",
+            "name",
+            "amount",
+            "frequency",
+            "next occurrence",
+            "last matching record"
+        );
+        for recpay in recurring_payments {
+            let last_record = recpay.fetch_last_matching_record(self.conn)?;
+            let next_occurrence = recpay.next_due(today);
+
+            table_push_row_elements!(
+                builder, &self.style;
+                recpay.id,
+                recpay.name,
+                Amount(recpay.amount, recpay.currency),
+                recpay.frequency.to_string(),
+                next_occurrence.map(|d| d.to_string()),
+                last_record.map(|r| format!("{} | {}", r.id, r.value_date))
+            );
+        }
+
+        println!("\nRecurring payments:\n{}", builder.build());
+
+        Ok(())
+    }
+
     fn show_merchant_records(&mut self, merchant: &Merchant) -> Result<()> {
         println!();
         let query = QueryRecord {
@@ -140,10 +266,11 @@ impl CommandContext<'_> {
         .with_account()
         .with_category();
 
+        self.style.load_category_paths(self.conn)?;
         let mut builder = TableBuilder::new();
-        table_push_row!(builder, query.type_marker());
+        table_push_row!(builder, &self.style, query.type_marker());
         for result in query.run(self.conn)? {
-            table_push_row!(builder, result);
+            table_push_row!(builder, &self.style, result);
         }
 
         let count = builder.count_records() - 1;
@@ -158,12 +285,25 @@ impl CommandContext<'_> {
     }
 
     fn create(&mut self, args: &Create) -> Result<()> {
-        NewMerchant {
+        let merchant = NewMerchant {
             name: &args.name,
             default_category: args.default_category(self.conn)?.as_ref(),
             replaced_by: args.replace_by(self.conn)?.as_ref(),
+            website: args.website.as_deref(),
+            notes: args.notes.as_deref(),
         }
         .save(self.conn)?;
+        Journal::new(self.config)?.log(
+            "merchant create",
+            json!({
+                "name": merchant.name,
+                "default_category": merchant.default_category_id,
+                "replaced_by": merchant.replaced_by_id,
+                "website": merchant.website,
+                "notes": merchant.notes,
+            }),
+            [merchant.id],
+        );
 
         Ok(())
     }
@@ -171,11 +311,18 @@ impl CommandContext<'_> {
     fn update(&mut self, args: &Update) -> Result<()> {
         let merchant = args.identifier.find(self.conn)?;
 
-        ResolvedUpdateArgs::new(self.conn, &args.args)?
-            .get(self.conn)?
+        let journal = Journal::new(self.config)?;
+        let changes = ResolvedUpdateArgs::new(self.conn, &args.args)?;
+        let changes = changes.get(self.conn)?;
+        let fields = match journal.is_enabled() {
+            true => changes.diff(self.conn, &merchant)?,
+            false => Vec::new(),
+        };
+        changes
             .validate(self.conn, &merchant)?
             .save(self.conn)
             .optional_empty_changeset()?;
+        journal.log("merchant update", journal::changes(&fields), [merchant.id]);
 
         Ok(())
     }
@@ -183,10 +330,52 @@ impl CommandContext<'_> {
     fn delete(&mut self, args: &Delete) -> Result<()> {
         let mut merchant = args.identifier.find(self.conn)?;
 
-        if args.confirm && crate::utils::confirm()? {
+        if args.confirm && crate::utils::confirm(self.config)? {
             merchant.delete(self.conn)?;
+            Journal::new(self.config)?.log("merchant delete", json!({}), [merchant.id]);
         } else {
-            anyhow::bail!("operation requires confirmation");
+            anyhow::bail!(CliError::confirmation_required());
+        }
+
+        Ok(())
+    }
+
+    fn alias(&mut self, action: &AliasAction) -> Result<()> {
+        match action {
+            AliasAction::List { merchant } => {
+                let merchant_id = merchant
+                    .as_ref()
+                    .map(|id| id.find(self.conn))
+                    .transpose()?
+                    .map(|m| m.id);
+
+                let mut builder = TableBuilder::new();
+                table_push_row_elements!(builder, &self.style; "alias", "merchant");
+                for (alias, merchant) in Merchant::all_aliases(self.conn)? {
+                    if merchant_id.is_none_or(|id| id == merchant.id) {
+                        table_push_row_elements!(builder, &self.style; alias, merchant);
+                    }
+                }
+
+                println!("{}", builder.build());
+            }
+            AliasAction::Add { merchant, alias } => {
+                let merchant = merchant.find(self.conn)?;
+                merchant.add_alias(self.conn, alias)?;
+                Journal::new(self.config)?.log(
+                    "merchant alias add",
+                    json!({ "alias": alias }),
+                    [merchant.id],
+                );
+            }
+            AliasAction::Remove { alias } => {
+                Merchant::remove_alias(self.conn, alias)?;
+                Journal::new(self.config)?.log(
+                    "merchant alias remove",
+                    json!({ "alias": alias }),
+                    [],
+                );
+            }
         }
 
         Ok(())
@@ -212,7 +401,7 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeMerchant<'a>
         })
     }
 
-    fn get(&'a self, conn: &mut Conn) -> Result<&ResolvedChangeMerchant<'a>> {
+    fn get(&'a self, conn: &mut Conn) -> Result<&'a ResolvedChangeMerchant<'a>> {
         #[allow(clippy::collapsible_if)]
         if self.change_args.get().is_none() {
             if self
@@ -222,6 +411,8 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeMerchant<'a>
                         name: self.args.new_name.as_deref(),
                         default_category: self.default_category.as_ref().map(|o| o.as_ref()),
                         replaced_by: self.replaced_by.as_ref().map(|o| o.as_ref()),
+                        website: self.args.website(),
+                        notes: self.args.notes(),
                     }
                     .into_resolved(conn)?,
                 )
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, bail, Result};
+
+use finnel::{category::NewCategory, merchant::NewMerchant, parse, prelude::*, record::NewRecord};
+
+use crate::cli::quick::Arguments;
+use crate::config::Config;
+use crate::journal::{self, Journal};
+use crate::utils::max_amount::MaxAmount;
+
+/// Record described by a quick entry like `4.5 coffee @bakery #food`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub amount: Decimal,
+    pub details: String,
+    pub merchant: Option<String>,
+    pub category: Option<String>,
+}
+
+impl std::str::FromStr for Entry {
+    type Err = anyhow::Error;
+
+    fn from_str(entry: &str) -> Result<Self> {
+        let mut words = split(entry)?.into_iter();
+        let amount = match words.next() {
+            Some((word, _)) => parse::amount(&word)
+                .map_err(|e| anyhow!("Missing amount at the start of the entry, {e}"))?,
+            None => bail!("Missing amount, expected e.g. \"4.5 coffee @bakery #food\""),
+        };
+
+        let mut details = Vec::new();
+        let mut merchant = None;
+        let mut category = None;
+        for (word, quoted) in words {
+            let (name, kind) = match word.chars().next() {
+                Some('@') if !quoted => (&mut merchant, "merchant"),
+                Some('#') if !quoted => (&mut category, "category"),
+                _ => {
+                    details.push(word);
+                    continue;
+                }
+            };
+
+            let (marker, value) = word.split_at(1);
+            if value.is_empty() {
+                bail!("Missing {kind} name after {marker}");
+            }
+            if let Some(previous) = name.replace(value.to_owned()) {
+                bail!("Only one {kind} can be given, got {marker}{previous} and {word}");
+            }
+        }
+
+        let details = match (details.is_empty(), &merchant) {
+            (false, _) => details.join(" "),
+            (true, Some(merchant)) => merchant.clone(),
+            (true, None) => bail!("Missing details after the amount"),
+        };
+
+        Ok(Self {
+            amount,
+            details,
+            merchant,
+            category,
+        })
+    }
+}
+
+/// Split the entry on whitespace outside of double quotes, telling for each
+/// word whether it starts with a quote, so that e.g. "#1" stays in the
+/// details
+fn split(entry: &str) -> Result<Vec<(String, bool)>> {
+    let mut words = Vec::new();
+    let mut word: Option<(String, bool)> = None;
+    let mut quoted = false;
+
+    for c in entry.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(|| (String::new(), true));
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(|| (String::new(), false)).0.push(c),
+        }
+    }
+    if quoted {
+        bail!("Unterminated quote in {entry:?}");
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+pub fn run(config: &Config, args: &Arguments) -> Result<()> {
+    let entry = args.entry.parse::<Entry>()?;
+    MaxAmount::load(config)?.check_creation(entry.amount, args.confirm)?;
+
+    let conn = &mut config.database()?;
+    let Some(account) = config.account_or_default(conn)? else {
+        bail!("Account not provided")
+    };
+
+    let record = conn.transaction(|conn| {
+        let merchant = entry
+            .merchant
+            .as_deref()
+            .map(|name| merchant(conn, name))
+            .transpose()?;
+        let category = match (&entry.category, &merchant) {
+            (Some(name), _) => Some(category(conn, name)?),
+            (None, Some(merchant)) => merchant.fetch_default_category(conn)?,
+            (None, None) => None,
+        };
+
+        let record = NewRecord {
+            amount: entry.amount,
+            direction: match args.credit {
+                true => Direction::Credit,
+                false => Direction::Debit,
+            },
+            details: &entry.details,
+            category: category.as_ref(),
+            merchant: merchant.as_ref(),
+            ..NewRecord::new(&account)
+        }
+        .save(conn)?;
+        crate::account::warn_balance_assertions(conn, [(account.id, record.operation_date)])?;
+
+        Result::<_>::Ok(record)
+    })?;
+    Journal::new(config)?.log("quick", journal::record(&record), [record.id]);
+
+    Ok(())
+}
+
+fn merchant(conn: &mut Conn, name: &str) -> Result<Merchant> {
+    let merchant = match Merchant::find_by_name_or_alias(conn, name) {
+        Err(e) if e.is_not_found() => NewMerchant::new(name).save(conn)?,
+        result => result?,
+    };
+    Ok(merchant.resolve(conn)?)
+}
+
+fn category(conn: &mut Conn, name: &str) -> Result<Category> {
+    match Category::find_by_name(conn, name) {
+        Err(e) if e.is_not_found() => Ok(NewCategory::new(name).save(conn)?),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    fn entry(
+        amount: Decimal,
+        details: &str,
+        merchant: Option<&str>,
+        category: Option<&str>,
+    ) -> Entry {
+        Entry {
+            amount,
+            details: details.to_owned(),
+            merchant: merchant.map(str::to_owned),
+            category: category.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parse() -> Result<()> {
+        assert_eq!(
+            entry(Decimal::new(45, 1), "coffee", Some("bakery"), Some("food")),
+            "4.5 coffee @bakery #food".parse()?
+        );
+        assert_eq!(
+            entry(Decimal::new(12, 0), "hot chocolate", None, None),
+            "12   hot \tchocolate ".parse()?
+        );
+        assert_eq!(
+            entry(Decimal::new(3, 0), "bakery", Some("bakery"), None),
+            "3 @bakery".parse()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_quoting() -> Result<()> {
+        assert_eq!(
+            entry(
+                Decimal::new(12, 0),
+                "lunch",
+                Some("Chez Paul"),
+                Some("Eating out")
+            ),
+            "12 lunch @\"Chez Paul\" #\"Eating out\"".parse()?
+        );
+        assert_eq!(
+            entry(Decimal::new(5, 0), "ticket #1 @ night", None, None),
+            "5 ticket \"#1\" \"@ night\"".parse()?
+        );
+        assert!("5 \"ticket".parse::<Entry>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_decimal_comma() -> Result<()> {
+        assert_eq!(
+            entry(Decimal::new(450, 2), "croissant", None, None),
+            "4,50 croissant".parse()?
+        );
+        assert_eq!(
+            entry(Decimal::new(12, 0), "pizza", None, None),
+            "€12 pizza".parse()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_errors() -> Result<()> {
+        for entry in [
+            "",
+            "coffee 4.5",
+            "@bakery 4.5",
+            "4.5",
+            "4.5 coffee @",
+            "4.5 coffee #",
+        ] {
+            assert!(
+                entry.parse::<Entry>().is_err(),
+                "{entry:?} should be rejected"
+            );
+        }
+
+        let error = "4.5 coffee #food #drinks".parse::<Entry>().unwrap_err();
+        assert_eq!(
+            "Only one category can be given, got #food and #drinks",
+            error.to_string()
+        );
+        assert!("4.5 coffee @bakery @cafe".parse::<Entry>().is_err());
+
+        Ok(())
+    }
+}
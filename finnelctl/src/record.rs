@@ -1,31 +1,122 @@
 use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde_json::json;
 use std::borrow::Borrow;
 use std::cell::OnceCell;
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+use std::marker::PhantomData;
 
-use crate::cli::record::*;
-use crate::config::Config;
-use crate::utils::DeferrableResolvedUpdateArgs;
+use crate::account::warn_balance_assertions;
+use crate::cli::{record::*, ListOutput};
+use crate::config::{Config, ConfigStore};
+use crate::error::CliError;
+use crate::journal::{self, Journal};
+use crate::utils::table_display::{
+    prepare_output, records_display, Flagged, RecordRow, RelativeDates, RowDisplay, Style,
+};
+use crate::utils::{
+    amount,
+    last_viewed::LastViewed,
+    max_amount::{self, MaxAmount},
+    DeferrableResolvedUpdateArgs,
+};
 
 use finnel::{
+    diff::FieldDiff,
     prelude::*,
     record::{
         change::{ChangeRecord, ResolvedChangeRecord, ViolatingChangeRecord},
-        NewRecord, QueryRecord, SplitRecord,
+        NewRecord, NewTransfer, QueryRecord, RecordChange, SplitRecord,
     },
 };
 
 use tabled::builder::Builder as TableBuilder;
 
+mod review;
+mod suggestion;
+
+/// Setting of the display scope showing the dates relative to today
+const RELATIVE_DATES_KEY: &str = "relative_dates";
+
+/// Whether a confirmed reconciliation clears the flags of the records it
+/// covers, from the `records/clear_flags_on_reconcile` setting
+pub fn clears_flags_on_reconcile(config: &Config) -> Result<bool> {
+    let key = ConfigurationKey::ClearFlagsOnReconcile.as_str();
+    Ok(match config.store()?.scoped("records")?.get(key)? {
+        Some(value) => value.trim().parse::<bool>().unwrap_or_else(|_| {
+            eprintln!("Warning: ignoring records/{key}, expected true or false");
+            true
+        }),
+        None => true,
+    })
+}
+
+/// Number of records listed and the totals of their amounts in each of
+/// their currencies
+#[derive(Debug, Default)]
+struct Footer {
+    records: usize,
+    /// Debits and credits, in the order the currencies appear
+    totals: Vec<(Currency, Decimal, Decimal)>,
+}
+
+impl Footer {
+    fn new<'a>(records: impl Iterator<Item = &'a Record>) -> Self {
+        let mut footer = Footer::default();
+        for record in records {
+            footer.records += 1;
+            let index = match footer
+                .totals
+                .iter()
+                .position(|(currency, ..)| *currency == record.currency)
+            {
+                Some(index) => index,
+                None => {
+                    footer
+                        .totals
+                        .push((record.currency, Decimal::ZERO, Decimal::ZERO));
+                    footer.totals.len() - 1
+                }
+            };
+            let (_, debits, credits) = &mut footer.totals[index];
+            match record.direction {
+                Direction::Debit => *debits += record.amount,
+                Direction::Credit => *credits += record.amount,
+            }
+        }
+        footer
+    }
+
+    fn print(&self, style: &Style) {
+        println!("{} record(s) shown", self.records);
+        for &(currency, debits, credits) in &self.totals {
+            println!(
+                "{}: debits {}, credits {}, net {}",
+                currency.code(),
+                style.format_amount(Amount(-debits, currency)),
+                style.format_amount(Amount(credits, currency)),
+                style.format_amount(Amount(credits - debits, currency))
+            );
+        }
+    }
+}
+
 struct CommandContext<'a> {
     config: &'a Config,
     conn: &'a mut Database,
-    account: Option<Account>,
+    /// Account or accounts of the group given, or the default account
+    accounts: Option<Vec<Account>>,
+    journal: Journal,
+    style: Style,
 }
 
 pub fn run(config: &Config, command: &Command) -> Result<()> {
-    let conn = &mut config.database()?;
+    let conn = &mut config.database_for(command)?;
     let mut cmd = CommandContext {
-        account: config.account_or_default(conn)?,
+        accounts: config.accounts_or_default(conn)?,
+        journal: Journal::new(config)?,
+        style: config.style()?,
         conn,
         config,
     };
@@ -34,11 +125,40 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
         Command::List(args) => cmd.list(args),
         Command::Show(args) => cmd.show(args),
         Command::Create(args) => cmd.create(args),
+        Command::Transfer(args) => cmd.transfer(args),
         Command::Update(args) => cmd.update(args),
+        Command::Search(args) => cmd.search(args),
+        Command::Duplicates(args) => cmd.duplicates(args),
+        Command::Flag(args) => cmd.flag(args),
+        Command::Unflag(args) => cmd.unflag(args),
+        Command::Review(args) => cmd.review(args),
+        Command::Assign(args) => cmd.assign(args),
     }
 }
 
 impl CommandContext<'_> {
+    /// Id of the single account selected, failing when a group is given
+    fn account_id(&self) -> Result<Option<i64>> {
+        self.config.ensure_single_account()?;
+        Ok(self
+            .accounts
+            .as_ref()
+            .and_then(|accounts| accounts.first())
+            .map(|a| a.id))
+    }
+
+    fn account_ids(&self) -> Option<Vec<i64>> {
+        self.accounts
+            .as_ref()
+            .map(|accounts| accounts.iter().map(|a| a.id).collect())
+    }
+
+    /// Whether the rows need the account column, i.e. unless they all belong
+    /// to the single account selected
+    fn shows_account(&self) -> bool {
+        self.accounts.is_none() || self.config.group_name().is_some()
+    }
+
     fn list(&mut self, args: &List) -> Result<()> {
         let List {
             operation_date,
@@ -50,6 +170,10 @@ impl CommandContext<'_> {
             ..
         } = args;
         let details = args.details();
+        let tag_ids = args.tag_ids(self.conn)?;
+        let category_ids = args.category_ids(self.conn)?;
+        let exclude_category_ids = args.exclude_category_ids(self.conn)?;
+        let exclude_merchant_ids = args.exclude_merchant_ids(self.conn)?;
 
         let mut order = args
             .sort
@@ -65,9 +189,24 @@ impl CommandContext<'_> {
             }
         }
 
+        // Pages need a stable order, the query then breaks ties by id
+        let page = args.page();
+        if order.is_empty() && page.is_some() {
+            order.push(Sort::try_from("date")?.into());
+        }
+
+        let last_viewed = LastViewed::new(self.config, "record")?;
+        let from = match args.since_last {
+            true => last_viewed.get()?,
+            false => args.from,
+        };
+
+        // Pending records have no account yet
+        let account_ids = self.account_ids().filter(|_| !args.pending);
         let query = QueryRecord {
-            account_id: self.account.as_ref().map(|a| a.id),
-            from: args.from,
+            account_id: None,
+            account_ids: account_ids.as_deref(),
+            from,
             to: args.to,
             operation_date: *operation_date,
             greater_than: *greater_than,
@@ -75,54 +214,132 @@ impl CommandContext<'_> {
             direction: *direction,
             mode: *mode,
             details: details.as_deref(),
-            category_id: args.category(self.conn)?.map(|c| c.map(|c| c.id)),
+            category_id: args.category_id(),
+            category_ids: category_ids.as_deref(),
+            exclude_category_ids: exclude_category_ids.as_deref(),
             merchant_id: args.merchant(self.conn)?.map(|m| m.map(|m| m.id)),
-            count: *count,
+            exclude_merchant_ids: exclude_merchant_ids.as_deref(),
+            exclude_mode: args.exclude_mode,
+            tag_ids: tag_ids.as_deref(),
+            flagged: args.flagged,
+            pending: args.pending,
+            original_currency: args.original_currency,
+            text: None,
+            skip_invalid: false,
+            count: page.map(|(_, per_page)| per_page).or(*count),
+            merchant_names_first: None,
+            offset: page.map(|(page, per_page)| (page - 1).saturating_mul(per_page)),
             order,
-            ..QueryRecord::default()
         };
 
         use ListAction::*;
 
         match &args.action {
             Some(Other(Action::Update(args))) => {
-                let changes = ResolvedUpdateArgs::deferred(args);
+                let update = ConfiguredUpdateArgs {
+                    config: self.config,
+                    args,
+                };
+                let changes = ResolvedUpdateArgs::deferred(&update);
 
+                if args.pretend {
+                    let mut changed = false;
+                    for record in query.run(self.conn)? {
+                        let changes = changes.get(self.conn)?;
+                        changed |= print_changes(self.conn, &record, changes)?;
+                    }
+                    if !changed {
+                        println!("No record would change");
+                    }
+                    return Ok(());
+                }
+
+                let mut dates = Vec::new();
                 for record in query.run(self.conn)? {
-                    changes
-                        .get(self.conn)?
-                        .validate(self.conn, &record)?
-                        .save(self.conn)?;
+                    let changes = changes.get(self.conn)?;
+                    dates.extend(changed_date(&record, changes));
+                    let fields = self.journaled_changes(&record, changes)?;
+                    changes.validate(self.conn, &record)?.save(self.conn)?;
+                    self.journal
+                        .log("record update", journal::changes(&fields), [record.id]);
                 }
+                warn_balance_assertions(self.conn, dates)?;
             }
             Some(Other(Action::Delete { confirm })) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
-                self.conn.transaction(|conn| {
+                let (ids, dates) = self.conn.transaction(|conn| {
+                    let mut ids = Vec::new();
+                    let mut dates = Vec::new();
                     for mut record in query.run(conn)? {
                         record.delete(conn)?;
+                        ids.push(record.id);
+                        dates.extend(record.account_id.map(|id| (id, record.operation_date)));
                     }
-                    Result::<()>::Ok(())
+                    Result::<_>::Ok((ids, dates))
                 })?;
+                self.journal.log("record delete", json!({}), ids);
+                warn_balance_assertions(self.conn, dates)?;
+            }
+            Some(ShiftDates(args)) => {
+                self.shift_dates(query, args)?;
             }
             Some(Config(config)) => {
                 self.configure(config)?;
             }
             None => {
-                if self.account.is_some() {
-                    table_display!(query
+                let total = page.map(|_| query.total(self.conn)).transpose()?;
+                let today = self.relative_dates(args.relative_dates)?;
+                prepare_output(&args.output);
+
+                if args.pending || !self.shows_account() {
+                    let rows = query
                         .with_category()
                         .with_parent()
                         .with_merchant()
-                        .run(self.conn)?);
+                        .run(self.conn)?;
+                    self.display(
+                        rows,
+                        args.flagged,
+                        today,
+                        &args.output,
+                        !args.no_footer,
+                        page.is_some(),
+                    )?;
                 } else {
-                    table_display!(query
+                    let rows = query
                         .with_account()
                         .with_category()
                         .with_parent()
                         .with_merchant()
-                        .run(self.conn)?);
+                        .run(self.conn)?;
+                    self.display(
+                        rows,
+                        args.flagged,
+                        today,
+                        &args.output,
+                        !args.no_footer,
+                        page.is_some(),
+                    )?;
+                }
+
+                if !args.no_mark {
+                    last_viewed.mark(chrono::Utc::now().date_naive())?;
+                }
+
+                // Only the rows when they are read by another program
+                if args.output.output.is_some() {
+                    return Ok(());
+                }
+                if let (Some((page, per_page)), Some(total)) = (page, total) {
+                    let pages = total / per_page + i64::from(total % per_page != 0);
+                    println!(
+                        "page {} of {} ({} records total)",
+                        page,
+                        pages.max(1),
+                        total
+                    );
                 }
             }
         }
@@ -130,6 +347,75 @@ impl CommandContext<'_> {
         Ok(())
     }
 
+    fn shift_dates(&mut self, query: QueryRecord, args: &ShiftDatesArgs) -> Result<()> {
+        let shift = args.shift();
+        let records = query.run(self.conn)?;
+        let shifted = records
+            .iter()
+            .map(|record| {
+                Ok((
+                    shift.apply(record.operation_date)?,
+                    shift.apply(record.value_date)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let before = date_range(records.iter().map(|r| (r.operation_date, r.value_date)));
+        let after = date_range(shifted.iter().copied());
+        let (Some(before), Some(after)) = (before, after) else {
+            println!("No records to shift");
+            return Ok(());
+        };
+        println!("Shifting {} record(s) by {shift}", records.len());
+        println!("Before: from {} to {}", before.0, before.1);
+        println!("After: from {} to {}", after.0, after.1);
+
+        if args.pretend {
+            return Ok(());
+        }
+        if !args.confirm || !crate::utils::confirm(self.config)? {
+            anyhow::bail!(CliError::confirmation_required());
+        }
+
+        // Cached stats of the months the records leave or enter
+        let months = records
+            .iter()
+            .zip(&shifted)
+            .flat_map(|(record, dates)| {
+                [record.operation_date, record.value_date, dates.0, dates.1]
+                    .map(|date| (date.year(), date.month() as i32, record.currency))
+            })
+            .collect::<BTreeSet<_>>();
+
+        self.conn.transaction(|conn| {
+            for (year, month, currency) in months {
+                stats::MonthlyStats::invalidate(conn, year, month, currency)?;
+            }
+            for (record, (operation_date, value_date)) in records.iter().zip(&shifted) {
+                ViolatingChangeRecord {
+                    operation_date: Some(*operation_date),
+                    value_date: Some(*value_date),
+                    // Both dates move together, records already inverted stay so
+                    allow_inverted_dates: true,
+                    ..Default::default()
+                }
+                .save(conn, record)?;
+            }
+            Result::<()>::Ok(())
+        })?;
+        self.journal.log(
+            "record shift-dates",
+            json!({ "shift": shift.to_string() }),
+            records.iter().map(|r| r.id),
+        );
+
+        let dates = records.iter().zip(&shifted).filter_map(|(record, dates)| {
+            let date = record.operation_date.min(dates.0);
+            record.account_id.map(|id| (id, date))
+        });
+        warn_balance_assertions(self.conn, dates)
+    }
+
     fn configure(&mut self, config: &ConfigurationAction) -> Result<()> {
         use ConfigurationAction::*;
         use ConfigurationKey::*;
@@ -143,60 +429,291 @@ impl CommandContext<'_> {
             Set { key, value } => {
                 let value = match key {
                     DefaultSort => Sort::try_from(value)?.to_string(),
+                    MaxAmount => max_amount::parse(value)?.to_string(),
+                    ClearFlagsOnReconcile => value
+                        .trim()
+                        .parse::<bool>()
+                        .map_err(|_| anyhow::anyhow!("Expected true or false, got {value:?}"))?
+                        .to_string(),
                 };
-                self.config
-                    .set(format!("records/{}", key.as_str()).as_str(), value.as_str())?;
+                self.settings()?.set(key.as_str(), value.as_str())?;
             }
             Reset { key } => {
-                self.config
-                    .reset(format!("records/{}", key.as_str()).as_str())?;
+                self.settings()?.reset(key.as_str())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn search(&mut self, args: &Search) -> Result<()> {
+        let text = args.terms.join(" ");
+        // The merchants named exactly like the search or one of its terms
+        // come first
+        let merchant_names = std::iter::once(text.clone())
+            .chain(args.terms.iter().cloned())
+            .collect::<Vec<_>>();
+        let account_ids = self.account_ids();
+        let query = QueryRecord {
+            account_ids: account_ids.as_deref(),
+            from: args.from,
+            to: args.to,
+            text: Some(&text),
+            count: args.count,
+            merchant_names_first: Some(&merchant_names),
+            order: vec![Sort::try_from("date.desc")?.into()],
+            ..Default::default()
+        };
+
+        if !self.shows_account() {
+            let rows = query
+                .with_category()
+                .with_parent()
+                .with_merchant()
+                .run(self.conn)?;
+            let today = self.relative_dates(false)?;
+            self.display(rows, false, today, &ListOutput::default(), false, false)
+        } else {
+            let rows = query
+                .with_account()
+                .with_category()
+                .with_parent()
+                .with_merchant()
+                .run(self.conn)?;
+            let today = self.relative_dates(false)?;
+            self.display(rows, false, today, &ListOutput::default(), false, false)
+        }
+    }
+
+    fn duplicates(&mut self, args: &Duplicates) -> Result<()> {
+        let account_id = self.account_id()?;
+        let candidates =
+            finnel::record::duplicates::candidates(self.conn, args.days.into(), account_id)?;
+
+        let mut rows = Vec::new();
+        for (original_id, id) in candidates {
+            let original = Record::find(self.conn, original_id)?;
+            let record = Record::find(self.conn, id)?;
+            if similar_details(&record.details, &original.details) {
+                rows.push((record, original));
+            }
+        }
+
+        table_display!(self.config, &self.style, rows);
+        Ok(())
+    }
+
+    /// Display the rows, with the flag column if requested or if any of them
+    /// is flagged, and the dates relative to `today` when given, unless they
+    /// are written for another program, followed by their totals with
+    /// `footer`
+    fn display<T>(
+        &mut self,
+        rows: Vec<T>,
+        flagged: bool,
+        today: Option<NaiveDate>,
+        output: &ListOutput,
+        footer: bool,
+        empty_header: bool,
+    ) -> Result<()>
+    where
+        T: RowDisplay + RecordRow,
+        PhantomData<T>: RowDisplay,
+        PhantomData<RelativeDates<T>>: RowDisplay,
+        PhantomData<Flagged<RelativeDates<T>>>: RowDisplay,
+    {
+        self.style.load_category_paths(self.conn)?;
+        let flagged = flagged || rows.iter().any(|row| row.record().is_flagged());
+        let footer = (footer && output.output.is_none() && !rows.is_empty())
+            .then(|| Footer::new(rows.iter().map(RecordRow::record)));
+        let today = today.filter(|_| output.output.is_none());
+        let rows = rows.into_iter().map(|row| RelativeDates(row, today));
+        let plain = self.config.plain();
+        if flagged {
+            records_display(
+                rows.map(Flagged).collect::<Vec<_>>(),
+                &self.style,
+                plain,
+                output,
+                empty_header,
+            )?;
+        } else {
+            records_display(
+                rows.collect::<Vec<_>>(),
+                &self.style,
+                plain,
+                output,
+                empty_header,
+            )?;
+        }
+
+        if let Some(footer) = footer {
+            footer.print(&self.style);
+        }
+        Ok(())
+    }
+
+    /// Today when the dates are shown relative to it, with `--relative-dates`
+    /// or the `display/relative_dates` setting
+    fn relative_dates(&self, flag: bool) -> Result<Option<NaiveDate>> {
+        let setting = self
+            .config
+            .store()?
+            .scoped(amount::SCOPE)?
+            .get(RELATIVE_DATES_KEY)?;
+        let relative = flag
+            || match setting {
+                Some(value) => value.trim().parse().unwrap_or_else(|_| {
+                    eprintln!(
+                        "Warning: ignoring {}/{RELATIVE_DATES_KEY}, expected true or false",
+                        amount::SCOPE
+                    );
+                    false
+                }),
+                None => false,
+            };
+
+        Ok(relative.then(|| Utc::now().date_naive()))
+    }
+
+    fn suggest(&mut self, record: &Record, args: &Suggest) -> Result<()> {
+        let (suggestions, total) = suggestion::suggest(self.conn, record)?;
+        let Some(best) = suggestions.first() else {
+            println!("No similar record with a category");
+            return Ok(());
+        };
+
+        self.style.load_category_paths(self.conn)?;
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "category", "records", "share");
+        for suggestion in &suggestions {
+            table_push_row_elements!(
+                builder, &self.style;
+                Some(&suggestion.category),
+                suggestion.count,
+                format!("{}%", suggestion.share(total))
+            );
+        }
+        println!("{total} similar record(s) with a category");
+        println!("{}", builder.build());
+
+        if args.apply_best {
+            if !best.dominates(total) {
+                anyhow::bail!(
+                    "Not applying {} used by only {}% of the similar records",
+                    best.category.name,
+                    best.share(total)
+                );
+            }
+            ChangeRecord {
+                category: Some(Some(&best.category)),
+                ..Default::default()
             }
+            .save(self.conn, record)?;
+            self.journal.log(
+                "record update",
+                json!({ "category": best.category.id }),
+                [record.id],
+            );
+            println!("Category set to {}", best.category.name);
         }
 
         Ok(())
     }
 
+    fn history(&mut self, id: i64) -> Result<()> {
+        let changes = RecordChange::for_record(self.conn, id)?;
+        if changes.is_empty() {
+            // Still fail for records that never existed
+            Record::find(self.conn, id)?;
+            println!("No changes recorded for record {id}");
+            return Ok(());
+        }
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "changed at", "field", "old", "new");
+        for change in changes {
+            table_push_row_elements!(
+                builder, &self.style;
+                change.changed_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                change.field,
+                change.old_value,
+                change.new_value
+            );
+        }
+        println!("{}", builder.build());
+
+        Ok(())
+    }
+
     fn show(&mut self, args: &Show) -> Result<()> {
+        // The history of a deleted record is still available
+        if let Some(ShowAction::History) = args.action {
+            return self.history(args.id());
+        }
         let mut record = Record::find(self.conn, args.id())?;
 
         use ShowAction::*;
 
         match &args.action {
             Some(Other(Action::Update(args))) => {
-                let changes = ResolvedUpdateArgs::deferred(args);
+                let update = ConfiguredUpdateArgs {
+                    config: self.config,
+                    args,
+                };
+                let changes = ResolvedUpdateArgs::deferred(&update);
 
-                changes
-                    .get(self.conn)?
-                    .validate(self.conn, &record)?
-                    .save(self.conn)?;
+                let changes = changes.get(self.conn)?;
+                if args.pretend {
+                    if !print_changes(self.conn, &record, changes)? {
+                        println!("No record would change");
+                    }
+                    return Ok(());
+                }
+                let date = changed_date(&record, changes);
+                let fields = self.journaled_changes(&record, changes)?;
+                changes.validate(self.conn, &record)?.save(self.conn)?;
+                self.journal
+                    .log("record update", journal::changes(&fields), [record.id]);
+                warn_balance_assertions(self.conn, date)?;
             }
             Some(Other(Action::Delete { confirm })) => {
-                if !confirm || !crate::utils::confirm()? {
-                    anyhow::bail!("operation requires confirmation");
+                if !confirm || !crate::utils::confirm(self.config)? {
+                    anyhow::bail!(CliError::confirmation_required());
                 }
                 record.delete(self.conn)?;
+                self.journal.log("record delete", json!({}), [record.id]);
+                let date = record.account_id.map(|id| (id, record.operation_date));
+                warn_balance_assertions(self.conn, date)?;
             }
             Some(Split(args)) => {
-                SplitRecord {
+                let split = SplitRecord {
                     amount: args.amount,
                     details: args.details.as_deref(),
                     category: args.category(self.conn)?.as_ref().map(|c| c.as_ref()),
                 }
                 .save(self.conn, &record)?;
-            }
-            None => {
-                let category = record.fetch_category(self.conn)?;
-                let merchant = record.fetch_merchant(self.conn)?;
-
-                let mut builder = TableBuilder::new();
-                table_push_row!(
-                    builder,
-                    std::marker::PhantomData::<(Record, Option<Category>, Option<Merchant>)>
+                self.journal.log(
+                    "record split",
+                    json!({ "record": record.id, "split": journal::record(&split) }),
+                    [split.id],
                 );
-                table_push_row!(builder, (record, category, merchant));
-
-                println!("{}", builder.build());
             }
+            Some(Tag(TagAction::Add { name })) => {
+                let tag = finnel::tag::Tag::find_or_create(self.conn, name)?;
+                record.add_tag(self.conn, &tag)?;
+                self.journal
+                    .log("record tag add", json!({ "tag": tag.id }), [record.id]);
+            }
+            Some(Tag(TagAction::Remove { name })) => {
+                let tag = finnel::tag::Tag::find_by_name(self.conn, name)?;
+                record.remove_tag(self.conn, &tag)?;
+                self.journal
+                    .log("record tag remove", json!({ "tag": tag.id }), [record.id]);
+            }
+            Some(Suggest(args)) => self.suggest(&record, args)?,
+            Some(History) => self.history(record.id)?,
+            None => print_details(self.conn, &record, &self.style)?,
         }
         Ok(())
     }
@@ -210,11 +727,21 @@ impl CommandContext<'_> {
             ..
         } = args;
 
-        let Some(account) = self.account.as_ref() else {
-            anyhow::bail!("Account not provided")
+        let account = match args.pending {
+            true => None,
+            false => {
+                self.config.ensure_single_account()?;
+                let Some(account) = self.accounts.as_ref().and_then(|accounts| accounts.first())
+                else {
+                    anyhow::bail!("Account not provided")
+                };
+                Some(account)
+            }
         };
 
-        NewRecord {
+        MaxAmount::load(self.config)?.check_creation(*amount, args.confirm)?;
+
+        let record = NewRecord {
             amount: *amount,
             operation_date: args.operation_date(),
             value_date: args.value_date(),
@@ -223,9 +750,58 @@ impl CommandContext<'_> {
             details: details.as_str(),
             category: args.category(self.conn)?.as_ref(),
             merchant: args.merchant(self.conn)?.as_ref(),
-            ..NewRecord::new(account)
+            allow_inverted_dates: args.allow_inverted_dates,
+            original_amount: args.original_amount,
+            original_currency: args.original_currency,
+            ..match account {
+                Some(account) => NewRecord::new(account),
+                None => NewRecord::pending(args.currency.unwrap_or(Currency::EUR)),
+            }
+        }
+        .save(self.conn)?;
+        self.journal
+            .log("record create", journal::record(&record), [record.id]);
+        match account {
+            Some(account) => {
+                warn_balance_assertions(self.conn, [(account.id, args.operation_date())])?
+            }
+            None => println!("Created pending record {}", record.id),
+        }
+
+        Ok(())
+    }
+
+    fn transfer(&mut self, args: &Transfer) -> Result<()> {
+        let find = |conn: &mut Conn, name: &str| match Account::find_by_name(conn, name) {
+            Err(e) if e.is_not_found() => Err(anyhow::Error::from(CliError::NotFound(format!(
+                "Account not found: {}",
+                name
+            )))),
+            result => Ok(result?),
+        };
+        let from = find(self.conn, &args.from_account)?;
+        let to = find(self.conn, &args.to_account)?;
+        MaxAmount::load(self.config)?.check_creation(args.amount, args.confirm)?;
+
+        let (debit, credit) = NewTransfer {
+            amount: args.amount,
+            date: args.date(),
+            details: args.details.as_deref(),
+            ..NewTransfer::new(&from, &to)
         }
         .save(self.conn)?;
+        self.journal.log(
+            "record transfer",
+            json!({
+                "from": from.id,
+                "to": to.id,
+                "amount": args.amount.to_string(),
+                "date": args.date(),
+                "details": args.details,
+            }),
+            [debit.id, credit.id],
+        );
+        warn_balance_assertions(self.conn, [(from.id, args.date()), (to.id, args.date())])?;
 
         Ok(())
     }
@@ -233,11 +809,128 @@ impl CommandContext<'_> {
     fn update(&mut self, args: &Update) -> Result<()> {
         let record = Record::find(self.conn, args.id())?;
 
-        ResolvedUpdateArgs::new(self.conn, &args.args)?
-            .get(self.conn)?
+        let update = ConfiguredUpdateArgs {
+            config: self.config,
+            args: &args.args,
+        };
+        let changes = ResolvedUpdateArgs::new(self.conn, &update)?;
+        let changes = changes.get(self.conn)?;
+        if args.args.pretend {
+            if !print_changes(self.conn, &record, changes)? {
+                println!("No record would change");
+            }
+            return Ok(());
+        }
+        let date = changed_date(&record, changes);
+        let fields = self.journaled_changes(&record, changes)?;
+        changes
             .validate(self.conn, &record)?
             .save(self.conn)
             .optional_empty_changeset()?;
+        self.journal
+            .log("record update", journal::changes(&fields), [record.id]);
+        warn_balance_assertions(self.conn, date)?;
+
+        Ok(())
+    }
+
+    fn flag(&mut self, args: &Flag) -> Result<()> {
+        let record = Record::find(self.conn, args.id())?;
+
+        ChangeRecord {
+            flagged_at: Some(Some(chrono::Utc::now().naive_utc())),
+            flag_reason: Some(args.reason.as_deref()),
+            ..Default::default()
+        }
+        .save(self.conn, &record)?;
+        self.journal
+            .log("record flag", json!({ "reason": args.reason }), [record.id]);
+
+        Ok(())
+    }
+
+    fn assign(&mut self, args: &Assign) -> Result<()> {
+        let mut record = Record::find(self.conn, args.id())?;
+        let account = match Account::find_by_name(self.conn, &args.account) {
+            Err(e) if e.is_not_found() => anyhow::bail!(CliError::NotFound(format!(
+                "Account not found: {}",
+                args.account
+            ))),
+            result => result?,
+        };
+
+        record.assign(self.conn, &account)?;
+        self.journal.log(
+            "record assign",
+            json!({ "account": account.id }),
+            [record.id],
+        );
+        println!("Assigned record {} to {}", record.id, account.name);
+        warn_balance_assertions(self.conn, [(account.id, record.operation_date)])?;
+
+        Ok(())
+    }
+
+    fn unflag(&mut self, args: &Unflag) -> Result<()> {
+        let record = Record::find(self.conn, args.id())?;
+
+        ChangeRecord {
+            flagged_at: Some(None),
+            flag_reason: Some(None),
+            ..Default::default()
+        }
+        .save(self.conn, &record)?;
+        self.journal.log("record unflag", json!({}), [record.id]);
+
+        Ok(())
+    }
+
+    /// Fields the changes modify on the record, only computed when they are
+    /// journaled
+    fn journaled_changes(
+        &mut self,
+        record: &Record,
+        changes: &ResolvedChangeRecord,
+    ) -> Result<Vec<FieldDiff>> {
+        match self.journal.is_enabled() {
+            true => Ok(changes.diff(self.conn, record)?),
+            false => Ok(Vec::new()),
+        }
+    }
+
+    fn review(&mut self, args: &Review) -> Result<()> {
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!("record review reads single keys, run it in a terminal");
+        }
+
+        let account_ids = self.account_ids();
+        let records = QueryRecord {
+            account_ids: account_ids.as_deref(),
+            from: args.from,
+            to: args.to,
+            category_id: args.no_category.then_some(None),
+            merchant_id: args.no_merchant.then_some(None),
+            flagged: args.flagged,
+            count: args.count,
+            order: vec![Sort::try_from("date")?.into()],
+            ..Default::default()
+        }
+        .run(self.conn)?;
+        if records.is_empty() {
+            println!("No records to review");
+            return Ok(());
+        }
+
+        self.style.load_category_paths(self.conn)?;
+        let style = &self.style;
+        let summary = review::review(
+            self.conn,
+            &self.journal,
+            &mut review::Terminal,
+            records,
+            |conn, record| print_details(conn, record, style),
+        )?;
+        println!("{summary}");
 
         Ok(())
     }
@@ -246,23 +939,130 @@ impl CommandContext<'_> {
     where
         T: Borrow<ConfigurationKey>,
     {
-        self.config
-            .get(format!("records/{}", key.borrow().as_str()).as_str())
+        self.settings()?.get(key.borrow().as_str())
     }
+
+    fn settings(&self) -> Result<ConfigStore> {
+        self.config.store()?.scoped("records")
+    }
+}
+
+/// Print the fields the changes would modify on the record, once checked
+/// they could be saved, returning whether there are any
+fn print_changes<'a>(
+    conn: &mut Conn,
+    record: &'a Record,
+    changes: &ResolvedChangeRecord<'a>,
+) -> Result<bool> {
+    changes.validate(conn, record)?;
+    let fields = changes.diff(conn, record)?;
+    if !fields.is_empty() {
+        println!("Record {}: {}", record.id, record.details);
+        crate::utils::print_diff(&fields);
+    }
+    Ok(!fields.is_empty())
+}
+
+/// Print the record with its category, merchant, tags and flag
+fn print_details(conn: &mut Conn, record: &Record, style: &Style) -> Result<()> {
+    let category = record.fetch_category(conn)?;
+    let merchant = record.fetch_merchant(conn)?;
+    let tags = record.fetch_tags(conn)?;
+    let flag = record
+        .flagged_at
+        .map(|flagged_at| (flagged_at.date(), record.flag_reason.clone()));
+    let original = record.original_amount();
+
+    let mut builder = TableBuilder::new();
+    table_push_row!(
+        builder,
+        style,
+        std::marker::PhantomData::<(Record, Option<Category>, Option<Merchant>)>
+    );
+    table_push_row!(builder, style, (record.clone(), category, merchant));
+
+    println!("{}", builder.build());
+
+    if !tags.is_empty() {
+        let names = tags.into_iter().map(|t| t.name).collect::<Vec<_>>();
+        println!("Tags: {}", names.join(", "));
+    }
+    if let Some(original) = original {
+        println!("Original amount: {}", style.format_amount(original));
+    }
+    match flag {
+        Some((date, Some(reason))) => println!("Flagged on {}: {}", date, reason),
+        Some((date, None)) => println!("Flagged on {}", date),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Account and earliest operation date of the record before and after the
+/// changes, None for a pending record
+fn changed_date(record: &Record, changes: &ResolvedChangeRecord) -> Option<(i64, NaiveDate)> {
+    let date = changes
+        .operation_date
+        .map_or(record.operation_date, |date| {
+            date.min(record.operation_date)
+        });
+    record.account_id.map(|id| (id, date))
+}
+
+/// Earliest and latest of the operation and value dates
+fn date_range(
+    dates: impl Iterator<Item = (NaiveDate, NaiveDate)>,
+) -> Option<(NaiveDate, NaiveDate)> {
+    dates
+        .flat_map(|(operation_date, value_date)| [operation_date, value_date])
+        .fold(None, |range, date| match range {
+            Some((min, max)) => Some((date.min(min), date.max(max))),
+            None => Some((date, date)),
+        })
+}
+
+/// Whether the details are the same or one starts with the other, ignoring
+/// case, punctuation and spacing
+fn similar_details(a: &str, b: &str) -> bool {
+    let normalize = |details: &str| {
+        details
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (a, b) = (normalize(a), normalize(b));
+
+    if a.is_empty() || b.is_empty() {
+        a == b
+    } else {
+        a.starts_with(&b) || b.starts_with(&a)
+    }
+}
+
+/// Update arguments along with the configuration confirming the changes
+/// which don't pass the validations
+struct ConfiguredUpdateArgs<'a> {
+    config: &'a Config,
+    args: &'a UpdateArgs,
 }
 
 struct ResolvedUpdateArgs<'a> {
+    config: &'a Config,
     args: &'a UpdateArgs,
     category: Option<Option<Category>>,
     merchant: Option<Option<Merchant>>,
     change_args: OnceCell<ResolvedChangeRecord<'a>>,
 }
 
-impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeRecord<'a>>
+impl<'a> DeferrableResolvedUpdateArgs<'a, ConfiguredUpdateArgs<'a>, ResolvedChangeRecord<'a>>
     for ResolvedUpdateArgs<'a>
 {
-    fn new(conn: &mut Conn, args: &'a UpdateArgs) -> Result<Self> {
+    fn new(conn: &mut Conn, update: &'a ConfiguredUpdateArgs<'a>) -> Result<Self> {
+        let ConfiguredUpdateArgs { config, args } = *update;
         Ok(Self {
+            config,
             args,
             category: args.category(conn)?,
             merchant: args.merchant(conn)?,
@@ -270,14 +1070,19 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeRecord<'a>>
         })
     }
 
-    fn get(&'a self, conn: &mut Conn) -> Result<&ResolvedChangeRecord<'a>> {
+    fn get(&'a self, conn: &mut Conn) -> Result<&'a ResolvedChangeRecord<'a>> {
         #[allow(clippy::collapsible_if)]
         if self.change_args.get().is_none() {
             if self
                 .change_args
                 .set(if self.args.confirm {
-                    if !crate::utils::confirm()? {
-                        anyhow::bail!("operation requires confirmation");
+                    if let Some(amount) = self.args.amount {
+                        if let Err(e) = MaxAmount::load(self.config)?.check(amount) {
+                            eprintln!("Warning: {e}");
+                        }
+                    }
+                    if !crate::utils::confirm(self.config)? {
+                        anyhow::bail!(CliError::confirmation_required());
                     }
 
                     ViolatingChangeRecord {
@@ -289,6 +1094,10 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeRecord<'a>>
                         details: self.args.details.as_deref(),
                         category: self.category.as_ref().map(|o| o.as_ref()),
                         merchant: self.merchant.as_ref().map(|o| o.as_ref()),
+                        allow_inverted_dates: self.args.allow_inverted_dates,
+                        original_amount: self.args.original_amount(),
+                        original_currency: self.args.original_currency(),
+                        ..Default::default()
                     }
                     .into_resolved(conn)?
                 } else {
@@ -297,6 +1106,10 @@ impl<'a> DeferrableResolvedUpdateArgs<'a, UpdateArgs, ResolvedChangeRecord<'a>>
                         details: self.args.details.as_deref(),
                         category: self.category.as_ref().map(|o| o.as_ref()),
                         merchant: self.merchant.as_ref().map(|o| o.as_ref()),
+                        allow_inverted_dates: self.args.allow_inverted_dates,
+                        original_amount: self.args.original_amount(),
+                        original_currency: self.args.original_currency(),
+                        ..Default::default()
                     }
                     .into_resolved(conn)?
                 })
@@ -0,0 +1,262 @@
+use anyhow::Result;
+use serde_json::json;
+use std::io::Write;
+
+use finnel::{prelude::*, record::ChangeRecord};
+
+use crate::cli::{
+    category::Identifier as CategoryIdentifier, merchant::Identifier as MerchantIdentifier,
+};
+use crate::journal::Journal;
+use crate::utils::raw_mode;
+
+const KEYS: &str = "[c]ategory [m]erchant [d]elete [s]kip [q]uit";
+
+/// Keys and lines typed while reviewing the records
+pub trait Input {
+    /// Single key, None at the end of the input
+    fn key(&mut self) -> Result<Option<char>>;
+
+    /// Line typed after the prompt, None at the end of the input
+    fn line(&mut self, prompt: &str) -> Result<Option<String>>;
+}
+
+/// Terminal of stdin, reading the keys without waiting for enter
+pub struct Terminal;
+
+impl Input for Terminal {
+    fn key(&mut self) -> Result<Option<char>> {
+        raw_mode::read_key()
+    }
+
+    fn line(&mut self, prompt: &str) -> Result<Option<String>> {
+        print!("{prompt}");
+        std::io::stdout().flush()?;
+
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim().to_owned())),
+        }
+    }
+}
+
+/// Number of records reviewed, and of the ones changed along the way
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub reviewed: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Reviewed {} records: {} updated, {} deleted",
+            self.reviewed, self.updated, self.deleted
+        )
+    }
+}
+
+/// Go through the records one at a time until the input ends or asks to
+/// quit, saving each change as soon as it is made
+pub fn review<P>(
+    conn: &mut Conn,
+    journal: &Journal,
+    input: &mut dyn Input,
+    records: Vec<Record>,
+    mut print: P,
+) -> Result<Summary>
+where
+    P: FnMut(&mut Conn, &Record) -> Result<()>,
+{
+    let mut summary = Summary::default();
+    let total = records.len();
+
+    'records: for (i, mut record) in records.into_iter().enumerate() {
+        println!("\nRecord {} of {total}", i + 1);
+        print(conn, &record)?;
+        summary.reviewed += 1;
+        let mut updated = false;
+
+        loop {
+            println!("{KEYS}");
+            let Some(key) = input.key()? else {
+                break 'records;
+            };
+
+            match key.to_ascii_lowercase() {
+                'c' => {
+                    let find = |conn: &mut Conn, name| CategoryIdentifier::from(name).find(conn);
+                    let Some(category) = prompt(conn, input, "Category: ", find)? else {
+                        continue;
+                    };
+                    let change = ChangeRecord {
+                        category: Some(Some(&category)),
+                        ..Default::default()
+                    };
+                    record = save(
+                        conn,
+                        journal,
+                        &record,
+                        change,
+                        json!({ "category": category.id }),
+                    )?;
+                }
+                'm' => {
+                    let find = |conn: &mut Conn, name| MerchantIdentifier::from(name).find(conn);
+                    let Some(merchant) = prompt(conn, input, "Merchant: ", find)? else {
+                        continue;
+                    };
+                    let change = ChangeRecord {
+                        merchant: Some(Some(&merchant)),
+                        ..Default::default()
+                    };
+                    record = save(
+                        conn,
+                        journal,
+                        &record,
+                        change,
+                        json!({ "merchant": merchant.id }),
+                    )?;
+                }
+                'd' => {
+                    println!("Delete record {}? [y/N]", record.id);
+                    if !matches!(input.key()?, Some('y' | 'Y')) {
+                        println!("Not deleted");
+                        continue;
+                    }
+                    conn.transaction(|conn| record.delete(conn))?;
+                    journal.log("record delete", json!({}), [record.id]);
+                    println!("Deleted record {}", record.id);
+                    summary.deleted += 1;
+                    continue 'records;
+                }
+                's' | ' ' | '\n' => break,
+                'q' => break 'records,
+                _ => {
+                    println!("Unknown key {key:?}");
+                    continue;
+                }
+            }
+
+            updated = true;
+            print(conn, &record)?;
+        }
+
+        if updated {
+            summary.updated += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Read a name and find what it names, printing why it can't be found, None
+/// when nothing was typed
+fn prompt<T, F>(conn: &mut Conn, input: &mut dyn Input, prompt: &str, find: F) -> Result<Option<T>>
+where
+    F: Fn(&mut Conn, String) -> Result<T>,
+{
+    loop {
+        let Some(name) = input.line(prompt)?.filter(|name| !name.is_empty()) else {
+            return Ok(None);
+        };
+        match find(conn, name) {
+            Ok(found) => return Ok(Some(found)),
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+/// Save the change in its own transaction, returning the record as changed
+fn save(
+    conn: &mut Conn,
+    journal: &Journal,
+    record: &Record,
+    change: ChangeRecord,
+    arguments: serde_json::Value,
+) -> Result<Record> {
+    let record = conn.transaction(|conn| {
+        change.save(conn, record)?;
+        Result::<_>::Ok(Record::find(conn, record.id)?)
+    })?;
+    journal.log("record update", arguments, [record.id]);
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use std::collections::VecDeque;
+
+    /// Input typed in advance
+    #[derive(Default)]
+    struct Script {
+        keys: VecDeque<char>,
+        lines: VecDeque<&'static str>,
+    }
+
+    impl Input for Script {
+        fn key(&mut self) -> Result<Option<char>> {
+            Ok(self.keys.pop_front())
+        }
+
+        fn line(&mut self, _prompt: &str) -> Result<Option<String>> {
+            Ok(self.lines.pop_front().map(str::to_owned))
+        }
+    }
+
+    #[test]
+    fn review() -> Result<()> {
+        with_config(|config| {
+            let conn = &mut config.database()?;
+            let journal = Journal::new(config)?;
+            let account = test::account!(conn, "Cash");
+            let food = test::category!(conn, "Food");
+            let bakery = test::merchant!(conn, "Bakery");
+            let bread = test::record!(conn, &account, details: "bread");
+            let rent = test::record!(conn, &account, details: "rent");
+            let gift = test::record!(conn, &account, details: "gift");
+            let tip = test::record!(conn, &account, details: "tip");
+
+            let mut input = Script {
+                // Bread: an unknown category then Food, and the merchant,
+                // rent: a refused then a confirmed deletion, gift: an
+                // unknown key then skipped, and quit at the tip
+                keys: "cmsdndy?sq".chars().collect(),
+                lines: ["Fod", "Food", "Bakery"].into(),
+            };
+            let records = vec![bread.clone(), rent.clone(), gift.clone(), tip.clone()];
+            let mut printed = Vec::new();
+            let summary = super::review(conn, &journal, &mut input, records, |_, record| {
+                printed.push(record.id);
+                Ok(())
+            })?;
+
+            assert_eq!(
+                Summary {
+                    reviewed: 4,
+                    updated: 1,
+                    deleted: 1,
+                },
+                summary
+            );
+            // Printed again after each change
+            assert_eq!(
+                vec![bread.id, bread.id, bread.id, rent.id, gift.id, tip.id],
+                printed
+            );
+
+            let bread = Record::find(conn, bread.id)?;
+            assert_eq!(Some(food.id), bread.category_id);
+            assert_eq!(Some(bakery.id), bread.merchant_id);
+            assert!(Record::find(conn, rent.id).is_err_and(|e| e.is_not_found()));
+            assert_eq!(None, Record::find(conn, gift.id)?.category_id);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+
+use finnel::{prelude::*, stats::SimilarRecords};
+
+/// Number of categories suggested for a record
+pub const SUGGESTIONS: usize = 3;
+
+/// Words frequently found in the details written by banks, which don't
+/// tell anything about the category
+const STOP_WORDS: &[&str] = &[
+    "and", "avec", "card", "carte", "des", "dab", "for", "from", "inst", "les", "paiement",
+    "payment", "pour", "prlv", "retrait", "sepa", "the", "vir", "virement",
+];
+
+/// Category suggested for a record, from the categories of similar records
+#[derive(Debug)]
+pub struct Suggestion {
+    pub category: Category,
+    /// Number of similar records in the category
+    pub count: i64,
+}
+
+impl Suggestion {
+    /// Part of the similar records in the category, in percent
+    pub fn share(&self, total: i64) -> i64 {
+        match total {
+            0 => 0,
+            total => self.count * 100 / total,
+        }
+    }
+
+    /// Whether the category is used by more than 80% of the similar records
+    pub fn dominates(&self, total: i64) -> bool {
+        self.count * 5 > total * 4
+    }
+}
+
+/// Significant words of the details, lowercased, leaving out the short ones,
+/// the numbers like dates or card numbers, and the common banking words
+pub fn tokens(details: &str) -> Vec<String> {
+    let mut tokens = Vec::<String>::new();
+    for token in details
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+    {
+        if token.chars().count() < 3
+            || token.chars().any(|c| c.is_ascii_digit())
+            || STOP_WORDS.contains(&token.as_str())
+            || tokens.contains(&token)
+        {
+            continue;
+        }
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Categories of the records with the same merchant or sharing a token of
+/// the details with the record, the most used first, along with the number
+/// of categorized similar records
+pub fn suggest(conn: &mut Conn, record: &Record) -> Result<(Vec<Suggestion>, i64)> {
+    let tokens = tokens(&record.details);
+    let tokens = tokens.iter().map(String::as_str).collect::<Vec<_>>();
+    let counts = SimilarRecords {
+        merchant_id: record.merchant_id,
+        tokens: &tokens,
+        excluded_id: Some(record.id),
+    }
+    .categories(conn)?;
+
+    // Count the records of replaced categories in their replacement
+    let mut suggestions = Vec::<Suggestion>::new();
+    for (category_id, count) in counts {
+        let category = Category::find(conn, category_id)?.resolve(conn)?;
+        match suggestions
+            .iter_mut()
+            .find(|s| s.category.id == category.id)
+        {
+            Some(suggestion) => suggestion.count += count,
+            None => suggestions.push(Suggestion { category, count }),
+        }
+    }
+
+    let total = suggestions.iter().map(|s| s.count).sum();
+    suggestions.sort_by_key(|s| (-s.count, s.category.id));
+    suggestions.truncate(SUGGESTIONS);
+
+    Ok((suggestions, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            vec!["chariot", "café"],
+            super::tokens("CB CHARIOT 01/08 Café chariot carte 4974XXXX")
+        );
+        assert_eq!(vec!["edf"], super::tokens("PRLV SEPA EDF"));
+        assert!(super::tokens("VIR 12/08 de M.").is_empty());
+    }
+
+    #[test]
+    fn suggest() -> Result<()> {
+        let conn = &mut test::conn()?;
+        let account = test::account!(conn, "Cash");
+        let bar = test::category!(conn, "Bar");
+        let pub_ = test::category!(conn, "Pub");
+        let food = test::category!(conn, "Food");
+        let chariot = test::merchant!(conn, "Chariot");
+        finnel::category::ChangeCategory {
+            replaced_by: Some(Some(&bar)),
+            ..Default::default()
+        }
+        .save(conn, &pub_)?;
+
+        let record =
+            test::record!(conn, &account, details: "CB Chariot 12/08", merchant: Some(&chariot));
+        test::record!(conn, &account, details: "Beers", category: Some(&bar), merchant: Some(&chariot));
+        test::record!(conn, &account, details: "CB CHARIOT", category: Some(&pub_));
+        test::record!(conn, &account, details: "CB Chariot", category: Some(&bar));
+        test::record!(conn, &account, details: "Chariot snacks", category: Some(&food));
+        test::record!(conn, &account, details: "CB Bakery", category: Some(&food));
+
+        let (suggestions, total) = super::suggest(conn, &record)?;
+        assert_eq!(4, total);
+        assert_eq!(
+            vec![(bar.id, 3), (food.id, 1)],
+            suggestions
+                .iter()
+                .map(|s| (s.category.id, s.count))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(75, suggestions[0].share(total));
+        assert!(!suggestions[0].dominates(total));
+        assert!(suggestions[0].dominates(3));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use finnel::{
+    account::QueryAccount,
+    prelude::*,
+    recurring_payment::{ChangeRecurringPayment, NewRecurringPayment, QueryRecurringPayment},
+};
+
+use crate::cli::recurring::*;
+use crate::config::Config;
+use crate::error::CliError;
+use crate::journal::Journal;
+use crate::utils::table_display::Style;
+
+use chrono::{Days, NaiveDate};
+use serde_json::json;
+
+use tabled::builder::Builder as TableBuilder;
+
+struct CommandContext<'a> {
+    config: &'a Config,
+    conn: &'a mut Database,
+    style: Style,
+}
+
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    let conn = &mut config.database_for(command)?;
+    let mut cmd = CommandContext {
+        conn,
+        config,
+        style: config.style()?,
+    };
+
+    match &command {
+        Command::List(args) => cmd.list(args),
+        Command::Create(args) => cmd.create(args),
+        Command::Update(args) => cmd.update(args),
+        Command::Delete(args) => cmd.delete(args),
+    }
+}
+
+impl CommandContext<'_> {
+    fn list(&mut self, args: &List) -> Result<()> {
+        let today = args.date();
+        let due_by = args.due.map(|days| {
+            today
+                .checked_add_days(Days::new(days))
+                .unwrap_or(NaiveDate::MAX)
+        });
+
+        let account_ids = self
+            .config
+            .accounts(self.conn)?
+            .map(|accounts| accounts.iter().map(|a| a.id).collect::<Vec<_>>());
+        let recurring_payments = QueryRecurringPayment {
+            account_ids: account_ids.as_deref(),
+            ..Default::default()
+        }
+        .run(self.conn)?;
+        let account_names = QueryAccount::default()
+            .run(self.conn)?
+            .into_iter()
+            .map(|account| (account.id, account.name))
+            .collect::<HashMap<_, _>>();
+        self.style.load_category_paths(self.conn)?;
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(
+            builder, &self.style;
+            "id",
+            "name",
+            "account",
+            "amount",
+            "frequency",
+            "next due",
+            "category",
+            "merchant"
+        );
+        for recpay in recurring_payments {
+            let next_due = recpay.next_due(today);
+            if let Some(due_by) = due_by {
+                if next_due.is_none_or(|date| date > due_by) {
+                    continue;
+                }
+            }
+            let category = recpay
+                .category_id
+                .map(|id| Category::find(self.conn, id))
+                .transpose()?;
+            let merchant = recpay
+                .merchant_id
+                .map(|id| Merchant::find(self.conn, id))
+                .transpose()?;
+
+            table_push_row_elements!(
+                builder, &self.style;
+                recpay.id,
+                recpay.name,
+                account_names[&recpay.account_id].clone(),
+                (Amount(recpay.amount, recpay.currency), recpay.direction),
+                recpay.frequency.to_string(),
+                next_due,
+                category,
+                merchant
+            );
+        }
+
+        println!("{}", builder.build());
+
+        Ok(())
+    }
+
+    fn create(&mut self, args: &Create) -> Result<()> {
+        let Some(account) = self.config.account_or_default(self.conn)? else {
+            anyhow::bail!("Account not provided")
+        };
+
+        let recpay = NewRecurringPayment {
+            name: &args.name,
+            description: &args.description,
+            frequency: args.frequency,
+            amount: args.amount,
+            direction: args.direction,
+            mode: args.mode,
+            category: args.category(self.conn)?.as_ref(),
+            merchant: args.merchant(self.conn)?.as_ref(),
+            start_date: Some(args.start_date()),
+            end_date: args.end_date,
+            ..NewRecurringPayment::new(&account)
+        }
+        .save(self.conn)?;
+        Journal::new(self.config)?.log(
+            "recurring create",
+            json!({
+                "name": recpay.name,
+                "description": recpay.description,
+                "frequency": recpay.frequency.to_string(),
+                "account": recpay.account_id,
+                "amount": recpay.amount.to_string(),
+                "direction": recpay.direction.to_string(),
+                "mode": recpay.mode.to_string(),
+                "category": recpay.category_id,
+                "merchant": recpay.merchant_id,
+                "start_date": recpay.start_date,
+                "end_date": recpay.end_date,
+            }),
+            [recpay.id],
+        );
+
+        Ok(())
+    }
+
+    fn update(&mut self, args: &Update) -> Result<()> {
+        let recpay = args.identifier.find(self.conn)?;
+        let category = args.category(self.conn)?;
+        let merchant = args.merchant(self.conn)?;
+
+        ChangeRecurringPayment {
+            name: args.new_name.as_deref(),
+            description: args.description.as_deref(),
+            frequency: args.frequency,
+            amount: args.amount,
+            direction: args.direction,
+            mode: args.mode,
+            category: category.as_ref().map(|c| c.as_ref()),
+            merchant: merchant.as_ref().map(|m| m.as_ref()),
+            start_date: args.start_date.map(Some),
+            end_date: args.end_date(),
+            ..Default::default()
+        }
+        .save(self.conn, &recpay)?;
+
+        let mut changes = serde_json::Map::new();
+        let mut change = |field: &str, value: serde_json::Value| {
+            changes.insert(field.to_owned(), value);
+        };
+        if let Some(name) = &args.new_name {
+            change("name", json!(name));
+        }
+        if let Some(description) = &args.description {
+            change("description", json!(description));
+        }
+        if let Some(frequency) = args.frequency {
+            change("frequency", json!(frequency.to_string()));
+        }
+        if let Some(amount) = args.amount {
+            change("amount", json!(amount.to_string()));
+        }
+        if let Some(direction) = args.direction {
+            change("direction", json!(direction.to_string()));
+        }
+        if let Some(mode) = args.mode {
+            change("mode", json!(mode.to_string()));
+        }
+        if let Some(category) = &category {
+            change("category", json!(category.as_ref().map(|c| c.id)));
+        }
+        if let Some(merchant) = &merchant {
+            change("merchant", json!(merchant.as_ref().map(|m| m.id)));
+        }
+        if let Some(start_date) = args.start_date {
+            change("start_date", json!(start_date));
+        }
+        if let Some(end_date) = args.end_date() {
+            change("end_date", json!(end_date));
+        }
+        Journal::new(self.config)?.log("recurring update", changes.into(), [recpay.id]);
+
+        Ok(())
+    }
+
+    fn delete(&mut self, args: &Delete) -> Result<()> {
+        let mut recpay = args.identifier.find(self.conn)?;
+
+        if args.confirm && crate::utils::confirm(self.config)? {
+            recpay.delete(self.conn)?;
+            Journal::new(self.config)?.log("recurring delete", json!({}), [recpay.id]);
+        } else {
+            anyhow::bail!(CliError::confirmation_required());
+        }
+
+        Ok(())
+    }
+}
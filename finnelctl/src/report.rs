@@ -1,22 +1,42 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-use finnel::prelude::*;
+use finnel::{
+    prelude::*,
+    stats::{CategoriesStats, MerchantMonthStats, ModeKind},
+};
 
 use crate::cli::report::*;
 use crate::config::Config;
+use crate::error::CliError;
+use crate::journal::Journal;
+use crate::utils::{
+    html_table::{HtmlPage, HtmlTable},
+    last_viewed::LastViewed,
+};
+use crate::utils::{note_skipped_currencies, table_display::Style};
+
+use chrono::{Datelike, Months};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
 use tabled::builder::Builder as TableBuilder;
 
+mod digest;
+use digest::Digest;
+
 struct CommandContext<'a> {
-    _config: &'a Config,
+    config: &'a Config,
     conn: &'a mut Database,
+    style: Style,
 }
 
 pub fn run(config: &Config, command: &Command) -> Result<()> {
-    let conn = &mut config.database()?;
+    let conn = &mut config.database_for(command)?;
     let mut cmd = CommandContext {
         conn,
-        _config: config,
+        config,
+        style: config.style()?,
     };
 
     match &command {
@@ -24,16 +44,22 @@ pub fn run(config: &Config, command: &Command) -> Result<()> {
         Command::Show(args) => cmd.show(args),
         Command::Create(args) => cmd.create(args),
         Command::Delete(args) => cmd.delete(args),
+        Command::Category(args) => cmd.category(args),
+        Command::CategoryDetail(args) => cmd.category_detail(args),
+        Command::Merchant(args) => cmd.merchant(args),
+        Command::Modes(args) => cmd.modes(args),
+        Command::Year(args) => cmd.year(args),
+        Command::Digest(args) => cmd.digest(args),
     }
 }
 
 impl CommandContext<'_> {
     fn list(&mut self, _args: &List) -> Result<()> {
         let mut builder = TableBuilder::new();
-        table_push_row_elements!(builder, "id", "name");
+        table_push_row_elements!(builder, &self.style; "id", "name");
 
         for (id, name) in Report::all(self.conn)? {
-            table_push_row_elements!(builder, id, name);
+            table_push_row_elements!(builder, &self.style; id, name);
         }
 
         println!("{}", builder.build());
@@ -51,6 +77,11 @@ impl CommandContext<'_> {
                     .map(|id| id.find(self.conn))
                     .collect::<Result<Vec<_>>>()?;
                 report.add(self.conn, categories.iter())?;
+                Journal::new(self.config)?.log(
+                    "report add",
+                    json!({ "categories": categories.iter().map(|c| c.id).collect::<Vec<_>>() }),
+                    [report.id],
+                );
             }
             Some(Action::Remove { categories }) => {
                 let categories = categories
@@ -58,16 +89,33 @@ impl CommandContext<'_> {
                     .map(|id| id.find(self.conn))
                     .collect::<Result<Vec<_>>>()?;
                 report.remove(self.conn, categories.iter())?;
+                Journal::new(self.config)?.log(
+                    "report remove",
+                    json!({ "categories": categories.iter().map(|c| c.id).collect::<Vec<_>>() }),
+                    [report.id],
+                );
             }
             None => {
-                println!("{} | {}", report.id, report.name);
-
                 let mut builder = TableBuilder::new();
-                table_push_row_elements!(builder, "id", "name");
+                table_push_row_elements!(builder, &self.style; "id", "name");
                 for category in &report.categories {
-                    table_push_row_elements!(builder, category.id, category.name);
+                    table_push_row_elements!(builder, &self.style; category.id, *category);
+                }
+
+                match args.output.get()? {
+                    Some((OutputFormat::Html, path)) => {
+                        let mut page = HtmlPage::new(&format!("Report {}", report.name));
+                        page.heading("Categories").table(
+                            &HtmlTable::from(builder)
+                                .with_categories(1, report.categories.iter().map(Some)),
+                        );
+                        write_page(&page, &path)?;
+                    }
+                    None => {
+                        println!("{} | {}", report.id, report.name);
+                        println!("{}", builder.build());
+                    }
                 }
-                println!("{}", builder.build());
             }
         }
 
@@ -75,18 +123,409 @@ impl CommandContext<'_> {
     }
 
     fn create(&mut self, args: &Create) -> Result<()> {
-        Report::create(self.conn, &args.name)?;
+        let report = Report::create(self.conn, &args.name)?;
+        Journal::new(self.config)?.log(
+            "report create",
+            json!({ "name": report.name }),
+            [report.id],
+        );
         Ok(())
     }
 
     fn delete(&mut self, args: &Delete) -> Result<()> {
         let mut report = args.identifier.find(self.conn)?;
 
-        if args.confirm && crate::utils::confirm()? {
+        if args.confirm && crate::utils::confirm(self.config)? {
             report.delete(self.conn)?;
+            Journal::new(self.config)?.log("report delete", json!({}), [report.id]);
         } else {
-            anyhow::bail!("operation requires confirmation");
+            anyhow::bail!(CliError::confirmation_required());
+        }
+        Ok(())
+    }
+
+    fn category(&mut self, args: &CategorySeries) -> Result<()> {
+        let category = args.category.find(self.conn)?;
+        let mut category_ids = vec![category.id];
+        if args.with_children {
+            category_ids.extend(category.descendant_ids(self.conn)?);
+        }
+
+        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+        let range = args.range()?;
+        let series = stats::category_series(self.conn, &category_ids, range.clone(), currency)?;
+
+        let mut skipped = BTreeMap::<String, (Currency, i64)>::new();
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "month", "debit", "credit", "records", "delta");
+        for month in series {
+            for (other, count) in &month.skipped_currencies {
+                skipped
+                    .entry(other.code().to_string())
+                    .or_insert((*other, 0))
+                    .1 += count;
+            }
+            table_push_row_elements!(
+                builder, &self.style;
+                format!("{}/{:02}", month.year, month.month),
+                month.debit_amount(),
+                month.credit_amount(),
+                month.count,
+                month.delta.map(|delta| {
+                    let sign = if delta.is_sign_positive() { "+" } else { "" };
+                    format!("{sign}{}", self.style.format_amount(Amount(delta, month.currency)))
+                }),
+            );
+        }
+
+        match args.output.get()? {
+            Some((OutputFormat::Html, path)) => {
+                let mut page = HtmlPage::new(&format!("{} month by month", category.name));
+                page.note(&format!(
+                    "From {} and before {}, in {}",
+                    range.start,
+                    range.end,
+                    currency.code()
+                ))
+                .table(&builder.into());
+                write_page(&page, &path)?;
+            }
+            None => println!("{}", builder.build()),
+        }
+        note_skipped_currencies(currency, skipped.values());
+
+        Ok(())
+    }
+
+    fn category_detail(&mut self, args: &CategoryDetail) -> Result<()> {
+        self.style.load_category_paths(self.conn)?;
+
+        let start = args.month.calendar_month()?.start_of_month;
+        let range = date::Month::calendar(start.year(), start.month() as i32).as_date_range()?;
+        // Stats cover all accounts unless one or a group is explicitly
+        // selected
+        let account_ids = self
+            .config
+            .accounts(self.conn)?
+            .map(|accounts| accounts.iter().map(|a| a.id).collect::<Vec<_>>());
+        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+
+        let mut stats = match &account_ids {
+            Some(ids) => CategoriesStats::from_date_range_currency_and_accounts(
+                self.conn,
+                range,
+                currency,
+                Some(ids),
+            )?,
+            // The stats of the month may have been precomputed
+            None => stats::for_range(self.conn, range, currency)?,
+        };
+        stats.stats.sort_by(|a, b| {
+            (a.direction.is_credit(), b.amount).cmp(&(b.direction.is_credit(), a.amount))
+        });
+
+        let categories = stats
+            .iter()
+            .filter_map(|stats| stats.category_id)
+            .map(|id| Ok((id, Category::find(self.conn, id)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(
+            builder, &self.style;
+            "category",
+            "direction",
+            "records",
+            "total",
+            "min",
+            "max",
+            "mean"
+        );
+        for category_stats in stats.iter() {
+            table_push_row_elements!(
+                builder, &self.style;
+                category_stats
+                    .category_id
+                    .and_then(|id| categories.get(&id)),
+                category_stats.direction.to_string(),
+                category_stats.count,
+                category_stats.amount(),
+                category_stats.min_amount(),
+                category_stats.max_amount(),
+                category_stats.mean_amount(),
+            );
         }
+
+        let title = format!("Categories of {}/{:02}", start.year(), start.month());
+        match args.output.get()? {
+            Some((OutputFormat::Html, path)) => {
+                let rows = stats.iter().map(|category_stats| {
+                    category_stats
+                        .category_id
+                        .and_then(|id| categories.get(&id))
+                });
+                let mut page = HtmlPage::new(&title);
+                page.table(&HtmlTable::from(builder).with_categories(0, rows));
+                write_page(&page, &path)?;
+            }
+            None => {
+                println!("{title}");
+                println!("{}", builder.build());
+            }
+        }
+        for warning in &stats.warnings {
+            eprintln!("Warning: {warning}, left out of the stats");
+        }
+        note_skipped_currencies(currency, &stats.skipped_currencies);
+
         Ok(())
     }
+
+    fn merchant(&mut self, args: &MerchantSeries) -> Result<()> {
+        let merchant = args.merchant.find(self.conn)?;
+        let merchant_ids = merchant.replaced_ids(self.conn)?;
+        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+        let range = args.range()?;
+        let stats = stats::merchant_months(self.conn, &merchant_ids, range.clone(), currency)?;
+
+        // Months without debits are shown too, they weigh in the average
+        let mut months = Vec::new();
+        let mut start = range.start;
+        while start < range.end {
+            let (year, month) = (start.year(), start.month());
+            months.push(
+                stats
+                    .iter()
+                    .find(|stats| (stats.year, stats.month) == (year, month))
+                    .cloned()
+                    .unwrap_or(MerchantMonthStats {
+                        year,
+                        month,
+                        debit_amount: Decimal::ZERO,
+                        count: 0,
+                        currency,
+                    }),
+            );
+            start = start + Months::new(1);
+        }
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "month", "debit", "records", "average");
+        for month in &months {
+            table_push_row_elements!(
+                builder, &self.style;
+                format!("{}/{:02}", month.year, month.month),
+                month.debit_amount(),
+                month.count,
+                month.average_ticket().map(|ticket| self.style.format_amount(ticket)),
+            );
+        }
+
+        let trend = trend(&months, &self.style);
+        match args.output.get()? {
+            Some((OutputFormat::Html, path)) => {
+                let mut page = HtmlPage::new(&format!("{} month by month", merchant.name));
+                page.table(&builder.into());
+                if let Some(trend) = trend {
+                    page.note(&trend);
+                }
+                write_page(&page, &path)?;
+            }
+            None => {
+                println!("{}", builder.build());
+                if let Some(trend) = trend {
+                    println!("{trend}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn modes(&mut self, args: &ModeSeries) -> Result<()> {
+        let currency = self.config.main_currency()?.unwrap_or(Currency::EUR);
+        let range = args.range()?;
+        let stats = stats::modes(self.conn, range.clone(), currency)?;
+
+        let mut months = BTreeMap::<(i32, u32), BTreeMap<ModeKind, Decimal>>::new();
+        let mut totals = BTreeMap::<ModeKind, Decimal>::new();
+        for stat in &stats {
+            *months
+                .entry((stat.year, stat.month))
+                .or_default()
+                .entry(stat.mode)
+                .or_default() += stat.debit_amount;
+            *totals.entry(stat.mode).or_default() += stat.debit_amount;
+        }
+
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "month", "direct", "atm", "transfer", "total");
+        let mut push_row = |label: String, amounts: &BTreeMap<ModeKind, Decimal>| {
+            let [direct, atm, transfer] = ModeKind::ALL
+                .map(|mode| Amount(amounts.get(&mode).copied().unwrap_or_default(), currency));
+            let total = Amount(amounts.values().sum(), currency);
+            table_push_row_elements!(builder, &self.style; label, direct, atm, transfer, total);
+        };
+        for ((year, month), amounts) in &months {
+            push_row(format!("{year}/{month:02}"), amounts);
+        }
+        push_row("total".to_string(), &totals);
+
+        match args.output.get()? {
+            Some((OutputFormat::Html, path)) => {
+                let mut page = HtmlPage::new("Payment modes month by month");
+                page.note(&format!(
+                    "Debits from {} and before {}, in {}",
+                    range.start,
+                    range.end,
+                    currency.code()
+                ))
+                .table(&builder.into());
+                write_page(&page, &path)?;
+            }
+            None => println!("{}", builder.build()),
+        }
+
+        Ok(())
+    }
+
+    fn year(&mut self, args: &YearOverview) -> Result<()> {
+        let year = args.year();
+        let currencies = match self.config.accounts(self.conn)? {
+            Some(accounts) => {
+                let mut currencies = accounts.iter().map(|a| a.currency).collect::<Vec<_>>();
+                currencies.sort_by_key(|c| c.code());
+                currencies.dedup();
+                currencies
+            }
+            None => {
+                let currencies = stats::year_currencies(self.conn, year)?;
+                if currencies.is_empty() {
+                    vec![self.config.main_currency()?.unwrap_or(Currency::EUR)]
+                } else {
+                    currencies
+                }
+            }
+        };
+
+        let mut tables = Vec::new();
+        let mut highlighted = false;
+        for currency in currencies {
+            let months = stats::year(self.conn, year, currency)?;
+            let highest = months
+                .iter()
+                .filter_map(|month| month.stats.as_ref())
+                .filter(|stats| !stats.debit_amount.is_zero())
+                .max_by_key(|stats| stats.debit_amount)
+                .map(|stats| stats.month as u32);
+            highlighted |= highest.is_some();
+
+            let mut builder = TableBuilder::new();
+            table_push_row_elements!(builder, &self.style; "month", "debit", "credit", "net", "cumulative");
+            let (mut debit, mut credit) = (Decimal::ZERO, Decimal::ZERO);
+            for month in &months {
+                let label = if highest == Some(month.month) {
+                    format!("{year}/{:02} *", month.month)
+                } else {
+                    format!("{year}/{:02}", month.month)
+                };
+                let Some(stats) = &month.stats else {
+                    table_push_row_elements!(builder, &self.style; label, "", "", "", "");
+                    continue;
+                };
+                debit += stats.debit_amount;
+                credit += stats.credit_amount;
+                table_push_row_elements!(
+                    builder, &self.style;
+                    label,
+                    stats.debit_amount(),
+                    stats.credit_amount(),
+                    Amount(stats.credit_amount - stats.debit_amount, currency),
+                    Amount(month.cumulative_net, currency),
+                );
+            }
+            table_push_row_elements!(
+                builder, &self.style;
+                "total",
+                Amount(debit, currency),
+                Amount(credit, currency),
+                Amount(credit - debit, currency),
+                "",
+            );
+            tables.push((currency, builder));
+        }
+
+        let note = highlighted.then_some("* month with the highest spending");
+        match args.output.get()? {
+            Some((OutputFormat::Html, path)) => {
+                let mut page = HtmlPage::new(&format!("Year {year}"));
+                for (currency, builder) in tables {
+                    page.heading(currency.code()).table(&builder.into());
+                }
+                if let Some(note) = note {
+                    page.note(note);
+                }
+                write_page(&page, &path)?;
+            }
+            None => {
+                for (currency, builder) in tables {
+                    println!("{year} in {}", currency.code());
+                    println!("{}", builder.build());
+                }
+                if let Some(note) = note {
+                    println!("{note}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn digest(&mut self, args: &DigestPeriod) -> Result<()> {
+        self.style.load_category_paths(self.conn)?;
+        let today = chrono::Utc::now().date_naive();
+        let last_viewed = LastViewed::new(self.config, "report")?;
+        let since = match args.since_last {
+            true => last_viewed.get()?,
+            false => None,
+        };
+        let since = since.unwrap_or_else(|| args.since());
+        print!("{}", Digest::load(self.conn, &self.style, since, today)?);
+
+        if !args.no_mark {
+            last_viewed.mark(today)?;
+        }
+        Ok(())
+    }
+}
+
+/// Debits of the last month compared with the average of the preceding ones
+fn trend(months: &[MerchantMonthStats], style: &Style) -> Option<String> {
+    let (last, previous) = months.split_last()?;
+    if previous.is_empty() {
+        return None;
+    }
+
+    let average = (previous
+        .iter()
+        .map(|month| month.debit_amount)
+        .sum::<Decimal>()
+        / Decimal::from(previous.len()))
+    .round_dp(2);
+    let arrow = match last.debit_amount.cmp(&average) {
+        std::cmp::Ordering::Greater => "↑",
+        std::cmp::Ordering::Less => "↓",
+        std::cmp::Ordering::Equal => "=",
+    };
+    Some(format!(
+        "Trend: {arrow} {} against an average of {} over the {} previous month(s)",
+        style.format_amount(last.debit_amount()),
+        style.format_amount(Amount(average, last.currency)),
+        previous.len()
+    ))
+}
+
+fn write_page(page: &HtmlPage, path: &Path) -> Result<()> {
+    page.write(path)
+        .with_context(|| format!("Unable to write {}", path.display()))
 }
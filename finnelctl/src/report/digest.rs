@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use std::fmt::{self, Write};
+
+use finnel::{
+    account::QueryAccount,
+    prelude::*,
+    record::{
+        query::{OrderDirection, OrderField, OrderNulls},
+        QueryRecord,
+    },
+    recurring_payment::{QueryRecurringPayment, RecurringPayment},
+};
+
+use crate::utils::table_display::{RowElementDisplay, Style};
+
+/// Number of largest debits listed
+const LARGEST_DEBITS: i64 = 5;
+/// Number of days ahead looked at for the recurring payments
+const UPCOMING_DAYS: u64 = 7;
+
+/// Summary of the records since a date, written as plain text to be read in
+/// a terminal or sent by mail
+pub struct Digest<'a> {
+    /// Style of the cells, for the paths of the categories
+    style: &'a Style,
+    pub since: NaiveDate,
+    /// Debit and credit of each account since the date
+    pub accounts: Vec<(Account, Decimal, Decimal)>,
+    pub largest_debits: Vec<(Record, Option<Category>, Option<Merchant>)>,
+    /// Merchants of the records since the date without any earlier record
+    pub new_merchants: Vec<Merchant>,
+    pub uncategorized: i64,
+    /// Records flagged as needing attention, whatever their date
+    pub flagged: i64,
+    /// Recurring payments due in the next days, with their due date
+    pub upcoming: Vec<(RecurringPayment, NaiveDate)>,
+}
+
+impl<'a> Digest<'a> {
+    /// Gather the digest of the records from `since`, with the recurring
+    /// payments due in the week following `today`
+    pub fn load(
+        conn: &mut Conn,
+        style: &'a Style,
+        since: NaiveDate,
+        today: NaiveDate,
+    ) -> Result<Self> {
+        let period = || QueryRecord {
+            from: Some(since),
+            operation_date: true,
+            ..Default::default()
+        };
+
+        let records = period().with_account().run(conn)?;
+        let accounts = QueryAccount::default()
+            .run(conn)?
+            .into_iter()
+            .map(|account| {
+                let (mut debit, mut credit) = (Decimal::ZERO, Decimal::ZERO);
+                for (record, _) in records.iter().filter(|(_, a)| a.id == account.id) {
+                    match record.direction {
+                        Direction::Debit => debit += record.amount,
+                        Direction::Credit => credit += record.amount,
+                    }
+                }
+                (account, debit, credit)
+            })
+            .collect();
+
+        let largest_debits = QueryRecord {
+            direction: Some(Direction::Debit),
+            order: vec![(
+                OrderField::Amount,
+                OrderDirection::Desc,
+                OrderNulls::Default,
+            )],
+            count: Some(LARGEST_DEBITS),
+            ..period()
+        }
+        .with_category()
+        .with_merchant()
+        .run(conn)?;
+
+        let mut new_merchants = Vec::<Merchant>::new();
+        let in_period = QueryRecord {
+            order: vec![(OrderField::Date, OrderDirection::Asc, OrderNulls::Default)],
+            ..period()
+        }
+        .with_category()
+        .with_merchant()
+        .run(conn)?;
+        for merchant in in_period
+            .into_iter()
+            .filter_map(|(_, _, merchant)| merchant)
+        {
+            if new_merchants.iter().any(|m| m.id == merchant.id) {
+                continue;
+            }
+            let earlier = QueryRecord {
+                to: Some(since),
+                operation_date: true,
+                merchant_id: Some(Some(merchant.id)),
+                ..Default::default()
+            }
+            .total(conn)?;
+            if earlier == 0 {
+                new_merchants.push(merchant);
+            }
+        }
+
+        let uncategorized = QueryRecord {
+            category_id: Some(None),
+            ..period()
+        }
+        .total(conn)?;
+        let flagged = Record::count_flagged(conn)?;
+
+        let due_by = today + Days::new(UPCOMING_DAYS);
+        let mut upcoming = QueryRecurringPayment::default()
+            .run(conn)?
+            .into_iter()
+            .filter_map(|recpay| {
+                let due = recpay.next_due(today)?;
+                (due <= due_by).then_some((recpay, due))
+            })
+            .collect::<Vec<_>>();
+        upcoming.sort_by_key(|(recpay, due)| (*due, recpay.id));
+
+        Ok(Self {
+            style,
+            since,
+            accounts,
+            largest_debits,
+            new_merchants,
+            uncategorized,
+            flagged,
+            upcoming,
+        })
+    }
+}
+
+/// Write the rows with their columns aligned, the ones in `right` aligned to
+/// the right as they hold amounts
+fn write_rows(f: &mut fmt::Formatter<'_>, rows: &[Vec<String>], right: &[usize]) -> fmt::Result {
+    if rows.is_empty() {
+        return writeln!(f, "  none");
+    }
+
+    let mut widths = Vec::<usize>::new();
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (column, cell) in row.iter().enumerate() {
+            widths[column] = widths[column].max(cell.chars().count());
+        }
+    }
+
+    for row in rows {
+        let mut line = String::new();
+        for (column, cell) in row.iter().enumerate() {
+            let width = widths[column];
+            if right.contains(&column) {
+                write!(line, "  {cell:>width$}")?;
+            } else {
+                write!(line, "  {cell:<width$}")?;
+            }
+        }
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn signed(style: &Style, amount: Decimal, currency: Currency, direction: Direction) -> String {
+    (Amount(amount, currency), direction).to_row_element(style)
+}
+
+impl fmt::Display for Digest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Digest since {}", self.since)?;
+
+        writeln!(f, "\nSpent and received by account")?;
+        let rows = self
+            .accounts
+            .iter()
+            .map(|(account, debit, credit)| {
+                vec![
+                    account.name.clone(),
+                    self.style.format_amount(Amount(*debit, account.currency)),
+                    self.style.format_amount(Amount(*credit, account.currency)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_rows(f, &rows, &[1, 2])?;
+
+        writeln!(f, "\nLargest debits")?;
+        let rows = self
+            .largest_debits
+            .iter()
+            .map(|(record, category, merchant)| {
+                vec![
+                    record.operation_date.to_string(),
+                    signed(self.style, record.amount, record.currency, record.direction),
+                    record.details.clone(),
+                    merchant.as_ref().to_row_element(self.style),
+                    category.as_ref().to_row_element(self.style),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_rows(f, &rows, &[1])?;
+
+        writeln!(f, "\nNew merchants")?;
+        let rows = self
+            .new_merchants
+            .iter()
+            .map(|merchant| vec![merchant.name.clone()])
+            .collect::<Vec<_>>();
+        write_rows(f, &rows, &[])?;
+
+        writeln!(f, "\nUncategorized records: {}", self.uncategorized)?;
+        writeln!(f, "Flagged records: {}", self.flagged)?;
+
+        writeln!(f, "\nDue in the next {UPCOMING_DAYS} days")?;
+        let rows = self
+            .upcoming
+            .iter()
+            .map(|(recpay, due)| {
+                vec![
+                    due.to_string(),
+                    signed(self.style, recpay.amount, recpay.currency, recpay.direction),
+                    recpay.name.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_rows(f, &rows, &[1])
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use chrono::Datelike;
+use serde_json::json;
+
+use finnel::stats::rebuild_months;
+
+use crate::cli::stats::*;
+use crate::config::Config;
+use crate::journal::Journal;
+
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    match command {
+        Command::Rebuild(args) => rebuild(config, args),
+    }
+}
+
+fn rebuild(config: &Config, args: &Rebuild) -> Result<()> {
+    let conn = &mut config.database()?;
+    let year = match args.all {
+        true => None,
+        false => Some(args.year.unwrap_or_else(|| chrono::Utc::now().year())),
+    };
+
+    let rebuilt = rebuild_months(conn, year)?;
+    for warning in &rebuilt.warnings {
+        eprintln!("Warning: {warning}, left out of the stats");
+    }
+    let currencies = rebuilt
+        .currencies
+        .iter()
+        .map(|c| c.code())
+        .collect::<Vec<_>>();
+    match currencies.is_empty() {
+        true => println!("No records to compute the stats of"),
+        false => println!(
+            "Rebuilt the stats of {} month(s) in {}",
+            rebuilt.months,
+            currencies.join(", ")
+        ),
+    }
+    Journal::new(config)?.log(
+        "stats rebuild",
+        json!({ "year": year, "all": args.all }),
+        [],
+    );
+
+    Ok(())
+}
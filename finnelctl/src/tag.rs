@@ -0,0 +1,60 @@
+use anyhow::Result;
+
+use finnel::prelude::*;
+
+use crate::cli::tag::*;
+use crate::config::Config;
+use crate::error::CliError;
+use crate::journal::Journal;
+use crate::utils::table_display::Style;
+
+use serde_json::json;
+use tabled::builder::Builder as TableBuilder;
+
+struct CommandContext<'a> {
+    config: &'a Config,
+    conn: &'a mut Database,
+    style: Style,
+}
+
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    let conn = &mut config.database_for(command)?;
+    let mut cmd = CommandContext {
+        conn,
+        config,
+        style: config.style()?,
+    };
+
+    match &command {
+        Command::List {} => cmd.list(),
+        Command::Delete(args) => cmd.delete(args),
+    }
+}
+
+impl CommandContext<'_> {
+    fn list(&mut self) -> Result<()> {
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, &self.style; "id", "name", "records");
+
+        for (tag, count) in Tag::all_with_count(self.conn)? {
+            table_push_row_elements!(builder, &self.style; tag.id, tag.name, count);
+        }
+
+        println!("{}", builder.build());
+
+        Ok(())
+    }
+
+    fn delete(&mut self, args: &Delete) -> Result<()> {
+        let mut tag = args.identifier.find(self.conn)?;
+
+        if args.confirm && crate::utils::confirm(self.config)? {
+            self.conn.transaction(|conn| tag.delete(conn))?;
+            Journal::new(self.config)?.log("tag delete", json!({}), [tag.id]);
+        } else {
+            anyhow::bail!(CliError::confirmation_required());
+        }
+
+        Ok(())
+    }
+}
@@ -1,23 +1,116 @@
 #[macro_use]
 pub mod table_display;
+pub mod amount;
+pub mod html_table;
+pub mod last_viewed;
+pub mod max_amount;
+pub mod raw_mode;
 
 use anyhow::{Context, Result};
 use std::cell::OnceCell;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
 
-use finnel::Conn;
+use finnel::{diff::FieldDiff, Conn, Currency};
+use tabled::settings::Color;
 
-pub fn confirm() -> Result<bool> {
+use crate::config::Config;
+
+pub fn confirm(config: &Config) -> Result<bool> {
     println!("Do you really want to do that?");
 
+    if config.assume_yes() {
+        println!("yes (--yes)");
+        return Ok(true);
+    }
+
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
 
     Ok(input.trim() == "yes")
 }
 
+/// Print which records were left out of numbers shown in `currency`, if any
+pub fn note_skipped_currencies<'a>(
+    currency: Currency,
+    skipped: impl IntoIterator<Item = &'a (Currency, i64)>,
+) {
+    let skipped = skipped
+        .into_iter()
+        .map(|(other, count)| format!("{count} in {}", other.code()))
+        .collect::<Vec<_>>();
+    if !skipped.is_empty() {
+        eprintln!(
+            "Note: records in other currencies than {} are not included ({})",
+            currency.code(),
+            skipped.join(", ")
+        );
+    }
+}
+
+/// Name of the setting, in the `display` scope, turning the colors off when
+/// false
+pub const COLOR_KEY: &str = "color";
+
+static COLOR: OnceLock<bool> = OnceLock::new();
+
+/// Read the `display/color` setting, once per invocation
+pub fn load_color(config: &Config) -> Result<()> {
+    let setting = config.store()?.scoped(amount::SCOPE)?.get(COLOR_KEY)?;
+    let color = match setting {
+        Some(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Warning: ignoring {}/{COLOR_KEY}, expected true or false",
+                amount::SCOPE
+            );
+            true
+        }),
+        None => true,
+    };
+    let _ = COLOR.set(color);
+    Ok(())
+}
+
+/// Whether stdout is a terminal and colors weren't disabled with `NO_COLOR`
+/// or the `display/color` setting
+pub fn colors_enabled() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && COLOR.get().copied().unwrap_or(true) && std::io::stdout().is_terminal()
+}
+
+/// Print the changed fields one per line as `field  old → new`, aligned on
+/// the arrow, the old values in red and the new ones in green when colored
+pub fn print_diff(fields: &[FieldDiff]) {
+    let colors = colors_enabled();
+    let paint = |value: &Option<String>, color: Color| {
+        let value = value.as_deref().unwrap_or("(none)");
+        if colors {
+            format!("{}{value}{}", color.get_prefix(), color.get_suffix())
+        } else {
+            value.to_string()
+        }
+    };
+
+    let field_width = fields.iter().map(|f| f.0.len()).max().unwrap_or_default();
+    let old_width = fields
+        .iter()
+        .map(|f| f.1.as_deref().unwrap_or("(none)").chars().count())
+        .max()
+        .unwrap_or_default();
+    for (field, old, new) in fields {
+        let padding = old_width - old.as_deref().unwrap_or("(none)").chars().count();
+        println!(
+            "  {field:field_width$}  {}{} → {}",
+            paint(old, Color::FG_RED),
+            " ".repeat(padding),
+            paint(new, Color::FG_GREEN)
+        );
+    }
+}
+
 pub trait DeferrableResolvedUpdateArgs<'a, U, C>: Sized {
     fn new(conn: &mut Conn, args: &'a U) -> Result<Self>;
-    fn get(&'a self, conn: &mut Conn) -> Result<&C>;
+    fn get(&'a self, conn: &mut Conn) -> Result<&'a C>;
 
     fn deferred(args: &'a U) -> DeferredUpdateArgsResolution<'a, U, Self, C> {
         DeferredUpdateArgsResolution::new(args)
@@ -42,7 +135,7 @@ where
         }
     }
 
-    pub fn get(&'a self, conn: &mut Conn) -> Result<&C> {
+    pub fn get(&'a self, conn: &mut Conn) -> Result<&'a C> {
         if self.resolved_args.get().is_none()
             && self.resolved_args.set(R::new(conn, self.args)?).is_err()
         {
@@ -0,0 +1,186 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use tabled::{settings::Color, Table};
+
+use finnel::prelude::*;
+
+use crate::config::Config;
+
+/// Scope and name of the setting in the key-value store
+pub const SCOPE: &str = "display";
+pub const KEY: &str = "amount_style";
+
+/// How amounts are written in the tables
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum AmountStyle {
+    /// `-€ 1,234.50`, using the code for currencies other than €, $ and £
+    #[default]
+    Symbol,
+    /// `-EUR 1,234.50`
+    Code,
+    /// `-1234.50`, without the currency nor separating the thousands
+    Plain,
+}
+
+impl FromStr for AmountStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(style: &str) -> Result<Self> {
+        match style.trim() {
+            "symbol" => Ok(Self::Symbol),
+            "code" => Ok(Self::Code),
+            "plain" => Ok(Self::Plain),
+            _ => anyhow::bail!("Unknown amount style {style:?}, expected symbol, code or plain"),
+        }
+    }
+}
+
+impl AmountStyle {
+    pub fn format(&self, value: Decimal, currency: Currency) -> String {
+        let rounded = value.round_dp(2);
+        let sign = if rounded.is_sign_negative() && !rounded.is_zero() {
+            "-"
+        } else {
+            ""
+        };
+        let number = format!("{:.2}", rounded.abs());
+
+        match self {
+            Self::Symbol => format!("{sign}{} {}", symbol(currency), group_thousands(&number)),
+            Self::Code => format!("{sign}{} {}", currency.code(), group_thousands(&number)),
+            Self::Plain => format!("{sign}{number}"),
+        }
+    }
+}
+
+fn symbol(currency: Currency) -> &'static str {
+    match currency {
+        Currency::EUR => "€",
+        Currency::USD => "$",
+        Currency::GBP => "£",
+        other => other.code(),
+    }
+}
+
+/// Separate the thousands of the integer part of `number` with commas
+fn group_thousands(number: &str) -> String {
+    let (integer, decimals) = number.split_once('.').unwrap_or((number, ""));
+
+    let mut grouped = String::with_capacity(number.len() + integer.len() / 3);
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if !decimals.is_empty() {
+        grouped.push('.');
+        grouped.push_str(decimals);
+    }
+    grouped
+}
+
+/// Read the style from the `display/amount_style` setting, falling back to
+/// the default one when it is invalid so that the setting can still be fixed
+pub fn load_style(config: &Config) -> Result<AmountStyle> {
+    Ok(match config.store()?.scoped(SCOPE)?.get(KEY)? {
+        Some(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring {SCOPE}/{KEY}, {e}");
+            AmountStyle::default()
+        }),
+        None => AmountStyle::default(),
+    })
+}
+
+/// Set when the output is read by another program, which expects numbers
+static FORCE_PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Format the amounts in the plain style from now on, whatever the setting
+pub fn force_plain() {
+    FORCE_PLAIN.store(true, Ordering::Relaxed);
+}
+
+/// Format the amount in the style, unless the plain one is forced
+pub fn format(style: AmountStyle, amount: Amount) -> String {
+    let style = match FORCE_PLAIN.load(Ordering::Relaxed) {
+        true => AmountStyle::Plain,
+        false => style,
+    };
+    style.format(amount.value(), amount.currency())
+}
+
+/// Color the amounts of the records in red for debits and in green for
+/// credits, the directions being the ones of the rows after the header
+pub fn color_directions(table: &mut Table, rows: &[Vec<String>], directions: &[Direction]) {
+    if !super::colors_enabled() {
+        return;
+    }
+    let Some(column) = rows
+        .first()
+        .and_then(|header| header.iter().position(|cell| cell == "amount"))
+    else {
+        return;
+    };
+
+    for (row, direction) in directions.iter().enumerate() {
+        let color = match direction {
+            Direction::Debit => Color::FG_RED,
+            Direction::Credit => Color::FG_GREEN,
+        };
+        table.modify((row + 1, column), color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::assert_eq;
+
+    #[test]
+    fn format() {
+        let amount = |value, scale| Decimal::new(value, scale);
+
+        for (expected, value, currency) in [
+            ("€ 12.50", amount(125, 1), Currency::EUR),
+            ("-€ 12.50", amount(-1250, 2), Currency::EUR),
+            ("$ 0.99", amount(99, 2), Currency::USD),
+            ("£ 1,000.00", amount(1000, 0), Currency::GBP),
+            ("CHF 3.00", amount(3, 0), Currency::CHF),
+            ("-JPY 1,500.00", amount(-1500, 0), Currency::JPY),
+            ("€ 1,234,567.89", amount(1234567891, 3), Currency::EUR),
+            ("-€ 100,000.00", amount(-100000, 0), Currency::EUR),
+            ("€ 0.00", amount(-1, 3), Currency::EUR),
+        ] {
+            assert_eq!(expected, AmountStyle::Symbol.format(value, currency));
+        }
+
+        assert_eq!(
+            "-EUR 1,234.50",
+            AmountStyle::Code.format(amount(-12345, 1), Currency::EUR)
+        );
+        assert_eq!(
+            "USD 999.00",
+            AmountStyle::Code.format(amount(999, 0), Currency::USD)
+        );
+        assert_eq!(
+            "-1234567.89",
+            AmountStyle::Plain.format(amount(-123456789, 2), Currency::EUR)
+        );
+        assert_eq!(
+            "0.50",
+            AmountStyle::Plain.format(amount(5, 1), Currency::GBP)
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(AmountStyle::Code, "code".parse::<AmountStyle>().unwrap());
+        assert_eq!(
+            AmountStyle::Plain,
+            "plain\n".parse::<AmountStyle>().unwrap()
+        );
+        assert!("euro".parse::<AmountStyle>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt::Write;
+
+use finnel::prelude::*;
+use tabled::builder::Builder as TableBuilder;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #eee; }
+tr:nth-child(even) td { background: #f8f8f8; }
+p.note { color: #666; font-size: 0.9em; }
+span.color { display: inline-block; width: 0.8em; height: 0.8em; margin-right: 0.4em; border-radius: 0.2em; }
+";
+
+/// Table written as HTML, converted from the builder of the terminal table
+/// so that both share the construction of the rows. The first row is the
+/// header.
+#[derive(Debug, Clone)]
+pub struct HtmlTable {
+    rows: Vec<Vec<String>>,
+    /// Column of the categories, with the category of each row but the header
+    categories: Option<(usize, Vec<Option<Category>>)>,
+}
+
+impl HtmlTable {
+    /// Show the color and emoji of the categories in the cells of the column,
+    /// given in the order of the rows
+    pub fn with_categories<'a>(
+        mut self,
+        column: usize,
+        categories: impl IntoIterator<Item = Option<&'a Category>>,
+    ) -> Self {
+        self.categories = Some((column, categories.into_iter().map(|c| c.cloned()).collect()));
+        self
+    }
+
+    fn category(&self, row: usize, column: usize) -> Option<&Category> {
+        match &self.categories {
+            Some((category_column, categories)) if row > 0 && column == *category_column => {
+                categories.get(row - 1)?.as_ref()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn build(&self) -> String {
+        let mut html = String::from("<table>\n");
+        for (index, row) in self.rows.iter().enumerate() {
+            let tag = if index == 0 { "th" } else { "td" };
+            html.push_str("  <tr>");
+            for (column, cell) in row.iter().enumerate() {
+                let _ = write!(html, "<{tag}>");
+                if let Some(category) = self.category(index, column) {
+                    if let Some(color) = &category.color {
+                        let _ = write!(
+                            html,
+                            "<span class=\"color\" style=\"background: {}\"></span>",
+                            escape(color)
+                        );
+                    }
+                    // The cell already has it with the display/emoji setting
+                    match &category.emoji {
+                        Some(emoji) if !cell.starts_with(emoji.as_str()) => {
+                            let _ = write!(html, "{} ", escape(emoji));
+                        }
+                        _ => (),
+                    }
+                }
+                let _ = write!(html, "{}</{tag}>", escape(cell));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+        html
+    }
+}
+
+impl From<TableBuilder> for HtmlTable {
+    fn from(builder: TableBuilder) -> Self {
+        Self {
+            rows: builder.into(),
+            categories: None,
+        }
+    }
+}
+
+/// Standalone HTML document, with its style inlined
+#[derive(Debug, Clone)]
+pub struct HtmlPage {
+    title: String,
+    body: String,
+}
+
+impl HtmlPage {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            body: format!("<h1>{}</h1>\n", escape(title)),
+        }
+    }
+
+    pub fn heading(&mut self, text: &str) -> &mut Self {
+        let _ = writeln!(self.body, "<h2>{}</h2>", escape(text));
+        self
+    }
+
+    pub fn table(&mut self, table: &HtmlTable) -> &mut Self {
+        self.body.push_str(&table.build());
+        self
+    }
+
+    pub fn note(&mut self, text: &str) -> &mut Self {
+        let _ = writeln!(self.body, "<p class=\"note\">{}</p>", escape(text));
+        self
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl std::fmt::Display for HtmlPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>{}</title>\n\
+             <style>\n{STYLE}</style>\n\
+             </head>\n\
+             <body>\n{}</body>\n\
+             </html>\n",
+            escape(&self.title),
+            self.body
+        )
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+    use crate::utils::table_display::Style;
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            "Fish &amp; Chips &lt;b&gt; &quot;Joe&#39;s&quot;",
+            super::escape("Fish & Chips <b> \"Joe's\"")
+        );
+    }
+
+    #[test]
+    fn from_tabled() {
+        let style = &Style::default();
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, style; "id", "name");
+        table_push_row_elements!(builder, style; 1, "Bar & Grill");
+
+        assert_eq!(
+            "<table>\n  \
+             <tr><th>id</th><th>name</th></tr>\n  \
+             <tr><td>1</td><td>Bar &amp; Grill</td></tr>\n\
+             </table>\n",
+            HtmlTable::from(builder).build()
+        );
+    }
+
+    #[test]
+    fn with_categories() -> Result<()> {
+        let conn = &mut test::conn()?;
+        let food = test::category!(conn, "Food", color: Some("#c33"), emoji: Some("🍕"));
+        let bills = test::category!(conn, "Bills");
+
+        let style = &Style::default();
+        let mut builder = TableBuilder::new();
+        table_push_row_elements!(builder, style; "category", "total");
+        table_push_row_elements!(builder, style; food.name, "12");
+        table_push_row_elements!(builder, style; bills.name, "30");
+        table_push_row_elements!(builder, style; "", "2");
+
+        assert_eq!(
+            "<table>\n  \
+             <tr><th>category</th><th>total</th></tr>\n  \
+             <tr><td><span class=\"color\" style=\"background: #cc3333\"></span>🍕 Food</td><td>12</td></tr>\n  \
+             <tr><td>Bills</td><td>30</td></tr>\n  \
+             <tr><td></td><td>2</td></tr>\n\
+             </table>\n",
+            HtmlTable::from(builder)
+                .with_categories(0, [Some(&food), Some(&bills), None])
+                .build()
+        );
+
+        Ok(())
+    }
+}
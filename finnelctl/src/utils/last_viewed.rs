@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+
+use crate::config::{Config, ConfigStore};
+
+/// Name of the setting in the scope of each command
+pub const KEY: &str = "last_viewed";
+
+/// Date a command last showed the records, kept as `<scope>/last_viewed` in
+/// the key-value store for `--since-last`
+pub struct LastViewed {
+    store: ConfigStore,
+}
+
+impl LastViewed {
+    pub fn new(config: &Config, scope: &str) -> Result<Self> {
+        Ok(Self {
+            store: config.store()?.scoped(scope)?,
+        })
+    }
+
+    pub fn get(&self) -> Result<Option<NaiveDate>> {
+        self.store
+            .get(KEY)?
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid {} {value:?}", self.store.name(KEY)))
+            })
+            .transpose()
+    }
+
+    /// Move the marker to the date, unless it is already there or later,
+    /// e.g. moved by a concurrent run
+    pub fn mark(&self, date: NaiveDate) -> Result<()> {
+        if self.get()?.is_some_and(|last| last >= date) {
+            return Ok(());
+        }
+        self.store.set(KEY, &date.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn mark() -> Result<()> {
+        with_config(|config| {
+            let date = |day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap();
+            let last_viewed = LastViewed::new(config, "report")?;
+            assert_eq!(None, last_viewed.get()?);
+
+            last_viewed.mark(date(10))?;
+            assert_eq!(Some(date(10)), last_viewed.get()?);
+
+            // Only moves forward
+            last_viewed.mark(date(5))?;
+            assert_eq!(Some(date(10)), last_viewed.get()?);
+            last_viewed.mark(date(12))?;
+            assert_eq!(Some(date(12)), LastViewed::new(config, "report")?.get()?);
+
+            // Each scope has its own
+            assert_eq!(None, LastViewed::new(config, "record")?.get()?);
+
+            Ok(())
+        })
+    }
+}
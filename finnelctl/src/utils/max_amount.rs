@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+
+use finnel::prelude::*;
+
+use crate::config::Config;
+use crate::error::CliError;
+
+/// Scope and name of the setting in the key-value store
+pub const SCOPE: &str = "records";
+pub const KEY: &str = "max_amount";
+
+/// Largest amount a record is created or imported with without being asked,
+/// from the `records/max_amount` setting, to catch typos like `12000` for
+/// `12.00`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MaxAmount(pub Option<Decimal>);
+
+impl MaxAmount {
+    pub fn load(config: &Config) -> Result<Self> {
+        match config.store()?.scoped(SCOPE)?.get(KEY)? {
+            Some(value) => Ok(Self(Some(parse(&value)?))),
+            None => Ok(Self(None)),
+        }
+    }
+
+    /// Fails when the amount is above the threshold, the threshold itself
+    /// being accepted
+    pub fn check(&self, amount: Decimal) -> Result<()> {
+        match self.0 {
+            Some(max) if amount.abs() > max => Err(anyhow!(
+                "amount {} is above {SCOPE}/{KEY} {}",
+                amount.abs(),
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the amount of a record about to be created, failing unless the
+    /// creation is confirmed, in which case it only warns
+    pub fn check_creation(&self, amount: Decimal, confirm: bool) -> Result<()> {
+        if let Err(e) = self.check(amount) {
+            if !confirm {
+                anyhow::bail!(CliError::ConfirmationRequired(format!(
+                    "Record not created, {e}, use --confirm to create it anyway"
+                )));
+            }
+            eprintln!("Warning: {e}");
+        }
+        Ok(())
+    }
+}
+
+/// Threshold as it is saved, failing on anything but a positive amount
+pub fn parse(value: &str) -> Result<Decimal> {
+    let max = finnel::parse::amount(value.trim())?;
+    if max <= Decimal::ZERO {
+        anyhow::bail!("Invalid {SCOPE}/{KEY} {value:?}, expected a positive amount");
+    }
+    Ok(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result};
+
+    #[test]
+    fn check() -> Result<()> {
+        let max = MaxAmount(Some(super::parse("1000")?));
+
+        max.check(Decimal::new(99999, 2))?;
+        max.check(Decimal::new(1000, 0))?;
+        max.check(Decimal::new(-1000, 0))?;
+        assert_eq!(
+            "amount 1000.01 is above records/max_amount 1000",
+            max.check(Decimal::new(100001, 2)).unwrap_err().to_string()
+        );
+        assert!(max.check(Decimal::new(-100001, 2)).is_err());
+
+        MaxAmount::default().check(Decimal::new(12000, 0))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Decimal::new(50, 0), super::parse(" 50\n").unwrap());
+        assert!(super::parse("0").is_err());
+        assert!(super::parse("-5").is_err());
+        assert!(super::parse("lots").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+/// Terminal of stdin reading each key as soon as it is typed, without
+/// echoing it, until dropped
+///
+/// The mode is switched with stty, which is enough to read single keys
+/// without a terminal library.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enable() -> Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_owned();
+        stty(&["-icanon", "-echo", "min", "1", "time", "0"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Err(e) = stty(&[&self.saved]) {
+            eprintln!("Warning: unable to restore the terminal, run `stty sane`. {e:#}");
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .context("Unable to run stty")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "stty {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Read a single key typed in the terminal, None at the end of the input
+pub fn read_key() -> Result<Option<char>> {
+    let _raw_mode = RawMode::enable()?;
+
+    let mut byte = [0; 1];
+    match std::io::stdin().read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(char::from(byte[0]))),
+    }
+}
@@ -1,70 +1,284 @@
+use std::io::{BufWriter, IsTerminal, Write};
 use std::marker::PhantomData;
+use std::sync::OnceLock;
 
 use finnel::{
+    category::{path::SEPARATOR, CategoryPaths},
     prelude::*,
     record::query::{RA, RAC, RACCM, RACM, RC, RCCM, RCM},
 };
 
 use chrono::NaiveDate;
+use tabled::{settings::Color, Table};
+
+use super::amount::{self, AmountStyle};
+use crate::cli::ListOutput;
+use crate::config::Config;
 
 macro_rules! table_push_row_elements {
-    ( $builder:ident, $($col:expr),* $(,)? ) => {
+    ( $builder:ident, $style:expr; $($col:expr),* $(,)? ) => {
         {
             use crate::utils::table_display::RowElementDisplay;
-            $builder.push_record([$(RowElementDisplay::to_row_element(&$col),)*])
+            let style: &crate::utils::table_display::Style = $style;
+            $builder.push_record([$(RowElementDisplay::to_row_element(&$col, style),)*])
         }
     }
 }
 
 macro_rules! table_push_row {
-    ( $builder:ident, $row:expr ) => {{
+    ( $builder:ident, $style:expr, $row:expr ) => {{
         use crate::utils::table_display::RowDisplay;
-        $builder.push_record(RowDisplay::to_row(&$row))
+        $builder.push_record(RowDisplay::to_row(&$row, $style))
     }};
 }
 
-pub fn table_display<T>(rows: Vec<T>)
+/// Print the rows with a header, as a table when writing to a terminal
+///
+/// With `plain` or when the output is redirected, the rows are written as
+/// they come as tab-separated cells, which is much faster for large lists.
+pub fn table_display<T>(rows: Vec<T>, style: &Style, plain: bool) -> std::io::Result<()>
+where
+    T: RowDisplay,
+    PhantomData<T>: RowDisplay,
+{
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    if plain || !std::io::stdout().is_terminal() {
+        plain_display(BufWriter::new(std::io::stdout().lock()), rows, style)
+    } else {
+        let mut cells = vec![PhantomData::<T>.to_row(style)];
+        cells.extend(rows.iter().map(|row| row.to_row(style)));
+
+        let mut table = tabled::builder::Builder::from(cells).build();
+        color_rows(&mut table, &rows);
+
+        println!("{}", table);
+        Ok(())
+    }
+}
+
+/// Print the rows as [`table_display`] does, unless `--output` asks for
+/// delimiter-separated values
+pub fn list_display<T>(
+    rows: Vec<T>,
+    style: &Style,
+    plain: bool,
+    output: &ListOutput,
+) -> std::io::Result<()>
 where
     T: RowDisplay,
     PhantomData<T>: RowDisplay,
 {
-    if !rows.is_empty() {
-        let mut builder = tabled::builder::Builder::new();
-        table_push_row!(builder, PhantomData::<T>);
-        for result in rows {
-            table_push_row!(builder, result);
+    let Some(format) = output.output else {
+        return table_display(rows, style, plain);
+    };
+
+    let header = (!output.no_header).then(|| PhantomData::<T>.to_row(style));
+    separated_display(
+        BufWriter::new(std::io::stdout().lock()),
+        header
+            .into_iter()
+            .chain(rows.iter().map(|row| row.to_row(style))),
+        format.delimiter(),
+    )
+}
+
+/// Print the rows of records as [`list_display`] does, the amounts of the
+/// table colored after the direction of their record
+///
+/// With `empty_header`, the header is printed even when there are no rows.
+pub fn records_display<T>(
+    rows: Vec<T>,
+    style: &Style,
+    plain: bool,
+    output: &ListOutput,
+    empty_header: bool,
+) -> std::io::Result<()>
+where
+    T: RowDisplay + RecordRow,
+    PhantomData<T>: RowDisplay,
+{
+    if output.output.is_some() || (rows.is_empty() && !empty_header) {
+        return list_display(rows, style, plain, output);
+    }
+    if plain || !std::io::stdout().is_terminal() {
+        return plain_display(BufWriter::new(std::io::stdout().lock()), rows, style);
+    }
+
+    let mut cells = vec![PhantomData::<T>.to_row(style)];
+    cells.extend(rows.iter().map(|row| row.to_row(style)));
+    let directions = rows
+        .iter()
+        .map(|row| row.record().direction)
+        .collect::<Vec<_>>();
+
+    let mut table = tabled::builder::Builder::from(cells.clone()).build();
+    color_rows(&mut table, &rows);
+    amount::color_directions(&mut table, &cells, &directions);
+
+    println!("{}", table);
+    Ok(())
+}
+
+/// Color the cells of the rows after the header, when writing to a terminal
+/// and colors weren't disabled
+fn color_rows<T: RowDisplay>(table: &mut Table, rows: &[T]) {
+    if !super::colors_enabled() {
+        return;
+    }
+
+    for (row, value) in rows.iter().enumerate() {
+        for (column, color) in value.colors() {
+            table.modify((row + 1, column), color);
         }
+    }
+}
 
+/// Print the table built with `table_push_row_elements!`, its first row
+/// being the header, unless `--output` asks for delimiter-separated values
+pub fn builder_display(
+    builder: tabled::builder::Builder,
+    output: &ListOutput,
+) -> std::io::Result<()> {
+    let Some(format) = output.output else {
         println!("{}", builder.build());
+        return Ok(());
+    };
+
+    let cells = Vec::<Vec<String>>::from(builder);
+    let skip = usize::from(output.no_header);
+    separated_display(
+        BufWriter::new(std::io::stdout().lock()),
+        cells.into_iter().skip(skip),
+        format.delimiter(),
+    )
+}
+
+/// Write the amounts without currency nor grouping when the listing is read
+/// by another program, to be called before building its rows
+pub fn prepare_output(output: &ListOutput) {
+    if output.output.is_some() {
+        amount::force_plain();
     }
 }
 
+/// Write the rows as delimiter-separated values, quoting the cells when
+/// needed
+pub fn separated_display<W, I>(writer: W, rows: I, delimiter: u8) -> std::io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = Vec<String>>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_writer(writer);
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()
+}
+
+pub fn plain_display<W, T>(mut writer: W, rows: Vec<T>, style: &Style) -> std::io::Result<()>
+where
+    W: Write,
+    T: RowDisplay,
+    PhantomData<T>: RowDisplay,
+{
+    write_plain_row(&mut writer, PhantomData::<T>.to_row(style))?;
+    for row in rows {
+        write_plain_row(&mut writer, row.to_row(style))?;
+    }
+    writer.flush()
+}
+
+fn write_plain_row<W: Write>(writer: &mut W, cells: Vec<String>) -> std::io::Result<()> {
+    let line = cells
+        .into_iter()
+        .map(|cell| cell.replace(['\t', '\n'], " "))
+        .collect::<Vec<_>>()
+        .join("\t");
+    writeln!(writer, "{}", line)
+}
+
 macro_rules! table_display {
-    ( $vec:expr ) => {{
+    ( $config:expr, $style:expr, $vec:expr ) => {{
         use crate::utils::table_display::table_display;
-        table_display($vec);
+        table_display($vec, $style, $config.plain())?;
+    }};
+    ( $config:expr, $style:expr, $output:expr, $vec:expr ) => {{
+        use crate::utils::table_display::list_display;
+        list_display($vec, $style, $config.plain(), $output)?;
     }};
 }
 
+/// What the cells of the tables are written with, beyond the values of the
+/// rows themselves
+#[derive(Default)]
+pub struct Style {
+    /// Full paths of the categories, shown in the category cells instead of
+    /// the name and parent when loaded
+    category_paths: Option<CategoryPaths>,
+    /// How the amounts are written
+    amount: AmountStyle,
+}
+
+impl Style {
+    pub fn new(amount: AmountStyle) -> Self {
+        Self {
+            amount,
+            ..Default::default()
+        }
+    }
+
+    /// Write the amount in the configured style
+    pub fn format_amount(&self, amount: Amount) -> String {
+        amount::format(self.amount, amount)
+    }
+
+    /// Load the full paths of the categories for the category cells, once
+    pub fn load_category_paths(&mut self, conn: &mut Conn) -> finnel::Result<()> {
+        if self.category_paths.is_none() {
+            self.category_paths = Some(CategoryPaths::load(conn)?);
+        }
+        Ok(())
+    }
+}
+
 pub trait RowDisplay {
-    fn to_row(&self) -> Vec<String>;
+    fn to_row(&self, style: &Style) -> Vec<String>;
+
+    /// Colors of the cells of the row by column, shown in the tables written
+    /// to a terminal
+    fn colors(&self) -> Vec<(usize, Color)> {
+        Vec::new()
+    }
 }
 
 impl RowDisplay for Record {
-    fn to_row(&self) -> Vec<String> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
         vec![
-            self.id.to_row_element(),
-            (self.amount(), self.direction).to_row_element(),
-            self.mode.to_row_element(),
-            self.operation_date.to_row_element(),
-            self.value_date.to_row_element(),
-            self.details.to_row_element(),
+            self.id.to_row_element(style),
+            (self.amount(), self.direction).to_row_element(style),
+            self.mode.to_row_element(style),
+            self.operation_date.to_row_element(style),
+            self.value_date.to_row_element(style),
+            self.details.to_row_element(style),
         ]
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        match self.direction.is_debit() && !self.amount.is_zero() {
+            true => vec![(1, Color::FG_RED)],
+            false => Vec::new(),
+        }
+    }
 }
 
 impl RowDisplay for PhantomData<Record> {
-    fn to_row(&self) -> Vec<String> {
+    fn to_row(&self, _style: &Style) -> Vec<String> {
         [
             "id",
             "amount",
@@ -80,256 +294,689 @@ impl RowDisplay for PhantomData<Record> {
 }
 
 impl RowDisplay for RC {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = self.0.to_row();
-        vec.extend([self.1.to_row_element()]);
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = self.0.to_row(style);
+        vec.extend([self.1.to_row_element(style)]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        self.0.colors()
+    }
 }
 
 impl RowDisplay for PhantomData<RC> {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = PhantomData::<Record>.to_row();
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<Record>.to_row(style);
         vec.extend(["category"].map(str::to_owned));
         vec
     }
 }
 
 impl RowDisplay for (Record, Option<&Category>, Option<&Merchant>) {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = self.0.to_row();
-        vec.extend([self.1.to_row_element(), self.2.to_row_element()]);
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = self.0.to_row(style);
+        vec.extend([self.1.to_row_element(style), self.2.to_row_element(style)]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        self.0.colors()
+    }
 }
 
 impl RowDisplay for RCM {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = self.0.to_row();
-        vec.extend([self.1.to_row_element(), self.2.to_row_element()]);
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = self.0.to_row(style);
+        vec.extend([self.1.to_row_element(style), self.2.to_row_element(style)]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        self.0.colors()
+    }
 }
 
 impl RowDisplay for PhantomData<RCM> {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = PhantomData::<Record>.to_row();
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<Record>.to_row(style);
         vec.extend(["category", "merchant"].map(str::to_owned));
         vec
     }
 }
 
 impl RowDisplay for RCCM {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = self.0.to_row();
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = self.0.to_row(style);
         vec.extend([
-            (self.1.as_ref(), self.2.as_ref()).to_row_element(),
-            self.3.to_row_element(),
+            (self.1.as_ref(), self.2.as_ref()).to_row_element(style),
+            self.3.to_row_element(style),
         ]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        self.0.colors()
+    }
 }
 
 impl RowDisplay for PhantomData<RCCM> {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = PhantomData::<Record>.to_row();
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<Record>.to_row(style);
         vec.extend(["categories", "merchant"].map(str::to_owned));
         vec
     }
 }
 
 impl RowDisplay for RA {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = vec![self.1.name.to_row_element()];
-        vec.extend(self.0.to_row());
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = vec![self.1.name.to_row_element(style)];
+        vec.extend(self.0.to_row(style));
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        // After the account column
+        self.0
+            .colors()
+            .into_iter()
+            .map(|(column, color)| (column + 1, color))
+            .collect()
+    }
 }
 
 impl RowDisplay for PhantomData<RA> {
-    fn to_row(&self) -> Vec<String> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
         let mut vec = vec!["account".to_owned()];
-        vec.extend(PhantomData::<Record>.to_row());
+        vec.extend(PhantomData::<Record>.to_row(style));
         vec
     }
 }
 
 impl RowDisplay for RAC {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = vec![self.1.name.to_row_element()];
-        vec.extend(self.0.to_row());
-        vec.extend([self.2.to_row_element()]);
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = vec![self.1.name.to_row_element(style)];
+        vec.extend(self.0.to_row(style));
+        vec.extend([self.2.to_row_element(style)]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        // After the account column
+        self.0
+            .colors()
+            .into_iter()
+            .map(|(column, color)| (column + 1, color))
+            .collect()
+    }
 }
 
 impl RowDisplay for PhantomData<RAC> {
-    fn to_row(&self) -> Vec<String> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
         let mut vec = vec!["account".to_owned()];
-        vec.extend(PhantomData::<Record>.to_row());
+        vec.extend(PhantomData::<Record>.to_row(style));
         vec.extend(["category"].map(str::to_owned));
         vec
     }
 }
 
 impl RowDisplay for RACM {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = vec![self.1.name.to_row_element()];
-        vec.extend(self.0.to_row());
-        vec.extend([self.2.to_row_element(), self.3.to_row_element()]);
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = vec![self.1.name.to_row_element(style)];
+        vec.extend(self.0.to_row(style));
+        vec.extend([self.2.to_row_element(style), self.3.to_row_element(style)]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        // After the account column
+        self.0
+            .colors()
+            .into_iter()
+            .map(|(column, color)| (column + 1, color))
+            .collect()
+    }
 }
 
 impl RowDisplay for PhantomData<RACM> {
-    fn to_row(&self) -> Vec<String> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
         let mut vec = vec!["account".to_owned()];
-        vec.extend(PhantomData::<Record>.to_row());
+        vec.extend(PhantomData::<Record>.to_row(style));
         vec.extend(["category", "merchant"].map(str::to_owned));
         vec
     }
 }
 
 impl RowDisplay for RACCM {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = vec![self.1.name.to_row_element()];
-        vec.extend(self.0.to_row());
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = vec![self.1.name.to_row_element(style)];
+        vec.extend(self.0.to_row(style));
         vec.extend([
-            (self.2.as_ref(), self.3.as_ref()).to_row_element(),
-            self.4.to_row_element(),
+            (self.2.as_ref(), self.3.as_ref()).to_row_element(style),
+            self.4.to_row_element(style),
         ]);
         vec
     }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        // After the account column
+        self.0
+            .colors()
+            .into_iter()
+            .map(|(column, color)| (column + 1, color))
+            .collect()
+    }
 }
 
 impl RowDisplay for PhantomData<RACCM> {
-    fn to_row(&self) -> Vec<String> {
-        let mut vec = PhantomData::<RA>.to_row();
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<RA>.to_row(style);
         vec.extend(["categories", "merchant"].map(str::to_owned));
         vec
     }
 }
 
+/// Duplicate record and the earlier record it duplicates
+impl RowDisplay for (Record, Record) {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let (record, original) = self;
+        vec![
+            record.id.to_row_element(style),
+            original.id.to_row_element(style),
+            (record.amount(), record.direction).to_row_element(style),
+            record.operation_date.to_row_element(style),
+            original.operation_date.to_row_element(style),
+            record.details.to_row_element(style),
+            original.details.to_row_element(style),
+        ]
+    }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        // The id of the original record comes before the amount
+        self.0
+            .colors()
+            .into_iter()
+            .map(|(column, color)| (column + 1, color))
+            .collect()
+    }
+}
+
+impl RowDisplay for PhantomData<(Record, Record)> {
+    fn to_row(&self, _style: &Style) -> Vec<String> {
+        [
+            "id",
+            "duplicate of",
+            "amount",
+            "operation date",
+            "original date",
+            "details",
+            "original details",
+        ]
+        .map(str::to_owned)
+        .into_iter()
+        .collect()
+    }
+}
+
+/// Rows holding a record, as returned by QueryRecord
+pub trait RecordRow {
+    /// Columns of the operation and value dates in the displayed row
+    const DATE_COLUMNS: (usize, usize);
+
+    fn record(&self) -> &Record;
+}
+
+impl RecordRow for RCCM {
+    const DATE_COLUMNS: (usize, usize) = (3, 4);
+
+    fn record(&self) -> &Record {
+        &self.0
+    }
+}
+
+impl RecordRow for RACCM {
+    // After the account column
+    const DATE_COLUMNS: (usize, usize) = (4, 5);
+
+    fn record(&self) -> &Record {
+        &self.0
+    }
+}
+
+/// Row with an additional column for the flag of its record
+pub struct Flagged<T>(pub T);
+
+impl<T> RowDisplay for Flagged<T>
+where
+    T: RowDisplay + RecordRow,
+{
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let record = self.0.record();
+        let flag = match (record.is_flagged(), &record.flag_reason) {
+            (true, Some(reason)) => reason.clone(),
+            (true, None) => "yes".to_owned(),
+            (false, _) => String::new(),
+        };
+
+        let mut vec = self.0.to_row(style);
+        vec.push(flag);
+        vec
+    }
+
+    fn colors(&self) -> Vec<(usize, Color)> {
+        self.0.colors()
+    }
+}
+
+impl<T: RecordRow> RecordRow for Flagged<T> {
+    const DATE_COLUMNS: (usize, usize) = T::DATE_COLUMNS;
+
+    fn record(&self) -> &Record {
+        self.0.record()
+    }
+}
+
+impl RowDisplay for PhantomData<Flagged<RCCM>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<RCCM>.to_row(style);
+        vec.push("flag".to_owned());
+        vec
+    }
+}
+
+impl RowDisplay for PhantomData<Flagged<RACCM>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut vec = PhantomData::<RACCM>.to_row(style);
+        vec.push("flag".to_owned());
+        vec
+    }
+}
+
+/// Row whose dates are written relative to a day, when there is one
+pub struct RelativeDates<T>(pub T, pub Option<NaiveDate>);
+
+impl<T: RecordRow> RelativeDates<T> {
+    /// Columns of the dates with the date they hold
+    fn dates(&self) -> [(usize, NaiveDate); 2] {
+        let record = self.0.record();
+        let (operation, value) = T::DATE_COLUMNS;
+        [
+            (operation, record.operation_date),
+            (value, record.value_date),
+        ]
+    }
+}
+
+impl<T> RowDisplay for RelativeDates<T>
+where
+    T: RowDisplay + RecordRow,
+{
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        let mut row = self.0.to_row(style);
+        if let Some(today) = self.1 {
+            for (column, date) in self.dates() {
+                row[column] = humanize_date(date, today);
+            }
+        }
+        row
+    }
+
+    /// Highlight the dates of today and yesterday
+    fn colors(&self) -> Vec<(usize, Color)> {
+        let mut colors = self.0.colors();
+        if let Some(today) = self.1 {
+            colors.extend(
+                self.dates()
+                    .into_iter()
+                    .filter(|(_, date)| matches!((*date - today).num_days(), -1 | 0))
+                    .map(|(column, _)| (column, Color::BOLD)),
+            );
+        }
+        colors
+    }
+}
+
+impl RowDisplay for PhantomData<RelativeDates<RCCM>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        PhantomData::<RCCM>.to_row(style)
+    }
+}
+
+impl RowDisplay for PhantomData<RelativeDates<RACCM>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        PhantomData::<RACCM>.to_row(style)
+    }
+}
+
+impl RowDisplay for PhantomData<Flagged<RelativeDates<RCCM>>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        PhantomData::<Flagged<RCCM>>.to_row(style)
+    }
+}
+
+impl RowDisplay for PhantomData<Flagged<RelativeDates<RACCM>>> {
+    fn to_row(&self, style: &Style) -> Vec<String> {
+        PhantomData::<Flagged<RACCM>>.to_row(style)
+    }
+}
+
+impl<T: RecordRow> RecordRow for RelativeDates<T> {
+    const DATE_COLUMNS: (usize, usize) = T::DATE_COLUMNS;
+
+    fn record(&self) -> &Record {
+        self.0.record()
+    }
+}
+
+/// Number of days around today within which dates are written relative to
+/// it, farther ones being clearer as dates
+const RELATIVE_DAYS: i64 = 30;
+
+/// Date relative to `today`, like `yesterday`, `3 days ago` or `in 2 days`
+pub fn humanize_date(date: NaiveDate, today: NaiveDate) -> String {
+    match (date - today).num_days() {
+        0 => "today".to_owned(),
+        -1 => "yesterday".to_owned(),
+        1 => "tomorrow".to_owned(),
+        days if days.abs() > RELATIVE_DAYS => date.to_string(),
+        days if days < 0 => format!("{} days ago", -days),
+        days => format!("in {days} days"),
+    }
+}
+
 pub trait RowElementDisplay {
-    fn to_row_element(&self) -> String;
+    fn to_row_element(&self, style: &Style) -> String;
+}
+
+/// Name of the setting, in the `display` scope, prefixing the category cells
+/// with the emoji of the category
+pub const EMOJI_KEY: &str = "emoji";
+
+static EMOJI: OnceLock<bool> = OnceLock::new();
+
+/// Read the `display/emoji` setting, once per invocation
+///
+/// The emojis are left out unless it is true, as some terminals don't give
+/// them the width the tables expect and misalign the columns.
+pub fn load_emoji(config: &Config) -> anyhow::Result<()> {
+    let setting = config.store()?.scoped(amount::SCOPE)?.get(EMOJI_KEY)?;
+    let emoji = match setting {
+        Some(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Warning: ignoring {}/{EMOJI_KEY}, expected true or false",
+                amount::SCOPE
+            );
+            false
+        }),
+        None => false,
+    };
+    let _ = EMOJI.set(emoji);
+    Ok(())
+}
+
+/// Prefix the text with the emoji of the category, if it has one and they
+/// are enabled
+fn with_emoji(category: &Category, text: String) -> String {
+    match &category.emoji {
+        Some(emoji) if EMOJI.get().copied().unwrap_or_default() => format!("{emoji} {text}"),
+        _ => text,
+    }
+}
+
+fn category_path(style: &Style, category: &Category, parent: Option<&Category>) -> String {
+    let path = style
+        .category_paths
+        .as_ref()
+        .and_then(|paths| paths.get(category.id));
+    let path = match path {
+        Some(path) => path.to_owned(),
+        None => match parent {
+            Some(parent) => format!("{}{SEPARATOR}{}", parent.name, category.name),
+            None => category.name.clone(),
+        },
+    };
+    with_emoji(category, path)
 }
 
 impl RowElementDisplay for (Amount, Direction) {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, style: &Style) -> String {
         let mut amount = self.0;
         amount.0.set_sign_negative(self.1.is_debit());
-        amount.to_string()
+        amount.to_row_element(style)
     }
 }
 
 impl RowElementDisplay for Category {
-    fn to_row_element(&self) -> String {
-        self.name.clone()
+    fn to_row_element(&self, _style: &Style) -> String {
+        with_emoji(self, self.name.clone())
     }
 }
 
 impl RowElementDisplay for Merchant {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.name.clone()
     }
 }
 
 impl RowElementDisplay for Option<Category> {
-    fn to_row_element(&self) -> String {
-        self.as_ref().map(|c| c.name.clone()).to_row_element()
+    fn to_row_element(&self, style: &Style) -> String {
+        self.as_ref().to_row_element(style)
     }
 }
 
 impl RowElementDisplay for Option<&Category> {
-    fn to_row_element(&self) -> String {
-        self.as_ref().map(|c| c.name.clone()).to_row_element()
+    fn to_row_element(&self, style: &Style) -> String {
+        self.map(|c| category_path(style, c, None))
+            .to_row_element(style)
     }
 }
 
 impl RowElementDisplay for (Option<Category>, Option<Category>) {
-    fn to_row_element(&self) -> String {
-        if let Some(category) = &self.0 {
-            if let Some(parent) = &self.1 {
-                format!("{}, {}", category.name, parent.name)
-            } else {
-                category.name.clone()
-            }
-        } else {
-            String::new()
-        }
+    fn to_row_element(&self, style: &Style) -> String {
+        (self.0.as_ref(), self.1.as_ref()).to_row_element(style)
     }
 }
 
 impl RowElementDisplay for (Option<&Category>, Option<&Category>) {
-    fn to_row_element(&self) -> String {
-        if let Some(category) = &self.0 {
-            if let Some(parent) = &self.1 {
-                format!("{}, {}", category.name, parent.name)
-            } else {
-                category.name.clone()
-            }
-        } else {
-            String::new()
-        }
+    fn to_row_element(&self, style: &Style) -> String {
+        self.0
+            .map(|category| category_path(style, category, self.1))
+            .unwrap_or_default()
     }
 }
 
 impl RowElementDisplay for Option<Merchant> {
-    fn to_row_element(&self) -> String {
-        self.as_ref().map(|c| c.name.clone()).to_row_element()
+    fn to_row_element(&self, style: &Style) -> String {
+        self.as_ref().map(|c| c.name.clone()).to_row_element(style)
     }
 }
 
 impl RowElementDisplay for Option<&Merchant> {
-    fn to_row_element(&self) -> String {
-        self.as_ref().map(|c| c.name.clone()).to_row_element()
+    fn to_row_element(&self, style: &Style) -> String {
+        self.as_ref().map(|c| c.name.clone()).to_row_element(style)
     }
 }
 
 impl RowElementDisplay for Option<String> {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.clone().unwrap_or_default()
     }
 }
 
 impl RowElementDisplay for String {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.clone()
     }
 }
 
 impl RowElementDisplay for &str {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.to_string()
     }
 }
 
 impl RowElementDisplay for i64 {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.to_string()
     }
 }
 
 impl RowElementDisplay for Amount {
-    fn to_row_element(&self) -> String {
-        self.to_string()
+    fn to_row_element(&self, style: &Style) -> String {
+        style.format_amount(*self)
     }
 }
 
 impl RowElementDisplay for Mode {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.to_string()
     }
 }
 
 impl RowElementDisplay for NaiveDate {
-    fn to_row_element(&self) -> String {
+    fn to_row_element(&self, _style: &Style) -> String {
         self.to_string()
     }
 }
 
 impl RowElementDisplay for Option<NaiveDate> {
-    fn to_row_element(&self) -> String {
-        self.map(|d| d.to_row_element()).unwrap_or_default()
+    fn to_row_element(&self, style: &Style) -> String {
+        self.map(|d| d.to_row_element(style)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::prelude::{assert_eq, Result, *};
+
+    #[test]
+    fn humanize_date() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let date = |days: i64| today + chrono::Duration::days(days);
+
+        for (expected, days) in [
+            ("today", 0),
+            ("yesterday", -1),
+            ("2 days ago", -2),
+            ("30 days ago", -30),
+            ("2024-01-30", -31),
+            ("tomorrow", 1),
+            ("in 3 days", 3),
+            ("in 30 days", 30),
+            ("2024-04-01", 31),
+        ] {
+            assert_eq!(expected, super::humanize_date(date(days), today));
+        }
+    }
+
+    #[test]
+    fn separated_display() -> Result<()> {
+        let rows = || {
+            vec![
+                vec!["id".to_owned(), "details".to_owned()],
+                vec!["1".to_owned(), "Bread, \"fresh\"".to_owned()],
+                vec!["2".to_owned(), "Tab\there".to_owned()],
+            ]
+        };
+
+        let mut csv = Vec::new();
+        super::separated_display(&mut csv, rows(), b',')?;
+        assert_eq!(
+            "id,details\n1,\"Bread, \"\"fresh\"\"\"\n2,Tab\there\n",
+            String::from_utf8(csv)?
+        );
+
+        let mut tsv = Vec::new();
+        super::separated_display(&mut tsv, rows(), b'\t')?;
+        assert_eq!(
+            "id\tdetails\n1\t\"Bread, \"\"fresh\"\"\"\n2\t\"Tab\there\"\n",
+            String::from_utf8(tsv)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_dates() -> Result<()> {
+        let conn = &mut test::conn()?;
+        let account = test::account!(conn, "Cash");
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        test::record!(
+            conn,
+            &account,
+            details: "Beers",
+            operation_date: date(2),
+            value_date: date(4)
+        );
+
+        let rows = finnel::record::QueryRecord::default()
+            .with_category()
+            .with_parent()
+            .with_merchant()
+            .run(conn)?;
+        let style = &Style::default();
+        let row = RelativeDates(rows.into_iter().next().unwrap(), None);
+        assert_eq!(row.0.to_row(style), row.to_row(style));
+
+        assert!(row.colors().is_empty());
+
+        let row = RelativeDates(row.0, Some(date(3)));
+        let cells = row.to_row(style);
+        assert_eq!(
+            vec!["yesterday", "tomorrow"],
+            vec![cells[3].as_str(), cells[4].as_str()]
+        );
+        assert_eq!("Beers", cells[5]);
+        assert_eq!(vec![(3, Color::BOLD)], row.colors());
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_display_same_cells() -> Result<()> {
+        let conn = &mut test::conn()?;
+        let account = test::account!(conn, "Cash");
+        let bar = test::category!(conn, "Bar");
+        let chariot = test::merchant!(conn, "Chariot");
+        test::record!(conn, &account, details: "Beers", category: Some(&bar));
+        test::record!(conn, &account, details: "Wine", merchant: Some(&chariot));
+
+        let rows = finnel::record::QueryRecord::default()
+            .with_category()
+            .with_merchant()
+            .run(conn)?;
+        let style = &Style::default();
+        let expected = {
+            let mut builder = tabled::builder::Builder::new();
+            table_push_row!(builder, style, PhantomData::<RCM>);
+            for row in &rows {
+                table_push_row!(builder, style, *row);
+            }
+            builder
+                .build()
+                .to_string()
+                .lines()
+                .filter(|line| line.starts_with('|'))
+                .map(|line| {
+                    line.trim_matches('|')
+                        .split('|')
+                        .map(|cell| cell.trim().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut output = Vec::new();
+        plain_display(&mut output, rows, style)?;
+        let cells = String::from_utf8(output)?
+            .lines()
+            .map(|line| line.split('\t').map(str::to_string).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(3, cells.len());
+        assert_eq!(expected, cells);
+
+        Ok(())
     }
 }
@@ -0,0 +1,30 @@
+use anyhow::Result;
+
+use crate::cli::workspace::Command;
+use crate::config::{Config, Workspace};
+
+pub fn run(config: &Config, command: &Command) -> Result<()> {
+    match command {
+        Command::List {} => {
+            for workspace in Workspace::all(config.table())? {
+                let marker = match config.workspace.as_ref() == Some(&workspace) {
+                    true => '*',
+                    false => ' ',
+                };
+                println!(
+                    "{marker} {}\t{}",
+                    workspace.name,
+                    config.workspace_database_path(&workspace).display()
+                );
+            }
+        }
+        Command::Current {} => {
+            if let Some(workspace) = &config.workspace {
+                println!("{}", workspace.name);
+            }
+        }
+        Command::SetDefault { name } => config.set_default_workspace(name)?,
+    }
+
+    Ok(())
+}
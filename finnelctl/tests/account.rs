@@ -42,6 +42,69 @@ fn create() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn iban() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Savings "--iban" "fr14 2004 1010 0505 0001 3m02 606").success();
+    cmd!(env, account show Savings)
+        .success()
+        .stdout(str::contains("IBAN: FR1420041010050500013M02606"));
+
+    cmd!(env, account create Bank "--iban" "FR15 2004 1010 0505 0001 3M02 606")
+        .failure()
+        .stderr(str::contains("invalid check digits"));
+
+    cmd!(env, account update Savings "--no-iban").success();
+    cmd!(env, account show Savings)
+        .success()
+        .stdout(str::contains("IBAN").not());
+
+    cmd!(env, account update Savings "--iban" "GB82 WEST 1234 5698 7654 32").success();
+    cmd!(env, account show Savings)
+        .success()
+        .stdout(str::contains("IBAN: GB82WEST12345698765432"));
+
+    Ok(())
+}
+
+#[test]
+fn main_currency() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Dollars "--currency" usd).success();
+    cmd!(env, account show Dollars)
+        .success()
+        .stdout(str::contains("Balance: $ 0.00"));
+
+    env.conf_dir
+        .child("config.toml")
+        .write_str("main_currency = 'EUR'")?;
+
+    cmd!(env, account create Cash)
+        .success()
+        .stderr(str::is_empty());
+    cmd!(env, account create Pounds "--currency" GBP)
+        .failure()
+        .stderr(str::contains(
+            "Account Pounds is in GBP, not in the main currency EUR, use --non-main-ok",
+        ));
+    cmd!(env, account show Pounds).failure();
+
+    cmd!(env, account create Pounds "--currency" GBP "--non-main-ok")
+        .success()
+        .stderr(str::contains(
+            "Warning: Account Pounds is in GBP, not in the main currency EUR",
+        ));
+    cmd!(env, account show Pounds).success();
+
+    cmd!(env, account create Foo "--currency" ABC)
+        .failure()
+        .stderr(str::contains("Unknown currency ABC"));
+
+    Ok(())
+}
+
 #[test]
 fn show() -> Result<()> {
     let env = Env::new()?;
@@ -66,11 +129,209 @@ fn show() -> Result<()> {
     cmd!(env, account show)
         .success()
         .stdout(str::contains("1 | Cash"))
-        .stdout(str::contains("Balance: € 0.00"));
+        .stdout(str::contains("Balance: € 0.00"))
+        .stdout(str::contains("Last 30 days: 0 records, € 0.00 debited"))
+        .stdout(str::contains("Last records").not());
+
+    cmd!(env, record create 10 bread).success();
+    cmd!(env, record create 20 salary "--direction" credit).success();
+    cmd!(env, record create 30 rent "--operation-date" "2000-01-01").success();
+
+    cmd!(env, account show)
+        .success()
+        .stdout(str::starts_with("1 | Cash\n\tBalance: € 0.00\n"))
+        .stdout(str::contains(
+            "Last 30 days: 2 records, € 10.00 debited, € 20.00 credited",
+        ))
+        .stdout(str::contains("Weekly debits: ▁▁▁▁▁▁▁█"))
+        .stdout(str::contains("Last records:"))
+        .stdout(str::contains("bread"))
+        .stdout(str::contains("rent"));
+
+    cmd!(env, account show "--days" 0).failure();
+    cmd!(env, account show "--days" "4000000000")
+        .failure()
+        .stderr(str::contains("Can't summarize the last 4000000000 days"));
+
+    Ok(())
+}
+
+#[test]
+fn adjust() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create 20 groceries "--operation-date" "2024-08-05").success();
+
+    // Less cash in hand than recorded
+    cmd!(env, account adjust "--to" "-25" "--date" "2024-08-10")
+        .success()
+        .stdout("Adjusted the balance of Cash to € -25.00 at 2024-08-10 with a debit of € 5.00\n");
+    cmd!(env, account adjust "--to" "-25" "--date" "2024-08-10")
+        .success()
+        .stdout("The balance of Cash is already € -25.00 at 2024-08-10, nothing to adjust\n");
+
+    // More cash in hand than recorded
+    cmd!(env, account adjust Cash "--to" "10" "--date" "2024-08-12" "--details" "Found in a coat")
+        .success()
+        .stdout(str::contains("with a credit of € 35.00"));
+
+    cmd!(env, record list "--category" "Balance adjustment")
+        .success()
+        .stdout(str::contains("5.00"))
+        .stdout(str::contains("Found in a coat"))
+        .stdout(str::contains("groceries").not());
+    cmd!(env, category show "Balance adjustment")
+        .success()
+        .stdout(str::contains("Stats: excluded"));
+
+    cmd!(env, record list set "max-amount" 100).success();
+    cmd!(env, account adjust "--to" 500 "--date" "2024-08-12")
+        .failure()
+        .stderr(str::contains("is above records/max_amount 100"));
+    cmd!(env, account adjust "--to" 500 "--date" "2024-08-12" "--confirm")
+        .success()
+        .stdout(str::contains("with a credit of € 490.00"));
+
+    Ok(())
+}
+
+#[test]
+fn recompute_balance() -> Result<()> {
+    use diesel::connection::SimpleConnection;
+
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create 20 groceries "--operation-date" "2024-08-05").success();
+    cmd!(env, record create "0,01" rounding "--direction" credit "--operation-date" "2024-08-06")
+        .success();
+    cmd!(env, record create 5 later "--operation-date" "2024-09-01").success();
+
+    cmd!(env, account "recompute-balance" "--date" "2024-08-31")
+        .success()
+        .stdout("Balance of Cash at 2024-08-31: € -19.99 from 2 records\nMatches the balance summed by the database\n");
+
+    // Edited by hand
+    finnel::Database::open(env.data_dir.path().join("db.finnel"))?
+        .batch_execute("UPDATE records SET amount = '5,00' WHERE details = 'later'")?;
+    cmd!(env, account "recompute-balance" Cash)
+        .success()
+        .stderr(str::contains(
+            "has an invalid amount \"5,00\", left out of the balance",
+        ))
+        .stdout(str::contains("€ -19.99 from 2 records"));
+
+    Ok(())
+}
+
+#[test]
+fn reconcile() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create 20 groceries "--operation-date" "2024-08-05").success();
+    cmd!(env, record create 50 refund "--direction" credit "--operation-date" "2024-08-20")
+        .success();
+    cmd!(env, record create "12,50" coffee "--operation-date" "2024-09-02").success();
+    cmd!(env, record create "7,50" lunch "--operation-date" "2024-09-03").success();
+
+    cmd!(env, account reconcile "--statement-balance" 30 "--at" "2024-08-31" "--confirm" "--yes")
+        .success()
+        .stdout(str::contains("Records: € 30.00"))
+        .stdout(str::contains("Never reconciled"))
+        .stdout(str::contains("The balances match"))
+        .stdout(str::contains("Reconciled Cash at 2024-08-31"));
+    cmd!(env, account show)
+        .success()
+        .stdout(str::contains("Reconciled: € 30.00 on 2024-08-31"));
+
+    // The coffee and the lunch aren't on the statement yet
+    cmd!(env, account reconcile "--statement-balance" 30 "--at" "2024-09-30" "--confirm" "--yes")
+        .success()
+        .stdout(str::contains("Records: € 10.00"))
+        .stdout(str::contains("Last reconciled: € 30.00 on 2024-08-31"))
+        .stdout(str::contains("Difference: € 20.00"))
+        .stdout(str::contains("2 record(s) since the last reconciliation"))
+        .stdout(str::contains("Records with unusual amounts").not())
+        .stdout(str::contains("Recent records adding up to the difference"))
+        .stdout(str::contains("groceries").not())
+        .stderr(str::contains("not recording the reconciliation"));
+    cmd!(env, account show)
+        .success()
+        .stdout(str::contains("Reconciled: € 30.00 on 2024-08-31"));
+
+    cmd!(env, account reconcile "--statement-balance" "-5" "--at" "2024-09-30")
+        .success()
+        .stdout(str::contains("Difference: € -15.00"))
+        .stdout(str::contains(
+            "No combination of recent records adds up to the difference",
+        ));
+
+    cmd!(env, account reconcile "--statement-balance" 10 "--at" "2024-09-30")
+        .success()
+        .stdout(str::contains("The balances match"))
+        .stdout(str::contains("Use --confirm to record the reconciliation"));
 
     Ok(())
 }
 
+#[test]
+fn balance_assertions() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, account check)
+        .success()
+        .stdout(str::contains("No balance assertions for Cash"));
+
+    cmd!(env, record create 20 groceries "--operation-date" "2024-08-05").success();
+    cmd!(env, record create 50 refund "--direction" credit "--operation-date" "2024-08-20")
+        .success();
+
+    cmd!(env, account "assert-balance" "2024-08-31" 30)
+        .success()
+        .stderr(str::is_empty());
+    cmd!(env, account "assert-balance" "2024-08-10" "-25")
+        .success()
+        .stderr(str::contains(
+            "Warning: the records add up to € -20.00 at 2024-08-10",
+        ));
+
+    cmd!(env, account check Cash)
+        .code(6)
+        .stdout(str::contains("2024-08-10\t€ -25.00\tFAIL\t€ 5.00"))
+        .stdout(str::contains("2024-08-31\t€ 30.00\tOK"))
+        .stderr(str::contains("1 balance assertion(s) of Cash failed"));
+    cmd!(env, account show)
+        .success()
+        .stdout(str::contains("\tBalance assertions:\n\t\t2024-08-10"));
+
+    // Records after the assertions don't count in them
+    cmd!(env, record create 10 coffee "--operation-date" "2024-09-02")
+        .success()
+        .stderr(str::is_empty());
+    cmd!(env, record create 5 tip "--operation-date" "2024-08-07")
+        .success()
+        .stderr(str::contains(
+            "Warning: 2 balance assertion(s) of Cash may need rechecking with account check",
+        ));
+    cmd!(env, account check)
+        .code(6)
+        .stdout(str::contains("2024-08-10\t€ -25.00\tOK"))
+        .stdout(str::contains("2024-08-31\t€ 30.00\tFAIL\t€ -5.00"));
+
+    Ok(())
+}
+
+/// What `account delete` prints about an empty Cash account
+const EMPTY_REPORT: &str =
+    "Cash holds no records\n\tDebit: € 0.00\n\tCredit: € 0.00\n\tRecurring payments: 0\n";
+
 #[test]
 fn delete() -> Result<()> {
     let env = Env::new()?;
@@ -88,21 +349,45 @@ fn delete() -> Result<()> {
 
     cmd!(env, account delete -A Cash --confirm)
         .failure()
-        .stdout("Do you really want to do that?\n")
+        .stdout(format!("{EMPTY_REPORT}Do you really want to do that?\n"))
         .stderr(str::contains("requires confirmation"));
 
     raw_cmd!(env, account delete -A Cash --confirm)
         .write_stdin("no")
         .assert()
         .failure()
-        .stdout("Do you really want to do that?\n")
+        .stdout(format!("{EMPTY_REPORT}Do you really want to do that?\n"))
         .stderr(str::contains("requires confirmation"));
 
     raw_cmd!(env, account delete -A Cash --confirm)
         .write_stdin("yes")
         .assert()
         .success()
-        .stdout("Do you really want to do that?\n");
+        .stdout(format!("{EMPTY_REPORT}Do you really want to do that?\n"));
+
+    cmd!(env, account show -A Cash)
+        .failure()
+        .stderr(str::contains("Account not found"));
+
+    Ok(())
+}
+
+#[test]
+fn delete_yes() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, account delete Cash "--yes")
+        .failure()
+        .stdout(str::is_empty())
+        .stderr(str::contains("requires confirmation"));
+
+    cmd!(env, account delete Cash "--confirm" "-y")
+        .success()
+        .stdout(format!(
+            "{EMPTY_REPORT}Do you really want to do that?\nyes (--yes)\n"
+        ));
 
     cmd!(env, account show -A Cash)
         .failure()
@@ -111,6 +396,57 @@ fn delete() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn delete_keep_records() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account create Bank).success();
+    cmd!(env, account create Dollars "--currency" USD).success();
+    for (amount, direction, date) in [
+        ("20", "debit", "2024-08-03"),
+        ("100", "credit", "2024-08-07"),
+        ("5", "debit", "2024-08-10"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "payment", "--direction", direction])
+            .args(["--operation-date", date])
+            .assert()
+            .success();
+    }
+
+    cmd!(env, account delete Cash "--reassign-to" Bank "--confirm" "-y")
+        .failure()
+        .stderr(str::contains("--keep-records"));
+
+    cmd!(env, account delete Cash "--keep-records" "--reassign-to" Dollars "--confirm" "-y")
+        .failure()
+        .stderr(str::contains(
+            "Cannot move the records of Cash in EUR to Dollars in USD",
+        ));
+
+    cmd!(env, account delete Cash "--keep-records" "--reassign-to" Bank "--confirm" "-y")
+        .success()
+        .stdout(
+            "Cash holds 3 records, from 2024-08-03 to 2024-08-10\n\
+             \tDebit: € 25.00\n\
+             \tCredit: € 100.00\n\
+             \tRecurring payments: 0\n\
+             The records will be moved to Bank\n\
+             Do you really want to do that?\n\
+             yes (--yes)\n",
+        );
+
+    cmd!(env, account show -A Cash)
+        .failure()
+        .stderr(str::contains("Account not found"));
+    cmd!(env, record list -A Bank)
+        .success()
+        .stdout(str::contains("payment").count(3));
+
+    Ok(())
+}
+
 #[test]
 fn default() -> Result<()> {
     let env = Env::new()?;
@@ -143,3 +479,93 @@ fn default() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn groups() -> Result<()> {
+    let env = Env::new()?;
+
+    for account in ["Cash", "Bank", "Savings"] {
+        raw_cmd!(env, account create)
+            .arg(account)
+            .assert()
+            .success();
+    }
+    cmd!(env, record create -A Cash 10 bread).success();
+    cmd!(env, record create -A Bank 20 groceries).success();
+    cmd!(env, record create -A Savings 300 transfer).success();
+
+    cmd!(env, account group create Daily).success();
+    cmd!(env, account group create Daily)
+        .failure()
+        .stderr(str::contains(
+            "UNIQUE constraint failed: account_groups.name",
+        ));
+    cmd!(env, account group add Daily Cash Bank Savings).success();
+    cmd!(env, account group remove Daily Savings).success();
+    cmd!(env, account group add Daily Unknown).failure();
+
+    cmd!(env, account group list)
+        .success()
+        .stdout(str::contains("| Daily | Bank, Cash |"));
+
+    cmd!(env, record list "-G" Daily)
+        .success()
+        .stdout(str::contains("bread"))
+        .stdout(str::contains("groceries"))
+        .stdout(str::contains("transfer").not())
+        .stdout(str::contains("account"));
+    cmd!(env, record list "-G" Unknown)
+        .failure()
+        .stderr(str::contains("Account group not found: Unknown"));
+    cmd!(env, record list "-G" Daily -A Cash)
+        .failure()
+        .stderr(str::contains("cannot be used with"));
+    cmd!(env, record create "-G" Daily 5 coffee)
+        .failure()
+        .stderr(str::contains(
+            "Group Daily cannot be used with this command, use --account instead",
+        ));
+
+    cmd!(env, account group delete Daily).success();
+    cmd!(env, account group list)
+        .success()
+        .stdout(str::contains("Daily").not());
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("bread"));
+
+    Ok(())
+}
+
+#[test]
+fn reconcile_clears_flags() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create 20 groceries "--operation-date" "2024-08-05").success();
+    cmd!(env, record create 10 coffee "--operation-date" "2024-09-02").success();
+    cmd!(env, record flag 1 "--reason" "receipt").success();
+    cmd!(env, record flag 2).success();
+
+    cmd!(env, account reconcile "--statement-balance" "-20" "--at" "2024-08-31" "--confirm" "--yes")
+        .success()
+        .stdout(str::contains("Cleared the flag of 1 record(s)"));
+    cmd!(env, record list "--flagged")
+        .success()
+        .stdout(str::contains("coffee"))
+        .stdout(str::contains("groceries").not());
+
+    cmd!(env, record list set "clear-flags-on-reconcile" false).success();
+    cmd!(env, account reconcile "--statement-balance" "-30" "--at" "2024-09-30" "--confirm" "--yes")
+        .success()
+        .stdout(str::contains("Reconciled Cash at 2024-09-30"))
+        .stdout(str::contains("Cleared the flag").not());
+    cmd!(env, record list "--flagged")
+        .success()
+        .stdout(str::contains("coffee"));
+
+    cmd!(env, record list set "clear-flags-on-reconcile" maybe).failure();
+
+    Ok(())
+}
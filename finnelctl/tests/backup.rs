@@ -0,0 +1,192 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+use finnel::{
+    category::{ChangeCategory, NewCategory},
+    merchant::{ChangeMerchant, NewMerchant},
+    prelude::{Account, Category, Decimal, Record, RecurringPayment},
+    recurring_payment::NewRecurringPayment,
+};
+use std::path::Path;
+
+fn export(env: &Env, path: &Path) -> Result<assert_cmd::assert::Assert> {
+    Ok(env
+        .command()?
+        .arg("export")
+        .arg("--output")
+        .arg(path)
+        .assert())
+}
+
+fn restore(env: &Env, path: &Path, merge: bool) -> Result<assert_cmd::assert::Assert> {
+    let mut cmd = env.command()?;
+    cmd.arg("restore").arg(path);
+    if merge {
+        cmd.arg("--merge");
+    }
+    Ok(cmd.assert())
+}
+
+fn seed(env: &Env) -> Result<()> {
+    cmd!(env, account create Cash).success();
+    cmd!(env, account create Bank "--iban" "FR14 2004 1010 0505 0001 3M02 606").success();
+
+    let (bar, pub_, tavern, chariot) = {
+        let conn = &mut env.database()?;
+        let alcohol = NewCategory {
+            color: Some("#aa3344"),
+            emoji: Some("🍷"),
+            ..NewCategory::new("Alcohol")
+        }
+        .save(conn)?;
+        let bar = NewCategory {
+            parent: Some(&alcohol),
+            ..NewCategory::new("Bar")
+        }
+        .save(conn)?;
+        let pub_ = NewCategory::new("Pub").save(conn)?;
+        let tavern = NewCategory::new("Tavern").save(conn)?;
+
+        let chariot = NewMerchant {
+            default_category: Some(&bar),
+            website: Some("https://chariot.example.com"),
+            ..NewMerchant::new("Chariot")
+        }
+        .save(conn)?;
+        let netflix = NewMerchant::new("Netflix").save(conn)?;
+
+        let cash = Account::find_by_name(conn, "Cash")?;
+        NewRecurringPayment {
+            name: "Subscription",
+            amount: Decimal::new(1399, 2),
+            merchant: Some(&netflix),
+            ..NewRecurringPayment::new(&cash)
+        }
+        .save(conn)?;
+
+        (bar, pub_, tavern, chariot)
+    };
+
+    cmd!(env, record create -A Cash 5 beer "--category" Pub "--merchant" Chariot).success();
+    cmd!(env, record create -A Bank 10 wine "--category" Bar "--operation-date" "2024-09-01")
+        .success();
+    cmd!(env, record create -A Cash 9 souvenir
+        "--original-amount" 10
+        "--original-currency" USD
+    )
+    .success();
+    cmd!(env, record create 7 taxi "--pending" "--currency" GBP).success();
+    cmd!(env, record show 1 tag add party).success();
+    cmd!(env, record flag 2 "--reason" "check with bank").success();
+
+    // Chain of replacements: Tavern -> Pub -> Bar
+    let conn = &mut env.database()?;
+    ChangeCategory {
+        replaced_by: Some(Some(&pub_)),
+        ..Default::default()
+    }
+    .save(conn, &tavern)?;
+    ChangeCategory {
+        replaced_by: Some(Some(&bar)),
+        ..Default::default()
+    }
+    .save(conn, &pub_)?;
+    assert_eq!(
+        Some(pub_.id),
+        Category::find(conn, tavern.id)?.replaced_by_id
+    );
+    let sas = NewMerchant::new("Chariot SAS").save(conn)?;
+    ChangeMerchant {
+        replaced_by: Some(Some(&chariot)),
+        ..Default::default()
+    }
+    .save(conn, &sas)?;
+
+    Ok(())
+}
+
+#[test]
+fn round_trip() -> Result<()> {
+    let env = Env::new()?;
+    seed(&env)?;
+
+    let backup = env.data_dir.child("backup.json");
+    export(&env, backup.path())?
+        .success()
+        .stdout(str::is_empty());
+    let exported = std::fs::read_to_string(backup.path())?;
+
+    let stdout = cmd!(env, export).success().into_stdout();
+    assert_eq!(exported, stdout);
+    assert_contains_in_order!(
+        exported,
+        "\"version\": 1",
+        "\"name\": \"Bank\"",
+        "\"iban\": \"FR1420041010050500013M02606\"",
+        "\"color\": \"#aa3344\"",
+        "\"emoji\": \"🍷\"",
+        "\"name\": \"Tavern\"",
+        "\"replaced_by\": \"Pub\"",
+        "\"name\": \"Chariot\"",
+        "\"default_category\": \"Bar\"",
+        "\"website\": \"https://chariot.example.com\"",
+        "\"category\": \"Pub\"",
+        "\"party\"",
+        "\"flag_reason\": \"check with bank\"",
+        "\"original_amount\": \"10.000\"",
+        "\"original_currency\": \"USD\"",
+        "\"currency\": \"GBP\"",
+        "\"name\": \"Subscription\"",
+    );
+
+    let other = Env::new()?;
+    restore(&other, backup.path(), false)?
+        .success()
+        .stdout(str::is_empty());
+    assert_eq!(exported, cmd!(other, export).success().into_stdout());
+
+    Ok(())
+}
+
+#[test]
+fn restore_non_empty() -> Result<()> {
+    let env = Env::new()?;
+    seed(&env)?;
+
+    let backup = env.data_dir.child("backup.json");
+    export(&env, backup.path())?.success();
+
+    restore(&env, backup.path(), false)?
+        .failure()
+        .stderr(str::contains("Database is not empty"));
+
+    restore(&env, backup.path(), true)?.success();
+
+    let conn = &mut env.database()?;
+    assert_eq!(8, Record::count(conn)?);
+    assert_eq!(2, RecurringPayment::all(conn)?.len());
+    assert_eq!(
+        Decimal::from(0),
+        Account::find_by_name(conn, "Cash")?.balance
+    );
+
+    Ok(())
+}
+
+#[test]
+fn restore_unsupported_version() -> Result<()> {
+    let env = Env::new()?;
+
+    let backup = env.data_dir.child("backup.json");
+    backup.write_str(
+        r#"{"version": 2, "accounts": [], "categories": [], "merchants": [],
+            "records": [], "recurring_payments": []}"#,
+    )?;
+
+    restore(&env, backup.path(), false)?
+        .failure()
+        .stderr(str::contains("Backup version 2 is not supported"));
+
+    Ok(())
+}
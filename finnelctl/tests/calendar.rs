@@ -0,0 +1,225 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+#[test]
+fn empty_database() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, calendar)
+        .success()
+        .stdout(str::contains("No data yet"))
+        .stdout(str::contains("Monday").not());
+
+    cmd!(env, calendar today)
+        .success()
+        .stdout(str::contains("No data yet"));
+
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("No data yet"));
+
+    Ok(())
+}
+
+#[test]
+fn future_month() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer).success();
+
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("No data yet").not())
+        .stdout(str::contains("future").not())
+        .stdout(str::contains("Debit: € 5.00"));
+
+    cmd!(env, calendar month "--next")
+        .success()
+        .stdout(str::contains("(future, no data)"));
+
+    Ok(())
+}
+
+#[test]
+fn main_currency() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Dollars "--currency" USD).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Dollars 5 beer).success();
+    cmd!(env, record create -A Cash 7 wine).success();
+    env.conf_dir
+        .child("config.toml")
+        .write_str("main_currency = 'USD'")?;
+
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("Debit: $ 5.00"))
+        .stderr(str::contains(
+            "records in other currencies than USD are not included (1 in EUR)",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn account() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account create Bank).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create -A Cash 5 beer).success();
+    cmd!(env, record create -A Bank 20 groceries).success();
+
+    // The default account doesn't restrict the stats
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("Debit: € 25.00"));
+
+    cmd!(env, calendar month -A Cash)
+        .success()
+        .stdout(str::contains("Debit: € 5.00"));
+    cmd!(env, calendar month -A Bank)
+        .success()
+        .stdout(str::contains("Debit: € 20.00"));
+
+    cmd!(env, calendar today -A Bank)
+        .success()
+        .stdout(str::contains("groceries").and(str::contains("beer").not()));
+
+    Ok(())
+}
+
+#[test]
+fn year() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 10 a "--operation-date" "2023-01-05").success();
+    cmd!(env, record create -A Cash 20 b "--operation-date" "2023-01-06").success();
+    cmd!(env, record create -A Cash 30 c "--operation-date" "2023-02-01").success();
+    cmd!(env, record create -A Cash 40 d "--operation-date" "2023-02-28").success();
+    cmd!(env, record create -A Cash 50 e "--operation-date" "2023-03-31").success();
+    cmd!(env, record create -A Cash 1000 salary "--direction" credit "--operation-date" "2023-01-02")
+        .success();
+
+    let output = cmd!(env, calendar year 2023).success().into_stdout();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!("2023, debit", lines[0]);
+    assert_eq!(15 + 1, lines.len());
+    assert_eq!(format!("Jan      .{}", " ".repeat(25)), lines[3]);
+    assert_eq!(format!("Feb :{}*", " ".repeat(26)), lines[4]);
+    assert_eq!(format!("Mar {}#", " ".repeat(30)), lines[5]);
+    assert_eq!(format!("Apr {}", " ".repeat(30)), lines[6]);
+    assert!(lines[15].contains(
+        "' ' up to € 10.00, '.' up to € 20.00, ':' up to € 30.00, '*' up to € 40.00, '#' above"
+    ));
+
+    let output = cmd!(env, calendar year 2023 "--quarter" 1 "--metric" net)
+        .success()
+        .into_stdout();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!("2023 Q1, net", lines[0]);
+    assert_eq!(format!("Jan  #  *:{}", " ".repeat(25)), lines[3]);
+    assert_eq!(format!("Mar {} ", " ".repeat(30)), lines[5]);
+
+    cmd!(env, calendar year 2023 "--quarter" 2)
+        .success()
+        .stdout(str::contains("No data for this period"));
+    cmd!(env, calendar year 2023 "--quarter" 5).failure();
+
+    Ok(())
+}
+
+#[test]
+fn monthly_target() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 320 groceries "--operation-date" "2024-06-10").success();
+    cmd!(env, record create -A Cash 100 rent).success();
+
+    cmd!(env, calendar month "2024/06")
+        .success()
+        .stdout(str::contains("Target").not());
+
+    cmd!(env, calendar config set "monthly-target" "€ 500").success();
+    cmd!(env, calendar config get "monthly-target")
+        .success()
+        .stdout("500\n");
+    cmd!(env, calendar config set "monthly-target" lots)
+        .failure()
+        .stderr(str::contains("\"lots\" is not an amount"));
+
+    // A past month compares the whole month to the target
+    cmd!(env, calendar month "2024/06")
+        .success()
+        .stdout(str::contains("Target: € 500.00"))
+        .stdout(str::contains("Remaining: € 180.00 "));
+
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("Remaining: € 400.00,"))
+        .stdout(str::contains(" per day for "));
+
+    cmd!(env, calendar config set "monthly-target" 300).success();
+    cmd!(env, calendar month "2024/06")
+        .success()
+        .stdout(str::contains("Over target by € 20.00"));
+
+    cmd!(env, calendar config reset "monthly-target").success();
+    cmd!(env, calendar month "2024/06")
+        .success()
+        .stdout(str::contains("Target").not());
+
+    Ok(())
+}
+
+#[test]
+fn first_weekday() -> Result<()> {
+    let env = Env::new()?;
+
+    // September 2024 starts on a Sunday
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 coffee "--operation-date" "2024-09-01").success();
+
+    let monday_first = "\
+| Monday | Tuesday | Wednesday | Thursday | Friday | Saturday | Sunday |
++--------+---------+-----------+----------+--------+----------+--------+
+|        |         |           |          |        |          | 1      |
+";
+    let sunday_first = "\
+| Sunday | Monday | Tuesday | Wednesday | Thursday | Friday | Saturday |
++--------+--------+---------+-----------+----------+--------+----------+
+| 1      | 2      | 3       | 4         | 5        | 6      | 7        |
+";
+
+    cmd!(env, calendar month "2024/09")
+        .success()
+        .stdout(str::contains(monday_first));
+    cmd!(env, calendar month "2024/09" "--first-weekday" sunday)
+        .success()
+        .stdout(str::contains(sunday_first));
+
+    cmd!(env, calendar config set "first-weekday" Sun).success();
+    cmd!(env, calendar config get "first-weekday")
+        .success()
+        .stdout("Sunday\n");
+    cmd!(env, calendar month "2024/09")
+        .success()
+        .stdout(str::contains(sunday_first));
+    cmd!(env, calendar month "2024/09" "--first-weekday" monday)
+        .success()
+        .stdout(str::contains(monday_first));
+
+    cmd!(env, calendar config set "first-weekday" funday)
+        .code(6)
+        .stderr(str::contains("Unknown day \"funday\""));
+
+    Ok(())
+}
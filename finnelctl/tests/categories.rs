@@ -64,6 +64,156 @@ fn list_not_in() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn list_filters() -> Result<()> {
+    let env = Env::new()?;
+
+    // (name, parent, replaced by)
+    let categories = [
+        ("Food", None, None),
+        ("Bars", Some(1), None),
+        ("Pubs", None, Some(1)),
+        ("Bistros", Some(1), Some(2)),
+        ("Beers", Some(2), None),
+        ("Breweries", None, Some(2)),
+        ("Snacks", Some(1), None),
+    ];
+    for (name, parent, replaced_by) in categories {
+        let mut cmd = env.command()?;
+        cmd.args(["category", "create", name]);
+        if let Some(id) = parent {
+            cmd.args(["--parent", &id.to_string()]);
+        }
+        if let Some(id) = replaced_by {
+            cmd.args(["--replace-by", &id.to_string()]);
+        }
+        cmd.assert().success();
+    }
+
+    // None for no filter, Some(None) for the --no-* flag
+    let relations = [None, Some(None), Some(Some(1)), Some(Some(2))];
+    for name in [None, Some("b")] {
+        for count in [None, Some(2)] {
+            for parent in relations {
+                for replaced_by in relations {
+                    let mut args = Vec::new();
+                    if let Some(name) = name {
+                        args.extend(["--name".to_owned(), name.to_owned()]);
+                    }
+                    if let Some(count) = count {
+                        args.extend(["--count".to_owned(), count.to_string()]);
+                    }
+                    match parent {
+                        Some(Some(id)) => args.extend(["--parent".to_owned(), id.to_string()]),
+                        Some(None) => args.push("--no-parent".to_owned()),
+                        None => {}
+                    }
+                    match replaced_by {
+                        Some(Some(id)) => args.extend(["--replace-by".to_owned(), id.to_string()]),
+                        Some(None) => args.push("--no-replace-by".to_owned()),
+                        None => {}
+                    }
+
+                    let expected = categories
+                        .iter()
+                        .zip(1..)
+                        .filter(|((category, category_parent, category_replaced_by), _)| {
+                            name.is_none_or(|name| category.to_lowercase().contains(name))
+                                && parent.is_none_or(|parent| parent == *category_parent)
+                                && replaced_by
+                                    .is_none_or(|replaced_by| replaced_by == *category_replaced_by)
+                        })
+                        .map(|(_, id)| id)
+                        .take(count.unwrap_or(usize::MAX))
+                        .collect::<Vec<_>>();
+
+                    let stdout = raw_cmd!(env, category list)
+                        .args(&args)
+                        .assert()
+                        .success()
+                        .into_stdout();
+                    let ids = stdout
+                        .lines()
+                        .filter_map(|line| line.strip_prefix("| ")?.split(' ').next()?.parse().ok())
+                        .collect::<Vec<_>>();
+
+                    assert_eq!(expected, ids, "category list {}", args.join(" "));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn list_sort() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Restaurant).success();
+    cmd!(env, category create Bar).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer "--category" Bar "--operation-date" "2024-08-01")
+        .success();
+    cmd!(env, record create -A Cash 20 pizza "--category" Restaurant "--operation-date" "2024-08-03").success();
+    cmd!(env, record create -A Cash 7 beer "--category" Bar "--operation-date" "2024-08-02")
+        .success();
+
+    let list = cmd!(env, category list "--sort" name)
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(list, "| Bar", "| Restaurant");
+
+    let list = cmd!(env, category list "--sort" "records.desc")
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(list, "| Bar", "| Restaurant");
+
+    cmd!(env, category list "--sort" "last-used.desc" "--count" 1)
+        .success()
+        .stdout(str::contains("| Restaurant"))
+        .stdout(str::contains("| Bar").not());
+
+    Ok(())
+}
+
+#[test]
+fn list_with_stats() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Bar).success();
+    cmd!(env, category create Restaurant).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer "--category" Bar).success();
+    cmd!(env, record create -A Cash "2,5" peanuts "--category" Bar).success();
+    cmd!(env, record create -A Cash 4 refund "--category" Bar "--direction" credit).success();
+
+    cmd!(env, category list "--with-stats")
+        .success()
+        .stdout(str::contains("| records | debit  |"))
+        .stdout(str::contains(
+            "| Bar        |        |             | 3       | € 7.50 |",
+        ))
+        .stdout(str::contains(
+            "| Restaurant |        |             | 0       | € 0.00 |",
+        ));
+
+    let tomorrow = chrono::Utc::now().date_naive() + chrono::Days::new(1);
+    raw_cmd!(env, category list)
+        .args(["--with-stats", "--from", &tomorrow.to_string()])
+        .assert()
+        .success()
+        .stdout(str::contains(
+            "| Bar        |        |             | 0       | € 0.00 |",
+        ));
+
+    cmd!(env, category list "--from" "2024-01-01")
+        .failure()
+        .stderr(str::contains("--with-stats"));
+
+    Ok(())
+}
+
 #[test]
 fn show() -> Result<()> {
     let env = Env::new()?;
@@ -124,8 +274,8 @@ fn show_records() -> Result<()> {
 
     cmd!(env, category show Bar)
         .success()
-        .stdout(str::contains("€ -5.00"))
-        .stdout(str::contains("€ -10.00"));
+        .stdout(str::contains("-€ 5.00"))
+        .stdout(str::contains("-€ 10.00"));
 
     Ok(())
 }
@@ -152,6 +302,145 @@ fn show_records_from_children_but_not_parents() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn full_path() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Restaurants "--create-parent" Food).success();
+    cmd!(env, category create "Fast food" "--parent" Restaurants).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 burger "--category" "Fast food").success();
+    cmd!(env, record create -A Cash 20 dinner "--category" Restaurants).success();
+
+    cmd!(env, category show Restaurants)
+        .success()
+        .stdout(str::starts_with("2 | Food > Restaurants\n"))
+        .stdout(str::contains("Food > Restaurants > Fast food"));
+
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("burger\tFood > Restaurants > Fast food"))
+        .stdout(str::contains("dinner\tFood > Restaurants"));
+
+    cmd!(env, category list)
+        .success()
+        .stdout(str::contains("| Fast food   | Food > Restaurants |"));
+
+    Ok(())
+}
+
+#[test]
+fn rules() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Withdrawals).success();
+    cmd!(env, category create Refund).success();
+    cmd!(env, category create Groceries).success();
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, category rules add ATM Withdrawals).success();
+    cmd!(env, category rules add direct Refund -d credit).success();
+    cmd!(env, category rules list)
+        .success()
+        .stdout(str::contains(
+            "| 1  | ATM    | any       | any     | 0        | Withdrawals |",
+        ))
+        .stdout(str::contains(
+            "| 2  | Direct | Credit    | any     | 0        | Refund      |",
+        ));
+
+    cmd!(env, record create -A Cash 20 withdrawal -m "ATM Card *1234").success();
+    cmd!(env, record create -A Cash 30 shopping -m ATM "--category" Groceries).success();
+    cmd!(env, record create -A Cash 5 refund -d credit).success();
+    cmd!(env, record create -A Cash 5 bread).success();
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("withdrawal\tWithdrawals"))
+        .stdout(str::contains("shopping\tGroceries"))
+        .stdout(str::contains("refund\tRefund"))
+        .stdout(str::contains("bread\t\t"));
+
+    raw_cmd!(env, category delete Withdrawals --confirm)
+        .write_stdin("yes")
+        .assert()
+        .success()
+        .stdout(str::contains(
+            "Deleting Withdrawals also deletes its rules:",
+        ))
+        .stdout(str::contains("| 1  | ATM  | any       | any     |"));
+    cmd!(env, category rules list)
+        .success()
+        .stdout(str::contains("Withdrawals").not())
+        .stdout(str::contains(
+            "| 2  | Direct | Credit    | any     | 0        | Refund   |",
+        ));
+    cmd!(env, record create -A Cash 20 cash -m ATM).success();
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("cash\t\t"));
+
+    cmd!(env, category rules remove 2).success();
+    cmd!(env, category rules list)
+        .success()
+        .stdout(str::contains("Refund").not());
+
+    Ok(())
+}
+
+#[test]
+fn details_rules() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Subscriptions).success();
+    cmd!(env, category create Energy).success();
+    cmd!(env, category create Withdrawals).success();
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, category rules add netflix Subscriptions "--details").success();
+    cmd!(env, category rules add "^PRLV .*EDF" Energy "--regex" "--priority" 2).success();
+    cmd!(env, category rules add "EDF (" Energy "--regex")
+        .code(6)
+        .stderr(str::contains("Regex \"EDF (\""));
+    cmd!(env, category rules add netflix Subscriptions "--details" "-d" credit).failure();
+    cmd!(env, category rules add Netflix Subscriptions)
+        .failure()
+        .stderr(str::contains("Netflix"));
+    cmd!(env, category rules add ATM Withdrawals).success();
+    cmd!(env, category rules list)
+        .success()
+        .stdout(str::contains(
+            "| 1  | any  | any       | netflix       | 0        | Subscriptions |",
+        ))
+        .stdout(str::contains(
+            "| 2  | any  | any       | /^PRLV .*EDF/ | 2        | Energy        |",
+        ))
+        .stdout(str::contains(
+            "| 3  | ATM  | any       | any           | 0        | Withdrawals   |",
+        ));
+
+    cmd!(env, category rules test "PRLV SEPA EDF NETFLIX")
+        .success()
+        .stdout("Rule 2 gives Energy\n");
+    cmd!(env, category rules test "CB NETFLIX.COM")
+        .success()
+        .stdout("Rule 1 gives Subscriptions\n");
+    cmd!(env, category rules test "CB AMAZON")
+        .success()
+        .stdout("No rule matches\n");
+
+    // The details come before the mode, and an explicit category first
+    cmd!(env, record create -A Cash 15 "CB NETFLIX.COM" -m ATM).success();
+    cmd!(env, record create -A Cash 20 "RETRAIT DAB" -m ATM).success();
+    cmd!(env, record create -A Cash 60 "PRLV SEPA EDF" "--category" Subscriptions).success();
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("CB NETFLIX.COM\tSubscriptions"))
+        .stdout(str::contains("RETRAIT DAB\tWithdrawals"))
+        .stdout(str::contains("PRLV SEPA EDF\tSubscriptions"));
+
+    Ok(())
+}
+
 #[test]
 fn create() -> Result<()> {
     let env = Env::new()?;
@@ -164,6 +453,13 @@ fn create() -> Result<()> {
         .success()
         .stdout(str::is_empty());
 
+    cmd!(env, category create " bar ")
+        .failure()
+        .stderr(str::contains("Category \"Bar\" already exists with id 1"));
+    cmd!(env, category create " ")
+        .failure()
+        .stderr(str::contains("Category name cannot be empty"));
+
     Ok(())
 }
 
@@ -204,6 +500,44 @@ fn update() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn color_emoji() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Pizza "--color" "#aabbc")
+        .failure()
+        .stderr(str::contains(
+            "Invalid category color \"#aabbc\", expected a hex code like #aabbcc",
+        ));
+    cmd!(env, category create Pizza "--color" "#AABBCC" "--emoji" "🍕").success();
+    cmd!(env, category create Bills).success();
+
+    cmd!(env, category show Pizza)
+        .success()
+        .stdout(str::contains("  Color: #aabbcc"))
+        .stdout(str::contains("  Emoji: 🍕"));
+    cmd!(env, category list)
+        .success()
+        .stdout(str::contains("| Pizza"));
+
+    cmd!(env, config set "display/emoji" true).success();
+    cmd!(env, category list)
+        .success()
+        .stdout(str::contains("| 🍕 Pizza"))
+        .stdout(str::contains("| Bills"));
+
+    cmd!(env, category update Pizza "--no-color" "--emoji" "🍔").success();
+    cmd!(env, category update Bills "--color" "#abc" "--no-color")
+        .failure()
+        .stderr(str::contains("cannot be used with"));
+    cmd!(env, category show Pizza)
+        .success()
+        .stdout(str::contains("Color").not())
+        .stdout(str::contains("  Emoji: 🍔"));
+
+    Ok(())
+}
+
 #[test]
 fn delete() -> Result<()> {
     let env = Env::new()?;
@@ -234,3 +568,111 @@ fn delete() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn doctor() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Pub).success();
+    cmd!(env, category create Bar).success();
+    cmd!(env, record create -A Cash 5 beer "--category" Pub).success();
+    cmd!(env, category update Pub "--replace-by" Bar).success();
+
+    cmd!(env, record create -A Cash 3 cider "--category" Pub)
+        .success()
+        .stderr(str::contains("Category Pub has been replaced by Bar"));
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("beer\tPub"))
+        .stdout(str::contains("cider\tBar"));
+
+    cmd!(env, category doctor)
+        .success()
+        .stdout(str::contains("| 1  | beer    | Pub      | Bar         |"))
+        .stdout(str::contains("cider").not());
+
+    cmd!(env, category doctor "--fix")
+        .success()
+        .stdout(str::contains("Moved 1 records"));
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("beer\tBar"));
+    cmd!(env, category doctor)
+        .success()
+        .stdout("No record uses a replaced category\n");
+
+    Ok(())
+}
+
+#[test]
+fn exclude_from_stats() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Transfers "--exclude-from-stats").success();
+    cmd!(env, category create Savings "--parent" Transfers).success();
+    cmd!(env, category create Refunds "--parent" Transfers "--include-in-stats").success();
+    cmd!(env, category create Food "--exclude-from-stats" "--include-in-stats").failure();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer).success();
+    cmd!(env, record create -A Cash 100 "to savings" "--category" Savings).success();
+    cmd!(env, record create -A Cash 20 refund "--category" Refunds).success();
+
+    cmd!(env, category show Transfers)
+        .success()
+        .stdout(str::contains("  Stats: excluded\n"));
+    cmd!(env, category show Savings)
+        .success()
+        .stdout(str::contains("  Stats: excluded, as its parent\n"));
+    cmd!(env, category show Refunds)
+        .success()
+        .stdout(str::contains("  Stats: included\n"));
+    cmd!(env, category list "--output" csv "--no-header")
+        .success()
+        .stdout("1,Transfers,,,yes\n2,Savings,Transfers,,yes\n3,Refunds,Transfers,,\n");
+    cmd!(env, category list "--output" csv)
+        .success()
+        .stdout(str::starts_with(
+            "id,name,parent,replaced by,excluded from stats\n",
+        ));
+
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("Debit: € 25.00"));
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains("to savings"));
+
+    cmd!(env, category update Transfers "--inherit-stats").success();
+    cmd!(env, category show Savings)
+        .success()
+        .stdout(str::contains("Stats:").not());
+    cmd!(env, calendar month)
+        .success()
+        .stdout(str::contains("Debit: € 125.00"));
+
+    Ok(())
+}
+
+#[test]
+fn list_output() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Food).success();
+    cmd!(env, category create Restaurants "--parent" Food).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 1234.5 Dinner "--category" Restaurants).success();
+
+    cmd!(env, category list "--output" tsv "--with-stats")
+        .success()
+        .stdout(
+            "id\tname\tparent\treplaced by\trecords\tdebit\n\
+             1\tFood\t\t\t0\t0.00\n\
+             2\tRestaurants\tFood\t\t1\t1234.50\n",
+        );
+    cmd!(env, category list "--output" csv "--no-header" "--no-parent")
+        .success()
+        .stdout("1,Food,,\n");
+
+    Ok(())
+}
@@ -81,6 +81,14 @@ impl Env {
         Ok(cmd)
     }
 
+    /// Open the database used by the commands, to seed data the CLI cannot
+    /// create
+    pub fn database(&self) -> Result<finnel::Database> {
+        let mut db = finnel::Database::open(self.data_dir.path().join("db.finnel"))?;
+        db.setup()?;
+        Ok(db)
+    }
+
     pub fn copy_fixtures(&self, patterns: &[&str]) -> Result<()> {
         use assert_fs::fixture::PathCopy;
         use std::path::PathBuf;
@@ -0,0 +1,58 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+#[test]
+fn script() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, complete bash)
+        .success()
+        .stdout(str::contains("_finnelctl() {"))
+        .stdout(str::contains("complete -F _finnelctl_values"));
+    cmd!(env, complete zsh)
+        .success()
+        .stdout(str::contains("compdef _finnelctl_values finnelctl"));
+    cmd!(env, complete fish).success().stdout(str::contains(
+        "-l category -x -a '(finnelctl _complete-values --shell fish category 2>/dev/null)'",
+    ));
+    cmd!(env, complete powershell).failure();
+
+    Ok(())
+}
+
+#[test]
+fn values() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, "_complete-values" account).success().stdout("");
+    env.data_dir
+        .child("db.finnel")
+        .assert(predicate::path::missing());
+
+    cmd!(env, account create "Main bank").success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create "Fish & Chips").success();
+    cmd!(env, category create Food).success();
+    cmd!(env, merchant create Chariot).success();
+
+    cmd!(env, "_complete-values" account)
+        .success()
+        .stdout("Cash\nMain\\ bank\n");
+    cmd!(env, "_complete-values" category "--" "Fish\\ ")
+        .success()
+        .stdout("Fish\\ \\&\\ Chips\n");
+    cmd!(env, "_complete-values" category "--shell" zsh)
+        .success()
+        .stdout("Fish & Chips\nFood\n");
+    cmd!(env, "_complete-values" merchant "--shell" fish)
+        .success()
+        .stdout("Chariot\n");
+
+    env.data_dir
+        .child("db.finnel")
+        .write_str("not a database")?;
+    cmd!(env, "_complete-values" account).success().stdout("");
+
+    Ok(())
+}
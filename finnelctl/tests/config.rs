@@ -0,0 +1,134 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+#[test]
+fn store() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, config list).success().stdout(str::is_empty());
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record list set "default-sort" "date.desc").success();
+
+    cmd!(env, config list)
+        .success()
+        .stdout("default_account\nrecords/default_sort\n");
+    cmd!(env, config list records)
+        .success()
+        .stdout("records/default_sort\n");
+    cmd!(env, config get default_account)
+        .success()
+        .stdout("Cash\n");
+
+    cmd!(env, config set "records/default_sort" date).success();
+    cmd!(env, record list get "default-sort")
+        .success()
+        .stdout("date\n");
+
+    cmd!(env, config reset "records/default_sort").success();
+    cmd!(env, config list records)
+        .success()
+        .stdout(str::is_empty());
+
+    cmd!(env, config get "../config.toml")
+        .failure()
+        .stderr(str::contains("cannot start with a dot"));
+    cmd!(env, config set "records/../default_account" Bank)
+        .failure()
+        .stderr(str::contains("cannot start with a dot"));
+    cmd!(env, config get default_account)
+        .success()
+        .stdout("Cash\n");
+
+    Ok(())
+}
+
+#[test]
+fn amount_style() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create "1 234,5" rent).success();
+
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("-€ 1,234.50\t"));
+
+    cmd!(env, config set "display/amount_style" code).success();
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("-EUR 1,234.50\t"));
+
+    cmd!(env, config set "display/amount_style" plain).success();
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("\t-1234.50\t"));
+
+    cmd!(env, config set "display/amount_style" euro).success();
+    cmd!(env, record list)
+        .success()
+        .stderr(str::contains(
+            "Warning: ignoring display/amount_style, Unknown amount style \"euro\"",
+        ))
+        .stdout(str::contains("-€ 1,234.50\t"));
+
+    Ok(())
+}
+
+#[test]
+fn workspaces() -> Result<()> {
+    let env = Env::new()?;
+    env.conf_dir.child("config.toml").write_str(
+        "[workspaces]\n\
+         personal = { filename = \"personal.finnel\" }\n\
+         association = { filename = \"association.finnel\" }\n",
+    )?;
+
+    cmd!(env, workspace list).success().stdout(format!(
+        "  association\t{}\n  personal\t{}\n",
+        env.data_dir.child("association.finnel").path().display(),
+        env.data_dir.child("personal.finnel").path().display(),
+    ));
+    cmd!(env, "-W" personal workspace list)
+        .success()
+        .stdout(str::contains("* personal\t"));
+
+    cmd!(env, "-W" personal account create Cash).success();
+    cmd!(env, "-W" personal account default "-A" Cash).success();
+    cmd!(env, "-W" personal account default)
+        .success()
+        .stdout(str::contains("Cash"));
+    cmd!(env, "-W" association account list)
+        .success()
+        .stdout(str::contains("Cash").not());
+    cmd!(env, "-W" association account default)
+        .success()
+        .stdout(str::contains("Cash").not());
+    assert!(env.data_dir.child("personal.finnel").exists());
+
+    cmd!(env, "-W" work account list)
+        .failure()
+        .stderr(str::contains(
+            "Unknown workspace work, expected one of: association, personal",
+        ));
+    cmd!(env, workspace "set-default" work)
+        .failure()
+        .stderr(str::contains("Unknown workspace work"));
+    cmd!(env, workspace "set-default" personal).success();
+    cmd!(env, config list)
+        .success()
+        .stdout(str::contains("default_workspace\n"));
+
+    // -D is given by the tests, leaving out the default workspace
+    cmd!(env, workspace current)
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, "-W" association workspace current)
+        .success()
+        .stdout("association\n");
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+fn setup(env: &Env) -> Result<()> {
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, category create Food).success();
+    cmd!(env, record create 10 Groceries "--operation-date" "2024-08-01").success();
+
+    Ok(())
+}
+
+#[test]
+fn diff_db() -> Result<()> {
+    let env = Env::new()?;
+    let other = Env::new()?;
+    setup(&env)?;
+    setup(&other)?;
+
+    cmd!(env, record create 5 Bakery "--operation-date" "2024-08-02").success();
+    cmd!(other, account create Bank).success();
+    cmd!(other, record create 7 Cinema "--operation-date" "2024-08-03").success();
+    cmd!(other, record create 8 Museum "--operation-date" "2024-08-04").success();
+    cmd!(other, category create Sweets).success();
+    cmd!(other, category show Food update "--parent" Sweets).success();
+
+    let path = other.data_dir.child("db.finnel");
+    let stdout = raw_cmd!(env, "diff-db" "--limit" 1)
+        .arg(path.path())
+        .assert()
+        .success()
+        .into_stdout();
+
+    assert_contains_in_order!(
+        stdout,
+        "Records only in this database (1)",
+        "Bakery",
+        "Records only in ",
+        " (2)",
+        "Cinema",
+        "... and 1 more",
+        "Entities with different attributes (1)",
+        "categories",
+        "Food",
+        "parent",
+        "Sweets",
+        "Summary",
+        "accounts",
+        "records total (EUR)",
+    );
+    assert!(!stdout.contains("Groceries"), "{}", stdout);
+    assert!(!stdout.contains("Museum"), "{}", stdout);
+
+    raw_cmd!(env, "diff-db")
+        .arg(env.data_dir.child("missing.finnel").path())
+        .assert()
+        .failure()
+        .stderr(str::contains("Database not found"));
+
+    Ok(())
+}
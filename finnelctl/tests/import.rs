@@ -65,7 +65,7 @@ fn default_account() -> Result<()> {
         .assert()
         .success();
 
-    raw_cmd!(env, import -P Boursobank)
+    raw_cmd!(env, import -P Boursobank "--force")
         .arg(env.data_dir.child(csv).as_os_str())
         .assert()
         .success();
@@ -165,6 +165,252 @@ fn pretend() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn porcelain() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/curated.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+
+    raw_cmd!(env, import -P Boursobank --porcelain --pretend)
+        .arg(file.as_os_str())
+        .assert()
+        .code(1)
+        .stdout(str::ends_with(
+            "imported=9 skipped=0 duplicates=0 errors=0 from=2024-06-05 to=2024-06-28\n",
+        ));
+
+    raw_cmd!(env, import -P Boursobank --porcelain "--from" "2024-06-10")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stdout(str::ends_with(
+            "imported=6 skipped=3 duplicates=0 errors=0 from=2024-06-10 to=2024-06-28\n",
+        ));
+
+    // Starting after the last imported date, nothing is left to import
+    raw_cmd!(env, import -P Boursobank --porcelain "--force")
+        .arg(file.as_os_str())
+        .assert()
+        .code(3)
+        .stdout("imported=0 skipped=0 duplicates=9 errors=0 from= to=\n");
+
+    Ok(())
+}
+
+#[test]
+fn progress() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/curated.csv";
+    env.copy_fixtures(&[csv])?;
+
+    raw_cmd!(env, import -P Boursobank "-vv" "--from" "2024-06-10")
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .success()
+        .stderr(str::contains("Imported: 9/9 rows (6 imported, 3 skipped)"));
+
+    Ok(())
+}
+
+#[test]
+fn account_source() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+    cmd!(env, account create Bank).success();
+    cmd!(env, account create Dollars "--currency" USD "--non-main-ok").success();
+
+    let csv = "boursobank/curated.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+
+    raw_cmd!(env, import -P Boursobank --pretend)
+        .arg(file.as_os_str())
+        .assert()
+        .stderr(str::contains(
+            "Importing into account Cash (source: default)",
+        ));
+
+    cmd!(env, import -P Boursobank set "default-account" Bank).success();
+    raw_cmd!(env, import -P Boursobank --pretend)
+        .arg(file.as_os_str())
+        .assert()
+        .stderr(str::contains(
+            "Importing into account Bank (source: profile)",
+        ));
+    raw_cmd!(env, import -P Boursobank --pretend -A Cash)
+        .arg(file.as_os_str())
+        .assert()
+        .stderr(str::contains("Importing into account Cash (source: cli)"));
+
+    raw_cmd!(env, import -P Boursobank -A Dollars)
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains(
+            "Account Dollars is in USD, but profile boursobank imports records in EUR",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn map_account() -> Result<()> {
+    let env = Env::new()?;
+    cmd!(env, account create Bank).success();
+    cmd!(env, account create Joint).success();
+
+    let csv = "boursobank/accounts.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+
+    // No account to import the rows without a mapping into
+    raw_cmd!(env, import -P Boursobank --pretend "--map-account" "00012345678=Bank")
+        .arg(file.as_os_str())
+        .assert()
+        .stderr(str::contains(
+            "Importing account number 00012345678 into account Bank",
+        ))
+        .stdout(str::contains("Skipped 2 rows of unmapped account numbers"));
+
+    raw_cmd!(env, import -P Boursobank --pretend "--strict-accounts")
+        .args(["--map-account", "00012345678=Bank"])
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("account number 00087654321 is not mapped"));
+
+    cmd!(env, import -P Boursobank set "account-map" "00012345678=Bank,00087654321=Unknown")
+        .failure();
+    cmd!(env, import -P Boursobank set "account-map" "00012345678=Joint,00087654321=Joint")
+        .success();
+    cmd!(env, import -P Boursobank get "account-map")
+        .success()
+        .stdout("00012345678=Joint,00087654321=Joint\n");
+
+    // The option takes precedence over the setting for the same number
+    raw_cmd!(env, import -P Boursobank "--map-account" "00012345678=Bank")
+        .arg(file.as_os_str())
+        .assert()
+        .success();
+    cmd!(env, record list -A Bank)
+        .success()
+        .stdout(str::contains("TRANSFERWISE"))
+        .stdout(str::contains("BLOC EN STOCK").not());
+    cmd!(env, record list -A Joint)
+        .success()
+        .stdout(str::contains("BLOC EN STOCK"))
+        .stdout(str::contains("TRANSFERWISE").not());
+
+    Ok(())
+}
+
+#[test]
+fn journal() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/curated.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+
+    cmd!(env, import -P Boursobank history)
+        .success()
+        .stdout(str::contains("2b12fde5").not());
+
+    raw_cmd!(env, import -P Boursobank --pretend)
+        .arg(file.as_os_str())
+        .assert()
+        .failure();
+    raw_cmd!(env, import -P Boursobank)
+        .arg(file.as_os_str())
+        .assert()
+        .success();
+
+    raw_cmd!(env, import -P Boursobank "--from" "2024-06-01")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains(
+            "was already imported with profile boursobank",
+        ))
+        .stderr(str::contains("(9 records), use --force to import it again"));
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("\n10\t").not());
+
+    raw_cmd!(env, import -P Boursobank "--from" "2024-06-01" "--force")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stderr(str::contains("Warning:"));
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("\n18\t"));
+
+    // Another profile doesn't know about the file
+    raw_cmd!(env, import -P Camt053 history)
+        .assert()
+        .success()
+        .stdout(str::contains("2b12fde5").not());
+
+    let hash = "2b12fde5034c2bf2b74b3f9bfeaf4e0c67e0bfb3913316c0941c661881233a41";
+    cmd!(env, import -P Boursobank history)
+        .success()
+        .stdout(str::is_match(format!(
+            r"\| 2 +\| [0-9: -]+ \| 9 +\| {hash} \|"
+        ))?)
+        .stdout(str::is_match(format!(
+            r"\| 1 +\| [0-9: -]+ \| 9 +\| {hash} \|"
+        ))?);
+
+    Ok(())
+}
+
+#[test]
+fn lock() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/curated.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+
+    // Hold the lock as another import would
+    let lock = std::fs::File::create(env.data_dir.child("import.lock").path())?;
+    lock.lock()?;
+
+    raw_cmd!(env, import -P Boursobank)
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("Another import is in progress"))
+        .stderr(str::contains("use --wait SECONDS to wait for it"));
+    raw_cmd!(env, import -P Boursobank "--wait" 0)
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains(
+            "Another import is still in progress after waiting 0 second(s)",
+        ));
+    cmd!(env, record list).success().stdout(str::is_empty());
+
+    // Configuring the profile doesn't need it
+    cmd!(env, import -P Boursobank history).success();
+
+    lock.unlock()?;
+    raw_cmd!(env, import -P Boursobank "--wait" 1)
+        .arg(file.as_os_str())
+        .assert()
+        .success();
+
+    Ok(())
+}
+
 #[test]
 fn print() -> Result<()> {
     let env = Env::new()?;
@@ -183,3 +429,339 @@ fn print() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn fee_rows() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/fees.csv";
+    env.copy_fixtures(&[csv])?;
+
+    raw_cmd!(env, import -P Boursobank --print --pretend)
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .failure()
+        .stdout(str::contains("Skipped 1 zero-amount rows"))
+        .stdout(str::contains("FRAIS PAIEMENT"));
+
+    raw_cmd!(env, import -P BoursoBank set)
+        .arg("merge-fee-rows")
+        .arg("true")
+        .assert()
+        .success();
+
+    raw_cmd!(env, import -P Boursobank --print)
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .success()
+        .stdout(str::contains("Skipped 1 zero-amount rows"))
+        .stdout(str::contains("5.75"))
+        .stdout(str::contains("FRAIS PAIEMENT").not());
+
+    cmd!(env, record show 1).success();
+    cmd!(env, record show 2).failure();
+
+    Ok(())
+}
+
+#[test]
+fn account_categories() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+    cmd!(env, account create Bank).success();
+    cmd!(env, category create Withdrawals).success();
+    cmd!(env, category create Fees).success();
+
+    let csv = "boursobank/atm_fees.csv";
+    env.copy_fixtures(&[csv])?;
+
+    cmd!(env, account config set "atm-category" Withdrawals).success();
+    cmd!(env, account config set "fee-category" 2).success();
+    cmd!(env, account config get "fee-category")
+        .success()
+        .stdout("Fees\n");
+    cmd!(env, account config get "atm-category" -A Bank)
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, account config set "atm-category" Unknown).failure();
+
+    raw_cmd!(env, import -P Boursobank --print --pretend)
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .failure()
+        .stdout(str::contains("Withdrawals"))
+        .stdout(str::contains("Fees"))
+        .stdout(str::contains("Retraits cash").not())
+        .stdout(str::contains("Frais bancaires").not())
+        .stdout(str::contains("Restaurants"));
+
+    raw_cmd!(env, import -P Boursobank -A Bank --print --pretend)
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .failure()
+        .stdout(str::contains("Retraits cash"))
+        .stdout(str::contains("Frais bancaires"))
+        .stdout(str::contains("Withdrawals").not())
+        .stdout(str::contains("Fees").not());
+
+    cmd!(env, account config reset "atm-category").success();
+    cmd!(env, account config get "atm-category")
+        .success()
+        .stdout(str::is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn merchant_alias() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+    cmd!(env, merchant create Chariot).success();
+    cmd!(env, merchant alias add Chariot "LE CHARIOT").success();
+
+    let csv = "boursobank/atm_fees.csv";
+    env.copy_fixtures(&[csv])?;
+
+    raw_cmd!(env, import -P Boursobank)
+        .arg(env.data_dir.child(csv).as_os_str())
+        .assert()
+        .success();
+
+    cmd!(env, merchant list)
+        .success()
+        .stdout(str::contains("Chariot").and(str::contains("le chariot").not()));
+    cmd!(env, record list "--merchant" Chariot)
+        .success()
+        .stdout(str::contains("CHARIOT"));
+
+    Ok(())
+}
+
+#[test]
+fn camt053() -> Result<()> {
+    let env = Env::new()?;
+    cmd!(env, account create Cash).success();
+
+    let xml = "camt053/statement.xml";
+    env.copy_fixtures(&[xml])?;
+
+    raw_cmd!(env, import -P "camt.053" set)
+        .arg("default-file")
+        .arg(env.data_dir.child(xml).as_os_str())
+        .assert()
+        .success();
+    raw_cmd!(env, import -P camt053 set)
+        .arg("default-account")
+        .arg("Cash")
+        .assert()
+        .success();
+
+    cmd!(env, import -P Camt053 "--print")
+        .success()
+        .stdout(str::contains("Bread and croissants"))
+        .stdout(str::contains("Salary July"));
+
+    cmd!(env, record show 3).success();
+
+    Ok(())
+}
+
+#[test]
+fn foreign_currency() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let xml = "camt053/foreign_currency.xml";
+    env.copy_fixtures(&[xml])?;
+    let file = env.data_dir.child(xml);
+
+    raw_cmd!(env, import -P camt053)
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("REF-002 (USD), SVC-003 (GBP)"));
+    cmd!(env, record show 1)
+        .failure()
+        .stderr(str::contains("not found"));
+
+    raw_cmd!(env, import -P camt053 "--coerce-currency" "--porcelain")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stderr(str::contains(
+            "Warning: row 2: USD record into EUR account, imported in EUR",
+        ))
+        .stderr(str::contains("row 3: GBP record into EUR account"))
+        // Nothing was left imported by the failed import
+        .stdout(str::starts_with("imported=3 skipped=0 duplicates=0 "));
+
+    Ok(())
+}
+
+#[test]
+fn large_amounts() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+    cmd!(env, record list set "max-amount" 2000).success();
+
+    let xml = "camt053/foreign_currency.xml";
+    env.copy_fixtures(&[xml])?;
+    let file = env.data_dir.child(xml);
+
+    raw_cmd!(env, import -P camt053 "--coerce-currency")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains(
+            "row 2: amount 2500.00 is above records/max_amount 2000, \
+             use --allow-large-amounts to import it anyway",
+        ));
+
+    raw_cmd!(env, import -P camt053 "--coerce-currency" "--allow-large-amounts" "--porcelain")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stderr(str::contains(
+            "Warning: row 2: amount 2500.00 is above records/max_amount 2000",
+        ))
+        .stdout(str::starts_with("imported=3 "));
+
+    Ok(())
+}
+
+#[test]
+fn skip_errors() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    let csv = "boursobank/bad_rows.csv";
+    env.copy_fixtures(&[csv])?;
+    let file = env.data_dir.child(csv);
+    let rejected = env.data_dir.child("boursobank/bad_rows.rejected.csv");
+
+    raw_cmd!(env, import -P Boursobank)
+        .arg(file.as_os_str())
+        .assert()
+        .failure();
+    cmd!(env, record show 1).failure();
+    rejected.assert(predicate::path::missing());
+
+    raw_cmd!(env, import -P Boursobank "--skip-errors" "--pretend")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stdout(str::contains("Rejected 3 rows"))
+        .stdout(str::contains("Rejected rows written to").not())
+        .stderr(str::contains("pretending"));
+    rejected.assert(predicate::path::missing());
+
+    raw_cmd!(env, import -P Boursobank "--skip-errors" "--porcelain" "--from" "2024-07-01")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stdout(str::contains("Rejected 3 rows"))
+        .stdout(str::contains("| 2   |"))
+        .stdout(str::contains("| 4   |"))
+        .stdout(str::contains("| 5   |"))
+        .stdout(str::contains("beaucoup"))
+        .stdout(str::contains("Rejected rows written to"))
+        .stdout(str::ends_with(
+            "imported=2 skipped=0 duplicates=0 errors=3 from=2024-07-01 to=2024-07-03\n",
+        ));
+
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("Spotify"))
+        .stdout(str::contains("LOYER"))
+        .stdout(str::contains("Netflix").not());
+    rejected.assert(
+        str::contains("PRLV SEPA Netflix")
+            .and(str::contains("PRLV SEPA EDF"))
+            .and(str::contains("Truncated row")),
+    );
+
+    Ok(())
+}
+
+#[test]
+fn legacy_finnel() -> Result<()> {
+    use diesel::{connection::SimpleConnection, Connection};
+
+    let env = Env::new()?;
+    let file = env.data_dir.child("old.finnel");
+    diesel::SqliteConnection::establish(&file.path().display().to_string())?.batch_execute(
+        "CREATE TABLE finnel (key TEXT PRIMARY KEY, value TEXT);
+         CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT, balance TEXT, currency TEXT);
+         CREATE TABLE categories (id INTEGER PRIMARY KEY, name TEXT);
+         CREATE TABLE merchants (id INTEGER PRIMARY KEY, name TEXT, default_category INTEGER);
+         CREATE TABLE records (
+             id INTEGER PRIMARY KEY, account INTEGER, amount TEXT, operation_date TEXT,
+             value_date TEXT, direction TEXT, mode TEXT, details TEXT, category INTEGER,
+             merchant INTEGER
+         );
+         INSERT INTO accounts VALUES (1, 'Checking', '87.5', 'EUR');
+         INSERT INTO categories VALUES (1, 'Bars');
+         INSERT INTO merchants VALUES (1, 'Chariot', 1);
+         INSERT INTO records VALUES
+             (1, 1, '12.5', '2024-07-01 10:00:00+00:00', '2024-07-01 10:00:00+00:00',
+              'Debit', 'Direct', 'Beer', 1, 1),
+             (2, 1, '3', '2024-07-03 10:00:00+00:00', '2024-07-03 10:00:00+00:00',
+              'Debit', 'Cheque', 'Bread', NULL, NULL),
+             (3, 2, '1', '2024-07-04 10:00:00+00:00', '2024-07-04 10:00:00+00:00',
+              'Debit', 'Direct', 'Lost', NULL, NULL);",
+    )?;
+    let content = std::fs::read(file.path())?;
+
+    raw_cmd!(env, import -P "legacy-finnel" "--pretend" "--skip-errors")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("pretending"));
+    cmd!(env, account show -A Checking).failure();
+
+    raw_cmd!(env, import -P "legacy-finnel")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains(
+            "Unable to recreate 1 records: Record 3: account 2 not found, use --skip-errors",
+        ));
+    cmd!(env, account show -A Checking).failure();
+
+    raw_cmd!(env, import -P "legacy-finnel" "--porcelain" "--skip-errors")
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stderr(str::contains(
+            "Warning: Record 2: unknown mode \"Cheque\", imported as Direct",
+        ))
+        .stdout(str::contains(
+            "Rejected 1 records\nRecord 3: account 2 not found\n",
+        ))
+        .stderr(str::contains(
+            "Created 1 accounts, 1 categories, 1 merchants and 2 records",
+        ))
+        .stdout(str::ends_with(
+            "imported=2 skipped=0 duplicates=0 errors=1 from=2024-07-01 to=2024-07-03\n",
+        ));
+
+    cmd!(env, record list -A Checking)
+        .success()
+        .stdout(str::contains("Beer"))
+        .stdout(str::contains("Chariot"))
+        .stdout(str::contains("Bread"));
+
+    raw_cmd!(env, import -P "legacy-finnel")
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("already imported"));
+
+    assert!(
+        content == std::fs::read(file.path())?,
+        "Legacy file was modified"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct CommandSchema {
+    name: String,
+    about: Option<String>,
+    args: Vec<ArgSchema>,
+    groups: Vec<GroupSchema>,
+    subcommands: Vec<CommandSchema>,
+}
+
+impl CommandSchema {
+    fn subcommand(&self, path: &[&str]) -> &CommandSchema {
+        path.iter().fold(self, |command, name| {
+            command
+                .subcommands
+                .iter()
+                .find(|sub| sub.name == *name)
+                .unwrap_or_else(|| panic!("No subcommand {name} in {}", command.name))
+        })
+    }
+
+    fn arg(&self, id: &str) -> &ArgSchema {
+        self.args
+            .iter()
+            .find(|arg| arg.id == id)
+            .unwrap_or_else(|| panic!("No argument {id} in {}", self.name))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ArgSchema {
+    id: String,
+    long: Option<String>,
+    positional: bool,
+    required: bool,
+    global: bool,
+    multiple: bool,
+    value_type: String,
+    possible_values: Vec<PossibleValueSchema>,
+    default_values: Vec<String>,
+    help: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PossibleValueSchema {
+    name: String,
+    help: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupSchema {
+    id: String,
+    args: Vec<String>,
+}
+
+#[test]
+fn json() -> Result<()> {
+    let env = Env::new()?;
+
+    let output = cmd!(env, introspect "--format" json)
+        .success()
+        .into_stdout();
+    let schema: CommandSchema = serde_json::from_str(&output)?;
+    assert_eq!("finnelctl", schema.name);
+
+    let sort = schema.subcommand(&["record", "list"]).arg("sort");
+    assert_eq!(Some("sort"), sort.long.as_deref());
+    assert_eq!("enum", sort.value_type);
+    assert!(sort.multiple);
+    let names = sort
+        .possible_values
+        .iter()
+        .map(|value| value.name.as_str())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"date.desc"));
+    assert!(names.contains(&"merchant_id.desc.nulls_last"));
+    let date = sort
+        .possible_values
+        .iter()
+        .find(|v| v.name == "date")
+        .unwrap();
+    assert!(date.help.is_some());
+
+    let list = schema.subcommand(&["record", "list"]);
+    assert_eq!("date", list.arg("from").value_type);
+    let category_args = list
+        .groups
+        .iter()
+        .find(|g| g.id == "category_args")
+        .unwrap();
+    assert_eq!(vec!["category", "no_category"], category_args.args);
+
+    let create = schema.subcommand(&["account", "create"]);
+    assert_eq!(Some("Create a new account"), create.about.as_deref());
+    let name = create.arg("name");
+    assert!(name.positional && name.required);
+    assert_eq!("string", name.value_type);
+    assert!(name.help.is_some());
+    let currency = create.arg("currency");
+    assert_eq!(vec!["EUR"], currency.default_values);
+    assert_eq!("flag", create.arg("non_main_ok").value_type);
+    // Global arguments are propagated to the subcommands
+    assert!(create.arg("account").global);
+
+    let key = schema.subcommand(&["account", "config", "get"]).arg("key");
+    let names = key
+        .possible_values
+        .iter()
+        .map(|value| value.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["atm-category", "fee-category"], names);
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+use serde_json::json;
+
+#[test]
+fn journal() -> Result<()> {
+    let env = Env::new()?;
+    let journal = env.data_dir.child("journal.jsonl");
+
+    // Disabled by default
+    cmd!(env, account create Cash).success();
+    journal.assert(predicate::path::missing());
+    cmd!(env, journal show)
+        .success()
+        .stdout("No operations journaled\n")
+        .stderr(str::contains("the journal is disabled"));
+
+    cmd!(env, config set "journal/enabled" true).success();
+    cmd!(env, account create Bank).success();
+    cmd!(env, category create Food).success();
+    cmd!(env, record create 12 Bakery -A Bank "--category" Food).success();
+    cmd!(env, record update 1 "--details" Bread).success();
+    cmd!(env, record show 1 delete "--confirm" "--yes").success();
+
+    cmd!(env, journal show)
+        .success()
+        .stdout(str::contains("account create\t2\t{"))
+        .stdout(str::contains(r#""name":"Bank""#))
+        .stdout(str::contains("category create\t1\t"))
+        .stdout(str::contains("record create\t1\t"))
+        .stdout(str::contains(r#""details":"Bakery""#))
+        .stdout(str::contains("record update\t1\t{\"details\":\"Bread\"}"))
+        .stdout(str::contains("record delete\t1\t{}"));
+
+    cmd!(env, journal show "--tail" 2)
+        .success()
+        .stdout(str::contains("record update"))
+        .stdout(str::contains("record delete"))
+        .stdout(str::contains("record create").not());
+
+    // One JSON object per line
+    let content = std::fs::read_to_string(journal.path())?;
+    assert_eq!(5, content.lines().count());
+    for line in content.lines() {
+        let entry: serde_json::Value = serde_json::from_str(line)?;
+        assert!(entry["at"].is_string());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn journal_other_commands() -> Result<()> {
+    let env = Env::new()?;
+    let journal = env.data_dir.child("journal.jsonl");
+
+    cmd!(env, config set "journal/enabled" true).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, merchant create Bakery).success();
+    cmd!(env, record create 4 Bread -A Cash "--operation-date" "2024-08-01").success();
+    cmd!(env, record flag 1 "--reason" "check").success();
+    cmd!(env, record unflag 1).success();
+    cmd!(env, record list "shift-dates" "--days" 1 "--confirm" "--yes").success();
+    cmd!(env, merchant alias add Bakery "BAKERY SARL").success();
+    cmd!(env, account group create Daily).success();
+
+    let commands = std::fs::read_to_string(journal.path())?
+        .lines()
+        .map(|line| Ok(serde_json::from_str::<serde_json::Value>(line)?))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .map(|entry| (entry["command"].clone(), entry["ids"].clone()))
+        .collect::<Vec<_>>();
+    let expected = [
+        ("record flag", json!([1])),
+        ("record unflag", json!([1])),
+        ("record shift-dates", json!([1])),
+        ("merchant alias add", json!([1])),
+        ("account group create", json!([1])),
+    ];
+    for (command, ids) in expected {
+        assert!(
+            commands.contains(&(json!(command), ids)),
+            "{command} not journaled"
+        );
+    }
+
+    Ok(())
+}
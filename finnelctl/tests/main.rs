@@ -23,6 +23,42 @@ fn consolidate() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn consolidate_dedup_categories() -> Result<()> {
+    let env = Env::new()?;
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Restaurants).success();
+    cmd!(env, category create Food).success();
+    cmd!(env, category create "Restaurants (3)" "--parent" Food).success();
+    cmd!(env, record create -A Cash 20 dinner "--category" Restaurants).success();
+
+    cmd!(env, consolidate "--dry-run")
+        .failure()
+        .stderr(str::contains("--dedup-categories"));
+    cmd!(env, consolidate "--dedup-categories" "--dry-run")
+        .success()
+        .stdout(str::contains(
+            "Would merge Restaurants (1) into Food > Restaurants (3)",
+        ));
+    cmd!(env, category show 1).success();
+
+    cmd!(env, consolidate "--dedup-categories")
+        .success()
+        .stdout(str::contains(
+            "Merged Restaurants (1) into Food > Restaurants (3)",
+        ));
+    cmd!(env, category show 1).failure();
+    cmd!(env, record list "-A" Cash)
+        .success()
+        .stdout(str::contains("Food > Restaurants"));
+
+    cmd!(env, consolidate "--dedup-categories")
+        .success()
+        .stdout(str::contains("No duplicate categories"));
+
+    Ok(())
+}
+
 #[test]
 fn reset() -> Result<()> {
     let env = Env::new()?;
@@ -44,3 +80,121 @@ fn reset() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn reset_yes() -> Result<()> {
+    let env = Env::new()?;
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, reset "--yes")
+        .failure()
+        .stdout(str::is_empty())
+        .stderr(str::contains("Usage:"));
+
+    cmd!(env, reset "--confirm" "--yes")
+        .success()
+        .stdout("Do you really want to do that?\nyes (--yes)\n");
+
+    cmd!(env, account show -A Cash)
+        .failure()
+        .stderr(str::contains("Account not found"));
+
+    Ok(())
+}
+
+#[test]
+fn exit_codes() -> Result<()> {
+    let env = Env::new()?;
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Food).success();
+
+    cmd!(env, account show "-A" Missing)
+        .code(4)
+        .stderr(str::contains("Account not found: Missing"));
+    cmd!(env, account delete "-A" Cash)
+        .code(7)
+        .stderr(str::contains("operation requires confirmation"));
+    cmd!(env, category create Food)
+        .code(5)
+        .stderr(str::contains("Category \"Food\" already exists"));
+    cmd!(env, account show "-A" Cash "--unknown").code(2);
+    cmd!(env, "--help")
+        .success()
+        .stdout(str::contains("7   Confirmation required"));
+
+    Ok(())
+}
+
+#[test]
+fn readonly_outdated_schema() -> Result<()> {
+    use diesel::connection::SimpleConnection;
+
+    let env = Env::new()?;
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer).success();
+
+    // Pretend the last migration is yet to be applied
+    let path = env.data_dir.path().join("db.finnel");
+    finnel::Database::open(&path)?.batch_execute(
+        "DELETE FROM __diesel_schema_migrations
+         WHERE version = (SELECT MAX(version) FROM __diesel_schema_migrations)",
+    )?;
+    let content = std::fs::read(&path)?;
+
+    cmd!(env, record list -A Cash)
+        .failure()
+        .stderr(str::contains("run any write command to migrate it"));
+    cmd!(env, calendar).failure();
+
+    assert!(content == std::fs::read(&path)?, "Database was modified");
+
+    Ok(())
+}
+
+#[test]
+fn bugreport() -> Result<()> {
+    let env = Env::new()?;
+    env.conf_dir.child("config.toml").write_str(&format!(
+        "data_dir = {:?}\n[db]\nfilename = \"bug.finnel\"\n",
+        env.data_dir.path().display()
+    ))?;
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    raw_cmd!(env, import -P Boursobank set)
+        .arg("default-file")
+        .arg(env.data_dir.child("secret-statement.csv").path())
+        .assert()
+        .success();
+
+    let stdout = cmd!(env, bugreport).success().into_stdout();
+
+    assert!(stdout.contains(&format!("finnelctl {} ", env!("CARGO_PKG_VERSION"))));
+    assert!(stdout.contains(&format!("finnel {} ", finnel::VERSION)));
+    assert!(stdout.contains(&format!("os {} ", std::env::consts::OS)));
+    assert_contains_in_order!(
+        stdout,
+        "```\n",
+        "sqlite 3.",
+        "key_value_store = [\"boursobank/default_file\", \"default_account\"]",
+        "filename = \"bug.finnel\"",
+        "migrations = [\"20240710083123\"",
+        "```\n",
+    );
+
+    for secret in [
+        env.conf_dir.path().display().to_string(),
+        env.data_dir.path().display().to_string(),
+        std::env::var("HOME")?,
+        "secret-statement".to_string(),
+        "Cash".to_string(),
+    ] {
+        assert!(
+            !stdout.contains(&secret),
+            "{:?} leaked in bug report:\n{}",
+            secret,
+            stdout
+        );
+    }
+
+    Ok(())
+}
@@ -41,6 +41,65 @@ fn list() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn list_sort() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Zinc).success();
+    cmd!(env, merchant create Chariot).success();
+    cmd!(env, merchant create Grognon).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer "--merchant" Chariot "--operation-date" "2024-08-01")
+        .success();
+    cmd!(env, record create -A Cash 7 beer "--merchant" Chariot "--operation-date" "2024-08-02")
+        .success();
+    cmd!(env, record create -A Cash 12 pizza "--merchant" Grognon "--operation-date" "2024-08-03")
+        .success();
+
+    let list = cmd!(env, merchant list "--sort" name)
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(list, "| Chariot", "| Grognon", "| Zinc");
+
+    // The count applies once sorted
+    cmd!(env, merchant list "--sort" "records.desc" "--count" 1)
+        .success()
+        .stdout(str::contains("| Chariot"))
+        .stdout(str::contains("| Grognon").not());
+
+    let list = cmd!(env, merchant list "--sort" "last-used.desc")
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(list, "| Grognon", "| Chariot", "| Zinc");
+
+    cmd!(env, merchant list "--sort" amount)
+        .failure()
+        .stderr(str::contains(
+            "[possible values: id, name, records, last-used",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn list_with_stats() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Chariot).success();
+    cmd!(env, merchant create Grognon).success();
+    cmd!(env, account create Cash).success();
+    cmd!(env, record create -A Cash 5 beer "--merchant" Chariot).success();
+    cmd!(env, record create -A Cash 12 pizza "--merchant" Chariot).success();
+
+    cmd!(env, merchant list "--with-stats")
+        .success()
+        .stdout(str::contains("| Chariot | "))
+        .stdout(str::contains("| 2       | € 17.00 |"))
+        .stdout(str::contains("| 0       | € 0.00  |"));
+
+    Ok(())
+}
+
 #[test]
 fn show() -> Result<()> {
     let env = Env::new()?;
@@ -96,8 +155,91 @@ fn show_records() -> Result<()> {
 
     cmd!(env, merchant show Chariot)
         .success()
-        .stdout(str::contains("€ -5.00"))
-        .stdout(str::contains("€ -10.00"));
+        .stdout(str::contains("-€ 5.00"))
+        .stdout(str::contains("-€ 10.00"));
+
+    Ok(())
+}
+
+#[test]
+fn show_stats() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Chariot).success();
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, merchant show Chariot)
+        .success()
+        .stdout(str::contains("Statistics").not());
+
+    cmd!(env, record create -A Cash 5 beer --merchant Chariot "--operation-date" "2024-01-10")
+        .success();
+    cmd!(env, record create -A Cash 10 beer --merchant Chariot "--operation-date" "2024-03-20")
+        .success();
+    cmd!(env, record create -A Cash 2 refund --merchant Chariot -d credit "--operation-date" "2024-02-01")
+        .success();
+
+    let output = cmd!(env, merchant show Chariot).success().into_stdout();
+    assert_contains_in_order!(
+        output,
+        "Statistics (EUR):",
+        "Records: 3, from 2024-01-10 to 2024-03-20",
+        "Spent: € 15.00, received: € 2.00",
+        "Monthly average over 3 month(s): € 5.00 spent, € 0.67 received",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn show_recurring_payments() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Netflix).success();
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, merchant show Netflix)
+        .success()
+        .stdout(str::contains("Recurring payments").not());
+
+    cmd!(env, recurring create Subscription "13.99" -A Cash "--merchant" Netflix
+        "--start-date" "2024-01-31")
+    .success();
+    cmd!(env, recurring create Ended "9.99" -A Cash "--merchant" Netflix
+        "--start-date" "2023-01-31" "--end-date" "2023-12-31")
+    .success();
+
+    cmd!(env, merchant show Netflix)
+        .success()
+        .stdout(str::contains("Recurring payments"))
+        .stdout(str::contains("Subscription"))
+        .stdout(str::contains("€ 13.99"))
+        .stdout(str::contains("Monthly"))
+        .stdout(str::contains("Ended").not());
+
+    cmd!(env, record create -A Cash 13.99 Netflix --merchant Netflix "--operation-date" "2024-09-03")
+        .success();
+    // Not an occurrence of the subscription
+    cmd!(env, record create -A Cash 4.50 Coffee --merchant Netflix "--operation-date" "2024-09-10")
+        .success();
+
+    // Same next date as `recurring list`
+    let list = cmd!(env, recurring list).success().into_stdout();
+    let next_due = list
+        .lines()
+        .find(|line| line.contains("Subscription"))
+        .and_then(|line| line.split('|').nth(6))
+        .map(|cell| cell.trim().to_owned())
+        .unwrap();
+    assert!(!next_due.is_empty());
+
+    let output = cmd!(env, merchant show Netflix).success().into_stdout();
+    let row = output
+        .lines()
+        .find(|line| line.contains("Subscription"))
+        .unwrap();
+    assert!(row.contains(&next_due), "{next_due} not in {row}");
+    assert!(row.contains("| 1 | 2024-09-03"));
 
     Ok(())
 }
@@ -116,7 +258,14 @@ fn create() -> Result<()> {
 
     cmd!(env, merchant create Chariot)
         .failure()
-        .stderr(str::contains("Conflict with existing data"));
+        .stderr(str::contains(
+            "Merchant \"Chariot\" already exists with id 1",
+        ));
+    cmd!(env, merchant create " chariot")
+        .failure()
+        .stderr(str::contains(
+            "Merchant \"Chariot\" already exists with id 1",
+        ));
 
     cmd!(env, merchant create Grognon "--create-default-category" Bar)
         .success()
@@ -200,6 +349,12 @@ fn update() -> Result<()> {
         .success()
         .stdout(str::contains("  Replaced by: 1 | Grognon"));
 
+    cmd!(env, merchant update Grognon "--replace-by" LeGrognon)
+        .code(6)
+        .stderr(str::contains(
+            "Merchant replacements form a cycle: Grognon → LeGrognon → Grognon",
+        ));
+
     Ok(())
 }
 
@@ -235,3 +390,77 @@ fn delete() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn alias() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Amazon).success();
+    cmd!(env, merchant create Ebay).success();
+
+    cmd!(env, merchant alias add Amazon "AMAZON EU SARL").success();
+    cmd!(env, merchant alias add 2 "EBAY O*").success();
+    cmd!(env, merchant alias add Ebay "amazon eu sarl")
+        .failure()
+        .stderr(str::contains("Conflict"));
+    cmd!(env, merchant alias add Unknown Foo).failure();
+
+    cmd!(env, merchant alias list)
+        .success()
+        .stdout(str::contains("AMAZON EU SARL").and(str::contains("EBAY O*")));
+    cmd!(env, merchant alias list Ebay)
+        .success()
+        .stdout(str::contains("EBAY O*").and(str::contains("AMAZON").not()));
+    cmd!(env, merchant show Amazon)
+        .success()
+        .stdout(str::contains("Aliases: AMAZON EU SARL"));
+
+    cmd!(env, merchant alias remove "amazon eu sarl").success();
+    cmd!(env, merchant alias remove "amazon eu sarl").failure();
+    cmd!(env, merchant show Amazon)
+        .success()
+        .stdout(str::contains("Aliases").not());
+
+    Ok(())
+}
+
+#[test]
+fn list_output() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, category create Food).success();
+    cmd!(env, merchant create "Chez Lulu, Paris" "--default-category" Food).success();
+
+    cmd!(env, merchant list "--output" csv).success().stdout(
+        "id,name,default category,replaced by\n\
+         1,\"Chez Lulu, Paris\",Food,\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn details() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, merchant create Chariot "--website" "chariot.example.com")
+        .code(6)
+        .stderr(str::contains(
+            "Invalid merchant website \"chariot.example.com\"",
+        ));
+    cmd!(env, merchant create Chariot "--website" "https://chariot.example.com").success();
+    cmd!(env, merchant update Chariot "--notes" "Closed on sundays").success();
+    cmd!(env, merchant show Chariot).success().stdout(
+        str::contains("Website: https://chariot.example.com")
+            .and(str::contains("Notes: Closed on sundays")),
+    );
+
+    cmd!(env, merchant update Chariot "--no-website").success();
+    cmd!(env, merchant show Chariot).success().stdout(
+        str::contains("Website")
+            .not()
+            .and(str::contains("Notes: Closed on sundays")),
+    );
+
+    Ok(())
+}
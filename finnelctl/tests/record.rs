@@ -4,8 +4,18 @@ use common::prelude::*;
 
 mod record {
     mod create;
+    mod duplicates;
+    mod flag;
+    mod history;
     mod list;
+    mod pending;
+    mod quick;
+    mod review;
+    mod search;
     mod split;
+    mod suggest;
+    mod tag;
+    mod transfer;
 }
 
 pub fn setup(env: &crate::Env) -> Result<()> {
@@ -34,3 +34,136 @@ fn operations() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn amount_formats() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, record create "12,50" lunch).success();
+    cmd!(env, record create "€ 3.20" coffee).success();
+    cmd!(env, record create "1 000" rent).success();
+
+    cmd!(env, record list "--greater-than" "12,5")
+        .success()
+        .stdout(str::contains("-€ 12.50\tDirect"))
+        .stdout(str::contains("-€ 1,000.00\tDirect"))
+        .stdout(str::contains("coffee").not());
+
+    cmd!(env, record create "12,505" lunch)
+        .failure()
+        .stderr(str::contains("\"12,505\" has more than 2 decimal places"));
+    cmd!(env, record create twelve lunch)
+        .failure()
+        .stderr(str::contains("\"twelve\" is not an amount"));
+
+    Ok(())
+}
+
+#[test]
+fn dates() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, record create 10 bread "--operation-date" "2024-09-10").success();
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("2024-09-10").count(2));
+
+    cmd!(env, record create 10 bread "--operation-date" "2024-09-10" "--value-date" "2024-09-09")
+        .failure()
+        .stderr(str::contains(
+            "Value date 2024-09-09 is before operation date 2024-09-10",
+        ));
+    cmd!(env, record create 10 bread
+        "--operation-date" "2024-09-10"
+        "--value-date" "2024-09-09"
+        "--allow-inverted-dates"
+    )
+    .success();
+
+    cmd!(env, record update 1 "--value-date" "2024-09-01")
+        .failure()
+        .stderr(str::contains("is before operation date"));
+    cmd!(env, record update 1 "--value-date" "2024-09-01" "--allow-inverted-dates").success();
+
+    Ok(())
+}
+
+#[test]
+fn original_amount() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, record create 9 souvenir "--original-amount" 10)
+        .failure()
+        .stderr(str::contains("--original-currency <CODE>"));
+    cmd!(env, record create 9 souvenir "--original-amount" 10 "--original-currency" EUR)
+        .failure()
+        .stderr(str::contains(
+            "Original currency must differ from the account currency EUR",
+        ));
+    cmd!(env, record create 9 souvenir "--original-amount" 10 "--original-currency" usd).success();
+    cmd!(env, record create 10 bread).success();
+
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Original amount: $ 10.00"));
+    cmd!(env, record list "--original-currency" USD)
+        .success()
+        .stdout(str::contains("souvenir"))
+        .stdout(str::contains("bread").not());
+
+    cmd!(env, record update 1 "--original-amount" 12).success();
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Original amount: $ 12.00"));
+    cmd!(env, record update 1 "--original-amount" 0)
+        .failure()
+        .stderr(str::contains("Original amount must be positive, not 0"));
+
+    cmd!(env, record update 1 "--no-original-amount").success();
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Original amount").not());
+
+    Ok(())
+}
+
+#[test]
+fn max_amount() -> Result<()> {
+    let env = crate::Env::new()?;
+    cmd!(env, account create Cash).success();
+
+    cmd!(env, record create -A Cash 12000 lunch).success();
+
+    cmd!(env, record list set "max-amount" 0)
+        .failure()
+        .stderr(str::contains("expected a positive amount"));
+    cmd!(env, record list set "max-amount" 100).success();
+    cmd!(env, config get "records/max_amount")
+        .success()
+        .stdout("100\n");
+
+    cmd!(env, record create -A Cash 100 lunch).success();
+    cmd!(env, record create -A Cash "100.01" lunch)
+        .failure()
+        .stderr(str::contains(
+            "Record not created, amount 100.01 is above records/max_amount 100, \
+             use --confirm to create it anyway",
+        ));
+    cmd!(env, record create -A Cash "100.01" lunch "--confirm")
+        .success()
+        .stderr(str::contains("Warning: amount 100.01 is above"));
+    cmd!(env, record list -A Cash "--plain")
+        .success()
+        .stdout(str::contains("\t-€ 100.01\t"));
+
+    raw_cmd!(env, record update 2 "--amount" 150 "--confirm")
+        .write_stdin("yes")
+        .assert()
+        .success()
+        .stderr(str::contains("Warning: amount 150 is above"));
+
+    Ok(())
+}
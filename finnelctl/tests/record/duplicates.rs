@@ -0,0 +1,50 @@
+use crate::common::prelude::*;
+
+pub fn setup(env: &crate::Env) -> Result<()> {
+    crate::setup(env)?;
+    cmd!(env, account create Bank).success();
+
+    cmd!(env, record create 10 "CB Bakery 01/08" "--operation-date" "2024-08-01").success();
+    cmd!(env, record create 10 "cb bakery" "--operation-date" "2024-08-03").success();
+    cmd!(env, record create 10 "Cinema" "--operation-date" "2024-08-02").success();
+    cmd!(env, record create 10 "CB Bakery" "--operation-date" "2024-08-10").success();
+    cmd!(env, record create 10 "CB Bakery" --account Bank "--operation-date" "2024-08-02")
+        .success();
+
+    Ok(())
+}
+
+#[test]
+fn duplicates() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    let stdout = cmd!(env, record duplicates).success().into_stdout();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(2, lines.len(), "{}", stdout);
+    assert!(lines[0].starts_with("id\tduplicate of\t"), "{}", stdout);
+    assert!(lines[1].starts_with("2\t1\t"), "{}", stdout);
+
+    cmd!(env, record duplicates "--days" "10")
+        .success()
+        .stdout(str::contains("\n4\t1\t").and(str::contains("\n4\t2\t")));
+
+    cmd!(env, record duplicates "--days" "1")
+        .success()
+        .stdout(str::is_empty());
+
+    cmd!(env, record duplicates -A Bank "--days" "10")
+        .success()
+        .stdout(str::is_empty());
+
+    // The reported id can be deleted right away
+    raw_cmd!(env, record show 2 delete "--confirm")
+        .write_stdin("yes")
+        .assert()
+        .success();
+    cmd!(env, record duplicates)
+        .success()
+        .stdout(str::is_empty());
+
+    Ok(())
+}
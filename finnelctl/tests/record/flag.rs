@@ -0,0 +1,66 @@
+use crate::common::prelude::*;
+
+pub fn setup(env: &crate::Env) -> Result<()> {
+    crate::setup(env)?;
+
+    cmd!(env, record create 10 Bread).success();
+    cmd!(env, record create 20 Cheese).success();
+
+    Ok(())
+}
+
+#[test]
+fn flag_unflag() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, record flag 1 "--reason" "check with bank")
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Flagged on ").and(str::contains(": check with bank")));
+
+    // The flag survives updates
+    cmd!(env, record update 1 "--details" "Baguette").success();
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("check with bank"));
+
+    cmd!(env, record unflag 1).success().stdout(str::is_empty());
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Flagged").not());
+
+    cmd!(env, record flag 3).failure();
+
+    Ok(())
+}
+
+#[test]
+fn list() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    // No flag column without any flagged record
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("flag").not());
+
+    cmd!(env, record flag 2).success();
+
+    let stdout = cmd!(env, record list).success().into_stdout();
+    assert!(stdout.lines().next().unwrap().ends_with("\tflag"));
+    assert!(stdout.contains("\tCheese\t\t\tyes\n"));
+
+    cmd!(env, record list "--flagged")
+        .success()
+        .stdout(str::contains("Cheese").and(str::contains("Bread").not()));
+
+    cmd!(env, record unflag 2).success();
+    cmd!(env, record list "--flagged")
+        .success()
+        .stdout(str::is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::common::prelude::*;
+
+#[test]
+fn history() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, record create 10 Bread).success();
+    cmd!(env, record update 1 "--details" Baguette).success();
+    cmd!(env, record show 1 history)
+        .success()
+        .stdout(str::contains("No changes recorded for record 1"));
+
+    cmd!(env, config set "db/audit_enabled" true).success();
+    cmd!(env, record update 1 "--details" Croissant).success();
+    cmd!(env, record show 1 split 4).success();
+    raw_cmd!(env, record show 2 delete --confirm)
+        .write_stdin("yes")
+        .assert()
+        .success();
+
+    let output = cmd!(env, record show 1 history).success().into_stdout();
+    assert_contains_in_order!(
+        output,
+        "| changed at ",
+        "| details ",
+        "| Baguette | Croissant |",
+        "| amount ",
+        "| 10.000   | 6.000     |",
+        "| split ",
+        "|          | 2         |"
+    );
+    cmd!(env, record show 2 history)
+        .success()
+        .stdout(str::contains("| split_from |"))
+        .stdout(str::contains("| deleted    | "))
+        .stdout(str::contains("Debit € 4.00 Croissant"));
+
+    cmd!(env, config set "db/audit_enabled" false).success();
+    cmd!(env, record update 1 "--details" Bread).success();
+    cmd!(env, record show 1 history)
+        .success()
+        .stdout(str::contains("| Croissant | Bread").not());
+
+    Ok(())
+}
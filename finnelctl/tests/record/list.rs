@@ -13,6 +13,7 @@ pub fn setup(env: &crate::Env) -> Result<()> {
         --merchant grocer
         "--value-date" "2024-08-01"
         "--operation-date" "2024-08-10"
+        "--allow-inverted-dates"
     )
     .success();
     cmd!(env, record create 5 Beer
@@ -126,6 +127,53 @@ fn filter_by_category() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn filter_by_categories_with_children() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, category create restaurants --parent food).success();
+    cmd!(env, category create burgers --parent restaurants).success();
+    cmd!(env, record create 15 Pizza --account Cash --category restaurants).success();
+    cmd!(env, record create 12 Burger --account Cash --category burgers).success();
+
+    cmd!(env, record list --category food)
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Pizza").not())
+        .stdout(str::contains("Burger").not());
+
+    cmd!(env, record list --category food "--with-children")
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Pizza"))
+        .stdout(str::contains("Burger"))
+        .stdout(str::contains("Beer").not());
+
+    cmd!(env, record list --category burgers --category beer)
+        .success()
+        .stdout(str::contains("Burger"))
+        .stdout(str::contains("Beer"))
+        .stdout(str::contains("Pizza").not())
+        .stdout(str::contains("Bread").not());
+
+    cmd!(env, record list --category restaurants --category food "--with-children")
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Pizza"))
+        .stdout(str::contains("Burger"));
+
+    cmd!(env, record list --category food "--no-category")
+        .failure()
+        .stderr(str::contains("cannot be used with"));
+
+    cmd!(env, record list "--with-children")
+        .failure()
+        .stderr(str::contains("--category"));
+
+    Ok(())
+}
+
 #[test]
 fn filter_by_merchant() -> Result<()> {
     let env = crate::Env::new()?;
@@ -144,6 +192,38 @@ fn filter_by_merchant() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn exclude() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+    cmd!(env, record create 3 Tip --account Cash "--mode" atm).success();
+
+    // Records without category or merchant are kept
+    cmd!(env, record list "--exclude-category" food)
+        .success()
+        .stdout(str::contains("Bread").not())
+        .stdout(str::contains("Beer"))
+        .stdout(str::contains("Tip"));
+    cmd!(env, record list "--exclude-category" food "--exclude-category" 1)
+        .success()
+        .stdout(str::contains("Bread").not())
+        .stdout(str::contains("Beer").not())
+        .stdout(str::contains("Tip"));
+    cmd!(env, record list "--exclude-merchant" grocer)
+        .success()
+        .stdout(str::contains("Bread").not())
+        .stdout(str::contains("Beer"))
+        .stdout(str::contains("Tip"));
+    cmd!(env, record list "--exclude-mode" atm)
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Tip").not());
+
+    cmd!(env, record list "--exclude-merchant" unknown).failure();
+
+    Ok(())
+}
+
 #[test]
 fn filter_from_is_inclusive() -> Result<()> {
     let env = crate::Env::new()?;
@@ -255,3 +335,334 @@ fn filter_less_than() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn plain() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    let stdout = cmd!(env, record list "--plain" "--no-footer")
+        .success()
+        .into_stdout();
+
+    assert_eq!(3, stdout.lines().count());
+    assert!(stdout.starts_with("account\tid\tamount\t"));
+    assert!(stdout.contains("\tBread\tfood\tgrocer\n"));
+    for c in ['|', '+', '─', '│', '┌', '└'] {
+        assert!(!stdout.contains(c), "{:?} in {}", c, stdout);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn footer() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+    cmd!(env, account create Dollars "--currency" USD "--non-main-ok").success();
+    cmd!(env, record create 1234.56 Salary -A Bank "--direction" credit "--operation-date" "2024-08-20")
+        .success();
+    cmd!(env, record create 0.1 Gum -A Cash "--operation-date" "2024-08-21").success();
+    cmd!(env, record create 0.2 Gum -A Cash "--operation-date" "2024-08-22").success();
+    cmd!(env, record create 3 Coffee -A Dollars "--operation-date" "2024-08-23").success();
+
+    let stdout = cmd!(env, record list "--sort" "date")
+        .success()
+        .into_stdout();
+    assert!(
+        stdout.ends_with(
+            "6 record(s) shown\n\
+             EUR: debits -€ 15.30, credits € 1,234.56, net € 1,219.26\n\
+             USD: debits -$ 3.00, credits $ 0.00, net -$ 3.00\n"
+        ),
+        "{stdout}"
+    );
+
+    // Only the records shown are counted
+    let stdout = cmd!(env, record list "--sort" "date" "--count" 3)
+        .success()
+        .into_stdout();
+    assert!(
+        stdout.ends_with(
+            "3 record(s) shown\n\
+             EUR: debits -€ 15.00, credits € 1,234.56, net € 1,219.56\n"
+        ),
+        "{stdout}"
+    );
+
+    cmd!(env, record list "--no-footer")
+        .success()
+        .stdout(str::contains("shown").not())
+        .stdout(str::contains("net").not());
+    cmd!(env, record list "--output" csv)
+        .success()
+        .stdout(str::contains("shown").not());
+
+    Ok(())
+}
+
+#[test]
+fn output() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+    cmd!(env, config set "display/relative_dates" true).success();
+    cmd!(env, record create 1234.5 "Bread, \"fresh\"" -A Cash "--operation-date" "2024-08-12")
+        .success();
+
+    cmd!(env, record list -A Cash "--output" csv "--sort" "date")
+        .success()
+        .stdout(
+            "id,amount,mode,operation date,value date,details,categories,merchant\n\
+             1,-10.00,Direct,2024-08-10,2024-08-01,Bread,food,grocer\n\
+             3,-1234.50,Direct,2024-08-12,2024-08-12,\"Bread, \"\"fresh\"\"\",,\n",
+        );
+
+    let stdout = cmd!(env, record list "--output" tsv "--no-header" "--page" 1)
+        .success()
+        .into_stdout();
+    assert_eq!(3, stdout.lines().count());
+    assert!(stdout.contains("Bank\t2\t-5.00\tDirect\t2024-08-01\t2024-08-10\tBeer\tbeer\t\n"));
+
+    cmd!(env, record list "--no-header")
+        .failure()
+        .stderr(str::contains("--output <FORMAT>"));
+
+    Ok(())
+}
+
+#[test]
+fn pages() -> Result<()> {
+    let env = crate::Env::new()?;
+    cmd!(env, account create Cash).success();
+    for details in ["A", "B", "C", "D", "E"] {
+        raw_cmd!(env, record create 1 -A Cash "--operation-date" "2024-08-01")
+            .arg(details)
+            .assert()
+            .success();
+    }
+    cmd!(env, record create 1 -A Cash Older "--operation-date" "2024-07-01").success();
+
+    // Ids of the records listed, in the second column
+    let ids = |stdout: &str| {
+        stdout
+            .lines()
+            .filter(|line| line.contains('\t') && !line.starts_with("account\t"))
+            .map(|line| line.split('\t').nth(1).unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let stdout = cmd!(env, record list "--page" 1 "--per-page" 4)
+        .success()
+        .into_stdout();
+    assert_eq!(vec!["6", "1", "2", "3"], ids(&stdout));
+    assert!(stdout.ends_with("page 1 of 2 (6 records total)\n"));
+
+    let stdout = cmd!(env, record list "--page" 2 "--per-page" 4)
+        .success()
+        .into_stdout();
+    assert_eq!(vec!["4", "5"], ids(&stdout));
+    assert!(stdout.ends_with("page 2 of 2 (6 records total)\n"));
+
+    // 50 records per page by default
+    let stdout = cmd!(env, record list "--page" 1).success().into_stdout();
+    assert_eq!(6, ids(&stdout).len());
+
+    // Only the header and the footer past the last page
+    let stdout = cmd!(env, record list "--page" 3 "--per-page" 4)
+        .success()
+        .into_stdout();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(2, lines.len());
+    assert!(lines[0].starts_with("account\t"));
+    assert_eq!("page 3 of 2 (6 records total)", lines[1]);
+
+    // Huge values are not overflowing
+    cmd!(env, record list "--page" "9223372036854775807")
+        .success()
+        .stdout(str::contains(
+            "page 9223372036854775807 of 1 (6 records total)",
+        ));
+    cmd!(env, record list "--per-page" "9223372036854775807")
+        .success()
+        .stdout(str::contains("page 1 of 1 (6 records total)"));
+
+    cmd!(env, record list "--page" 0).failure();
+    cmd!(env, record list "--page" 1 "--count" 2).failure();
+
+    Ok(())
+}
+
+#[test]
+fn shift_dates() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, record list "shift-dates" "--confirm")
+        .failure()
+        .stderr(str::contains("required arguments were not provided"));
+    cmd!(env, record list "shift-dates" "--days" 1 "--years" 1 "--confirm")
+        .failure()
+        .stderr(str::contains("cannot be used with"));
+
+    cmd!(env, record list --account Cash "shift-dates" "--years" 1 "--pretend")
+        .success()
+        .stdout(str::contains("Shifting 1 record(s) by +1 year(s)\n"))
+        .stdout(str::contains("Before: from 2024-08-01 to 2024-08-10\n"))
+        .stdout(str::contains("After: from 2025-08-01 to 2025-08-10\n"));
+    // Cache the stats of the month
+    cmd!(env, report category food "--from" "2024-08-01" "--to" "2024-09-01")
+        .success()
+        .stdout(str::contains("2024/08 | € 10.00 |"));
+
+    cmd!(env, record list --account Cash "shift-dates" "--years" 1)
+        .failure()
+        .stderr(str::contains("operation requires confirmation"));
+    cmd!(env, record list "--plain")
+        .success()
+        .stdout(str::contains("2025-").not());
+
+    raw_cmd!(env, record list --account Cash "shift-dates" "--years" 1 "--confirm")
+        .write_stdin("yes")
+        .assert()
+        .success();
+    cmd!(env, record list "--plain")
+        .success()
+        .stdout(str::contains("\t2025-08-10\t2025-08-01\t"))
+        .stdout(str::contains("\t2024-08-01\t2024-08-10\t"));
+    cmd!(env, report category food "--from" "2024-08-01" "--to" "2024-09-01")
+        .success()
+        .stdout(str::contains("2024/08 | € 0.00 |"));
+
+    raw_cmd!(env, record list "shift-dates" "--months" "-1" "--confirm")
+        .write_stdin("yes")
+        .assert()
+        .success()
+        .stdout(str::contains("Before: from 2024-08-01 to 2025-08-10\n"))
+        .stdout(str::contains("After: from 2024-07-01 to 2025-07-10\n"));
+
+    cmd!(env, record list --account Cash "--from" "2026-01-01" "shift-dates" "--days" 3)
+        .success()
+        .stdout("No records to shift\n");
+
+    Ok(())
+}
+
+#[test]
+fn relative_dates() -> Result<()> {
+    let env = crate::Env::new()?;
+    cmd!(env, account create Cash).success();
+    // Dated today by the command, which is yesterday if midnight passed since
+    cmd!(env, record create 10 Bread "--account" Cash).success();
+    raw_cmd!(env, record create 20 Wine "--account" Cash)
+        .args([
+            "--operation-date",
+            "2020-01-01",
+            "--value-date",
+            "2020-01-01",
+        ])
+        .assert()
+        .success();
+
+    let absolute = r"\t\d{4}-\d{2}-\d{2}\t\d{4}-\d{2}-\d{2}\tBread";
+    let relative = r"\t(today\ttoday|yesterday\tyesterday)\tBread";
+    let old = "\t2020-01-01\t2020-01-01\tWine";
+
+    cmd!(env, record list "--plain")
+        .success()
+        .stdout(str::is_match(absolute)?.and(str::contains(old)));
+    cmd!(env, record list "--plain" "--relative-dates")
+        .success()
+        .stdout(str::is_match(relative)?.and(str::contains(old)));
+
+    cmd!(env, config set "display/relative_dates" true).success();
+    cmd!(env, record list "--plain")
+        .success()
+        .stdout(str::is_match(relative)?);
+    cmd!(env, record search Bread "--plain")
+        .success()
+        .stdout(str::is_match(relative)?);
+
+    cmd!(env, config set "display/relative_dates" maybe).success();
+    cmd!(env, record list "--plain")
+        .success()
+        .stdout(str::is_match(absolute)?)
+        .stderr(str::contains(
+            "Warning: ignoring display/relative_dates, expected true or false",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn update_pretend() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    // The beer already has both values
+    cmd!(env, record list update "--category" beer "--value-date" "2024-08-10" "--pretend")
+        .success()
+        .stdout(
+            "\
+Record 1: Bread
+  value_date  2024-08-01 → 2024-08-10
+  category    food       → beer
+",
+        );
+    cmd!(env, record list "--category" food)
+        .success()
+        .stdout(str::contains("Bread"));
+
+    cmd!(env, record update 2 "--details" Beer "--no-merchant" "--pretend")
+        .success()
+        .stdout("No record would change\n");
+    cmd!(env, record show 1 update "--no-merchant" "--pretend")
+        .success()
+        .stdout(str::contains("  merchant  grocer → (none)"));
+    cmd!(env, record list update "--create-category" wine "--pretend").code(2);
+
+    Ok(())
+}
+
+#[test]
+fn since_last() -> Result<()> {
+    let env = Env::new()?;
+    let today = chrono::Utc::now().date_naive().to_string();
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, record create 5 winter "--operation-date" "2024-01-01").success();
+    cmd!(env, record create 7 summer "--operation-date" "2024-07-01").success();
+
+    // All the records without a marker
+    cmd!(env, record list "--since-last" "--no-mark")
+        .success()
+        .stdout(str::contains("winter"))
+        .stdout(str::contains("summer"));
+    cmd!(env, config get "record/last_viewed")
+        .success()
+        .stdout(str::is_empty());
+
+    cmd!(env, record list).success();
+    cmd!(env, config get "record/last_viewed")
+        .success()
+        .stdout(format!("{today}\n"));
+
+    cmd!(env, config set "record/last_viewed" "2024-06-01").success();
+    cmd!(env, record list "--since-last")
+        .success()
+        .stdout(str::contains("winter").not())
+        .stdout(str::contains("summer"));
+    cmd!(env, config get "record/last_viewed")
+        .success()
+        .stdout(format!("{today}\n"));
+
+    // The marker only moves forward
+    cmd!(env, config set "record/last_viewed" "2999-01-01").success();
+    cmd!(env, record list).success();
+    cmd!(env, config get "record/last_viewed")
+        .success()
+        .stdout("2999-01-01\n");
+
+    cmd!(env, record list "--since-last" "--from" "2024-01-01").code(2);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use crate::common::prelude::*;
+
+#[test]
+fn pending() -> Result<()> {
+    let env = Env::new()?;
+    crate::setup(&env)?;
+    cmd!(env, account create Dollars "--currency" USD).success();
+    cmd!(env, record create 10 Bread).success();
+
+    cmd!(env, record create 25 Taxi "--pending")
+        .success()
+        .stdout("Created pending record 2\n");
+    cmd!(env, record create 5 Tip "--currency" USD)
+        .failure()
+        .stderr(str::contains("--pending"));
+
+    // Only listed on request, and out of the balance
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Taxi").not());
+    cmd!(env, record list "--pending")
+        .success()
+        .stdout(str::contains("Taxi"))
+        .stdout(str::contains("Bread").not());
+    cmd!(env, account show Cash)
+        .success()
+        .stdout(str::contains("1 records, € 10.00 debited"));
+    cmd!(env, account "recompute-balance")
+        .success()
+        .stdout(str::contains("€ -10.00 from 1 records"));
+
+    cmd!(env, record assign 2 "--account" Dollars)
+        .code(6)
+        .stderr(str::contains("Record in EUR can't go to account Dollars in USD"));
+    cmd!(env, record assign 2 "--account" Savings).code(4);
+    cmd!(env, record assign 2 "--account" Cash)
+        .success()
+        .stdout("Assigned record 2 to Cash\n");
+    cmd!(env, record assign 2 "--account" Cash)
+        .code(6)
+        .stderr(str::contains("Record #2 is not pending"));
+
+    cmd!(env, record list "--pending")
+        .success()
+        .stdout(str::contains("Taxi").not());
+    cmd!(env, record list).success().stdout(str::contains("Taxi"));
+    cmd!(env, account show Cash)
+        .success()
+        .stdout(str::contains("2 records, € 35.00 debited"));
+    cmd!(env, account "recompute-balance")
+        .success()
+        .stdout(str::contains("€ -35.00 from 2 records"));
+
+    Ok(())
+}
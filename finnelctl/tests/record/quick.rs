@@ -0,0 +1,58 @@
+use crate::common::prelude::*;
+
+#[test]
+fn quick() -> Result<()> {
+    let env = crate::Env::new()?;
+
+    cmd!(env, quick "4.5 coffee")
+        .failure()
+        .stderr(str::contains("Account not provided"));
+
+    crate::setup(&env)?;
+
+    cmd!(env, quick "4,5 coffee @bakery #food").success();
+    cmd!(env, quick "12 \"#1 refund\" @bakery" "--credit").success();
+    cmd!(env, quick "coffee")
+        .failure()
+        .stderr(str::contains("Missing amount at the start of the entry"));
+    cmd!(env, quick "3 tea #food #drinks")
+        .failure()
+        .stderr(str::contains("Only one category can be given"));
+
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("-€ 4.50\tDirect"))
+        .stdout(str::contains("coffee\tfood\tbakery"))
+        .stdout(str::contains("€ 12.00\tDirect"))
+        .stdout(str::contains("#1 refund\t\tbakery"))
+        .stdout(str::contains("tea").not());
+    cmd!(env, category list)
+        .success()
+        .stdout(str::contains("drinks").not());
+
+    Ok(())
+}
+
+#[test]
+fn max_amount() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+    cmd!(env, record list set "max-amount" 100).success();
+
+    cmd!(env, quick "1200 coffee")
+        .failure()
+        .stderr(str::contains(
+            "Record not created, amount 1200 is above records/max_amount 100, \
+             use --confirm to create it anyway",
+        ));
+    cmd!(env, record list).success().stdout(str::is_empty());
+
+    cmd!(env, quick "1200 coffee" "--confirm")
+        .success()
+        .stderr(str::contains("Warning: amount 1200 is above"));
+    cmd!(env, record list)
+        .success()
+        .stdout(str::contains("-€ 1,200.00"));
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use crate::common::prelude::*;
+
+#[test]
+fn not_a_terminal() -> Result<()> {
+    let env = Env::new()?;
+    crate::setup(&env)?;
+    cmd!(env, record create 10 Bread).success();
+
+    cmd!(env, record review "--no-category")
+        .failure()
+        .stderr(str::contains("run it in a terminal"));
+
+    Ok(())
+}
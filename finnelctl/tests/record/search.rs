@@ -0,0 +1,67 @@
+use crate::common::prelude::*;
+
+pub fn setup(env: &crate::Env) -> Result<()> {
+    crate::setup(env)?;
+
+    cmd!(env, category create food).success();
+    cmd!(env, merchant create Bakery).success();
+    cmd!(env, record create 5 "Bakery visit" "--operation-date" "2024-08-10").success();
+    cmd!(env, record create 10 Bread
+        --category food
+        --merchant Bakery
+        "--operation-date" "2024-08-01"
+    )
+    .success();
+    cmd!(env, record create 20 Cinema "--operation-date" "2024-08-05").success();
+
+    Ok(())
+}
+
+#[test]
+fn search() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    // The record from the merchant comes first, even though it is older
+    let stdout = cmd!(env, record search bakery).success().into_stdout();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(3, lines.len(), "{}", stdout);
+    assert!(lines[1].contains("Bread"), "{}", stdout);
+    assert!(lines[2].contains("Bakery visit"), "{}", stdout);
+
+    cmd!(env, record search FOOD)
+        .success()
+        .stdout(str::contains("Bread").and(str::contains("Cinema").not()));
+
+    cmd!(env, record search bread bakery)
+        .success()
+        .stdout(str::contains("Bread").and(str::contains("Bakery visit").not()));
+
+    cmd!(env, record search bakery "--from" "2024-08-05")
+        .success()
+        .stdout(str::contains("Bakery visit").and(str::contains("Bread").not()));
+
+    cmd!(env, record search groceries)
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, record search).failure();
+
+    Ok(())
+}
+
+#[test]
+fn exact_merchant_past_count() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+    cmd!(env, record create 7 "Bakery cake" "--operation-date" "2024-08-20").success();
+
+    // The record from the merchant is the oldest, still shown within the count
+    let stdout = cmd!(env, record search bakery "--count" 1)
+        .success()
+        .into_stdout();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(2, lines.len(), "{}", stdout);
+    assert!(lines[1].contains("Bread"), "{}", stdout);
+
+    Ok(())
+}
@@ -3,10 +3,10 @@ use crate::common::prelude::*;
 pub fn setup(env: &crate::Env) -> Result<()> {
     crate::setup(env)?;
 
-    cmd!(env, category create beer --create_replace_by Beer).success();
-    cmd!(env, category create food --create_replace_by Food).success();
-    cmd!(env, merchant create grocer --create_replace_by Grocer).success();
-    cmd!(env, record create 10 Bread --category food --merchant grocer).success();
+    cmd!(env, category create old_beer --create_replace_by Beer).success();
+    cmd!(env, category create old_food --create_replace_by Food).success();
+    cmd!(env, merchant create old_grocer --create_replace_by Grocer).success();
+    cmd!(env, record create 10 Bread --category old_food --merchant old_grocer).success();
 
     Ok(())
 }
@@ -39,7 +39,7 @@ fn operations() -> Result<()> {
         .stdout(str::contains("Bread"))
         .stdout(str::contains("Food"))
         .stdout(str::contains("Grocer"))
-        .stdout(str::contains("€ -5.00"));
+        .stdout(str::contains("-€ 5.00"));
 
     cmd!(env, record show 2 split 1 --details Candy)
         .success()
@@ -50,20 +50,20 @@ fn operations() -> Result<()> {
         .stdout(str::contains("Candy"))
         .stdout(str::contains("Food"))
         .stdout(str::contains("Grocer"))
-        .stdout(str::contains("€ -1.00"));
+        .stdout(str::contains("-€ 1.00"));
 
-    cmd!(env, record show 1 split 2 --category beer)
+    cmd!(env, record show 1 split 2 --category old_beer)
         .success()
         .stdout(str::is_empty());
 
     cmd!(env, record show 4)
         .success()
         .stdout(str::contains("Beer"))
-        .stdout(str::contains("€ -2.00"));
+        .stdout(str::contains("-€ 2.00"));
 
     cmd!(env, record show 1)
         .success()
-        .stdout(str::contains("€ -3.00"));
+        .stdout(str::contains("-€ 3.00"));
 
     Ok(())
 }
@@ -0,0 +1,48 @@
+use crate::common::prelude::*;
+
+#[test]
+fn suggest() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, category create Bar).success();
+    cmd!(env, category create Food).success();
+    cmd!(env, merchant create Chariot).success();
+
+    cmd!(env, record create 5 "CB CHARIOT 01/08" "--merchant" Chariot).success();
+    cmd!(env, record show 1 suggest)
+        .success()
+        .stdout("No similar record with a category\n");
+
+    cmd!(env, record create 5 beer "--merchant" Chariot "--category" Bar).success();
+    cmd!(env, record create 6 "CB Chariot 12/07" "--category" Bar).success();
+    cmd!(env, record create 7 "CB Chariot 02/07" "--category" Bar).success();
+    cmd!(env, record create 8 "Chariot snacks" "--category" Food).success();
+    cmd!(env, record create 9 "CB Bakery" "--category" Food).success();
+
+    cmd!(env, record show 1 suggest)
+        .success()
+        .stdout(str::contains("4 similar record(s) with a category"))
+        .stdout(str::contains("| Bar      | 3       | 75%   |"))
+        .stdout(str::contains("| Food     | 1       | 25%   |"));
+
+    cmd!(env, record show 1 suggest "--apply-best")
+        .failure()
+        .stderr(str::contains(
+            "Not applying Bar used by only 75% of the similar records",
+        ));
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Bar").not());
+
+    cmd!(env, record show 5 update "--category" Bar).success();
+    cmd!(env, record show 1 suggest "--apply-best")
+        .success()
+        .stdout(str::contains("| Bar      | 4       | 100%  |"))
+        .stdout(str::contains("Category set to Bar"));
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Bar"));
+
+    Ok(())
+}
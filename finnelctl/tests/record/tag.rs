@@ -0,0 +1,80 @@
+use crate::common::prelude::*;
+
+pub fn setup(env: &crate::Env) -> Result<()> {
+    crate::setup(env)?;
+
+    cmd!(env, record create 10 Bread).success();
+    cmd!(env, record create 20 Cheese).success();
+    cmd!(env, record create 30 Wine).success();
+
+    Ok(())
+}
+
+#[test]
+fn add_remove() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, record show 1 tag add holidays)
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, record show 1 tag add Holidays).success();
+    cmd!(env, record show 1 tag add food).success();
+
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Tags: food, holidays"));
+
+    cmd!(env, record show 1 tag remove HOLIDAYS).success();
+    cmd!(env, record show 1)
+        .success()
+        .stdout(str::contains("Tags: food"));
+
+    cmd!(env, record show 1 tag remove unknown)
+        .failure()
+        .stderr(str::contains("Tag not found by name"));
+
+    Ok(())
+}
+
+#[test]
+fn list() -> Result<()> {
+    let env = crate::Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, record show 1 tag add food).success();
+    cmd!(env, record show 2 tag add food).success();
+    cmd!(env, record show 2 tag add holidays).success();
+    cmd!(env, record show 3 tag add holidays).success();
+
+    cmd!(env, record list --tag food)
+        .success()
+        .stdout(str::contains("Bread"))
+        .stdout(str::contains("Cheese"))
+        .stdout(str::contains("Wine").not());
+
+    cmd!(env, record list --tag food --tag holidays)
+        .success()
+        .stdout(str::contains("Bread").not())
+        .stdout(str::contains("Cheese"))
+        .stdout(str::contains("Wine").not());
+
+    cmd!(env, tag list)
+        .success()
+        .stdout(str::contains("1  | food     | 2"))
+        .stdout(str::contains("2  | holidays | 2"));
+
+    raw_cmd!(env, tag delete food --confirm)
+        .write_stdin("yes")
+        .assert()
+        .success();
+
+    cmd!(env, record list --tag food)
+        .failure()
+        .stderr(str::contains("Tag not found by name"));
+    cmd!(env, tag list)
+        .success()
+        .stdout(str::contains("food").not());
+
+    Ok(())
+}
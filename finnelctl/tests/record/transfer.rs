@@ -0,0 +1,70 @@
+use crate::common::prelude::*;
+
+#[test]
+fn transfer() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+    cmd!(env, account create Bank).success();
+
+    cmd!(env, record transfer 50 "--from-account" Bank "--to-account" Cash "--date" "2024-09-01")
+        .success();
+
+    cmd!(env, record list -A Bank)
+        .success()
+        .stdout(str::contains(
+            "-€ 50.00\tTransfer\t2024-09-01\t2024-09-01\tTransfer from Bank to Cash",
+        ));
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::contains(
+            "€ 50.00\tTransfer\t2024-09-01\t2024-09-01\tTransfer from Bank to Cash",
+        ));
+
+    cmd!(env, record list set "max-amount" 100).success();
+    cmd!(env, record transfer 500 "--from-account" Bank "--to-account" Cash)
+        .failure()
+        .stderr(str::contains("amount 500 is above records/max_amount 100"));
+    cmd!(env, record transfer 500 "--from-account" Bank "--to-account" Cash "--confirm")
+        .success()
+        .stderr(str::contains("Warning: amount 500 is above"));
+
+    Ok(())
+}
+
+#[test]
+fn currency_mismatch() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+    cmd!(env, account create Dollars "--currency" USD "--non-main-ok").success();
+
+    cmd!(env, record transfer 50 "--from-account" Cash "--to-account" Dollars)
+        .failure()
+        .stderr(str::contains(
+            "Cannot transfer from Cash in EUR to Dollars in USD",
+        ));
+
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::is_empty());
+    cmd!(env, record list -A Dollars)
+        .success()
+        .stdout(str::is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn missing_account() -> Result<()> {
+    let env = crate::Env::new()?;
+    crate::setup(&env)?;
+
+    cmd!(env, record transfer 50 "--from-account" Cash "--to-account" Bank)
+        .failure()
+        .stderr(str::contains("Account not found: Bank"));
+
+    cmd!(env, record list -A Cash)
+        .success()
+        .stdout(str::is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+fn setup(env: &Env) -> Result<()> {
+    cmd!(env, account create Cash).success();
+    cmd!(env, account default -A Cash).success();
+    cmd!(env, merchant create Netflix).success();
+
+    Ok(())
+}
+
+#[test]
+fn create_list() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, recurring create Rent 800 "--start-date" "2024-01-31" "--end-date" "2024-01-01")
+        .failure()
+        .stderr(str::contains(
+            "End date 2024-01-01 is before start date 2024-01-31",
+        ));
+    cmd!(env, recurring create Subscription "13.99" "--merchant" Netflix
+        "--start-date" "2024-01-10")
+    .success();
+    cmd!(env, recurring create Insurance 120 "-f" yearly "--start-date" "2020-02-29").success();
+
+    cmd!(env, recurring list)
+        .success()
+        .stdout(str::contains("| Subscription | Cash    | -€ 13.99"))
+        .stdout(str::contains("| Netflix"))
+        .stdout(str::contains(
+            "| Insurance    | Cash    | -€ 120.00 | Yearly",
+        ));
+
+    cmd!(env, recurring list "--due" 7 "--date" "2024-03-10")
+        .success()
+        .stdout(str::contains("| Monthly   | 2024-03-10"))
+        .stdout(str::contains("Insurance").not());
+    // Due on the 28th when the year has no 29th of February
+    cmd!(env, recurring list "--due" 7 "--date" "2025-02-22")
+        .success()
+        .stdout(str::contains("| Yearly    | 2025-02-28"));
+
+    cmd!(env, recurring create Weekly 5 "-f" weekly "--start-date" "2024-03-17").success();
+    cmd!(env, recurring list "--due" 6 "--date" "2024-03-10")
+        .success()
+        .stdout(str::contains("Weekly").not());
+    cmd!(env, recurring list "--due" 7 "--date" "2024-03-10")
+        .success()
+        .stdout(str::contains("| Weekly       | Cash    | -€ 5.00  | Weekly    | 2024-03-17"));
+
+    Ok(())
+}
+
+#[test]
+fn update_delete() -> Result<()> {
+    let env = Env::new()?;
+    setup(&env)?;
+
+    cmd!(env, recurring create Subscription "13.99" "--start-date" "2024-01-15").success();
+    cmd!(env, recurring update Subscription "--new-name" Netflix "--amount" "15.99"
+        "--merchant" Netflix "--end-date" "2024-06-30")
+    .success();
+    cmd!(env, recurring update 1 "--end-date" "2024-01-01")
+        .failure()
+        .stderr(str::contains("is before start date"));
+
+    cmd!(env, recurring list).success().stdout(str::contains(
+        "| Netflix | Cash    | -€ 15.99 | Monthly   |          |",
+    ));
+
+    cmd!(env, recurring update 1 "--no-end-date").success();
+    cmd!(env, recurring list)
+        .success()
+        .stdout(str::contains("| Monthly   | 20"));
+
+    cmd!(env, recurring delete Netflix)
+        .failure()
+        .stderr(str::contains("operation requires confirmation"));
+    raw_cmd!(env, recurring delete Netflix --confirm)
+        .write_stdin("yes")
+        .assert()
+        .success();
+    cmd!(env, recurring list)
+        .success()
+        .stdout(str::contains("Netflix").not());
+
+    Ok(())
+}
@@ -2,6 +2,8 @@
 mod common;
 use common::prelude::*;
 
+use chrono::{Datelike, Utc};
+
 #[test]
 fn empty() -> Result<()> {
     let env = Env::new()?;
@@ -111,3 +113,422 @@ fn delete() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn category() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Food).success();
+    cmd!(env, category create Restaurant "--parent" Food).success();
+
+    for (amount, category, date) in [
+        ("20", "Food", "2024-01-10"),
+        ("30", "Restaurant", "2024-01-20"),
+        ("15", "Food", "2024-03-05"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "meal", "--category", category])
+            .args(["--operation-date", date])
+            .assert()
+            .success();
+    }
+
+    let range = ["--from", "2024-01-01", "--to", "2024-04-01"];
+    raw_cmd!(env, report category Food)
+        .args(range)
+        .assert()
+        .success()
+        .stdout(str::contains("2024/01 | € 20.00 | € 0.00 | 1"))
+        .stdout(str::contains(
+            "2024/02 | € 0.00  | € 0.00 | 0       | -€ 20.00",
+        ))
+        .stdout(str::contains(
+            "2024/03 | € 15.00 | € 0.00 | 1       | +€ 15.00",
+        ));
+
+    raw_cmd!(env, report category Food "--with-children")
+        .args(range)
+        .assert()
+        .success()
+        .stdout(str::contains("2024/01 | € 50.00 | € 0.00 | 2"));
+
+    cmd!(env, account create Dollars "--currency" USD).success();
+    cmd!(env, record create -A Dollars 12 meal "--category" Food "--operation-date" "2024-02-10")
+        .success();
+    raw_cmd!(env, report category Food)
+        .args(range)
+        .assert()
+        .success()
+        .stdout(str::contains("2024/02 | € 0.00  | € 0.00 | 0"))
+        .stderr(str::contains(
+            "Note: records in other currencies than EUR are not included (1 in USD)",
+        ));
+
+    cmd!(env, report category Food "--from" "2024-04-01" "--to" "2024-01-01")
+        .failure()
+        .stderr(str::contains("--from must be before --to"));
+
+    Ok(())
+}
+
+#[test]
+fn html_output() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create "Fish & Chips" "--color" "#C0FFEE" "--emoji" "🐟").success();
+    cmd!(env, category create "<Snacks>" "--parent" "Fish & Chips").success();
+    for (amount, category, date) in [
+        ("12.50", "Fish & Chips", "2024-01-10"),
+        ("4", "<Snacks>", "2024-01-20"),
+        ("9", "Fish & Chips", "2024-03-05"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "meal", "--category", category])
+            .args(["--operation-date", date])
+            .assert()
+            .success();
+    }
+
+    let file = env.data_dir.child("series.html");
+    raw_cmd!(env, report category "Fish & Chips" "--with-children")
+        .args(["--from", "2024-01-01", "--to", "2024-04-01"])
+        .args(["--output", "html"])
+        .arg(file.as_os_str())
+        .assert()
+        .success()
+        .stdout(str::is_empty());
+    assert_eq!(
+        include_str!("fixtures/report/category.html"),
+        std::fs::read_to_string(&file)?
+    );
+
+    cmd!(env, report create "Food & drinks").success();
+    cmd!(env, report show "Food & drinks" add "Fish & Chips" "<Snacks>").success();
+    let file = env.data_dir.child("report.html");
+    raw_cmd!(env, report show "Food & drinks" "--output" html)
+        .arg(file.as_os_str())
+        .assert()
+        .success();
+    assert_eq!(
+        include_str!("fixtures/report/show.html"),
+        std::fs::read_to_string(&file)?
+    );
+
+    raw_cmd!(env, report show "Food & drinks" "--output" pdf)
+        .arg(file.as_os_str())
+        .assert()
+        .failure()
+        .stderr(str::contains("Unknown output format \"pdf\""));
+
+    Ok(())
+}
+
+#[test]
+fn category_detail() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Food).success();
+    for (amount, category, date) in [
+        ("20", "Food", "2024-01-10"),
+        ("4.50", "Food", "2024-01-20"),
+        ("10", "Food", "2024-01-21"),
+        ("7", "Food", "2024-02-01"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "meal", "--category", category])
+            .args(["--operation-date", date])
+            .assert()
+            .success();
+    }
+    cmd!(env, record create -A Cash 3 misc "--operation-date" "2024-01-05").success();
+    cmd!(env, record create -A Cash 100 refund "--direction" credit "--operation-date" "2024-01-05")
+        .success();
+
+    let stdout = cmd!(env, report "category-detail" "2024/01")
+        .success()
+        .into_stdout();
+    assert!(stdout.starts_with("Categories of 2024/01\n"));
+    assert_contains_in_order!(
+        stdout,
+        "| category | direction | records | total    | min      | max      | mean     |",
+        "| Food     | Debit     | 3       | € 34.50  | € 4.50   | € 20.00  | € 11.50  |",
+        "|          | Debit     | 1       | € 3.00   | € 3.00   | € 3.00   | € 3.00   |",
+        "|          | Credit    | 1       | € 100.00 | € 100.00 | € 100.00 | € 100.00 |"
+    );
+
+    // The records of the accounts of the group are aggregated
+    cmd!(env, account create Bank).success();
+    cmd!(env, account create Savings).success();
+    cmd!(env, record create -A Bank 5 meal "--category" Food "--operation-date" "2024-01-11")
+        .success();
+    cmd!(env, record create -A Savings 50 meal "--category" Food "--operation-date" "2024-01-12")
+        .success();
+    cmd!(env, account group create Daily).success();
+    cmd!(env, account group add Daily Cash Bank).success();
+
+    let stdout = cmd!(env, report "category-detail" "2024/01" "-G" Daily)
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(
+        stdout,
+        "| Food     | Debit     | 4       | € 39.50  | € 4.50   | € 20.00  | € 9.88   |"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn merchant() -> Result<()> {
+    let env = Env::new()?;
+    let this_month = Utc::now().date_naive().with_day(1).unwrap();
+    let two_months_ago = this_month - chrono::Months::new(2);
+    let label = |date: chrono::NaiveDate| format!("{}/{:02}", date.year(), date.month());
+
+    cmd!(env, account create Cash).success();
+    raw_cmd!(env, record create -A Cash 30 order "--create-merchant" AMZN)
+        .args(["--operation-date", &two_months_ago.to_string()])
+        .assert()
+        .success();
+    // Replaced by the merchant, which then includes its history
+    cmd!(env, merchant update AMZN "--create-replace-by" Amazon).success();
+    cmd!(env, merchant create Chariot).success();
+    for (amount, merchant, direction) in [
+        ("40", "Amazon", "debit"),
+        ("20", "Amazon", "debit"),
+        ("5", "Amazon", "credit"),
+        ("100", "Chariot", "debit"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "order", "--merchant", merchant])
+            .args(["--direction", direction])
+            .args(["--operation-date", &this_month.to_string()])
+            .assert()
+            .success();
+    }
+
+    cmd!(env, report merchant Amazon "--months" 3)
+        .success()
+        .stdout(str::contains("| month   | debit   | records | average |"))
+        .stdout(str::contains(format!(
+            "| {} | € 30.00 | 1       | € 30.00 |",
+            label(two_months_ago)
+        )))
+        .stdout(str::contains(format!(
+            "| {} | € 0.00  | 0       |         |",
+            label(this_month - chrono::Months::new(1))
+        )))
+        .stdout(str::contains(format!(
+            "| {} | € 60.00 | 2       | € 30.00 |",
+            label(this_month)
+        )))
+        .stdout(str::contains(
+            "Trend: ↑ € 60.00 against an average of € 15.00 over the 2 previous month(s)",
+        ));
+
+    cmd!(env, report merchant Amazon "--months" 1)
+        .success()
+        .stdout(str::contains("Trend").not());
+    cmd!(env, report merchant Amazon "--months" 0)
+        .failure()
+        .stderr(str::contains("0 is not in 1.."));
+    cmd!(env, report merchant Zinc).code(4);
+
+    Ok(())
+}
+
+#[test]
+fn modes() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    for (amount, mode, date) in [
+        ("12.50", "card *1234", "2024-01-10"),
+        ("7.50", "card *5678", "2024-01-12"),
+        ("40", "atm card *1234", "2024-01-15"),
+        ("20", "atm", "2024-01-20"),
+        ("500", "transfer", "2024-01-28"),
+        ("30", "direct", "2024-02-03"),
+        ("1000", "transfer", "2024-04-02"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "payment", "--mode", mode])
+            .args(["--operation-date", date, "--value-date", date])
+            .assert()
+            .success();
+    }
+    cmd!(env, record create -A Cash 2000 salary "--direction" credit "--mode" transfer "--operation-date" "2024-02-01" "--value-date" "2024-02-01")
+        .success();
+
+    let output = cmd!(env, report modes "--from" "2024-01-01" "--to" "2024-04-01")
+        .success()
+        .into_stdout();
+    assert_contains_in_order!(
+        output,
+        "| month   | direct  | atm     | transfer | total    |",
+        "| 2024/01 | € 20.00 | € 60.00 | € 500.00 | € 580.00 |",
+        "| 2024/02 | € 30.00 | € 0.00  | € 0.00   | € 30.00  |",
+        "| total   | € 50.00 | € 60.00 | € 500.00 | € 610.00 |"
+    );
+
+    cmd!(env, report modes "--from" "2024-04-01" "--to" "2024-01-01")
+        .failure()
+        .stderr(str::contains("--from must be before --to"));
+
+    Ok(())
+}
+
+#[test]
+fn year() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account create Dollars "--currency" USD).success();
+    for (amount, direction, date) in [
+        ("100", "debit", "2024-01-10"),
+        ("2000", "credit", "2024-01-25"),
+        ("150", "debit", "2024-03-03"),
+        ("50", "debit", "2024-03-14"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([amount, "payment", "--direction", direction])
+            .args(["--operation-date", date, "--value-date", date])
+            .assert()
+            .success();
+    }
+    cmd!(env, record create -A Dollars 40 souvenir "--operation-date" "2024-02-01").success();
+
+    let output = cmd!(env, report year 2024).success().into_stdout();
+    assert_contains_in_order!(
+        output,
+        "2024 in EUR",
+        "| month     | debit    | credit     | net        | cumulative |",
+        "| 2024/01   | € 100.00 | € 2,000.00 | € 1,900.00 | € 1,900.00 |",
+        "| 2024/02   | € 0.00   | € 0.00     | € 0.00     | € 1,900.00 |",
+        "| 2024/03 * | € 200.00 | € 0.00     | -€ 200.00  | € 1,700.00 |",
+        "| 2024/12   | € 0.00   | € 0.00     | € 0.00     | € 1,700.00 |",
+        "| total     | € 300.00 | € 2,000.00 | € 1,700.00 |            |",
+        "2024 in USD",
+        "| 2024/02 * | $ 40.00 | $ 0.00 | -$ 40.00 | -$ 40.00   |",
+        "* month with the highest spending"
+    );
+
+    let output = cmd!(env, report year 2024 "-A" Dollars)
+        .success()
+        .into_stdout();
+    assert!(!output.contains("EUR"));
+
+    let next_year = (Utc::now().year() + 1).to_string();
+    let output = raw_cmd!(env, report year)
+        .arg(&next_year)
+        .assert()
+        .success()
+        .into_stdout();
+    assert!(output.contains(&format!(
+        "| {next_year}/01 |        |        |        |            |"
+    )));
+    assert!(output.contains("| total   | € 0.00 | € 0.00 | € 0.00 |            |"));
+    assert!(!output.contains("highest spending"));
+
+    Ok(())
+}
+
+#[test]
+fn digest() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, account create Bank).success();
+    for merchant in ["Chariot", "Landlord", "Boulangerie"] {
+        raw_cmd!(env, merchant create)
+            .arg(merchant)
+            .assert()
+            .success();
+    }
+    cmd!(env, category create Food).success();
+    cmd!(env, category create Groceries "--parent" Food).success();
+    cmd!(env, category create Housing).success();
+    cmd!(env, category create Leisure).success();
+
+    cmd!(env, record create -A Bank 2500 salary "-d" credit "--operation-date" "2024-09-02")
+        .success();
+    for (account, amount, details, date, merchant, category) in [
+        ("Bank", "30", "drinks", "2024-08-20", "Chariot", ""),
+        ("Bank", "1200", "rent", "2024-09-03", "Landlord", "Housing"),
+        ("Cash", "3.20", "bread", "2024-09-04", "Boulangerie", "Food"),
+        ("Cash", "12", "beer", "2024-09-05", "Chariot", ""),
+        ("Cash", "9", "souvenir", "2024-09-06", "", ""),
+        ("Bank", "85.40", "groceries", "2024-09-07", "", "Groceries"),
+        ("Bank", "21", "cinema", "2024-09-08", "", "Leisure"),
+        ("Bank", "4", "coffee", "2024-09-08", "", "Food"),
+    ] {
+        let mut cmd = env.command()?;
+        cmd.args(["record", "create", "-A", account, amount, details])
+            .args(["--operation-date", date]);
+        if !merchant.is_empty() {
+            cmd.args(["--merchant", merchant]);
+        }
+        if !category.is_empty() {
+            cmd.args(["--category", category]);
+        }
+        cmd.assert().success();
+    }
+
+    // Flagged records are counted even before the period
+    cmd!(env, record flag 2 "--reason" "check with bank").success();
+
+    let due = Utc::now().date_naive() + chrono::Days::new(3);
+    raw_cmd!(env, recurring create Netflix "13.99" "-A" Bank "--start-date")
+        .arg(due.to_string())
+        .assert()
+        .success();
+    cmd!(env, recurring create Gym 40 "-A" Bank "--start-date" "2024-01-15"
+        "--end-date" "2024-06-30")
+    .success();
+
+    let output = cmd!(env, report digest "--since" "2024-09-01")
+        .success()
+        .into_stdout();
+    assert_eq!(
+        include_str!("fixtures/report/digest.txt").replace("{due}", &due.to_string()),
+        output
+    );
+
+    cmd!(env, report digest)
+        .success()
+        .stdout(str::contains("Digest since "))
+        .stdout(str::contains("Largest debits\n  none\n"))
+        .stdout(str::contains("Uncategorized records: 0"))
+        .stdout(str::contains("Flagged records: 1"));
+
+    Ok(())
+}
+
+#[test]
+fn digest_since_last() -> Result<()> {
+    let env = Env::new()?;
+    let today = Utc::now().date_naive().to_string();
+    let week_ago = (Utc::now().date_naive() - chrono::Days::new(7)).to_string();
+
+    // The default period without a marker
+    cmd!(env, report digest "--since-last" "--no-mark")
+        .success()
+        .stdout(str::contains(format!("Digest since {week_ago}")));
+    cmd!(env, config get "report/last_viewed")
+        .success()
+        .stdout(str::is_empty());
+
+    cmd!(env, config set "report/last_viewed" "2024-09-05").success();
+    cmd!(env, report digest "--since-last")
+        .success()
+        .stdout(str::contains("Digest since 2024-09-05"));
+    cmd!(env, config get "report/last_viewed")
+        .success()
+        .stdout(format!("{today}\n"));
+
+    cmd!(env, report digest "--since-last" "--since" "2024-09-01").code(2);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+#[macro_use]
+mod common;
+use common::prelude::*;
+
+#[test]
+fn rebuild() -> Result<()> {
+    let env = Env::new()?;
+
+    cmd!(env, stats rebuild "--all")
+        .success()
+        .stdout(str::contains("No records to compute the stats of"));
+
+    cmd!(env, account create Cash).success();
+    cmd!(env, category create Food).success();
+    for (amount, date) in [
+        ("20", "2024-01-10"),
+        ("4.50", "2024-01-20"),
+        ("7", "2024-02-01"),
+    ] {
+        raw_cmd!(env, record create -A Cash)
+            .args([
+                amount,
+                "meal",
+                "--category",
+                "Food",
+                "--operation-date",
+                date,
+            ])
+            .assert()
+            .success();
+    }
+
+    let live = cmd!(env, report "category-detail" "2024/01")
+        .success()
+        .into_stdout();
+    cmd!(env, stats rebuild "--all")
+        .success()
+        .stdout(str::contains("Rebuilt the stats of 2 month(s) in EUR"));
+    let precomputed = cmd!(env, report "category-detail" "2024/01")
+        .success()
+        .into_stdout();
+    assert_eq!(live, precomputed);
+    assert_contains_in_order!(
+        precomputed,
+        "| Food     | Debit     | 2       | € 24.50 | € 4.50 | € 20.00 | € 12.25 |"
+    );
+
+    // Records added later are counted, the stats of their month being
+    // forgotten
+    cmd!(env, record create -A Cash 10 meal "--category" Food "--operation-date" "2024-01-21")
+        .success();
+    cmd!(env, report "category-detail" "2024/01")
+        .success()
+        .stdout(str::contains(
+            "| Food     | Debit     | 3       | € 34.50 | € 4.50 | € 20.00 | € 11.50 |",
+        ));
+
+    cmd!(env, stats rebuild "--year" 2024)
+        .success()
+        .stdout(str::contains("Rebuilt the stats of 12 month(s) in EUR"));
+    cmd!(env, stats rebuild "--year" 2024 "--all").code(2);
+
+    Ok(())
+}